@@ -15,95 +15,89 @@
 use std::sync::Arc;
 
 use arrow_arith::arity::binary;
-use arrow_array::{Int32Array, LargeBinaryArray, RecordBatch};
+use arrow_array::{Int32Array, RecordBatch, StringArray};
 use arrow_schema::{DataType, Field, Schema};
 use arrow_udf::function;
+use arrow_udf::types::{DECIMAL_EXTENSION_NAME, EXTENSION_KEY};
+use arrow_udf_bench::Workload;
 use arrow_udf_js::Runtime as JsRuntime;
 use arrow_udf_python::Runtime as PythonRuntime;
 use arrow_udf_wasm::Runtime as WasmRuntime;
 use criterion::{criterion_group, criterion_main, Criterion};
 
-fn bench_eval_gcd(c: &mut Criterion) {
-    #[function("gcd(int, int) -> int")]
-    fn gcd(mut a: i32, mut b: i32) -> i32 {
-        while b != 0 {
-            (a, b) = (b, a % b);
-        }
-        a
-    }
-
-    let js_code = r#"
-    export function gcd(a, b) {
-        while (b) {
-            let t = b;
-            b = a % b;
-            a = t;
-        }
-        return a;
-    }
-    "#;
-
-    let python_code = r#"
-def gcd(a: int, b: int) -> int:
-    while b:
-        a, b = b, a % b
-    return a
-"#;
-
-    let input = RecordBatch::try_new(
-        Arc::new(Schema::new(vec![
-            Field::new("a", DataType::Int32, true),
-            Field::new("b", DataType::Int32, true),
-        ])),
-        vec![
-            Arc::new(Int32Array::from_iter(0..1024)),
-            Arc::new(Int32Array::from_iter((0..2048).step_by(2))),
-        ],
-    )
-    .unwrap();
+/// The WASM module built from `arrow-udf-example`.
+const WASM_MODULE: &str = "../target/wasm32-wasi/release/arrow_udf_example.wasm";
 
-    c.bench_function("gcd/native", |bencher| {
-        let a = Int32Array::from_iter(0..1024);
-        let b = Int32Array::from_iter((0..2048).step_by(2));
-        bencher.iter(|| {
-            let _: Int32Array = binary(&a, &b, gcd).unwrap();
-        })
-    });
+/// Run a workload on all runtimes.
+fn bench_workload(c: &mut Criterion, workload: &Workload) {
+    let name = workload.name;
+    let input = &workload.input;
 
-    c.bench_function("gcd/rust", |bencher| {
-        bencher.iter(|| gcd_int4_int4_int4_eval(&input).unwrap())
+    c.bench_function(&format!("{name}/rust"), |bencher| {
+        bencher.iter(|| (workload.rust)(input).unwrap())
     });
 
-    c.bench_function("gcd/wasm", |bencher| {
-        let filepath = "../target/wasm32-wasi/release/arrow_udf_example.wasm";
-        let binary = std::fs::read(filepath).unwrap();
+    c.bench_function(&format!("{name}/wasm"), |bencher| {
+        let binary = std::fs::read(WASM_MODULE).unwrap();
         let rt = WasmRuntime::new(&binary).unwrap();
-        bencher.iter(|| rt.call("gcd(int4,int4)->int4", &input).unwrap())
+        bencher.iter(|| rt.call(workload.wasm, input).unwrap())
     });
 
-    c.bench_function("gcd/js", |bencher| {
+    c.bench_function(&format!("{name}/js"), |bencher| {
         let mut rt = JsRuntime::new().unwrap();
         rt.add_function(
-            "gcd",
-            DataType::Int32,
+            name,
+            workload.return_type.clone(),
             arrow_udf_js::CallMode::ReturnNullOnNullInput,
-            js_code,
+            workload.js,
         )
         .unwrap();
-        bencher.iter(|| rt.call("gcd", &input).unwrap())
+        bencher.iter(|| rt.call(name, input).unwrap())
     });
 
-    c.bench_function("gcd/python", |bencher| {
+    c.bench_function(&format!("{name}/python"), |bencher| {
         let mut rt = PythonRuntime::new().unwrap();
         rt.add_function(
-            "gcd",
-            DataType::Int32,
+            name,
+            workload.return_type.clone(),
             arrow_udf_python::CallMode::ReturnNullOnNullInput,
-            python_code,
+            workload.python,
         )
         .unwrap();
-        bencher.iter(|| rt.call("gcd", &input).unwrap())
+        bencher.iter(|| rt.call(name, input).unwrap())
+    });
+}
+
+fn bench_eval_gcd(c: &mut Criterion) {
+    fn gcd(mut a: i32, mut b: i32) -> i32 {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+
+    // the baseline: call the function directly on arrays
+    c.bench_function("gcd/native", |bencher| {
+        let a = Int32Array::from_iter(0..1024);
+        let b = Int32Array::from_iter((0..2048).step_by(2));
+        bencher.iter(|| {
+            let _: Int32Array = binary(&a, &b, gcd).unwrap();
+        })
     });
+
+    bench_workload(c, &arrow_udf_bench::gcd());
+}
+
+fn bench_eval_upper(c: &mut Criterion) {
+    bench_workload(c, &arrow_udf_bench::upper());
+}
+
+fn bench_eval_json(c: &mut Criterion) {
+    bench_workload(c, &arrow_udf_bench::json_get_id());
+}
+
+fn bench_eval_struct(c: &mut Criterion) {
+    bench_workload(c, &arrow_udf_bench::key_value());
 }
 
 fn bench_eval_range(c: &mut Criterion) {
@@ -138,8 +132,7 @@ def range1(n: int):
     .unwrap();
 
     c.bench_function("range/wasm", |bencher| {
-        let filepath = "../target/wasm32-wasi/release/arrow_udf_example.wasm";
-        let binary = std::fs::read(filepath).unwrap();
+        let binary = std::fs::read(WASM_MODULE).unwrap();
         let rt = WasmRuntime::new(&binary).unwrap();
         bencher.iter(|| {
             rt.call_table_function("range(int4)->>int4", &input)
@@ -198,13 +191,11 @@ def decimal(a):
     return a
     "#;
 
+    let field = Field::new("a", DataType::Utf8, true)
+        .with_metadata([(EXTENSION_KEY.into(), DECIMAL_EXTENSION_NAME.into())].into());
     let input = RecordBatch::try_new(
-        Arc::new(Schema::new(vec![Field::new(
-            "a",
-            DataType::LargeBinary,
-            true,
-        )])),
-        vec![Arc::new(LargeBinaryArray::from(vec![&b"0.0"[..]; 1024]))],
+        Arc::new(Schema::new(vec![field.clone()])),
+        vec![Arc::new(StringArray::from(vec!["0.0"; 1024]))],
     )
     .unwrap();
 
@@ -216,7 +207,7 @@ def decimal(a):
         let mut rt = JsRuntime::new().unwrap();
         rt.add_function(
             "decimal",
-            DataType::LargeBinary,
+            field.clone(),
             arrow_udf_js::CallMode::ReturnNullOnNullInput,
             js_code,
         )
@@ -228,7 +219,7 @@ def decimal(a):
         let mut rt = PythonRuntime::new().unwrap();
         rt.add_function(
             "decimal",
-            DataType::LargeBinary,
+            field.clone(),
             arrow_udf_python::CallMode::ReturnNullOnNullInput,
             python_code,
         )
@@ -240,6 +231,9 @@ def decimal(a):
 criterion_group!(
     benches,
     bench_eval_gcd,
+    bench_eval_upper,
+    bench_eval_json,
+    bench_eval_struct,
     bench_eval_range,
     bench_eval_decimal
 );
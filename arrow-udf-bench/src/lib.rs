@@ -0,0 +1,216 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shared workloads for benchmarking UDF runtimes.
+//!
+//! Each [`Workload`] describes the same function implemented in Rust, Python and JavaScript,
+//! together with the signature of its counterpart in the `arrow-udf-example` WASM module,
+//! so that every runtime is measured on identical input.
+
+use std::sync::Arc;
+
+use arrow_array::{Int32Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use arrow_udf::types::{StructType, EXTENSION_KEY, JSON_EXTENSION_NAME};
+use arrow_udf::{function, ScalarFunction};
+
+/// The number of rows in each input batch.
+pub const NUM_ROWS: usize = 1024;
+
+/// A function implemented in every runtime.
+pub struct Workload {
+    /// The name of the workload, used as the prefix of benchmark ids.
+    /// It is also the function name in Python and JS code.
+    pub name: &'static str,
+    /// The input batch.
+    pub input: RecordBatch,
+    /// The return type of the function.
+    pub return_type: DataType,
+    /// The native function generated by `#[function]`.
+    pub rust: ScalarFunction,
+    /// The signature of the function in the WASM example module.
+    pub wasm: &'static str,
+    /// The Python source code.
+    pub python: &'static str,
+    /// The JavaScript source code.
+    pub js: &'static str,
+}
+
+/// Returns all workloads.
+pub fn workloads() -> Vec<Workload> {
+    vec![gcd(), upper(), json_get_id(), key_value()]
+}
+
+#[function("gcd(int, int) -> int", output = "eval_gcd")]
+fn gcd_(mut a: i32, mut b: i32) -> i32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Numeric scalar: `gcd(int, int) -> int`.
+pub fn gcd() -> Workload {
+    Workload {
+        name: "gcd",
+        input: batch(vec![
+            ("a", Arc::new(Int32Array::from_iter(0..NUM_ROWS as i32))),
+            (
+                "b",
+                Arc::new(Int32Array::from_iter((0..2 * NUM_ROWS as i32).step_by(2))),
+            ),
+        ]),
+        return_type: DataType::Int32,
+        rust: eval_gcd,
+        wasm: "gcd(int4,int4)->int4",
+        python: r#"
+def gcd(a: int, b: int) -> int:
+    while b:
+        a, b = b, a % b
+    return a
+"#,
+        js: r#"
+export function gcd(a, b) {
+    while (b) {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+    return a;
+}
+"#,
+    }
+}
+
+#[function("upper(varchar) -> varchar", output = "eval_upper")]
+fn upper_(s: &str, writer: &mut impl std::fmt::Write) {
+    for c in s.chars().flat_map(char::to_uppercase) {
+        writer.write_char(c).unwrap();
+    }
+}
+
+/// String transform: `upper(varchar) -> varchar`.
+///
+/// All runtimes use the Unicode case mapping, like Python's `str.upper` and JavaScript's
+/// `toUpperCase`.
+pub fn upper() -> Workload {
+    Workload {
+        name: "upper",
+        input: batch(vec![(
+            "s",
+            Arc::new(StringArray::from_iter_values(
+                (0..NUM_ROWS).map(|i| format!("hello, world! {i}")),
+            )),
+        )]),
+        return_type: DataType::Utf8,
+        rust: eval_upper,
+        wasm: "upper(varchar)->varchar",
+        python: r#"
+def upper(s):
+    return s.upper()
+"#,
+        js: r#"
+export function upper(s) {
+    return s.toUpperCase();
+}
+"#,
+    }
+}
+
+#[function("json_get_id(json) -> int", output = "eval_json_get_id")]
+fn json_get_id_(v: serde_json::Value) -> Option<i32> {
+    v.get("id")?.as_i64().map(|id| id as i32)
+}
+
+/// JSON parse: `json_get_id(json) -> int`.
+pub fn json_get_id() -> Workload {
+    Workload {
+        name: "json_get_id",
+        input: {
+            let field = Field::new("json", DataType::Utf8, true)
+                .with_metadata([(EXTENSION_KEY.into(), JSON_EXTENSION_NAME.into())].into());
+            let array =
+                StringArray::from_iter_values((0..NUM_ROWS).map(|i| {
+                    format!(r#"{{"id": {i}, "name": "user{i}", "tags": ["a", "b", "c"]}}"#)
+                }));
+            RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![Arc::new(array)]).unwrap()
+        },
+        return_type: DataType::Int32,
+        rust: eval_json_get_id,
+        wasm: "json_get_id(json)->int4",
+        python: r#"
+def json_get_id(v):
+    return v.get("id")
+"#,
+        js: r#"
+export function json_get_id(v) {
+    return v.id;
+}
+"#,
+    }
+}
+
+#[derive(StructType)]
+struct KeyValue<'a> {
+    key: &'a str,
+    value: &'a str,
+}
+
+#[function("key_value(varchar) -> struct KeyValue", output = "eval_key_value")]
+fn key_value_(kv: &str) -> Option<KeyValue<'_>> {
+    let (key, value) = kv.split_once('=')?;
+    Some(KeyValue { key, value })
+}
+
+/// Struct build: `key_value(varchar) -> struct KeyValue`.
+pub fn key_value() -> Workload {
+    Workload {
+        name: "key_value",
+        input: batch(vec![(
+            "kv",
+            Arc::new(StringArray::from_iter_values(
+                (0..NUM_ROWS).map(|i| format!("key{i}=value{i}")),
+            )),
+        )]),
+        return_type: DataType::Struct(KeyValue::fields()),
+        rust: eval_key_value,
+        wasm: "key_value(varchar)->struct KeyValue",
+        python: r#"
+class KeyValue:
+    def __init__(self, key, value):
+        self.key = key
+        self.value = value
+
+def key_value(s: str):
+    key, value = s.split('=', 1)
+    return KeyValue(key, value)
+"#,
+        js: r#"
+export function key_value(s) {
+    const i = s.indexOf('=');
+    return { key: s.slice(0, i), value: s.slice(i + 1) };
+}
+"#,
+    }
+}
+
+/// Build a record batch from named columns.
+fn batch(columns: Vec<(&str, arrow_array::ArrayRef)>) -> RecordBatch {
+    let fields = columns
+        .iter()
+        .map(|(name, array)| Field::new(*name, array.data_type().clone(), true))
+        .collect::<Vec<_>>();
+    let arrays = columns.into_iter().map(|(_, array)| array).collect();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).unwrap()
+}
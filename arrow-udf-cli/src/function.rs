@@ -0,0 +1,301 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Loading and calling functions of different languages.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use arrow_array::RecordBatch;
+#[cfg(any(feature = "python", feature = "js"))]
+use arrow_schema::Field;
+
+use crate::{Args, Lang};
+
+/// A loaded function.
+pub struct Function {
+    name: String,
+    kind: Kind,
+}
+
+enum Kind {
+    Rust(Dylib),
+    #[cfg(feature = "python")]
+    Python(arrow_udf_python::Runtime),
+    #[cfg(feature = "js")]
+    Js(arrow_udf_js::Runtime),
+    #[cfg(feature = "wasm")]
+    Wasm(arrow_udf_wasm::Runtime),
+}
+
+impl Function {
+    /// Loads the function from the file given in the arguments.
+    pub fn load(args: &Args) -> Result<Self> {
+        let lang = match args.lang {
+            Some(lang) => lang,
+            None => Lang::from_path(&args.file).with_context(|| {
+                format!(
+                    "unknown language of {}. specify it with --lang",
+                    args.file.display()
+                )
+            })?,
+        };
+        let name = args.function.clone();
+        let kind = match lang {
+            Lang::Rust => Kind::Rust(Dylib::open(&args.file, &name)?),
+            Lang::Python => load_python(args, &name)?,
+            Lang::Js => load_js(args, &name)?,
+            Lang::Wasm => load_wasm(args, &name)?,
+            Lang::Sql => load_sql(args, &name)?,
+        };
+        Ok(Self { name, kind })
+    }
+
+    /// Calls the function on a batch.
+    pub fn call(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        let output = match &self.kind {
+            Kind::Rust(dylib) => dylib.call(input),
+            #[cfg(feature = "python")]
+            Kind::Python(runtime) => runtime.call(&self.name, input),
+            #[cfg(feature = "js")]
+            Kind::Js(runtime) => runtime.call(&self.name, input),
+            #[cfg(feature = "wasm")]
+            Kind::Wasm(runtime) => runtime.call(&self.name, input),
+        };
+        output.with_context(|| format!("failed to call {}", self.name))
+    }
+}
+
+#[cfg(feature = "python")]
+fn load_python(args: &Args, name: &str) -> Result<Kind> {
+    let code = std::fs::read_to_string(&args.file)?;
+    python(
+        name,
+        return_type(args)?,
+        args.return_null_on_null_input,
+        &code,
+    )
+}
+
+#[cfg(not(feature = "python"))]
+fn load_python(_: &Args, _: &str) -> Result<Kind> {
+    python_unsupported()
+}
+
+#[cfg(feature = "python")]
+fn python(name: &str, return_type: Field, strict: bool, code: &str) -> Result<Kind> {
+    use arrow_udf_python::CallMode;
+    let mode = match strict {
+        true => CallMode::ReturnNullOnNullInput,
+        false => CallMode::CalledOnNullInput,
+    };
+    let mut runtime = arrow_udf_python::Runtime::new()?;
+    runtime.add_function(name, return_type, mode, code)?;
+    Ok(Kind::Python(runtime))
+}
+
+#[cfg(not(feature = "python"))]
+fn python_unsupported() -> Result<Kind> {
+    bail!("Python functions are not supported. rebuild with the `python` feature")
+}
+
+#[cfg(feature = "js")]
+fn load_js(args: &Args, name: &str) -> Result<Kind> {
+    let code = std::fs::read_to_string(&args.file)?;
+    js(
+        name,
+        return_type(args)?,
+        args.return_null_on_null_input,
+        &code,
+    )
+}
+
+#[cfg(not(feature = "js"))]
+fn load_js(_: &Args, _: &str) -> Result<Kind> {
+    js_unsupported()
+}
+
+#[cfg(feature = "js")]
+fn js(name: &str, return_type: Field, strict: bool, code: &str) -> Result<Kind> {
+    use arrow_udf_js::CallMode;
+    let mode = match strict {
+        true => CallMode::ReturnNullOnNullInput,
+        false => CallMode::CalledOnNullInput,
+    };
+    let mut runtime = arrow_udf_js::Runtime::new()?;
+    runtime.add_function(name, return_type, mode, code)?;
+    Ok(Kind::Js(runtime))
+}
+
+#[cfg(not(feature = "js"))]
+fn js_unsupported() -> Result<Kind> {
+    bail!("JavaScript functions are not supported. rebuild with the `js` feature")
+}
+
+/// Loads a function from a `CREATE FUNCTION` statement.
+///
+/// The argument types are not checked, as the input columns are passed to the function as is.
+fn load_sql(args: &Args, name: &str) -> Result<Kind> {
+    let sql = std::fs::read_to_string(&args.file)?;
+    let function = arrow_udf::sql::parse_create_function(&sql)
+        .with_context(|| format!("failed to parse {}", args.file.display()))?;
+    ensure!(
+        function.name == name,
+        "function not found: {name}. the file defines {}",
+        function.name
+    );
+    ensure!(
+        !function.is_table_function,
+        "table functions are not supported"
+    );
+    match function.language.as_str() {
+        #[cfg(feature = "python")]
+        "python" => python(
+            name,
+            function.return_type,
+            function.return_null_on_null_input,
+            &function.body,
+        ),
+        #[cfg(not(feature = "python"))]
+        "python" => python_unsupported(),
+        #[cfg(feature = "js")]
+        "javascript" | "js" => js(
+            name,
+            function.return_type,
+            function.return_null_on_null_input,
+            &function.body,
+        ),
+        #[cfg(not(feature = "js"))]
+        "javascript" | "js" => js_unsupported(),
+        language => bail!("unsupported language: {language}"),
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn load_wasm(args: &Args, name: &str) -> Result<Kind> {
+    let binary = std::fs::read(&args.file)?;
+    let runtime = arrow_udf_wasm::Runtime::new(&binary)?;
+    if !runtime.functions().any(|f| f == name) {
+        let functions = runtime.functions().collect::<Vec<_>>().join("\n  ");
+        bail!("function not found: {name}\navailable functions:\n  {functions}");
+    }
+    Ok(Kind::Wasm(runtime))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm(_: &Args, _: &str) -> Result<Kind> {
+    bail!("WebAssembly functions are not supported. rebuild with the `wasm` feature")
+}
+
+/// Returns the return type given in the arguments, named after the function.
+#[cfg(any(feature = "python", feature = "js"))]
+fn return_type(args: &Args) -> Result<Field> {
+    let name = args
+        .return_type
+        .as_deref()
+        .context("--return-type is required for Python and JavaScript functions")?;
+    arrow_udf::sql::field(&args.function, name)
+        .with_context(|| format!("unsupported return type: {name}"))
+}
+
+/// A Rust function in a dynamic library built with arrow-udf.
+struct Dylib {
+    library: libloading::Library,
+    /// The symbol of the function.
+    symbol: String,
+}
+
+/// `extern "C" fn(ptr: *const u8, len: usize, out: *mut CSlice) -> i32`
+type ScalarFn = unsafe extern "C" fn(*const u8, usize, *mut arrow_udf::ffi::CSlice) -> i32;
+/// `extern "C" fn(ptr: *mut u8, len: usize, align: usize)`
+type DeallocFn = unsafe extern "C" fn(*mut u8, usize, usize);
+
+impl Dylib {
+    /// Opens the library and finds the function by its signature.
+    fn open(path: &std::path::Path, signature: &str) -> Result<Self> {
+        // SAFETY: the library is trusted by the user
+        let library = unsafe { libloading::Library::new(path) }
+            .with_context(|| format!("failed to load {}", path.display()))?;
+        // check abi version
+        let has_version =
+            |version: &str| unsafe { library.get::<*const ()>(version.as_bytes()) }.is_ok();
+        ensure!(
+            has_version("ARROWUDF_VERSION_3_0")
+                || has_version("ARROWUDF_VERSION_2_1")
+                || has_version("ARROWUDF_VERSION_2_0")
+                || has_version("ARROWUDF_VERSION_1_0"),
+            "not a library of arrow-udf functions, or unsupported abi version"
+        );
+        let symbol = format!("arrowudf_{}", base64_encode(signature));
+        unsafe { library.get::<ScalarFn>(symbol.as_bytes()) }.with_context(|| {
+            format!("function not found: {signature}. the signature should be like `gcd(int4,int4)->int4`")
+        })?;
+        Ok(Self { library, symbol })
+    }
+
+    /// Calls the function through the FFI interface.
+    fn call(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        let (function, dealloc) = unsafe {
+            (
+                self.library.get::<ScalarFn>(self.symbol.as_bytes())?,
+                self.library.get::<DeallocFn>(b"dealloc")?,
+            )
+        };
+        let input = encode_record_batch(input)?;
+        let mut out = arrow_udf::ffi::CSlice {
+            ptr: std::ptr::null(),
+            len: 0,
+        };
+        // SAFETY: the function follows the ABI of `arrow_udf::ffi::scalar_wrapper`
+        let errno = unsafe { function(input.as_ptr(), input.len(), &mut out) };
+        let output = unsafe { std::slice::from_raw_parts(out.ptr, out.len) };
+        let result = match errno {
+            0 => decode_record_batch(output),
+            _ => Err(anyhow!("{}", String::from_utf8_lossy(output))),
+        };
+        // the output buffer is allocated by the library
+        unsafe { dealloc(out.ptr as *mut u8, out.len, 1) };
+        result
+    }
+}
+
+/// Encodes a record batch to an Arrow IPC file.
+fn encode_record_batch(batch: &RecordBatch) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut writer = arrow_ipc::writer::FileWriter::try_new(&mut buf, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(buf)
+}
+
+/// Decodes a record batch from an Arrow IPC file.
+fn decode_record_batch(bytes: &[u8]) -> Result<RecordBatch> {
+    let mut reader = arrow_ipc::reader::FileReader::try_new(std::io::Cursor::new(bytes), None)?;
+    let batch = reader.next().context("no record batch")??;
+    Ok(batch)
+}
+
+/// Encodes a symbol name using the customized base64 of arrow-udf.
+fn base64_encode(input: &str) -> String {
+    use base64::{
+        alphabet::Alphabet,
+        engine::{general_purpose::NO_PAD, GeneralPurpose},
+        Engine,
+    };
+    // standard base64 uses '+' and '/', which is not a valid symbol name.
+    // we use '$' and '_' instead.
+    let alphabet =
+        Alphabet::new("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789$_").unwrap();
+    let engine = GeneralPurpose::new(&alphabet, NO_PAD);
+    engine.encode(input)
+}
@@ -0,0 +1,168 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and writing Parquet, CSV and NDJSON files.
+
+use std::fs::File;
+use std::io::{BufReader, Seek};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use arrow_array::RecordBatch;
+use arrow_schema::ArrowError;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+
+/// The maximum number of records to read for inferring the schema of CSV and NDJSON files.
+const INFER_SCHEMA_RECORDS: usize = 1000;
+
+/// The format of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Parquet,
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// Infers the format from the extension of a file.
+    fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        Ok(match extension.as_str() {
+            "parquet" => Self::Parquet,
+            "csv" => Self::Csv,
+            "json" | "ndjson" | "jsonl" => Self::Json,
+            _ => bail!("unknown file format: {}", path.display()),
+        })
+    }
+}
+
+/// An iterator of record batches.
+pub type BatchReader = Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>>>;
+
+/// Reads record batches from a file.
+pub fn read(path: &Path, batch_size: usize) -> Result<BatchReader> {
+    let format = Format::from_path(path)?;
+    let mut file =
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    Ok(match format {
+        Format::Parquet => {
+            let reader = ParquetRecordBatchReaderBuilder::try_new(file)?
+                .with_batch_size(batch_size)
+                .build()?;
+            Box::new(reader)
+        }
+        Format::Csv => {
+            let (schema, _) = arrow_csv::reader::Format::default()
+                .with_header(true)
+                .infer_schema(&mut file, Some(INFER_SCHEMA_RECORDS))?;
+            file.rewind()?;
+            let reader = arrow_csv::ReaderBuilder::new(Arc::new(schema))
+                .with_header(true)
+                .with_batch_size(batch_size)
+                .build(file)?;
+            Box::new(reader)
+        }
+        Format::Json => {
+            let mut file = BufReader::new(file);
+            let (schema, _) = arrow_json::reader::infer_json_schema_from_seekable(
+                &mut file,
+                Some(INFER_SCHEMA_RECORDS),
+            )?;
+            let reader = arrow_json::ReaderBuilder::new(Arc::new(schema))
+                .with_batch_size(batch_size)
+                .build(file)?;
+            Box::new(reader)
+        }
+    })
+}
+
+/// Writes record batches to a file, or prints them as a table.
+pub struct Writer {
+    /// The output file and its format. `None` for printing.
+    file: Option<(File, Format)>,
+    /// The writer created on the first batch.
+    inner: Option<Inner>,
+    /// The batches to print.
+    batches: Vec<RecordBatch>,
+}
+
+enum Inner {
+    Parquet(ArrowWriter<File>),
+    Csv(arrow_csv::Writer<File>),
+    Json(arrow_json::LineDelimitedWriter<File>),
+}
+
+impl Writer {
+    /// Creates a writer to the file, or prints the batches if `path` is `None`.
+    pub fn new(path: Option<&Path>) -> Result<Self> {
+        let file = match path {
+            Some(path) => {
+                let format = Format::from_path(path)?;
+                let file = File::create(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                Some((file, format))
+            }
+            None => None,
+        };
+        Ok(Self {
+            file,
+            inner: None,
+            batches: vec![],
+        })
+    }
+
+    /// Writes a batch.
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        if self.inner.is_none() {
+            let Some((file, format)) = self.file.take() else {
+                self.batches.push(batch.clone());
+                return Ok(());
+            };
+            self.inner = Some(match format {
+                Format::Parquet => {
+                    Inner::Parquet(ArrowWriter::try_new(file, batch.schema(), None)?)
+                }
+                Format::Csv => Inner::Csv(arrow_csv::Writer::new(file)),
+                Format::Json => Inner::Json(arrow_json::LineDelimitedWriter::new(file)),
+            });
+        }
+        match self.inner.as_mut().unwrap() {
+            Inner::Parquet(writer) => writer.write(batch)?,
+            Inner::Csv(writer) => writer.write(batch)?,
+            Inner::Json(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+
+    /// Finishes writing, or prints the batches.
+    pub fn finish(self) -> Result<()> {
+        match self.inner {
+            Some(Inner::Parquet(writer)) => {
+                writer.close()?;
+            }
+            Some(Inner::Csv(_)) => {}
+            Some(Inner::Json(mut writer)) => writer.finish()?,
+            // nothing is written to the output file if there is no batch
+            None if self.file.is_some() => {}
+            None => arrow_cast::pretty::print_batches(&self.batches)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Run a user-defined function on a Parquet, CSV or NDJSON file.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use arrow_array::RecordBatch;
+use clap::{Parser, ValueEnum};
+
+use self::function::Function;
+use self::io::Writer;
+
+mod function;
+mod io;
+
+/// Run a user-defined function on a Parquet, CSV or NDJSON file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The file of the function: a Rust dynamic library, a Python or JavaScript file,
+    /// a WebAssembly module, or a SQL file with a `CREATE FUNCTION` statement.
+    #[arg(long)]
+    file: PathBuf,
+
+    /// The language of the function. Inferred from the extension of the file if not specified.
+    #[arg(long, value_enum)]
+    lang: Option<Lang>,
+
+    /// The function to call.
+    ///
+    /// For Python and JavaScript, it is the name of the function in the file.
+    /// For Rust and WebAssembly, it is the signature like `gcd(int4,int4)->int4`.
+    #[arg(long)]
+    function: String,
+
+    /// The return type of Python and JavaScript functions, like `int4` or `varchar`.
+    /// Not needed for SQL files, where it is given by the `RETURNS` clause.
+    #[arg(long)]
+    return_type: Option<String>,
+
+    /// Return null without calling Python and JavaScript functions if any argument is null.
+    #[arg(long)]
+    return_null_on_null_input: bool,
+
+    /// The input file. The format is inferred from the extension:
+    /// `.parquet`, `.csv`, `.json` or `.ndjson`.
+    #[arg(long)]
+    input: PathBuf,
+
+    /// The columns of the input passed to the function, in order. All columns by default.
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+
+    /// The output file. The format is inferred from the extension.
+    /// If not specified, the results are printed as a table.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// The number of rows in each batch passed to the function.
+    #[arg(long, default_value_t = 8192)]
+    batch_size: usize,
+}
+
+/// The language of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Lang {
+    Rust,
+    Python,
+    Js,
+    Wasm,
+    Sql,
+}
+
+impl Lang {
+    /// Infers the language from the extension of a file.
+    fn from_path(path: &std::path::Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "so" | "dylib" | "dll" => Some(Self::Rust),
+            "py" => Some(Self::Python),
+            "js" | "mjs" => Some(Self::Js),
+            "wasm" => Some(Self::Wasm),
+            "sql" => Some(Self::Sql),
+            _ => None,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let function = Function::load(&args)?;
+    let reader = io::read(&args.input, args.batch_size)?;
+    let mut writer = Writer::new(args.output.as_deref())?;
+    for (i, batch) in reader.enumerate() {
+        let batch = batch.context("failed to read input")?;
+        let batch = select_columns(&batch, &args.columns)?;
+        let output = function
+            .call(&batch)
+            .with_context(|| format!("failed to evaluate batch {i}"))?;
+        writer.write(&output)?;
+    }
+    writer.finish()
+}
+
+/// Selects the columns by name. Returns all columns if `columns` is empty.
+fn select_columns(batch: &RecordBatch, columns: &[String]) -> Result<RecordBatch> {
+    if columns.is_empty() {
+        return Ok(batch.clone());
+    }
+    let schema = batch.schema();
+    let indices = columns
+        .iter()
+        .map(|name| {
+            schema
+                .index_of(name)
+                .with_context(|| format!("column not found: {name}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(batch.project(&indices)?)
+}
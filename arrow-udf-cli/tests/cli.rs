@@ -0,0 +1,105 @@
+#![cfg(feature = "js")]
+
+use std::process::Command;
+
+#[test]
+fn test_js_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    std::fs::write(
+        path("gcd.js"),
+        r#"
+export function gcd(a, b) {
+    while (b) {
+        [a, b] = [b, a % b];
+    }
+    return a;
+}
+"#,
+    )
+    .unwrap();
+    std::fs::write(path("input.csv"), "x,a,b\nfoo,12,18\nbar,7,3\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_arrow-udf-cli"))
+        .arg("--file")
+        .arg(path("gcd.js"))
+        .args([
+            "--function",
+            "gcd",
+            "--return-type",
+            "int8",
+            "--columns",
+            "a,b",
+        ])
+        .arg("--input")
+        .arg(path("input.csv"))
+        .arg("--output")
+        .arg(path("output.csv"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = std::fs::read_to_string(path("output.csv")).unwrap();
+    assert_eq!(output, "gcd\n6\n1\n");
+}
+
+#[test]
+fn test_sql_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    std::fs::write(
+        path("gcd.sql"),
+        r#"
+CREATE FUNCTION gcd(a int, b int) RETURNS bigint LANGUAGE javascript AS $$
+export function gcd(a, b) {
+    while (b) {
+        [a, b] = [b, a % b];
+    }
+    return a;
+}
+$$;
+"#,
+    )
+    .unwrap();
+    std::fs::write(path("input.csv"), "a,b\n12,18\n7,3\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_arrow-udf-cli"))
+        .arg("--file")
+        .arg(path("gcd.sql"))
+        .args(["--function", "gcd", "--columns", "a,b"])
+        .arg("--input")
+        .arg(path("input.csv"))
+        .arg("--output")
+        .arg(path("output.csv"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = std::fs::read_to_string(path("output.csv")).unwrap();
+    assert_eq!(output, "gcd\n6\n1\n");
+}
+
+#[test]
+fn test_unknown_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    std::fs::write(path("id.js"), "export function id(x) { return x; }").unwrap();
+    std::fs::write(path("input.csv"), "a\n1\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_arrow-udf-cli"))
+        .arg("--file")
+        .arg(path("id.js"))
+        .args([
+            "--function",
+            "id",
+            "--return-type",
+            "int8",
+            "--columns",
+            "b",
+        ])
+        .arg("--input")
+        .arg(path("input.csv"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("column not found: b"), "{stderr}");
+}
@@ -58,6 +58,18 @@ fn length(s: impl AsRef<[u8]>) -> i32 {
     s.as_ref().len() as i32
 }
 
+#[function("upper(varchar) -> varchar")]
+fn upper(s: &str, writer: &mut impl std::fmt::Write) {
+    for c in s.chars().flat_map(char::to_uppercase) {
+        writer.write_char(c).unwrap();
+    }
+}
+
+#[function("json_get_id(json) -> int")]
+fn json_get_id(v: serde_json::Value) -> Option<i32> {
+    v.get("id")?.as_i64().map(|id| id as i32)
+}
+
 #[derive(StructType)]
 struct KeyValue<'a> {
     key: &'a str,
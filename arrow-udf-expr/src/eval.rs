@@ -0,0 +1,239 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Evaluation of expressions with Arrow compute kernels.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use arrow_array::{
+    cast::AsArray, new_null_array, Array, ArrayRef, BooleanArray, Float64Array, Int64Array,
+    RecordBatch, StringArray,
+};
+use arrow_cast::{cast_with_options, CastOptions};
+use arrow_schema::DataType;
+use arrow_select::filter::filter_record_batch;
+
+use crate::parser::{BinaryOp, Expr, Literal, UnaryOp};
+
+/// Evaluate the expression on the input batch. Returns an array of the same length.
+pub(crate) fn eval(expr: &Expr, input: &RecordBatch) -> Result<ArrayRef> {
+    let n = input.num_rows();
+    match expr {
+        Expr::Literal(literal) => Ok(match literal {
+            Literal::Null => new_null_array(&DataType::Null, n),
+            Literal::Bool(v) => Arc::new(BooleanArray::from(vec![*v; n])),
+            Literal::Int(v) => Arc::new(Int64Array::from_value(*v, n)),
+            Literal::Float(v) => Arc::new(Float64Array::from_value(*v, n)),
+            Literal::String(s) => Arc::new(StringArray::from(vec![s.as_str(); n])),
+        }),
+        Expr::Argument(i) => Ok(input.column(*i).clone()),
+        Expr::Unary(UnaryOp::Neg, expr) => {
+            let array = eval(expr, input)?;
+            Ok(arrow_arith::numeric::neg(&array)?)
+        }
+        Expr::Unary(UnaryOp::Not, expr) => {
+            let array = boolean(eval(expr, input)?)?;
+            Ok(Arc::new(arrow_arith::boolean::not(array.as_boolean())?))
+        }
+        Expr::IsNull { expr, negated } => {
+            let array = eval(expr, input)?;
+            Ok(Arc::new(match negated {
+                false => arrow_arith::boolean::is_null(&array)?,
+                true => arrow_arith::boolean::is_not_null(&array)?,
+            }))
+        }
+        Expr::Binary(op, left, right) => {
+            let l = eval(left, input)?;
+            let r = eval(right, input)?;
+            eval_binary(*op, (l, left.is_literal()), (r, right.is_literal()))
+        }
+        Expr::Case { branches, else_ } => {
+            // evaluate each branch only on the rows it selects, so that conditions can guard
+            // values against errors, like `CASE WHEN b = 0 THEN 0 ELSE a / b END`
+            let mut rest = input.clone();
+            let mut rest_rows = (0..n).collect::<Vec<_>>();
+            let mut values = Vec::with_capacity(branches.len() + 1);
+            let mut rows = Vec::with_capacity(branches.len() + 1);
+            for (cond, value) in branches {
+                let cond = boolean(eval(cond, &rest)?)?;
+                let mask = match cond.null_count() {
+                    0 => cond.as_boolean().clone(),
+                    _ => arrow_select::filter::prep_null_mask_filter(cond.as_boolean()),
+                };
+                values.push(eval(value, &filter_record_batch(&rest, &mask)?)?);
+                let mut matched_rows = vec![];
+                let mut unmatched_rows = vec![];
+                for (row, matched) in rest_rows.into_iter().zip(mask.values()) {
+                    match matched {
+                        true => matched_rows.push(row),
+                        false => unmatched_rows.push(row),
+                    }
+                }
+                rows.push(matched_rows);
+                rest_rows = unmatched_rows;
+                rest = filter_record_batch(&rest, &arrow_arith::boolean::not(&mask)?)?;
+            }
+            if let Some(else_) = else_ {
+                values.push(eval(else_, &rest)?);
+                rows.push(rest_rows);
+            }
+            // the result type is the type of the first non-null value
+            let data_type = values
+                .iter()
+                .map(|v| v.data_type())
+                .find(|t| **t != DataType::Null)
+                .unwrap_or(&DataType::Null)
+                .clone();
+            let mut values = values
+                .iter()
+                .map(|v| cast(v, &data_type))
+                .collect::<Result<Vec<_>>>()
+                .context("failed to evaluate CASE")?;
+            // scatter the values back to their rows. rows matching no branch are null
+            let mut indices = vec![(values.len(), 0); n];
+            for (i, branch_rows) in rows.iter().enumerate() {
+                for (j, row) in branch_rows.iter().enumerate() {
+                    indices[*row] = (i, j);
+                }
+            }
+            values.push(new_null_array(&data_type, 1));
+            let values = values.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+            Ok(arrow_select::interleave::interleave(&values, &indices)?)
+        }
+    }
+}
+
+fn eval_binary(op: BinaryOp, l: (ArrayRef, bool), r: (ArrayRef, bool)) -> Result<ArrayRef> {
+    use arrow_arith::numeric;
+    use arrow_ord::cmp;
+
+    let array: ArrayRef = match op {
+        BinaryOp::And | BinaryOp::Or => {
+            let l = boolean(l.0)?;
+            let r = boolean(r.0)?;
+            let (l, r) = (l.as_boolean(), r.as_boolean());
+            Arc::new(match op {
+                BinaryOp::And => arrow_arith::boolean::and_kleene(l, r)?,
+                _ => arrow_arith::boolean::or_kleene(l, r)?,
+            })
+        }
+        BinaryOp::Concat => {
+            // non-string operands are converted to strings
+            let data_type = match (l.0.data_type(), r.0.data_type()) {
+                (DataType::LargeUtf8, _) | (_, DataType::LargeUtf8) => DataType::LargeUtf8,
+                _ => DataType::Utf8,
+            };
+            let l = cast(&l.0, &data_type)?;
+            let r = cast(&r.0, &data_type)?;
+            arrow_string::concat_elements::concat_elements_dyn(&l, &r)?
+        }
+        _ => {
+            let (l, r) = coerce(l, r)?;
+            match op {
+                BinaryOp::Add => numeric::add(&l, &r)?,
+                BinaryOp::Sub => numeric::sub(&l, &r)?,
+                BinaryOp::Mul => numeric::mul(&l, &r)?,
+                BinaryOp::Div => numeric::div(&l, &r)?,
+                BinaryOp::Rem => numeric::rem(&l, &r)?,
+                BinaryOp::Eq => Arc::new(cmp::eq(&l, &r)?),
+                BinaryOp::NotEq => Arc::new(cmp::neq(&l, &r)?),
+                BinaryOp::Lt => Arc::new(cmp::lt(&l, &r)?),
+                BinaryOp::LtEq => Arc::new(cmp::lt_eq(&l, &r)?),
+                BinaryOp::Gt => Arc::new(cmp::gt(&l, &r)?),
+                BinaryOp::GtEq => Arc::new(cmp::gt_eq(&l, &r)?),
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Concat => unreachable!(),
+            }
+        }
+    };
+    Ok(array)
+}
+
+/// Cast the operands of a binary operation to a common type.
+///
+/// A literal operand is cast to the type of the other operand if possible, so that `a + 1`
+/// keeps the type of `a`. Otherwise numbers are cast to the wider type, and strings to
+/// `LargeUtf8` if either is. Other types are left as is for the kernel to check.
+fn coerce(l: (ArrayRef, bool), r: (ArrayRef, bool)) -> Result<(ArrayRef, ArrayRef)> {
+    let (lt, rt) = (l.0.data_type().clone(), r.0.data_type().clone());
+    let (lt, rt) = (&lt, &rt);
+    if lt == rt {
+        return Ok((l.0, r.0));
+    }
+    if *lt == DataType::Null {
+        return Ok((cast(&l.0, rt)?, r.0));
+    }
+    if *rt == DataType::Null {
+        return Ok((l.0, cast(&r.0, lt)?));
+    }
+    if lt.is_numeric() && rt.is_numeric() {
+        if l.1 && !r.1 {
+            if let Ok(array) = cast(&l.0, rt) {
+                return Ok((array, r.0));
+            }
+        }
+        if r.1 && !l.1 {
+            if let Ok(array) = cast(&r.0, lt) {
+                return Ok((l.0, array));
+            }
+        }
+        let data_type = common_numeric_type(lt, rt);
+        return Ok((cast(&l.0, &data_type)?, cast(&r.0, &data_type)?));
+    }
+    if matches!(lt, DataType::Utf8 | DataType::LargeUtf8)
+        && matches!(rt, DataType::Utf8 | DataType::LargeUtf8)
+    {
+        let data_type = DataType::LargeUtf8;
+        return Ok((cast(&l.0, &data_type)?, cast(&r.0, &data_type)?));
+    }
+    Ok((l.0, r.0))
+}
+
+/// Returns the type that both numeric types can be cast to.
+fn common_numeric_type(a: &DataType, b: &DataType) -> DataType {
+    use DataType::*;
+    let signed = |t: &DataType| matches!(t, Int8 | Int16 | Int32 | Int64);
+    let unsigned = |t: &DataType| matches!(t, UInt8 | UInt16 | UInt32 | UInt64);
+    if (signed(a) && signed(b)) || (unsigned(a) && unsigned(b)) {
+        match a.primitive_width() >= b.primitive_width() {
+            true => a.clone(),
+            false => b.clone(),
+        }
+    } else if (signed(a) || unsigned(a)) && (signed(b) || unsigned(b)) {
+        Int64
+    } else {
+        Float64
+    }
+}
+
+/// Cast the array to the given type. Values that can not be cast are errors instead of nulls.
+pub(crate) fn cast(array: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    if array.data_type() == data_type {
+        return Ok(array.clone());
+    }
+    let options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    cast_with_options(array, data_type, &options)
+        .with_context(|| format!("failed to cast {} to {data_type}", array.data_type()))
+}
+
+/// Check that the array is boolean. Nulls are cast to boolean.
+fn boolean(array: ArrayRef) -> Result<ArrayRef> {
+    match array.data_type() {
+        DataType::Boolean => Ok(array),
+        DataType::Null => Ok(new_null_array(&DataType::Boolean, array.len())),
+        t => bail!("expect boolean, got {t}"),
+    }
+}
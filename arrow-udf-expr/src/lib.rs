@@ -0,0 +1,178 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![doc = include_str!("../README.md")]
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Context, Result};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, FieldRef, Schema, SchemaRef};
+use arrow_udf::sig::FunctionRegistry;
+
+mod eval;
+mod parser;
+
+/// The expression UDF runtime.
+#[derive(Default)]
+pub struct Runtime {
+    functions: HashMap<String, Arc<Function>>,
+}
+
+impl Debug for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Runtime")
+            .field("functions", &self.functions.keys())
+            .finish()
+    }
+}
+
+/// A registered function.
+#[derive(Debug)]
+struct Function {
+    expr: parser::Expr,
+    arg_schema: SchemaRef,
+    return_field: FieldRef,
+}
+
+impl Function {
+    /// Evaluate the expression and cast the result to the return type.
+    fn eval(&self, input: &RecordBatch) -> Result<ArrayRef> {
+        let array = eval::eval(&self.expr, input)?;
+        eval::cast(&array, self.return_field.data_type())
+    }
+}
+
+/// A trait for types that can be converted into a [`Field`].
+pub trait IntoField {
+    /// Converts the type into a field with the given name.
+    fn into_field(self, name: &str) -> Field;
+}
+
+impl IntoField for DataType {
+    fn into_field(self, name: &str) -> Field {
+        Field::new(name, self, true)
+    }
+}
+
+impl IntoField for Field {
+    fn into_field(self, _name: &str) -> Field {
+        self
+    }
+}
+
+impl IntoField for FieldRef {
+    fn into_field(self, _name: &str) -> Field {
+        (*self).clone()
+    }
+}
+
+impl Runtime {
+    /// Create a new expression UDF runtime.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a function defined by an expression.
+    ///
+    /// Arguments are referenced in the expression by their names or positions like `$1`.
+    /// The expression is type checked against the argument types, and its result is cast to
+    /// the return type. The return type can be a [`DataType`], or a [`Field`] to specify an
+    /// extension type.
+    pub fn add_function(
+        &mut self,
+        name: &str,
+        args: &[(&str, DataType)],
+        return_type: impl IntoField,
+        expr: &str,
+    ) -> Result<()> {
+        let arg_names = args.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let expr = parser::parse(expr, &arg_names).context("failed to parse expression")?;
+        let arg_schema = Arc::new(Schema::new(
+            args.iter()
+                .map(|(name, ty)| Field::new(*name, ty.clone(), true))
+                .collect::<Vec<_>>(),
+        ));
+        let function = Function {
+            expr,
+            arg_schema,
+            return_field: Arc::new(return_type.into_field(name)),
+        };
+        // type check on an empty batch
+        function
+            .eval(&RecordBatch::new_empty(function.arg_schema.clone()))
+            .context("invalid expression")?;
+        self.functions.insert(name.to_string(), Arc::new(function));
+        Ok(())
+    }
+
+    /// Remove a function.
+    pub fn del_function(&mut self, name: &str) -> Result<()> {
+        self.functions.remove(name).context("function not found")?;
+        Ok(())
+    }
+
+    /// Call a function.
+    pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+        let function = self.functions.get(name).context("function not found")?;
+        let fields = function.arg_schema.fields();
+        ensure!(
+            input.num_columns() == fields.len(),
+            "expect {} arguments, got {}",
+            fields.len(),
+            input.num_columns()
+        );
+        for (i, (column, field)) in input.columns().iter().zip(fields).enumerate() {
+            if column.data_type() != field.data_type() {
+                bail!(
+                    "expect {} for the {i}-th argument, got {}",
+                    field.data_type(),
+                    column.data_type()
+                );
+            }
+        }
+        let array = function.eval(input)?;
+        let schema = Schema::new(vec![function.return_field.clone()]);
+        Ok(RecordBatch::try_new(Arc::new(schema), vec![array])?)
+    }
+
+    /// Register a function into the registry as a scalar function.
+    ///
+    /// The function can then be looked up by its signature and called like functions defined
+    /// by `#[function]`.
+    pub fn register(&self, name: &str, registry: &mut FunctionRegistry) -> Result<()> {
+        let function = self
+            .functions
+            .get(name)
+            .context("function not found")?
+            .clone();
+        let arg_types = function
+            .arg_schema
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect::<Vec<_>>();
+        let return_type = function.return_field.data_type().clone();
+        registry.register_scalar(name, &arg_types, return_type, move |input| {
+            function
+                .eval(input)
+                .map_err(|e| match e.downcast::<ArrowError>() {
+                    Ok(e) => e,
+                    Err(e) => ArrowError::ExternalError(e.into()),
+                })
+        })?;
+        Ok(())
+    }
+}
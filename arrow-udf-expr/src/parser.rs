@@ -0,0 +1,462 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Parser of expressions.
+//!
+//! The grammar follows SQL, from the lowest to the highest precedence:
+//!
+//! ```text
+//! expr    := or
+//! or      := and ( OR and )*
+//! and     := not ( AND not )*
+//! not     := NOT not | cmp
+//! cmp     := concat ( ( = | <> | != | < | <= | > | >= ) concat | IS [NOT] NULL )?
+//! concat  := add ( || add )*
+//! add     := mul ( ( + | - ) mul )*
+//! mul     := unary ( ( * | / | % ) unary )*
+//! unary   := - unary | primary
+//! primary := literal | name | $n | ( expr )
+//!          | CASE [expr] ( WHEN expr THEN expr )+ [ ELSE expr ] END
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    /// A constant value.
+    Literal(Literal),
+    /// The argument at the given index.
+    Argument(usize),
+    /// A unary operation.
+    Unary(UnaryOp, Box<Expr>),
+    /// A binary operation.
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// `expr IS [NOT] NULL`.
+    IsNull { expr: Box<Expr>, negated: bool },
+    /// `CASE WHEN cond THEN value .. [ELSE value] END`.
+    Case {
+        branches: Vec<(Expr, Expr)>,
+        else_: Option<Box<Expr>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Literal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Concat,
+}
+
+impl Expr {
+    /// Returns true if the expression is a literal, possibly negated.
+    pub fn is_literal(&self) -> bool {
+        match self {
+            Expr::Literal(_) => true,
+            Expr::Unary(UnaryOp::Neg, expr) => expr.is_literal(),
+            _ => false,
+        }
+    }
+}
+
+/// Parse an expression. Names in the expression are resolved to the index in `args`.
+pub(crate) fn parse(input: &str, args: &[&str]) -> Result<Expr> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        args,
+    };
+    let expr = parser.parse_or()?;
+    match parser.peek() {
+        Token::Eof => Ok(expr),
+        _ => Err(parser.unexpected("end of expression")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// An identifier or keyword.
+    Ident(String),
+    /// A positional argument `$n`.
+    Param(usize),
+    /// An operator or parenthesis.
+    Symbol(&'static str),
+    Eof,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Int(v) => write!(f, "{v}"),
+            Token::Float(v) => write!(f, "{v}"),
+            Token::String(s) => write!(f, "'{s}'"),
+            Token::Ident(s) => write!(f, "{s}"),
+            Token::Param(n) => write!(f, "${n}"),
+            Token::Symbol(s) => write!(f, "{s}"),
+            Token::Eof => write!(f, "end of expression"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &[
+    "||", "<>", "!=", "<=", ">=", "+", "-", "*", "/", "%", "=", "<", ">", "(", ")",
+];
+
+/// Split the input into tokens with their byte offsets.
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    let bytes = input.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let token = if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let mut float = false;
+            if i < bytes.len() && bytes[i] == b'.' {
+                float = true;
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            if i < bytes.len() && matches!(bytes[i], b'e' | b'E') {
+                float = true;
+                i += 1;
+                if i < bytes.len() && matches!(bytes[i], b'+' | b'-') {
+                    i += 1;
+                }
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let s = &input[start..i];
+            match float {
+                false => match s.parse() {
+                    Ok(v) => Token::Int(v),
+                    // too large for an integer
+                    Err(_) => Token::Float(s.parse()?),
+                },
+                true => Token::Float(
+                    s.parse()
+                        .map_err(|_| anyhow!("invalid number {s:?} at offset {start}"))?,
+                ),
+            }
+        } else if c == b'\'' {
+            // quotes are escaped by doubling them
+            let mut s = String::new();
+            i += 1;
+            loop {
+                let Some(end) = input[i..].find('\'') else {
+                    bail!("unterminated string at offset {start}");
+                };
+                s.push_str(&input[i..i + end]);
+                i += end + 1;
+                if bytes.get(i) == Some(&b'\'') {
+                    s.push('\'');
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            Token::String(s)
+        } else if c == b'"' {
+            // quoted identifier
+            let Some(end) = input[i + 1..].find('"') else {
+                bail!("unterminated identifier at offset {start}");
+            };
+            i += end + 2;
+            Token::Ident(input[start + 1..i - 1].to_string())
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            Token::Ident(input[start..i].to_string())
+        } else if c == b'$' {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            match input[start + 1..i].parse() {
+                Ok(n) => Token::Param(n),
+                Err(_) => bail!("invalid parameter at offset {start}"),
+            }
+        } else {
+            match SYMBOLS.iter().find(|s| input[i..].starts_with(*s)) {
+                Some(s) => {
+                    i += s.len();
+                    Token::Symbol(s)
+                }
+                None => bail!(
+                    "unexpected character {:?} at offset {start}",
+                    input[i..].chars().next().unwrap()
+                ),
+            }
+        };
+        tokens.push((token, start));
+    }
+    tokens.push((Token::Eof, input.len()));
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    args: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Consume the next token if it is the given keyword.
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Token::Ident(s) if s.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consume the next token if it is one of the given symbols.
+    fn consume_symbol(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Token::Symbol(s) if symbols.contains(s) => {
+                let s = *s;
+                self.pos += 1;
+                Some(s)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.consume_keyword(keyword) {
+            true => Ok(()),
+            false => Err(self.unexpected(&keyword.to_uppercase())),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> anyhow::Error {
+        let (token, offset) = &self.tokens[self.pos];
+        anyhow!("expected {expected}, found {token} at offset {offset}")
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.consume_keyword("or") {
+            let right = self.parse_and()?;
+            expr = Expr::Binary(BinaryOp::Or, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.consume_keyword("and") {
+            let right = self.parse_not()?;
+            expr = Expr::Binary(BinaryOp::And, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.consume_keyword("not") {
+            let expr = self.parse_not()?;
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(expr)));
+        }
+        self.parse_cmp()
+    }
+
+    fn parse_cmp(&mut self) -> Result<Expr> {
+        let expr = self.parse_concat()?;
+        if self.consume_keyword("is") {
+            let negated = self.consume_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(expr),
+                negated,
+            });
+        }
+        let op = match self.consume_symbol(&["=", "<>", "!=", "<", "<=", ">", ">="]) {
+            Some("=") => BinaryOp::Eq,
+            Some("<>" | "!=") => BinaryOp::NotEq,
+            Some("<") => BinaryOp::Lt,
+            Some("<=") => BinaryOp::LtEq,
+            Some(">") => BinaryOp::Gt,
+            Some(">=") => BinaryOp::GtEq,
+            _ => return Ok(expr),
+        };
+        let right = self.parse_concat()?;
+        Ok(Expr::Binary(op, Box::new(expr), Box::new(right)))
+    }
+
+    fn parse_concat(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_add()?;
+        while self.consume_symbol(&["||"]).is_some() {
+            let right = self.parse_add()?;
+            expr = Expr::Binary(BinaryOp::Concat, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_add(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_mul()?;
+        while let Some(op) = self.consume_symbol(&["+", "-"]) {
+            let op = match op {
+                "+" => BinaryOp::Add,
+                _ => BinaryOp::Sub,
+            };
+            let right = self.parse_mul()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_mul(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while let Some(op) = self.consume_symbol(&["*", "/", "%"]) {
+            let op = match op {
+                "*" => BinaryOp::Mul,
+                "/" => BinaryOp::Div,
+                _ => BinaryOp::Rem,
+            };
+            let right = self.parse_unary()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.consume_symbol(&["-"]).is_some() {
+            let expr = self.parse_unary()?;
+            return Ok(Expr::Unary(UnaryOp::Neg, Box::new(expr)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let (pos, offset) = (self.pos, self.tokens[self.pos].1);
+        let expr = match self.next() {
+            Token::Int(v) => Expr::Literal(Literal::Int(v)),
+            Token::Float(v) => Expr::Literal(Literal::Float(v)),
+            Token::String(s) => Expr::Literal(Literal::String(s)),
+            Token::Param(n) => match n {
+                1.. if n <= self.args.len() => Expr::Argument(n - 1),
+                _ => bail!(
+                    "parameter ${n} out of range at offset {offset}, expect at most {} arguments",
+                    self.args.len()
+                ),
+            },
+            Token::Symbol("(") => {
+                let expr = self.parse_or()?;
+                if self.consume_symbol(&[")"]).is_none() {
+                    return Err(self.unexpected(")"));
+                }
+                expr
+            }
+            Token::Ident(s) if s.eq_ignore_ascii_case("null") => Expr::Literal(Literal::Null),
+            Token::Ident(s) if s.eq_ignore_ascii_case("true") => Expr::Literal(Literal::Bool(true)),
+            Token::Ident(s) if s.eq_ignore_ascii_case("false") => {
+                Expr::Literal(Literal::Bool(false))
+            }
+            Token::Ident(s) if s.eq_ignore_ascii_case("case") => self.parse_case()?,
+            Token::Ident(s) => match self.args.iter().position(|arg| *arg == s) {
+                Some(i) => Expr::Argument(i),
+                None => bail!("unknown argument {s:?} at offset {offset}"),
+            },
+            _ => {
+                self.pos = pos;
+                return Err(self.unexpected("expression"));
+            }
+        };
+        Ok(expr)
+    }
+
+    /// Parse the rest of a `CASE` expression after the keyword.
+    fn parse_case(&mut self) -> Result<Expr> {
+        // `CASE x WHEN v ..` is a shorthand of `CASE WHEN x = v ..`
+        let operand = match self.peek() {
+            Token::Ident(s) if s.eq_ignore_ascii_case("when") => None,
+            _ => Some(self.parse_or()?),
+        };
+        let mut branches = vec![];
+        while self.consume_keyword("when") {
+            let mut cond = self.parse_or()?;
+            if let Some(operand) = &operand {
+                cond = Expr::Binary(BinaryOp::Eq, Box::new(operand.clone()), Box::new(cond));
+            }
+            self.expect_keyword("then")?;
+            let value = self.parse_or()?;
+            branches.push((cond, value));
+        }
+        if branches.is_empty() {
+            return Err(self.unexpected("WHEN"));
+        }
+        let else_ = match self.consume_keyword("else") {
+            true => Some(Box::new(self.parse_or()?)),
+            false => None,
+        };
+        self.expect_keyword("end")?;
+        Ok(Expr::Case { branches, else_ })
+    }
+}
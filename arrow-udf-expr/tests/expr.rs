@@ -0,0 +1,368 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::sync::Arc;
+
+use arrow_array::{BooleanArray, Int32Array, RecordBatch, StringArray};
+use arrow_cast::pretty::pretty_format_batches;
+use arrow_schema::{DataType, Field, Schema};
+use arrow_udf::sig::FunctionRegistry;
+use arrow_udf_expr::Runtime;
+
+#[test]
+fn test_arithmetic() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function(
+            "f",
+            &[("a", DataType::Int32), ("b", DataType::Int32)],
+            DataType::Int32,
+            "-a + b * 2 - $2 % 3",
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "avg2",
+            &[("a", DataType::Int32), ("b", DataType::Float64)],
+            DataType::Float64,
+            "(a + b) / 2",
+        )
+        .unwrap();
+
+    let input = int_batch(
+        vec![Some(1), None, Some(5)],
+        vec![Some(4), Some(2), Some(7)],
+    );
+    let output = runtime.call("f", &input).unwrap();
+    check(
+        &[output],
+        r#"
++---+
+| f |
++---+
+| 6 |
+|   |
+| 8 |
++---+"#,
+    );
+
+    // integers are cast to floats
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Float64, true),
+    ]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])),
+            Arc::new(arrow_array::Float64Array::from(vec![2.0, 0.5])),
+        ],
+    )
+    .unwrap();
+    let output = runtime.call("avg2", &input).unwrap();
+    check(
+        &[output],
+        r#"
++------+
+| avg2 |
++------+
+| 1.5  |
+| 1.25 |
++------+"#,
+    );
+
+    // division by zero
+    runtime
+        .add_function(
+            "div",
+            &[("a", DataType::Int32), ("b", DataType::Int32)],
+            DataType::Int32,
+            "a / b",
+        )
+        .unwrap();
+    let input = int_batch(vec![Some(1)], vec![Some(0)]);
+    let err = runtime.call("div", &input).unwrap_err();
+    assert_eq!(format!("{err:#}"), "Divide by zero error");
+}
+
+#[test]
+fn test_case() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function(
+            "grade",
+            &[("score", DataType::Int32)],
+            DataType::Utf8,
+            "CASE WHEN score >= 90 THEN 'A' WHEN score >= 60 THEN 'B' ELSE 'C' END",
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "name",
+            &[("n", DataType::Int32)],
+            DataType::Utf8,
+            "case n when 1 then 'one' when 2 then 'two' end",
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(Int32Array::from(vec![
+            Some(95),
+            Some(60),
+            Some(2),
+            None,
+        ]))],
+    )
+    .unwrap();
+    let grade = runtime.call("grade", &input).unwrap();
+    let name = runtime.call("name", &input).unwrap();
+    check(
+        &[grade],
+        r#"
++-------+
+| grade |
++-------+
+| A     |
+| B     |
+| C     |
+| C     |
++-------+"#,
+    );
+    check(
+        &[name],
+        r#"
++------+
+| name |
++------+
+|      |
+|      |
+| two  |
+|      |
++------+"#,
+    );
+
+    // values are only evaluated on the rows selected by their branch
+    runtime
+        .add_function(
+            "safe_div",
+            &[("a", DataType::Int32), ("b", DataType::Int32)],
+            DataType::Int32,
+            "CASE WHEN b = 0 THEN 0 ELSE a / b END",
+        )
+        .unwrap();
+    let input = int_batch(
+        vec![Some(6), Some(1), None, Some(7)],
+        vec![Some(2), Some(0), Some(0), None],
+    );
+    let output = runtime.call("safe_div", &input).unwrap();
+    check(
+        &[output],
+        r#"
++----------+
+| safe_div |
++----------+
+| 3        |
+| 0        |
+| 0        |
+|          |
++----------+"#,
+    );
+}
+
+#[test]
+fn test_string_and_boolean() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function(
+            "greet",
+            &[("name", DataType::Utf8), ("n", DataType::Int32)],
+            DataType::Utf8,
+            "'Hello, ' || name || ' #' || n || '!'",
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "check",
+            &[("name", DataType::Utf8), ("flag", DataType::Boolean)],
+            DataType::Boolean,
+            "name IS NULL OR NOT flag AND name <> 'it''s'",
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("name", DataType::Utf8, true),
+        Field::new("n", DataType::Int32, true),
+    ]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(StringArray::from(vec![Some("world"), None])),
+            Arc::new(Int32Array::from(vec![1, 2])),
+        ],
+    )
+    .unwrap();
+    let output = runtime.call("greet", &input).unwrap();
+    check(
+        &[output],
+        r#"
++------------------+
+| greet            |
++------------------+
+| Hello, world #1! |
+|                  |
++------------------+"#,
+    );
+
+    let schema = Schema::new(vec![
+        Field::new("name", DataType::Utf8, true),
+        Field::new("flag", DataType::Boolean, true),
+    ]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(StringArray::from(vec![
+                None,
+                Some("a"),
+                Some("it's"),
+                Some("a"),
+            ])),
+            Arc::new(BooleanArray::from(vec![
+                Some(true),
+                Some(false),
+                Some(false),
+                None,
+            ])),
+        ],
+    )
+    .unwrap();
+    let output = runtime.call("check", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-------+
+| check |
++-------+
+| true  |
+| true  |
+| false |
+|       |
++-------+"#,
+    );
+}
+
+#[test]
+fn test_errors() {
+    let mut runtime = Runtime::new();
+    let args = [("a", DataType::Int32), ("s", DataType::Utf8)];
+    let cases = [
+        (
+            "a +",
+            "expected expression, found end of expression at offset 3",
+        ),
+        ("a + b", "unknown argument \"b\" at offset 4"),
+        ("(a + 1", "expected ), found end of expression at offset 6"),
+        (
+            "a + $3",
+            "parameter $3 out of range at offset 4, expect at most 2 arguments",
+        ),
+        ("'abc", "unterminated string at offset 0"),
+        ("a ? 1", "unexpected character '?' at offset 2"),
+        (
+            "case when a = 1 end",
+            "expected THEN, found end at offset 16",
+        ),
+    ];
+    for (expr, msg) in cases {
+        let err = runtime
+            .add_function("f", &args, DataType::Int32, expr)
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), msg, "{expr}");
+    }
+
+    // type errors are reported when adding the function
+    let err = runtime
+        .add_function("f", &args, DataType::Int32, "NOT a")
+        .unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "invalid expression: expect boolean, got Int32"
+    );
+
+    runtime
+        .add_function("f", &args, DataType::Int32, "a + 1")
+        .unwrap();
+    let input = int_batch(vec![Some(1)], vec![Some(2)]);
+    let err = runtime.call("f", &input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expect Utf8 for the 1-th argument, got Int32"
+    );
+}
+
+#[test]
+fn test_register() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function(
+            "add",
+            &[("a", DataType::Int32), ("b", DataType::Int32)],
+            DataType::Int64,
+            "a + b",
+        )
+        .unwrap();
+    let mut registry = FunctionRegistry::default();
+    runtime.register("add", &mut registry).unwrap();
+
+    let sig = registry
+        .get("add", &[DataType::Int32, DataType::Int32], &DataType::Int64)
+        .unwrap();
+    let input = int_batch(vec![Some(1), Some(i32::MAX)], vec![Some(2), Some(1)]);
+    let err = sig.function.eval(&input).unwrap_err();
+    assert!(err.to_string().contains("Overflow"), "{err}");
+    let input = int_batch(vec![Some(1), None], vec![Some(2), Some(1)]);
+    let output = sig.function.eval(&input).unwrap();
+    check(
+        &[output],
+        r#"
++-----+
+| add |
++-----+
+| 3   |
+|     |
++-----+"#,
+    );
+}
+
+/// Returns a batch of two int32 columns.
+fn int_batch(a: Vec<Option<i32>>, b: Vec<Option<i32>>) -> RecordBatch {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(Int32Array::from(a)), Arc::new(Int32Array::from(b))],
+    )
+    .unwrap()
+}
+
+/// Compare the actual output with the expected output.
+#[track_caller]
+fn check(actual: &[RecordBatch], expect: &str) {
+    assert_eq!(
+        expect.trim(),
+        pretty_format_batches(actual).unwrap().to_string().trim()
+    );
+}
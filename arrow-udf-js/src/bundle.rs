@@ -0,0 +1,165 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! ES module bundles.
+
+use anyhow::{Context as _, Result};
+use rquickjs::loader::{Loader, Resolver};
+use rquickjs::module::Declared;
+use rquickjs::{Ctx, Module};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A set of ES modules that can import each other, keyed by their paths in the bundle.
+///
+/// Functions are added from a bundle by [`Runtime::add_function_from_bundle`]. Modules import
+/// each other by relative paths like `./lib/math.js`, and packages by bare names like `pad`,
+/// which are looked up in `node_modules` of the bundle. A path may omit the `.js` or `.mjs`
+/// extension, or name a directory with an `index.js`. Modules outside the bundle can not be
+/// imported.
+///
+/// # Examples
+///
+/// ```
+/// # use arrow_udf_js::Bundle;
+/// let bundle = Bundle::new()
+///     .with_module("index.js", "import { pad } from 'pad'; export function f(s) { return pad(s); }")
+///     .with_module("node_modules/pad/index.js", "export function pad(s) { return ` ${s} `; }");
+/// ```
+///
+/// [`Runtime::add_function_from_bundle`]: crate::Runtime::add_function_from_bundle
+#[derive(Debug, Clone, Default)]
+pub struct Bundle {
+    modules: HashMap<String, String>,
+}
+
+impl Bundle {
+    /// Create an empty bundle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a module at `path`, relative to the root of the bundle.
+    pub fn with_module(mut self, path: impl AsRef<str>, source: impl Into<String>) -> Self {
+        let path = normalize(path.as_ref()).unwrap_or_else(|| path.as_ref().to_string());
+        self.modules.insert(path, source.into());
+        self
+    }
+
+    /// Load all `.js` and `.mjs` files in a directory and its subdirectories.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut bundle = Self::new();
+        bundle.load_dir(dir.as_ref(), "")?;
+        Ok(bundle)
+    }
+
+    fn load_dir(&mut self, dir: &Path, prefix: &str) -> Result<()> {
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory: {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .context("invalid file name")?;
+            let name = format!("{prefix}{name}");
+            if path.is_dir() {
+                self.load_dir(&path, &format!("{name}/"))?;
+            } else if name.ends_with(".js") || name.ends_with(".mjs") {
+                let source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read file: {}", path.display()))?;
+                self.modules.insert(name, source);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the source of the module at `path`.
+    pub(crate) fn get(&self, path: &str) -> Option<&str> {
+        self.modules.get(path).map(|s| s.as_str())
+    }
+
+    /// Returns the paths and sources of all modules.
+    pub(crate) fn modules(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.modules.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// The modules of all bundles added to a runtime, keyed by `bundle{id}:{path}`.
+pub(crate) type Modules = Arc<Mutex<HashMap<String, String>>>;
+
+/// Returns the module name of a path in a bundle.
+pub(crate) fn module_name(bundle_id: usize, path: &str) -> String {
+    format!("bundle{bundle_id}:{path}")
+}
+
+/// Resolves imports within the bundle of the importing module.
+pub(crate) struct BundleResolver {
+    pub modules: Modules,
+}
+
+impl Resolver for BundleResolver {
+    fn resolve<'js>(
+        &mut self,
+        _ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+    ) -> rquickjs::Result<String> {
+        let error = || rquickjs::Error::new_resolving(base, name);
+        let (bundle, base_path) = base.split_once(':').ok_or_else(error)?;
+        let path = if name.starts_with("./") || name.starts_with("../") {
+            let dir = base_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+            normalize(&format!("{dir}/{name}"))
+        } else {
+            normalize(&format!("node_modules/{name}"))
+        }
+        .ok_or_else(error)?;
+        let modules = self.modules.lock().unwrap();
+        ["", ".js", ".mjs", "/index.js"]
+            .iter()
+            .map(|ext| format!("{bundle}:{path}{ext}"))
+            .find(|name| modules.contains_key(name))
+            .ok_or_else(error)
+    }
+}
+
+/// Loads modules from the bundles.
+pub(crate) struct BundleLoader {
+    pub modules: Modules,
+}
+
+impl Loader for BundleLoader {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> rquickjs::Result<Module<'js, Declared>> {
+        let source = (self.modules.lock().unwrap().get(name).cloned())
+            .ok_or_else(|| rquickjs::Error::new_loading(name))?;
+        Module::declare(ctx.clone(), name, source)
+    }
+}
+
+/// Normalizes a relative path, or returns `None` if it goes out of the root.
+pub(crate) fn normalize(path: &str) -> Option<String> {
+    let mut parts = vec![];
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
@@ -0,0 +1,111 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Custom extension types.
+
+use anyhow::Result;
+use arrow_array::{Array, ArrayRef};
+use arrow_schema::Field;
+use rquickjs::{Ctx, Value};
+use std::fmt::Debug;
+
+/// Converts values of an Arrow extension type to and from JS values.
+///
+/// A field is of the extension type if its `ARROW:extension:name` metadata equals [`name`].
+/// Codecs are registered with [`Runtime::add_extension_type`], and are used for arguments and
+/// return values of the type, including those nested in lists and structs.
+///
+/// A codec registered with the name `arrowudf.json` or `arrowudf.decimal` overrides the
+/// built-in conversion of that type.
+///
+/// # Examples
+///
+/// ```
+/// # use arrow_udf_js::{ExtensionTypeCodec, Runtime};
+/// # use arrow_array::{cast::AsArray, Array, ArrayRef, FixedSizeBinaryArray};
+/// # use arrow_schema::Field;
+/// # use rquickjs::{Ctx, FromJs, IntoJs, Value};
+/// # use std::sync::Arc;
+/// /// `arrow.uuid` values as hex strings.
+/// #[derive(Debug)]
+/// struct Uuid;
+///
+/// impl ExtensionTypeCodec for Uuid {
+///     fn name(&self) -> &str {
+///         "arrow.uuid"
+///     }
+///
+///     fn to_jsvalue<'a>(
+///         &self,
+///         ctx: &Ctx<'a>,
+///         _field: &Field,
+///         array: &dyn Array,
+///         i: usize,
+///     ) -> rquickjs::Result<Value<'a>> {
+///         let bytes = array.as_fixed_size_binary().value(i);
+///         let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+///         hex.into_js(ctx)
+///     }
+///
+///     fn build_array<'a>(
+///         &self,
+///         ctx: &Ctx<'a>,
+///         _field: &Field,
+///         values: Vec<Value<'a>>,
+///     ) -> anyhow::Result<ArrayRef> {
+///         let mut bytes = Vec::with_capacity(values.len());
+///         for value in values {
+///             if value.is_null() || value.is_undefined() {
+///                 bytes.push(None);
+///                 continue;
+///             }
+///             let hex = String::from_js(ctx, value)?;
+///             let value = (0..hex.len())
+///                 .step_by(2)
+///                 .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+///                 .collect::<Result<Vec<u8>, _>>()?;
+///             bytes.push(Some(value));
+///         }
+///         let array = FixedSizeBinaryArray::try_from_sparse_iter_with_size(bytes.into_iter(), 16)?;
+///         Ok(Arc::new(array))
+///     }
+/// }
+///
+/// let mut runtime = Runtime::new().unwrap();
+/// runtime.add_extension_type(Uuid);
+/// ```
+///
+/// [`name`]: ExtensionTypeCodec::name
+/// [`Runtime::add_extension_type`]: crate::Runtime::add_extension_type
+pub trait ExtensionTypeCodec: Debug + Send + Sync {
+    /// Returns the name of the extension type.
+    fn name(&self) -> &str;
+
+    /// Converts the non-null element `i` of the storage array to a JS value.
+    fn to_jsvalue<'a>(
+        &self,
+        ctx: &Ctx<'a>,
+        field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> rquickjs::Result<Value<'a>>;
+
+    /// Builds a storage array of the field from JS values, where `null` and `undefined` are null.
+    fn build_array<'a>(
+        &self,
+        ctx: &Ctx<'a>,
+        field: &Field,
+        values: Vec<Value<'a>>,
+    ) -> Result<ArrayRef>;
+}
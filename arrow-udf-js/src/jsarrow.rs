@@ -14,11 +14,16 @@
 
 //! Convert arrow array from/to python objects.
 
-use anyhow::{Context, Result};
-use arrow_array::{array::*, builder::*};
+use crate::extension::ExtensionTypeCodec;
+use anyhow::{bail, Context, Result};
+use arrow_array::types::{Decimal128Type, Int16Type, Int32Type, Int64Type, RunEndIndexType};
+use arrow_array::{array::*, builder::*, cast::AsArray};
 use arrow_buffer::OffsetBuffer;
-use arrow_schema::DataType;
+use arrow_cast::parse::parse_decimal;
+use arrow_schema::{DataType, Field};
+use half::f16;
 use rquickjs::{function::Args, Ctx, Error, FromJs, Function, IntoJs, Object, TypedArray, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 macro_rules! get_jsvalue {
@@ -35,79 +40,6 @@ macro_rules! get_typed_array {
     }};
 }
 
-/// Get array element as a JS Value.
-pub fn get_jsvalue<'a>(
-    ctx: &Ctx<'a>,
-    bigdecimal: &Function<'a>,
-    array: &dyn Array,
-    i: usize,
-) -> Result<Value<'a>, Error> {
-    if array.is_null(i) {
-        return Ok(Value::new_null(ctx.clone()));
-    }
-    match array.data_type() {
-        DataType::Null => Ok(Value::new_null(ctx.clone())),
-        DataType::Boolean => get_jsvalue!(BooleanArray, ctx, array, i),
-        DataType::Int8 => get_jsvalue!(Int8Array, ctx, array, i),
-        DataType::Int16 => get_jsvalue!(Int16Array, ctx, array, i),
-        DataType::Int32 => get_jsvalue!(Int32Array, ctx, array, i),
-        DataType::Int64 => get_jsvalue!(Int64Array, ctx, array, i),
-        DataType::UInt8 => get_jsvalue!(UInt8Array, ctx, array, i),
-        DataType::UInt16 => get_jsvalue!(UInt16Array, ctx, array, i),
-        DataType::UInt32 => get_jsvalue!(UInt32Array, ctx, array, i),
-        DataType::UInt64 => get_jsvalue!(UInt64Array, ctx, array, i),
-        DataType::Float32 => get_jsvalue!(Float32Array, ctx, array, i),
-        DataType::Float64 => get_jsvalue!(Float64Array, ctx, array, i),
-        DataType::Utf8 => get_jsvalue!(StringArray, ctx, array, i),
-        DataType::Binary => get_jsvalue!(BinaryArray, ctx, array, i),
-        // json type
-        DataType::LargeUtf8 => {
-            let array = array.as_any().downcast_ref::<LargeStringArray>().unwrap();
-            ctx.json_parse(array.value(i))
-        }
-        // decimal type
-        DataType::LargeBinary => {
-            let array = array.as_any().downcast_ref::<LargeBinaryArray>().unwrap();
-            let string = std::str::from_utf8(array.value(i))?;
-            bigdecimal.call((string,))
-        }
-        // list
-        DataType::List(inner) => {
-            let array = array.as_any().downcast_ref::<ListArray>().unwrap();
-            let list = array.value(i);
-            match inner.data_type() {
-                DataType::Int8 => get_typed_array!(Int8Array, ctx, list),
-                DataType::Int16 => get_typed_array!(Int16Array, ctx, list),
-                DataType::Int32 => get_typed_array!(Int32Array, ctx, list),
-                DataType::Int64 => get_typed_array!(Int64Array, ctx, list),
-                DataType::UInt8 => get_typed_array!(UInt8Array, ctx, list),
-                DataType::UInt16 => get_typed_array!(UInt16Array, ctx, list),
-                DataType::UInt32 => get_typed_array!(UInt32Array, ctx, list),
-                DataType::UInt64 => get_typed_array!(UInt64Array, ctx, list),
-                DataType::Float32 => get_typed_array!(Float32Array, ctx, list),
-                DataType::Float64 => get_typed_array!(Float64Array, ctx, list),
-                _ => {
-                    let mut values = Vec::with_capacity(list.len());
-                    for j in 0..list.len() {
-                        values.push(get_jsvalue(ctx, bigdecimal, list.as_ref(), j)?);
-                    }
-                    values.into_js(ctx)
-                }
-            }
-        }
-        DataType::Struct(fields) => {
-            let array = array.as_any().downcast_ref::<StructArray>().unwrap();
-            let object = Object::new(ctx.clone())?;
-            for (j, field) in fields.iter().enumerate() {
-                let value = get_jsvalue(ctx, bigdecimal, array.column(j).as_ref(), i)?;
-                object.set(field.name(), value)?;
-            }
-            Ok(object.into_value())
-        }
-        _ => todo!(),
-    }
-}
-
 macro_rules! build_array {
     (NullBuilder, $ctx:expr, $values:expr) => {{
         let mut builder = NullBuilder::with_capacity($values.len());
@@ -146,114 +78,420 @@ macro_rules! build_array {
     }};
 }
 
-/// Build arrow array from JS objects.
-pub fn build_array<'a>(
-    data_type: &DataType,
-    ctx: &Ctx<'a>,
-    values: Vec<Value<'a>>,
-) -> Result<ArrayRef> {
-    match data_type {
-        DataType::Null => build_array!(NullBuilder, ctx, values),
-        DataType::Boolean => build_array!(BooleanBuilder, ctx, values),
-        DataType::Int8 => build_array!(Int8Builder, ctx, values),
-        DataType::Int16 => build_array!(Int16Builder, ctx, values),
-        DataType::Int32 => build_array!(Int32Builder, ctx, values),
-        DataType::Int64 => build_array!(Int64Builder, ctx, values),
-        DataType::UInt8 => build_array!(UInt8Builder, ctx, values),
-        DataType::UInt16 => build_array!(UInt16Builder, ctx, values),
-        DataType::UInt32 => build_array!(UInt32Builder, ctx, values),
-        DataType::UInt64 => build_array!(UInt64Builder, ctx, values),
-        DataType::Float32 => build_array!(Float32Builder, ctx, values),
-        DataType::Float64 => build_array!(Float64Builder, ctx, values),
-        DataType::Utf8 => build_array!(StringBuilder, String, ctx, values),
-        DataType::Binary => build_array!(BinaryBuilder, Vec::<u8>, ctx, values),
-        // json type
-        DataType::LargeUtf8 => {
-            let mut builder = LargeStringBuilder::with_capacity(values.len(), 1024);
-            for val in values {
-                if val.is_null() || val.is_undefined() {
-                    builder.append_null();
-                } else if let Some(s) = ctx.json_stringify(val)? {
-                    builder.append_value(s.to_string()?);
-                } else {
-                    builder.append_null();
+/// The metadata key for the extension name of a field.
+const EXTENSION_KEY: &str = "ARROW:extension:name";
+/// The extension name of JSON type.
+const JSON_EXTENSION_NAME: &str = "arrowudf.json";
+/// The extension name of decimal type.
+const DECIMAL_EXTENSION_NAME: &str = "arrowudf.decimal";
+/// A JS function that replaces lone surrogates in a string with `U+FFFD`.
+const TO_WELL_FORMED: &str = r"(s) => s.replace(/[\uD800-\uDBFF](?![\uDC00-\uDFFF])|(?<![\uD800-\uDBFF])[\uDC00-\uDFFF]/g, '\uFFFD')";
+
+/// How to handle strings that can not be converted between JS and arrow.
+///
+/// This applies to JS strings containing lone surrogates, which can not be encoded as UTF-8,
+/// and to arrow binary values with invalid UTF-8 that are converted to strings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidStringPolicy {
+    /// Return an error for the whole batch.
+    #[default]
+    Error,
+    /// Replace each invalid character with the replacement character `U+FFFD`.
+    Replace,
+    /// Convert the value to null.
+    Null,
+}
+
+/// Converts between arrow arrays and JS values.
+#[derive(Debug, Default, Clone)]
+pub struct Converter {
+    /// Treat `LargeUtf8` fields without extension metadata as JSON.
+    pub legacy_json: bool,
+    /// Treat `LargeBinary` fields without extension metadata as decimal.
+    pub legacy_decimal: bool,
+    /// How to handle strings that can not be converted.
+    pub invalid_string: InvalidStringPolicy,
+    /// Custom extension types by name.
+    pub extension_types: HashMap<String, Arc<dyn ExtensionTypeCodec>>,
+}
+
+impl Converter {
+    /// Returns the codec if the field is of a custom extension type.
+    fn extension_type(&self, field: &Field) -> Option<&dyn ExtensionTypeCodec> {
+        let name = field.metadata().get(EXTENSION_KEY)?;
+        self.extension_types.get(name).map(|codec| codec.as_ref())
+    }
+
+    /// Returns whether the field is a JSON type.
+    fn is_json(&self, field: &Field) -> bool {
+        match field.metadata().get(EXTENSION_KEY) {
+            Some(name) => name == JSON_EXTENSION_NAME,
+            None => self.legacy_json && field.data_type() == &DataType::LargeUtf8,
+        }
+    }
+
+    /// Returns whether the field is a decimal type stored as strings.
+    fn is_decimal(&self, field: &Field) -> bool {
+        match field.metadata().get(EXTENSION_KEY) {
+            Some(name) => name == DECIMAL_EXTENSION_NAME,
+            None => self.legacy_decimal && field.data_type() == &DataType::LargeBinary,
+        }
+    }
+
+    /// Get array element as a JS Value.
+    pub fn get_jsvalue<'a>(
+        &self,
+        ctx: &Ctx<'a>,
+        bigdecimal: &Function<'a>,
+        field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> Result<Value<'a>, Error> {
+        if array.is_null(i) {
+            return Ok(Value::new_null(ctx.clone()));
+        }
+        if let Some(codec) = self.extension_type(field) {
+            return codec.to_jsvalue(ctx, field, array, i);
+        }
+        match array.data_type() {
+            DataType::Null => Ok(Value::new_null(ctx.clone())),
+            DataType::Boolean => get_jsvalue!(BooleanArray, ctx, array, i),
+            DataType::Int8 => get_jsvalue!(Int8Array, ctx, array, i),
+            DataType::Int16 => get_jsvalue!(Int16Array, ctx, array, i),
+            DataType::Int32 => get_jsvalue!(Int32Array, ctx, array, i),
+            DataType::Int64 => get_jsvalue!(Int64Array, ctx, array, i),
+            DataType::UInt8 => get_jsvalue!(UInt8Array, ctx, array, i),
+            DataType::UInt16 => get_jsvalue!(UInt16Array, ctx, array, i),
+            DataType::UInt32 => get_jsvalue!(UInt32Array, ctx, array, i),
+            DataType::UInt64 => get_jsvalue!(UInt64Array, ctx, array, i),
+            DataType::Float16 => {
+                let array = array.as_any().downcast_ref::<Float16Array>().unwrap();
+                array.value(i).to_f64().into_js(ctx)
+            }
+            DataType::Float32 => get_jsvalue!(Float32Array, ctx, array, i),
+            DataType::Float64 => get_jsvalue!(Float64Array, ctx, array, i),
+            // json type
+            DataType::Utf8 | DataType::LargeUtf8 if self.is_json(field) => {
+                let json_str = match array.data_type() {
+                    DataType::Utf8 => array.as_string::<i32>().value(i),
+                    _ => array.as_string::<i64>().value(i),
+                };
+                ctx.json_parse(json_str)
+            }
+            // decimal type
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::LargeBinary
+                if self.is_decimal(field) =>
+            {
+                let string = match array.data_type() {
+                    DataType::Utf8 => array.as_string::<i32>().value(i).into(),
+                    DataType::LargeUtf8 => array.as_string::<i64>().value(i).into(),
+                    _ => {
+                        let bytes = array.as_binary::<i64>().value(i);
+                        match self.invalid_string {
+                            InvalidStringPolicy::Error => std::str::from_utf8(bytes)?.into(),
+                            InvalidStringPolicy::Replace => String::from_utf8_lossy(bytes),
+                            InvalidStringPolicy::Null => match std::str::from_utf8(bytes) {
+                                Ok(s) => s.into(),
+                                Err(_) => return Ok(Value::new_null(ctx.clone())),
+                            },
+                        }
+                    }
+                };
+                bigdecimal.call((&*string,))
+            }
+            DataType::Decimal128(_, _) => {
+                let array = array.as_primitive::<Decimal128Type>();
+                bigdecimal.call((array.value_as_string(i),))
+            }
+            DataType::Utf8 => get_jsvalue!(StringArray, ctx, array, i),
+            DataType::LargeUtf8 => get_jsvalue!(LargeStringArray, ctx, array, i),
+            DataType::Binary => get_jsvalue!(BinaryArray, ctx, array, i),
+            DataType::LargeBinary => get_jsvalue!(LargeBinaryArray, ctx, array, i),
+            DataType::FixedSizeBinary(_) => {
+                let array = array.as_fixed_size_binary();
+                TypedArray::<u8>::new(ctx.clone(), array.value(i)).map(|a| a.into_value())
+            }
+            // list
+            DataType::List(inner) => {
+                let array = array.as_any().downcast_ref::<ListArray>().unwrap();
+                let list = array.value(i);
+                match inner.data_type() {
+                    DataType::Int8 => get_typed_array!(Int8Array, ctx, list),
+                    DataType::Int16 => get_typed_array!(Int16Array, ctx, list),
+                    DataType::Int32 => get_typed_array!(Int32Array, ctx, list),
+                    DataType::Int64 => get_typed_array!(Int64Array, ctx, list),
+                    DataType::UInt8 => get_typed_array!(UInt8Array, ctx, list),
+                    DataType::UInt16 => get_typed_array!(UInt16Array, ctx, list),
+                    DataType::UInt32 => get_typed_array!(UInt32Array, ctx, list),
+                    DataType::UInt64 => get_typed_array!(UInt64Array, ctx, list),
+                    DataType::Float32 => get_typed_array!(Float32Array, ctx, list),
+                    DataType::Float64 => get_typed_array!(Float64Array, ctx, list),
+                    _ => {
+                        let mut values = Vec::with_capacity(list.len());
+                        for j in 0..list.len() {
+                            values.push(self.get_jsvalue(
+                                ctx,
+                                bigdecimal,
+                                inner,
+                                list.as_ref(),
+                                j,
+                            )?);
+                        }
+                        values.into_js(ctx)
+                    }
                 }
             }
-            Ok(Arc::new(builder.finish()))
-        }
-        // decimal type
-        DataType::LargeBinary => {
-            let mut builder = LargeBinaryBuilder::with_capacity(values.len(), 1024);
-            let bigdecimal_to_string: Function = ctx
-                .eval("BigDecimal.prototype.toString")
-                .context("failed to get BigDecimal.prototype.string")?;
-            for val in values {
-                if val.is_null() || val.is_undefined() {
-                    builder.append_null();
-                } else {
-                    let mut args = Args::new(ctx.clone(), 0);
-                    args.this(val)?;
-                    let string: String = bigdecimal_to_string.call_arg(args).context(
-                        "failed to convert BigDecimal to string. make sure you return a BigDecimal value",
-                    )?;
-                    builder.append_value(string);
+            DataType::Struct(fields) => {
+                let array = array.as_any().downcast_ref::<StructArray>().unwrap();
+                let object = Object::new(ctx.clone())?;
+                for (j, field) in fields.iter().enumerate() {
+                    let value =
+                        self.get_jsvalue(ctx, bigdecimal, field, array.column(j).as_ref(), i)?;
+                    object.set(field.name(), value)?;
                 }
+                Ok(object.into_value())
+            }
+            DataType::RunEndEncoded(run_ends, values) => {
+                let (array, index) = match run_ends.data_type() {
+                    DataType::Int16 => run_value::<Int16Type>(array, i),
+                    DataType::Int32 => run_value::<Int32Type>(array, i),
+                    _ => run_value::<Int64Type>(array, i),
+                };
+                self.get_jsvalue(ctx, bigdecimal, values, array.as_ref(), index)
             }
-            Ok(Arc::new(builder.finish()))
+            _ => todo!(),
+        }
+    }
+
+    /// Build arrow array from JS objects.
+    pub fn build_array<'a>(
+        &self,
+        field: &Field,
+        ctx: &Ctx<'a>,
+        values: Vec<Value<'a>>,
+    ) -> Result<ArrayRef> {
+        if let Some(codec) = self.extension_type(field) {
+            return codec.build_array(ctx, field, values);
         }
-        // list
-        DataType::List(inner) => {
-            // flatten lists
-            let mut flatten_values = vec![];
-            let mut offsets = Vec::<i32>::with_capacity(values.len() + 1);
-            offsets.push(0);
-            for val in &values {
-                if !val.is_null() && !val.is_undefined() {
-                    let array = val.as_array().context("failed to convert to array")?;
-                    flatten_values.reserve(array.len());
-                    for elem in array.iter() {
-                        flatten_values.push(elem?);
+        match field.data_type() {
+            DataType::Null => build_array!(NullBuilder, ctx, values),
+            DataType::Boolean => build_array!(BooleanBuilder, ctx, values),
+            DataType::Int8 => build_array!(Int8Builder, ctx, values),
+            DataType::Int16 => build_array!(Int16Builder, ctx, values),
+            DataType::Int32 => build_array!(Int32Builder, ctx, values),
+            DataType::Int64 => build_array!(Int64Builder, ctx, values),
+            DataType::UInt8 => build_array!(UInt8Builder, ctx, values),
+            DataType::UInt16 => build_array!(UInt16Builder, ctx, values),
+            DataType::UInt32 => build_array!(UInt32Builder, ctx, values),
+            DataType::UInt64 => build_array!(UInt64Builder, ctx, values),
+            DataType::Float16 => {
+                let mut builder = Float16Builder::with_capacity(values.len());
+                for val in values {
+                    if val.is_null() || val.is_undefined() {
+                        builder.append_null();
+                    } else {
+                        builder.append_value(f16::from_f64(FromJs::from_js(ctx, val)?));
                     }
                 }
-                offsets.push(flatten_values.len() as i32);
+                Ok(Arc::new(builder.finish()))
             }
-            let values_array = build_array(inner.data_type(), ctx, flatten_values)?;
-            let nulls = values
-                .iter()
-                .map(|v| !v.is_null() && !v.is_undefined())
-                .collect();
-            Ok(Arc::new(ListArray::new(
-                inner.clone(),
-                OffsetBuffer::new(offsets.into()),
-                values_array,
-                Some(nulls),
-            )))
-        }
-        DataType::Struct(fields) => {
-            let mut arrays = Vec::with_capacity(fields.len());
-            for field in fields {
-                let mut field_values = Vec::with_capacity(values.len());
-                for val in &values {
-                    let v = if val.is_null() || val.is_undefined() {
-                        Value::new_null(ctx.clone())
+            DataType::Float32 => build_array!(Float32Builder, ctx, values),
+            DataType::Float64 => build_array!(Float64Builder, ctx, values),
+            // json type
+            DataType::Utf8 | DataType::LargeUtf8 if self.is_json(field) => {
+                let mut strings = Vec::with_capacity(values.len());
+                for val in values {
+                    if val.is_null() || val.is_undefined() {
+                        strings.push(None);
+                    } else if let Some(s) = ctx.json_stringify(val)? {
+                        strings.push(Some(s.to_string()?));
+                    } else {
+                        strings.push(None);
+                    }
+                }
+                Ok(match field.data_type() {
+                    DataType::Utf8 => Arc::new(StringArray::from(strings)),
+                    _ => Arc::new(LargeStringArray::from(strings)),
+                })
+            }
+            // decimal type
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::LargeBinary
+                if self.is_decimal(field) =>
+            {
+                let bigdecimal_to_string: Function = ctx
+                    .eval("BigDecimal.prototype.toString")
+                    .context("failed to get BigDecimal.prototype.toString")?;
+                let mut strings = Vec::with_capacity(values.len());
+                for val in values {
+                    if val.is_null() || val.is_undefined() {
+                        strings.push(None);
+                    } else {
+                        let mut args = Args::new(ctx.clone(), 0);
+                        args.this(val)?;
+                        let string: String = bigdecimal_to_string.call_arg(args).context(
+                            "failed to convert BigDecimal to string. make sure you return a BigDecimal value",
+                        )?;
+                        strings.push(Some(string));
+                    }
+                }
+                Ok(match field.data_type() {
+                    DataType::Utf8 => Arc::new(StringArray::from(strings)),
+                    DataType::LargeUtf8 => Arc::new(LargeStringArray::from(strings)),
+                    _ => Arc::new(LargeBinaryArray::from_iter(strings)),
+                })
+            }
+            DataType::Decimal128(precision, scale) => {
+                let bigdecimal_to_fixed: Function = ctx
+                    .eval("BigDecimal.prototype.toFixed")
+                    .context("failed to get BigDecimal.prototype.toFixed")?;
+                let mut builder = Decimal128Builder::with_capacity(values.len());
+                for val in values {
+                    if val.is_null() || val.is_undefined() {
+                        builder.append_null();
                     } else {
-                        let object = val.as_object().context("expect object")?;
-                        object.get(field.name())?
+                        // round to the scale in fixed-point notation
+                        let mut args = Args::new(ctx.clone(), 1);
+                        args.this(val)?;
+                        args.push_arg((*scale).max(0) as i32)?;
+                        let string: String = bigdecimal_to_fixed.call_arg(args).context(
+                            "failed to convert BigDecimal to string. make sure you return a BigDecimal value",
+                        )?;
+                        builder.append_value(parse_decimal::<Decimal128Type>(
+                            &string, *precision, *scale,
+                        )?);
+                    }
+                }
+                Ok(Arc::new(
+                    builder
+                        .finish()
+                        .with_precision_and_scale(*precision, *scale)?,
+                ))
+            }
+            DataType::Utf8 => self.build_string_array::<i32>(ctx, values),
+            DataType::LargeUtf8 => self.build_string_array::<i64>(ctx, values),
+            DataType::Binary => build_array!(BinaryBuilder, Vec::<u8>, ctx, values),
+            DataType::LargeBinary => build_array!(LargeBinaryBuilder, Vec::<u8>, ctx, values),
+            DataType::FixedSizeBinary(size) => {
+                let mut builder = FixedSizeBinaryBuilder::with_capacity(values.len(), *size);
+                for val in values {
+                    if val.is_null() || val.is_undefined() {
+                        builder.append_null();
+                        continue;
+                    }
+                    // accept both `Uint8Array` and arrays of numbers
+                    let bytes = match TypedArray::<u8>::from_js(ctx, val.clone()) {
+                        Ok(array) => array.as_ref().to_vec(),
+                        Err(_) => Vec::<u8>::from_js(ctx, val)?,
                     };
-                    field_values.push(v);
+                    if bytes.len() != *size as usize {
+                        bail!("expect {size} bytes, got {}", bytes.len());
+                    }
+                    builder.append_value(&bytes)?;
                 }
-                arrays.push(build_array(field.data_type(), ctx, field_values)?);
+                Ok(Arc::new(builder.finish()))
             }
-            let nulls = values
-                .iter()
-                .map(|v| !v.is_null() && !v.is_undefined())
-                .collect();
-            Ok(Arc::new(StructArray::new(
-                fields.clone(),
-                arrays,
-                Some(nulls),
-            )))
+            // list
+            DataType::List(inner) => {
+                // flatten lists
+                let mut flatten_values = vec![];
+                let mut offsets = Vec::<i32>::with_capacity(values.len() + 1);
+                offsets.push(0);
+                for val in &values {
+                    if !val.is_null() && !val.is_undefined() {
+                        let array = val.as_array().context("failed to convert to array")?;
+                        flatten_values.reserve(array.len());
+                        for elem in array.iter() {
+                            flatten_values.push(elem?);
+                        }
+                    }
+                    offsets.push(flatten_values.len() as i32);
+                }
+                let values_array = self.build_array(inner, ctx, flatten_values)?;
+                let nulls = values
+                    .iter()
+                    .map(|v| !v.is_null() && !v.is_undefined())
+                    .collect();
+                Ok(Arc::new(ListArray::new(
+                    inner.clone(),
+                    OffsetBuffer::new(offsets.into()),
+                    values_array,
+                    Some(nulls),
+                )))
+            }
+            DataType::Struct(fields) => {
+                let mut arrays = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let mut field_values = Vec::with_capacity(values.len());
+                    for val in &values {
+                        let v = if val.is_null() || val.is_undefined() {
+                            Value::new_null(ctx.clone())
+                        } else {
+                            let object = val.as_object().context("expect object")?;
+                            object.get(field.name())?
+                        };
+                        field_values.push(v);
+                    }
+                    arrays.push(self.build_array(field, ctx, field_values)?);
+                }
+                let nulls = values
+                    .iter()
+                    .map(|v| !v.is_null() && !v.is_undefined())
+                    .collect();
+                Ok(Arc::new(StructArray::new(
+                    fields.clone(),
+                    arrays,
+                    Some(nulls),
+                )))
+            }
+            _ => todo!(),
         }
-        _ => todo!(),
     }
+
+    /// Build a string array from JS strings, applying the policy for invalid strings.
+    fn build_string_array<'a, O: OffsetSizeTrait>(
+        &self,
+        ctx: &Ctx<'a>,
+        values: Vec<Value<'a>>,
+    ) -> Result<ArrayRef> {
+        let mut builder = GenericStringBuilder::<O>::with_capacity(values.len(), 1024);
+        let mut to_well_formed: Option<Function> = None;
+        for val in values {
+            if val.is_null() || val.is_undefined() {
+                builder.append_null();
+                continue;
+            }
+            let err = match String::from_js(ctx, val.clone()) {
+                Ok(s) => {
+                    builder.append_value(s);
+                    continue;
+                }
+                Err(err) => err,
+            };
+            // strings with lone surrogates can not be encoded as UTF-8
+            if !val.is_string() {
+                return Err(err.into());
+            }
+            match self.invalid_string {
+                InvalidStringPolicy::Error => return Err(err.into()),
+                InvalidStringPolicy::Replace => {
+                    if to_well_formed.is_none() {
+                        let function = ctx
+                            .eval(TO_WELL_FORMED)
+                            .context("failed to compile string replacement function")?;
+                        to_well_formed = Some(function);
+                    }
+                    let string: String = to_well_formed.as_ref().unwrap().call((val,))?;
+                    builder.append_value(string);
+                }
+                InvalidStringPolicy::Null => builder.append_null(),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
+
+/// Returns the values and the physical index of the `i`-th element of a run-end encoded array.
+fn run_value<R: RunEndIndexType>(array: &dyn Array, i: usize) -> (&ArrayRef, usize) {
+    let array = array.as_any().downcast_ref::<RunArray<R>>().unwrap();
+    (array.values(), array.get_physical_index(i))
 }
@@ -18,23 +18,44 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use anyhow::{anyhow, Context as _, Result};
-use arrow_array::{builder::Int32Builder, Array, RecordBatch};
-use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use anyhow::{anyhow, bail, ensure, Context as _, Result};
+use arrow_array::{builder::Int32Builder, make_array, Array, ArrayRef, BooleanArray, RecordBatch};
+use arrow_schema::{DataType, Field, FieldRef, Schema, SchemaRef};
 use rquickjs::{
     context::intrinsic::{BaseObjects, BigDecimal, Eval, Json, TypedArrays},
     function::Args,
     Context, Ctx, Object, Persistent, Value,
 };
 
+mod bundle;
+mod env;
+mod extension;
 mod jsarrow;
 
+pub use self::bundle::Bundle;
+use self::bundle::{BundleLoader, BundleResolver, Modules};
+pub use self::env::Env;
+pub use self::extension::ExtensionTypeCodec;
+use self::jsarrow::Converter;
+pub use self::jsarrow::InvalidStringPolicy;
+pub use rquickjs;
+
 /// The JS UDF runtime.
 pub struct Runtime {
     functions: HashMap<String, Function>,
+    aggregates: HashMap<String, Aggregate>,
     /// The `BigDecimal` constructor.
     bigdecimal: Persistent<rquickjs::Function<'static>>,
-    // NOTE: `functions` and `bigdecimal` must be put before the runtime and context to be dropped first.
+    /// A function that gets the iterator of the value returned by a table function.
+    get_iterator: Persistent<rquickjs::Function<'static>>,
+    converter: Converter,
+    /// The target number of rows of each call to user code.
+    batch_size: Option<usize>,
+    /// The modules of bundles, shared with the module loader.
+    modules: Modules,
+    /// The number of bundles added.
+    num_bundles: usize,
+    // NOTE: `functions`, `aggregates`, `bigdecimal` and `get_iterator` must be put before the runtime and context to be dropped first.
     _runtime: rquickjs::Runtime,
     context: Context,
 }
@@ -43,6 +64,7 @@ impl Debug for Runtime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Runtime")
             .field("functions", &self.functions.keys())
+            .field("aggregates", &self.aggregates.keys())
             .finish()
     }
 }
@@ -50,14 +72,55 @@ impl Debug for Runtime {
 /// A registered function.
 struct Function {
     function: Persistent<rquickjs::Function<'static>>,
-    return_type: DataType,
+    return_field: FieldRef,
     mode: CallMode,
+    /// Variables and secrets exposed in `this.env`.
+    env: Env,
 }
 
 // XXX: to make `Runtime` Send and Sync. not sure if this is safe.
 unsafe impl Send for Function {}
 unsafe impl Sync for Function {}
 
+/// A registered aggregate function.
+struct Aggregate {
+    state_field: FieldRef,
+    output_field: FieldRef,
+    mode: CallMode,
+    create_state: Persistent<rquickjs::Function<'static>>,
+    accumulate: Persistent<rquickjs::Function<'static>>,
+    retract: Option<Persistent<rquickjs::Function<'static>>>,
+    merge: Option<Persistent<rquickjs::Function<'static>>>,
+    finish: Option<Persistent<rquickjs::Function<'static>>>,
+}
+
+unsafe impl Send for Aggregate {}
+unsafe impl Sync for Aggregate {}
+
+/// A trait for types that can be converted into a [`Field`].
+pub trait IntoField {
+    /// Converts the type into a field with the given name.
+    fn into_field(self, name: &str) -> Field;
+}
+
+impl IntoField for DataType {
+    fn into_field(self, name: &str) -> Field {
+        Field::new(name, self, true)
+    }
+}
+
+impl IntoField for Field {
+    fn into_field(self, _name: &str) -> Field {
+        self
+    }
+}
+
+impl IntoField for FieldRef {
+    fn into_field(self, _name: &str) -> Field {
+        (*self).clone()
+    }
+}
+
 /// Whether the function will be called when some of its arguments are null.
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CallMode {
@@ -82,23 +145,102 @@ impl Runtime {
                 &runtime,
             )
             .context("failed to create quickjs context")?;
-        let bigdecimal = context.with(|ctx| {
+        let modules = Modules::default();
+        runtime.set_loader(
+            BundleResolver {
+                modules: modules.clone(),
+            },
+            BundleLoader {
+                modules: modules.clone(),
+            },
+        );
+        let (bigdecimal, get_iterator) = context.with(|ctx| {
             let bigdecimal: rquickjs::Function = ctx.eval("BigDecimal")?;
-            Ok(Persistent::save(&ctx, bigdecimal)) as Result<_>
+            let get_iterator: rquickjs::Function = ctx.eval(GET_ITERATOR)?;
+            Ok((
+                Persistent::save(&ctx, bigdecimal),
+                Persistent::save(&ctx, get_iterator),
+            )) as Result<_>
         })?;
         Ok(Self {
             functions: HashMap::new(),
+            aggregates: HashMap::new(),
             bigdecimal,
+            get_iterator,
+            converter: Converter::default(),
+            batch_size: None,
+            modules,
+            num_bundles: 0,
             _runtime: runtime,
             context,
         })
     }
 
+    /// Set whether to treat `LargeUtf8` as JSON type.
+    ///
+    /// JSON values are identified by the extension type `arrowudf.json` in the field metadata.
+    /// Enable this option to also treat `LargeUtf8` fields without extension metadata as JSON,
+    /// which is the convention used by previous versions.
+    ///
+    /// The default is `false`.
+    pub fn set_legacy_json(&mut self, legacy_json: bool) {
+        self.converter.legacy_json = legacy_json;
+    }
+
+    /// Set whether to treat `LargeBinary` as decimal type.
+    ///
+    /// Decimal values are read from `Decimal128` arrays, or from strings with the extension type
+    /// `arrowudf.decimal` in the field metadata.
+    /// Enable this option to also treat `LargeBinary` fields without extension metadata as decimal
+    /// strings, which is the convention used by previous versions.
+    ///
+    /// The default is `false`.
+    pub fn set_legacy_decimal(&mut self, legacy_decimal: bool) {
+        self.converter.legacy_decimal = legacy_decimal;
+    }
+
+    /// Set how to handle strings that can not be converted, such as JS strings with lone
+    /// surrogates returned by functions.
+    ///
+    /// The default is [`InvalidStringPolicy::Error`].
+    pub fn set_invalid_string_policy(&mut self, policy: InvalidStringPolicy) {
+        self.converter.invalid_string = policy;
+    }
+
+    /// Register a codec for a custom extension type.
+    ///
+    /// Arguments and return values of the extension type are converted by the codec instead
+    /// of by their storage type. See [`ExtensionTypeCodec`] for an example.
+    pub fn add_extension_type(&mut self, codec: impl ExtensionTypeCodec + 'static) {
+        self.converter
+            .extension_types
+            .insert(codec.name().to_string(), Arc::new(codec));
+    }
+
+    /// Set the target number of rows of each call to user code.
+    ///
+    /// Larger inputs are sliced into batches of this size, so that the values of a slice are
+    /// released before converting the next one. Small inputs passed to [`Runtime::call_batches`]
+    /// are coalesced up to this size to reduce the overhead per call. The outputs are stitched
+    /// back to the shape of the inputs.
+    ///
+    /// By default, inputs are evaluated as they are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is 0.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = Some(batch_size);
+    }
+
     /// Add a JS function.
+    ///
+    /// The return type can be a [`DataType`], or a [`Field`] to specify an extension type.
     pub fn add_function(
         &mut self,
         name: &str,
-        return_type: DataType,
+        return_type: impl IntoField,
         mode: CallMode,
         code: &str,
     ) -> Result<()> {
@@ -115,26 +257,421 @@ impl Runtime {
         })?;
         let function = Function {
             function,
-            return_type,
+            return_field: return_type.into_field(name).into(),
+            mode,
+            env: Env::default(),
+        };
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    /// Add a JS function from a bundle of ES modules.
+    ///
+    /// The function named `name` is exported by the module at `entry`, which can import other
+    /// modules of the bundle. See [`Bundle`] for how imports are resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arrow_udf_js::{Bundle, Runtime, CallMode};
+    /// # use arrow_schema::DataType;
+    /// let bundle = Bundle::new()
+    ///     .with_module("index.js", "import { gcd } from './math.js'; export { gcd };")
+    ///     .with_module(
+    ///         "math.js",
+    ///         "export function gcd(a, b) { while (b) { [a, b] = [b, a % b]; } return a; }",
+    ///     );
+    /// let mut runtime = Runtime::new().unwrap();
+    /// runtime
+    ///     .add_function_from_bundle(
+    ///         "gcd",
+    ///         DataType::Int32,
+    ///         CallMode::ReturnNullOnNullInput,
+    ///         &bundle,
+    ///         "index.js",
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn add_function_from_bundle(
+        &mut self,
+        name: &str,
+        return_type: impl IntoField,
+        mode: CallMode,
+        bundle: &Bundle,
+        entry: &str,
+    ) -> Result<()> {
+        // normalize the entry like the paths of modules, e.g. `./index.js` to `index.js`
+        let entry = bundle::normalize(entry).unwrap_or_else(|| entry.to_string());
+        let code = bundle
+            .get(&entry)
+            .with_context(|| format!("module not found in bundle: {entry}"))?;
+        // modules of each bundle are named by a unique prefix, so that bundles with modules of
+        // the same path do not conflict in the module cache of the context.
+        let bundle_id = self.num_bundles;
+        self.num_bundles += 1;
+        self.modules.lock().unwrap().extend(
+            bundle
+                .modules()
+                .map(|(path, source)| (bundle::module_name(bundle_id, path), source.to_string())),
+        );
+        let function = self.context.with(|ctx| {
+            let module = ctx
+                .clone()
+                .compile(bundle::module_name(bundle_id, &entry), code)
+                .map_err(|e| check_exception(e, &ctx))
+                .context("failed to compile module")?;
+            let function: rquickjs::Function = module
+                .get(name)
+                .context("failed to get function. HINT: make sure the function is exported")?;
+            Ok(Persistent::save(&ctx, function)) as Result<_>
+        })?;
+        let function = Function {
+            function,
+            return_field: return_type.into_field(name).into(),
             mode,
+            env: Env::default(),
         };
         self.functions.insert(name.to_string(), function);
         Ok(())
     }
 
+    /// Set the variables and secrets of a function, exposed as the `this.env` object.
+    ///
+    /// Values of secrets are redacted in the errors of the function. Note that arrow functions
+    /// do not have their own `this`, so the function must be declared with `function`.
+    ///
+    /// ```js
+    /// export function fetch(x) {
+    ///     return callApi(this.env.ENDPOINT, this.env.API_KEY, x);
+    /// }
+    /// ```
+    pub fn set_env(&mut self, name: &str, env: Env) -> Result<()> {
+        let function = self.functions.get_mut(name).context("function not found")?;
+        function.env = env;
+        Ok(())
+    }
+
+    /// Add a JS aggregate function.
+    ///
+    /// The module must export the following functions:
+    ///
+    /// - `create_state()`: returns the initial state.
+    /// - `accumulate(state, ...args)`: returns the state after adding a row.
+    /// - `retract(state, ...args)`: (optional) returns the state after removing a row,
+    ///   which is required by [`Runtime::accumulate_or_retract`], e.g. for sliding windows.
+    /// - `merge(state1, state2)`: (optional) returns the state merged from two states.
+    /// - `finish(state)`: (optional) returns the output of a state.
+    ///   If not defined, the state is the output and `output_type` must be the same as `state_type`.
+    ///
+    /// States are passed between calls as arrow arrays of `state_type`, so they can be
+    /// checkpointed or sent to other nodes by the engine. `accumulate` and `retract` may modify
+    /// the state in place and return `undefined`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arrow_udf_js::{Runtime, CallMode};
+    /// # use arrow_schema::DataType;
+    /// let mut runtime = Runtime::new().unwrap();
+    /// runtime
+    ///     .add_aggregate(
+    ///         "sum",
+    ///         DataType::Int32,
+    ///         DataType::Int32,
+    ///         CallMode::ReturnNullOnNullInput,
+    ///         r#"
+    ///         export function create_state() { return 0; }
+    ///         export function accumulate(state, value) { return state + value; }
+    ///         export function retract(state, value) { return state - value; }
+    ///         export function merge(state1, state2) { return state1 + state2; }
+    ///         "#,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn add_aggregate(
+        &mut self,
+        name: &str,
+        state_type: impl IntoField,
+        output_type: impl IntoField,
+        mode: CallMode,
+        code: &str,
+    ) -> Result<()> {
+        let state_field: FieldRef = state_type.into_field(name).into();
+        let output_field: FieldRef = output_type.into_field(name).into();
+        let aggregate = self.context.with(|ctx| {
+            let module = ctx
+                .clone()
+                .compile("main", code)
+                .map_err(|e| check_exception(e, &ctx))
+                .context("failed to compile module")?;
+            let required = |name: &str| -> Result<_> {
+                let function: rquickjs::Function = module.get(name).with_context(|| {
+                    format!(
+                        "failed to get function '{name}'. HINT: make sure the function is exported"
+                    )
+                })?;
+                Ok(Persistent::save(&ctx, function))
+            };
+            let optional = |name: &str| -> Result<_> {
+                let function: Option<rquickjs::Function> = module.get(name)?;
+                Ok(function.map(|f| Persistent::save(&ctx, f)))
+            };
+            Ok(Aggregate {
+                state_field,
+                output_field,
+                mode,
+                create_state: required("create_state")?,
+                accumulate: required("accumulate")?,
+                retract: optional("retract")?,
+                merge: optional("merge")?,
+                finish: optional("finish")?,
+            }) as Result<_>
+        })?;
+        if aggregate.finish.is_none()
+            && aggregate.state_field.data_type() != aggregate.output_field.data_type()
+        {
+            bail!("`output_type` must be the same as `state_type` if `finish` is not defined");
+        }
+        self.aggregates.insert(name.to_string(), aggregate);
+        Ok(())
+    }
+
+    /// Create the initial state of an aggregate function, as an array of length 1.
+    pub fn create_state(&self, name: &str) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        self.context.with(|ctx| {
+            let state = self.js_create_state(&ctx, aggregate)?;
+            self.converter
+                .build_array(&aggregate.state_field, &ctx, vec![state])
+                .context("failed to build arrow array from state")
+        })
+    }
+
+    /// Accumulate the rows of `input` into `state`, and return the new state.
+    ///
+    /// `state` is an array of length 1 returned by [`Runtime::create_state`] or other functions.
+    pub fn accumulate(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.accumulate_impl(name, state, None, input)
+    }
+
+    /// Accumulate or retract the rows of `input` into `state`, and return the new state.
+    ///
+    /// Rows whose value in `ops` is true are retracted, and others are accumulated.
+    pub fn accumulate_or_retract(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        ops: &BooleanArray,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.accumulate_impl(name, state, Some(ops), input)
+    }
+
+    fn accumulate_impl(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        ops: Option<&BooleanArray>,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        self.context.with(|ctx| {
+            let bigdecimal = self.bigdecimal.clone().restore(&ctx)?;
+            let accumulate = aggregate.accumulate.clone().restore(&ctx)?;
+            let retract = match &aggregate.retract {
+                Some(f) => Some(f.clone().restore(&ctx)?),
+                None => None,
+            };
+            let mut state = self
+                .converter
+                .get_jsvalue(&ctx, &bigdecimal, &aggregate.state_field, state, 0)
+                .context("failed to get jsvalue from state")?;
+            let mut row = Vec::with_capacity(input.num_columns());
+            let schema = input.schema();
+            for i in 0..input.num_rows() {
+                row.clear();
+                for (column, field) in input.columns().iter().zip(schema.fields()) {
+                    let val = self
+                        .converter
+                        .get_jsvalue(&ctx, &bigdecimal, field, column, i)
+                        .context("failed to get jsvalue from arrow array")?;
+                    row.push(val);
+                }
+                if aggregate.mode == CallMode::ReturnNullOnNullInput
+                    && row.iter().any(|v| v.is_null())
+                {
+                    continue;
+                }
+                let function = match ops {
+                    Some(ops) if ops.is_valid(i) && ops.value(i) => retract
+                        .as_ref()
+                        .context("function does not support retraction")?,
+                    _ => &accumulate,
+                };
+                let mut args = Args::new(ctx.clone(), row.len() + 1);
+                args.push_args(std::iter::once(state.clone()).chain(row.drain(..)))?;
+                let new_state: Value = function
+                    .call_arg(args)
+                    .map_err(|e| check_exception(e, &ctx))
+                    .context("failed to call accumulate or retract")?;
+                // the state may be modified in place
+                if !new_state.is_undefined() {
+                    state = new_state;
+                }
+            }
+            self.converter
+                .build_array(&aggregate.state_field, &ctx, vec![state])
+                .context("failed to build arrow array from state")
+        })
+    }
+
+    /// Merge the states into one, and return it as an array of length 1.
+    ///
+    /// Returns the initial state if `states` is empty.
+    pub fn merge(&self, name: &str, states: &dyn Array) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        let merge = aggregate
+            .merge
+            .as_ref()
+            .context("function does not support merging")?;
+        self.context.with(|ctx| {
+            let bigdecimal = self.bigdecimal.clone().restore(&ctx)?;
+            let merge = merge.clone().restore(&ctx)?;
+            let mut merged: Option<Value> = None;
+            for i in 0..states.len() {
+                let state = self
+                    .converter
+                    .get_jsvalue(&ctx, &bigdecimal, &aggregate.state_field, states, i)
+                    .context("failed to get jsvalue from state")?;
+                merged = Some(match merged {
+                    None => state,
+                    Some(merged) => merge
+                        .call((merged, state))
+                        .map_err(|e| check_exception(e, &ctx))
+                        .context("failed to call merge")?,
+                });
+            }
+            let state = match merged {
+                Some(state) => state,
+                None => self.js_create_state(&ctx, aggregate)?,
+            };
+            self.converter
+                .build_array(&aggregate.state_field, &ctx, vec![state])
+                .context("failed to build arrow array from state")
+        })
+    }
+
+    /// Get the output of each state.
+    pub fn finish(&self, name: &str, states: &dyn Array) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        let Some(finish) = &aggregate.finish else {
+            return Ok(make_array(states.to_data()));
+        };
+        self.context.with(|ctx| {
+            let bigdecimal = self.bigdecimal.clone().restore(&ctx)?;
+            let finish = finish.clone().restore(&ctx)?;
+            let mut results = Vec::with_capacity(states.len());
+            for i in 0..states.len() {
+                let state = self
+                    .converter
+                    .get_jsvalue(&ctx, &bigdecimal, &aggregate.state_field, states, i)
+                    .context("failed to get jsvalue from state")?;
+                let result = finish
+                    .call((state,))
+                    .map_err(|e| check_exception(e, &ctx))
+                    .context("failed to call finish")?;
+                results.push(result);
+            }
+            self.converter
+                .build_array(&aggregate.output_field, &ctx, results)
+                .context("failed to build arrow array from return values")
+        })
+    }
+
+    /// Call `create_state` of the aggregate function.
+    fn js_create_state<'js>(&self, ctx: &Ctx<'js>, aggregate: &Aggregate) -> Result<Value<'js>> {
+        let create_state = aggregate.create_state.clone().restore(ctx)?;
+        create_state
+            .call(())
+            .map_err(|e| check_exception(e, ctx))
+            .context("failed to call create_state")
+    }
+
     /// Call the JS UDF.
     pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+        let num_rows = input.num_rows();
+        let size = match self.batch_size {
+            Some(size) if num_rows > size => size,
+            _ => return self.call_batch(name, input),
+        };
+        let mut outputs = Vec::with_capacity(num_rows.div_ceil(size));
+        for offset in (0..num_rows).step_by(size) {
+            let slice = input.slice(offset, size.min(num_rows - offset));
+            outputs.push(self.call_batch(name, &slice)?);
+        }
+        let schema = outputs[0].schema();
+        Ok(arrow_select::concat::concat_batches(&schema, &outputs)?)
+    }
+
+    /// Call the JS UDF on multiple batches, returning one output for each input.
+    ///
+    /// If [`Runtime::set_batch_size`] is set, consecutive small batches are coalesced up to the
+    /// batch size before calling user code, and large batches are sliced.
+    pub fn call_batches(&self, name: &str, inputs: &[RecordBatch]) -> Result<Vec<RecordBatch>> {
+        let mut outputs = Vec::with_capacity(inputs.len());
+        let mut rest = inputs;
+        while !rest.is_empty() {
+            // take as many batches as fit in the batch size, but at least one
+            let mut len = 1;
+            let mut num_rows = rest[0].num_rows();
+            while let Some(next) = rest.get(len) {
+                match self.batch_size {
+                    Some(size) if num_rows + next.num_rows() <= size => {}
+                    _ => break,
+                }
+                num_rows += next.num_rows();
+                len += 1;
+            }
+            let (group, remaining) = rest.split_at(len);
+            rest = remaining;
+            if group.len() == 1 {
+                outputs.push(self.call(name, &group[0])?);
+                continue;
+            }
+            let input = arrow_select::concat::concat_batches(&group[0].schema(), group)?;
+            let output = self.call(name, &input)?;
+            // split the output back into the shape of the inputs
+            let mut offset = 0;
+            for input in group {
+                outputs.push(output.slice(offset, input.num_rows()));
+                offset += input.num_rows();
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Call the JS UDF on a batch as a whole.
+    fn call_batch(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
         let function = self.functions.get(name).context("function not found")?;
         // convert each row to python objects and call the function
         self.context.with(|ctx| {
             let bigdecimal = self.bigdecimal.clone().restore(&ctx)?;
             let js_function = function.function.clone().restore(&ctx)?;
+            let this = function.env.this_object(&ctx)?;
             let mut results = Vec::with_capacity(input.num_rows());
             let mut row = Vec::with_capacity(input.num_columns());
+            let schema = input.schema();
             for i in 0..input.num_rows() {
                 row.clear();
-                for column in input.columns() {
-                    let val = jsarrow::get_jsvalue(&ctx, &bigdecimal, column, i)
+                for (column, field) in input.columns().iter().zip(schema.fields()) {
+                    let val = self
+                        .converter
+                        .get_jsvalue(&ctx, &bigdecimal, field, column, i)
                         .context("failed to get jsvalue from arrow array")?;
                     row.push(val);
                 }
@@ -145,21 +682,34 @@ impl Runtime {
                     continue;
                 }
                 let mut args = Args::new(ctx.clone(), row.len());
+                args.this(this.clone())?;
                 args.push_args(row.drain(..))?;
                 let result = js_function
                     .call_arg(args)
-                    .map_err(|e| check_exception(e, &ctx))
+                    .map_err(|e| function.env.redact(check_exception(e, &ctx)))
                     .context("failed to call function")?;
                 results.push(result);
             }
-            let array = jsarrow::build_array(&function.return_type, &ctx, results)
+            let array = self
+                .converter
+                .build_array(&function.return_field, &ctx, results)
                 .context("failed to build arrow array from return values")?;
-            let schema = Schema::new(vec![Field::new(name, array.data_type().clone(), true)]);
+            ensure!(
+                array.len() == input.num_rows(),
+                "expect {} rows in the output of {name}, got {}",
+                input.num_rows(),
+                array.len()
+            );
+            let schema = Schema::new(vec![function.return_field.clone()]);
             Ok(RecordBatch::try_new(Arc::new(schema), vec![array])?)
         })
     }
 
     /// Call a table function.
+    ///
+    /// The function can be a generator function, or return any iterable such as an array,
+    /// or an iterator with a `next` method. The values are yielded in chunks of `chunk_size`
+    /// rows, with the index of the input row in the `row` column.
     pub fn call_table_function<'a>(
         &'a self,
         name: &'a str,
@@ -173,11 +723,13 @@ impl Runtime {
         Ok(RecordBatchIter {
             context: &self.context,
             bigdecimal: &self.bigdecimal,
+            get_iterator: &self.get_iterator,
+            converter: &self.converter,
             input,
             function,
             schema: Arc::new(Schema::new(vec![
-                Field::new("row", DataType::Int32, true),
-                Field::new(name, function.return_type.clone(), true),
+                Arc::new(Field::new("row", DataType::Int32, true)),
+                function.return_field.clone(),
             ])),
             chunk_size,
             row: 0,
@@ -190,6 +742,8 @@ impl Runtime {
 pub struct RecordBatchIter<'a> {
     context: &'a Context,
     bigdecimal: &'a Persistent<rquickjs::Function<'static>>,
+    get_iterator: &'a Persistent<rquickjs::Function<'static>>,
+    converter: &'a Converter,
     input: &'a RecordBatch,
     function: &'a Function,
     schema: SchemaRef,
@@ -217,8 +771,12 @@ impl RecordBatchIter<'_> {
         self.context.with(|ctx| {
             let bigdecimal = self.bigdecimal.clone().restore(&ctx)?;
             let js_function = self.function.function.clone().restore(&ctx)?;
+            let get_iterator = self.get_iterator.clone().restore(&ctx)?;
+            let function = self.function;
+            let env = &function.env;
+            let this = env.this_object(&ctx)?;
             let mut indexes = Int32Builder::with_capacity(self.chunk_size);
-            let mut results = Vec::with_capacity(self.input.num_rows());
+            let mut results = Vec::with_capacity(self.chunk_size);
             let mut row = Vec::with_capacity(self.input.num_columns());
             // restore generator from state
             let mut generator = match self.generator.take() {
@@ -236,8 +794,11 @@ impl RecordBatchIter<'_> {
                 } else {
                     // call the table function to get a generator
                     row.clear();
-                    for column in self.input.columns() {
-                        let val = jsarrow::get_jsvalue(&ctx, &bigdecimal, column, self.row)
+                    let schema = self.input.schema();
+                    for (column, field) in self.input.columns().iter().zip(schema.fields()) {
+                        let val = self
+                            .converter
+                            .get_jsvalue(&ctx, &bigdecimal, field, column, self.row)
                             .context("failed to get jsvalue from arrow array")?;
                         row.push(val);
                     }
@@ -248,22 +809,25 @@ impl RecordBatchIter<'_> {
                         continue;
                     }
                     let mut args = Args::new(ctx.clone(), row.len());
+                    args.this(this.clone())?;
                     args.push_args(row.drain(..))?;
-                    let gen = js_function
-                        .call_arg::<Object>(args)
-                        .map_err(|e| check_exception(e, &ctx))
+                    let value = js_function
+                        .call_arg::<Value>(args)
+                        .map_err(|e| env.redact(check_exception(e, &ctx)))
                         .context("failed to call function")?;
+                    let gen: Object = get_iterator
+                        .call((value,))
+                        .map_err(|e| check_exception(e, &ctx))
+                        .context("failed to get iterator from return value")?;
                     let next: rquickjs::Function =
                         gen.get("next").context("failed to get 'next' method")?;
-                    let mut args = Args::new(ctx.clone(), 0);
-                    args.this(gen.clone())?;
                     generator.insert((gen, next))
                 };
                 let mut args = Args::new(ctx.clone(), 0);
                 args.this(gen.clone())?;
                 let object: Object = next
                     .call_arg(args)
-                    .map_err(|e| check_exception(e, &ctx))
+                    .map_err(|e| env.redact(check_exception(e, &ctx)))
                     .context("failed to call next")?;
                 let value: Value = object.get("value")?;
                 let done: bool = object.get("done")?;
@@ -281,7 +845,9 @@ impl RecordBatchIter<'_> {
                 return Ok(None);
             }
             let indexes = Arc::new(indexes.finish());
-            let array = jsarrow::build_array(&self.function.return_type, &ctx, results)
+            let array = self
+                .converter
+                .build_array(&self.function.return_field, &ctx, results)
                 .context("failed to build arrow array from return values")?;
             Ok(Some(RecordBatch::try_new(
                 self.schema.clone(),
@@ -298,6 +864,22 @@ impl Iterator for RecordBatchIter<'_> {
     }
 }
 
+/// Gets the iterator of the value returned by a table function.
+///
+/// Generators and other iterators are returned as is, and iterables like arrays are
+/// converted by `Symbol.iterator`.
+const GET_ITERATOR: &str = r#"
+(value) => {
+    if (typeof value?.next === "function") {
+        return value;
+    }
+    if (typeof value?.[Symbol.iterator] === "function") {
+        return value[Symbol.iterator]();
+    }
+    throw new TypeError("table function must return an iterable or iterator");
+}
+"#;
+
 /// Get exception from `ctx` if the error is an exception.
 fn check_exception(err: rquickjs::Error, ctx: &Ctx) -> anyhow::Error {
     match err {
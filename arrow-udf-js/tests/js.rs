@@ -15,12 +15,16 @@
 use std::sync::Arc;
 
 use arrow_array::{
-    types::*, ArrayRef, BinaryArray, Int32Array, LargeBinaryArray, LargeStringArray, ListArray,
-    RecordBatch, StringArray, StructArray,
+    cast::AsArray, types::*, Array, ArrayRef, BinaryArray, BooleanArray, Decimal128Array,
+    FixedSizeBinaryArray, Float64Array, Int32Array, LargeBinaryArray, LargeStringArray, ListArray,
+    RecordBatch, RunArray, StringArray, StructArray,
 };
 use arrow_cast::pretty::pretty_format_batches;
 use arrow_schema::{DataType, Field, Schema};
-use arrow_udf_js::{CallMode, Runtime};
+use arrow_udf::testing::{assert_roundtrip_batch, random_field_array};
+use arrow_udf_js::rquickjs::{Ctx, FromJs, IntoJs, Value};
+use arrow_udf_js::{Bundle, CallMode, Env, ExtensionTypeCodec, InvalidStringPolicy, Runtime};
+use proptest::prelude::*;
 
 #[test]
 fn test_gcd() {
@@ -71,6 +75,53 @@ fn test_gcd() {
     );
 }
 
+#[test]
+fn test_batch_size() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime.set_batch_size(2);
+    runtime
+        .add_function(
+            "square",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            "export function square(x) { return x * x; }",
+        )
+        .unwrap();
+
+    let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, true)]));
+    let batch = |values: Vec<Option<i32>>| {
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))]).unwrap()
+    };
+
+    // large batches are sliced
+    let input = batch(vec![Some(1), None, Some(3), Some(4), Some(5)]);
+    let output = runtime.call("square", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(1), None, Some(9), Some(16), Some(25)])
+    );
+
+    // small batches are coalesced
+    let inputs = [
+        batch(vec![Some(1)]),
+        batch(vec![Some(2)]),
+        batch(vec![Some(3), Some(4), Some(5)]),
+    ];
+    let outputs = runtime.call_batches("square", &inputs).unwrap();
+    let outputs: Vec<_> = outputs
+        .iter()
+        .map(|o| o.column(0).as_primitive::<Int32Type>().clone())
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            Int32Array::from(vec![1]),
+            Int32Array::from(vec![4]),
+            Int32Array::from(vec![9, 16, 25]),
+        ]
+    );
+}
+
 #[test]
 fn test_to_string() {
     let mut runtime = Runtime::new().unwrap();
@@ -155,10 +206,377 @@ fn test_concat() {
     );
 }
 
+#[test]
+fn test_fixed_size_binary() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "reverse",
+            DataType::FixedSizeBinary(4),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function reverse(b) {
+                return b.reverse();
+            }
+            "#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "truncate",
+            DataType::FixedSizeBinary(4),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function truncate(b) {
+                return b.slice(0, 2);
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("b", DataType::FixedSizeBinary(4), true)]);
+    let arg0 = FixedSizeBinaryArray::from(vec![Some(&[1, 2, 3, 4][..]), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("reverse", &input).unwrap();
+    let expected = FixedSizeBinaryArray::from(vec![Some(&[4, 3, 2, 1][..]), None]);
+    assert_eq!(output.column(0).as_fixed_size_binary(), &expected);
+
+    let error = runtime.call("truncate", &input).unwrap_err();
+    assert_eq!(error.root_cause().to_string(), "expect 4 bytes, got 2");
+}
+
+/// `arrow.uuid` values as hex strings.
+#[derive(Debug)]
+struct UuidCodec;
+
+impl ExtensionTypeCodec for UuidCodec {
+    fn name(&self) -> &str {
+        "arrow.uuid"
+    }
+
+    fn to_jsvalue<'a>(
+        &self,
+        ctx: &Ctx<'a>,
+        _field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> arrow_udf_js::rquickjs::Result<Value<'a>> {
+        let bytes = array.as_fixed_size_binary().value(i);
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        hex.into_js(ctx)
+    }
+
+    fn build_array<'a>(
+        &self,
+        ctx: &Ctx<'a>,
+        _field: &Field,
+        values: Vec<Value<'a>>,
+    ) -> anyhow::Result<ArrayRef> {
+        let mut bytes = Vec::with_capacity(values.len());
+        for value in values {
+            if value.is_null() || value.is_undefined() {
+                bytes.push(None);
+                continue;
+            }
+            let hex = String::from_js(ctx, value)?;
+            let value = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()?;
+            bytes.push(Some(value));
+        }
+        let array = FixedSizeBinaryArray::try_from_sparse_iter_with_size(bytes.into_iter(), 16)?;
+        Ok(Arc::new(array))
+    }
+}
+
+#[test]
+fn test_bundle() {
+    let dir = std::env::temp_dir().join(format!("arrow-udf-js-bundle-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::create_dir_all(dir.join("node_modules/pad")).unwrap();
+    std::fs::write(
+        dir.join("index.js"),
+        r#"
+        import { shout } from './lib/words';
+        import { pad } from 'pad';
+        export function greet(name) { return pad(shout(name)); }
+        "#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("lib/words.js"),
+        "export function shout(s) { return s.toUpperCase() + '!'; }",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("node_modules/pad/index.js"),
+        "export function pad(s) { return `[${s}]`; }",
+    )
+    .unwrap();
+    let bundle = Bundle::from_dir(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function_from_bundle(
+            "greet",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            &bundle,
+            "index.js",
+        )
+        .unwrap();
+    // modules of the same path in another bundle do not conflict
+    let other = Bundle::new()
+        .with_module(
+            "index.js",
+            "export { shout as loud } from './lib/words.js';",
+        )
+        .with_module(
+            "lib/words.js",
+            "export function shout(s) { return s + '!!!'; }",
+        );
+    runtime
+        .add_function_from_bundle(
+            "loud",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            &other,
+            // the entry is normalized like the paths of modules
+            "./index.js",
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("name", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("alice"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let output = runtime.call("greet", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &StringArray::from(vec![Some("[ALICE!]"), None])
+    );
+    let output = runtime.call("loud", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &StringArray::from(vec![Some("alice!!!"), None])
+    );
+
+    // modules out of the bundle can not be imported
+    let escape =
+        Bundle::new().with_module("index.js", "import { f } from '../index.js'; export { f };");
+    let error = runtime
+        .add_function_from_bundle(
+            "f",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            &escape,
+            "index.js",
+        )
+        .unwrap_err();
+    assert_eq!(error.to_string(), "failed to compile module");
+
+    let error = runtime
+        .add_function_from_bundle(
+            "f",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            &escape,
+            "main.js",
+        )
+        .unwrap_err();
+    assert_eq!(error.to_string(), "module not found in bundle: main.js");
+}
+
+#[test]
+fn test_env() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "sign",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function sign(x) {
+                if (x === 'fail') {
+                    throw new Error(`bad request with key ${this.env.API_KEY}`);
+                }
+                return `${this.env.ENDPOINT}/${x}`;
+            }
+            "#,
+        )
+        .unwrap();
+    let env = Env::new()
+        .with_var("ENDPOINT", "https://api.example.com")
+        .with_secret("API_KEY", "s3cr3t");
+    runtime.set_env("sign", env).unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(arg0)]).unwrap();
+    let output = runtime.call("sign", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &StringArray::from(vec![Some("https://api.example.com/a"), None])
+    );
+
+    // secrets are redacted in errors
+    let arg0 = StringArray::from(vec!["fail"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let error = runtime.call("sign", &input).unwrap_err();
+    let message = format!("{error:#}");
+    assert!(message.contains("bad request with key ***"), "{message}");
+    assert!(!message.contains("s3cr3t"), "{message}");
+
+    assert!(runtime.set_env("unknown", Env::new()).is_err());
+}
+
+#[test]
+fn test_extension_type() {
+    let uuid_field = |name: &str| {
+        Field::new(name, DataType::FixedSizeBinary(16), true)
+            .with_metadata([("ARROW:extension:name".into(), "arrow.uuid".into())].into())
+    };
+    let mut runtime = Runtime::new().unwrap();
+    runtime.add_extension_type(UuidCodec);
+    runtime
+        .add_function(
+            "set_last",
+            uuid_field("set_last"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function set_last(u) {
+                return u.slice(0, 30) + 'ff';
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![uuid_field("u")]);
+    let arg0 = FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+        [Some([0u8; 16]), None].into_iter(),
+        16,
+    )
+    .unwrap();
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("set_last", &input).unwrap();
+    let mut expected = [0u8; 16];
+    expected[15] = 0xff;
+    let array = output.column(0).as_fixed_size_binary();
+    assert_eq!(array.value(0), expected);
+    assert!(array.is_null(1));
+}
+
+#[test]
+fn test_invalid_string() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "lone_surrogate",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function lone_surrogate(x) {
+                return x + "\udc80b";
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    runtime.call("lone_surrogate", &input).unwrap_err();
+
+    runtime.set_invalid_string_policy(InvalidStringPolicy::Replace);
+    let output = runtime.call("lone_surrogate", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_string::<i32>(),
+        &StringArray::from(vec![Some("a\u{FFFD}b"), None])
+    );
+
+    runtime.set_invalid_string_policy(InvalidStringPolicy::Null);
+    let output = runtime.call("lone_surrogate", &input).unwrap();
+    assert_eq!(output.column(0).null_count(), 2);
+}
+
+#[test]
+fn test_run_end_encoded() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "double",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function double(x) {
+                return x * 2;
+            }
+            "#,
+        )
+        .unwrap();
+
+    let run_ends = Int32Array::from(vec![2, 3, 5]);
+    let values = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let arg0 = RunArray::try_new(&run_ends, &values).unwrap();
+    let schema = Schema::new(vec![Field::new("x", arg0.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("double", &input).unwrap();
+    let expected = Int32Array::from(vec![Some(2), Some(2), None, Some(6), Some(6)]);
+    assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
+}
+
 #[test]
 fn test_json_array_access() {
     let mut runtime = Runtime::new().unwrap();
 
+    runtime
+        .add_function(
+            "json_array_access",
+            json_field("json_array_access"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function json_array_access(array, i) {
+                return array[i];
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        json_field("array"),
+        Field::new("i", DataType::Int32, true),
+    ]);
+    let arg0 = StringArray::from(vec![r#"[1, null, ""]"#]);
+    let arg1 = Int32Array::from(vec![0]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("json_array_access", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------------+
+| json_array_access |
++-------------------+
+| 1                 |
++-------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_legacy_json() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime.set_legacy_json(true);
+
     runtime
         .add_function(
             "json_array_access",
@@ -214,8 +632,8 @@ fn test_json_stringify() {
         )
         .unwrap();
 
-    let schema = Schema::new(vec![Field::new("json", DataType::LargeUtf8, true)]);
-    let arg0 = LargeStringArray::from(vec![r#"[1, null, ""]"#]);
+    let schema = Schema::new(vec![json_field("json")]);
+    let arg0 = StringArray::from(vec![r#"[1, null, ""]"#]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
     let output = runtime.call("json_stringify", &input).unwrap();
@@ -238,6 +656,94 @@ fn test_json_stringify() {
 fn test_decimal_add() {
     let mut runtime = Runtime::new().unwrap();
 
+    runtime
+        .add_function(
+            "decimal_add",
+            decimal_field("decimal_add"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function decimal_add(a, b) {
+                return a + b;
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![decimal_field("a"), decimal_field("b")]);
+    let arg0 = StringArray::from(vec!["0.0001"]);
+    let arg1 = StringArray::from(vec!["0.0002"]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("decimal_add", &input).unwrap();
+    assert_eq!(output.schema().field(0), &decimal_field("decimal_add"));
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------+
+| decimal_add |
++-------------+
+| 0.0003      |
++-------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_decimal128_add() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "decimal_add",
+            DataType::Decimal128(10, 2),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function decimal_add(a, b) {
+                return a + b;
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Decimal128(10, 4), true),
+        Field::new("b", DataType::Decimal128(10, 4), true),
+    ]);
+    let arg0 = Decimal128Array::from(vec![12345, -1])
+        .with_precision_and_scale(10, 4)
+        .unwrap();
+    let arg1 = Decimal128Array::from(vec![10000, 0])
+        .with_precision_and_scale(10, 4)
+        .unwrap();
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("decimal_add", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------+
+| decimal_add |
++-------------+
+| 2.23        |
+| 0.00        |
++-------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_legacy_decimal() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime.set_legacy_decimal(true);
+
     runtime
         .add_function(
             "decimal_add",
@@ -424,7 +930,7 @@ fn test_struct_to_json() {
     runtime
         .add_function(
             "to_json",
-            DataType::LargeUtf8,
+            json_field("to_json"),
             CallMode::ReturnNullOnNullInput,
             r#"
             export function to_json(object) {
@@ -524,3 +1030,269 @@ fn test_range() {
         .trim()
     );
 }
+
+#[test]
+fn test_table_function_iterables() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "repeat",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function repeat(n) {
+                return new Array(n).fill(n);
+            }
+            "#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "countdown",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function countdown(n) {
+                return {
+                    next() {
+                        return n > 0 ? { value: n--, done: false } : { done: true };
+                    },
+                };
+            }
+            "#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "scalar",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function scalar(n) {
+                return n;
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(2)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let outputs = runtime.call_table_function("repeat", &input, 2).unwrap();
+    let outputs: Vec<_> = outputs.map(|o| o.unwrap()).collect();
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(
+        pretty_format_batches(&outputs).unwrap().to_string(),
+        r#"
++-----+--------+
+| row | repeat |
++-----+--------+
+| 0   | 1      |
+| 2   | 2      |
+| 2   | 2      |
++-----+--------+
+"#
+        .trim()
+    );
+
+    let outputs = runtime
+        .call_table_function("countdown", &input, 10)
+        .unwrap();
+    let outputs: Vec<_> = outputs.map(|o| o.unwrap()).collect();
+    assert_eq!(
+        pretty_format_batches(&outputs).unwrap().to_string(),
+        r#"
++-----+-----------+
+| row | countdown |
++-----+-----------+
+| 0   | 1         |
+| 2   | 2         |
+| 2   | 1         |
++-----+-----------+
+"#
+        .trim()
+    );
+
+    let mut outputs = runtime.call_table_function("scalar", &input, 2).unwrap();
+    let err = outputs.next().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "failed to get iterator from return value");
+}
+
+#[test]
+fn test_aggregate() {
+    let mut runtime = Runtime::new().unwrap();
+    let state_type = DataType::Struct(
+        vec![
+            Field::new("sum", DataType::Float64, false),
+            Field::new("count", DataType::Int32, false),
+        ]
+        .into(),
+    );
+    runtime
+        .add_aggregate(
+            "avg",
+            state_type,
+            DataType::Float64,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            export function create_state() {
+                return { sum: 0, count: 0 };
+            }
+            export function accumulate(state, value) {
+                state.sum += value;
+                state.count += 1;
+            }
+            export function retract(state, value) {
+                state.sum -= value;
+                state.count -= 1;
+            }
+            export function merge(state1, state2) {
+                return { sum: state1.sum + state2.sum, count: state1.count + state2.count };
+            }
+            export function finish(state) {
+                return state.count == 0 ? null : state.sum / state.count;
+            }
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Float64, true)]);
+    let arg0 = Float64Array::from(vec![Some(1.0), None, Some(2.0), Some(6.0)]);
+    let input = RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(arg0)]).unwrap();
+
+    let empty = runtime.create_state("avg").unwrap();
+    assert_eq!(empty.len(), 1);
+    let state = runtime.accumulate("avg", &empty, &input).unwrap();
+    let output = runtime.finish("avg", &state).unwrap();
+    assert_eq!(output.as_primitive::<Float64Type>().value(0), 3.0);
+
+    // slide the window by one row
+    let arg0 = Float64Array::from(vec![Some(1.0), Some(7.0)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let ops = BooleanArray::from(vec![true, false]);
+    let state = runtime
+        .accumulate_or_retract("avg", &state, &ops, &input)
+        .unwrap();
+    let output = runtime.finish("avg", &state).unwrap();
+    assert_eq!(output.as_primitive::<Float64Type>().value(0), 5.0);
+
+    let states =
+        arrow_select::concat::concat(&[state.as_ref(), empty.as_ref(), state.as_ref()]).unwrap();
+    let output = runtime.finish("avg", &states).unwrap();
+    assert_eq!(
+        output.as_primitive::<Float64Type>(),
+        &Float64Array::from(vec![Some(5.0), None, Some(5.0)])
+    );
+    let merged = runtime.merge("avg", &states).unwrap();
+    let output = runtime.finish("avg", &merged).unwrap();
+    assert_eq!(output.as_primitive::<Float64Type>().value(0), 5.0);
+}
+
+#[test]
+fn test_aggregate_without_finish() {
+    let mut runtime = Runtime::new().unwrap();
+    let code = r#"
+        export function create_state() {
+            return 0;
+        }
+        export function accumulate(state, value) {
+            return state + value;
+        }
+    "#;
+    runtime
+        .add_aggregate(
+            "sum",
+            DataType::Int32,
+            DataType::Float64,
+            CallMode::ReturnNullOnNullInput,
+            code,
+        )
+        .unwrap_err();
+    runtime
+        .add_aggregate(
+            "sum",
+            DataType::Int32,
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            code,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(2)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let state = runtime.create_state("sum").unwrap();
+    let state = runtime.accumulate("sum", &state, &input).unwrap();
+    let output = runtime.finish("sum", &state).unwrap();
+    assert_eq!(output.as_primitive::<Int32Type>().value(0), 3);
+
+    let ops = BooleanArray::from(vec![true, false, false]);
+    let err = runtime
+        .accumulate_or_retract("sum", &state, &ops, &input)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "function does not support retraction");
+    let err = runtime.merge("sum", &state).unwrap_err();
+    assert_eq!(err.to_string(), "function does not support merging");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_identity_roundtrip(seed: u64, len in 0..64usize, null_density in 0.0f32..1.0) {
+        let mut runtime = Runtime::new().unwrap();
+        // 64-bit integers lose precision as JS numbers, and lists of primitives are passed
+        // as typed arrays, so they are not covered here.
+        let fields = [
+            DataType::Boolean,
+            DataType::Int8,
+            DataType::Int16,
+            DataType::Int32,
+            DataType::UInt8,
+            DataType::UInt16,
+            DataType::Float16,
+            DataType::Float32,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::LargeUtf8,
+            DataType::FixedSizeBinary(16),
+            DataType::Decimal128(38, 10),
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int32, true),
+                    Field::new("b", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+        ]
+        .into_iter()
+        .map(|data_type| Field::new("x", data_type, true))
+        .chain([json_field("x"), decimal_field("x")]);
+        for field in fields {
+            runtime
+                .add_function(
+                    "identity",
+                    field.clone(),
+                    CallMode::CalledOnNullInput,
+                    "export function identity(x) { return x; }",
+                )
+                .unwrap();
+            let array = random_field_array(&field, len, null_density, seed);
+            let input = RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![array]).unwrap();
+            assert_roundtrip_batch(|input: &RecordBatch| runtime.call("identity", input), &input);
+        }
+    }
+}
+
+/// Returns a nullable field of JSON type.
+fn json_field(name: &str) -> Field {
+    Field::new(name, DataType::Utf8, true)
+        .with_metadata([("ARROW:extension:name".into(), "arrowudf.json".into())].into())
+}
+
+/// Returns a nullable field of decimal type.
+fn decimal_field(name: &str) -> Field {
+    Field::new(name, DataType::Utf8, true)
+        .with_metadata([("ARROW:extension:name".into(), "arrowudf.decimal".into())].into())
+}
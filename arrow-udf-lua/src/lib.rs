@@ -0,0 +1,294 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![doc = include_str!("../README.md")]
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, Field, FieldRef, Schema};
+use mlua::{Lua, LuaOptions, MultiValue, RegistryKey, StdLib, Value};
+
+mod luaarrow;
+
+/// The Lua UDF runtime.
+pub struct Runtime {
+    functions: HashMap<String, Function>,
+    // NOTE: `functions` must be put before the interpreter to be dropped first.
+    lua: Lua,
+}
+
+impl Debug for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Runtime")
+            .field("functions", &self.functions.keys())
+            .finish()
+    }
+}
+
+/// A registered function.
+struct Function {
+    function: RegistryKey,
+    return_field: FieldRef,
+    mode: CallMode,
+    batched: bool,
+}
+
+/// A builder for `Runtime`.
+#[derive(Default, Debug)]
+pub struct Builder {
+    sandboxed: bool,
+}
+
+impl Builder {
+    /// Set whether the runtime is sandboxed.
+    ///
+    /// When sandboxed, only the `string`, `table`, `math`, `utf8` and `coroutine` libraries are
+    /// loaded, and the base functions `dofile`, `loadfile`, `load`, `require` and `collectgarbage`
+    /// are removed. This is useful for running untrusted code.
+    ///
+    /// The default is `false`, which loads all safe standard libraries including `io`, `os`
+    /// and `package`.
+    pub fn sandboxed(mut self, sandboxed: bool) -> Self {
+        self.sandboxed = sandboxed;
+        self
+    }
+
+    /// Build the `Runtime`.
+    pub fn build(self) -> Result<Runtime> {
+        let libs = match self.sandboxed {
+            true => {
+                StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE
+            }
+            false => StdLib::ALL_SAFE,
+        };
+        let lua = Lua::new_with(libs, LuaOptions::default())
+            .context("failed to create lua interpreter")?;
+        if self.sandboxed {
+            // remove base functions that read files, load code or control the garbage collector
+            let globals = lua.globals();
+            for name in ["dofile", "loadfile", "load", "require", "collectgarbage"] {
+                globals.raw_set(name, mlua::Nil)?;
+            }
+        }
+        Ok(Runtime {
+            functions: HashMap::new(),
+            lua,
+        })
+    }
+}
+
+/// A trait for types that can be converted into a [`Field`].
+pub trait IntoField {
+    /// Converts the type into a field with the given name.
+    fn into_field(self, name: &str) -> Field;
+}
+
+impl IntoField for DataType {
+    fn into_field(self, name: &str) -> Field {
+        Field::new(name, self, true)
+    }
+}
+
+impl IntoField for Field {
+    fn into_field(self, _name: &str) -> Field {
+        self
+    }
+}
+
+impl IntoField for FieldRef {
+    fn into_field(self, _name: &str) -> Field {
+        (*self).clone()
+    }
+}
+
+/// Whether the function will be called when some of its arguments are null.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallMode {
+    /// The function will be called normally when some of its arguments are null.
+    /// It is then the function author's responsibility to check for null values if necessary and respond appropriately.
+    #[default]
+    CalledOnNullInput,
+
+    /// The function always returns null whenever any of its arguments are null.
+    /// If this parameter is specified, the function is not executed when there are null arguments;
+    /// instead a null result is assumed automatically.
+    ReturnNullOnNullInput,
+}
+
+impl Runtime {
+    /// Create a new Lua UDF runtime.
+    pub fn new() -> Result<Self> {
+        Builder::default().build()
+    }
+
+    /// Return a new builder for `Runtime`.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Add a Lua function that is called on each row.
+    ///
+    /// The code should define a global function with the given name.
+    /// The return type can be a [`DataType`], or a [`Field`] to specify an extension type.
+    pub fn add_function(
+        &mut self,
+        name: &str,
+        return_type: impl IntoField,
+        mode: CallMode,
+        code: &str,
+    ) -> Result<()> {
+        self.add(name, return_type.into_field(name), mode, false, code)
+    }
+
+    /// Add a Lua function that is called once on each batch.
+    ///
+    /// The function is called with a table for each column and the number of rows, and should
+    /// return a table of results. Tables are indexed from 1, and null values are `nil`.
+    ///
+    /// ```lua
+    /// function add(a, b, n)
+    ///     local c = {}
+    ///     for i = 1, n do
+    ///         if a[i] ~= nil and b[i] ~= nil then
+    ///             c[i] = a[i] + b[i]
+    ///         end
+    ///     end
+    ///     return c
+    /// end
+    /// ```
+    pub fn add_batch_function(
+        &mut self,
+        name: &str,
+        return_type: impl IntoField,
+        code: &str,
+    ) -> Result<()> {
+        let field = return_type.into_field(name);
+        self.add(name, field, CallMode::CalledOnNullInput, true, code)
+    }
+
+    fn add(
+        &mut self,
+        name: &str,
+        return_field: Field,
+        mode: CallMode,
+        batched: bool,
+        code: &str,
+    ) -> Result<()> {
+        self.lua
+            .load(code)
+            .set_name(name)
+            .exec()
+            .context("failed to load code")?;
+        let function: mlua::Function = self
+            .lua
+            .globals()
+            .get(name)
+            .context("failed to get function. HINT: make sure the function is global")?;
+        let function = Function {
+            function: self.lua.create_registry_value(function)?,
+            return_field: return_field.into(),
+            mode,
+            batched,
+        };
+        if let Some(old) = self.functions.insert(name.to_string(), function) {
+            self.lua.remove_registry_value(old.function)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a function.
+    pub fn del_function(&mut self, name: &str) -> Result<()> {
+        let function = self.functions.remove(name).context("function not found")?;
+        self.lua.remove_registry_value(function.function)?;
+        Ok(())
+    }
+
+    /// Call the Lua UDF.
+    pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+        let function = self.functions.get(name).context("function not found")?;
+        let lua_function: mlua::Function = self.lua.registry_value(&function.function)?;
+        let results = match function.batched {
+            true => self.call_batch(&lua_function, input)?,
+            false => self.call_rows(&lua_function, function, input)?,
+        };
+        let array = luaarrow::build_array(&function.return_field, &self.lua, results)
+            .context("failed to build arrow array from return values")?;
+        let schema = Schema::new(vec![function.return_field.clone()]);
+        Ok(RecordBatch::try_new(Arc::new(schema), vec![array])?)
+    }
+
+    /// Call the function on each row.
+    fn call_rows<'lua>(
+        &'lua self,
+        lua_function: &mlua::Function<'lua>,
+        function: &Function,
+        input: &RecordBatch,
+    ) -> Result<Vec<Value<'lua>>> {
+        let mut results = Vec::with_capacity(input.num_rows());
+        let mut row = Vec::with_capacity(input.num_columns());
+        for i in 0..input.num_rows() {
+            row.clear();
+            for column in input.columns() {
+                let val = luaarrow::get_value(&self.lua, column, i)
+                    .context("failed to get lua value from arrow array")?;
+                row.push(val);
+            }
+            if function.mode == CallMode::ReturnNullOnNullInput && row.iter().any(|v| v.is_nil()) {
+                results.push(Value::Nil);
+                continue;
+            }
+            let args = MultiValue::from_iter(row.drain(..));
+            let result = lua_function.call(args).context("failed to call function")?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Call the function on the whole batch.
+    fn call_batch<'lua>(
+        &'lua self,
+        lua_function: &mlua::Function<'lua>,
+        input: &RecordBatch,
+    ) -> Result<Vec<Value<'lua>>> {
+        let mut args = Vec::with_capacity(input.num_columns() + 1);
+        for column in input.columns() {
+            let table = self.lua.create_table_with_capacity(input.num_rows(), 0)?;
+            for i in 0..input.num_rows() {
+                let val = luaarrow::get_value(&self.lua, column, i)
+                    .context("failed to get lua value from arrow array")?;
+                table.raw_set(i + 1, val)?;
+            }
+            args.push(Value::Table(table));
+        }
+        args.push(Value::Integer(input.num_rows() as _));
+        let result: Value = lua_function
+            .call(MultiValue::from_vec(args))
+            .context("failed to call function")?;
+        let Value::Table(table) = result else {
+            bail!(
+                "expect a table from batch function, got {}",
+                result.type_name()
+            );
+        };
+        let mut results = Vec::with_capacity(input.num_rows());
+        for i in 1..=input.num_rows() {
+            results.push(table.raw_get(i)?);
+        }
+        Ok(results)
+    }
+}
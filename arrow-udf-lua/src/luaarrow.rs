@@ -0,0 +1,227 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Convert arrow array from/to Lua values.
+
+use anyhow::{bail, Result};
+use arrow_array::{array::*, builder::*, cast::AsArray};
+use arrow_buffer::OffsetBuffer;
+use arrow_schema::{DataType, Field};
+use half::f16;
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+use std::sync::Arc;
+
+macro_rules! get_value {
+    ($array_type: ty, $lua:expr, $array:expr, $i:expr) => {{
+        let array = $array.as_any().downcast_ref::<$array_type>().unwrap();
+        Ok(array.value($i).into_lua($lua)?)
+    }};
+}
+
+macro_rules! get_string {
+    ($array_type: ty, $lua:expr, $array:expr, $i:expr) => {{
+        let array = $array.as_any().downcast_ref::<$array_type>().unwrap();
+        Ok(Value::String($lua.create_string(array.value($i))?))
+    }};
+}
+
+macro_rules! build_array {
+    // primitive types
+    ($builder_type: ty, $native_type: ty, $lua:expr, $values:expr) => {{
+        let mut builder = <$builder_type>::with_capacity($values.len());
+        for val in $values {
+            match val {
+                Value::Nil => builder.append_null(),
+                val => builder.append_value(<$native_type>::from_lua(val, $lua)?),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }};
+}
+
+macro_rules! build_string_array {
+    ($builder_type: ty, $lua:expr, $values:expr) => {{
+        let mut builder = <$builder_type>::with_capacity($values.len(), 1024);
+        for val in $values {
+            match val {
+                Value::Nil => builder.append_null(),
+                val => builder.append_value(String::from_lua(val, $lua)?),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }};
+}
+
+macro_rules! build_binary_array {
+    ($builder_type: ty, $lua:expr, $values:expr) => {{
+        let mut builder = <$builder_type>::with_capacity($values.len(), 1024);
+        for val in $values {
+            match val {
+                Value::Nil => builder.append_null(),
+                val => builder.append_value(mlua::String::from_lua(val, $lua)?.as_bytes()),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }};
+}
+
+/// Get array element as a Lua value.
+pub fn get_value<'lua>(lua: &'lua Lua, array: &dyn Array, i: usize) -> Result<Value<'lua>> {
+    if array.is_null(i) {
+        return Ok(Value::Nil);
+    }
+    match array.data_type() {
+        DataType::Null => Ok(Value::Nil),
+        DataType::Boolean => get_value!(BooleanArray, lua, array, i),
+        DataType::Int8 => get_value!(Int8Array, lua, array, i),
+        DataType::Int16 => get_value!(Int16Array, lua, array, i),
+        DataType::Int32 => get_value!(Int32Array, lua, array, i),
+        DataType::Int64 => get_value!(Int64Array, lua, array, i),
+        DataType::UInt8 => get_value!(UInt8Array, lua, array, i),
+        DataType::UInt16 => get_value!(UInt16Array, lua, array, i),
+        DataType::UInt32 => get_value!(UInt32Array, lua, array, i),
+        DataType::UInt64 => get_value!(UInt64Array, lua, array, i),
+        DataType::Float16 => {
+            let array = array.as_any().downcast_ref::<Float16Array>().unwrap();
+            Ok(Value::Number(array.value(i).to_f64()))
+        }
+        DataType::Float32 => get_value!(Float32Array, lua, array, i),
+        DataType::Float64 => get_value!(Float64Array, lua, array, i),
+        DataType::Utf8 => get_string!(StringArray, lua, array, i),
+        DataType::LargeUtf8 => get_string!(LargeStringArray, lua, array, i),
+        DataType::Binary => get_string!(BinaryArray, lua, array, i),
+        DataType::LargeBinary => get_string!(LargeBinaryArray, lua, array, i),
+        DataType::List(_) => {
+            let list = array.as_list::<i32>().value(i);
+            let table = lua.create_table_with_capacity(list.len(), 1)?;
+            for j in 0..list.len() {
+                table.raw_set(j + 1, get_value(lua, list.as_ref(), j)?)?;
+            }
+            // store the length like `table.pack`, since nil elements make the length ambiguous
+            table.raw_set("n", list.len())?;
+            Ok(Value::Table(table))
+        }
+        DataType::Struct(fields) => {
+            let array = array.as_struct();
+            let table = lua.create_table_with_capacity(0, fields.len())?;
+            for (j, field) in fields.iter().enumerate() {
+                let value = get_value(lua, array.column(j).as_ref(), i)?;
+                table.raw_set(field.name().as_str(), value)?;
+            }
+            Ok(Value::Table(table))
+        }
+        t => bail!("unsupported data type: {t}"),
+    }
+}
+
+/// Build arrow array from Lua values.
+pub fn build_array<'lua>(
+    field: &Field,
+    lua: &'lua Lua,
+    values: Vec<Value<'lua>>,
+) -> Result<ArrayRef> {
+    match field.data_type() {
+        DataType::Null => {
+            let mut builder = NullBuilder::new();
+            for val in values {
+                match val {
+                    Value::Nil => builder.append_null(),
+                    _ => builder.append_empty_value(),
+                }
+            }
+            Ok(Arc::new(builder.finish()))
+        }
+        DataType::Boolean => build_array!(BooleanBuilder, bool, lua, values),
+        DataType::Int8 => build_array!(Int8Builder, i8, lua, values),
+        DataType::Int16 => build_array!(Int16Builder, i16, lua, values),
+        DataType::Int32 => build_array!(Int32Builder, i32, lua, values),
+        DataType::Int64 => build_array!(Int64Builder, i64, lua, values),
+        DataType::UInt8 => build_array!(UInt8Builder, u8, lua, values),
+        DataType::UInt16 => build_array!(UInt16Builder, u16, lua, values),
+        DataType::UInt32 => build_array!(UInt32Builder, u32, lua, values),
+        DataType::UInt64 => build_array!(UInt64Builder, u64, lua, values),
+        DataType::Float16 => {
+            let mut builder = Float16Builder::with_capacity(values.len());
+            for val in values {
+                match val {
+                    Value::Nil => builder.append_null(),
+                    val => builder.append_value(f16::from_f64(f64::from_lua(val, lua)?)),
+                }
+            }
+            Ok(Arc::new(builder.finish()))
+        }
+        DataType::Float32 => build_array!(Float32Builder, f32, lua, values),
+        DataType::Float64 => build_array!(Float64Builder, f64, lua, values),
+        DataType::Utf8 => build_string_array!(StringBuilder, lua, values),
+        DataType::LargeUtf8 => build_string_array!(LargeStringBuilder, lua, values),
+        DataType::Binary => build_binary_array!(BinaryBuilder, lua, values),
+        DataType::LargeBinary => build_binary_array!(LargeBinaryBuilder, lua, values),
+        DataType::List(inner) => {
+            // flatten lists
+            let mut flatten_values = vec![];
+            let mut offsets = Vec::<i32>::with_capacity(values.len() + 1);
+            offsets.push(0);
+            for val in &values {
+                if let Some(table) = as_table(val)? {
+                    let len = table
+                        .raw_get::<_, Option<usize>>("n")?
+                        .unwrap_or(table.raw_len());
+                    flatten_values.reserve(len);
+                    for j in 1..=len {
+                        flatten_values.push(table.raw_get(j)?);
+                    }
+                }
+                offsets.push(flatten_values.len() as i32);
+            }
+            let values_array = build_array(inner, lua, flatten_values)?;
+            let nulls = values.iter().map(|v| !v.is_nil()).collect();
+            Ok(Arc::new(ListArray::new(
+                inner.clone(),
+                OffsetBuffer::new(offsets.into()),
+                values_array,
+                Some(nulls),
+            )))
+        }
+        DataType::Struct(fields) => {
+            let mut arrays = Vec::with_capacity(fields.len());
+            for field in fields {
+                let mut field_values = Vec::with_capacity(values.len());
+                for val in &values {
+                    let v = match as_table(val)? {
+                        Some(table) => table.get(field.name().as_str())?,
+                        None => Value::Nil,
+                    };
+                    field_values.push(v);
+                }
+                arrays.push(build_array(field, lua, field_values)?);
+            }
+            let nulls = values.iter().map(|v| !v.is_nil()).collect();
+            Ok(Arc::new(StructArray::new(
+                fields.clone(),
+                arrays,
+                Some(nulls),
+            )))
+        }
+        t => bail!("unsupported data type: {t}"),
+    }
+}
+
+/// Returns the table, or `None` if the value is nil.
+fn as_table<'a, 'lua>(value: &'a Value<'lua>) -> Result<Option<&'a Table<'lua>>> {
+    match value {
+        Value::Nil => Ok(None),
+        Value::Table(table) => Ok(Some(table)),
+        v => bail!("expect table, got {}", v.type_name()),
+    }
+}
@@ -0,0 +1,507 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow_array::types::Int32Type;
+use arrow_array::{
+    Array, BinaryArray, Int32Array, ListArray, RecordBatch, StringArray, StructArray,
+};
+use arrow_cast::pretty::pretty_format_batches;
+use arrow_schema::{DataType, Field, Schema};
+use arrow_udf::testing::{assert_roundtrip_batch, random_field_array};
+use arrow_udf_lua::{CallMode, Runtime};
+use proptest::prelude::*;
+
+#[test]
+fn test_gcd() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "gcd",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            function gcd(a, b)
+                while b ~= 0 do
+                    a, b = b, a % b
+                end
+                return a
+            end
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from(vec![Some(25), None]);
+    let arg1 = Int32Array::from(vec![Some(15), Some(1)]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("gcd", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-----+
+| gcd |
++-----+
+| 5   |
+|     |
++-----+"#,
+    );
+}
+
+#[test]
+fn test_called_on_null_input() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "to_string",
+            DataType::Utf8,
+            CallMode::CalledOnNullInput,
+            r#"
+            function to_string(a)
+                if a == nil then
+                    return "null"
+                end
+                return tostring(a)
+            end
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(5), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("to_string", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-----------+
+| to_string |
++-----------+
+| 5         |
+| null      |
++-----------+"#,
+    );
+}
+
+#[test]
+fn test_batch_function() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_batch_function(
+            "add",
+            DataType::Int32,
+            r#"
+            function add(a, b, n)
+                local c = {}
+                for i = 1, n do
+                    if a[i] ~= nil and b[i] ~= nil then
+                        c[i] = a[i] + b[i]
+                    end
+                end
+                return c
+            end
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let arg1 = Int32Array::from(vec![Some(2), Some(2), Some(4)]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("add", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-----+
+| add |
++-----+
+| 3   |
+|     |
+| 7   |
++-----+"#,
+    );
+}
+
+#[test]
+fn test_batch_function_not_table() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_batch_function("one", DataType::Int32, "function one(n) return 1 end")
+        .unwrap();
+    let input = RecordBatch::try_new_with_options(
+        Arc::new(Schema::empty()),
+        vec![],
+        &arrow_array::RecordBatchOptions::default().with_row_count(Some(1)),
+    )
+    .unwrap();
+    let err = runtime.call("one", &input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expect a table from batch function, got integer"
+    );
+}
+
+#[test]
+fn test_binary() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "reverse",
+            DataType::Binary,
+            CallMode::ReturnNullOnNullInput,
+            "function reverse(s) return string.reverse(s) end",
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Binary, true)]);
+    let arg0 = BinaryArray::from(vec![Some(&b"ab\0c"[..]), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("reverse", &input).unwrap();
+    let expected = BinaryArray::from(vec![Some(&b"c\0ba"[..]), None]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+}
+
+#[test]
+fn test_list() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "split",
+            DataType::new_list(DataType::Utf8, true),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            function split(s)
+                local t = {}
+                for word in string.gmatch(s, "[^,]+") do
+                    table.insert(t, word)
+                end
+                return t
+            end
+            "#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "sum",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            function sum(t)
+                local s = 0
+                for _, v in ipairs(t) do
+                    s = s + v
+                end
+                return s
+            end
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a,b,c"), None, Some("")]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("split", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-----------+
+| split     |
++-----------+
+| [a, b, c] |
+|           |
+| []        |
++-----------+"#,
+    );
+
+    let list = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+        Some(vec![Some(1), Some(2), Some(3)]),
+        None,
+    ]);
+    let schema = Schema::new(vec![Field::new("x", list.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(list)]).unwrap();
+
+    let output = runtime.call("sum", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-----+
+| sum |
++-----+
+| 6   |
+|     |
++-----+"#,
+    );
+}
+
+#[test]
+fn test_struct() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "key_value",
+            DataType::Struct(
+                vec![
+                    Field::new("key", DataType::Utf8, true),
+                    Field::new("value", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            function key_value(s)
+                local k, v = string.match(s, "([^=]*)=(.*)")
+                if k == nil then
+                    return nil
+                end
+                return { key = k, value = v }
+            end
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a=b"), None, Some("abc")]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("key_value", &input).unwrap();
+    check(
+        &[output],
+        r#"
++--------------------+
+| key_value          |
++--------------------+
+| {key: a, value: b} |
+|                    |
+|                    |
++--------------------+"#,
+    );
+}
+
+#[test]
+fn test_struct_arg() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "get_a",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            "function get_a(s) return s.a end",
+        )
+        .unwrap();
+
+    let array = StructArray::from(vec![(
+        Arc::new(Field::new("a", DataType::Int32, true)),
+        Arc::new(Int32Array::from(vec![Some(1), None])) as _,
+    )]);
+    let schema = Schema::new(vec![Field::new("x", array.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+
+    let output = runtime.call("get_a", &input).unwrap();
+    let expected = Int32Array::from(vec![Some(1), None]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+}
+
+#[test]
+fn test_sandboxed() {
+    let code = r#"
+        function unsafe_globals()
+            local names = {}
+            for _, name in ipairs({"io", "os", "dofile", "loadfile", "load", "require", "collectgarbage"}) do
+                if _G[name] ~= nil then
+                    table.insert(names, name)
+                end
+            end
+            return table.concat(names, ",")
+        end
+    "#;
+    let input = RecordBatch::try_new_with_options(
+        Arc::new(Schema::empty()),
+        vec![],
+        &arrow_array::RecordBatchOptions::default().with_row_count(Some(1)),
+    )
+    .unwrap();
+
+    let mut runtime = Runtime::builder().sandboxed(true).build().unwrap();
+    runtime
+        .add_function(
+            "unsafe_globals",
+            DataType::Utf8,
+            CallMode::CalledOnNullInput,
+            code,
+        )
+        .unwrap();
+    let output = runtime.call("unsafe_globals", &input).unwrap();
+    assert_eq!(output.column(0).as_ref(), &StringArray::from(vec![""]));
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "unsafe_globals",
+            DataType::Utf8,
+            CallMode::CalledOnNullInput,
+            code,
+        )
+        .unwrap();
+    let output = runtime.call("unsafe_globals", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &StringArray::from(vec!["io,os,dofile,loadfile,load,require,collectgarbage"])
+    );
+}
+
+#[test]
+fn test_error() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "div",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+            function div(a, b)
+                if b == 0 then
+                    error("division by zero")
+                end
+                return a // b
+            end
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from(vec![1]);
+    let arg1 = Int32Array::from(vec![0]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let err = runtime.call("div", &input).unwrap_err();
+    assert!(format!("{err:?}").contains("division by zero"));
+
+    // syntax error
+    let err = runtime
+        .add_function(
+            "bad",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            "function bad(",
+        )
+        .unwrap_err();
+    assert_eq!(err.to_string(), "failed to load code");
+
+    // function not defined
+    let err = runtime
+        .add_function(
+            "undefined",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            "",
+        )
+        .unwrap_err();
+    assert!(err.to_string().starts_with("failed to get function"));
+}
+
+#[test]
+fn test_del_function() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "one",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            "function one() return 1 end",
+        )
+        .unwrap();
+    runtime.del_function("one").unwrap();
+
+    let input = RecordBatch::new_empty(Arc::new(Schema::empty()));
+    let err = runtime.call("one", &input).unwrap_err();
+    assert_eq!(err.to_string(), "function not found");
+    assert!(runtime.del_function("one").is_err());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_identity_roundtrip(seed: u64, len in 0..64usize, null_density in 0.0f32..1.0) {
+        let mut runtime = Runtime::new().unwrap();
+        // unsigned 64-bit integers may overflow Lua integers, so they are not covered here.
+        let fields = [
+            DataType::Boolean,
+            DataType::Int8,
+            DataType::Int16,
+            DataType::Int32,
+            DataType::Int64,
+            DataType::UInt8,
+            DataType::UInt16,
+            DataType::UInt32,
+            DataType::Float16,
+            DataType::Float32,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::LargeUtf8,
+            DataType::Binary,
+            DataType::LargeBinary,
+            DataType::new_list(DataType::Int32, true),
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int32, true),
+                    Field::new("b", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+        ]
+        .into_iter()
+        .map(|data_type| Field::new("x", data_type, true));
+        for field in fields {
+            runtime
+                .add_function(
+                    "identity",
+                    field.clone(),
+                    CallMode::CalledOnNullInput,
+                    "function identity(x) return x end",
+                )
+                .unwrap();
+            let array = random_field_array(&field, len, null_density, seed);
+            let input = RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![array]).unwrap();
+            assert_roundtrip_batch(|input: &RecordBatch| runtime.call("identity", input), &input);
+        }
+    }
+}
+
+/// Compare the actual output with the expected output.
+#[track_caller]
+fn check(actual: &[RecordBatch], expect: &str) {
+    assert_eq!(
+        expect.trim(),
+        pretty_format_batches(actual).unwrap().to_string(),
+    );
+}
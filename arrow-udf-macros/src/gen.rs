@@ -23,6 +23,48 @@ use super::*;
 impl FunctionAttr {
     /// Expands the wildcard in function arguments or return type.
     pub fn expand(&self) -> Vec<Self> {
+        self.expand_correlated()
+            .iter()
+            .flat_map(|attr| attr.expand_independent())
+            .collect()
+    }
+
+    /// Expands the correlated wildcards, such as `*numeric` or `{int4,int8}`.
+    ///
+    /// All occurrences of the same wildcard are replaced with the same type,
+    /// so `neg(*numeric) -> *numeric` expands to `neg(int2) -> int2`, `neg(int4) -> int4`, etc.
+    fn expand_correlated(&self) -> Vec<Self> {
+        // the wildcards and their suffix `[]` stripped
+        let base = |ty: &str| ty.trim_end_matches("[]").to_string();
+        let wildcards = (self.args.iter().chain([&self.ret]))
+            .map(|ty| base(ty))
+            .filter(|ty| types::expand_correlated_type_wildcard(ty).is_some())
+            .unique()
+            .collect_vec();
+        if wildcards.is_empty() {
+            return vec![self.clone()];
+        }
+        let substitute =
+            |ty: &str, types: &[&str]| match wildcards.iter().position(|w| *w == base(ty)) {
+                Some(i) => format!("{}{}", types[i], &ty[base(ty).len()..]),
+                None => ty.to_string(),
+            };
+        wildcards
+            .iter()
+            .map(|w| types::expand_correlated_type_wildcard(w).unwrap())
+            .multi_cartesian_product()
+            .map(|types| FunctionAttr {
+                args: self.args.iter().map(|ty| substitute(ty, &types)).collect(),
+                ret: substitute(&self.ret, &types),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Expands the independent wildcards, such as `*` or `int*`.
+    ///
+    /// Each occurrence is expanded independently to the cartesian product of all types.
+    fn expand_independent(&self) -> Vec<Self> {
         let args = self.args.iter().map(|ty| types::expand_type_wildcard(ty));
         let ret = types::expand_type_wildcard(&self.ret);
         // multi_cartesian_product should emit an empty set if the input is empty.
@@ -45,7 +87,9 @@ impl FunctionAttr {
     }
 
     /// Generate the type infer function.
-    #[allow(dead_code)]
+    ///
+    /// The function infers the return type from the argument types.
+    /// It is required for functions with polymorphic return type `any` or `anyarray`.
     fn generate_type_infer_fn(&self) -> Result<TokenStream2> {
         if let Some(func) = &self.type_infer {
             if func == "panic" {
@@ -61,7 +105,10 @@ impl FunctionAttr {
             }
             if let Some(i) = self.args.iter().position(|t| t == "anyarray") {
                 // infer as the element type of "anyarray" argument
-                return Ok(quote! { |args| Ok(args[#i].as_list().clone()) });
+                return Ok(quote! { |args| match &args[#i] {
+                    ::arrow_udf::codegen::arrow_schema::DataType::List(field) => Ok(field.data_type().clone()),
+                    t => Err(::arrow_udf::Error::InvalidArgumentError(format!("expect list type, got {t}"))),
+                } });
             }
         } else if self.ret == "anyarray" {
             if let Some(i) = self.args.iter().position(|t| t == "anyarray") {
@@ -69,17 +116,14 @@ impl FunctionAttr {
                 return Ok(quote! { |args| Ok(args[#i].clone()) });
             }
             if let Some(i) = self.args.iter().position(|t| t == "any") {
-                // infer as the array type of "any" argument
-                return Ok(quote! { |args| Ok(DataType::List(Box::new(args[#i].clone()))) });
-            }
-        } else if self.ret == "struct" {
-            if let Some(i) = self.args.iter().position(|t| t == "struct") {
-                // infer as the type of "struct" argument
-                return Ok(quote! { |args| Ok(args[#i].clone()) });
+                // infer as the list type of "any" argument
+                return Ok(quote! { |args| Ok(
+                    ::arrow_udf::codegen::arrow_schema::DataType::new_list(args[#i].clone(), true)
+                ) });
             }
         } else {
             // the return type is fixed
-            let ty = data_type(&self.ret);
+            let ty = self.ret_data_type();
             return Ok(quote! { |_| Ok(#ty) });
         }
         Err(Error::new(
@@ -93,6 +137,11 @@ impl FunctionAttr {
     /// The types of arguments and return value should not contain wildcard.
     pub fn generate_function_descriptor(&self, user_fn: &UserFunctionAttr) -> Result<TokenStream2> {
         let name = self.name.clone();
+        let schema = match &self.schema {
+            Some(schema) => quote! { Some(#schema.into()) },
+            None => quote! { None },
+        };
+        let version = self.version.unwrap_or(1);
         let variadic = matches!(self.args.last(), Some(t) if t == "...");
         let args = match variadic {
             true => &self.args[..self.args.len() - 1],
@@ -101,7 +150,22 @@ impl FunctionAttr {
         .iter()
         .map(|ty| sig_data_type(ty))
         .collect_vec();
-        let ret = sig_data_type(&self.ret);
+        let arg_types = match self.flatten_struct() {
+            // the fields of the struct are the arguments
+            Some(struct_type) => {
+                quote! { ::arrow_udf::codegen::flatten_struct_types(&<#struct_type as ::arrow_udf::types::StructType>::fields()) }
+            }
+            None => quote! { vec![#(#args),*] },
+        };
+        let ret = match types::tuple_types(&self.ret) {
+            Some(_) => {
+                let ty = self.ret_data_type();
+                quote! { SigDataType::Exact(#ty) }
+            }
+            None => sig_data_type(&self.ret),
+        };
+        let type_infer = self.generate_type_infer_fn()?;
+        let volatile = self.volatile;
 
         let eval_name = match &self.output {
             Some(output) => format_ident!("{}", output),
@@ -110,7 +174,30 @@ impl FunctionAttr {
         let sig_name = format_ident!("{}_sig", self.ident_name());
         let ffi_name = format_ident!("{}_ffi", self.ident_name());
         let export_name = format!("arrowudf_{}", base64_encode(&self.normalize_signature()));
+        let ffi_cfg = ffi_cfg();
         let eval_function = self.generate_function(user_fn, &eval_name)?;
+        let eval_scalar = match self.has_eval_scalar(user_fn) {
+            true => {
+                let eval_scalar_name = format_ident!("{}_scalar", eval_name);
+                quote! { Some(#eval_scalar_name) }
+            }
+            false => quote! { None },
+        };
+        let init = match &self.init {
+            Some(_) => {
+                let eval_init_name = format_ident!("{}_init", eval_name);
+                quote! { Some(#eval_init_name) }
+            }
+            None => quote! { None },
+        };
+        let fallback = match &self.fallback {
+            Some(fallback) => {
+                let function: syn::Path = syn::parse_str(fallback)?;
+                let max_failures = self.max_failures.unwrap_or(3);
+                quote! { Some(::arrow_udf::sig::Fallback { function: #function, max_failures: #max_failures }) }
+            }
+            None => quote! { None },
+        };
         let kind = match self.is_table_function {
             true => quote! { Table },
             false => quote! { Scalar },
@@ -119,10 +206,30 @@ impl FunctionAttr {
             true => quote! { table_wrapper },
             false => quote! { scalar_wrapper },
         };
+        let aliases = self.aliases.iter().enumerate().map(|(i, alias)| {
+            let alias_sig_name = format_ident!("{}_alias{i}_sig", self.ident_name());
+            let (schema, name) = match alias.split_once('.') {
+                Some((schema, name)) => (quote! { Some(#schema.into()) }, name),
+                None => (quote! { None }, alias.as_str()),
+            };
+            quote! {
+                #[cfg(feature = "global_registry")]
+                #[::arrow_udf::codegen::linkme::distributed_slice(::arrow_udf::sig::SIGNATURES)]
+                #[linkme(crate = ::arrow_udf::codegen::linkme)]
+                fn #alias_sig_name() -> ::arrow_udf::sig::FunctionSignature {
+                    ::arrow_udf::sig::FunctionSignature {
+                        schema: #schema,
+                        name: #name.into(),
+                        ..#sig_name()
+                    }
+                }
+            }
+        });
 
         Ok(quote! {
             #eval_function
 
+            #ffi_cfg
             #[export_name = #export_name]
             unsafe extern "C" fn #ffi_name(ptr: *const u8, len: usize, out: *mut arrow_udf::ffi::CSlice) -> i32 {
                 arrow_udf::ffi::#ffi_wrapper(#eval_name, ptr, len, out)
@@ -130,22 +237,333 @@ impl FunctionAttr {
 
             #[cfg(feature = "global_registry")]
             #[::arrow_udf::codegen::linkme::distributed_slice(::arrow_udf::sig::SIGNATURES)]
+            #[linkme(crate = ::arrow_udf::codegen::linkme)]
             fn #sig_name() -> ::arrow_udf::sig::FunctionSignature {
+                use ::std::sync::Arc;
                 use ::arrow_udf::sig::{FunctionSignature, FunctionKind, SigDataType};
                 use ::arrow_udf::codegen::arrow_schema::{self, TimeUnit, IntervalUnit};
 
                 FunctionSignature {
+                    schema: #schema,
                     name: #name.into(),
-                    arg_types: vec![#(#args),*],
+                    version: #version,
+                    arg_types: #arg_types,
                     variadic: #variadic,
                     return_type: #ret,
+                    type_infer: {
+                        let type_infer: fn(&[arrow_schema::DataType]) -> ::arrow_udf::Result<arrow_schema::DataType> = #type_infer;
+                        Box::new(type_infer)
+                    },
+                    volatile: #volatile,
+                    runtime: "rust".into(),
                     function: FunctionKind::#kind(#eval_name),
+                    eval_scalar: #eval_scalar,
+                    init: #init,
+                    fallback: #fallback,
                 }
             }
+
+            #(#aliases)*
         })
     }
 
+    /// Generate the accumulate function of an aggregate function and its FFI export.
+    ///
+    /// The state is of the return type. It is read from and written to an array of length 1,
+    /// so that the caller can keep it between calls.
+    pub fn generate_aggregate_descriptor(
+        &self,
+        user_fn: &UserFunctionAttr,
+    ) -> Result<TokenStream2> {
+        let err = |msg: &str| Err(Error::new(Span::call_site(), msg));
+        if self.is_table_function {
+            return err("aggregate functions can not return a set");
+        }
+        if let Some(state) = &self.state {
+            if types::normalize_type(state) != self.ret {
+                return err("state type different from the return type is not supported yet");
+            }
+        }
+        let state_ty = self.ret.as_str();
+        if !types::is_primitive(state_ty)
+            && !types::is_bytes(state_ty)
+            && !matches!(
+                state_ty,
+                "boolean" | "date" | "time" | "timestamp" | "interval" | "decimal" | "json"
+            )
+        {
+            return err(&format!("unsupported state type: {state_ty}"));
+        }
+        for ty in &self.args {
+            if ty == "..."
+                || ty.ends_with("[]")
+                || ty.starts_with("struct ")
+                || types::is_polymorphic(ty)
+                || types::vector_size(ty).is_some()
+            {
+                return err(&format!("unsupported argument type: {ty}"));
+            }
+        }
+        if user_fn.first_mut_ref_arg.is_some() {
+            return err("`&mut` state is not supported yet");
+        }
+        if user_fn.async_ || user_fn.context || user_fn.write {
+            return err("aggregate functions can not be async or take a context or writer");
+        }
+        let num_args = self.args.len() + 1 + user_fn.retract as usize;
+        if user_fn.args_option.len() != num_args {
+            return err(&format!(
+                "expect {num_args} arguments for the user function, got {}",
+                user_fn.args_option.len()
+            ));
+        }
+        let state_option = user_fn.args_option[0];
+        if !state_option && self.init_state.is_none() {
+            return err("`init_state` is required if the state argument is not `Option`");
+        }
+
+        let eval_name = format_ident!("{}_eval", self.ident_name());
+        let ffi_name = format_ident!("{}_ffi", self.ident_name());
+        let export_name = format!("arrowudaf_{}", base64_encode(&self.normalize_signature()));
+        let ffi_cfg = ffi_cfg();
+        let user_fn_name = format_ident!("{}", user_fn.name);
+
+        // read the state
+        let state_array = format_ident!("{}", types::array_type(state_ty));
+        let v = format_ident!("v");
+        let owned_state = match state_ty {
+            "varchar" | "large_varchar" => quote! { v.to_string() },
+            "bytea" | "large_bytea" => quote! { v.to_vec() },
+            _ => transform_input(&v, state_ty),
+        };
+        let init_state = match &self.init_state {
+            Some(init) => {
+                let init: syn::Expr = syn::parse_str(init)?;
+                quote! { Some(#init) }
+            }
+            None => quote! { None },
+        };
+
+        // read the arguments
+        let indices = (0..self.args.len()).collect_vec();
+        let arrays = indices.iter().map(|i| format_ident!("a{i}")).collect_vec();
+        let inputs = indices.iter().map(|i| format_ident!("i{i}")).collect_vec();
+        let downcast_arrays = indices.iter().zip(&arrays).map(|(i, array)| {
+            let arg_array = format_ident!("{}", types::array_type(&self.args[*i]));
+            // the first column is the retract flags
+            let column = i + 1;
+            quote! {
+                let #array: &#arg_array = input.column(#column).as_any().downcast_ref()
+                    .ok_or_else(|| ::arrow_udf::codegen::arrow_schema::ArrowError::CastError(
+                        format!("expect {} for the {}-th argument", stringify!(#arg_array), #i)
+                    ))?;
+            }
+        });
+        let get_inputs = indices.iter().map(|i| {
+            let (input, array) = (&inputs[*i], &arrays[*i]);
+            let transformed = transform_input(input, &self.args[*i]);
+            match user_fn.args_option[i + 1] {
+                true => quote! {
+                    let #input = (!#array.is_null(i)).then(|| #array.value(i)).map(|#input| #transformed);
+                },
+                false => quote! {
+                    if #array.is_null(i) {
+                        continue;
+                    }
+                    let #input = #array.value(i);
+                    let #input = #transformed;
+                },
+            }
+        });
+        let retract_arg = user_fn.retract.then(|| quote! { retract, });
+        let check_retract = (!user_fn.retract).then(|| {
+            quote! {
+                if retract {
+                    return Err(::arrow_udf::codegen::arrow_schema::ArrowError::InvalidArgumentError(
+                        "function does not support retraction".into(),
+                    ));
+                }
+            }
+        });
+        let state_arg = match state_option {
+            true => quote! { state.take() },
+            false => quote! {
+                match state.take() {
+                    Some(state) => state,
+                    None => continue,
+                }
+            },
+        };
+        let call = quote! { #user_fn_name(#state_arg, #(#inputs,)* #retract_arg) };
+        let update = match user_fn.return_type_kind {
+            ReturnTypeKind::T => quote! { state = Some(#call); },
+            ReturnTypeKind::Option => quote! { state = #call; },
+            _ => return err("aggregate functions can not return `Result`"),
+        };
+
+        // write the state
+        let builder = builder(state_ty, &quote! { 1 }, &quote! { 0 });
+        let append = gen_append(state_ty);
+
+        Ok(quote! {
+            fn #eval_name(
+                state: Option<&dyn ::arrow_udf::codegen::arrow_array::Array>,
+                input: &::arrow_udf::codegen::arrow_array::RecordBatch,
+            ) -> ::arrow_udf::Result<::arrow_udf::codegen::arrow_array::ArrayRef> {
+                use ::std::sync::Arc;
+                use ::arrow_udf::codegen::arrow_array::{Array, array::*, builder::*};
+
+                let mut state = match state {
+                    Some(state) if state.is_valid(0) => {
+                        let state: &#state_array = state.as_any().downcast_ref()
+                            .ok_or_else(|| ::arrow_udf::codegen::arrow_schema::ArrowError::CastError(
+                                format!("expect {} for the state", stringify!(#state_array))
+                            ))?;
+                        let v = state.value(0);
+                        Some(#owned_state)
+                    }
+                    _ => #init_state,
+                };
+                if input.num_rows() > 0 {
+                    let ops: &BooleanArray = input.column(0).as_any().downcast_ref()
+                        .ok_or_else(|| ::arrow_udf::codegen::arrow_schema::ArrowError::CastError(
+                            "expect BooleanArray for the retract flags".into()
+                        ))?;
+                    #(#downcast_arrays)*
+                    for i in 0..input.num_rows() {
+                        let retract = ops.is_valid(i) && ops.value(i);
+                        #check_retract
+                        #(#get_inputs)*
+                        #update
+                    }
+                }
+                let mut builder = #builder;
+                let v = state;
+                #append
+                Ok(Arc::new(builder.finish()))
+            }
+
+            #ffi_cfg
+            #[export_name = #export_name]
+            unsafe extern "C" fn #ffi_name(
+                state_ptr: *const u8,
+                state_len: usize,
+                ptr: *const u8,
+                len: usize,
+                out: *mut arrow_udf::ffi::CSlice,
+            ) -> i32 {
+                arrow_udf::ffi::aggregate_wrapper(#eval_name, state_ptr, state_len, ptr, len, out)
+            }
+        })
+    }
+
+    /// Returns the fields of the output columns, excluding the error column.
+    ///
+    /// A tuple return type `(T1, T2, ..)` has one column for each element,
+    /// named by `table(name type, ..)` or `{name}_0`, `{name}_1`, etc.
+    fn ret_fields(&self) -> Vec<TokenStream2> {
+        match types::tuple_types(&self.ret) {
+            Some(types) => types
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| match self.ret_names.get(i) {
+                    Some(name) => field(name, ty),
+                    None => field(&format!("{}_{i}", self.name), ty),
+                })
+                .collect(),
+            None => vec![field(&self.name, &self.ret)],
+        }
+    }
+
+    /// Returns the `DataType` of the return value.
+    ///
+    /// A tuple return type is represented as a struct of the output columns.
+    fn ret_data_type(&self) -> TokenStream2 {
+        if types::tuple_types(&self.ret).is_some() {
+            let fields = self.ret_fields();
+            return quote! {
+                arrow_schema::DataType::Struct(arrow_schema::Fields::from(vec![#(#fields),*]))
+            };
+        }
+        data_type(&self.ret)
+    }
+
+    /// Generate code to compute `data_capacity`, the estimated output size in bytes.
+    ///
+    /// It is only generated for functions returning `varchar` or `bytea`. The size is evaluated
+    /// from `output_size_hint` if specified, where `input` is the total size of string and
+    /// binary arguments in bytes and `rows` is the number of rows. Otherwise, it is estimated
+    /// as the total size of string and binary arguments.
+    fn generate_data_capacity(&self, arrays: &[Ident]) -> Result<Option<TokenStream2>> {
+        if !types::is_bytes(&self.ret) {
+            if self.output_size_hint.is_some() {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "`output_size_hint` can only be used for functions that return `varchar` or `bytea`",
+                ));
+            }
+            return Ok(None);
+        }
+        let input_bytes = arrays
+            .iter()
+            .zip(&self.args)
+            .filter(|(_, ty)| types::is_bytes(ty) || matches!(ty.as_str(), "decimal" | "json"))
+            .map(|(array, _)| {
+                quote! {{
+                    let offsets = #array.value_offsets();
+                    (offsets[offsets.len() - 1] - offsets[0]) as usize
+                }}
+            })
+            .collect_vec();
+        let data_capacity = match &self.output_size_hint {
+            Some(hint) => {
+                let hint: TokenStream2 = hint.parse().map_err(|_| {
+                    Error::new(
+                        Span::call_site(),
+                        "invalid expression in `output_size_hint`",
+                    )
+                })?;
+                quote! {{
+                    #[allow(unused_variables)]
+                    let rows: usize = input.num_rows();
+                    #[allow(unused_variables)]
+                    let input: usize = 0 #(+ #input_bytes)*;
+                    (#hint) as usize
+                }}
+            }
+            None if input_bytes.is_empty() => quote! { 1024 },
+            None => quote! { 0 #(+ #input_bytes)* },
+        };
+        Ok(Some(quote! { let data_capacity: usize = #data_capacity; }))
+    }
+
+    /// Returns true if a function evaluating on scalar values should be generated.
+    fn has_eval_scalar(&self, user_fn: &UserFunctionAttr) -> bool {
+        let variadic = matches!(self.args.last(), Some(t) if t == "...");
+        !self.is_table_function
+            && self.batch_fn.is_none()
+            && !variadic
+            && !user_fn.context
+            && !user_fn.async_
+            && types::tuple_types(&self.ret).is_none()
+            && self.init.is_none()
+            && !self.volatile
+            && !self.args.iter().any(|ty| ty.starts_with("struct "))
+    }
+
+    /// Returns the struct type whose fields are the input columns, if `flatten_input` is set.
+    fn flatten_struct(&self) -> Option<Ident> {
+        if !self.flatten_input {
+            return None;
+        }
+        let struct_type = self.args.first()?.strip_prefix("struct ")?;
+        Some(format_ident!("{}", struct_type))
+    }
+
     /// Generate a scalar or table function.
+    ///
+    /// For scalar functions, a `{eval_fn_name}_scalar` function that evaluates on
+    /// scalar values is also generated if possible.
     fn generate_function(
         &self,
         user_fn: &UserFunctionAttr,
@@ -167,14 +585,160 @@ impl FunctionAttr {
         }
         let inputs = idents("i", &children_indices);
         let arrays = idents("a", &children_indices);
-        let arg_arrays = children_indices
+        // downcast input arrays
+        let downcast_arrays = children_indices
+            .iter()
+            .zip(&arrays)
+            .map(|(i, array)| {
+                if self.args[*i] == "any" {
+                    return quote! { let #array: &dyn Array = input.column(#i).as_ref(); };
+                }
+                if let Some(struct_type) = self.args[*i].strip_prefix("struct ") {
+                    let struct_type = format_ident!("{}", struct_type);
+                    return quote! {
+                        let #array = ::arrow_udf::codegen::downcast_struct(
+                            input.column(#i).as_ref(), &<#struct_type as ::arrow_udf::types::StructType>::fields(), #i,
+                        )?;
+                    };
+                }
+                let arg_array = format_ident!("{}", types::array_type(&self.args[*i]));
+                quote! {
+                    let #array: &#arg_array = input.column(#i).as_any().downcast_ref()
+                        .ok_or_else(|| ::arrow_udf::codegen::arrow_schema::ArrowError::CastError(
+                            format!("expect {} for the {}-th argument", stringify!(#arg_array), #i)
+                        ))?;
+                }
+            })
+            .collect_vec();
+        // get the i-th value of input arrays
+        // `any` values are passed as arrays of length 1
+        let get_inputs = inputs
             .iter()
-            .map(|i| format_ident!("{}", types::array_type(&self.args[*i])));
-        let ret_array_type = format_ident!("{}", types::array_type(&self.ret));
-        let ret_data_type = data_type(&self.ret);
+            .zip(&arrays)
+            .zip(&self.args)
+            .map(|((input, array), ty)| {
+                if ty == "any" {
+                    quote! { let #input = (!#array.is_null(i)).then(|| #array.slice(i, 1)); }
+                } else if let Some(struct_type) = ty.strip_prefix("struct ") {
+                    let struct_type = format_ident!("{}", struct_type);
+                    quote! {
+                        let #input = match #array.is_null(i) {
+                            true => None,
+                            false => <#struct_type as ::arrow_udf::types::StructValue<'_>>::from_columns(#array.columns(), i),
+                        };
+                    }
+                } else {
+                    quote! { let #input = unsafe { (!#array.is_null(i)).then(|| #array.value_unchecked(i)) }; }
+                }
+            })
+            .collect_vec();
+        let polymorphic_ret = types::is_polymorphic(&self.ret);
+        if polymorphic_ret && self.is_table_function {
+            return Err(Error::new(
+                Span::call_site(),
+                "polymorphic return type is not supported for table functions",
+            ));
+        }
+        if self.broadcast && self.batch_fn.is_none() {
+            return Err(Error::new(
+                Span::call_site(),
+                "`broadcast` is only supported for customized batch functions",
+            ));
+        }
+        if self.flatten_input && (self.flatten_struct().is_none() || self.args.len() != 1) {
+            return Err(Error::new(
+                Span::call_site(),
+                "`flatten_input` is only supported for functions with a single struct argument",
+            ));
+        }
+        if self.flatten_input && self.is_table_function {
+            return Err(Error::new(
+                Span::call_site(),
+                "`flatten_input` is not supported for table functions",
+            ));
+        }
+        if self.init.is_some() && (self.is_table_function || self.batch_fn.is_some()) {
+            return Err(Error::new(
+                Span::call_site(),
+                "`init` is not supported for table functions and customized batch functions",
+            ));
+        }
+        // the first argument of user function is the context if `init` is specified
+        let args_option = match self.init {
+            Some(_) => user_fn.args_option.get(1..).unwrap_or_default(),
+            None => &user_fn.args_option[..],
+        };
+        let ret_tuple = types::tuple_types(&self.ret);
+        if let Some(ret_types) = &ret_tuple {
+            if user_fn.tuple_option.len() != ret_types.len() {
+                return Err(Error::new(
+                    user_fn.return_type_span,
+                    format!("expect a tuple of {} elements", ret_types.len()),
+                ));
+            }
+            if user_fn.write || ret_types.iter().any(|ty| types::is_polymorphic(ty)) {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "writer and polymorphic types are not supported for multiple output columns",
+                ));
+            }
+        }
+        let ret_fields = self.ret_fields();
+        let ret_arrays = match &ret_tuple {
+            Some(types) => idents("array", &(0..types.len()).collect_vec()),
+            None => vec![format_ident!("array")],
+        };
+        // one builder for each output column of a tuple
+        let ret_builders = match &ret_tuple {
+            Some(types) => idents("builder", &(0..types.len()).collect_vec()),
+            None => vec![format_ident!("builder")],
+        };
+        // append null to all output columns
+        let append_null_output = match &ret_tuple {
+            Some(ret_types) => {
+                let append_nulls = ret_types.iter().zip(&ret_builders).map(|(ty, builder)| {
+                    let append_null = gen_append_null(ty);
+                    quote! {{
+                        let builder = &mut #builder;
+                        #append_null;
+                    }}
+                });
+                quote! { #(#append_nulls)* }
+            }
+            None => quote! { builder.append_null(); },
+        };
+        // append `v: Option<T>` to the output columns
+        let append_ret = match &ret_tuple {
+            Some(ret_types) => {
+                let append_values = ret_types.iter().enumerate().map(|(i, ty)| {
+                    let builder = &ret_builders[i];
+                    let index = syn::Index::from(i);
+                    // append `Option<T>` if the element of tuple is optional
+                    let append = match user_fn.tuple_option[i] {
+                        true => gen_append(ty),
+                        false => gen_append_value(ty),
+                    };
+                    quote! {{
+                        let builder = &mut #builder;
+                        let v = v.#index;
+                        #append;
+                    }}
+                });
+                quote! {
+                    match v {
+                        Some(v) => { #(#append_values)* }
+                        None => { #append_null_output }
+                    }
+                }
+            }
+            None => gen_append(&self.ret),
+        };
 
         let variadic_args = variadic.then(|| quote! { variadic_row, });
-        let context = user_fn.context.then(|| quote! { &self.context, });
+        let context = user_fn.context.then(|| {
+            quote! { { eval_context.set_row(i); &eval_context }, }
+        });
+        let init_context = self.init.as_ref().map(|_| quote! { ctx, });
         let writer = user_fn.write.then(|| quote! { builder, });
         let await_ = user_fn.async_.then(|| quote! { .await });
         // transform inputs for array arguments
@@ -185,11 +749,48 @@ impl FunctionAttr {
             .map(|(input, ty)| transform_input(input, ty));
         // call the user defined function
         let mut output = quote! { #user_fn_name(
+            #init_context
             #(#transformed_inputs,)*
             #variadic_args
             #context
             #writer
         ) #await_ };
+        // set the overflow behavior of arithmetic helpers
+        if let Some(overflow) = &self.overflow {
+            if self.is_table_function || user_fn.async_ {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "`overflow` is not supported for table functions and async functions",
+                ));
+            }
+            let overflow = match overflow.as_str() {
+                "wrap" => quote! { Wrap },
+                "saturate" => quote! { Saturate },
+                _ => quote! { Error },
+            };
+            output = quote! {
+                ::arrow_udf::arith::Overflow::#overflow.scope(|| #output)
+            };
+        }
+        // catch panics of each row, unless opted out.
+        // the `.await` of async functions can not be put in the closure.
+        let catch_unwind = !self.no_catch_unwind && !user_fn.async_;
+        if catch_unwind {
+            // table functions report the panic from their generator
+            let on_panic = match self.is_table_function {
+                true => quote! {{
+                    yield_!(Err(::arrow_udf::codegen::panic_error(payload, i)));
+                    return;
+                }},
+                false => quote! { return Err(::arrow_udf::codegen::panic_error(payload, i)) },
+            };
+            output = quote! {
+                match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #output)) {
+                    Ok(v) => v,
+                    Err(payload) => #on_panic,
+                }
+            };
+        }
         // handle error if the function returns `Result`
         // wrap a `Some` if the function doesn't return `Option`
         output = if self.is_table_function {
@@ -201,7 +802,7 @@ impl FunctionAttr {
                         Ok(x) => Some(x),
                         Err(e) => {
                             index_builder.append_value(i as i32);
-                            builder.append_null();
+                            #append_null_output
                             error_builder.append_value(e.to_string());
                             None
                         }
@@ -212,7 +813,7 @@ impl FunctionAttr {
                         Ok(x) => x,
                         Err(e) => {
                             index_builder.append_value(i as i32);
-                            builder.append_null();
+                            #append_null_output
                             error_builder.append_value(e.to_string());
                             None
                         }
@@ -239,16 +840,13 @@ impl FunctionAttr {
         };
         // if user function accepts non-option arguments, we assume the function
         // returns null on null input, so we need to unwrap the inputs before calling.
-        let some_inputs = inputs
-            .iter()
-            .zip(user_fn.args_option.iter())
-            .map(|(input, opt)| {
-                if *opt {
-                    quote! { #input }
-                } else {
-                    quote! { Some(#input) }
-                }
-            });
+        let some_inputs = inputs.iter().zip(args_option.iter()).map(|(input, opt)| {
+            if *opt {
+                quote! { #input }
+            } else {
+                quote! { Some(#input) }
+            }
+        });
         if !self.is_table_function && user_fn.has_error() {
             output = quote! {
                 match (#(#inputs,)*) {
@@ -265,9 +863,41 @@ impl FunctionAttr {
             };
         }
 
+        // append the `output` to the `builder`
+        let append_output = if user_fn.write {
+            if !types::is_bytes(&self.ret) {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "`&mut Write` can only be used for functions that return `varchar` or `bytea`",
+                ));
+            }
+            quote! {{
+                if #output.is_some() {
+                    builder.append_value("");
+                } else {
+                    builder.append_null();
+                }
+            }}
+        } else {
+            let append = gen_append(&self.ret);
+            quote! {{
+                let v = #output;
+                #append
+            }}
+        };
+
+        // the estimated size of string or binary output in bytes
+        let data_capacity = self.generate_data_capacity(&arrays)?;
+
         let eval = if self.is_table_function {
-            let builder = builder(&self.ret);
-            let append_output = gen_append(&self.ret);
+            let ret_types = match &ret_tuple {
+                Some(types) => types.clone(),
+                None => vec![self.ret.as_str()],
+            };
+            let new_builders = ret_types
+                .iter()
+                .map(|ty| builder(ty, &quote! { input.num_rows() }, &quote! { 1024 }));
+            let finish = ret_types.iter().map(|ty| finish_builder(ty));
             let error_append_null = user_fn
                 .has_error()
                 .then(|| quote! { error_builder.append_null(); });
@@ -297,31 +927,70 @@ impl FunctionAttr {
             let error_array = user_fn.has_error().then(|| {
                 quote! { Arc::new(error_builder.finish()) }
             });
+            // appending vectors of a wrong dimension returns an error, which aborts the call
+            let append_ret = match self.ret.contains("vector(") {
+                true => quote! {
+                    #[allow(clippy::redundant_closure_call)]
+                    let appended = (|| -> ::arrow_udf::Result<()> {
+                        #append_ret
+                        Ok(())
+                    })();
+                    if let Err(e) = appended {
+                        yield_!(Err(e));
+                        return;
+                    }
+                },
+                false => append_ret,
+            };
+            // catch panics of each step of the iterator
+            let for_each_value = match catch_unwind {
+                true => quote! {
+                    let mut iter = iter.into_iter();
+                    while let Some(v) = match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| iter.next())) {
+                        Ok(v) => v,
+                        Err(payload) => {
+                            yield_!(Err(::arrow_udf::codegen::panic_error(payload, i)));
+                            return;
+                        }
+                    }
+                },
+                false => quote! { for v in iter },
+            };
             let yield_batch = quote! {
                 let index_array = Arc::new(index_builder.finish());
-                let value_array = Arc::new(builder.finish());
-                yield_!(RecordBatch::try_new(SCHEMA.clone(), vec![index_array, value_array, #error_array]).unwrap());
+                #(let #ret_arrays: ArrayRef = {
+                    let builder = &mut #ret_builders;
+                    #finish
+                };)*
+                yield_!(Ok(RecordBatch::try_new(SCHEMA.clone(), vec![index_array, #(#ret_arrays,)* #error_array]).unwrap()));
             };
             quote! {{
                 lazy_static! {
                     static ref SCHEMA: SchemaRef = Arc::new(Schema::new(vec![
                         Field::new("row", DataType::Int32, true),
-                        Field::new(#fn_name, #ret_data_type, true),
+                        #(#ret_fields,)*
                         #error_field
                     ]));
                 }
                 let mut index_builder = Int32Builder::with_capacity(input.num_rows());
-                let mut builder = #builder;
+                #(let mut #ret_builders = #new_builders;)*
                 #let_error_builder
                 for i in 0..input.num_rows() {
-                    #(let #inputs = unsafe { (!#arrays.is_null(i)).then(|| #arrays.value_unchecked(i)) };)*
-                    let Some(iter) = (#output) else {
-                        continue;
+                    // stop producing rows once cancelled, and report it to the caller
+                    if let Err(e) = ::arrow_udf::codegen::check_cancelled(cancel_token.as_ref(), i) {
+                        yield_!(Err(e));
+                        return;
+                    }
+                    #(#get_inputs)*
+                    // not `let else`, whose body is not visited by `gen!`
+                    let iter = match #output {
+                        Some(iter) => iter,
+                        None => continue,
                     };
-                    for v in iter {
+                    #for_each_value {
                         index_builder.append_value(i as i32);
                         let v = #element;
-                        #append_output
+                        #append_ret
                         if index_builder.len() == BATCH_SIZE {
                             #yield_batch
                         }
@@ -340,18 +1009,82 @@ impl FunctionAttr {
             }
             // user defined batch function
             let fn_name = format_ident!("{}", batch_fn);
+            let broadcast = self.broadcast;
             quote! {
                 let c = #fn_name(#(#arrays),*);
-                let array = Arc::new(c);
+                let array = ::arrow_udf::codegen::check_output_len(
+                    Arc::new(c), input.num_rows(), #broadcast, #batch_fn,
+                )?;
+            }
+        } else if let Some(ret_types) = &ret_tuple {
+            // one builder for each output column
+            let new_builders = ret_types
+                .iter()
+                .map(|ty| builder(ty, &quote! { input.num_rows() }, &quote! { 1024 }));
+            let finish = ret_types.iter().map(|ty| finish_builder(ty));
+            quote! {
+                #(let mut #ret_builders = #new_builders;)*
+                let cancel_token = ::arrow_udf::codegen::cancellation_token();
+                for i in 0..input.num_rows() {
+                    ::arrow_udf::codegen::check_cancelled(cancel_token.as_ref(), i)?;
+                    #(#get_inputs)*
+                    let v = #output;
+                    #append_ret
+                }
+                #(let #ret_arrays: ArrayRef = {
+                    let builder = &mut #ret_builders;
+                    #finish
+                };)*
             }
         } else if types::is_primitive(&self.ret)
             && self.args.iter().all(|ty| types::is_primitive(ty))
             && self.args.len() <= 2
             && user_fn.is_pure()
+            && self.init.is_none()
+            && self.overflow.is_none()
             && !variadic
         {
             // SIMD optimization for primitive types
+            let ret_array_type = format_ident!("{}", types::array_type(&self.ret));
             match self.args.len() {
+                // catch panics of the whole batch, and find the panicking row by calling again
+                0 if catch_unwind => quote! {
+                    let values = (0..input.num_rows())
+                        .map(|i| ::std::panic::catch_unwind(#user_fn_name)
+                            .map_err(|payload| ::arrow_udf::codegen::panic_error(payload, i)))
+                        .collect::<Result<Vec<_>>>()?;
+                    let c = #ret_array_type::from_iter_values(values);
+                    let array = Arc::new(c);
+                },
+                1 if catch_unwind => quote! {
+                    let c: #ret_array_type = match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                        || arrow_arith::arity::unary(a0, #user_fn_name)
+                    )) {
+                        Ok(c) => c,
+                        Err(payload) => {
+                            let row = ::arrow_udf::codegen::find_panic_row(input.num_rows(), a0.nulls(), |i| {
+                                let _ = #user_fn_name(a0.value(i));
+                            });
+                            return Err(::arrow_udf::codegen::panic_error(payload, row));
+                        }
+                    };
+                    let array = Arc::new(c);
+                },
+                2 if catch_unwind => quote! {
+                    let c: #ret_array_type = match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                        || arrow_arith::arity::binary(a0, a1, #user_fn_name)
+                    )) {
+                        Ok(c) => c?,
+                        Err(payload) => {
+                            let nulls = ::arrow_udf::codegen::arrow_buffer::NullBuffer::union(a0.nulls(), a1.nulls());
+                            let row = ::arrow_udf::codegen::find_panic_row(input.num_rows(), nulls.as_ref(), |i| {
+                                let _ = #user_fn_name(a0.value(i), a1.value(i));
+                            });
+                            return Err(::arrow_udf::codegen::panic_error(payload, row));
+                        }
+                    };
+                    let array = Arc::new(c);
+                },
                 0 => quote! {
                     let c = #ret_array_type::from_iter_values(
                         std::iter::repeat_with(|| #user_fn_name()).take(input.num_rows())
@@ -369,38 +1102,80 @@ impl FunctionAttr {
                 n => todo!("SIMD optimization for {n} arguments"),
             }
         } else {
-            // no optimization
-            let builder = builder(&self.ret);
-            // append the `output` to the `builder`
-            let append_output = if user_fn.write {
-                if self.ret != "varchar" && self.ret != "bytea" {
-                    return Err(Error::new(
-                        Span::call_site(),
-                        "`&mut Write` can only be used for functions that return `varchar` or `bytea`",
-                    ));
-                }
-                quote! {{
-                    if #output.is_some() {
-                        builder.append_value("");
-                    } else {
-                        builder.append_null();
+            let builder = builder(
+                &self.ret,
+                &quote! { input.num_rows() },
+                &quote! { data_capacity },
+            );
+            let finish = finish_builder(&self.ret);
+            if num_args > 0 && !variadic && args_option[..num_args].iter().all(|opt| !opt) {
+                // the output is null if any input is null.
+                // compute the valid rows from null buffers, and only call the function on them.
+                let get_valid_inputs = inputs
+                    .iter()
+                    .zip(&arrays)
+                    .zip(&self.args)
+                    .map(|((input, array), ty)| {
+                        if ty == "any" {
+                            quote! { let #input = Some(#array.slice(i, 1)); }
+                        } else if let Some(struct_type) = ty.strip_prefix("struct ") {
+                            // null fields make the row null
+                            let struct_type = format_ident!("{}", struct_type);
+                            quote! { let #input = <#struct_type as ::arrow_udf::types::StructValue<'_>>::from_columns(#array.columns(), i); }
+                        } else {
+                            quote! { let #input = Some(unsafe { #array.value_unchecked(i) }); }
+                        }
+                    })
+                    .collect_vec();
+                let append_null = gen_append_null(&self.ret);
+                let error_append_null = user_fn
+                    .has_error()
+                    .then(|| quote! { error_builder.append_null(); });
+                quote! {
+                    #data_capacity
+                    let mut builder = #builder;
+                    let builder = &mut builder;
+                    let nulls = [#(#arrays.nulls()),*]
+                        .into_iter()
+                        .fold(None, |acc, nulls| ::arrow_udf::codegen::arrow_buffer::NullBuffer::union(acc.as_ref(), nulls));
+                    let valid_slices: Box<dyn Iterator<Item = (usize, usize)>> = match &nulls {
+                        Some(nulls) => Box::new(nulls.valid_slices()),
+                        None => Box::new(std::iter::once((0, input.num_rows()))),
+                    };
+                    let cancel_token = ::arrow_udf::codegen::cancellation_token();
+                    let mut end = 0;
+                    for (valid_start, valid_end) in valid_slices {
+                        for _ in end..valid_start {
+                            #error_append_null
+                            #append_null;
+                        }
+                        for i in valid_start..valid_end {
+                            ::arrow_udf::codegen::check_cancelled(cancel_token.as_ref(), i)?;
+                            #(#get_valid_inputs)*
+                            #append_output
+                        }
+                        end = valid_end;
                     }
-                }}
+                    for _ in end..input.num_rows() {
+                        #error_append_null
+                        #append_null;
+                    }
+                    let array = #finish;
+                }
             } else {
-                let append = gen_append(&self.ret);
-                quote! {{
-                    let v = #output;
-                    #append
-                }}
-            };
-            quote! {
-                let mut builder = #builder;
-                let builder = &mut builder;
-                for i in 0..input.num_rows() {
-                    #(let #inputs = unsafe { (!#arrays.is_null(i)).then(|| #arrays.value_unchecked(i)) };)*
-                    #append_output
+                // no optimization
+                quote! {
+                    #data_capacity
+                    let mut builder = #builder;
+                    let builder = &mut builder;
+                    let cancel_token = ::arrow_udf::codegen::cancellation_token();
+                    for i in 0..input.num_rows() {
+                        ::arrow_udf::codegen::check_cancelled(cancel_token.as_ref(), i)?;
+                        #(#get_inputs)*
+                        #append_output
+                    }
+                    let array = #finish;
                 }
-                let array = Arc::new(builder.finish());
             }
         };
 
@@ -418,32 +1193,60 @@ impl FunctionAttr {
             let error_array = user_fn.has_error().then(|| {
                 quote! { Arc::new(error_builder.finish()) }
             });
-            quote! {
-                #let_error_builder
-                #eval
+            if polymorphic_ret {
+                // the return type is inferred from the input types
+                let type_infer = self.generate_type_infer_fn()?;
+                quote! {
+                    let arg_types = input.schema().fields().iter().map(|f| f.data_type().clone()).collect::<Vec<_>>();
+                    let type_infer: fn(&[DataType]) -> Result<DataType> = #type_infer;
+                    let return_type = type_infer(&arg_types)?;
+                    #let_error_builder
+                    #eval
 
-                lazy_static! {
-                    static ref SCHEMA: SchemaRef = Arc::new(Schema::new(vec![
-                        Field::new(#fn_name, #ret_data_type, true),
+                    let schema = Arc::new(Schema::new(vec![
+                        Field::new(#fn_name, return_type, true),
                         #error_field
                     ]));
+                    Ok(RecordBatch::try_new(schema, vec![array, #error_array]).unwrap())
+                }
+            } else {
+                quote! {
+                    #let_error_builder
+                    #eval
+
+                    lazy_static! {
+                        static ref SCHEMA: SchemaRef = Arc::new(Schema::new(vec![
+                            #(#ret_fields,)*
+                            #error_field
+                        ]));
+                    }
+                    Ok(RecordBatch::try_new(SCHEMA.clone(), vec![#(#ret_arrays,)* #error_array]).unwrap())
                 }
-                Ok(RecordBatch::try_new(SCHEMA.clone(), vec![array, #error_array]).unwrap())
             }
         };
 
-        // downcast input arrays
+        // get the context before the table function is lazily evaluated
+        let let_context = user_fn
+            .context
+            .then(|| quote! { let mut eval_context = ::arrow_udf::EvalContext::current(); });
+        let let_cancel_token = self.is_table_function.then(|| {
+            quote! { let cancel_token = ::arrow_udf::codegen::cancellation_token(); }
+        });
+        // combine the input columns into the struct argument, after run-end encoded columns
+        // are decoded or evaluated per run
+        let unflatten = self.flatten_struct().map(|struct_type| {
+            quote! {
+                let input = &::arrow_udf::codegen::unflatten_struct(input, <#struct_type as ::arrow_udf::types::StructType>::fields())?;
+            }
+        });
         let downcast_arrays = quote! {
-            #(
-                let #arrays: &#arg_arrays = input.column(#children_indices).as_any().downcast_ref()
-                    .ok_or_else(|| ::arrow_udf::codegen::arrow_schema::ArrowError::CastError(
-                        format!("expect {} for the {}-th argument", stringify!(#arg_arrays), #children_indices)
-                    ))?;
-            )*
+            #let_context
+            #let_cancel_token
+            #(#downcast_arrays)*
         };
 
-        // the function body
-        let body = quote! {
+        // the imports of the function body
+        let imports = quote! {
             use ::std::sync::Arc;
             use ::arrow_udf::{Result, Error};
             use ::arrow_udf::codegen::arrow_array::RecordBatch;
@@ -456,14 +1259,81 @@ impl FunctionAttr {
             use ::arrow_udf::codegen::lazy_static::lazy_static;
             use ::arrow_udf::codegen::rust_decimal;
             use ::arrow_udf::codegen::serde_json;
-
+        };
+        let body = quote! {
+            #imports
             #eval_and_return
         };
 
+        // the function that evaluates on scalar values
+        let eval_scalar = self.has_eval_scalar(user_fn).then(|| {
+            let eval_scalar_name = format_ident!("{}_scalar", eval_fn_name);
+            let downcast_scalars = children_indices.iter().zip(&arrays).map(|(i, array)| {
+                if self.args[*i] == "any" {
+                    return quote! { let #array: &dyn Array = args[#i].get().0; };
+                }
+                let arg_array = format_ident!("{}", types::array_type(&self.args[*i]));
+                quote! {
+                    let #array: &#arg_array = args[#i].get().0.as_any().downcast_ref()
+                        .ok_or_else(|| Error::CastError(
+                            format!("expect {} for the {}-th argument", stringify!(#arg_array), #i)
+                        ))?;
+                }
+            });
+            let builder = builder(&self.ret, &quote! { 1 }, &quote! { 1024 });
+            let finish = finish_builder(&self.ret);
+            let return_type = polymorphic_ret.then(|| {
+                let type_infer = self.generate_type_infer_fn().unwrap();
+                quote! {
+                    let arg_types = args.iter().map(|v| v.get().0.data_type().clone()).collect::<Vec<_>>();
+                    let type_infer: fn(&[DataType]) -> Result<DataType> = #type_infer;
+                    let return_type = type_infer(&arg_types)?;
+                }
+            });
+            let let_error_builder = user_fn.has_error().then(|| {
+                quote! { let mut error_builder = StringBuilder::with_capacity(1, 16); }
+            });
+            let check_error = user_fn.has_error().then(|| {
+                quote! {
+                    let error = error_builder.finish();
+                    if error.is_valid(0) {
+                        return Err(Error::ComputeError(error.value(0).to_string()));
+                    }
+                }
+            });
+            quote! {
+                #[allow(dead_code)]
+                fn #eval_scalar_name(args: &[::arrow_udf::ScalarValue])
+                    -> ::arrow_udf::Result<::arrow_udf::ScalarValue>
+                {
+                    #imports
+                    use ::arrow_udf::codegen::arrow_array::{Datum, Scalar};
+
+                    if args.len() != #num_args {
+                        return Err(Error::InvalidArgumentError(format!(
+                            "expect {} arguments, got {}", #num_args, args.len()
+                        )));
+                    }
+                    #(#downcast_scalars)*
+                    #return_type
+                    #let_error_builder
+                    let mut builder = #builder;
+                    let builder = &mut builder;
+                    #[allow(unused_variables)]
+                    let i = 0;
+                    #(#get_inputs)*
+                    #append_output
+                    #check_error
+                    let array: ArrayRef = #finish;
+                    Ok(Scalar::new(array))
+                }
+            }
+        });
+
         Ok(if self.is_table_function {
             quote! {
                 fn #eval_fn_name<'a>(input: &'a ::arrow_udf::codegen::arrow_array::RecordBatch)
-                    -> ::arrow_udf::Result<Box<dyn Iterator<Item = ::arrow_udf::codegen::arrow_array::RecordBatch> + 'a>>
+                    -> ::arrow_udf::Result<Box<dyn Iterator<Item = ::arrow_udf::Result<::arrow_udf::codegen::arrow_array::RecordBatch>> + 'a>>
                 {
                     const BATCH_SIZE: usize = 1024;
                     use ::arrow_udf::codegen::genawaiter::{rc::gen, yield_};
@@ -472,22 +1342,73 @@ impl FunctionAttr {
                     Ok(Box::new(gen!({ #body }).into_iter()))
                 }
             }
+        } else if let Some(init) = &self.init {
+            // the context is created by `init` and shared by all calls of the returned function
+            let eval_init_name = format_ident!("{}_init", eval_fn_name);
+            let init = format_ident!("{}", init);
+            let finish = match &self.finish {
+                Some(finish) => {
+                    let finish = format_ident!("{}", finish);
+                    quote! { Some(#finish as fn(_)) }
+                }
+                None => quote! { None },
+            };
+            quote! {
+                fn #eval_fn_name(input: &::arrow_udf::codegen::arrow_array::RecordBatch)
+                    -> ::arrow_udf::Result<::arrow_udf::codegen::arrow_array::RecordBatch>
+                {
+                    #eval_init_name()(input)
+                }
+
+                fn #eval_init_name() -> ::arrow_udf::BoxScalarFunction {
+                    let guard = ::arrow_udf::codegen::ContextGuard::new(#init(), #finish);
+                    let eval = move |input: &::arrow_udf::codegen::arrow_array::RecordBatch|
+                        -> ::arrow_udf::Result<::arrow_udf::codegen::arrow_array::RecordBatch>
+                    {
+                        let ctx = guard.get();
+                        #unflatten
+                        #downcast_arrays
+                        #body
+                    };
+                    // the context may be stateful, so run-end encoded inputs are always decoded
+                    Box::new(move |input: &::arrow_udf::codegen::arrow_array::RecordBatch| {
+                        match ::arrow_udf::codegen::eval_run_end_encoded(input, false, &eval)? {
+                            Some(output) => Ok(output),
+                            None => eval(input),
+                        }
+                    })
+                }
+            }
         } else {
+            // volatile functions and functions taking the context may return different values
+            // for rows of the same run, so they can not be evaluated once per run
+            let per_run = !self.volatile && !user_fn.context;
             quote! {
                 fn #eval_fn_name(input: &::arrow_udf::codegen::arrow_array::RecordBatch)
                     -> ::arrow_udf::Result<::arrow_udf::codegen::arrow_array::RecordBatch>
                 {
+                    if let Some(output) = ::arrow_udf::codegen::eval_run_end_encoded(input, #per_run, &#eval_fn_name)? {
+                        return Ok(output);
+                    }
+                    #unflatten
                     #downcast_arrays
                     #body
                 }
+
+                #eval_scalar
             }
         })
     }
 }
 
 fn sig_data_type(ty: &str) -> TokenStream2 {
+    if let Some(name) = types::extension_type(ty) {
+        let storage = data_type(ty);
+        return quote! { SigDataType::Extension { name: #name.into(), storage: #storage } };
+    }
     match ty {
         "any" => quote! { SigDataType::Any },
+        "anyarray" => quote! { SigDataType::AnyList },
         _ => {
             let datatype = data_type(ty);
             quote! { SigDataType::Exact(#datatype) }
@@ -495,50 +1416,112 @@ fn sig_data_type(ty: &str) -> TokenStream2 {
     }
 }
 
+/// Returns a nullable `Field` from name and type name.
+///
+/// For extension types, the extension name is set in the field metadata.
+pub fn field(name: &str, ty: &str) -> TokenStream2 {
+    let data_type = data_type(ty);
+    match types::extension_type(ty) {
+        Some(extension) => quote! {
+            arrow_schema::Field::new(#name, #data_type, true).with_metadata(
+                [("ARROW:extension:name".into(), #extension.into())].into()
+            )
+        },
+        None => quote! { arrow_schema::Field::new(#name, #data_type, true) },
+    }
+}
+
 /// Returns a `DataType` from type name.
 pub fn data_type(ty: &str) -> TokenStream2 {
     if let Some(ty) = ty.strip_suffix("[]") {
-        let inner_type = data_type(ty);
-        return quote! { arrow_schema::DataType::List(Arc::new(arrow_schema::Field::new("item", #inner_type, true))) };
+        let item = field("item", ty);
+        return quote! { arrow_schema::DataType::List(Arc::new(#item)) };
     }
     if let Some(s) = ty.strip_prefix("struct ") {
         let struct_type = format_ident!("{}", s);
         return quote! { arrow_schema::DataType::Struct(#struct_type::fields()) };
     }
+    if let Some(n) = types::fixed_size(ty) {
+        return quote! { arrow_schema::DataType::FixedSizeBinary(#n) };
+    }
+    if let Some(n) = types::vector_size(ty) {
+        let item = field("item", "float4");
+        return quote! { arrow_schema::DataType::FixedSizeList(Arc::new(#item), #n) };
+    }
     let variant: TokenStream2 = types::data_type(ty).parse().unwrap();
     quote! { arrow_schema::DataType::#variant }
 }
 
-/// Generate a builder for the given type.
-fn builder(ty: &str) -> TokenStream2 {
+/// Generate a builder for the given type with `capacity` rows.
+///
+/// For `varchar` and `bytea`, `data_capacity` is the capacity of the value data in bytes.
+fn builder(ty: &str, capacity: &TokenStream2, data_capacity: &TokenStream2) -> TokenStream2 {
     match ty {
         "varchar" => {
-            quote! { StringBuilder::with_capacity(input.num_rows(), 1024) }
+            quote! { StringBuilder::with_capacity(#capacity, #data_capacity) }
         }
         "bytea" => {
-            quote! { BinaryBuilder::with_capacity(input.num_rows(), 1024) }
+            quote! { BinaryBuilder::with_capacity(#capacity, #data_capacity) }
+        }
+        "large_varchar" => {
+            quote! { LargeStringBuilder::with_capacity(#capacity, #data_capacity) }
+        }
+        "large_bytea" => {
+            quote! { LargeBinaryBuilder::with_capacity(#capacity, #data_capacity) }
         }
         "decimal" => {
-            quote! { LargeBinaryBuilder::with_capacity(input.num_rows(), input.num_rows() * 8) }
+            quote! { StringBuilder::with_capacity(#capacity, #capacity * 8) }
         }
         "json" => {
-            quote! { LargeStringBuilder::with_capacity(input.num_rows(), input.num_rows() * 8) }
+            quote! { StringBuilder::with_capacity(#capacity, #capacity * 8) }
+        }
+        "geometry" => {
+            quote! { BinaryBuilder::with_capacity(#capacity, #capacity * 32) }
+        }
+        "any" | "anyarray" => {
+            quote! { Vec::<Option<ArrayRef>>::with_capacity(#capacity) }
         }
         s if s.ends_with("[]") => {
-            let values_builder = builder(ty.strip_suffix("[]").unwrap());
-            quote! { ListBuilder::<Box<dyn ArrayBuilder>>::with_capacity(Box::new(#values_builder), input.num_rows()) }
+            let values_builder =
+                builder(ty.strip_suffix("[]").unwrap(), capacity, &quote! { 1024 });
+            quote! { ListBuilder::<Box<dyn ArrayBuilder>>::with_capacity(Box::new(#values_builder), #capacity) }
         }
         s if s.starts_with("struct ") => {
             let struct_ident = format_ident!("{}", &s[7..]);
-            quote! { StructBuilder::from_fields(#struct_ident::fields(), input.num_rows()) }
+            quote! { StructBuilder::from_fields(#struct_ident::fields(), #capacity) }
+        }
+        s if types::fixed_size(s).is_some() => {
+            let byte_width = types::fixed_size(s).unwrap();
+            quote! { FixedSizeBinaryBuilder::with_capacity(#capacity, #byte_width) }
+        }
+        s if types::vector_size(s).is_some() => {
+            let n = types::vector_size(s).unwrap();
+            quote! {
+                FixedSizeListBuilder::with_capacity(
+                    Float32Builder::with_capacity(#capacity * #n as usize),
+                    #n,
+                    #capacity,
+                )
+            }
         }
         _ => {
             let builder_type = format_ident!("{}", types::array_builder_type(ty));
-            quote! { #builder_type::with_capacity(input.num_rows()) }
+            quote! { #builder_type::with_capacity(#capacity) }
         }
     }
 }
 
+/// Generate code to finish the `builder` into an `ArrayRef`.
+///
+/// For polymorphic types, the `return_type` is required.
+fn finish_builder(ty: &str) -> TokenStream2 {
+    match ty {
+        "any" => quote! { ::arrow_udf::codegen::concat_scalars(&return_type, builder)? },
+        "anyarray" => quote! { ::arrow_udf::codegen::concat_lists(&return_type, builder)? },
+        _ => quote! { Arc::new(builder.finish()) },
+    }
+}
+
 /// Return the builder type for the given type.
 ///
 /// This should be consistent with `StructBuilder::from_fields`.
@@ -576,28 +1559,55 @@ pub fn gen_append_value(ty: &str) -> TokenStream2 {
         quote! {{
             v.append_to(builder);
         }}
+    } else if types::is_polymorphic(ty) {
+        quote! { builder.push(Some(v)) }
     } else if ty == "json" {
         quote! {{
-            // builder: LargeStringBuilder
+            // builder: StringBuilder
             use std::fmt::Write;
             write!(builder, "{}", v).expect("write json");
             builder.append_value("");
         }}
     } else if ty == "decimal" {
         quote! { builder.append_value(v.to_string()) }
+    } else if ty == "geometry" {
+        quote! {{
+            let v: ::arrow_udf::geo::Geometry = v.into();
+            builder.append_value(::arrow_udf::geo::to_wkb(&v))
+        }}
     } else if ty == "date" {
-        quote! { builder.append_value(arrow_array::types::Date32Type::from_naive_date(v)) }
+        quote! { builder.append_value(::arrow_udf::codegen::arrow_array::types::Date32Type::from_naive_date(v)) }
     } else if ty == "time" {
-        quote! { builder.append_value(arrow_array::temporal_conversions::time_to_time64us(v)) }
+        quote! { builder.append_value(::arrow_udf::codegen::arrow_array::temporal_conversions::time_to_time64us(v)) }
     } else if ty == "timestamp" {
         quote! { builder.append_value(v.timestamp_micros()) }
     } else if ty == "interval" {
         quote! { builder.append_value({
             let v: arrow_udf::types::Interval = v.into();
-            arrow_array::types::IntervalMonthDayNanoType::make_value(v.months, v.days, v.nanos)
+            ::arrow_udf::codegen::arrow_array::types::IntervalMonthDayNanoType::make_value(v.months, v.days, v.nanos)
         }) }
     } else if ty == "void" {
         quote! { builder.append_empty_value() }
+    } else if let Some(n) = types::fixed_size(ty) {
+        // the length is checked at compile time
+        let n = n as usize;
+        quote! {{
+            let v: [u8; #n] = v;
+            builder.append_value(v).expect("fixed size binary");
+        }}
+    } else if let Some(n) = types::vector_size(ty) {
+        let n = n as usize;
+        quote! {{
+            // the enclosing function or closure returns the error, with `i` as the current row
+            let v: &[f32] = v.as_ref();
+            if v.len() != #n {
+                return Err(::arrow_udf::Error::InvalidArgumentError(format!(
+                    "expect a vector of dimension {} in row {}, got {}", #n, i, v.len()
+                )));
+            }
+            builder.values().append_slice(v);
+            builder.append(true);
+        }}
     } else {
         quote! { builder.append_value(v) }
     }
@@ -608,6 +1618,15 @@ pub fn gen_append_null(ty: &str) -> TokenStream2 {
     if let Some(s) = ty.strip_prefix("struct ") {
         let struct_type = format_ident!("{}", s);
         quote! { #struct_type::append_null(builder) }
+    } else if types::is_polymorphic(ty) {
+        quote! { builder.push(None) }
+    } else if let Some(n) = types::vector_size(ty) {
+        // the values of null vectors are also appended
+        let n = n as usize;
+        quote! {{
+            builder.values().append_nulls(#n);
+            builder.append(false);
+        }}
     } else {
         quote! { builder.append_null() }
     }
@@ -623,6 +1642,8 @@ pub fn gen_append_null(ty: &str) -> TokenStream2 {
 /// | `interval`  | `i128`           | `arrow_udf::types::Interval` |
 /// | `decimal`   | `&str`           | `rust_decimal::Decimal`      |
 /// | `json`      | `&str`           | `serde_json::Value`          |
+/// | `geometry`  | `&[u8]`          | `geo_types::Geometry`        |
+/// | `vector(n)` | `ArrayRef`       | `&[f32]`                     |
 /// | `smallint[]`| `ArrayRef`       | `&[i16]`                     |
 /// | `int[]`     | `ArrayRef`       | `&[i32]`                     |
 /// | `bigint[]`  | `ArrayRef`       | `&[i64]`                     |
@@ -630,36 +1651,50 @@ pub fn gen_append_null(ty: &str) -> TokenStream2 {
 /// | `float[]`   | `ArrayRef`       | `&[f64]`                     |
 /// | `varchar[]` | `ArrayRef`       | `arrow::array::StringArray`  |
 /// | `bytea[]`   | `ArrayRef`       | `arrow::array::BinaryArray`  |
-fn transform_input(input: &Ident, ty: &str) -> TokenStream2 {
+pub fn transform_input(input: &Ident, ty: &str) -> TokenStream2 {
     if ty == "decimal" {
-        return quote! { std::str::from_utf8(#input).expect("invalid utf8 for decimal").parse::<rust_decimal::Decimal>().expect("invalid decimal") };
+        return quote! { #input.parse::<rust_decimal::Decimal>().expect("invalid decimal") };
     } else if ty == "date" {
-        return quote! { arrow_array::types::Date32Type::to_naive_date(#input) };
+        return quote! { ::arrow_udf::codegen::arrow_array::types::Date32Type::to_naive_date(#input) };
     } else if ty == "time" {
-        return quote! { arrow_array::temporal_conversions::as_time::<arrow_array::types::Time64MicrosecondType>(#input).expect("invalid time") };
+        return quote! { ::arrow_udf::codegen::arrow_array::temporal_conversions::as_time::<::arrow_udf::codegen::arrow_array::types::Time64MicrosecondType>(#input).expect("invalid time") };
     } else if ty == "timestamp" {
-        return quote! { arrow_array::temporal_conversions::as_datetime::<arrow_array::types::TimestampMicrosecondType>(#input).expect("invalid timestamp") };
+        return quote! { ::arrow_udf::codegen::arrow_array::temporal_conversions::as_datetime::<::arrow_udf::codegen::arrow_array::types::TimestampMicrosecondType>(#input).expect("invalid timestamp") };
     } else if ty == "interval" {
         return quote! {{
-            let (months, days, nanos) = arrow_array::types::IntervalMonthDayNanoType::to_parts(#input);
+            let (months, days, nanos) = ::arrow_udf::codegen::arrow_array::types::IntervalMonthDayNanoType::to_parts(#input);
             arrow_udf::types::Interval { months, days, nanos }
         }};
     } else if ty == "json" {
         return quote! { #input.parse::<serde_json::Value>().expect("invalid json") };
+    } else if ty == "geometry" {
+        return quote! { ::arrow_udf::geo::from_wkb(#input).expect("invalid wkb") };
+    } else if types::vector_size(ty).is_some() {
+        return quote! {
+            #input.as_any().downcast_ref::<::arrow_udf::codegen::arrow_array::Float32Array>().expect("float32 array").values().as_ref()
+        };
     } else if let Some(elem_type) = ty.strip_suffix("[]") {
         if types::is_primitive(elem_type) {
             let array_type = format_ident!("{}", types::array_type(elem_type));
             return quote! {{
-                let primitive_array: &#array_type = #input.as_primitive();
+                let primitive_array = #input.as_any().downcast_ref::<::arrow_udf::codegen::arrow_array::#array_type>().expect("primitive array");
                 primitive_array.values().as_ref()
             }};
         } else if elem_type == "varchar" {
             return quote! {
-                #input.as_any().downcast_ref::<arrow_array::StringArray>().expect("string array")
+                #input.as_any().downcast_ref::<::arrow_udf::codegen::arrow_array::StringArray>().expect("string array")
             };
         } else if elem_type == "bytea" {
             return quote! {
-                #input.as_any().downcast_ref::<arrow_array::BinaryArray>().expect("binary array")
+                #input.as_any().downcast_ref::<::arrow_udf::codegen::arrow_array::BinaryArray>().expect("binary array")
+            };
+        } else if elem_type == "large_varchar" {
+            return quote! {
+                #input.as_any().downcast_ref::<::arrow_udf::codegen::arrow_array::LargeStringArray>().expect("large string array")
+            };
+        } else if elem_type == "large_bytea" {
+            return quote! {
+                #input.as_any().downcast_ref::<::arrow_udf::codegen::arrow_array::LargeBinaryArray>().expect("large binary array")
             };
         } else {
             return quote! { #input };
@@ -668,6 +1703,18 @@ fn transform_input(input: &Ident, ty: &str) -> TokenStream2 {
     quote! { #input }
 }
 
+/// Returns the attribute to put on exported symbols.
+///
+/// Symbols are always exported on WebAssembly. On other targets, they are exported only if the
+/// `ffi` feature is enabled, so that native crates can opt out of the exports.
+pub fn ffi_cfg() -> TokenStream2 {
+    if cfg!(feature = "ffi") {
+        quote! {}
+    } else {
+        quote! { #[cfg(target_arch = "wasm32")] }
+    }
+}
+
 /// Encode a string to a symbol name using customized base64.
 pub fn base64_encode(input: &str) -> String {
     use base64::{
@@ -62,8 +62,12 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 ///     - [Return Value](#return-value)
 ///     - [Optimization](#optimization)
 ///     - [Functions Returning Strings](#functions-returning-strings)
+///     - [Multiple Output Columns](#multiple-output-columns)
+///     - [Initialization and Cleanup](#initialization-and-cleanup)
+///     - [Evaluation Context](#evaluation-context)
 /// - [Table Function](#table-function)
 /// - [Registration and Invocation](#registration-and-invocation)
+///     - [Evaluating on Scalar Values](#evaluating-on-scalar-values)
 /// - [Appendix: Type Matrix](#appendix-type-matrix)
 ///
 /// The following example demonstrates a simple usage:
@@ -81,7 +85,7 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// invocation. The signature follows this pattern:
 ///
 /// ```text
-/// name ( [arg_types],* [...] ) [ -> [setof] return_type ]
+/// name ( [arg_types],* [...] ) [ -> [setof] return_type | -> table ( [column_name type],* ) ]
 /// ```
 ///
 /// Where `name` is the function name.
@@ -96,6 +100,42 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 ///
 /// If no return type is specified, the function returns `void`.
 ///
+/// Type names and keywords are case-insensitive, and words can be separated by any whitespace,
+/// e.g. `INT`, `Double  Precision` and `SETOF`. A malformed signature or an unknown type is
+/// reported as a compile error pointing to the offset of the bad token.
+///
+/// ## Schemas and Aliases
+///
+/// The function name can be qualified by a schema like `my_schema.levenshtein`, and the function
+/// is registered in that schema. Use the `alias` option to register the same function under other
+/// names, separated by commas. Aliases are qualified by their own schema if any:
+///
+/// ```ignore
+/// #[function("my_schema.levenshtein(varchar, varchar) -> int", alias = "lev, my_schema.lev")]
+/// fn levenshtein(a: &str, b: &str) -> i32 {
+///     ...
+/// }
+/// ```
+///
+/// This registers `my_schema.levenshtein`, `lev` and `my_schema.lev`.
+/// The output column is named after the unqualified function name.
+///
+/// ## Versions
+///
+/// Multiple versions of a function with the same signature can be registered side by side with
+/// the `version` option. Functions without the option are version 1.
+///
+/// ```ignore
+/// #[function("greet(varchar) -> varchar")]
+/// fn greet(name: &str) -> String { .. }
+///
+/// #[function("greet(varchar) -> varchar", version = "2")]
+/// fn greet_v2(name: &str) -> String { .. }
+/// ```
+///
+/// The registry returns the latest version by default, or a pinned one by `get_version`.
+/// The name of the generated function includes the version, like `greet_v2_varchar_varchar_eval`.
+///
 /// ## Multiple Function Definitions
 ///
 /// Multiple `#[function]` macros can be applied to a single generic Rust function to define
@@ -110,6 +150,54 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// }
 /// ```
 ///
+/// ## Generic Signatures
+///
+/// The above can be written in a single line with a type set `{...}`:
+///
+/// ```ignore
+/// #[function("add({int2, int4, int8}, {int2, int4, int8}) -> {int2, int4, int8}")]
+/// fn add<T: Add>(x: T, y: T) -> T {
+///     x + y
+/// }
+/// ```
+///
+/// All occurrences of the same type set in a signature are instantiated with the same type,
+/// so this expands to `add(int2, int2) -> int2`, `add(int4, int4) -> int4` and
+/// `add(int8, int8) -> int8`. Different type sets are expanded independently.
+///
+/// `*numeric` is a shorthand for `{int2, int4, int8, float4, float8}`:
+///
+/// ```ignore
+/// #[function("neg(*numeric) -> *numeric")]
+/// #[function("array_sum(*numeric[]) -> *numeric")]
+/// ```
+///
+/// ## Polymorphic Functions
+///
+/// Functions that work on values of any type can use the polymorphic types `any` and `anyarray`.
+/// The values are passed to the Rust function as arrays. See the [type matrix] for details.
+///
+/// ```ignore
+/// #[function("array_first(anyarray) -> any")]
+/// fn array_first(array: ArrayRef) -> Option<ArrayRef> {
+///     (!array.is_empty()).then(|| array.slice(0, 1))
+/// }
+/// ```
+///
+/// The actual return type is inferred from the argument types when the function is called.
+/// By default, `any` is inferred as the type of the first `any` argument, or the item type of
+/// the first `anyarray` argument, and `anyarray` the other way around. Otherwise, a type
+/// inference function `fn(&[DataType]) -> Result<DataType>` must be provided:
+///
+/// ```ignore
+/// #[function("array_get(anyarray, int) -> any", type_infer = "array_get_type")]
+/// fn array_get(array: ArrayRef, i: i32) -> Option<ArrayRef> { ... }
+///
+/// fn array_get_type(args: &[DataType]) -> arrow_udf::Result<DataType> { ... }
+/// ```
+///
+/// Table functions can not return polymorphic types.
+///
 /// # Rust Function Signature
 ///
 /// The `#[function]` macro can handle various types of Rust functions.
@@ -135,6 +223,34 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// - `Result<T>`: Indicates that an error may occur, but a null value will not be returned.
 /// - `Result<Option<T>>`: Indicates that a null value may be returned, and an error may also occur.
 ///
+/// ## Integer Overflow
+///
+/// The arithmetic helpers in [`arrow_udf::arith`] return an error on integer overflow by
+/// default. The `overflow` option sets the behavior for a function to `"error"`, `"wrap"` or
+/// `"saturate"`. Without it, the behavior is taken from the `EvalContext` of the engine:
+///
+/// ```ignore
+/// #[function("add(int, int) -> int", overflow = "wrap")]
+/// fn add(x: i32, y: i32) -> Result<i32> {
+///     arrow_udf::arith::add(x, y)
+/// }
+/// ```
+///
+/// ## Panics
+///
+/// Panics of the function are caught for each row and returned as an error of the whole batch,
+/// which wraps an [`arrow_udf::PanicError`] with the panic message and the row index. This
+/// requires `panic = "unwind"`, the default panic strategy. Table functions yield the error and
+/// stop. Panics of async functions and customized batch functions are not caught.
+///
+/// Catching panics may prevent some optimizations of the compiler. If the function never panics,
+/// use `no_catch_unwind` to opt out:
+///
+/// ```ignore
+/// #[function("add(int, int) -> int", no_catch_unwind)]
+/// fn add(x: i32, y: i32) -> i32 {...}
+/// ```
+///
 /// ## Optimization
 ///
 /// When all input and output types of the function are *primitive type* (int2, int4, int8, float4, float8)
@@ -143,6 +259,9 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 ///
 /// Therefore, try to avoid returning `Option` and `Result` whenever possible.
 ///
+/// Otherwise, if no argument is `Option`, the macro computes the valid rows from the null
+/// buffers of the inputs in advance, and only calls the function on runs of valid rows.
+///
 /// ## Functions Returning Strings
 ///
 /// For functions that return varchar types, you can also use the writer style function signature to
@@ -179,6 +298,172 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// }
 /// ```
 ///
+/// The buffer of output strings is pre-allocated with the total size of string and binary
+/// arguments. If the output size differs a lot from that, you can give a hint by
+/// `output_size_hint`, an expression of `input` (the total size of string and binary arguments
+/// in bytes) and `rows` (the number of rows):
+///
+/// ```ignore
+/// #[function("repeat(varchar, int) -> varchar", output_size_hint = "input * 4")]
+/// fn repeat(s: &str, n: i32, writer: &mut impl Write) { ... }
+///
+/// #[function("to_string(int) -> varchar", output_size_hint = "rows * 11")]
+/// fn to_string(x: i32, writer: &mut impl Write) { ... }
+/// ```
+///
+/// ## Batch Functions
+///
+/// A function can be evaluated on whole arrays by `batch_fn`, which takes the input arrays and
+/// returns the output array:
+///
+/// ```ignore
+/// #[function("add(int, int) -> int", batch_fn = "add_batch")]
+/// fn add(a: i32, b: i32) -> i32 { a + b }
+///
+/// fn add_batch(a: &Int32Array, b: &Int32Array) -> Int32Array {
+///     arrow_arith::arity::binary(a, b, |a, b| a + b).unwrap()
+/// }
+/// ```
+///
+/// An error is returned if the output length differs from the number of input rows.
+/// With `broadcast`, an output of length 1 is repeated for all rows, which is useful when the
+/// result does not depend on the rows.
+///
+/// ## Multiple Output Columns
+///
+/// A function can return multiple columns instead of a struct by declaring a tuple as the
+/// return type. The Rust function should return a tuple of the same length:
+///
+/// ```ignore
+/// #[function("split_host_port(varchar) -> (varchar, int)")]
+/// fn split_host_port(addr: &str) -> Option<(&str, Option<i32>)> {
+///     let (host, port) = addr.rsplit_once(':')?;
+///     Some((host, port.parse().ok()))
+/// }
+/// ```
+///
+/// The output batch has one column for each element, named `split_host_port_0`,
+/// `split_host_port_1`, etc. If the tuple is `None`, all columns are null in that row,
+/// while an element of `Option<T>` makes only its own column null. In the function registry,
+/// the return type is a struct of these columns.
+///
+/// Multiple output columns are not supported for writer style functions and polymorphic types.
+/// See the section on table functions for named output columns.
+///
+/// ## Struct Arguments
+///
+/// A struct type derived by `#[derive(StructType)]` can be an argument, if no field is a list,
+/// a struct or a fixed-size type. The row is null if the struct or any field that is not
+/// `Option<T>` is null.
+///
+/// With `flatten_input`, a function taking a single struct is called with the fields of the
+/// struct as separate columns, so engines that decompose structs don't need to build a struct
+/// array. The argument types in the function registry are the types of the fields:
+///
+/// ```ignore
+/// #[derive(StructType)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// // called with columns `x: float8, y: float8`
+/// #[function("dist(struct Point) -> float8", flatten_input)]
+/// fn dist(p: Point) -> f64 {
+///     p.x.hypot(p.y)
+/// }
+/// ```
+///
+/// ## Initialization and Cleanup
+///
+/// If a function needs an expensive resource, such as a cache, a compiled pattern or a
+/// connection handle, it can be created once by an `init` function and passed by reference
+/// to each row as the first argument:
+///
+/// ```ignore
+/// fn compile() -> Regex {
+///     Regex::new(r"\d+").unwrap()
+/// }
+///
+/// #[function("count_numbers(varchar) -> int", init = "compile")]
+/// fn count_numbers(re: &Regex, s: &str) -> i32 {
+///     re.find_iter(s).count() as i32
+/// }
+/// ```
+///
+/// The macro generates an `{output}_init` function of type `arrow_udf::ScalarFunctionInit`.
+/// It creates the context and returns a boxed function that shares the context in all calls.
+/// Call it once per query or stream, and drop the returned function when the query ends.
+/// The context is dropped along with it, or passed to the `finish` function if specified:
+///
+/// ```ignore
+/// #[function("lookup(int) -> varchar", init = "connect", finish = "disconnect")]
+/// fn lookup(conn: &Connection, id: i32) -> Option<String> { ... }
+///
+/// fn disconnect(conn: Connection) { ... }
+///
+/// let lookup = lookup_eval_init();
+/// for batch in batches {
+///     let output = lookup(&batch)?;
+/// }
+/// drop(lookup); // calls `disconnect`
+/// ```
+///
+/// The context must be `Send`. The `{output}` function is still generated, but it creates a new
+/// context for each call. `init` is not supported for table functions and customized batch
+/// functions.
+///
+/// ## Fallback
+///
+/// A scalar function can fall back to another function with the same signature when it fails,
+/// for example, a remote function falling back to a local approximation, or a function mapping
+/// errors to a default value. The fallback is the name of a batch function like the `output`
+/// of another `#[function]`:
+///
+/// ```ignore
+/// #[function("geoip(varchar) -> varchar", fallback = "eval_geoip_cached", max_failures = "5")]
+/// fn geoip(addr: &str) -> Result<String> { ... }
+///
+/// #[function("geoip_cached(varchar) -> varchar", output = "eval_geoip_cached")]
+/// fn geoip_cached(addr: &str) -> Option<String> { ... }
+/// ```
+///
+/// The fallback is registered in `FunctionSignature::fallback`, and
+/// `FunctionSignature::fallback_function` returns a function that evaluates the fallback on
+/// failed batches, and only the fallback after `max_failures` (3 by default) consecutive failures.
+/// The generated functions are not affected. Fallbacks are not supported for table functions.
+///
+/// ## Evaluation Context
+///
+/// Functions relying on time or randomness can take `&arrow_udf::EvalContext` as an argument
+/// after the SQL arguments, to get a fixed query timestamp and a seedable random number generator:
+///
+/// ```ignore
+/// #[function("random_int(int) -> int")]
+/// fn random_int(max: i32, ctx: &EvalContext) -> i32 {
+///     ctx.rng().gen_range(0..max)
+/// }
+///
+/// let ctx = EvalContext::new(now, seed);
+/// let output = ctx.scope(|| random_int_int4_int4_eval(&input))?;
+/// ```
+///
+/// The context is taken from `EvalContext::scope` when the function is called.
+/// If not set, a new context is created from the system time and a random seed.
+///
+/// The context also carries the index of the current row, a batch id and string metadata set by
+/// the caller, which is useful for logging and auditing:
+///
+/// ```ignore
+/// #[function("audit(int) -> varchar")]
+/// fn audit(x: i32, ctx: &EvalContext) -> String {
+///     format!("{}:{}:{}", ctx.metadata("query_id").unwrap_or_default(), ctx.batch_id(), ctx.row())
+/// }
+///
+/// let ctx = EvalContext::default().with_metadata("query_id", "q1").with_batch_id(7);
+/// let output = ctx.scope(|| audit_int4_varchar_eval(&input))?;
+/// ```
+///
 /// # Table Function
 ///
 /// A table function is a special kind of function that can return multiple values instead of just
@@ -187,7 +472,7 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 ///
 /// For example:
 /// ```ignore
-/// #[function("generate_series(int32, int32) -> setof int32")]
+/// #[function("generate_series(int4, int4) -> setof int4")]
 /// fn generate_series(start: i32, stop: i32) -> impl Iterator<Item = i32> {
 ///     start..=stop
 /// }
@@ -200,6 +485,30 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// - `Result<impl Iterator<Item = T>>`
 /// - `Result<impl Iterator<Item = Result<Option<T>>>>`
 ///
+/// The generated function returns an iterator of `Result<RecordBatch>`. Errors of rows are
+/// written to the `error` column, while an `Err` item means the evaluation was aborted, for
+/// example by cancellation, and no more batches follow.
+///
+/// ## Named Output Columns
+///
+/// A table function can declare its output columns by `table(name type, ..)`. The iterator yields
+/// tuples of the same length, and the output batch has a column for each element after the `row`
+/// column, instead of a single struct column:
+///
+/// ```ignore
+/// #[function("word_count(varchar) -> table(word varchar, count int)")]
+/// fn word_count(s: &str) -> impl Iterator<Item = (&str, i32)> {
+///     let mut counts = std::collections::BTreeMap::new();
+///     for word in s.split_whitespace() {
+///         *counts.entry(word).or_insert(0) += 1;
+///     }
+///     counts.into_iter()
+/// }
+/// ```
+///
+/// The output schema is `(row int32, word varchar, count int32)`. Elements of `Option<T>` can be
+/// null. In the function registry, the return type is a struct of these columns.
+///
 /// # Registration and Invocation
 ///
 /// Every function defined by `#[function]` is automatically registered in the global function registry.
@@ -213,6 +522,25 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// let sig = REGISTRY.get("add", &[Int32, Int32], &Int32).unwrap();
 /// ```
 ///
+/// Functions in a schema are looked up by the qualified name like `my_schema.levenshtein`.
+///
+/// ## Evaluating on Scalar Values
+///
+/// For scalar functions, the macro also generates a function that evaluates on
+/// `arrow_udf::ScalarValue`s, so that the function can be called on constant
+/// arguments without building record batches. It is available as
+/// `FunctionSignature::eval_scalar`, or `{output}_scalar` if the name of the output function is
+/// specified.
+///
+/// ```ignore
+/// let scalar = |v: i32| Scalar::new(Arc::new(Int32Array::from(vec![v])) as ArrayRef);
+/// let eval_scalar = sig.eval_scalar.unwrap();
+/// let output = eval_scalar(&[scalar(1), scalar(2)])?;
+/// ```
+///
+/// It is not generated for variadic functions, functions with a customized batch function,
+/// functions with multiple output columns, functions with an `init` hook and volatile functions.
+///
 /// # Appendix: Type Matrix
 ///
 /// ## Base Types
@@ -223,6 +551,7 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// | `smallint`           | `i16`                          | `i16`                          |
 /// | `integer`            | `i32`                          | `i32`                          |
 /// | `bigint`             | `i64`                          | `i64`                          |
+/// | `half`               | [`half::f16`]                  | [`half::f16`]                  |
 /// | `real`               | `f32`                          | `f32`                          |
 /// | `double precision`   | `f64`                          | `f64`                          |
 /// | `decimal`            | [`rust_decimal::Decimal`]      | [`rust_decimal::Decimal`]      |
@@ -232,8 +561,22 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// | `timestamptz`        | not supported yet              | not supported yet              |
 /// | `interval`           | [`arrow_udf::types::Interval`] | [`arrow_udf::types::Interval`] |
 /// | `json`               | [`serde_json::Value`]          | [`serde_json::Value`]          |
+/// | `geometry`           | [`geo_types::Geometry`]        | `impl Into<geo_types::Geometry>` |
 /// | `varchar`            | `&str`                         | `impl AsRef<str>`, e.g. `String`, `Box<str>`, `&str`     |
 /// | `bytea`              | `&[u8]`                        | `impl AsRef<[u8]>`, e.g. `Vec<u8>`, `Box<[u8]>`, `&[u8]` |
+/// | `large_varchar`      | `&str`                         | `impl AsRef<str>`, e.g. `String`, `Box<str>`, `&str`     |
+/// | `large_bytea`        | `&[u8]`                        | `impl AsRef<[u8]>`, e.g. `Vec<u8>`, `Box<[u8]>`, `&[u8]` |
+/// | `bytea(N)`           | `&[u8]` of length `N`          | `[u8; N]`                      |
+/// | `vector(N)`          | `&[f32]` of length `N`         | `impl AsRef<[f32]>` of length `N`, e.g. `Vec<f32>`, `[f32; N]` |
+///
+/// `vector(N)` is an embedding stored in a `FixedSizeList(Float32, N)` array. A return value of
+/// a different length fails the evaluation with an `InvalidArgumentError`.
+///
+/// `geometry` is stored as WKB with the GeoArrow extension type `geoarrow.wkb`, and requires
+/// the `geo` feature of `arrow-udf`.
+///
+/// `large_varchar` and `large_bytea` (alias `large_string` and `large_binary`) are stored in
+/// `LargeUtf8` and `LargeBinary` arrays with 64-bit offsets, for columns larger than 2GB.
 ///
 /// ## Array Types
 ///
@@ -242,10 +585,13 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// | `smallint[]`         | `&[i16]`                       | `impl Iterator<Item = i16>`    |
 /// | `integer[]`          | `&[i32]`                       | `impl Iterator<Item = i32>`    |
 /// | `bigint[]`           | `&[i64]`                       | `impl Iterator<Item = i64>`    |
+/// | `half[]`             | `&[f16]`                       | `impl Iterator<Item = f16>`    |
 /// | `real[]`             | `&[f32]`                       | `impl Iterator<Item = f32>`    |
 /// | `double precision[]` | `&[f64]`                       | `impl Iterator<Item = f64>`    |
 /// | `varchar[]`          | [`&arrow::array::StringArray`] | `impl Iterator<Item = &str>`   |
 /// | `bytea[]`            | [`&arrow::array::BinaryArray`] | `impl Iterator<Item = &[u8]>`  |
+/// | `large_varchar[]`    | [`&arrow::array::LargeStringArray`] | `impl Iterator<Item = &str>` |
+/// | `large_bytea[]`      | [`&arrow::array::LargeBinaryArray`] | `impl Iterator<Item = &[u8]>` |
 /// | `others[]`           | not supported yet              | not supported yet              |
 ///
 /// ## Composite Types
@@ -254,15 +600,25 @@ pub fn struct_type(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// | -------------------- | ------------------------------ | ------------------------------ |
 /// | `struct<..>`         | `UserDefinedStruct`            | `UserDefinedStruct`            |
 ///
+/// ## Polymorphic Types
+///
+/// | SQL type             | Rust type as argument          | Rust type as return value      |
+/// | -------------------- | ------------------------------ | ------------------------------ |
+/// | `any`, `anyelement`  | [`ArrayRef`] of length 1       | [`ArrayRef`] of length 1       |
+/// | `anyarray`           | [`ArrayRef`] of list items     | [`ArrayRef`] of list items     |
+///
 /// [type matrix]: #appendix-type-matrix
+/// [`half::f16`]: https://docs.rs/half/2.3.1/half/struct.f16.html
 /// [`rust_decimal::Decimal`]: https://docs.rs/rust_decimal/1.33.1/rust_decimal/struct.Decimal.html
 /// [`chrono::NaiveDate`]: https://docs.rs/chrono/0.4.31/chrono/naive/struct.NaiveDate.html
 /// [`chrono::NaiveTime`]: https://docs.rs/chrono/0.4.31/chrono/naive/struct.NaiveTime.html
 /// [`chrono::NaiveDateTime`]: https://docs.rs/chrono/0.4.31/chrono/naive/struct.NaiveDateTime.html
 /// [`arrow_udf::types::Interval`]: https://docs.rs/arrow_udf/0.1.0/arrow_udf/types/struct.Interval.html
 /// [`serde_json::Value`]: https://docs.rs/serde_json/1.0.108/serde_json/enum.Value.html
+/// [`geo_types::Geometry`]: https://docs.rs/geo-types/0.7.13/geo_types/geometry/enum.Geometry.html
 /// [`&arrow::array::StringArray`]: https://docs.rs/arrow/50.0.0/arrow/array/type.StringArray.html
 /// [`&arrow::array::BinaryArray`]: https://docs.rs/arrow/50.0.0/arrow/array/type.BinaryArray.html
+/// [`ArrayRef`]: https://docs.rs/arrow/50.0.0/arrow/array/type.ArrayRef.html
 #[proc_macro_attribute]
 pub fn function(attr: TokenStream, item: TokenStream) -> TokenStream {
     fn inner(attr: TokenStream, item: TokenStream) -> Result<TokenStream2> {
@@ -281,20 +637,77 @@ pub fn function(attr: TokenStream, item: TokenStream) -> TokenStream {
     }
 }
 
+/// Defining an aggregate function.
+///
+/// The first argument of the function is the state, which is of the return type.
+/// The function returns the new state after accumulating a row of the other arguments.
+/// If the last argument is `retract: bool`, rows can also be retracted, e.g. for sliding windows.
+///
+/// ```ignore
+/// #[aggregate("sum(int4) -> int8", init_state = "0")]
+/// fn sum(state: i64, value: i32, retract: bool) -> i64 {
+///     if retract {
+///         state - value as i64
+///     } else {
+///         state + value as i64
+///     }
+/// }
+/// ```
+///
+/// The initial state is `init_state`, or null if not specified. If the state argument is
+/// `Option<T>`, the function is also called with null states. Otherwise `init_state` is required,
+/// and rows are skipped while the state is null. Similarly, rows with null arguments are skipped
+/// unless the argument is `Option<T>`.
+///
+/// The macro generates a function of type `arrow_udf::AggregateFunction` named
+/// `{name}_{args}_{ret}_eval`, and exports it to the WebAssembly runtime.
+///
+/// The state can be a primitive type, `boolean`, `date`, `time`, `timestamp`, `interval`,
+/// `decimal`, `json`, `varchar` or `bytea`. Arguments can not be arrays, structs or polymorphic types.
+#[proc_macro_attribute]
+pub fn aggregate(attr: TokenStream, item: TokenStream) -> TokenStream {
+    fn inner(attr: TokenStream, item: TokenStream) -> Result<TokenStream2> {
+        let fn_attr: FunctionAttr = syn::parse(attr)?;
+        let user_fn: UserFunctionAttr = syn::parse(item.clone())?;
+
+        let mut tokens: TokenStream2 = item.into();
+        for attr in fn_attr.expand() {
+            tokens.extend(attr.generate_aggregate_descriptor(&user_fn)?);
+        }
+        Ok(tokens)
+    }
+    match inner(attr, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct FunctionAttr {
+    /// Schema of the function
+    schema: Option<String>,
     /// Function name
     name: String,
+    /// Other names of the function, which may be qualified by a schema
+    aliases: Vec<String>,
+    /// Version of the function. If not specified, it will be 1.
+    version: Option<u32>,
     /// Input argument types
     args: Vec<String>,
     /// Return type
     ret: String,
+    /// Names of the output columns declared by `table(name type, ..)`
+    ret_names: Vec<String>,
     /// Whether it is a table function
     is_table_function: bool,
     /// Whether it is an append-only aggregate function
     append_only: bool,
     /// Optional function for batch evaluation.
     batch_fn: Option<String>,
+    /// Whether an output of length 1 from `batch_fn` is broadcast to all rows.
+    broadcast: bool,
+    /// Whether the fields of the struct argument are passed as separate columns.
+    flatten_input: bool,
     /// State type for aggregate function.
     /// If not specified, it will be the same as return type.
     state: Option<String>,
@@ -307,9 +720,23 @@ struct FunctionAttr {
     generic: Option<String>,
     /// Whether the function is volatile.
     volatile: bool,
+    /// Whether panics of the function are not caught.
+    no_catch_unwind: bool,
+    /// How integer overflow is handled by the arithmetic helpers.
+    overflow: Option<String>,
     /// Generated batch function name.
     /// If not specified, the macro will not generate batch function.
     output: Option<String>,
+    /// Expression of the estimated output size in bytes for string or binary return type.
+    output_size_hint: Option<String>,
+    /// Function to create the context shared by all rows.
+    init: Option<String>,
+    /// Function to finish the context when it is dropped.
+    finish: Option<String>,
+    /// Scalar function to use when this function fails.
+    fallback: Option<String>,
+    /// Number of consecutive failures after which only the fallback is called.
+    max_failures: Option<u32>,
 }
 
 /// Attributes from function signature `fn(..)`
@@ -320,7 +747,7 @@ struct UserFunctionAttr {
     name: String,
     /// Whether the function is async.
     async_: bool,
-    /// Whether contains argument `&Context`.
+    /// Whether contains argument `&EvalContext`.
     context: bool,
     /// Whether contains argument `&mut impl Write`.
     write: bool,
@@ -336,6 +763,8 @@ struct UserFunctionAttr {
     iterator_item_kind: Option<ReturnTypeKind>,
     /// The core return type without `Option` or `Result`.
     core_return_type: String,
+    /// If the core return type is a tuple, whether each element type is `Option<T>`.
+    tuple_option: Vec<bool>,
     /// The number of generic types.
     generic: usize,
     /// The span of return type.
@@ -360,19 +789,40 @@ impl ReturnTypeKind {
 impl FunctionAttr {
     /// Return a unique name that can be used as an identifier.
     fn ident_name(&self) -> String {
-        format!("{}_{}_{}", self.name, self.args.join("_"), self.ret)
-            .replace("[]", "array")
-            .replace("...", "variadic")
-            .replace(['<', ' ', ',', ':'], "_")
-            .replace('>', "")
-            .replace("__", "_")
+        let version = match self.version {
+            Some(v) => format!("_v{v}"),
+            None => String::new(),
+        };
+        format!(
+            "{}{version}_{}_{}",
+            self.qualified_name(),
+            self.args.join("_"),
+            self.ret
+        )
+        .replace("[]", "array")
+        .replace("...", "variadic")
+        .replace(['<', ' ', ',', ':', '(', '.'], "_")
+        .replace(['>', ')'], "")
+        .replace("__", "_")
+    }
+
+    /// Returns the name qualified by the schema, like `schema.name`.
+    fn qualified_name(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!("{schema}.{}", self.name),
+            None => self.name.clone(),
+        }
     }
 
     /// Return a unique signature of the function.
     fn normalize_signature(&self) -> String {
         format!(
-            "{}({}){}{}",
-            self.name,
+            "{}{}({}){}{}",
+            self.qualified_name(),
+            match self.version {
+                Some(v) => format!("@{v}"),
+                None => String::new(),
+            },
             self.args.join(","),
             if self.is_table_function { "->>" } else { "->" },
             self.ret
@@ -28,29 +28,8 @@ impl Parse for FunctionAttr {
 
         let sig = input.parse::<LitStr>()?;
         let sig_str = sig.value();
-        let (name_args, ret) = match sig_str.split_once("->") {
-            Some((name_args, ret)) => (name_args, ret),
-            None => (sig_str.as_str(), "void"),
-        };
-        let (name, args) = name_args
-            .split_once('(')
-            .ok_or_else(|| Error::new_spanned(&sig, "expected '('"))?;
-        let args = args.trim_start().trim_end_matches([')', ' ']);
-        let (is_table_function, ret) = match ret.trim_start() {
-            s if s.starts_with("setof") => (true, &s[5..]), // -> setof
-            s if s.starts_with('>') => (true, &s[1..]),     // ->>
-            _ => (false, ret),
-        };
-        parsed.name = name.trim().to_string();
-        parsed.args = if args.is_empty() {
-            vec![]
-        } else {
-            args.split(',')
-                .map(|s| types::normalize_type(s.trim()))
-                .collect()
-        };
-        parsed.ret = types::normalize_type(ret.trim());
-        parsed.is_table_function = is_table_function;
+        let mut parser = SigParser::new(&sig, &sig_str);
+        parser.parse_signature(&mut parsed)?;
 
         if input.parse::<Token![,]>().is_err() {
             return Ok(parsed);
@@ -80,10 +59,65 @@ impl Parse for FunctionAttr {
                 parsed.generic = Some(get_value()?);
             } else if meta.path().is_ident("output") {
                 parsed.output = Some(get_value()?);
+            } else if meta.path().is_ident("output_size_hint") {
+                parsed.output_size_hint = Some(get_value()?);
+            } else if meta.path().is_ident("overflow") {
+                let value = get_value()?;
+                if !matches!(value.as_str(), "wrap" | "saturate" | "error") {
+                    return Err(Error::new(
+                        meta.span(),
+                        format!("invalid overflow: {value:?}. expect \"wrap\", \"saturate\" or \"error\""),
+                    ));
+                }
+                parsed.overflow = Some(value);
+            } else if meta.path().is_ident("init") {
+                parsed.init = Some(get_value()?);
+            } else if meta.path().is_ident("finish") {
+                parsed.finish = Some(get_value()?);
+            } else if meta.path().is_ident("alias") {
+                let value = get_value()?;
+                for alias in value.split(',').map(str::trim) {
+                    if !is_valid_name(alias) {
+                        return Err(Error::new(
+                            meta.span(),
+                            format!("invalid alias: {alias:?}. expect `name` or `schema.name`"),
+                        ));
+                    }
+                    parsed.aliases.push(alias.to_string());
+                }
+            } else if meta.path().is_ident("version") {
+                let value = get_value()?;
+                let version = value.parse::<u32>().map_err(|_| {
+                    Error::new(
+                        meta.span(),
+                        format!("invalid version: {value:?}. expect a positive integer"),
+                    )
+                })?;
+                if version == 0 {
+                    return Err(Error::new(meta.span(), "version must be positive"));
+                }
+                parsed.version = Some(version);
+            } else if meta.path().is_ident("fallback") {
+                parsed.fallback = Some(get_value()?);
+            } else if meta.path().is_ident("max_failures") {
+                let value = get_value()?;
+                let max_failures = value.parse::<u32>().map_err(|_| {
+                    Error::new(
+                        meta.span(),
+                        format!("invalid max_failures: {value:?}. expect an integer"),
+                    )
+                })?;
+                parsed.max_failures = Some(max_failures);
             } else if meta.path().is_ident("volatile") {
                 parsed.volatile = true;
+            } else if meta.path().is_ident("no_catch_unwind") {
+                parsed.no_catch_unwind = true;
             } else if meta.path().is_ident("append_only") {
                 parsed.append_only = true;
+            } else if meta.path().is_ident("broadcast") {
+                parsed.broadcast = true;
+            } else if meta.path().is_ident("flatten_input") {
+                parsed.flatten_input = true;
             } else {
                 return Err(Error::new(
                     meta.span(),
@@ -91,10 +125,359 @@ impl Parse for FunctionAttr {
                 ));
             }
         }
+        if parsed.finish.is_some() && parsed.init.is_none() {
+            return Err(Error::new_spanned(&sig, "`finish` requires `init`"));
+        }
+        if parsed.max_failures.is_some() && parsed.fallback.is_none() {
+            return Err(Error::new_spanned(
+                &sig,
+                "`max_failures` requires `fallback`",
+            ));
+        }
+        if parsed.fallback.is_some() && parsed.is_table_function {
+            return Err(Error::new_spanned(
+                &sig,
+                "`fallback` is not supported for table functions",
+            ));
+        }
         Ok(parsed)
     }
 }
 
+/// Checks if the string is a function name that may be qualified by a schema, like `schema.name`.
+fn is_valid_name(name: &str) -> bool {
+    let is_ident = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_');
+    match name.split_once('.') {
+        Some((schema, name)) => is_ident(schema) && is_ident(name),
+        None => is_ident(name),
+    }
+}
+
+/// A parser of function signatures like `gcd(int, int) -> int`.
+///
+/// Type names are case-insensitive and may contain any whitespace between words.
+/// Errors are reported with the offset of the bad token in the signature.
+struct SigParser<'a> {
+    lit: &'a LitStr,
+    sig: &'a str,
+    pos: usize,
+}
+
+impl<'a> SigParser<'a> {
+    fn new(lit: &'a LitStr, sig: &'a str) -> Self {
+        Self { lit, sig, pos: 0 }
+    }
+
+    /// Parses `[schema.]name(arg, ...) [-> [setof] ret | -> table(name type, ...)]`.
+    fn parse_signature(&mut self, parsed: &mut FunctionAttr) -> Result<()> {
+        if let Err(msg) = types::type_aliases() {
+            return Err(self.error(0, self.sig.len(), msg));
+        }
+        self.skip_whitespace();
+        let name = self
+            .parse_ident()
+            .ok_or_else(|| self.expected_here("function name"))?;
+        if self.eat('.') {
+            parsed.schema = Some(name.to_string());
+            parsed.name = self
+                .parse_ident()
+                .ok_or_else(|| self.expected_here("function name"))?
+                .to_string();
+        } else {
+            parsed.name = name.to_string();
+        }
+        self.expect('(')?;
+        self.skip_whitespace();
+        parsed.args = if self.eat(')') {
+            vec![]
+        } else {
+            self.parse_type_list(')')?
+        };
+        self.skip_whitespace();
+        if self.rest().is_empty() {
+            parsed.ret = "void".to_string();
+            return Ok(());
+        }
+        if !self.rest().starts_with("->") {
+            return Err(self.expected_here("'->'"));
+        }
+        self.pos += 2;
+        if self.eat('>') {
+            // ->>
+            parsed.is_table_function = true;
+        } else {
+            self.skip_whitespace();
+            let start = self.pos;
+            match self.parse_ident() {
+                Some(word) if word.eq_ignore_ascii_case("setof") => {
+                    parsed.is_table_function = true;
+                }
+                _ => self.pos = start,
+            }
+        }
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.parse_ident() {
+            Some(word) if word.eq_ignore_ascii_case("table") && self.eat_after_whitespace('(') => {
+                // named output columns of a table function
+                parsed.is_table_function = true;
+                let (names, types) = self.parse_column_list()?;
+                parsed.ret = format!("({})", types.join(","));
+                parsed.ret_names = names;
+            }
+            _ => {
+                self.pos = start;
+                parsed.ret = self.parse_type()?;
+            }
+        }
+        self.skip_whitespace();
+        if !self.rest().is_empty() {
+            return Err(self.error(self.pos, self.sig.len(), "unexpected trailing characters"));
+        }
+        Ok(())
+    }
+
+    /// Parses columns like `name type` separated by commas until `)`.
+    fn parse_column_list(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        let mut names: Vec<String> = vec![];
+        let mut types = vec![];
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let name = self
+                .parse_ident()
+                .ok_or_else(|| self.expected_here("column name"))?;
+            if names.iter().any(|n| n == name) {
+                return Err(self.error(start, self.pos, &format!("duplicate column `{name}`")));
+            }
+            names.push(name.to_string());
+            types.push(self.parse_type()?);
+            self.skip_whitespace();
+            if self.eat(',') {
+                continue;
+            }
+            if self.eat(')') {
+                return Ok((names, types));
+            }
+            return Err(self.expected_here("',' or ')'"));
+        }
+    }
+
+    /// Parses types separated by commas until the closing delimiter.
+    fn parse_type_list(&mut self, close: char) -> Result<Vec<String>> {
+        let mut types = vec![];
+        loop {
+            types.push(self.parse_type()?);
+            self.skip_whitespace();
+            if self.eat(',') {
+                continue;
+            }
+            if self.eat(close) {
+                return Ok(types);
+            }
+            return Err(self.expected_here(&format!("',' or '{close}'")));
+        }
+    }
+
+    /// Parses a type and returns its normalized name.
+    fn parse_type(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut ty = if self.eat('{') {
+            format!("{{{}}}", self.parse_type_list('}')?.join(","))
+        } else if self.eat('(') {
+            format!("({})", self.parse_type_list(')')?.join(","))
+        } else if self.eat('*') {
+            // wildcards like `*` and `*numeric`
+            let suffix = self.parse_ident().unwrap_or_default().to_ascii_lowercase();
+            let ty = format!("*{suffix}");
+            self.check_known_type(start, &ty)?;
+            ty
+        } else if let Some(word) = self.parse_ident() {
+            self.parse_named_type(start, word)?
+        } else {
+            return Err(self.expected_here("type"));
+        };
+        loop {
+            let start = self.pos;
+            self.skip_whitespace();
+            if !self.eat('[') {
+                self.pos = start;
+                break;
+            }
+            self.skip_whitespace();
+            self.expect(']')?;
+            ty.push_str("[]");
+        }
+        Ok(ty)
+    }
+
+    /// Parses a type starting with the word, such as `int`, `double precision`, `bytea(16)`
+    /// or `struct Key`.
+    fn parse_named_type(&mut self, start: usize, word: &str) -> Result<String> {
+        let mut name = word.to_ascii_lowercase();
+        if name == "struct" {
+            self.skip_whitespace();
+            let struct_name = self
+                .parse_ident()
+                .ok_or_else(|| self.expected_here("struct name"))?;
+            return Ok(format!("struct {struct_name}"));
+        }
+        // aliases with multiple words
+        let second = match name.as_str() {
+            "double" => Some("precision"),
+            "character" => Some("varying"),
+            _ => None,
+        };
+        if let Some(second) = second {
+            let before = self.pos;
+            self.skip_whitespace();
+            let word_start = self.pos;
+            match self.parse_ident() {
+                Some(word) if word.eq_ignore_ascii_case(second) => {
+                    name = format!("{name} {second}");
+                }
+                // the first word alone can be a type alias like `double`
+                _ if types::type_aliases().is_ok_and(|aliases| aliases.contains_key(&name)) => {
+                    self.pos = before;
+                }
+                _ => return Err(self.expected(word_start, self.pos, &format!("`{second}`"))),
+            }
+        }
+        // wildcards like `int*`
+        if self.eat('*') {
+            name.push('*');
+        }
+        let ty = types::normalize_type(&name);
+        if ty == "vector" {
+            // embeddings like `vector(768)`
+            self.expect('(')?;
+            let size = self.parse_size("the dimension of vector")?;
+            return Ok(format!("vector({size})"));
+        }
+        self.check_known_type(start, &ty)?;
+        if ty == "bytea" {
+            // fixed-size binary like `bytea(16)`
+            let before = self.pos;
+            self.skip_whitespace();
+            if !self.eat('(') {
+                self.pos = before;
+                return Ok(ty);
+            }
+            let size = self.parse_size("the size of bytea")?;
+            return Ok(format!("bytea({size})"));
+        }
+        Ok(ty)
+    }
+
+    /// Parses a size followed by `)`, such as `16)` in `bytea(16)`.
+    fn parse_size(&mut self, what: &str) -> Result<&'a str> {
+        self.skip_whitespace();
+        let size_start = self.pos;
+        let size = self.take_while(|c| c.is_ascii_digit());
+        if size.parse::<i32>().is_err() {
+            return Err(self.expected(size_start, self.pos, what));
+        }
+        self.expect(')')?;
+        Ok(size)
+    }
+
+    fn check_known_type(&self, start: usize, ty: &str) -> Result<()> {
+        if types::is_known_type(ty) {
+            return Ok(());
+        }
+        let text = self.sig[start..self.pos].trim();
+        Err(self.error(start, self.pos, &format!("unknown type `{text}`")))
+    }
+
+    fn parse_ident(&mut self) -> Option<&'a str> {
+        let ident = self.take_while(|c| c.is_alphanumeric() || c == '_');
+        (!ident.is_empty()).then_some(ident)
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    /// Consumes the character after optional whitespace, or consumes nothing.
+    fn eat_after_whitespace(&mut self, c: char) -> bool {
+        let start = self.pos;
+        self.skip_whitespace();
+        if self.eat(c) {
+            return true;
+        }
+        self.pos = start;
+        false
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.expected_here(&format!("'{c}'")))
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.sig[self.pos..]
+    }
+
+    /// Returns an error that `what` is expected at the next character.
+    fn expected_here(&self, what: &str) -> Error {
+        self.expected(self.pos, self.pos, what)
+    }
+
+    /// Returns an error that `what` is expected instead of `sig[start..end]`,
+    /// or the next character if the range is empty.
+    fn expected(&self, start: usize, mut end: usize, what: &str) -> Error {
+        if start == end {
+            end += self.sig[start..].chars().next().map_or(0, char::len_utf8);
+        }
+        let found = match &self.sig[start..end] {
+            "" => "end of signature".to_string(),
+            s => format!("`{s}`"),
+        };
+        self.error(start, end, &format!("expected {what}, found {found}"))
+    }
+
+    /// Returns an error pointing to `sig[start..end]`.
+    ///
+    /// The error is spanned to the bad token if the compiler supports it, otherwise to the
+    /// whole literal. The message always includes the offset and a marker under the token.
+    fn error(&self, start: usize, end: usize, msg: &str) -> Error {
+        let marker = format!(
+            "{}{}",
+            " ".repeat(self.sig[..start].chars().count()),
+            "^".repeat(self.sig[start..end].chars().count().max(1)),
+        );
+        let msg = format!("{msg} at offset {start}\n  {}\n  {marker}", self.sig);
+        // the offset in the source is only known if the literal has no escapes or raw prefix
+        let token = self.lit.token();
+        let span = match token.to_string() == format!("\"{}\"", self.sig) {
+            true => token.subspan(start + 1..end.max(start + 1) + 1),
+            false => None,
+        };
+        Error::new(span.unwrap_or_else(|| self.lit.span()), msg)
+    }
+}
+
 impl Parse for UserFunctionAttr {
     fn parse(input: ParseStream<'_>) -> Result<Self> {
         let itemfn: syn::ItemFn = input.parse()?;
@@ -104,6 +487,7 @@ impl Parse for UserFunctionAttr {
 
 impl From<&syn::Signature> for UserFunctionAttr {
     fn from(sig: &syn::Signature) -> Self {
+        let mut tuple_option = vec![];
         let (return_type_kind, iterator_item_kind, core_return_type) = match &sig.output {
             syn::ReturnType::Default => (ReturnTypeKind::T, None, "()".into()),
             syn::ReturnType::Type(_, ty) => {
@@ -111,9 +495,17 @@ impl From<&syn::Signature> for UserFunctionAttr {
                 match strip_iterator(inner) {
                     Some(ty) => {
                         let (inner_kind, inner) = check_type(ty);
+                        if let syn::Type::Tuple(tuple) = inner {
+                            tuple_option = tuple.elems.iter().map(type_is_option).collect();
+                        }
                         (kind, Some(inner_kind), inner.to_token_stream().to_string())
                     }
-                    None => (kind, None, inner.to_token_stream().to_string()),
+                    None => {
+                        if let syn::Type::Tuple(tuple) = inner {
+                            tuple_option = tuple.elems.iter().map(type_is_option).collect();
+                        }
+                        (kind, None, inner.to_token_stream().to_string())
+                    }
                 }
             }
         };
@@ -128,6 +520,7 @@ impl From<&syn::Signature> for UserFunctionAttr {
             return_type_kind,
             iterator_item_kind,
             core_return_type,
+            tuple_option,
             generic: sig.generics.params.len(),
             return_type_span: sig.output.span(),
         }
@@ -154,7 +547,7 @@ fn arg_is_write(arg: &syn::FnArg) -> bool {
     seg.ident == "Write"
 }
 
-/// Check if the argument is `&Context`.
+/// Check if the argument is `&EvalContext`.
 fn arg_is_context(arg: &syn::FnArg) -> bool {
     let syn::FnArg::Typed(arg) = arg else {
         return false;
@@ -168,7 +561,7 @@ fn arg_is_context(arg: &syn::FnArg) -> bool {
     let Some(seg) = path.path.segments.last() else {
         return false;
     };
-    seg.ident == "Context"
+    seg.ident == "EvalContext"
 }
 
 /// Check if the last argument is `retract: bool`.
@@ -187,7 +580,12 @@ fn arg_is_option(arg: &syn::FnArg) -> bool {
     let syn::FnArg::Typed(arg) = arg else {
         return false;
     };
-    let syn::Type::Path(path) = arg.ty.as_ref() else {
+    type_is_option(&arg.ty)
+}
+
+/// Check if the type is `Option`.
+fn type_is_option(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
         return false;
     };
     let Some(seg) = path.path.segments.last() else {
@@ -277,3 +675,69 @@ fn strip_iterator(ty: &syn::Type) -> Option<&syn::Type> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(sig: &str) -> Result<FunctionAttr> {
+        syn::parse_str(&format!("{sig:?}"))
+    }
+
+    #[test]
+    fn test_parse_signature() {
+        let attr = parse("gcd(INT, Integer) -> int").unwrap();
+        assert_eq!(attr.name, "gcd");
+        assert_eq!(attr.args, ["int4", "int4"]);
+        assert_eq!(attr.ret, "int4");
+        assert!(!attr.is_table_function);
+
+        let attr =
+            parse("f(Double   Precision, CHARACTER\tVARYING [ ]) -> SETOF struct Key").unwrap();
+        assert_eq!(attr.args, ["float8", "varchar[]"]);
+        assert_eq!(attr.ret, "struct Key");
+        assert!(attr.is_table_function);
+
+        let attr = parse("f(bytea( 16 ), *numeric) ->> {int, bigint}").unwrap();
+        assert_eq!(attr.args, ["bytea(16)", "*numeric"]);
+        assert_eq!(attr.ret, "{int4,int8}");
+        assert!(attr.is_table_function);
+
+        let attr = parse("f(Vector(3)) -> vector( 3 )").unwrap();
+        assert_eq!(attr.args, ["vector(3)"]);
+        assert_eq!(attr.ret, "vector(3)");
+
+        let attr = parse("split(varchar) -> (varchar, int)").unwrap();
+        assert_eq!(attr.ret, "(varchar,int4)");
+
+        let attr = parse("my_schema.levenshtein(varchar, varchar) -> int").unwrap();
+        assert_eq!(attr.schema.as_deref(), Some("my_schema"));
+        assert_eq!(attr.name, "levenshtein");
+
+        let attr = parse("f()").unwrap();
+        assert!(attr.args.is_empty());
+        assert_eq!(attr.ret, "void");
+    }
+
+    #[test]
+    fn test_parse_signature_error() {
+        let error = |sig| parse(sig).err().unwrap().to_string();
+        assert_eq!(
+            error("gcd(int, int44) -> int"),
+            "unknown type `int44` at offset 9\n  gcd(int, int44) -> int\n           ^^^^^"
+        );
+        assert!(
+            error("gcd(int int) -> int").starts_with("expected ',' or ')', found `i` at offset 8")
+        );
+        assert!(error("gcd(int, int) => int").starts_with("expected '->', found `=` at offset 14"));
+        assert!(
+            error("f(double) -> int").starts_with("expected `precision`, found `)` at offset 8")
+        );
+        assert!(error("f(bytea(n)) -> int")
+            .starts_with("expected the size of bytea, found `n` at offset 8"));
+        assert!(error("f(vector(n)) -> int")
+            .starts_with("expected the dimension of vector, found `n` at offset 9"));
+        assert!(error("f(vector) -> int").starts_with("expected '('"));
+        assert!(error("f(int) -> int)").starts_with("unexpected trailing characters at offset 13"));
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use itertools::Itertools;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, ToTokens};
 use syn::{Data, DeriveInput, Result};
 
@@ -34,8 +34,7 @@ pub fn gen(tokens: TokenStream) -> Result<TokenStream> {
         .map(Field::parse)
         .collect::<Result<Vec<Field>>>()?;
 
-    let names = fields.iter().map(|f| &f.name);
-    let types = fields.iter().map(|f| gen::data_type(&f.type_));
+    let arrow_fields = fields.iter().map(|f| gen::field(&f.name, &f.type_));
     let append_values = fields.iter().enumerate().map(|(i, f)| {
         let field = &f.ident;
         let append_value = gen::gen_append_value(&f.type_);
@@ -64,6 +63,63 @@ pub fn gen(tokens: TokenStream) -> Result<TokenStream> {
             #append_null
         }}
     });
+    // struct values can be read from arrays if all fields are scalars
+    let readable = fields.iter().all(|f| {
+        let ty = f.type_.as_str();
+        !ty.ends_with("[]")
+            && !ty.starts_with("struct")
+            && !types::is_polymorphic(ty)
+            && types::fixed_size(ty).is_none()
+            && types::vector_size(ty).is_none()
+            && ty != "void"
+    });
+    let struct_value = readable.then(|| {
+        let lifetime = match generics.lifetimes().next() {
+            Some(param) => param.lifetime.clone(),
+            None => syn::Lifetime::new("'a", Span::call_site()),
+        };
+        let impl_generics = match generics.params.is_empty() {
+            true => quote! { <#lifetime> },
+            false => quote! { #generics },
+        };
+        let read_fields = fields.iter().enumerate().map(|(i, f)| {
+            let field = &f.ident;
+            let ty = &f.ty;
+            let array_type = format_ident!("{}", types::array_type(&f.type_));
+            let transformed = gen::transform_input(&format_ident!("v"), &f.type_);
+            let value = quote! {{
+                let array = columns[#i].as_any().downcast_ref::<#array_type>()
+                    .expect(concat!("expect ", stringify!(#array_type)));
+                match array.is_null(row) {
+                    true => None,
+                    false => {
+                        let v = array.value(row);
+                        let v: #ty = #transformed.into();
+                        Some(v)
+                    }
+                }
+            }};
+            match f.option {
+                true => quote! { #field: #value, },
+                false => quote! { #field: #value?, },
+            }
+        });
+        quote! {
+            impl #impl_generics ::arrow_udf::types::StructValue<#lifetime> for #struct_name #generics {
+                fn from_columns(
+                    columns: &#lifetime [::arrow_udf::codegen::arrow_array::ArrayRef],
+                    row: usize,
+                ) -> Option<Self> {
+                    use ::arrow_udf::codegen::arrow_array::array::*;
+                    use ::arrow_udf::codegen::rust_decimal;
+                    use ::arrow_udf::codegen::serde_json;
+                    Some(Self {
+                        #(#read_fields)*
+                    })
+                }
+            }
+        }
+    });
     let static_name = format_ident!("{}_METADATA", struct_name.to_string().to_uppercase());
     let export_name = format!(
         "arrowudt_{}",
@@ -78,8 +134,11 @@ pub fn gen(tokens: TokenStream) -> Result<TokenStream> {
         ))
     );
 
+    let ffi_cfg = gen::ffi_cfg();
+
     Ok(quote! {
         // export a symbol to describe the struct type
+        #ffi_cfg
         #[export_name = #export_name]
         static #static_name: () = ();
 
@@ -87,7 +146,7 @@ pub fn gen(tokens: TokenStream) -> Result<TokenStream> {
             fn fields() -> ::arrow_udf::codegen::arrow_schema::Fields {
                 use ::arrow_udf::codegen::arrow_schema::{self, Field, TimeUnit, IntervalUnit};
                 let fields: Vec<Field> = vec![
-                    #(Field::new(#names, #types, true),)*
+                    #(#arrow_fields,)*
                 ];
                 fields.into()
             }
@@ -102,6 +161,8 @@ pub fn gen(tokens: TokenStream) -> Result<TokenStream> {
                 builder.append_null();
             }
         }
+
+        #struct_value
     })
 }
 
@@ -112,6 +173,8 @@ struct Field {
     ident: syn::Ident,
     /// The name of the field. `r#` is stripped.
     name: String,
+    /// The type of the field without `Option`.
+    ty: syn::Type,
     /// The normalized type of the field. e.g. `int4` for `i32`.
     type_: String,
     /// Whether the field is nullable.
@@ -135,6 +198,7 @@ impl Field {
             Some(ty) => (true, ty),
             None => (false, ty),
         };
+        let option_stripped = ty.clone();
         let (list, ty) = match strip_outer_type(ty, "Vec") {
             // exclude `Vec<u8>` from list
             Some(ty) if ty.to_token_stream().to_string() != "u8" => (true, ty),
@@ -148,6 +212,7 @@ impl Field {
         Ok(Self {
             ident,
             name,
+            ty: option_stripped,
             type_,
             option,
         })
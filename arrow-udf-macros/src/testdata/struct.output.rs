@@ -1,26 +1,33 @@
+#[cfg(target_arch = "wasm32")]
 #[export_name = "arrowudt_RGF0YT1hOnZvaWQsYjpib29sZWFuLGM6aW50MixkOmludDQsZTppbnQ4LGY6ZmxvYXQ0LGc6ZmxvYXQ4LGg6ZGVjaW1hbCxpOmRhdGUsajp0aW1lLGs6dGltZXN0YW1wLGw6aW50ZXJ2YWwsbTpqc29uLG46dmFyY2hhcixvOmJ5dGVhLHA6dmFyY2hhcltdLHE6c3RydWN0IEtleVZhbHVl"]
 static DATA_METADATA: () = ();
 impl ::arrow_udf::types::StructType for Data {
     fn fields() -> ::arrow_udf::codegen::arrow_schema::Fields {
         use ::arrow_udf::codegen::arrow_schema::{self, Field, TimeUnit, IntervalUnit};
         let fields: Vec<Field> = vec![
-            Field::new("a", arrow_schema::DataType::Null, true), Field::new("b",
-            arrow_schema::DataType::Boolean, true), Field::new("c",
-            arrow_schema::DataType::Int16, true), Field::new("d",
-            arrow_schema::DataType::Int32, true), Field::new("e",
-            arrow_schema::DataType::Int64, true), Field::new("f",
-            arrow_schema::DataType::Float32, true), Field::new("g",
-            arrow_schema::DataType::Float64, true), Field::new("h",
-            arrow_schema::DataType::LargeBinary, true), Field::new("i",
-            arrow_schema::DataType::Date32, true), Field::new("j",
-            arrow_schema::DataType::Time64(TimeUnit::Microsecond), true), Field::new("k",
+            arrow_schema::Field::new("a", arrow_schema::DataType::Null, true),
+            arrow_schema::Field::new("b", arrow_schema::DataType::Boolean, true),
+            arrow_schema::Field::new("c", arrow_schema::DataType::Int16, true),
+            arrow_schema::Field::new("d", arrow_schema::DataType::Int32, true),
+            arrow_schema::Field::new("e", arrow_schema::DataType::Int64, true),
+            arrow_schema::Field::new("f", arrow_schema::DataType::Float32, true),
+            arrow_schema::Field::new("g", arrow_schema::DataType::Float64, true),
+            arrow_schema::Field::new("h", arrow_schema::DataType::Utf8, true)
+            .with_metadata([("ARROW:extension:name".into(), "arrowudf.decimal".into())]
+            .into()), arrow_schema::Field::new("i", arrow_schema::DataType::Date32,
+            true), arrow_schema::Field::new("j",
+            arrow_schema::DataType::Time64(TimeUnit::Microsecond), true),
+            arrow_schema::Field::new("k",
             arrow_schema::DataType::Timestamp(TimeUnit::Microsecond, None), true),
-            Field::new("l", arrow_schema::DataType::Interval(IntervalUnit::MonthDayNano),
-            true), Field::new("m", arrow_schema::DataType::LargeUtf8, true),
-            Field::new("n", arrow_schema::DataType::Utf8, true), Field::new("o",
-            arrow_schema::DataType::Binary, true), Field::new("p",
+            arrow_schema::Field::new("l",
+            arrow_schema::DataType::Interval(IntervalUnit::MonthDayNano), true),
+            arrow_schema::Field::new("m", arrow_schema::DataType::Utf8, true)
+            .with_metadata([("ARROW:extension:name".into(), "arrowudf.json".into())]
+            .into()), arrow_schema::Field::new("n", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new("o", arrow_schema::DataType::Binary, true),
+            arrow_schema::Field::new("p",
             arrow_schema::DataType::List(Arc::new(arrow_schema::Field::new("item",
-            arrow_schema::DataType::Utf8, true))), true), Field::new("q",
+            arrow_schema::DataType::Utf8, true))), true), arrow_schema::Field::new("q",
             arrow_schema::DataType::Struct(KeyValue::fields()), true),
         ];
         fields.into()
@@ -66,21 +73,31 @@ impl ::arrow_udf::types::StructType for Data {
             builder.append_value(v)
         }
         {
-            let builder = builder.field_builder::<LargeBinaryBuilder>(7usize).unwrap();
+            let builder = builder.field_builder::<StringBuilder>(7usize).unwrap();
             let v = self.h;
             builder.append_value(v.to_string())
         }
         {
             let builder = builder.field_builder::<Date32Builder>(8usize).unwrap();
             let v = self.i;
-            builder.append_value(arrow_array::types::Date32Type::from_naive_date(v))
+            builder
+                .append_value(
+                    ::arrow_udf::codegen::arrow_array::types::Date32Type::from_naive_date(
+                        v,
+                    ),
+                )
         }
         {
             let builder = builder
                 .field_builder::<Time64MicrosecondBuilder>(9usize)
                 .unwrap();
             let v = self.j;
-            builder.append_value(arrow_array::temporal_conversions::time_to_time64us(v))
+            builder
+                .append_value(
+                    ::arrow_udf::codegen::arrow_array::temporal_conversions::time_to_time64us(
+                        v,
+                    ),
+                )
         }
         {
             let builder = builder
@@ -97,7 +114,7 @@ impl ::arrow_udf::types::StructType for Data {
             builder
                 .append_value({
                     let v: arrow_udf::types::Interval = v.into();
-                    arrow_array::types::IntervalMonthDayNanoType::make_value(
+                    ::arrow_udf::codegen::arrow_array::types::IntervalMonthDayNanoType::make_value(
                         v.months,
                         v.days,
                         v.nanos,
@@ -105,7 +122,7 @@ impl ::arrow_udf::types::StructType for Data {
                 })
         }
         {
-            let builder = builder.field_builder::<LargeStringBuilder>(12usize).unwrap();
+            let builder = builder.field_builder::<StringBuilder>(12usize).unwrap();
             let v = self.m;
             {
                 use std::fmt::Write;
@@ -180,7 +197,7 @@ impl ::arrow_udf::types::StructType for Data {
             builder.append_null()
         }
         {
-            let builder = builder.field_builder::<LargeBinaryBuilder>(7usize).unwrap();
+            let builder = builder.field_builder::<StringBuilder>(7usize).unwrap();
             builder.append_null()
         }
         {
@@ -206,7 +223,7 @@ impl ::arrow_udf::types::StructType for Data {
             builder.append_null()
         }
         {
-            let builder = builder.field_builder::<LargeStringBuilder>(12usize).unwrap();
+            let builder = builder.field_builder::<StringBuilder>(12usize).unwrap();
             builder.append_null()
         }
         {
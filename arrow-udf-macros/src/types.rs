@@ -14,6 +14,12 @@
 
 //! This module provides utility functions for Arrow data type conversion and manipulation.
 
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The environment variable of type aliases, like `string=varchar,double=float8`.
+pub const TYPE_ALIASES_ENV: &str = "ARROW_UDF_TYPE_ALIASES";
+
 //  name    primitive   rust type       array prefix            data type
 const TYPE_MATRIX: &str = "
     void        _       ()              Null                    Null
@@ -21,18 +27,26 @@ const TYPE_MATRIX: &str = "
     int2        y       i16             Int16                   Int16
     int4        y       i32             Int32                   Int32
     int8        y       i64             Int64                   Int64
+    float2      y       f16             Float16                 Float16
     float4      y       f32             Float32                 Float32
     float8      y       f64             Float64                 Float64
-    decimal     _       Decimal         LargeBinary             LargeBinary
+    decimal     _       Decimal         String                  Utf8
     date        _       NaiveDate       Date32                  Date32
     time        _       NaiveTime       Time64Microsecond       Time64(TimeUnit::Microsecond)
     timestamp   _       NaiveDateTime   TimestampMicrosecond    Timestamp(TimeUnit::Microsecond,None)
     interval    _       Interval        IntervalMonthDayNano    Interval(IntervalUnit::MonthDayNano)
-    json        _       Value           LargeString             LargeUtf8
+    json        _       Value           String                  Utf8
+    geometry    _       Geometry        Binary                  Binary
     varchar     _       String,str      String                  Utf8
     bytea       _       Vec<u8>,[u8]    Binary                  Binary
+    large_varchar _     String,str      LargeString             LargeUtf8
+    large_bytea _       Vec<u8>,[u8]    LargeBinary             LargeBinary
+    bytea(n)    _       [u8;N]          FixedSizeBinary         FixedSizeBinary
+    vector(n)   _       _               FixedSizeList           FixedSizeList
     array       _       _               List                    List
     struct      _       _               Struct                  Struct
+    any         _       ArrayRef        _                       _
+    anyarray    _       ArrayRef        List                    _
 ";
 
 /// Maps a data type to its corresponding data type name.
@@ -50,6 +64,66 @@ pub fn array_builder_type(ty: &str) -> String {
     format!("{}Builder", lookup_matrix(ty, 3))
 }
 
+/// Returns the extension type name of a data type, if it is an extension type.
+///
+/// Extension types are stored as their storage type in arrow arrays,
+/// with the extension name annotated in the field metadata.
+pub fn extension_type(ty: &str) -> Option<&'static str> {
+    match ty {
+        "json" => Some("arrowudf.json"),
+        "decimal" => Some("arrowudf.decimal"),
+        "geometry" => Some("geoarrow.wkb"),
+        _ => None,
+    }
+}
+
+/// Checks if a data type is polymorphic, whose actual type is determined by the input types.
+pub fn is_polymorphic(ty: &str) -> bool {
+    ty == "any" || ty == "anyarray"
+}
+
+/// Returns the element types if the data type is a tuple of multiple output columns.
+///
+/// # Examples
+/// ```text
+/// "(varchar,int4)" => ["varchar", "int4"]
+/// "int4" => None
+/// ```
+pub fn tuple_types(ty: &str) -> Option<Vec<&str>> {
+    let tuple = ty.strip_prefix('(')?.strip_suffix(')')?;
+    Some(tuple.split(',').collect())
+}
+
+/// Returns the byte width if the data type is a fixed-size binary like `bytea(16)`.
+pub fn fixed_size(ty: &str) -> Option<i32> {
+    ty.strip_prefix("bytea(")?.strip_suffix(')')?.parse().ok()
+}
+
+/// Returns the dimension if the data type is a vector like `vector(768)`.
+pub fn vector_size(ty: &str) -> Option<i32> {
+    ty.strip_prefix("vector(")?.strip_suffix(')')?.parse().ok()
+}
+
+/// Checks if a normalized type name without the array suffix is a known type or wildcard.
+pub fn is_known_type(ty: &str) -> bool {
+    if fixed_size(ty).is_some()
+        || vector_size(ty).is_some()
+        || matches!(ty, "*" | "int*" | "float*" | "*numeric")
+    {
+        return true;
+    }
+    TYPE_MATRIX
+        .trim()
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .any(|name| name == ty && name != "bytea(n)" && name != "vector(n)")
+}
+
+/// Checks if a data type is a string or binary type with variable length.
+pub fn is_bytes(ty: &str) -> bool {
+    matches!(ty, "varchar" | "bytea" | "large_varchar" | "large_bytea")
+}
+
 /// Checks if a data type is primitive.
 pub fn is_primitive(ty: &str) -> bool {
     lookup_matrix(ty, 1) == "y"
@@ -57,6 +131,12 @@ pub fn is_primitive(ty: &str) -> bool {
 
 /// Maps a Rust type to its corresponding data type name.
 pub fn type_of(rust_type: &str) -> String {
+    if let Some(n) = rust_type
+        .strip_prefix("[u8;")
+        .and_then(|s| s.strip_suffix(']'))
+    {
+        return format!("bytea({n})");
+    }
     if let Some(ty) = TYPE_MATRIX.trim().lines().find_map(|line| {
         let mut parts = line.split_whitespace();
         let ty = parts.next()?;
@@ -82,6 +162,10 @@ fn lookup_matrix(mut ty: &str, idx: usize) -> &str {
         ty = "array";
     } else if ty.starts_with("struct") {
         ty = "struct";
+    } else if fixed_size(ty).is_some() {
+        ty = "bytea(n)";
+    } else if vector_size(ty).is_some() {
+        ty = "vector(n)";
     }
     let s = TYPE_MATRIX.trim().lines().find_map(|line| {
         let mut parts = line.split_whitespace();
@@ -101,27 +185,100 @@ fn lookup_matrix(mut ty: &str, idx: usize) -> &str {
 /// "int" => "int4"
 /// "int[]" => "int4[]"
 /// "struct  Key" => "struct Key"
+/// "{int, bigint}" => "{int4,int8}"
+/// "(varchar, int)" => "(varchar,int4)"
+/// "bytea( 16 )" => "bytea(16)"
 /// ```
 pub fn normalize_type(ty: &str) -> String {
     if let Some(t) = ty.strip_suffix("[]") {
         return format!("{}[]", normalize_type(t));
     }
+    if let Some(tuple) = ty.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        let types = tuple.split(',').map(|t| normalize_type(t.trim()));
+        return format!("({})", types.collect::<Vec<_>>().join(","));
+    }
+    if let Some(set) = ty.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+        let types = set.split(',').map(|t| normalize_type(t.trim()));
+        return format!("{{{}}}", types.collect::<Vec<_>>().join(","));
+    }
     if let Some(s) = ty.strip_prefix("struct ") {
         return format!("struct {}", s.trim());
     }
+    if let Some(n) = ty.strip_prefix("bytea(").and_then(|s| s.strip_suffix(')')) {
+        return format!("bytea({})", n.trim());
+    }
+    if let Some(n) = ty.strip_prefix("vector(").and_then(|s| s.strip_suffix(')')) {
+        return format!("vector({})", n.trim());
+    }
+    if let Some(t) = type_aliases().ok().and_then(|aliases| aliases.get(ty)) {
+        return t.clone();
+    }
+    builtin_alias(ty).to_string()
+}
+
+/// Resolves the builtin aliases of a type without parameters.
+fn builtin_alias(ty: &str) -> &str {
     match ty {
         "bool" => "boolean",
         "smallint" => "int2",
         "int" | "integer" => "int4",
         "bigint" => "int8",
+        "half" => "float2",
         "real" => "float4",
         "double precision" => "float8",
         "numeric" => "decimal",
         "character varying" => "varchar",
+        "large_string" => "large_varchar",
+        "large_binary" => "large_bytea",
         "jsonb" => "json",
+        "anyelement" => "any",
         _ => ty,
     }
-    .to_string()
+}
+
+/// Returns the type aliases given by the environment variable [`TYPE_ALIASES_ENV`],
+/// or an error message if it is malformed.
+///
+/// The types of aliases are normalized, so they can be builtin aliases like `bigint`.
+pub fn type_aliases() -> Result<&'static HashMap<String, String>, &'static str> {
+    static ALIASES: OnceLock<Result<HashMap<String, String>, String>> = OnceLock::new();
+    let aliases = ALIASES.get_or_init(|| {
+        // read at the build time of this crate, `build.rs` makes cargo rebuild it when the
+        // variable changes
+        match option_env!("ARROW_UDF_TYPE_ALIASES") {
+            Some(value) => parse_type_aliases(value),
+            None => Ok(HashMap::new()),
+        }
+    });
+    aliases.as_ref().map_err(|e| e.as_str())
+}
+
+/// Parses type aliases like `string=varchar,double=float8`.
+fn parse_type_aliases(value: &str) -> Result<HashMap<String, String>, String> {
+    let mut aliases = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let invalid = || format!("invalid type alias `{entry}` in {TYPE_ALIASES_ENV}");
+        let (alias, ty) = entry.split_once('=').ok_or_else(invalid)?;
+        let alias = alias.split_whitespace().collect::<Vec<_>>().join(" ");
+        let alias = alias.to_ascii_lowercase();
+        if alias.is_empty()
+            || alias.contains(|c: char| !c.is_alphanumeric() && c != '_' && c != ' ')
+        {
+            return Err(invalid());
+        }
+        let ty = ty.split_whitespace().collect::<Vec<_>>().join(" ");
+        let ty = ty.to_ascii_lowercase();
+        // the type can be an array like `varchar[]`
+        let item = ty.trim_end_matches("[]");
+        let resolved = builtin_alias(item);
+        if !is_known_type(resolved) || resolved.starts_with('*') {
+            return Err(format!(
+                "unknown type `{ty}` of alias `{alias}` in {TYPE_ALIASES_ENV}"
+            ));
+        }
+        aliases.insert(alias, format!("{resolved}{}", &ty[item.len()..]));
+    }
+    Ok(aliases)
 }
 
 /// Expands a type wildcard string into a list of concrete types.
@@ -131,7 +288,10 @@ pub fn expand_type_wildcard(ty: &str) -> Vec<&str> {
             .trim()
             .lines()
             .map(|l| l.split_whitespace().next().unwrap())
-            .filter(|l| *l != "any" && *l != "void")
+            // `geometry` requires the `geo` feature of `arrow-udf`
+            .filter(|l| {
+                !is_polymorphic(l) && !matches!(*l, "void" | "bytea(n)" | "vector(n)" | "geometry")
+            })
             .collect(),
         "int*" => vec!["int2", "int4", "int8"],
         "float*" => vec!["float4", "float8"],
@@ -139,6 +299,26 @@ pub fn expand_type_wildcard(ty: &str) -> Vec<&str> {
     }
 }
 
+/// Expands a correlated type wildcard into a list of concrete types.
+///
+/// Unlike [`expand_type_wildcard`], all occurrences of the same correlated wildcard in a
+/// signature are replaced with the same type. Returns `None` if the type is not one.
+///
+/// # Examples
+/// ```text
+/// "*numeric" => ["int2", "int4", "int8", "float4", "float8"]
+/// "{int4,int8}" => ["int4", "int8"]
+/// ```
+pub fn expand_correlated_type_wildcard(ty: &str) -> Option<Vec<&str>> {
+    match ty {
+        "*numeric" => Some(vec!["int2", "int4", "int8", "float4", "float8"]),
+        _ => {
+            let set = ty.strip_prefix('{')?.strip_suffix('}')?;
+            Some(set.split(',').collect())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,12 +329,70 @@ mod tests {
         assert_eq!(normalize_type("smallint"), "int2");
         assert_eq!(normalize_type("int"), "int4");
         assert_eq!(normalize_type("bigint"), "int8");
+        assert_eq!(normalize_type("half"), "float2");
         assert_eq!(normalize_type("real"), "float4");
         assert_eq!(normalize_type("double precision"), "float8");
         assert_eq!(normalize_type("numeric"), "decimal");
         assert_eq!(normalize_type("character varying"), "varchar");
+        assert_eq!(normalize_type("large_string"), "large_varchar");
+        assert_eq!(normalize_type("large_binary"), "large_bytea");
         assert_eq!(normalize_type("jsonb"), "json");
+        assert_eq!(normalize_type("anyelement"), "any");
         assert_eq!(normalize_type("int[]"), "int4[]");
         assert_eq!(normalize_type("struct   Key"), "struct Key");
+        assert_eq!(normalize_type("{int, bigint}"), "{int4,int8}");
+        assert_eq!(normalize_type("{int, bigint}[]"), "{int4,int8}[]");
+        assert_eq!(normalize_type("(varchar, int)"), "(varchar,int4)");
+        assert_eq!(normalize_type("bytea( 16 )"), "bytea(16)");
+        assert_eq!(normalize_type("vector( 3 )"), "vector(3)");
+    }
+
+    #[test]
+    fn test_parse_type_aliases() {
+        let aliases = parse_type_aliases("string=varchar, Double = float8,text=varchar[]").unwrap();
+        assert_eq!(aliases["string"], "varchar");
+        assert_eq!(aliases["double"], "float8");
+        assert_eq!(aliases["text"], "varchar[]");
+        let aliases = parse_type_aliases("long=bigint,").unwrap();
+        assert_eq!(aliases["long"], "int8");
+        assert!(parse_type_aliases("string").is_err());
+        assert!(parse_type_aliases("string=str").is_err());
+        assert!(parse_type_aliases("any=*").is_err());
+    }
+
+    #[test]
+    fn test_expand_correlated_type_wildcard() {
+        assert_eq!(
+            expand_correlated_type_wildcard("*numeric").unwrap(),
+            ["int2", "int4", "int8", "float4", "float8"]
+        );
+        assert_eq!(
+            expand_correlated_type_wildcard("{int4,int8}").unwrap(),
+            ["int4", "int8"]
+        );
+        assert_eq!(expand_correlated_type_wildcard("int*"), None);
+        assert_eq!(expand_correlated_type_wildcard("int4"), None);
+    }
+
+    #[test]
+    fn test_fixed_size() {
+        assert_eq!(fixed_size("bytea(16)"), Some(16));
+        assert_eq!(fixed_size("bytea"), None);
+        assert_eq!(type_of("[u8;32]"), "bytea(32)");
+        assert_eq!(array_type("bytea(16)"), "FixedSizeBinaryArray");
+    }
+
+    #[test]
+    fn test_vector_size() {
+        assert_eq!(vector_size("vector(768)"), Some(768));
+        assert_eq!(vector_size("vector"), None);
+        assert!(is_known_type("vector(3)"));
+        assert_eq!(array_type("vector(3)"), "FixedSizeListArray");
+    }
+
+    #[test]
+    fn test_tuple_types() {
+        assert_eq!(tuple_types("(varchar,int4)").unwrap(), ["varchar", "int4"]);
+        assert_eq!(tuple_types("int4"), None);
     }
 }
@@ -0,0 +1,199 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![doc = include_str!("../README.md")]
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Context, Result};
+use arrow_array::{
+    cast::AsArray, types::Float32Type, Array, ArrayRef, FixedSizeListArray, Float32Array,
+    RecordBatch,
+};
+use arrow_buffer::NullBuffer;
+use arrow_schema::{DataType, Field, Schema};
+use tract_onnx::prelude::*;
+
+/// The ONNX UDF runtime.
+#[derive(Default)]
+pub struct Runtime {
+    functions: HashMap<String, Function>,
+}
+
+impl Debug for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Runtime")
+            .field("functions", &self.functions.keys())
+            .finish()
+    }
+}
+
+/// A registered function.
+struct Function {
+    plan: TypedSimplePlan<TypedModel>,
+    num_inputs: usize,
+}
+
+/// The mapping between the columns of input batches and the inputs and outputs of a model.
+///
+/// By default, the columns are fed to the model inputs in order, and the first model output
+/// is returned.
+#[derive(Debug, Default, Clone)]
+pub struct Mapping {
+    inputs: Vec<String>,
+    output: Option<String>,
+}
+
+impl Mapping {
+    /// Feed the next column of input batches to the model input with the given name.
+    pub fn input(mut self, name: &str) -> Self {
+        self.inputs.push(name.to_string());
+        self
+    }
+
+    /// Return the model output with the given name.
+    pub fn output(mut self, name: &str) -> Self {
+        self.output = Some(name.to_string());
+        self
+    }
+}
+
+impl Runtime {
+    /// Create a new ONNX UDF runtime.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a function that runs an ONNX model.
+    ///
+    /// The inputs of the model must have known shapes, except for the first dimension which
+    /// is the number of rows.
+    pub fn add_function(&mut self, name: &str, model: &[u8], mapping: Mapping) -> Result<()> {
+        let model = tract_onnx::onnx()
+            .model_for_read(&mut &model[..])
+            .context("failed to load model")?;
+        self.add(name, model, mapping)
+    }
+
+    /// Add a function that runs an ONNX model from a file.
+    ///
+    /// See [`add_function`](Self::add_function) for details.
+    pub fn add_function_from_file(
+        &mut self,
+        name: &str,
+        path: impl AsRef<Path>,
+        mapping: Mapping,
+    ) -> Result<()> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)
+            .context("failed to load model")?;
+        self.add(name, model, mapping)
+    }
+
+    fn add(&mut self, name: &str, mut model: InferenceModel, mapping: Mapping) -> Result<()> {
+        if !mapping.inputs.is_empty() {
+            model.set_input_names(&mapping.inputs)?;
+        }
+        match &mapping.output {
+            Some(output) => model.set_output_names([output])?,
+            None => {
+                let first = *model.outputs.first().context("model has no output")?;
+                model.set_output_outlets(&[first])?;
+            }
+        }
+        let num_inputs = model.inputs.len();
+        let plan = model
+            .into_optimized()
+            .context("failed to optimize model. HINT: make sure the inputs have known shapes")?
+            .into_runnable()?;
+        let function = Function { plan, num_inputs };
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    /// Remove a function.
+    pub fn del_function(&mut self, name: &str) -> Result<()> {
+        self.functions.remove(name).context("function not found")?;
+        Ok(())
+    }
+
+    /// Run the model on the input batch.
+    ///
+    /// If any input of a row is null, the output of the row is null.
+    pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+        let function = self.functions.get(name).context("function not found")?;
+        ensure!(
+            input.num_columns() == function.num_inputs,
+            "expect {} columns, got {}",
+            function.num_inputs,
+            input.num_columns()
+        );
+        let inputs = input
+            .columns()
+            .iter()
+            .map(|array| Ok(array_to_tensor(array)?.into()))
+            .collect::<Result<TVec<TValue>>>()?;
+        let mut outputs = function.plan.run(inputs).context("failed to run model")?;
+        let nulls = input.columns().iter().fold(None, |acc, array| {
+            NullBuffer::union(acc.as_ref(), array.nulls())
+        });
+        let array = tensor_to_array(&outputs.remove(0), input.num_rows(), nulls)?;
+        let schema = Schema::new(vec![Field::new(name, array.data_type().clone(), true)]);
+        Ok(RecordBatch::try_new(Arc::new(schema), vec![array])?)
+    }
+}
+
+/// Convert an array of `Float32` or nested `FixedSizeList<Float32>` to a tensor.
+///
+/// The first dimension of the tensor is the number of rows.
+fn array_to_tensor(array: &dyn Array) -> Result<Tensor> {
+    let mut shape = vec![array.len()];
+    let mut values = array;
+    loop {
+        match values.data_type() {
+            DataType::FixedSizeList(_, size) => {
+                shape.push(*size as usize);
+                values = values.as_fixed_size_list().values().as_ref();
+            }
+            DataType::Float32 => break,
+            t => bail!("unsupported data type: {t}"),
+        }
+    }
+    Tensor::from_shape(&shape, values.as_primitive::<Float32Type>().values())
+}
+
+/// Convert a tensor to an array of `Float32` or nested `FixedSizeList<Float32>`.
+fn tensor_to_array(tensor: &Tensor, rows: usize, nulls: Option<NullBuffer>) -> Result<ArrayRef> {
+    let tensor = tensor.cast_to::<f32>()?;
+    let shape = tensor.shape();
+    ensure!(
+        shape.first() == Some(&rows),
+        "expect output of {rows} rows, got shape {shape:?}"
+    );
+    let values = tensor.as_slice::<f32>()?.to_vec();
+    if shape.len() == 1 {
+        return Ok(Arc::new(Float32Array::new(values.into(), nulls)));
+    }
+    let mut array: ArrayRef = Arc::new(Float32Array::from(values));
+    for (i, size) in shape.iter().enumerate().skip(1).rev() {
+        let field = Arc::new(Field::new("item", array.data_type().clone(), true));
+        // only the outermost list has nulls
+        let nulls = if i == 1 { nulls.clone() } else { None };
+        array = Arc::new(FixedSizeListArray::new(field, *size as i32, array, nulls));
+    }
+    Ok(array)
+}
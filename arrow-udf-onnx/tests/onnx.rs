@@ -0,0 +1,312 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow_array::{
+    types::Float32Type, Array, FixedSizeListArray, Float32Array, Int32Array, RecordBatch,
+};
+use arrow_cast::pretty::pretty_format_batches;
+use arrow_schema::{DataType, Field, Schema};
+use arrow_udf_onnx::{Mapping, Runtime};
+use prost::Message;
+use tract_onnx::pb::{
+    tensor_proto, tensor_shape_proto, type_proto, GraphProto, ModelProto, NodeProto,
+    OperatorSetIdProto, TensorProto, TensorShapeProto, TypeProto, ValueInfoProto,
+};
+
+#[test]
+fn test_scale() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function("scale", &scale_model(&[]), Mapping::default())
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Float32, true)]);
+    let arg0 = Float32Array::from(vec![Some(1.0), None, Some(-1.5)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("scale", &input).unwrap();
+    check(
+        &[output],
+        r#"
++-------+
+| scale |
++-------+
+| 2.0   |
+|       |
+| -3.0  |
++-------+"#,
+    );
+}
+
+#[test]
+fn test_fixed_size_list() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function("scale", &scale_model(&[2]), Mapping::default())
+        .unwrap();
+
+    let array = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+        vec![
+            Some(vec![Some(1.0), Some(2.0)]),
+            None,
+            Some(vec![Some(3.0), Some(4.0)]),
+        ],
+        2,
+    );
+    let schema = Schema::new(vec![Field::new("x", array.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+
+    let output = runtime.call("scale", &input).unwrap();
+    check(
+        &[output],
+        r#"
++------------+
+| scale      |
++------------+
+| [2.0, 4.0] |
+|            |
+| [6.0, 8.0] |
++------------+"#,
+    );
+
+    // sliced input
+    let output = runtime.call("scale", &input.slice(2, 1)).unwrap();
+    check(
+        &[output],
+        r#"
++------------+
+| scale      |
++------------+
+| [6.0, 8.0] |
++------------+"#,
+    );
+}
+
+#[test]
+fn test_empty_batch() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function("scale", &scale_model(&[2]), Mapping::default())
+        .unwrap();
+
+    let data_type = DataType::new_fixed_size_list(DataType::Float32, 2, true);
+    let schema = Schema::new(vec![Field::new("x", data_type.clone(), true)]);
+    let output = runtime
+        .call("scale", &RecordBatch::new_empty(Arc::new(schema)))
+        .unwrap();
+    assert_eq!(output.num_rows(), 0);
+    assert_eq!(output.schema().field(0).data_type(), &data_type);
+}
+
+#[test]
+fn test_mapping() {
+    // outputs: diff = a - b, sum = a + b
+    let model = model(
+        vec![
+            node("Sub", &["a", "b"], "diff"),
+            node("Add", &["a", "b"], "sum"),
+        ],
+        vec![],
+        vec![value_info("a", &[]), value_info("b", &[])],
+        vec![value_info("diff", &[]), value_info("sum", &[])],
+    );
+
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function("diff", &model, Mapping::default())
+        .unwrap();
+    runtime
+        .add_function(
+            "reversed_diff",
+            &model,
+            Mapping::default().input("b").input("a"),
+        )
+        .unwrap();
+    runtime
+        .add_function("sum", &model, Mapping::default().output("sum"))
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Float32, true),
+        Field::new("y", DataType::Float32, true),
+    ]);
+    let arg0 = Float32Array::from(vec![5.0, 1.0]);
+    let arg1 = Float32Array::from(vec![3.0, 2.0]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("diff", &input).unwrap();
+    let expected = Float32Array::from(vec![2.0, -1.0]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+
+    let output = runtime.call("reversed_diff", &input).unwrap();
+    let expected = Float32Array::from(vec![-2.0, 1.0]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+
+    let output = runtime.call("sum", &input).unwrap();
+    let expected = Float32Array::from(vec![8.0, 3.0]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+}
+
+#[test]
+fn test_from_file() {
+    let path = std::env::temp_dir().join("arrow-udf-onnx-test-scale.onnx");
+    std::fs::write(&path, scale_model(&[])).unwrap();
+
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function_from_file("scale", &path, Mapping::default())
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Float32, true)]);
+    let arg0 = Float32Array::from(vec![1.0]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("scale", &input).unwrap();
+    let expected = Float32Array::from(vec![2.0]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+}
+
+#[test]
+fn test_error() {
+    let mut runtime = Runtime::new();
+    let err = runtime
+        .add_function("invalid", b"not a model", Mapping::default())
+        .unwrap_err();
+    assert_eq!(err.to_string(), "failed to load model");
+
+    let err = runtime
+        .add_function("scale", &scale_model(&[]), Mapping::default().input("z"))
+        .unwrap_err();
+    assert!(format!("{err:?}").contains("z"), "{err:?}");
+
+    runtime
+        .add_function("scale", &scale_model(&[]), Mapping::default())
+        .unwrap();
+
+    // unsupported type
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let err = runtime.call("scale", &input).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported data type: Int32");
+
+    // wrong number of columns
+    let input = RecordBatch::new_empty(Arc::new(Schema::empty()));
+    let err = runtime.call("scale", &input).unwrap_err();
+    assert_eq!(err.to_string(), "expect 1 columns, got 0");
+}
+
+#[test]
+fn test_del_function() {
+    let mut runtime = Runtime::new();
+    runtime
+        .add_function("scale", &scale_model(&[]), Mapping::default())
+        .unwrap();
+    runtime.del_function("scale").unwrap();
+
+    let input = RecordBatch::new_empty(Arc::new(Schema::empty()));
+    let err = runtime.call("scale", &input).unwrap_err();
+    assert_eq!(err.to_string(), "function not found");
+    assert!(runtime.del_function("scale").is_err());
+}
+
+/// Returns a model that computes `y = x * 2`, where `x` has the shape `[N, ...dims]`.
+fn scale_model(dims: &[i64]) -> Vec<u8> {
+    let two = TensorProto {
+        name: "two".into(),
+        data_type: tensor_proto::DataType::Float as i32,
+        float_data: vec![2.0],
+        ..Default::default()
+    };
+    model(
+        vec![node("Mul", &["x", "two"], "y")],
+        vec![two],
+        vec![value_info("x", dims)],
+        vec![value_info("y", dims)],
+    )
+}
+
+/// Returns an encoded ONNX model.
+fn model(
+    node: Vec<NodeProto>,
+    initializer: Vec<TensorProto>,
+    input: Vec<ValueInfoProto>,
+    output: Vec<ValueInfoProto>,
+) -> Vec<u8> {
+    let model = ModelProto {
+        ir_version: 7,
+        opset_import: vec![OperatorSetIdProto {
+            domain: String::new(),
+            version: 13,
+        }],
+        graph: Some(GraphProto {
+            name: "test".into(),
+            node,
+            initializer,
+            input,
+            output,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    model.encode_to_vec()
+}
+
+/// Returns a node with a single output.
+fn node(op_type: &str, inputs: &[&str], output: &str) -> NodeProto {
+    NodeProto {
+        name: output.into(),
+        op_type: op_type.into(),
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: vec![output.into()],
+        ..Default::default()
+    }
+}
+
+/// Returns a float tensor of the shape `[N, ...dims]`.
+fn value_info(name: &str, dims: &[i64]) -> ValueInfoProto {
+    use tensor_shape_proto::{dimension::Value, Dimension};
+
+    let dim = std::iter::once(Value::DimParam("N".into()))
+        .chain(dims.iter().map(|d| Value::DimValue(*d)))
+        .map(|value| Dimension {
+            denotation: String::new(),
+            value: Some(value),
+        })
+        .collect();
+    ValueInfoProto {
+        name: name.into(),
+        r#type: Some(TypeProto {
+            denotation: String::new(),
+            value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                elem_type: tensor_proto::DataType::Float as i32,
+                shape: Some(TensorShapeProto { dim }),
+            })),
+        }),
+        doc_string: String::new(),
+    }
+}
+
+/// Compare the actual output with the expected output.
+#[track_caller]
+fn check(actual: &[RecordBatch], expect: &str) {
+    assert_eq!(
+        expect.trim(),
+        pretty_format_batches(actual).unwrap().to_string(),
+    );
+}
@@ -0,0 +1,110 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Configuration and secrets of functions.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// The replacement of secret values in errors.
+const REDACTED: &str = "***";
+
+/// Environment variables and secrets passed to a function, such as endpoints and API keys.
+///
+/// They are set by [`Runtime::set_env`] when registering a function, and exposed to the
+/// function in the `ctx.env` dict, so that they are not hardcoded in the source code.
+/// Values of secrets are replaced by `***` in the errors of the function and in the
+/// [`Debug`] output.
+///
+/// # Examples
+///
+/// ```
+/// # use arrow_udf_python::Env;
+/// let env = Env::new()
+///     .with_var("ENDPOINT", "https://api.example.com")
+///     .with_secret("API_KEY", "s3cr3t");
+/// assert_eq!(env.get("API_KEY"), Some("s3cr3t"));
+/// assert!(!format!("{env:?}").contains("s3cr3t"));
+/// ```
+///
+/// [`Runtime::set_env`]: crate::Runtime::set_env
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Env {
+    vars: BTreeMap<String, String>,
+    secrets: BTreeSet<String>,
+}
+
+impl Env {
+    /// Create an empty environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a variable.
+    pub fn with_var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.secrets.remove(&key);
+        self.vars.insert(key, value.into());
+        self
+    }
+
+    /// Add a secret, whose value is redacted in errors.
+    pub fn with_secret(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.secrets.insert(key.clone());
+        self.vars.insert(key, value.into());
+        self
+    }
+
+    /// Returns the value of a variable or secret.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.vars.get(key).map(|s| s.as_str())
+    }
+
+    /// Returns whether there are no variables or secrets.
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Returns all variables and secrets.
+    pub(crate) fn vars(&self) -> &BTreeMap<String, String> {
+        &self.vars
+    }
+
+    /// Replaces the values of secrets in `s` by `***`.
+    pub(crate) fn redact(&self, s: &str) -> String {
+        let mut s = s.to_string();
+        for key in &self.secrets {
+            match self.vars.get(key) {
+                Some(value) if !value.is_empty() => s = s.replace(value.as_str(), REDACTED),
+                _ => {}
+            }
+        }
+        s
+    }
+}
+
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.vars.iter().map(|(key, value)| {
+                let value = match self.secrets.contains(key) {
+                    true => REDACTED,
+                    false => value.as_str(),
+                };
+                (key, value)
+            }))
+            .finish()
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Structured errors of Python functions.
+
+use crate::Env;
+use pyo3::{PyErr, Python};
+use std::fmt;
+
+/// An error raised by a Python function.
+///
+/// It is returned as the source of [`anyhow::Error`] from calling functions,
+/// and can be retrieved by [`anyhow::Error::downcast_ref`]:
+///
+/// ```ignore
+/// match runtime.call("div", &input) {
+///     Ok(output) => ...,
+///     Err(e) => match e.downcast_ref::<UdfError>() {
+///         Some(UdfError::Exception { row, type_name, traceback, .. }) => ...,
+///         _ => ...,
+///     },
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UdfError {
+    /// The function raised an exception when called on a row.
+    Exception {
+        /// The index of the input row.
+        row: usize,
+        /// The type name of the exception, e.g. `ZeroDivisionError`.
+        type_name: String,
+        /// The message of the exception, i.e. `str(exception)`.
+        message: String,
+        /// The formatted traceback, or an empty string if not available.
+        traceback: String,
+    },
+}
+
+impl UdfError {
+    /// Creates an error from the exception raised on the `row`.
+    pub(crate) fn from_pyerr(py: Python<'_>, err: &PyErr, row: usize) -> Self {
+        let type_name = err
+            .get_type(py)
+            .name()
+            .map_or_else(|_| "Exception".into(), |s| s.to_string());
+        let message = err.value(py).to_string();
+        let traceback = err
+            .traceback(py)
+            .and_then(|tb| tb.format().ok())
+            .unwrap_or_default();
+        UdfError::Exception {
+            row,
+            type_name,
+            message,
+            traceback,
+        }
+    }
+
+    /// Replaces the values of secrets in the message and traceback.
+    pub(crate) fn redact(mut self, env: &Env) -> Self {
+        match &mut self {
+            UdfError::Exception {
+                message, traceback, ..
+            } => {
+                *message = env.redact(message);
+                *traceback = env.redact(traceback);
+            }
+        }
+        self
+    }
+
+    /// Adds `offset` to the row index, for errors from a slice of the input.
+    pub(crate) fn with_row_offset(mut self, offset: usize) -> Self {
+        match &mut self {
+            UdfError::Exception { row, .. } => *row += offset,
+        }
+        self
+    }
+}
+
+impl fmt::Display for UdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UdfError::Exception {
+                row,
+                type_name,
+                message,
+                ..
+            } => write!(f, "{type_name}: {message} (at row {row})"),
+        }
+    }
+}
+
+impl std::error::Error for UdfError {}
@@ -0,0 +1,100 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Custom extension types.
+
+use anyhow::Result;
+use arrow_array::{Array, ArrayRef};
+use arrow_schema::Field;
+use pyo3::{PyObject, Python};
+use std::fmt::Debug;
+
+/// Converts values of an Arrow extension type to and from Python objects.
+///
+/// A field is of the extension type if its `ARROW:extension:name` metadata equals [`name`].
+/// Codecs are registered with [`Builder::extension_type`], and are used for arguments and
+/// return values of the type, including those nested in lists, structs and unions.
+///
+/// A codec registered with the name `arrowudf.json` or `arrowudf.decimal` overrides the
+/// built-in conversion of that type.
+///
+/// # Examples
+///
+/// ```
+/// # use arrow_udf_python::{ExtensionTypeCodec, Runtime};
+/// # use arrow_array::{cast::AsArray, Array, ArrayRef, FixedSizeBinaryArray};
+/// # use arrow_schema::Field;
+/// # use pyo3::types::{IntoPyDict, PyBytes};
+/// # use pyo3::{PyObject, Python};
+/// # use std::sync::Arc;
+/// /// `arrow.uuid` values as `uuid.UUID` objects.
+/// #[derive(Debug)]
+/// struct Uuid;
+///
+/// impl ExtensionTypeCodec for Uuid {
+///     fn name(&self) -> &str {
+///         "arrow.uuid"
+///     }
+///
+///     fn to_pyobject(
+///         &self,
+///         py: Python<'_>,
+///         _field: &Field,
+///         array: &dyn Array,
+///         i: usize,
+///     ) -> anyhow::Result<PyObject> {
+///         let bytes = PyBytes::new(py, array.as_fixed_size_binary().value(i));
+///         let uuid = py.import("uuid")?.getattr("UUID")?;
+///         Ok(uuid.call((), Some([("bytes", bytes)].into_py_dict(py)))?.into())
+///     }
+///
+///     fn build_array(
+///         &self,
+///         py: Python<'_>,
+///         _field: &Field,
+///         values: &[PyObject],
+///     ) -> anyhow::Result<ArrayRef> {
+///         let mut bytes = Vec::with_capacity(values.len());
+///         for value in values {
+///             match value.is_none(py) {
+///                 true => bytes.push(None),
+///                 false => bytes.push(Some(value.getattr(py, "bytes")?.extract::<Vec<u8>>(py)?)),
+///             }
+///         }
+///         let array = FixedSizeBinaryArray::try_from_sparse_iter_with_size(bytes.into_iter(), 16)?;
+///         Ok(Arc::new(array))
+///     }
+/// }
+///
+/// let builder = Runtime::builder().extension_type(Uuid);
+/// ```
+///
+/// [`name`]: ExtensionTypeCodec::name
+/// [`Builder::extension_type`]: crate::Builder::extension_type
+pub trait ExtensionTypeCodec: Debug + Send + Sync {
+    /// Returns the name of the extension type.
+    fn name(&self) -> &str;
+
+    /// Converts the non-null element `i` of the storage array to a Python object.
+    fn to_pyobject(
+        &self,
+        py: Python<'_>,
+        field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> Result<PyObject>;
+
+    /// Builds a storage array of the field from Python objects, where `None` is null.
+    fn build_array(&self, py: Python<'_>, field: &Field, values: &[PyObject]) -> Result<ArrayRef>;
+}
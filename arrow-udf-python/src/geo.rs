@@ -0,0 +1,74 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Geometry types.
+
+use crate::extension::ExtensionTypeCodec;
+use anyhow::Result;
+use arrow_array::{cast::AsArray, Array, ArrayRef, BinaryArray, LargeBinaryArray};
+use arrow_schema::{DataType, Field};
+use pyo3::types::PyBytes;
+use pyo3::{PyObject, Python, ToPyObject};
+use std::sync::Arc;
+
+/// The extension name of GeoArrow WKB type.
+pub(crate) const GEOARROW_WKB_EXTENSION_NAME: &str = "geoarrow.wkb";
+
+/// Converts GeoArrow WKB values to shapely geometries.
+///
+/// If shapely can not be imported, e.g. in sandboxed mode, values are converted to `bytes`.
+#[derive(Debug)]
+pub(crate) struct WkbCodec;
+
+impl ExtensionTypeCodec for WkbCodec {
+    fn name(&self) -> &str {
+        GEOARROW_WKB_EXTENSION_NAME
+    }
+
+    fn to_pyobject(
+        &self,
+        py: Python<'_>,
+        _field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> Result<PyObject> {
+        let wkb = match array.data_type() {
+            DataType::LargeBinary => array.as_binary::<i64>().value(i),
+            _ => array.as_binary::<i32>().value(i),
+        };
+        let bytes = PyBytes::new(py, wkb);
+        match py.import("shapely.wkb") {
+            Ok(module) => Ok(module.getattr("loads")?.call1((bytes,))?.into()),
+            Err(_) => Ok(bytes.to_object(py)),
+        }
+    }
+
+    fn build_array(&self, py: Python<'_>, field: &Field, values: &[PyObject]) -> Result<ArrayRef> {
+        let mut wkbs = Vec::with_capacity(values.len());
+        for value in values {
+            if value.is_none(py) {
+                wkbs.push(None);
+            } else if let Ok(bytes) = value.as_ref(py).downcast::<PyBytes>() {
+                wkbs.push(Some(bytes.as_bytes().to_vec()));
+            } else {
+                let dumps = py.import("shapely.wkb")?.getattr("dumps")?;
+                wkbs.push(Some(dumps.call1((value,))?.extract::<Vec<u8>>()?));
+            }
+        }
+        Ok(match field.data_type() {
+            DataType::LargeBinary => Arc::new(LargeBinaryArray::from_iter(wkbs)),
+            _ => Arc::new(BinaryArray::from_iter(wkbs)),
+        })
+    }
+}
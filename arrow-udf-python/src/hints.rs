@@ -0,0 +1,208 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inferring the signature of a function from its Python type hints.
+
+use crate::pyarrow::{Converter, DECIMAL_EXTENSION_NAME, EXTENSION_KEY, JSON_EXTENSION_NAME};
+use anyhow::{bail, Result};
+use arrow_schema::{DataType, Field, IntervalUnit, TimeUnit};
+use std::fmt::Display;
+
+/// The argument and return types of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// The fields of the arguments.
+    pub args: Vec<Field>,
+    /// The field of the return value.
+    pub return_field: Field,
+}
+
+/// A Python type hint, as normalized by `_type_hints` in the interpreter.
+#[derive(Debug, Clone, PartialEq)]
+enum Hint {
+    Bool,
+    Int,
+    Float,
+    Str,
+    Bytes,
+    Decimal,
+    Timedelta,
+    Datetime,
+    Dict,
+    List(Option<Box<Hint>>),
+    /// `typing.Any`, a union of multiple types or a missing hint.
+    Any,
+    /// A type that has no corresponding Arrow type, e.g. a user defined class.
+    Other(String),
+}
+
+impl Hint {
+    fn parse(s: &str) -> Self {
+        match s {
+            "bool" => Self::Bool,
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "str" => Self::Str,
+            "bytes" => Self::Bytes,
+            "decimal.Decimal" => Self::Decimal,
+            "datetime.timedelta" => Self::Timedelta,
+            "datetime.datetime" => Self::Datetime,
+            "dict" => Self::Dict,
+            "list" => Self::List(None),
+            "any" => Self::Any,
+            _ => match s.strip_prefix("list[").and_then(|s| s.strip_suffix(']')) {
+                Some(inner) => Self::List(Some(Box::new(Self::parse(inner)))),
+                None => Self::Other(s.to_string()),
+            },
+        }
+    }
+
+    /// Returns the default Arrow field for values of this type.
+    fn default_field(&self, name: &str) -> Option<Field> {
+        let extension = |ext: &str| {
+            Field::new(name, DataType::Utf8, true)
+                .with_metadata([(EXTENSION_KEY.into(), ext.into())].into())
+        };
+        Some(match self {
+            Self::Bool => Field::new(name, DataType::Boolean, true),
+            Self::Int => Field::new(name, DataType::Int64, true),
+            Self::Float => Field::new(name, DataType::Float64, true),
+            Self::Str => Field::new(name, DataType::Utf8, true),
+            Self::Bytes => Field::new(name, DataType::Binary, true),
+            Self::Decimal => extension(DECIMAL_EXTENSION_NAME),
+            Self::Timedelta => {
+                Field::new(name, DataType::Interval(IntervalUnit::MonthDayNano), true)
+            }
+            Self::Datetime => {
+                Field::new(name, DataType::Timestamp(TimeUnit::Microsecond, None), true)
+            }
+            Self::Dict | Self::List(None) => extension(JSON_EXTENSION_NAME),
+            Self::List(Some(inner)) => Field::new_list(name, inner.default_field("item")?, true),
+            Self::Any | Self::Other(_) => return None,
+        })
+    }
+
+    /// Returns whether values of the field are converted to or from this type.
+    fn accepts(&self, field: &Field, converter: &Converter) -> bool {
+        if converter.extension_type(field).is_some() {
+            // the Python type of custom extension types is unknown
+            return true;
+        }
+        if converter.is_json(field) {
+            return matches!(self, Self::Dict | Self::List(None) | Self::Any);
+        }
+        if converter.is_decimal(field) {
+            return matches!(self, Self::Decimal | Self::Any);
+        }
+        match (self, field.data_type()) {
+            (Self::Any | Self::Other(_), _) => true,
+            (_, DataType::RunEndEncoded(_, values)) => self.accepts(values, converter),
+            (Self::Bool, DataType::Boolean) => true,
+            (Self::Int, t) => t.is_integer(),
+            (Self::Float, t) => t.is_floating(),
+            (Self::Str, DataType::Utf8 | DataType::LargeUtf8) => true,
+            (
+                Self::Bytes,
+                DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_),
+            ) => true,
+            (Self::Decimal, DataType::Decimal128(_, _)) => true,
+            (Self::Timedelta, DataType::Interval(IntervalUnit::MonthDayNano)) => true,
+            (Self::Datetime, DataType::Timestamp(_, _)) => true,
+            (Self::List(None), DataType::List(_)) => true,
+            (Self::List(Some(inner)), DataType::List(item)) => inner.accepts(item, converter),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Hint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool => write!(f, "bool"),
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Str => write!(f, "str"),
+            Self::Bytes => write!(f, "bytes"),
+            Self::Decimal => write!(f, "decimal.Decimal"),
+            Self::Timedelta => write!(f, "datetime.timedelta"),
+            Self::Datetime => write!(f, "datetime.datetime"),
+            Self::Dict => write!(f, "dict"),
+            Self::List(None) => write!(f, "list"),
+            Self::List(Some(inner)) => write!(f, "list[{inner}]"),
+            Self::Any => write!(f, "typing.Any"),
+            Self::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Infers the signature of a function from its type hints, or validates the declared one.
+///
+/// `args` are the names and type hints of the positional arguments, and `ret` is the type hint
+/// of the return value. Missing hints accept any declared type but can not be inferred.
+pub(crate) fn infer(
+    converter: &Converter,
+    name: &str,
+    args: &[(String, Option<String>)],
+    ret: Option<&str>,
+    declared: Option<&Signature>,
+) -> Result<Signature> {
+    let parse = |hint: Option<&str>| hint.map_or(Hint::Any, Hint::parse);
+    let Some(declared) = declared else {
+        let mut fields = Vec::with_capacity(args.len());
+        for (arg, hint) in args {
+            let Some(hint) = hint else {
+                bail!("can not infer the type of argument `{arg}` without a type hint");
+            };
+            let hint = Hint::parse(hint);
+            let Some(field) = hint.default_field(arg) else {
+                bail!("can not infer the type of argument `{arg}` from type hint `{hint}`");
+            };
+            fields.push(field);
+        }
+        let Some(hint) = ret.map(Hint::parse) else {
+            bail!("can not infer the return type of `{name}` without a type hint");
+        };
+        let Some(return_field) = hint.default_field(name) else {
+            bail!("can not infer the return type of `{name}` from type hint `{hint}`");
+        };
+        return Ok(Signature {
+            args: fields,
+            return_field,
+        });
+    };
+    if declared.args.len() != args.len() {
+        bail!(
+            "function `{name}` takes {} arguments, but {} are declared",
+            args.len(),
+            declared.args.len()
+        );
+    }
+    for ((arg, hint), field) in args.iter().zip(&declared.args) {
+        let hint = parse(hint.as_deref());
+        if !hint.accepts(field, converter) {
+            bail!(
+                "argument `{arg}` is declared as {} but has type hint `{hint}`",
+                field.data_type()
+            );
+        }
+    }
+    let hint = parse(ret);
+    if !hint.accepts(&declared.return_field, converter) {
+        bail!(
+            "return type of `{name}` is declared as {} but has type hint `{hint}`",
+            declared.return_field.data_type()
+        );
+    }
+    Ok(declared.clone())
+}
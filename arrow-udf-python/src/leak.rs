@@ -0,0 +1,80 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Leak check of Python objects created by evaluations.
+//!
+//! Enabled by the `leak_check` feature. It is meant for debugging, as a full garbage collection
+//! runs before and after each evaluation.
+
+use pyo3::{PyResult, Python};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The leaks of a function found by the leak check.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LeakReport {
+    /// The number of checked evaluations.
+    pub evals: u64,
+    /// The number of evaluations that left more memory blocks allocated than before.
+    pub leaking_evals: u64,
+    /// The total number of memory blocks left allocated by the leaking evaluations.
+    ///
+    /// Each Python object takes at least one block, so this is an upper bound of leaked objects.
+    pub leaked_blocks: u64,
+}
+
+/// The leak check of a function.
+#[derive(Debug, Default)]
+pub(crate) struct LeakCheck {
+    evals: AtomicU64,
+    leaking_evals: AtomicU64,
+    leaked_blocks: AtomicU64,
+}
+
+impl LeakCheck {
+    /// Records an evaluation with the number of allocated blocks before and after it.
+    pub fn record(&self, before: usize, after: usize) {
+        self.evals.fetch_add(1, Ordering::Relaxed);
+        if after > before {
+            self.leaking_evals.fetch_add(1, Ordering::Relaxed);
+            self.leaked_blocks
+                .fetch_add((after - before) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns a snapshot of the report.
+    pub fn get(&self) -> LeakReport {
+        LeakReport {
+            evals: self.evals.load(Ordering::Relaxed),
+            leaking_evals: self.leaking_evals.load(Ordering::Relaxed),
+            leaked_blocks: self.leaked_blocks.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Resets the report.
+    pub fn reset(&self) {
+        self.evals.store(0, Ordering::Relaxed);
+        self.leaking_evals.store(0, Ordering::Relaxed);
+        self.leaked_blocks.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of memory blocks allocated by the interpreter after collecting garbage.
+///
+/// It must be called outside of the evaluation, after the objects owned by the GIL pool of
+/// the evaluation are released.
+pub(crate) fn allocated_blocks(py: Python<'_>) -> PyResult<usize> {
+    py.import("gc")?.call_method0("collect")?;
+    py.import("sys")?
+        .call_method0("getallocatedblocks")?
+        .extract()
+}
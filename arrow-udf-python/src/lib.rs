@@ -15,19 +15,53 @@
 #![doc = include_str!("../README.md")]
 
 use self::interpreter::SubInterpreter;
-use anyhow::{Context, Result};
+use self::pyarrow::Converter;
+use self::requirement::Requirement;
+use self::stats::{Meter, Stats};
+use anyhow::{bail, ensure, Context, Result};
 use arrow_array::builder::Int32Builder;
-use arrow_array::{Array, ArrayRef, RecordBatch};
-use arrow_schema::{DataType, Field, Schema, SchemaRef};
-use pyo3::types::{PyIterator, PyModule, PyTuple};
-use pyo3::{Py, PyObject, PyResult};
+use arrow_array::{make_array, Array, ArrayRef, BooleanArray, RecordBatch};
+use arrow_buffer::NullBuffer;
+use arrow_schema::{DataType, Field, FieldRef, Schema, SchemaRef};
+use pyo3::types::{IntoPyDict, PyIterator, PyModule, PyTuple};
+use pyo3::{Py, PyAny, PyObject, PyResult, Python};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 // #[cfg(Py_3_12)]
+mod cancel;
+mod env;
+mod error;
+mod extension;
+#[cfg(feature = "geo")]
+mod geo;
+mod hints;
 mod interpreter;
+#[cfg(feature = "leak_check")]
+mod leak;
+mod pool;
 mod pyarrow;
+mod requirement;
+mod stats;
+
+pub use self::cancel::CancellationToken;
+pub use self::env::Env;
+pub use self::error::UdfError;
+pub use self::extension::ExtensionTypeCodec;
+pub use self::hints::Signature;
+#[cfg(feature = "leak_check")]
+pub use self::leak::LeakReport;
+pub use self::pool::RuntimePool;
+pub use self::pyarrow::{IntOverflowPolicy, InvalidStringPolicy};
+pub use self::stats::{FunctionStats, Quota};
+pub use pyo3;
+
+/// The number of rows between releasing the GIL, so that other threads waiting for the GIL
+/// of the same interpreter can run during a long evaluation.
+const YIELD_INTERVAL: usize = 1024;
 
 /// The Python UDF runtime.
 ///
@@ -35,12 +69,18 @@ mod pyarrow;
 pub struct Runtime {
     interpreter: SubInterpreter,
     functions: HashMap<String, Function>,
+    aggregates: HashMap<String, Aggregate>,
+    converter: Converter,
+    sandboxed: bool,
+    track_memory: bool,
+    batch_size: Option<usize>,
 }
 
 impl Debug for Runtime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Runtime")
             .field("functions", &self.functions.keys())
+            .field("aggregates", &self.aggregates.keys())
             .finish()
     }
 }
@@ -48,15 +88,45 @@ impl Debug for Runtime {
 /// A user defined function.
 struct Function {
     function: PyObject,
-    return_type: DataType,
+    return_field: FieldRef,
+    mode: CallMode,
+    /// Whether the function takes a keyword-only `ctx` argument.
+    context: bool,
+    /// Variables and secrets exposed in `ctx.env`.
+    env: Env,
+    stats: Stats,
+    #[cfg(feature = "leak_check")]
+    leaks: leak::LeakCheck,
+}
+
+/// A user defined aggregate function.
+struct Aggregate {
+    state_field: FieldRef,
+    output_field: FieldRef,
     mode: CallMode,
+    create_state: PyObject,
+    accumulate: PyObject,
+    retract: Option<PyObject>,
+    merge: Option<PyObject>,
+    finish: Option<PyObject>,
 }
 
 /// A builder for `Runtime`.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Builder {
     sandboxed: bool,
     removed_symbols: Vec<String>,
+    legacy_json: bool,
+    legacy_decimal: bool,
+    invalid_string: InvalidStringPolicy,
+    int_overflow: IntOverflowPolicy,
+    venv: Option<PathBuf>,
+    track_memory: bool,
+    extension_types: HashMap<String, Arc<dyn ExtensionTypeCodec>>,
+    timezone: Option<String>,
+    preload: Vec<String>,
+    batch_size: Option<usize>,
+    null_missing_fields: bool,
 }
 
 impl Builder {
@@ -94,9 +164,197 @@ impl Builder {
         self
     }
 
+    /// Set whether to treat `LargeUtf8` as JSON type.
+    ///
+    /// JSON values are identified by the extension type `arrowudf.json` in the field metadata.
+    /// Enable this option to also treat `LargeUtf8` fields without extension metadata as JSON,
+    /// which is the convention used by previous versions.
+    ///
+    /// The default is `false`.
+    pub fn legacy_json(mut self, legacy_json: bool) -> Self {
+        self.legacy_json = legacy_json;
+        self
+    }
+
+    /// Set whether to treat `LargeBinary` as decimal type.
+    ///
+    /// Decimal values are read from `Decimal128` arrays, or from strings with the extension type
+    /// `arrowudf.decimal` in the field metadata.
+    /// Enable this option to also treat `LargeBinary` fields without extension metadata as decimal
+    /// strings, which is the convention used by previous versions.
+    ///
+    /// The default is `false`.
+    pub fn legacy_decimal(mut self, legacy_decimal: bool) -> Self {
+        self.legacy_decimal = legacy_decimal;
+        self
+    }
+
+    /// Set whether to convert struct fields missing in returned values to null.
+    ///
+    /// Functions returning structs can return objects with the fields as attributes, or dicts
+    /// keyed by the field names. Other attributes and keys are ignored. Enable this option so that
+    /// adding a field to the return type doesn't break functions that don't return it yet.
+    ///
+    /// The default is `false`, which returns an error for missing fields.
+    pub fn null_missing_fields(mut self, null_missing_fields: bool) -> Self {
+        self.null_missing_fields = null_missing_fields;
+        self
+    }
+
+    /// Set how to handle strings that can not be converted, such as Python `str` with lone
+    /// surrogates returned by functions.
+    ///
+    /// The default is [`InvalidStringPolicy::Error`].
+    pub fn invalid_string_policy(mut self, policy: InvalidStringPolicy) -> Self {
+        self.invalid_string = policy;
+        self
+    }
+
+    /// Set how to handle integers returned by functions that are out of the range of the
+    /// integer return type, e.g. `2**31` for `Int32`.
+    ///
+    /// The default is [`IntOverflowPolicy::Error`], which reports the value and its row.
+    pub fn int_overflow_policy(mut self, policy: IntOverflowPolicy) -> Self {
+        self.int_overflow = policy;
+        self
+    }
+
+    /// Register a codec for a custom extension type.
+    ///
+    /// Arguments and return values of the extension type are converted by the codec instead
+    /// of by their storage type. See [`ExtensionTypeCodec`] for an example.
+    pub fn extension_type(mut self, codec: impl ExtensionTypeCodec + 'static) -> Self {
+        self.extension_types
+            .insert(codec.name().to_string(), Arc::new(codec));
+        self
+    }
+
+    /// Set the session time zone, e.g. `Asia/Shanghai` or `+08:00`.
+    ///
+    /// Timestamps with a time zone (`timestamptz`) are passed to functions as aware
+    /// `datetime.datetime` in the session time zone, and naive datetimes returned for them
+    /// are interpreted in it. Aware datetimes returned for timestamps without a time zone
+    /// (`timestamp`) are converted to the wall clock of it.
+    ///
+    /// If not set, the time zone of the arrow field is used, and returning aware datetimes
+    /// for timestamps without a time zone is an error. It can be overridden for each call
+    /// by [`EvalContext::with_timezone`].
+    pub fn timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+
+    /// Set whether to track the peak memory of functions with `tracemalloc`.
+    ///
+    /// The memory is reported by [`Runtime::stats`] and limited by [`Quota::max_memory`].
+    /// Tracking slows down memory allocations in Python.
+    ///
+    /// The default is `false`.
+    pub fn track_memory(mut self, track_memory: bool) -> Self {
+        self.track_memory = track_memory;
+        self
+    }
+
+    /// Use the packages of a virtual environment or conda environment at `prefix`.
+    ///
+    /// The `site-packages` directory of the environment is put in front of `sys.path` of this
+    /// runtime, so that its packages take precedence over the system ones. Other runtimes are
+    /// not affected, so functions with conflicting requirements can be added to runtimes of
+    /// different environments.
+    ///
+    /// The environment must be created by the same Python version as the embedded interpreter.
+    /// The interpreter itself is linked at build time, and can be selected by setting the
+    /// `PYO3_PYTHON` environment variable to the Python executable of the environment.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use arrow_udf_python::Runtime;
+    /// let runtime = Runtime::builder().venv("/opt/udf/.venv").build().unwrap();
+    /// ```
+    pub fn venv(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.venv = Some(prefix.into());
+        self
+    }
+
+    /// Set the target number of rows of each call to user code.
+    ///
+    /// Larger inputs are sliced into batches of this size, so that the GIL is released
+    /// between them instead of being held for the whole input. Small inputs passed to
+    /// [`Runtime::call_batches`] are coalesced up to this size to reduce the overhead per call.
+    /// The outputs are stitched back to the shape of the inputs.
+    ///
+    /// By default, inputs are evaluated as they are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is 0.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Import a module when the runtime is built. See [`Runtime::preload`].
+    ///
+    /// Runtimes of a [`RuntimePool`] are built in parallel, so preloading modules here
+    /// also warms up every runtime of the pool before the first call.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use arrow_udf_python::Runtime;
+    /// let pool = Runtime::builder().preload("numpy").preload("pandas").build_pool(4).unwrap();
+    /// ```
+    pub fn preload(mut self, module: &str) -> Self {
+        self.preload.push(module.to_string());
+        self
+    }
+
+    /// Build a [`RuntimePool`] of `size` runtimes to evaluate functions in parallel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn build_pool(self, size: usize) -> Result<RuntimePool> {
+        RuntimePool::new(self, size)
+    }
+
     /// Build the `Runtime`.
     pub fn build(self) -> Result<Runtime> {
         let interpreter = SubInterpreter::new()?;
+        if let Some(prefix) = &self.venv {
+            let prefix = prefix.to_str().context("invalid path of environment")?;
+            interpreter.with_gil(|py| {
+                let locals = [("prefix", prefix)].into_py_dict(py);
+                py.run(
+                    r#"
+import os, site, sys
+
+version = f'python{sys.version_info.major}.{sys.version_info.minor}'
+site_packages = None
+for path in [os.path.join(prefix, 'lib', version, 'site-packages'), os.path.join(prefix, 'Lib', 'site-packages')]:
+    if os.path.isdir(path):
+        site_packages = path
+        break
+if site_packages is None:
+    raise FileNotFoundError(f'site-packages of {version} is not found in {prefix}')
+
+# add the directory and its .pth files, and move them to the front
+original_path = list(sys.path)
+site.addsitedir(site_packages)
+added = []
+for path in sys.path:
+    if path not in original_path:
+        added.append(path)
+sys.path[:] = added + original_path
+"#,
+                    None,
+                    Some(locals),
+                )
+            })
+            .context("failed to activate the environment")?;
+        }
         interpreter.run(
             r#"
 # internal use for json types
@@ -105,8 +363,86 @@ import json
 # an internal class used for struct input arguments
 class Struct:
     pass
+
+# an internal class used for the `ctx` argument
+class Context:
+    pass
+
+# internal use for resource accounting
+from time import thread_time_ns as _thread_time_ns
+
+# internal use for inferring signatures from type hints
+import types as _types, typing as _typing
+
+def _type_hints(f):
+    def name(t):
+        if t is None or t is type(None):
+            return 'none'
+        origin = _typing.get_origin(t)
+        args = [a for a in _typing.get_args(t) if a is not type(None)]
+        if origin is _typing.Union or origin is _types.UnionType:
+            # `Optional[T]` and `T | None` are `T`, other unions can be anything
+            return name(args[0]) if len(args) == 1 else 'any'
+        if origin is list:
+            return f'list[{name(args[0])}]' if args else 'list'
+        if origin is not None:
+            return name(origin)
+        if t is _typing.Any:
+            return 'any'
+        if isinstance(t, type):
+            return t.__qualname__ if t.__module__ == 'builtins' else f'{t.__module__}.{t.__qualname__}'
+        return repr(t)
+    hints = _typing.get_type_hints(f)
+    code = f.__code__
+    args = [(a, name(hints[a]) if a in hints else None) for a in code.co_varnames[:code.co_argcount]]
+    return args, name(hints['return']) if 'return' in hints else None
+
+# internal use for timestamp types
+import datetime as _datetime, functools as _functools, zoneinfo as _zoneinfo
+
+_EPOCH = _datetime.datetime(1970, 1, 1)
+_EPOCH_UTC = _EPOCH.replace(tzinfo=_datetime.timezone.utc)
+
+@_functools.lru_cache
+def _timezone(name):
+    # fixed offsets like '+08:00', or names in the tz database like 'Asia/Shanghai'
+    if name[0] in '+-':
+        offset = name[1:].replace(':', '')
+        delta = _datetime.timedelta(hours=int(offset[:2]), minutes=int(offset[2:] or 0))
+        return _datetime.timezone(-delta if name[0] == '-' else delta)
+    return _zoneinfo.ZoneInfo(name)
+
+def _from_timestamp(micros, tz, session_tz):
+    delta = _datetime.timedelta(microseconds=micros)
+    if tz is None:
+        return _EPOCH + delta
+    return (_EPOCH_UTC + delta).astimezone(_timezone(session_tz or tz))
+
+def _to_timestamp(value, tz, session_tz):
+    if tz is None:
+        if value.tzinfo is not None:
+            if session_tz is None:
+                raise ValueError('can not convert an aware datetime to timestamp without time zone unless the session time zone is set')
+            value = value.astimezone(_timezone(session_tz)).replace(tzinfo=None)
+        delta = value - _EPOCH
+    else:
+        if value.tzinfo is None:
+            value = value.replace(tzinfo=_timezone(session_tz or tz))
+        delta = value - _EPOCH_UTC
+    return (delta.days * 86400 + delta.seconds) * 1000000 + delta.microseconds
 "#,
         )?;
+        if let Some(timezone) = &self.timezone {
+            interpreter
+                .with_gil(|py| -> Result<()> {
+                    py.eval("_timezone", None, None)?.call1((timezone,))?;
+                    Ok(())
+                })
+                .with_context(|| format!("invalid time zone: {timezone}"))?;
+        }
+        if self.track_memory {
+            interpreter.run("import tracemalloc as _tracemalloc\n_tracemalloc.start()")?;
+        }
         if self.sandboxed {
             let mut script = r#"
 # limit the modules that can be imported
@@ -145,10 +481,32 @@ del limited_import
             }
             interpreter.run(&script)?;
         }
-        Ok(Runtime {
+        #[allow(unused_mut)]
+        let mut extension_types = self.extension_types;
+        #[cfg(feature = "geo")]
+        extension_types
+            .entry(geo::GEOARROW_WKB_EXTENSION_NAME.to_string())
+            .or_insert_with(|| Arc::new(geo::WkbCodec));
+        let runtime = Runtime {
             interpreter,
+            sandboxed: self.sandboxed,
+            track_memory: self.track_memory,
+            batch_size: self.batch_size,
             functions: HashMap::new(),
-        })
+            aggregates: HashMap::new(),
+            converter: Converter {
+                legacy_json: self.legacy_json,
+                legacy_decimal: self.legacy_decimal,
+                invalid_string: self.invalid_string,
+                int_overflow: self.int_overflow,
+                extension_types,
+                timezone: self.timezone,
+                null_missing_fields: self.null_missing_fields,
+            },
+        };
+        let modules: Vec<&str> = self.preload.iter().map(|s| s.as_str()).collect();
+        runtime.preload(&modules)?;
+        Ok(runtime)
     }
 }
 
@@ -164,63 +522,746 @@ impl Runtime {
     }
 
     /// Add a new function from Python code.
+    ///
+    /// The return type can be a [`DataType`], or a [`Field`] to specify an extension type.
+    ///
+    /// If the function declares a keyword-only argument `ctx`, it is called with an object
+    /// with the attributes `row`, `batch_id` and `metadata` from the [`EvalContext`]:
+    ///
+    /// ```python
+    /// def audit(x, *, ctx):
+    ///     return f"{ctx.metadata['query_id']}:{ctx.batch_id}:{ctx.row}:{x}"
+    /// ```
     pub fn add_function(
         &mut self,
         name: &str,
-        return_type: DataType,
+        return_type: impl IntoField,
         mode: CallMode,
         code: &str,
     ) -> Result<()> {
-        let function = self.interpreter.with_gil(|py| -> PyResult<PyObject> {
-            Ok(PyModule::from_code(py, code, "", "")?.getattr(name)?.into())
+        let (function, context) = self.interpreter.with_gil(|py| -> PyResult<_> {
+            let function = PyModule::from_code(py, code, "", "")?.getattr(name)?;
+            Ok((function.into(), takes_context(function)?))
         })?;
         let function = Function {
             function,
-            return_type,
+            return_field: return_type.into_field(name).into(),
             mode,
+            context,
+            env: Env::default(),
+            stats: Stats::default(),
+            #[cfg(feature = "leak_check")]
+            leaks: Default::default(),
         };
         self.functions.insert(name.to_string(), function);
         Ok(())
     }
 
-    /// Remove a function.
-    pub fn del_function(&mut self, name: &str) -> Result<()> {
-        let function = self.functions.remove(name).context("function not found")?;
-        self.interpreter.with_gil(|_| drop(function));
+    /// Add a new aggregate function from Python code.
+    ///
+    /// The code must define the following functions:
+    ///
+    /// - `create_state()`: returns the initial state.
+    /// - `accumulate(state, *args)`: returns the state after adding a row.
+    /// - `retract(state, *args)`: (optional) returns the state after removing a row,
+    ///   which is required by [`Runtime::accumulate_or_retract`] to maintain sliding windows
+    ///   without recomputing them.
+    /// - `merge(state1, state2)`: (optional) returns the state merged from two states.
+    /// - `finish(state)`: (optional) returns the output of a state.
+    ///   If not defined, the state is the output and `output_type` must be the same as `state_type`.
+    ///
+    /// States are passed between calls as arrow arrays of `state_type`, so they can be
+    /// checkpointed by the engine. `accumulate` and `retract` may modify the state in place
+    /// and return `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arrow_udf_python::{Runtime, CallMode};
+    /// # use arrow_schema::DataType;
+    /// let mut runtime = Runtime::new().unwrap();
+    /// runtime
+    ///     .add_aggregate(
+    ///         "sum",
+    ///         DataType::Int64,
+    ///         DataType::Int64,
+    ///         CallMode::ReturnNullOnNullInput,
+    ///         r#"
+    /// def create_state():
+    ///     return 0
+    ///
+    /// def accumulate(state, value):
+    ///     return state + value
+    ///
+    /// def retract(state, value):
+    ///     return state - value
+    /// "#,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn add_aggregate(
+        &mut self,
+        name: &str,
+        state_type: impl IntoField,
+        output_type: impl IntoField,
+        mode: CallMode,
+        code: &str,
+    ) -> Result<()> {
+        let state_field: FieldRef = state_type.into_field(name).into();
+        let output_field: FieldRef = output_type.into_field(name).into();
+        let aggregate = self.interpreter.with_gil(|py| -> Result<_> {
+            let module = PyModule::from_code(py, code, "", "")?;
+            let optional = |name: &str| -> PyResult<Option<PyObject>> {
+                match module.hasattr(name)? {
+                    true => Ok(Some(module.getattr(name)?.into())),
+                    false => Ok(None),
+                }
+            };
+            let finish = optional("finish")?;
+            if finish.is_none() && state_field.data_type() != output_field.data_type() {
+                bail!("`output_type` must be the same as `state_type` if `finish` is not defined");
+            }
+            Ok(Aggregate {
+                state_field,
+                output_field,
+                mode,
+                create_state: module.getattr("create_state")?.into(),
+                accumulate: module.getattr("accumulate")?.into(),
+                retract: optional("retract")?,
+                merge: optional("merge")?,
+                finish,
+            })
+        })?;
+        let old = self.aggregates.insert(name.to_string(), aggregate);
+        self.interpreter.with_gil(|_| drop(old));
         Ok(())
     }
 
-    /// Call the Python UDF.
-    pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
-        let function = self.functions.get(name).context("function not found")?;
-        // convert each row to python objects and call the function
-        let array = self.interpreter.with_gil(|py| -> Result<ArrayRef> {
-            let mut results = Vec::with_capacity(input.num_rows());
-            let mut row = Vec::with_capacity(input.num_columns());
+    /// Create the initial state of an aggregate function, as an array of length 1.
+    pub fn create_state(&self, name: &str) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        self.interpreter.with_gil(|py| {
+            let state = aggregate.create_state.call0(py)?;
+            self.converter
+                .build_array(&aggregate.state_field, py, &[state])
+                .context("failed to build arrow array from state")
+        })
+    }
+
+    /// Accumulate the rows of `input` into `state`, and return the new state.
+    ///
+    /// `state` is an array of length 1 returned by [`Runtime::create_state`] or other functions.
+    pub fn accumulate(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.accumulate_impl(name, state, None, input)
+    }
+
+    /// Accumulate or retract the rows of `input` into `state`, and return the new state.
+    ///
+    /// Rows whose value in `ops` is true are retracted, and others are accumulated.
+    pub fn accumulate_or_retract(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        ops: &BooleanArray,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.accumulate_impl(name, state, Some(ops), input)
+    }
+
+    fn accumulate_impl(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        ops: Option<&BooleanArray>,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        self.interpreter.with_gil(|py| {
+            let mut state = self
+                .converter
+                .get_pyobject(py, &aggregate.state_field, state, 0)?;
+            // the state followed by the arguments
+            let mut row = Vec::with_capacity(input.num_columns() + 1);
+            let schema = input.schema();
             for i in 0..input.num_rows() {
                 row.clear();
-                for column in input.columns() {
-                    let pyobj = pyarrow::get_pyobject(py, column, i)?;
-                    row.push(pyobj);
+                row.push(state.clone_ref(py));
+                for (column, field) in input.columns().iter().zip(schema.fields()) {
+                    row.push(self.converter.get_pyobject(py, field, column, i)?);
                 }
-                if function.mode == CallMode::ReturnNullOnNullInput
-                    && row.iter().any(|v| v.is_none(py))
+                if aggregate.mode == CallMode::ReturnNullOnNullInput
+                    && row[1..].iter().any(|v| v.is_none(py))
                 {
-                    results.push(py.None());
                     continue;
                 }
+                let function = match ops {
+                    Some(ops) if ops.is_valid(i) && ops.value(i) => aggregate
+                        .retract
+                        .as_ref()
+                        .context("function does not support retraction")?,
+                    _ => &aggregate.accumulate,
+                };
                 let args = PyTuple::new(py, row.drain(..));
-                let result = function.function.call1(py, args)?;
+                let new_state = function
+                    .call1(py, args)
+                    .map_err(|e| UdfError::from_pyerr(py, &e, i))?;
+                // the state may be modified in place
+                if !new_state.is_none(py) {
+                    state = new_state;
+                }
+            }
+            self.converter
+                .build_array(&aggregate.state_field, py, &[state])
+                .context("failed to build arrow array from state")
+        })
+    }
+
+    /// Merge the states into one, and return it as an array of length 1.
+    ///
+    /// Returns the initial state if `states` is empty.
+    pub fn merge(&self, name: &str, states: &dyn Array) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        let merge = aggregate
+            .merge
+            .as_ref()
+            .context("function does not support merging")?;
+        self.interpreter.with_gil(|py| {
+            let mut merged: Option<PyObject> = None;
+            for i in 0..states.len() {
+                let state = self
+                    .converter
+                    .get_pyobject(py, &aggregate.state_field, states, i)?;
+                merged = Some(match merged {
+                    None => state,
+                    Some(merged) => merge.call1(py, (merged, state))?,
+                });
+            }
+            let state = match merged {
+                Some(state) => state,
+                None => aggregate.create_state.call0(py)?,
+            };
+            self.converter
+                .build_array(&aggregate.state_field, py, &[state])
+                .context("failed to build arrow array from state")
+        })
+    }
+
+    /// Get the output of each state.
+    pub fn finish(&self, name: &str, states: &dyn Array) -> Result<ArrayRef> {
+        let aggregate = self.aggregates.get(name).context("function not found")?;
+        let Some(finish) = &aggregate.finish else {
+            return Ok(make_array(states.to_data()));
+        };
+        self.interpreter.with_gil(|py| {
+            let mut results = Vec::with_capacity(states.len());
+            for i in 0..states.len() {
+                let state = self
+                    .converter
+                    .get_pyobject(py, &aggregate.state_field, states, i)?;
+                let result = finish
+                    .call1(py, (state,))
+                    .map_err(|e| UdfError::from_pyerr(py, &e, i))?;
                 results.push(result);
             }
-            let result = pyarrow::build_array(&function.return_type, py, &results)?;
-            Ok(result)
+            self.converter
+                .build_array(&aggregate.output_field, py, &results)
+                .context("failed to build arrow array from return values")
+        })
+    }
+
+    /// Add a new function from Python code, inferring its signature from the type hints.
+    ///
+    /// If a signature is declared, it is validated against the type hints of the function,
+    /// so that a mismatch is reported when the function is added instead of when it is called.
+    /// Arguments without type hints accept any type.
+    ///
+    /// Otherwise the signature is generated from the type hints, which must be given for every
+    /// argument and the return value. The default types are:
+    ///
+    /// | Python Type          | Arrow Type             |
+    /// | -------------------- | ---------------------- |
+    /// | bool                 | Boolean                |
+    /// | int                  | Int64                  |
+    /// | float                | Float64                |
+    /// | str                  | Utf8                   |
+    /// | bytes                | Binary                 |
+    /// | decimal.Decimal      | Utf8 (decimal)         |
+    /// | datetime.timedelta   | Interval(MonthDayNano) |
+    /// | dict, list           | Utf8 (json)            |
+    /// | list[T]              | List(T)                |
+    ///
+    /// `Optional[T]` and `T | None` are treated as `T`.
+    ///
+    /// Returns the signature of the function.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let signature = runtime.add_function_with_type_hints(
+    ///     "weight",
+    ///     None,
+    ///     CallMode::ReturnNullOnNullInput,
+    ///     "def weight(x: int, unit: str) -> float:\n    return x * 2.5\n",
+    /// )?;
+    /// assert_eq!(signature.return_field.data_type(), &DataType::Float64);
+    /// ```
+    pub fn add_function_with_type_hints(
+        &mut self,
+        name: &str,
+        signature: Option<&Signature>,
+        mode: CallMode,
+        code: &str,
+    ) -> Result<Signature> {
+        let (function, context, args, ret) = self.interpreter.with_gil(|py| -> PyResult<_> {
+            let function = PyModule::from_code(py, code, "", "")?.getattr(name)?;
+            type Hints = (Vec<(String, Option<String>)>, Option<String>);
+            let (args, ret): Hints = py
+                .eval("_type_hints", None, None)?
+                .call1((function,))?
+                .extract()?;
+            Ok((function.into(), takes_context(function)?, args, ret))
         })?;
-        let schema = Schema::new(vec![Field::new(name, array.data_type().clone(), true)]);
+        let signature = hints::infer(&self.converter, name, &args, ret.as_deref(), signature);
+        let signature = match signature {
+            Ok(signature) => signature,
+            Err(e) => {
+                self.interpreter.with_gil(|_| drop(function));
+                return Err(e);
+            }
+        };
+        let function = Function {
+            function,
+            return_field: signature.return_field.clone().into(),
+            mode,
+            context,
+            env: Env::default(),
+            stats: Stats::default(),
+            #[cfg(feature = "leak_check")]
+            leaks: Default::default(),
+        };
+        self.functions.insert(name.to_string(), function);
+        Ok(signature)
+    }
+
+    /// Add a new function from Python code, which requires the given packages.
+    ///
+    /// Each package is a requirement like `numpy==1.26`, `numpy>=1.20,<2` or `regex`.
+    /// The installed versions are checked before loading the code, and an error is returned
+    /// if any package is missing or does not satisfy the requirement.
+    ///
+    /// Packages are not installed automatically. All runtimes share the packages of the Python
+    /// environment, so functions with conflicting requirements should be added to runtimes of
+    /// different environments. Packages are not supported in sandboxed mode.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// runtime.add_function_with_packages(
+    ///     "extract",
+    ///     DataType::Utf8,
+    ///     CallMode::ReturnNullOnNullInput,
+    ///     code,
+    ///     &["numpy==1.26.*", "regex"],
+    /// )?;
+    /// ```
+    pub fn add_function_with_packages(
+        &mut self,
+        name: &str,
+        return_type: impl IntoField,
+        mode: CallMode,
+        code: &str,
+        packages: &[&str],
+    ) -> Result<()> {
+        self.check_packages(packages)?;
+        self.add_function(name, return_type, mode, code)
+    }
+
+    /// Add a new function from a zip archive containing multiple modules and resources,
+    /// such as a zipapp (`.pyz`) or a pure Python wheel (`.whl`).
+    ///
+    /// The archive is put in front of `sys.path` of this runtime, so its modules can import
+    /// each other and read their resources through `importlib.resources`. `entry` names the
+    /// function as `module:function`, or only `module` if the function is named `name`.
+    ///
+    /// Modules are cached by their names in a runtime, so archives with modules of the same
+    /// name should be added to different runtimes. Archives are not supported in sandboxed mode.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// runtime.add_function_from_archive(
+    ///     "word_count",
+    ///     DataType::Int32,
+    ///     CallMode::ReturnNullOnNullInput,
+    ///     "/opt/udf/text.pyz",
+    ///     "text.count:word_count",
+    /// )?;
+    /// ```
+    pub fn add_function_from_archive(
+        &mut self,
+        name: &str,
+        return_type: impl IntoField,
+        mode: CallMode,
+        archive: impl AsRef<Path>,
+        entry: &str,
+    ) -> Result<()> {
+        if self.sandboxed {
+            bail!("archives are not supported in sandboxed mode");
+        }
+        let archive = archive.as_ref();
+        ensure!(
+            archive.is_file(),
+            "archive not found: {}",
+            archive.display()
+        );
+        let archive = archive.to_str().context("invalid path of archive")?;
+        let (module, attr) = entry.split_once(':').unwrap_or((entry, name));
+        let (function, context) = self.interpreter.with_gil(|py| -> PyResult<_> {
+            let sys_path = PyModule::import(py, "sys")?.getattr("path")?;
+            if !sys_path.contains(archive)? {
+                sys_path.call_method1("insert", (0, archive))?;
+            }
+            let function = PyModule::import(py, module)?.getattr(attr)?;
+            Ok((function.into(), takes_context(function)?))
+        })?;
+        let function = Function {
+            function,
+            return_field: return_type.into_field(name).into(),
+            mode,
+            context,
+            env: Env::default(),
+            stats: Stats::default(),
+            #[cfg(feature = "leak_check")]
+            leaks: Default::default(),
+        };
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    /// Check that the installed packages satisfy the requirements.
+    fn check_packages(&self, packages: &[&str]) -> Result<()> {
+        if packages.is_empty() {
+            return Ok(());
+        }
+        if self.sandboxed {
+            bail!("packages are not supported in sandboxed mode");
+        }
+        let requirements = packages
+            .iter()
+            .map(|s| Requirement::parse(s))
+            .collect::<Result<Vec<_>>>()?;
+        let unsatisfied = self.interpreter.with_gil(|py| -> PyResult<Vec<String>> {
+            let version = PyModule::import(py, "importlib.metadata")?.getattr("version")?;
+            let mut unsatisfied = vec![];
+            for req in &requirements {
+                match version.call1((&req.name,)) {
+                    Ok(v) => {
+                        let v: String = v.extract()?;
+                        if !req.matches(&v) {
+                            unsatisfied.push(format!("{req} (found {v})"));
+                        }
+                    }
+                    Err(_) => unsatisfied.push(format!("{req} (not installed)")),
+                }
+            }
+            Ok(unsatisfied)
+        })?;
+        if !unsatisfied.is_empty() {
+            bail!("unsatisfied requirements: {}", unsatisfied.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Import modules ahead of the first call.
+    ///
+    /// Importing large packages like `numpy` or `pandas` can take seconds. Preloading them
+    /// moves the cost out of the first call of functions importing them, which then only
+    /// look up the loaded modules. In sandboxed mode, only allowed modules can be preloaded.
+    pub fn preload(&self, modules: &[&str]) -> Result<()> {
+        self.interpreter.with_gil(|py| -> Result<()> {
+            for module in modules {
+                PyModule::import(py, *module)
+                    .with_context(|| format!("failed to preload module `{module}`"))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove a function.
+    pub fn del_function(&mut self, name: &str) -> Result<()> {
+        let function = self.functions.remove(name).context("function not found")?;
+        self.interpreter.with_gil(|_| drop(function));
+        Ok(())
+    }
+
+    /// Returns the resource usage of all functions.
+    ///
+    /// The statistics of a function are reset when it is added again.
+    pub fn stats(&self) -> HashMap<String, FunctionStats> {
+        self.functions
+            .iter()
+            .map(|(name, function)| (name.clone(), function.stats.get()))
+            .collect()
+    }
+
+    /// Returns the leaks of all functions found by the leak check.
+    ///
+    /// Every evaluation of a scalar function is checked by comparing the memory blocks allocated
+    /// by the interpreter before and after it. The first evaluations may allocate caches, such as
+    /// imported modules and interned strings, but a function leaking objects, e.g. by a missing
+    /// decref in the conversion of values, keeps growing in `leaked_blocks` as it is called.
+    ///
+    /// The report of a function is reset when it is added again.
+    #[cfg(feature = "leak_check")]
+    pub fn leaks(&self) -> HashMap<String, LeakReport> {
+        self.functions
+            .iter()
+            .map(|(name, function)| (name.clone(), function.leaks.get()))
+            .collect()
+    }
+
+    /// Reset the leak report of a function.
+    #[cfg(feature = "leak_check")]
+    pub fn reset_leaks(&self, name: &str) -> Result<()> {
+        let function = self.functions.get(name).context("function not found")?;
+        function.leaks.reset();
+        Ok(())
+    }
+
+    /// Set the quota of a function. The function is disabled once it exceeds the quota.
+    pub fn set_quota(&mut self, name: &str, quota: Quota) -> Result<()> {
+        let function = self.functions.get_mut(name).context("function not found")?;
+        function.stats.quota = quota;
+        Ok(())
+    }
+
+    /// Set the variables and secrets of a function, exposed in the `ctx.env` dict.
+    ///
+    /// The function must take a keyword-only `ctx` argument to read them. Values of secrets
+    /// are redacted in the errors of the function.
+    ///
+    /// ```python
+    /// def fetch(x, *, ctx):
+    ///     return call_api(ctx.env['ENDPOINT'], ctx.env['API_KEY'], x)
+    /// ```
+    pub fn set_env(&mut self, name: &str, env: Env) -> Result<()> {
+        let function = self.functions.get_mut(name).context("function not found")?;
+        function.env = env;
+        Ok(())
+    }
+
+    /// Reset the resource usage of a function, and enable it if it was disabled.
+    pub fn reset_stats(&self, name: &str) -> Result<()> {
+        let function = self.functions.get(name).context("function not found")?;
+        function.stats.reset();
+        Ok(())
+    }
+
+    /// Call the Python UDF.
+    pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+        self.call_with_context(name, input, &mut EvalContext::default())
+    }
+
+    /// Call the Python UDF, reusing the buffers in `context` between calls.
+    ///
+    /// This avoids allocating buffers for every batch when calling functions in a loop.
+    /// The batch id and metadata of `context` are passed to functions taking a `ctx` argument.
+    pub fn call_with_context(
+        &self,
+        name: &str,
+        input: &RecordBatch,
+        context: &mut EvalContext,
+    ) -> Result<RecordBatch> {
+        let num_rows = input.num_rows();
+        let size = match self.batch_size {
+            Some(size) if num_rows > size => size,
+            _ => return self.call_batch(name, input, context),
+        };
+        // evaluate slices of the input and release the GIL between them
+        let row_offset = context.row_offset;
+        let mut outputs = Vec::with_capacity(num_rows.div_ceil(size));
+        for offset in (0..num_rows).step_by(size) {
+            let slice = input.slice(offset, size.min(num_rows - offset));
+            context.row_offset = row_offset + offset;
+            let output = self.call_batch(name, &slice, context);
+            context.row_offset = row_offset;
+            // the row index of errors should be relative to the whole batch
+            outputs.push(output.map_err(|e| match e.downcast::<UdfError>() {
+                Ok(e) => e.with_row_offset(offset).into(),
+                Err(e) => e,
+            })?);
+        }
+        let schema = outputs[0].schema();
+        Ok(arrow_select::concat::concat_batches(&schema, &outputs)?)
+    }
+
+    /// Call the Python UDF on multiple batches, returning one output for each input.
+    ///
+    /// If [`Builder::batch_size`] is set, consecutive small batches are coalesced up to the
+    /// batch size before calling user code, and large batches are sliced. In coalesced batches,
+    /// `ctx.row` and the row index of errors are relative to the coalesced batch.
+    pub fn call_batches(
+        &self,
+        name: &str,
+        inputs: &[RecordBatch],
+        context: &mut EvalContext,
+    ) -> Result<Vec<RecordBatch>> {
+        let mut outputs = Vec::with_capacity(inputs.len());
+        let mut rest = inputs;
+        while !rest.is_empty() {
+            // take as many batches as fit in the batch size, but at least one
+            let mut len = 1;
+            let mut num_rows = rest[0].num_rows();
+            while let Some(next) = rest.get(len) {
+                match self.batch_size {
+                    Some(size) if num_rows + next.num_rows() <= size => {}
+                    _ => break,
+                }
+                num_rows += next.num_rows();
+                len += 1;
+            }
+            let (group, remaining) = rest.split_at(len);
+            rest = remaining;
+            if group.len() == 1 {
+                outputs.push(self.call_with_context(name, &group[0], context)?);
+                continue;
+            }
+            let input = arrow_select::concat::concat_batches(&group[0].schema(), group)?;
+            let output = self.call_with_context(name, &input, context)?;
+            // split the output back into the shape of the inputs
+            let mut offset = 0;
+            for input in group {
+                outputs.push(output.slice(offset, input.num_rows()));
+                offset += input.num_rows();
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Call the Python UDF on a batch as a whole.
+    fn call_batch(
+        &self,
+        name: &str,
+        input: &RecordBatch,
+        context: &mut EvalContext,
+    ) -> Result<RecordBatch> {
+        let function = self.functions.get(name).context("function not found")?;
+        function.stats.check(name)?;
+        // find null rows before acquiring the GIL
+        let nulls = function.mode.null_rows(input);
+        // columns of all nulls are not converted row by row
+        let null_columns = null_columns(input);
+        // convert each row to python objects and call the function
+        let schema = input.schema();
+        // the time zone of the call overrides the one of the runtime
+        let converter = match &context.timezone {
+            Some(timezone) => Cow::Owned(Converter {
+                timezone: Some(timezone.clone()),
+                ..self.converter.clone()
+            }),
+            None => Cow::Borrowed(&self.converter),
+        };
+        #[cfg(feature = "leak_check")]
+        let blocks = self.interpreter.with_gil(leak::allocated_blocks)?;
+        let array = self.interpreter.with_gil(|py| -> Result<ArrayRef> {
+            let EvalContext {
+                results,
+                row,
+                row_offset,
+                batch_id,
+                metadata,
+                cancellation_token,
+                ..
+            } = context;
+            let meter = Meter::start(py, self.track_memory)?;
+            let mut calls = 0;
+            let mut eval = || -> Result<ArrayRef> {
+                let kwargs = match function.context {
+                    true => {
+                        let ctx = py.eval("Context()", None, None)?;
+                        ctx.setattr("batch_id", *batch_id)?;
+                        ctx.setattr("metadata", metadata.clone().into_py_dict(py))?;
+                        ctx.setattr("timezone", converter.timezone.as_deref())?;
+                        ctx.setattr("env", function.env.vars().clone().into_py_dict(py))?;
+                        Some((ctx, [("ctx", ctx)].into_py_dict(py)))
+                    }
+                    false => None,
+                };
+                for i in 0..input.num_rows() {
+                    if i > 0 && i % YIELD_INTERVAL == 0 {
+                        py.allow_threads(|| {});
+                    }
+                    CancellationToken::check(cancellation_token.as_ref())?;
+                    if nulls.as_ref().is_some_and(|nulls| nulls.is_null(i)) {
+                        results.push(py.None());
+                        continue;
+                    }
+                    row.clear();
+                    for ((column, field), &null) in input
+                        .columns()
+                        .iter()
+                        .zip(schema.fields())
+                        .zip(&null_columns)
+                    {
+                        match null {
+                            true => row.push(py.None()),
+                            false => row.push(converter.get_pyobject(py, field, column, i)?),
+                        }
+                    }
+                    if function.mode == CallMode::ReturnNullOnNullInput
+                        && row.iter().any(|v| v.is_none(py))
+                    {
+                        results.push(py.None());
+                        continue;
+                    }
+                    let args = PyTuple::new(py, row.drain(..));
+                    if let Some((ctx, _)) = kwargs {
+                        ctx.setattr("row", *row_offset + i)?;
+                    }
+                    calls += 1;
+                    let result = function
+                        .function
+                        .call(py, args, kwargs.map(|(_, kwargs)| kwargs))
+                        .map_err(|e| UdfError::from_pyerr(py, &e, i).redact(&function.env))?;
+                    results.push(result);
+                }
+                let result = converter.build_array(&function.return_field, py, results)?;
+                ensure!(
+                    result.len() == input.num_rows(),
+                    "expect {} rows in the output of {name}, got {}",
+                    input.num_rows(),
+                    result.len()
+                );
+                Ok(result)
+            };
+            let result = eval();
+            // python objects must be dropped with the GIL held
+            results.clear();
+            row.clear();
+            function.stats.record(meter.finish(py, calls)?);
+            result
+        });
+        // objects of the evaluation are released at the end of `with_gil`, even on errors
+        #[cfg(feature = "leak_check")]
+        function
+            .leaks
+            .record(blocks, self.interpreter.with_gil(leak::allocated_blocks)?);
+        let array = array?;
+        let schema = Schema::new(vec![function.return_field.clone()]);
         Ok(RecordBatch::try_new(Arc::new(schema), vec![array])?)
     }
 
     /// Call a table function.
+    ///
+    /// The returned iterator is lazy: each call of `next` drives the generators of the function
+    /// until `chunk_size` rows are yielded, so at most one chunk of outputs is buffered no matter
+    /// how many rows the function yields. Once the iterator is dropped, the current generator is
+    /// closed and no more rows are evaluated.
     pub fn call_table_function<'a>(
         &'a self,
         name: &'a str,
@@ -229,30 +1270,144 @@ impl Runtime {
     ) -> Result<RecordBatchIter<'a>> {
         assert!(chunk_size > 0);
         let function = self.functions.get(name).context("function not found")?;
+        function.stats.check(name)?;
 
         // initial state
         Ok(RecordBatchIter {
             interpreter: &self.interpreter,
+            converter: &self.converter,
+            track_memory: self.track_memory,
+            name,
             input,
             function,
             schema: Arc::new(Schema::new(vec![
-                Field::new("row", DataType::Int32, true),
-                Field::new(name, function.return_type.clone(), true),
+                Arc::new(Field::new("row", DataType::Int32, true)),
+                function.return_field.clone(),
             ])),
             chunk_size,
+            nulls: function.mode.null_rows(input),
+            null_columns: null_columns(input),
+            cancellation_token: None,
             row: 0,
             generator: None,
         })
     }
 }
 
+/// Reusable buffers and engine-provided information for calling functions.
+///
+/// Pass the same context to [`Runtime::call_with_context`] to avoid allocating
+/// buffers for Python objects on every call.
+#[derive(Debug, Default)]
+pub struct EvalContext {
+    /// The return values of a batch.
+    results: Vec<PyObject>,
+    /// The arguments of a row.
+    row: Vec<PyObject>,
+    /// The index of the first row of the input, when called on a slice of a batch.
+    row_offset: usize,
+    /// The id of the input batch.
+    batch_id: u64,
+    /// Key-value metadata such as the query id and user.
+    metadata: HashMap<String, String>,
+    /// The token to cancel the call.
+    cancellation_token: Option<CancellationToken>,
+    /// The session time zone that overrides the one of the runtime.
+    timezone: Option<String>,
+}
+
+impl EvalContext {
+    /// Create a new context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the id of the input batch, exposed as `ctx.batch_id`.
+    pub fn with_batch_id(mut self, batch_id: u64) -> Self {
+        self.batch_id = batch_id;
+        self
+    }
+
+    /// Add a metadata entry, exposed in the `ctx.metadata` dict.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the token to cancel the call.
+    ///
+    /// Once cancelled, the call returns an error before evaluating the next row.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Set the session time zone of the call, exposed as `ctx.timezone`.
+    ///
+    /// This overrides the time zone set by [`Builder::timezone`].
+    pub fn with_timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+
+    /// Set the id of the input batch.
+    pub fn set_batch_id(&mut self, batch_id: u64) {
+        self.batch_id = batch_id;
+    }
+
+    /// Create a context for a slice of the input starting at `row_offset`.
+    fn for_slice(&self, row_offset: usize) -> Self {
+        Self {
+            row_offset: self.row_offset + row_offset,
+            batch_id: self.batch_id,
+            metadata: self.metadata.clone(),
+            cancellation_token: self.cancellation_token.clone(),
+            timezone: self.timezone.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns whether each column of the input is all null.
+fn null_columns(input: &RecordBatch) -> Vec<bool> {
+    input
+        .columns()
+        .iter()
+        .map(|c| c.null_count() == c.len())
+        .collect()
+}
+
+/// Returns whether the function declares a keyword-only `ctx` argument.
+fn takes_context(function: &PyAny) -> PyResult<bool> {
+    let Ok(code) = function.getattr("__code__") else {
+        return Ok(false);
+    };
+    let argcount: usize = code.getattr("co_argcount")?.extract()?;
+    let kwonlyargcount: usize = code.getattr("co_kwonlyargcount")?.extract()?;
+    let varnames: Vec<String> = code.getattr("co_varnames")?.extract()?;
+    Ok(varnames
+        .get(argcount..argcount + kwonlyargcount)
+        .unwrap_or_default()
+        .iter()
+        .any(|name| name == "ctx"))
+}
+
 /// An iterator over the result of a table function.
 pub struct RecordBatchIter<'a> {
     interpreter: &'a SubInterpreter,
+    converter: &'a Converter,
+    track_memory: bool,
+    name: &'a str,
     input: &'a RecordBatch,
     function: &'a Function,
     schema: SchemaRef,
     chunk_size: usize,
+    /// The rows to skip if the function returns null on null input.
+    nulls: Option<NullBuffer>,
+    /// Whether each column is all null.
+    null_columns: Vec<bool>,
+    /// The token to cancel the call.
+    cancellation_token: Option<CancellationToken>,
     // mutable states
     /// Current row index.
     row: usize,
@@ -266,56 +1421,101 @@ impl RecordBatchIter<'_> {
         &self.schema
     }
 
+    /// Set the token to cancel the call.
+    ///
+    /// Once cancelled, the iterator returns an error before evaluating the next row.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
     fn next(&mut self) -> Result<Option<RecordBatch>> {
         if self.row == self.input.num_rows() {
             return Ok(None);
         }
+        self.function.stats.check(self.name)?;
         self.interpreter.with_gil(|py| {
-            let mut indexes = Int32Builder::with_capacity(self.chunk_size);
-            let mut results = Vec::with_capacity(self.input.num_rows());
-            let mut row = Vec::with_capacity(self.input.num_columns());
-            while self.row < self.input.num_rows() && results.len() < self.chunk_size {
-                let generator = if let Some(g) = self.generator.as_ref() {
-                    g
-                } else {
-                    // call the table function to get a generator
-                    row.clear();
-                    for column in self.input.columns() {
-                        let val = pyarrow::get_pyobject(py, column, self.row)?;
-                        row.push(val);
-                    }
-                    if self.function.mode == CallMode::ReturnNullOnNullInput
-                        && row.iter().any(|v| v.is_none(py))
-                    {
-                        self.row += 1;
-                        continue;
+            let meter = Meter::start(py, self.track_memory)?;
+            let mut calls = 0;
+            let result = self.next_chunk(py, &mut calls);
+            self.function.stats.record(meter.finish(py, calls)?);
+            result
+        })
+    }
+
+    /// Evaluates the next chunk of rows, counting the calls of the function.
+    fn next_chunk(&mut self, py: Python<'_>, calls: &mut u64) -> Result<Option<RecordBatch>> {
+        let mut indexes = Int32Builder::with_capacity(self.chunk_size);
+        let mut results = Vec::with_capacity(self.chunk_size);
+        let mut row = Vec::with_capacity(self.input.num_columns());
+        while self.row < self.input.num_rows() && results.len() < self.chunk_size {
+            CancellationToken::check(self.cancellation_token.as_ref())?;
+            let generator = if let Some(g) = self.generator.as_ref() {
+                g
+            } else {
+                if self
+                    .nulls
+                    .as_ref()
+                    .is_some_and(|nulls| nulls.is_null(self.row))
+                {
+                    self.row += 1;
+                    continue;
+                }
+                // call the table function to get a generator
+                row.clear();
+                let schema = self.input.schema();
+                for ((column, field), &null) in self
+                    .input
+                    .columns()
+                    .iter()
+                    .zip(schema.fields())
+                    .zip(&self.null_columns)
+                {
+                    match null {
+                        true => row.push(py.None()),
+                        false => {
+                            row.push(self.converter.get_pyobject(py, field, column, self.row)?)
+                        }
                     }
-                    let args = PyTuple::new(py, row.drain(..));
-                    let result = self.function.function.call1(py, args)?;
-                    let iter = result.as_ref(py).iter()?.into();
-                    self.generator.insert(iter)
-                };
-                if let Some(value) = generator.as_ref(py).next() {
-                    let value: PyObject = value?.into();
-                    indexes.append_value(self.row as i32);
-                    results.push(value);
-                } else {
+                }
+                if self.function.mode == CallMode::ReturnNullOnNullInput
+                    && row.iter().any(|v| v.is_none(py))
+                {
                     self.row += 1;
-                    self.generator = None;
+                    continue;
                 }
+                let args = PyTuple::new(py, row.drain(..));
+                *calls += 1;
+                let result = self.function.function.call1(py, args).map_err(|e| {
+                    UdfError::from_pyerr(py, &e, self.row).redact(&self.function.env)
+                })?;
+                let iter = result.as_ref(py).iter()?.into();
+                self.generator.insert(iter)
+            };
+            if let Some(value) = generator.as_ref(py).next() {
+                let value: PyObject = value
+                    .map_err(|e| UdfError::from_pyerr(py, &e, self.row).redact(&self.function.env))?
+                    .into();
+                indexes.append_value(self.row as i32);
+                results.push(value);
+            } else {
+                self.row += 1;
+                self.generator = None;
             }
+        }
 
-            if results.is_empty() {
-                return Ok(None);
-            }
-            let indexes = Arc::new(indexes.finish());
-            let array = pyarrow::build_array(&self.function.return_type, py, &results)
-                .context("failed to build arrow array from return values")?;
-            Ok(Some(RecordBatch::try_new(
-                self.schema.clone(),
-                vec![indexes, array],
-            )?))
-        })
+        if results.is_empty() {
+            return Ok(None);
+        }
+        let indexes = Arc::new(indexes.finish());
+        let array = self
+            .converter
+            .build_array(&self.function.return_field, py, &results)
+            .context("failed to build arrow array from return values")?;
+        Ok(Some(RecordBatch::try_new(
+            self.schema.clone(),
+            vec![indexes, array],
+        )?))
     }
 }
 
@@ -329,13 +1529,42 @@ impl Iterator for RecordBatchIter<'_> {
 impl Drop for RecordBatchIter<'_> {
     fn drop(&mut self) {
         if let Some(generator) = self.generator.take() {
-            self.interpreter.with_gil(|_| drop(generator));
+            self.interpreter.with_gil(|py| {
+                // run the `finally` blocks of the generator now, even if it's referenced elsewhere.
+                // other iterators don't have `close`, and errors can not be reported in `drop`.
+                _ = generator.call_method0(py, "close");
+                drop(generator);
+            });
         }
     }
 }
 
+/// A trait for types that can be converted into a [`Field`].
+pub trait IntoField {
+    /// Converts the type into a field with the given name.
+    fn into_field(self, name: &str) -> Field;
+}
+
+impl IntoField for DataType {
+    fn into_field(self, name: &str) -> Field {
+        Field::new(name, self, true)
+    }
+}
+
+impl IntoField for Field {
+    fn into_field(self, _name: &str) -> Field {
+        self
+    }
+}
+
+impl IntoField for FieldRef {
+    fn into_field(self, _name: &str) -> Field {
+        (*self).clone()
+    }
+}
+
 /// Whether the function will be called when some of its arguments are null.
-#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CallMode {
     /// The function will be called normally when some of its arguments are null.
     /// It is then the function author's responsibility to check for null values if necessary and respond appropriately.
@@ -348,9 +1577,31 @@ pub enum CallMode {
     ReturnNullOnNullInput,
 }
 
+impl CallMode {
+    /// Returns the rows where any argument is null if the function returns null on null input.
+    ///
+    /// This is computed without the GIL, so that null rows are skipped before converting
+    /// arguments to Python objects.
+    fn null_rows(&self, input: &RecordBatch) -> Option<NullBuffer> {
+        match self {
+            CallMode::CalledOnNullInput => None,
+            CallMode::ReturnNullOnNullInput => input
+                .columns()
+                .iter()
+                .map(|column| column.logical_nulls())
+                .fold(None, |acc, nulls| {
+                    NullBuffer::union(acc.as_ref(), nulls.as_ref())
+                }),
+        }
+    }
+}
+
 impl Drop for Runtime {
     fn drop(&mut self) {
         // `PyObject` must be dropped inside the interpreter
-        self.interpreter.with_gil(|_| self.functions.clear());
+        self.interpreter.with_gil(|_| {
+            self.functions.clear();
+            self.aggregates.clear();
+        });
     }
 }
@@ -0,0 +1,322 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! A pool of runtimes to evaluate functions in parallel.
+
+use super::{Builder, CallMode, EvalContext, IntoField, Runtime, UdfError};
+use anyhow::{anyhow, Context, Result};
+use arrow_array::RecordBatch;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, RwLock};
+use std::thread::JoinHandle;
+
+/// The minimum number of rows evaluated by a worker.
+///
+/// Batches smaller than this are not split, since the overhead of dispatching would exceed
+/// the gain of parallelism.
+const MIN_ROWS_PER_WORKER: usize = 256;
+
+type Job = Box<dyn FnOnce(&mut Runtime) + Send>;
+
+/// A pool of Python runtimes, each running in its own thread with its own interpreter and GIL.
+///
+/// Since Python 3.12, sub-interpreters don't share the GIL. A pool spreads the rows of a batch,
+/// as well as concurrent calls, across multiple runtimes, so that CPU-bound functions scale
+/// across cores instead of serializing on one GIL.
+///
+/// Functions are added to every runtime in the pool by default. Therefore functions must not
+/// rely on states shared between rows, unless their concurrency is limited by
+/// [`add_function_with_concurrency`](Self::add_function_with_concurrency).
+/// Table functions are not supported.
+///
+/// # Examples
+///
+/// ```ignore
+/// let pool = Runtime::builder().build_pool(4)?;
+/// pool.add_function("gcd", DataType::Int32, CallMode::ReturnNullOnNullInput, code)?;
+/// let output = pool.call("gcd", &input)?;
+/// ```
+pub struct RuntimePool {
+    workers: Vec<Worker>,
+    /// The runtimes of each function.
+    placements: RwLock<HashMap<String, Placement>>,
+    /// The first runtime of the next function.
+    next_first: AtomicUsize,
+    /// The index of the next worker to dispatch jobs to.
+    next: AtomicUsize,
+}
+
+/// The runtimes a function is added to, which are `concurrency` consecutive workers starting
+/// from `first`, wrapping around.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    first: usize,
+    concurrency: usize,
+}
+
+impl Placement {
+    /// Returns the `i`-th worker of the function, wrapping around.
+    fn worker(&self, i: usize, size: usize) -> usize {
+        (self.first + i % self.concurrency) % size
+    }
+
+    /// Returns all workers of the function.
+    fn workers(&self, size: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.concurrency).map(move |i| self.worker(i, size))
+    }
+}
+
+impl std::fmt::Debug for RuntimePool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuntimePool")
+            .field("size", &self.workers.len())
+            .finish()
+    }
+}
+
+/// A thread that owns a runtime and runs jobs on it.
+struct Worker {
+    sender: Option<mpsc::Sender<Job>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RuntimePool {
+    /// Create a pool of `size` runtimes with the given builder.
+    pub(crate) fn new(builder: Builder, size: usize) -> Result<Self> {
+        assert!(size > 0, "pool size must be positive");
+        let mut workers = Vec::with_capacity(size);
+        for i in 0..size {
+            let builder = builder.clone();
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let (init_sender, init_receiver) = mpsc::channel();
+            let handle = std::thread::Builder::new()
+                .name(format!("arrow-udf-python-{i}"))
+                .spawn(move || {
+                    // the interpreter must be used in the thread that created it
+                    let mut runtime = match builder.build() {
+                        Ok(runtime) => {
+                            _ = init_sender.send(Ok(()));
+                            runtime
+                        }
+                        Err(e) => {
+                            _ = init_sender.send(Err(e));
+                            return;
+                        }
+                    };
+                    while let Ok(job) = receiver.recv() {
+                        job(&mut runtime);
+                    }
+                })
+                .context("failed to spawn worker thread")?;
+            let worker = Worker {
+                sender: Some(sender),
+                handle: Some(handle),
+            };
+            // push before checking, so that the thread is joined on error
+            workers.push(worker);
+            init_receiver
+                .recv()
+                .context("worker thread exited unexpectedly")??;
+        }
+        Ok(Self {
+            workers,
+            placements: RwLock::new(HashMap::new()),
+            next_first: AtomicUsize::new(0),
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the number of runtimes in the pool.
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Add a new function from Python code to all runtimes.
+    ///
+    /// See [`Runtime::add_function`] for details.
+    pub fn add_function(
+        &self,
+        name: &str,
+        return_type: impl IntoField,
+        mode: CallMode,
+        code: &str,
+    ) -> Result<()> {
+        self.add_function_with_concurrency(name, return_type, mode, code, self.size())
+    }
+
+    /// Add a new function from Python code, which is evaluated by at most `concurrency`
+    /// runtimes in parallel.
+    ///
+    /// The function is only added to `concurrency` runtimes of the pool. With a concurrency
+    /// of 1, all calls are evaluated by the same runtime one after another, so the function
+    /// can keep states in global variables across calls. Functions with limited concurrency
+    /// are spread over different runtimes, so that they don't wait for each other.
+    ///
+    /// A concurrency larger than the pool size is limited to the pool size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is 0.
+    pub fn add_function_with_concurrency(
+        &self,
+        name: &str,
+        return_type: impl IntoField,
+        mode: CallMode,
+        code: &str,
+        concurrency: usize,
+    ) -> Result<()> {
+        assert!(concurrency > 0, "concurrency must be positive");
+        let size = self.size();
+        let concurrency = concurrency.min(size);
+        let first = self.next_first.fetch_add(concurrency, Ordering::Relaxed) % size;
+        let placement = Placement { first, concurrency };
+
+        // remove the function with the same name from other runtimes
+        if self.placements.read().unwrap().contains_key(name) {
+            self.del_function(name)?;
+        }
+        let field = return_type.into_field(name);
+        let code = code.to_string();
+        self.broadcast(placement.workers(size), name, move |runtime, name| {
+            runtime.add_function(name, field.clone(), mode, &code)
+        })?;
+        self.placements
+            .write()
+            .unwrap()
+            .insert(name.to_string(), placement);
+        Ok(())
+    }
+
+    /// Remove a function from all runtimes.
+    pub fn del_function(&self, name: &str) -> Result<()> {
+        let placement = self.placement(name);
+        self.broadcast(placement.workers(self.size()), name, |runtime, name| {
+            runtime.del_function(name)
+        })?;
+        self.placements.write().unwrap().remove(name);
+        Ok(())
+    }
+
+    /// Returns the runtimes of the function, or all runtimes if the function is unknown.
+    fn placement(&self, name: &str) -> Placement {
+        let placements = self.placements.read().unwrap();
+        placements.get(name).copied().unwrap_or(Placement {
+            first: 0,
+            concurrency: self.size(),
+        })
+    }
+
+    /// Call the Python UDF.
+    ///
+    /// Large batches are split into slices and evaluated by multiple runtimes in parallel,
+    /// up to the concurrency of the function.
+    pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+        self.call_with_context(name, input, &EvalContext::default())
+    }
+
+    /// Call the Python UDF with the batch id and metadata of `context`.
+    ///
+    /// The `ctx.row` seen by functions is the index in the whole batch, not in the slice.
+    pub fn call_with_context(
+        &self,
+        name: &str,
+        input: &RecordBatch,
+        context: &EvalContext,
+    ) -> Result<RecordBatch> {
+        let placement = self.placement(name);
+        let num_rows = input.num_rows();
+        let num_slices = (num_rows / MIN_ROWS_PER_WORKER).clamp(1, placement.concurrency);
+        let slice_len = num_rows.div_ceil(num_slices);
+        let mut receivers = Vec::with_capacity(num_slices);
+        for i in 0..num_slices {
+            let offset = (i * slice_len).min(num_rows);
+            let slice = input.slice(offset, slice_len.min(num_rows - offset));
+            let name = name.to_string();
+            let mut context = context.for_slice(offset);
+            let worker = placement.worker(self.next.fetch_add(1, Ordering::Relaxed), self.size());
+            receivers.push(self.send(worker, move |runtime| {
+                // the row index of errors should be relative to the whole batch
+                runtime
+                    .call_with_context(&name, &slice, &mut context)
+                    .map_err(|e| match e.downcast::<UdfError>() {
+                        Ok(e) => e.with_row_offset(offset).into(),
+                        Err(e) => e,
+                    })
+            })?);
+        }
+        let mut outputs = Vec::with_capacity(num_slices);
+        for receiver in receivers {
+            outputs.push(
+                receiver
+                    .recv()
+                    .context("worker thread exited unexpectedly")??,
+            );
+        }
+        if outputs.len() == 1 {
+            return Ok(outputs.pop().unwrap());
+        }
+        let schema = outputs[0].schema();
+        Ok(arrow_select::concat::concat_batches(&schema, &outputs)?)
+    }
+
+    /// Run a function on the runtimes of `workers` and wait for the results.
+    fn broadcast(
+        &self,
+        workers: impl Iterator<Item = usize>,
+        name: &str,
+        f: impl Fn(&mut Runtime, &str) -> Result<()> + Clone + Send + 'static,
+    ) -> Result<()> {
+        let mut receivers = Vec::with_capacity(self.workers.len());
+        for worker in workers {
+            let f = f.clone();
+            let name = name.to_string();
+            receivers.push(self.send(worker, move |runtime| f(runtime, &name))?);
+        }
+        for receiver in receivers {
+            receiver
+                .recv()
+                .context("worker thread exited unexpectedly")??;
+        }
+        Ok(())
+    }
+
+    /// Send a job to the worker, and return a receiver of its result.
+    fn send<T: Send + 'static>(
+        &self,
+        worker: usize,
+        f: impl FnOnce(&mut Runtime) -> Result<T> + Send + 'static,
+    ) -> Result<mpsc::Receiver<Result<T>>> {
+        let (sender, receiver) = mpsc::channel();
+        let job: Job = Box::new(move |runtime| {
+            _ = sender.send(f(runtime));
+        });
+        self.workers[worker]
+            .sender
+            .as_ref()
+            .expect("worker is stopped")
+            .send(job)
+            .map_err(|_| anyhow!("worker thread exited unexpectedly"))?;
+        Ok(receiver)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // close the channel to stop the thread
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+    }
+}
@@ -14,11 +14,23 @@
 
 //! Convert arrow array from/to python objects.
 
-use anyhow::Result;
-use arrow_array::{array::*, builder::*};
-use arrow_buffer::OffsetBuffer;
-use arrow_schema::DataType;
-use pyo3::{types::PyString, IntoPy, PyObject, Python};
+use crate::extension::ExtensionTypeCodec;
+use anyhow::{bail, Context, Result};
+use arrow_array::types::{
+    Decimal128Type, Int16Type, Int32Type, Int64Type, Int8Type, IntervalMonthDayNanoType,
+    RunEndIndexType, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_array::{array::*, builder::*, cast::AsArray, ArrowNativeTypeOp};
+use arrow_buffer::{Buffer, OffsetBuffer};
+use arrow_cast::parse::parse_decimal;
+use arrow_data::ArrayData;
+use arrow_schema::{DataType, Field, IntervalUnit, TimeUnit, UnionMode};
+use half::f16;
+use pyo3::exceptions::{PyAttributeError, PyOverflowError};
+use pyo3::types::{IntoPyDict, PyDict, PyFloat, PyLong, PyString};
+use pyo3::{intern, IntoPy, PyAny, PyObject, PyResult, Python};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 macro_rules! get_pyobject {
@@ -28,65 +40,6 @@ macro_rules! get_pyobject {
     }};
 }
 
-/// Get array element as a python object.
-pub fn get_pyobject(py: Python<'_>, array: &dyn Array, i: usize) -> Result<PyObject> {
-    if array.is_null(i) {
-        return Ok(py.None());
-    }
-    Ok(match array.data_type() {
-        DataType::Null => py.None(),
-        DataType::Boolean => get_pyobject!(BooleanArray, py, array, i),
-        DataType::Int8 => get_pyobject!(Int8Array, py, array, i),
-        DataType::Int16 => get_pyobject!(Int16Array, py, array, i),
-        DataType::Int32 => get_pyobject!(Int32Array, py, array, i),
-        DataType::Int64 => get_pyobject!(Int64Array, py, array, i),
-        DataType::UInt8 => get_pyobject!(UInt8Array, py, array, i),
-        DataType::UInt16 => get_pyobject!(UInt16Array, py, array, i),
-        DataType::UInt32 => get_pyobject!(UInt32Array, py, array, i),
-        DataType::UInt64 => get_pyobject!(UInt64Array, py, array, i),
-        DataType::Float32 => get_pyobject!(Float32Array, py, array, i),
-        DataType::Float64 => get_pyobject!(Float64Array, py, array, i),
-        DataType::Utf8 => get_pyobject!(StringArray, py, array, i),
-        DataType::Binary => get_pyobject!(BinaryArray, py, array, i),
-        // json type
-        DataType::LargeUtf8 => {
-            let array = array.as_any().downcast_ref::<LargeStringArray>().unwrap();
-            let json_str = PyString::new(py, array.value(i));
-            // XXX: it is slow to call eval every time
-            let json_loads = py.eval("json.loads", None, None)?;
-            json_loads.call1((json_str,))?.into()
-        }
-        // decimal type
-        DataType::LargeBinary => {
-            let array = array.as_any().downcast_ref::<LargeBinaryArray>().unwrap();
-            let string = std::str::from_utf8(array.value(i))?;
-            // XXX: it is slow to call eval every time
-            let decimal_constructor = py.import("decimal")?.getattr("Decimal")?;
-            decimal_constructor.call1((string,))?.into()
-        }
-        // list
-        DataType::List(_) => {
-            let array = array.as_any().downcast_ref::<ListArray>().unwrap();
-            let list = array.value(i);
-            let mut values = Vec::with_capacity(list.len());
-            for j in 0..list.len() {
-                values.push(get_pyobject(py, list.as_ref(), j)?);
-            }
-            values.into_py(py)
-        }
-        DataType::Struct(fields) => {
-            let array = array.as_any().downcast_ref::<StructArray>().unwrap();
-            let object = py.eval("Struct()", None, None)?;
-            for (j, field) in fields.iter().enumerate() {
-                let value = get_pyobject(py, array.column(j).as_ref(), i)?;
-                object.setattr(field.name().as_str(), value)?;
-            }
-            object.into()
-        }
-        _ => todo!(),
-    })
-}
-
 macro_rules! build_array {
     (NullBuilder, $py:expr, $pyobjects:expr) => {{
         let mut builder = NullBuilder::with_capacity($pyobjects.len());
@@ -106,7 +59,7 @@ macro_rules! build_array {
             if pyobj.is_none($py) {
                 builder.append_null();
             } else {
-                builder.append_value(pyobj.extract($py)?);
+                builder.append_value(numpy_item(pyobj.as_ref($py))?.extract()?);
             }
         }
         Ok(Arc::new(builder.finish()))
@@ -125,95 +78,636 @@ macro_rules! build_array {
     }};
 }
 
-/// Build arrow array from python objects.
-pub fn build_array(data_type: &DataType, py: Python<'_>, values: &[PyObject]) -> Result<ArrayRef> {
-    match data_type {
-        DataType::Null => build_array!(NullBuilder, py, values),
-        DataType::Boolean => build_array!(BooleanBuilder, py, values),
-        DataType::Int8 => build_array!(Int8Builder, py, values),
-        DataType::Int16 => build_array!(Int16Builder, py, values),
-        DataType::Int32 => build_array!(Int32Builder, py, values),
-        DataType::Int64 => build_array!(Int64Builder, py, values),
-        DataType::UInt8 => build_array!(UInt8Builder, py, values),
-        DataType::UInt16 => build_array!(UInt16Builder, py, values),
-        DataType::UInt32 => build_array!(UInt32Builder, py, values),
-        DataType::UInt64 => build_array!(UInt64Builder, py, values),
-        DataType::Float32 => build_array!(Float32Builder, py, values),
-        DataType::Float64 => build_array!(Float64Builder, py, values),
-        DataType::Utf8 => build_array!(StringBuilder, &str, py, values),
-        DataType::Binary => build_array!(BinaryBuilder, &[u8], py, values),
-        // json type
-        DataType::LargeUtf8 => {
-            let json_dumps = py.eval("json.dumps", None, None)?;
-            let mut builder = LargeStringBuilder::with_capacity(values.len(), 1024);
-            for val in values {
-                if val.is_none(py) {
-                    builder.append_null();
-                    continue;
-                };
-                let json_str = json_dumps.call1((val,))?;
-                builder.append_value(json_str.extract::<&str>()?);
-            }
-            Ok(Arc::new(builder.finish()))
+/// The metadata key for the extension name of a field.
+pub(crate) const EXTENSION_KEY: &str = "ARROW:extension:name";
+/// The extension name of JSON type.
+pub(crate) const JSON_EXTENSION_NAME: &str = "arrowudf.json";
+/// The extension name of decimal type.
+pub(crate) const DECIMAL_EXTENSION_NAME: &str = "arrowudf.decimal";
+
+/// How to handle strings that can not be converted between Python and arrow.
+///
+/// This applies to Python `str` containing lone surrogates, which can not be encoded as UTF-8,
+/// and to arrow binary values with invalid UTF-8 that are converted to `str`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidStringPolicy {
+    /// Return an error for the whole batch.
+    #[default]
+    Error,
+    /// Replace each invalid character with the replacement character `U+FFFD`.
+    Replace,
+    /// Convert the value to null.
+    Null,
+}
+
+/// How to handle Python integers that are out of the range of the integer return type.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IntOverflowPolicy {
+    /// Return an error for the whole batch.
+    #[default]
+    Error,
+    /// Clamp the value to the minimum or maximum of the type.
+    Saturate,
+    /// Convert the value to null.
+    Null,
+}
+
+/// Converts between arrow arrays and python objects.
+#[derive(Debug, Default, Clone)]
+pub struct Converter {
+    /// Treat `LargeUtf8` fields without extension metadata as JSON.
+    pub legacy_json: bool,
+    /// Treat `LargeBinary` fields without extension metadata as decimal.
+    pub legacy_decimal: bool,
+    /// How to handle invalid strings.
+    pub invalid_string: InvalidStringPolicy,
+    /// How to handle integers out of range.
+    pub int_overflow: IntOverflowPolicy,
+    /// Custom extension types by name.
+    pub extension_types: HashMap<String, Arc<dyn ExtensionTypeCodec>>,
+    /// The session time zone, e.g. `Asia/Shanghai` or `+08:00`.
+    ///
+    /// Timestamps with a time zone are converted to aware datetimes in the session time zone,
+    /// and naive datetimes returned for them are interpreted in it. Aware datetimes returned
+    /// for timestamps without a time zone are converted to the wall clock of it.
+    pub timezone: Option<String>,
+    /// Convert struct fields missing in returned objects to null instead of an error.
+    pub null_missing_fields: bool,
+}
+
+impl Converter {
+    /// Returns the codec if the field is of a custom extension type.
+    pub(crate) fn extension_type(&self, field: &Field) -> Option<&dyn ExtensionTypeCodec> {
+        let name = field.metadata().get(EXTENSION_KEY)?;
+        self.extension_types.get(name).map(|codec| codec.as_ref())
+    }
+
+    /// Returns whether the field is a JSON type.
+    pub(crate) fn is_json(&self, field: &Field) -> bool {
+        match field.metadata().get(EXTENSION_KEY) {
+            Some(name) => name == JSON_EXTENSION_NAME,
+            None => self.legacy_json && field.data_type() == &DataType::LargeUtf8,
+        }
+    }
+
+    /// Returns whether the field is a decimal type stored as strings.
+    pub(crate) fn is_decimal(&self, field: &Field) -> bool {
+        match field.metadata().get(EXTENSION_KEY) {
+            Some(name) => name == DECIMAL_EXTENSION_NAME,
+            None => self.legacy_decimal && field.data_type() == &DataType::LargeBinary,
         }
-        // decimal type
-        DataType::LargeBinary => {
-            let mut builder = LargeBinaryBuilder::with_capacity(values.len(), 1024);
-            for val in values {
-                if val.is_none(py) {
-                    builder.append_null();
+    }
+
+    /// Get array element as a python object.
+    pub fn get_pyobject(
+        &self,
+        py: Python<'_>,
+        field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> Result<PyObject> {
+        if array.is_null(i) {
+            return Ok(py.None());
+        }
+        if let Some(codec) = self.extension_type(field) {
+            return codec.to_pyobject(py, field, array, i);
+        }
+        Ok(match array.data_type() {
+            DataType::Null => py.None(),
+            DataType::Boolean => get_pyobject!(BooleanArray, py, array, i),
+            DataType::Int8 => get_pyobject!(Int8Array, py, array, i),
+            DataType::Int16 => get_pyobject!(Int16Array, py, array, i),
+            DataType::Int32 => get_pyobject!(Int32Array, py, array, i),
+            DataType::Int64 => get_pyobject!(Int64Array, py, array, i),
+            DataType::UInt8 => get_pyobject!(UInt8Array, py, array, i),
+            DataType::UInt16 => get_pyobject!(UInt16Array, py, array, i),
+            DataType::UInt32 => get_pyobject!(UInt32Array, py, array, i),
+            DataType::UInt64 => get_pyobject!(UInt64Array, py, array, i),
+            DataType::Float16 => {
+                let array = array.as_any().downcast_ref::<Float16Array>().unwrap();
+                array.value(i).to_f64().into_py(py)
+            }
+            DataType::Float32 => get_pyobject!(Float32Array, py, array, i),
+            DataType::Float64 => get_pyobject!(Float64Array, py, array, i),
+            DataType::Interval(IntervalUnit::MonthDayNano) => {
+                let array = array.as_primitive::<IntervalMonthDayNanoType>();
+                let (months, days, nanos) = IntervalMonthDayNanoType::to_parts(array.value(i));
+                // nanoseconds are truncated to microseconds
+                let kwargs = [("days", days as i64), ("microseconds", nanos / 1000)];
+                if months == 0 {
+                    let timedelta = py.import("datetime")?.getattr("timedelta")?;
+                    timedelta.call((), Some(kwargs.into_py_dict(py)))?.into()
                 } else {
-                    builder.append_value(val.to_string());
+                    // `timedelta` can not represent months
+                    let relativedelta = py
+                        .import("dateutil.relativedelta")?
+                        .getattr("relativedelta")?;
+                    let kwargs = kwargs.into_py_dict(py);
+                    kwargs.set_item("months", months)?;
+                    relativedelta.call((), Some(kwargs))?.into()
                 }
             }
-            Ok(Arc::new(builder.finish()))
-        }
-        // list
-        DataType::List(inner) => {
-            // flatten lists
-            let mut flatten_values = vec![];
-            let mut offsets = Vec::<i32>::with_capacity(values.len() + 1);
-            offsets.push(0);
-            for val in values {
-                if !val.is_none(py) {
-                    let array = val.as_ref(py);
-                    flatten_values.reserve(array.len()?);
-                    for elem in array.iter()? {
-                        flatten_values.push(elem?.into());
+            DataType::Timestamp(unit, tz) => {
+                let micros = match unit {
+                    TimeUnit::Second => {
+                        array.as_primitive::<TimestampSecondType>().value(i) * 1_000_000
+                    }
+                    TimeUnit::Millisecond => {
+                        array.as_primitive::<TimestampMillisecondType>().value(i) * 1000
                     }
+                    TimeUnit::Microsecond => {
+                        array.as_primitive::<TimestampMicrosecondType>().value(i)
+                    }
+                    // nanoseconds are truncated to microseconds
+                    TimeUnit::Nanosecond => array
+                        .as_primitive::<TimestampNanosecondType>()
+                        .value(i)
+                        .div_euclid(1000),
+                };
+                let from_timestamp = py.eval("_from_timestamp", None, None)?;
+                from_timestamp
+                    .call1((micros, tz.as_deref(), self.timezone.as_deref()))?
+                    .into()
+            }
+            // json type
+            DataType::Utf8 | DataType::LargeUtf8 if self.is_json(field) => {
+                let json_str = match array.data_type() {
+                    DataType::Utf8 => array.as_string::<i32>().value(i),
+                    _ => array.as_string::<i64>().value(i),
+                };
+                // XXX: it is slow to call eval every time
+                let json_loads = py.eval("json.loads", None, None)?;
+                json_loads.call1((PyString::new(py, json_str),))?.into()
+            }
+            // decimal type
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::LargeBinary
+                if self.is_decimal(field) =>
+            {
+                let string = match array.data_type() {
+                    DataType::Utf8 => array.as_string::<i32>().value(i).into(),
+                    DataType::LargeUtf8 => array.as_string::<i64>().value(i).into(),
+                    _ => {
+                        let bytes = array.as_binary::<i64>().value(i);
+                        match self.invalid_string {
+                            InvalidStringPolicy::Error => std::str::from_utf8(bytes)?.into(),
+                            InvalidStringPolicy::Replace => String::from_utf8_lossy(bytes),
+                            InvalidStringPolicy::Null => match std::str::from_utf8(bytes) {
+                                Ok(s) => s.into(),
+                                Err(_) => return Ok(py.None()),
+                            },
+                        }
+                    }
+                };
+                // XXX: it is slow to call eval every time
+                let decimal_constructor = py.import("decimal")?.getattr("Decimal")?;
+                decimal_constructor.call1((string.as_ref(),))?.into()
+            }
+            DataType::Decimal128(_, _) => {
+                let array = array.as_primitive::<Decimal128Type>();
+                let decimal_constructor = py.import("decimal")?.getattr("Decimal")?;
+                decimal_constructor
+                    .call1((array.value_as_string(i),))?
+                    .into()
+            }
+            DataType::Utf8 => get_pyobject!(StringArray, py, array, i),
+            DataType::LargeUtf8 => get_pyobject!(LargeStringArray, py, array, i),
+            DataType::Binary => get_pyobject!(BinaryArray, py, array, i),
+            DataType::LargeBinary => get_pyobject!(LargeBinaryArray, py, array, i),
+            DataType::FixedSizeBinary(_) => get_pyobject!(FixedSizeBinaryArray, py, array, i),
+            // list
+            DataType::List(inner) => {
+                let array = array.as_any().downcast_ref::<ListArray>().unwrap();
+                let list = array.value(i);
+                let mut values = Vec::with_capacity(list.len());
+                for j in 0..list.len() {
+                    values.push(self.get_pyobject(py, inner, list.as_ref(), j)?);
+                }
+                values.into_py(py)
+            }
+            DataType::Struct(fields) => {
+                let array = array.as_any().downcast_ref::<StructArray>().unwrap();
+                let object = py.eval("Struct()", None, None)?;
+                for (j, field) in fields.iter().enumerate() {
+                    let value = self.get_pyobject(py, field, array.column(j).as_ref(), i)?;
+                    object.setattr(field.name().as_str(), value)?;
                 }
-                offsets.push(flatten_values.len() as i32);
+                object.into()
             }
-            let values_array = build_array(inner.data_type(), py, &flatten_values)?;
-            let nulls = values.iter().map(|v| !v.is_none(py)).collect();
-            Ok(Arc::new(ListArray::new(
-                inner.clone(),
-                OffsetBuffer::new(offsets.into()),
-                values_array,
-                Some(nulls),
-            )))
+            DataType::RunEndEncoded(run_ends, values) => {
+                let (array, index) = match run_ends.data_type() {
+                    DataType::Int16 => run_value::<Int16Type>(array, i),
+                    DataType::Int32 => run_value::<Int32Type>(array, i),
+                    _ => run_value::<Int64Type>(array, i),
+                };
+                self.get_pyobject(py, values, array.as_ref(), index)?
+            }
+            // union values are `(tag, value)` tuples, where the tag is the name of the variant
+            DataType::Union(fields, _) => {
+                let array = array.as_any().downcast_ref::<UnionArray>().unwrap();
+                let type_id = array.type_id(i);
+                let (_, field) = fields.iter().find(|(id, _)| *id == type_id).unwrap();
+                let child = array.child(type_id);
+                let value = self.get_pyobject(py, field, child.as_ref(), array.value_offset(i))?;
+                (field.name(), value).into_py(py)
+            }
+            _ => todo!(),
+        })
+    }
+
+    /// Returns the field of a struct value, which is an object with the field as an attribute,
+    /// or a dict with the field as a key. Other attributes and keys are ignored.
+    fn get_struct_field(&self, value: &PyAny, name: &str) -> Result<PyObject> {
+        let py = value.py();
+        let field = match value.downcast::<PyDict>() {
+            Ok(dict) => dict.get_item(name)?,
+            Err(_) => match value.getattr(name) {
+                Ok(v) => Some(v),
+                Err(e) if e.is_instance_of::<PyAttributeError>(py) => None,
+                Err(e) => return Err(e.into()),
+            },
+        };
+        match field {
+            Some(v) => Ok(v.into()),
+            None if self.null_missing_fields => Ok(py.None()),
+            None => bail!(
+                "missing field `{name}` of struct in {}",
+                value.get_type().name()?
+            ),
+        }
+    }
+
+    /// Build arrow array from python objects.
+    pub fn build_array(
+        &self,
+        field: &Field,
+        py: Python<'_>,
+        values: &[PyObject],
+    ) -> Result<ArrayRef> {
+        // skip conversion if all values are null, e.g. for sparse optional fields of structs
+        if values.iter().all(|v| v.is_none(py)) {
+            return Ok(new_null_array(field.data_type(), values.len()));
         }
-        DataType::Struct(fields) => {
-            let mut arrays = Vec::with_capacity(fields.len());
-            for field in fields {
-                let mut field_values = Vec::with_capacity(values.len());
+        if let Some(codec) = self.extension_type(field) {
+            return codec.build_array(py, field, values);
+        }
+        match field.data_type() {
+            DataType::Null => build_array!(NullBuilder, py, values),
+            DataType::Boolean => build_array!(BooleanBuilder, py, values),
+            DataType::Int8 => self.build_int_array::<Int8Type>(py, values),
+            DataType::Int16 => self.build_int_array::<Int16Type>(py, values),
+            DataType::Int32 => self.build_int_array::<Int32Type>(py, values),
+            DataType::Int64 => self.build_int_array::<Int64Type>(py, values),
+            DataType::UInt8 => self.build_int_array::<UInt8Type>(py, values),
+            DataType::UInt16 => self.build_int_array::<UInt16Type>(py, values),
+            DataType::UInt32 => self.build_int_array::<UInt32Type>(py, values),
+            DataType::UInt64 => self.build_int_array::<UInt64Type>(py, values),
+            DataType::Float16 => {
+                let mut builder = Float16Builder::with_capacity(values.len());
+                for val in values {
+                    if val.is_none(py) {
+                        builder.append_null();
+                    } else {
+                        let val = numpy_item(val.as_ref(py))?;
+                        builder.append_value(f16::from_f64(val.extract()?));
+                    }
+                }
+                Ok(Arc::new(builder.finish()))
+            }
+            DataType::Float32 => build_array!(Float32Builder, py, values),
+            DataType::Float64 => build_array!(Float64Builder, py, values),
+            DataType::Interval(IntervalUnit::MonthDayNano) => {
+                let mut builder = IntervalMonthDayNanoBuilder::with_capacity(values.len());
+                for val in values {
+                    if val.is_none(py) {
+                        builder.append_null();
+                        continue;
+                    }
+                    let val = val.as_ref(py);
+                    let get = |name: &str| -> Result<i64> { Ok(val.getattr(name)?.extract()?) };
+                    let (months, days, micros) = if val.hasattr("months")? {
+                        // dateutil.relativedelta
+                        let months = get("years")? * 12 + get("months")?;
+                        let seconds = (get("hours")? * 60 + get("minutes")?) * 60 + get("seconds")?;
+                        (
+                            months,
+                            get("days")?,
+                            seconds * 1_000_000 + get("microseconds")?,
+                        )
+                    } else {
+                        // datetime.timedelta
+                        let seconds = get("seconds")?;
+                        (0, get("days")?, seconds * 1_000_000 + get("microseconds")?)
+                    };
+                    builder.append_value(IntervalMonthDayNanoType::make_value(
+                        months.try_into()?,
+                        days.try_into()?,
+                        micros * 1000,
+                    ));
+                }
+                Ok(Arc::new(builder.finish()))
+            }
+            DataType::Timestamp(unit, tz) => {
+                let to_timestamp = py.eval("_to_timestamp", None, None)?;
+                let mut builder = Int64Builder::with_capacity(values.len());
+                for val in values {
+                    if val.is_none(py) {
+                        builder.append_null();
+                        continue;
+                    }
+                    let micros: i64 = to_timestamp
+                        .call1((val, tz.as_deref(), self.timezone.as_deref()))?
+                        .extract()?;
+                    builder.append_value(match unit {
+                        TimeUnit::Second => micros.div_euclid(1_000_000),
+                        TimeUnit::Millisecond => micros.div_euclid(1000),
+                        TimeUnit::Microsecond => micros,
+                        TimeUnit::Nanosecond => micros * 1000,
+                    });
+                }
+                let data = builder
+                    .finish()
+                    .into_data()
+                    .into_builder()
+                    .data_type(field.data_type().clone())
+                    .build()?;
+                Ok(make_array(data))
+            }
+            // json type
+            DataType::Utf8 | DataType::LargeUtf8 if self.is_json(field) => {
+                let json_dumps = py.eval("json.dumps", None, None)?;
+                let mut strings = Vec::with_capacity(values.len());
                 for val in values {
-                    let v = if val.is_none(py) {
-                        py.None()
+                    if val.is_none(py) {
+                        strings.push(None);
+                        continue;
+                    };
+                    let json_str = json_dumps.call1((val,))?;
+                    strings.push(Some(json_str.extract::<String>()?));
+                }
+                Ok(match field.data_type() {
+                    DataType::Utf8 => Arc::new(StringArray::from(strings)),
+                    _ => Arc::new(LargeStringArray::from(strings)),
+                })
+            }
+            // decimal type
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::LargeBinary
+                if self.is_decimal(field) =>
+            {
+                let strings = values
+                    .iter()
+                    .map(|val| (!val.is_none(py)).then(|| val.to_string()))
+                    .collect::<Vec<_>>();
+                Ok(match field.data_type() {
+                    DataType::Utf8 => Arc::new(StringArray::from(strings)),
+                    DataType::LargeUtf8 => Arc::new(LargeStringArray::from(strings)),
+                    _ => Arc::new(LargeBinaryArray::from_iter(strings)),
+                })
+            }
+            DataType::Decimal128(precision, scale) => {
+                let mut builder = Decimal128Builder::with_capacity(values.len());
+                // round to the scale in fixed-point notation
+                let format_spec = format!(".{}f", (*scale).max(0));
+                for val in values {
+                    if val.is_none(py) {
+                        builder.append_null();
                     } else {
-                        val.as_ref(py).getattr(field.name().as_str())?.into()
+                        let string: String = val
+                            .call_method1(py, "__format__", (&format_spec,))?
+                            .extract(py)?;
+                        builder.append_value(parse_decimal::<Decimal128Type>(
+                            &string, *precision, *scale,
+                        )?);
+                    }
+                }
+                Ok(Arc::new(
+                    builder
+                        .finish()
+                        .with_precision_and_scale(*precision, *scale)?,
+                ))
+            }
+            DataType::Utf8 => self.build_string_array::<i32>(py, values),
+            DataType::LargeUtf8 => self.build_string_array::<i64>(py, values),
+            DataType::Binary => build_array!(BinaryBuilder, &[u8], py, values),
+            DataType::LargeBinary => build_array!(LargeBinaryBuilder, &[u8], py, values),
+            DataType::FixedSizeBinary(size) => {
+                let mut builder = FixedSizeBinaryBuilder::with_capacity(values.len(), *size);
+                for val in values {
+                    if val.is_none(py) {
+                        builder.append_null();
+                        continue;
+                    }
+                    let bytes = val.extract::<&[u8]>(py)?;
+                    if bytes.len() != *size as usize {
+                        bail!("expect {size} bytes, got {}", bytes.len());
+                    }
+                    builder.append_value(bytes)?;
+                }
+                Ok(Arc::new(builder.finish()))
+            }
+            // list
+            DataType::List(inner) => {
+                // flatten lists
+                let mut flatten_values = vec![];
+                let mut offsets = Vec::<i32>::with_capacity(values.len() + 1);
+                offsets.push(0);
+                for val in values {
+                    if !val.is_none(py) {
+                        let array = val.as_ref(py);
+                        flatten_values.reserve(array.len()?);
+                        for elem in array.iter()? {
+                            flatten_values.push(elem?.into());
+                        }
+                    }
+                    offsets.push(flatten_values.len() as i32);
+                }
+                let values_array = self.build_array(inner, py, &flatten_values)?;
+                let nulls = values.iter().map(|v| !v.is_none(py)).collect();
+                Ok(Arc::new(ListArray::new(
+                    inner.clone(),
+                    OffsetBuffer::new(offsets.into()),
+                    values_array,
+                    Some(nulls),
+                )))
+            }
+            DataType::Struct(fields) => {
+                let mut arrays = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let mut field_values = Vec::with_capacity(values.len());
+                    for val in values {
+                        let v = if val.is_none(py) {
+                            py.None()
+                        } else {
+                            self.get_struct_field(val.as_ref(py), field.name())?
+                        };
+                        field_values.push(v);
+                    }
+                    arrays.push(self.build_array(field, py, &field_values)?);
+                }
+                let nulls = values.iter().map(|v| !v.is_none(py)).collect();
+                Ok(Arc::new(StructArray::new(
+                    fields.clone(),
+                    arrays,
+                    Some(nulls),
+                )))
+            }
+            DataType::Union(fields, mode) => {
+                let mut type_ids = Vec::with_capacity(values.len());
+                let mut offsets = Vec::with_capacity(values.len());
+                let mut children = fields.iter().map(|_| vec![]).collect::<Vec<_>>();
+                for val in values {
+                    // null is represented as a null value of the first variant
+                    let (index, value) = if val.is_none(py) {
+                        (0, py.None())
+                    } else {
+                        let (tag, value): (&str, PyObject) = val.extract(py)?;
+                        let index = fields
+                            .iter()
+                            .position(|(_, field)| field.name() == tag)
+                            .with_context(|| format!("unknown tag of union: {tag}"))?;
+                        (index, value)
                     };
-                    field_values.push(v);
+                    let (type_id, _) = fields.iter().nth(index).context("empty union")?;
+                    type_ids.push(type_id);
+                    match mode {
+                        UnionMode::Sparse => {
+                            for (i, child) in children.iter_mut().enumerate() {
+                                child.push(match i == index {
+                                    true => value.clone_ref(py),
+                                    false => py.None(),
+                                });
+                            }
+                        }
+                        UnionMode::Dense => {
+                            offsets.push(children[index].len() as i32);
+                            children[index].push(value);
+                        }
+                    }
                 }
-                arrays.push(build_array(field.data_type(), py, &field_values)?);
+                let mut child_data = Vec::with_capacity(children.len());
+                for ((_, field), values) in fields.iter().zip(&children) {
+                    child_data.push(self.build_array(field, py, values)?.to_data());
+                }
+                let mut builder = ArrayData::builder(field.data_type().clone())
+                    .len(values.len())
+                    .add_buffer(Buffer::from_vec(type_ids))
+                    .child_data(child_data);
+                if *mode == UnionMode::Dense {
+                    builder = builder.add_buffer(Buffer::from_vec(offsets));
+                }
+                Ok(make_array(builder.build()?))
             }
-            let nulls = values.iter().map(|v| !v.is_none(py)).collect();
-            Ok(Arc::new(StructArray::new(
-                fields.clone(),
-                arrays,
-                Some(nulls),
-            )))
+            _ => todo!(),
         }
-        _ => todo!(),
     }
+
+    /// Build a string array from python `str` objects.
+    fn build_string_array<O: OffsetSizeTrait>(
+        &self,
+        py: Python<'_>,
+        values: &[PyObject],
+    ) -> Result<ArrayRef> {
+        let mut builder = GenericStringBuilder::<O>::with_capacity(values.len(), 1024);
+        for val in values {
+            if val.is_none(py) {
+                builder.append_null();
+                continue;
+            }
+            let val = val.as_ref(py);
+            let err = match val.extract::<&str>() {
+                Ok(s) => {
+                    builder.append_value(s);
+                    continue;
+                }
+                Err(err) => err,
+            };
+            // strings with lone surrogates can not be encoded as UTF-8
+            if !val.is_instance_of::<PyString>() {
+                return Err(err.into());
+            }
+            match self.invalid_string {
+                InvalidStringPolicy::Error => return Err(err.into()),
+                InvalidStringPolicy::Replace => {
+                    let s = val
+                        .call_method1("encode", ("utf-16", "surrogatepass"))?
+                        .call_method1("decode", ("utf-16", "replace"))?;
+                    builder.append_value(s.extract::<&str>()?);
+                }
+                InvalidStringPolicy::Null => builder.append_null(),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+
+    /// Build an integer array from python integers.
+    fn build_int_array<T: ArrowPrimitiveType>(
+        &self,
+        py: Python<'_>,
+        values: &[PyObject],
+    ) -> Result<ArrayRef>
+    where
+        T::Native: TryFrom<i128>,
+    {
+        let mut builder = PrimitiveBuilder::<T>::with_capacity(values.len());
+        for (i, val) in values.iter().enumerate() {
+            if val.is_none(py) {
+                builder.append_null();
+                continue;
+            }
+            let val = numpy_item(val.as_ref(py))?;
+            // `__index__` is used to accept other integer-like objects
+            let value = match val.extract::<i128>() {
+                Ok(v) => T::Native::try_from(v).ok(),
+                Err(e) if e.is_instance_of::<PyOverflowError>(py) => None,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(v) = value {
+                builder.append_value(v);
+                continue;
+            }
+            match self.int_overflow {
+                IntOverflowPolicy::Error => bail!(
+                    "integer {} at row {i} is out of range of {}",
+                    val.str()?,
+                    T::DATA_TYPE
+                ),
+                IntOverflowPolicy::Saturate => match val.lt(0)? {
+                    true => builder.append_value(T::Native::MIN_TOTAL_ORDER),
+                    false => builder.append_value(T::Native::MAX_TOTAL_ORDER),
+                },
+                IntOverflowPolicy::Null => builder.append_null(),
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
+
+/// Converts numpy scalars and 0-d arrays to the equivalent python objects, e.g. `numpy.int64`
+/// to `int` and `numpy.bool_` to `bool`. Other objects are returned as is.
+///
+/// numpy is not imported here, so it's only required by functions that return its values.
+fn numpy_item(obj: &PyAny) -> PyResult<&PyAny> {
+    if obj.is_instance_of::<PyLong>() || obj.is_instance_of::<PyFloat>() {
+        return Ok(obj);
+    }
+    let ty = obj.get_type();
+    if ty
+        .getattr(intern!(obj.py(), "__module__"))?
+        .extract::<&str>()?
+        != "numpy"
+    {
+        return Ok(obj);
+    }
+    if ty.name()? == "ndarray" && obj.getattr(intern!(obj.py(), "ndim"))?.extract::<usize>()? != 0 {
+        return Ok(obj);
+    }
+    match obj.getattr(intern!(obj.py(), "item")) {
+        Ok(item) => item.call0(),
+        Err(_) => Ok(obj),
+    }
+}
+
+/// Returns the values and the physical index of the `i`-th element of a run-end encoded array.
+fn run_value<R: RunEndIndexType>(array: &dyn Array, i: usize) -> (&ArrayRef, usize) {
+    let array = array.as_any().downcast_ref::<RunArray<R>>().unwrap();
+    (array.values(), array.get_physical_index(i))
 }
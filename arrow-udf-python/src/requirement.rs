@@ -0,0 +1,207 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Python package requirements like `numpy==1.26`.
+
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+
+/// A requirement of a Python package.
+///
+/// Only a subset of [PEP 440] version specifiers are supported:
+/// `==`, `!=`, `>=`, `<=`, `>`, `<`, `~=` and the prefix match `==1.26.*`.
+/// Multiple specifiers are separated by commas, e.g. `numpy>=1.20,<2`.
+///
+/// Versions are compared by their release segments, so pre-releases and post-releases
+/// are considered equal to their release.
+///
+/// [PEP 440]: https://peps.python.org/pep-0440/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    /// The distribution name.
+    pub name: String,
+    /// The version specifiers.
+    specifiers: Vec<(Operator, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Compatible,
+}
+
+impl Requirement {
+    /// Parses a requirement string.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (name, specs) = match s.find(['=', '!', '<', '>', '~']) {
+            Some(i) => s.split_at(i),
+            None => (s, ""),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("invalid requirement: {s:?}");
+        }
+        let mut specifiers = vec![];
+        for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (op, version) = match spec {
+                s if s.starts_with("==") => (Operator::Eq, &s[2..]),
+                s if s.starts_with("!=") => (Operator::Ne, &s[2..]),
+                s if s.starts_with(">=") => (Operator::Ge, &s[2..]),
+                s if s.starts_with("<=") => (Operator::Le, &s[2..]),
+                s if s.starts_with("~=") => (Operator::Compatible, &s[2..]),
+                s if s.starts_with('>') => (Operator::Gt, &s[1..]),
+                s if s.starts_with('<') => (Operator::Lt, &s[1..]),
+                _ => bail!("invalid version specifier {spec:?} in requirement {s:?}"),
+            };
+            let version = version.trim();
+            if version.is_empty() {
+                bail!("missing version in requirement {s:?}");
+            }
+            specifiers.push((op, version.to_string()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            specifiers,
+        })
+    }
+
+    /// Returns true if the installed version satisfies the requirement.
+    pub fn matches(&self, version: &str) -> bool {
+        let installed = release(version);
+        self.specifiers.iter().all(|(op, spec)| {
+            if let Some(prefix) = spec.strip_suffix(".*") {
+                let prefix = release(prefix);
+                let is_prefix = installed.len() >= prefix.len()
+                    && compare(&installed[..prefix.len()], &prefix).is_eq();
+                return match op {
+                    Operator::Eq => is_prefix,
+                    Operator::Ne => !is_prefix,
+                    _ => false,
+                };
+            }
+            let spec = release(spec);
+            let ord = compare(&installed, &spec);
+            match op {
+                Operator::Eq => ord.is_eq(),
+                Operator::Ne => ord.is_ne(),
+                Operator::Ge => ord.is_ge(),
+                Operator::Le => ord.is_le(),
+                Operator::Gt => ord.is_gt(),
+                Operator::Lt => ord.is_lt(),
+                // `~=1.26.0` means `>=1.26.0, ==1.26.*`
+                Operator::Compatible => {
+                    let prefix = &spec[..spec.len().saturating_sub(1).max(1)];
+                    ord.is_ge()
+                        && installed.len() >= prefix.len()
+                        && compare(&installed[..prefix.len()], prefix).is_eq()
+                }
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (i, (op, version)) in self.specifiers.iter().enumerate() {
+            let op = match op {
+                Operator::Eq => "==",
+                Operator::Ne => "!=",
+                Operator::Ge => ">=",
+                Operator::Le => "<=",
+                Operator::Gt => ">",
+                Operator::Lt => "<",
+                Operator::Compatible => "~=",
+            };
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{sep}{op}{version}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the release segments of a version, e.g. `1.26.0rc1` => `[1, 26, 0]`.
+fn release(version: &str) -> Vec<u64> {
+    let version = version.trim();
+    // strip epoch
+    let version = version.split_once('!').map_or(version, |(_, v)| v);
+    let mut segments = vec![];
+    for part in version.split('.') {
+        let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let Ok(n) = part[..digits].parse() else {
+            break;
+        };
+        segments.push(n);
+        if digits != part.len() {
+            // pre-release, post-release or local version
+            break;
+        }
+    }
+    segments
+}
+
+/// Compares two releases, padding the shorter one with zeros.
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    let get = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| get(a, i).cmp(&get(b, i)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn check(requirement: &str, version: &str, expected: bool) {
+        let req = Requirement::parse(requirement).unwrap();
+        assert_eq!(req.matches(version), expected, "{requirement} {version}");
+    }
+
+    #[test]
+    fn test_parse() {
+        let req = Requirement::parse("numpy >= 1.20, < 2").unwrap();
+        assert_eq!(req.name, "numpy");
+        assert_eq!(req.to_string(), "numpy>=1.20,<2");
+        assert_eq!(Requirement::parse("regex").unwrap().to_string(), "regex");
+        assert!(Requirement::parse("==1.0").is_err());
+        assert!(Requirement::parse("numpy=1.0").is_err());
+        assert!(Requirement::parse("numpy==").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        check("regex", "2023.12.25", true);
+        check("numpy==1.26", "1.26.0", true);
+        check("numpy==1.26", "1.26.4", false);
+        check("numpy==1.26.*", "1.26.4", true);
+        check("numpy==1.26.*", "1.27.0", false);
+        check("numpy!=1.26.*", "1.27.0", true);
+        check("numpy>=1.20,<2", "1.26.4", true);
+        check("numpy>=1.20,<2", "2.0.0", false);
+        check("numpy>=1.20,<2", "2.0.0rc1", false);
+        check("numpy>1.26", "1.26.0", false);
+        check("numpy<=1.26", "1.26.0", true);
+        check("numpy~=1.26", "1.27.1", true);
+        check("numpy~=1.26", "2.0", false);
+        check("numpy~=1.26.0", "1.26.4", true);
+        check("numpy~=1.26.0", "1.27.0", false);
+    }
+}
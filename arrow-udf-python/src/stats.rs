@@ -0,0 +1,168 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Resource accounting and quotas of functions.
+
+use anyhow::{bail, Result};
+use pyo3::{PyResult, Python};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The resource usage of a function.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionStats {
+    /// The number of times the Python function is called.
+    pub calls: u64,
+    /// The cumulative CPU time of evaluations, including the conversion of values.
+    pub cpu_time: Duration,
+    /// The peak memory allocated by Python during an evaluation, in bytes.
+    ///
+    /// This is only tracked if enabled by [`Builder::track_memory`](crate::Builder::track_memory).
+    pub peak_memory: usize,
+    /// Whether the function is disabled for exceeding its quota.
+    pub disabled: bool,
+}
+
+/// The limits of resource usage of a function.
+///
+/// Once any limit is exceeded, the function is disabled and later calls return an error,
+/// until the stats are reset by [`Runtime::reset_stats`](crate::Runtime::reset_stats).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// The maximum number of times the Python function can be called.
+    pub max_calls: Option<u64>,
+    /// The maximum cumulative CPU time.
+    pub max_cpu_time: Option<Duration>,
+    /// The maximum memory allocated by Python during an evaluation, in bytes.
+    ///
+    /// This requires [`Builder::track_memory`](crate::Builder::track_memory).
+    pub max_memory: Option<usize>,
+}
+
+/// The statistics and quota of a function.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    calls: AtomicU64,
+    cpu_time_ns: AtomicU64,
+    peak_memory: AtomicUsize,
+    disabled: AtomicBool,
+    pub quota: Quota,
+}
+
+impl Stats {
+    /// Returns an error if the function is disabled.
+    pub fn check(&self, name: &str) -> Result<()> {
+        if self.disabled.load(Ordering::Relaxed) {
+            bail!("function {name} is disabled for exceeding its quota");
+        }
+        Ok(())
+    }
+
+    /// Adds the usage of an evaluation, and disables the function if it exceeds the quota.
+    pub fn record(&self, usage: Usage) {
+        let calls = self.calls.fetch_add(usage.calls, Ordering::Relaxed) + usage.calls;
+        let cpu_time_ns = self
+            .cpu_time_ns
+            .fetch_add(usage.cpu_time_ns, Ordering::Relaxed)
+            + usage.cpu_time_ns;
+        let peak_memory = self
+            .peak_memory
+            .fetch_max(usage.peak_memory, Ordering::Relaxed)
+            .max(usage.peak_memory);
+        let quota = &self.quota;
+        if quota.max_calls.is_some_and(|max| calls > max)
+            || quota
+                .max_cpu_time
+                .is_some_and(|max| Duration::from_nanos(cpu_time_ns) > max)
+            || quota.max_memory.is_some_and(|max| peak_memory > max)
+        {
+            self.disabled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns a snapshot of the statistics.
+    pub fn get(&self) -> FunctionStats {
+        FunctionStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            cpu_time: Duration::from_nanos(self.cpu_time_ns.load(Ordering::Relaxed)),
+            peak_memory: self.peak_memory.load(Ordering::Relaxed),
+            disabled: self.disabled.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Resets the statistics and enables the function.
+    pub fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.cpu_time_ns.store(0, Ordering::Relaxed);
+        self.peak_memory.store(0, Ordering::Relaxed);
+        self.disabled.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The resource usage of an evaluation.
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    pub calls: u64,
+    cpu_time_ns: u64,
+    peak_memory: usize,
+}
+
+/// Measures the CPU time and memory of an evaluation in the interpreter.
+///
+/// It uses the internal names defined when the runtime is built.
+pub(crate) struct Meter {
+    track_memory: bool,
+    start_cpu_time_ns: u64,
+    start_memory: usize,
+}
+
+impl Meter {
+    /// Starts measuring.
+    pub fn start(py: Python<'_>, track_memory: bool) -> PyResult<Self> {
+        let start_memory = match track_memory {
+            true => py
+                .eval(
+                    "_tracemalloc.reset_peak() or _tracemalloc.get_traced_memory()[0]",
+                    None,
+                    None,
+                )?
+                .extract()?,
+            false => 0,
+        };
+        let start_cpu_time_ns = py.eval("_thread_time_ns()", None, None)?.extract()?;
+        Ok(Self {
+            track_memory,
+            start_cpu_time_ns,
+            start_memory,
+        })
+    }
+
+    /// Stops measuring and returns the usage with the number of calls.
+    pub fn finish(self, py: Python<'_>, calls: u64) -> PyResult<Usage> {
+        let cpu_time_ns: u64 = py.eval("_thread_time_ns()", None, None)?.extract()?;
+        let peak_memory = match self.track_memory {
+            true => {
+                let peak: usize = py
+                    .eval("_tracemalloc.get_traced_memory()[1]", None, None)?
+                    .extract()?;
+                peak.saturating_sub(self.start_memory)
+            }
+            false => 0,
+        };
+        Ok(Usage {
+            calls,
+            cpu_time_ns: cpu_time_ns.saturating_sub(self.start_cpu_time_ns),
+            peak_memory,
+        })
+    }
+}
@@ -14,10 +14,23 @@
 
 use std::sync::Arc;
 
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int32Type, Int64Type, IntervalMonthDayNanoType,
+};
 use arrow_array::*;
 use arrow_cast::pretty::pretty_format_batches;
-use arrow_schema::{DataType, Field, Schema};
-use arrow_udf_python::{CallMode, Runtime};
+use arrow_schema::{
+    DataType, Field, Fields, IntervalUnit, Schema, TimeUnit, UnionFields, UnionMode,
+};
+use arrow_udf::testing::{assert_roundtrip_batch, random_field_array};
+use arrow_udf_python::{
+    CallMode, CancellationToken, Env, EvalContext, ExtensionTypeCodec, IntOverflowPolicy,
+    InvalidStringPolicy, Quota, Runtime, Signature, UdfError,
+};
+use proptest::prelude::*;
+use pyo3::types::{IntoPyDict, PyBytes};
+use pyo3::{PyObject, Python};
 
 #[test]
 fn test_gcd() {
@@ -65,281 +78,286 @@ def gcd(a: int, b: int) -> int:
 }
 
 #[test]
-fn test_fib() {
+fn test_skip_null_rows() {
     let mut runtime = Runtime::new().unwrap();
     runtime
         .add_function(
-            "fib",
+            "add",
             DataType::Int32,
             CallMode::ReturnNullOnNullInput,
             r#"
-def fib(n: int) -> int:
-    if n <= 1:
-        return n
-    else:
-        return fib(n - 1) + fib(n - 2)
+def add(a: int, b: int) -> int:
+    assert a is not None and b is not None
+    return a + b
 "#,
         )
         .unwrap();
 
-    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
-    let arg0 = Int32Array::from(vec![30]);
-    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    // more rows than the interval of releasing the GIL
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from_iter((0..3000).map(|i| (i % 3 != 0).then_some(i)));
+    let arg1 = Int32Array::from_iter((0..3000).map(|i| (i % 5 != 0).then_some(1)));
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
 
-    let output = runtime.call("fib", &input).unwrap();
-    assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+--------+
-| fib    |
-+--------+
-| 832040 |
-+--------+
-"#
-        .trim()
-    );
+    let output = runtime.call("add", &input).unwrap();
+    let expected =
+        Int32Array::from_iter((0..3000).map(|i| (i % 3 != 0 && i % 5 != 0).then_some(i + 1)));
+    assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
 }
 
 #[test]
-fn test_decimal_add() {
+fn test_run_end_encoded() {
     let mut runtime = Runtime::new().unwrap();
-
     runtime
         .add_function(
-            "decimal_add",
-            DataType::LargeBinary,
+            "double",
+            DataType::Int32,
             CallMode::ReturnNullOnNullInput,
             r#"
-def decimal_add(a, b):
-    return a + b
-            "#,
+def double(x):
+    return x * 2
+"#,
         )
         .unwrap();
 
-    let schema = Schema::new(vec![
-        Field::new("a", DataType::LargeBinary, true),
-        Field::new("b", DataType::LargeBinary, true),
-    ]);
-    let arg0 = LargeBinaryArray::from(vec![b"0.0001".as_ref()]);
-    let arg1 = LargeBinaryArray::from(vec![b"0.0002".as_ref()]);
-    let input =
-        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+    let run_ends = Int32Array::from(vec![2, 3, 5]);
+    let values = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let arg0 = RunArray::try_new(&run_ends, &values).unwrap();
+    let schema = Schema::new(vec![Field::new("x", arg0.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = runtime.call("decimal_add", &input).unwrap();
-    assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+--------------+
-| decimal_add  |
-+--------------+
-| 302e30303033 |
-+--------------+
-"#
-        .trim()
-    );
+    let output = runtime.call("double", &input).unwrap();
+    let expected = Int32Array::from(vec![Some(2), Some(2), None, Some(6), Some(6)]);
+    assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
 }
 
 #[test]
-fn test_json_array_access() {
+fn test_all_null() {
     let mut runtime = Runtime::new().unwrap();
-
+    let fields = Fields::from(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::new_list(DataType::Utf8, true), true),
+    ]);
     runtime
         .add_function(
-            "json_array_access",
-            DataType::LargeUtf8,
-            CallMode::ReturnNullOnNullInput,
+            "wrap",
+            DataType::Struct(fields.clone()),
+            CallMode::CalledOnNullInput,
             r#"
-def json_array_access(array, i):
-    return array[i]
-            "#,
+class Wrapped:
+    def __init__(self, a):
+        self.a = a
+        self.b = None
+
+def wrap(x):
+    return None if x is None else Wrapped(x)
+"#,
         )
         .unwrap();
 
-    let schema = Schema::new(vec![
-        Field::new("array", DataType::LargeUtf8, true),
-        Field::new("i", DataType::Int32, true),
-    ]);
-    let arg0 = LargeStringArray::from(vec![r#"[1, null, ""]"#]);
-    let arg1 = Int32Array::from(vec![0]);
-    let input =
-        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![None, None, None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = runtime.call("json_array_access", &input).unwrap();
+    let output = runtime.call("wrap", &input).unwrap();
+    assert_eq!(output.column(0).data_type(), &DataType::Struct(fields));
+    assert_eq!(output.column(0).null_count(), 3);
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), Some(2)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    // the list field is all null
+    let output = runtime.call("wrap", &input).unwrap();
+    let output = output.column(0).as_struct();
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+-------------------+
-| json_array_access |
-+-------------------+
-| 1                 |
-+-------------------+
-"#
-        .trim()
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![1, 2])
     );
+    assert_eq!(output.column(1).null_count(), 2);
 }
 
 #[test]
-fn test_return_array() {
-    let mut runtime = Runtime::new().unwrap();
+fn test_struct_fields() {
+    let fields = Fields::from(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Utf8, true),
+    ]);
+    let code = r#"
+class Point:
+    def __init__(self, a):
+        self.a = a
+        self.extra = 0
 
+def to_struct(x):
+    if x == 1:
+        return {"a": x, "b": "dict", "extra": 0}
+    if x == 2:
+        return {"a": x}
+    return Point(x)
+"#;
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), Some(2), Some(3), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    // missing fields are errors by default
+    let mut runtime = Runtime::new().unwrap();
     runtime
         .add_function(
-            "to_array",
-            DataType::new_list(DataType::Int32, true),
-            CallMode::CalledOnNullInput,
-            r#"
-def to_array(x):
-    if x is None:
-        return None
-    else:
-        return [x]
-"#,
+            "to_struct",
+            DataType::Struct(fields.clone()),
+            CallMode::ReturnNullOnNullInput,
+            code,
         )
         .unwrap();
+    let error = runtime.call("to_struct", &input).unwrap_err();
+    assert_eq!(error.to_string(), "missing field `b` of struct in dict");
 
-    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
-    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
-    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
-
-    let output = runtime.call("to_array", &input).unwrap();
+    let mut runtime = Runtime::builder()
+        .null_missing_fields(true)
+        .build()
+        .unwrap();
+    runtime
+        .add_function(
+            "to_struct",
+            DataType::Struct(fields),
+            CallMode::ReturnNullOnNullInput,
+            code,
+        )
+        .unwrap();
+    let output = runtime.call("to_struct", &input).unwrap();
+    let output = output.column(0).as_struct();
+    assert!(output.is_null(3));
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+----------+
-| to_array |
-+----------+
-| [1]      |
-|          |
-| [3]      |
-+----------+
-    "#
-        .trim()
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(1), Some(2), Some(3), None])
+    );
+    assert_eq!(
+        output.column(1).as_string::<i32>(),
+        &StringArray::from(vec![Some("dict"), None, None, None])
     );
 }
 
 #[test]
-fn test_key_value() {
+#[cfg(feature = "leak_check")]
+fn test_leak_check() {
     let mut runtime = Runtime::new().unwrap();
-
     runtime
         .add_function(
-            "key_value",
-            DataType::Struct(
-                vec![
-                    Field::new("key", DataType::Utf8, true),
-                    Field::new("value", DataType::Utf8, true),
-                ]
-                .into(),
-            ),
-            CallMode::ReturnNullOnNullInput,
+            "leak",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
             r#"
-class KeyValue:
-    def __init__(self, key, value):
-        self.key = key
-        self.value = value
-
-def key_value(s: str):
-    key, value = s.split('=')
-    return KeyValue(key, value)
+_cache = []
+def leak(x):
+    _cache.append([x] * 10)
+    return x
+"#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "no_leak",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            r#"
+def no_leak(x):
+    return len([x] * 10)
 "#,
         )
         .unwrap();
 
-    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
-    let arg0 = StringArray::from(vec!["a=b"]);
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1, 2, 3]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = runtime.call("key_value", &input).unwrap();
-    assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+--------------------+
-| key_value          |
-+--------------------+
-| {key: a, value: b} |
-+--------------------+
-"#
-        .trim()
-    );
+    // warm up caches
+    runtime.call("leak", &input).unwrap();
+    runtime.call("no_leak", &input).unwrap();
+    runtime.reset_leaks("leak").unwrap();
+    runtime.reset_leaks("no_leak").unwrap();
+    for _ in 0..5 {
+        runtime.call("leak", &input).unwrap();
+        runtime.call("no_leak", &input).unwrap();
+    }
+    let leaks = runtime.leaks();
+    assert_eq!(leaks["leak"].evals, 5);
+    assert_eq!(leaks["leak"].leaking_evals, 5);
+    // a list for each row
+    assert!(leaks["leak"].leaked_blocks >= 15);
+    assert_eq!(leaks["no_leak"].evals, 5);
+    assert!(leaks["no_leak"].leaked_blocks < leaks["leak"].leaked_blocks);
 }
 
 #[test]
-fn test_struct_to_json() {
-    let mut runtime = Runtime::new().unwrap();
-
+fn test_stats_and_quota() {
+    let mut runtime = Runtime::builder().track_memory(true).build().unwrap();
     runtime
         .add_function(
-            "to_json",
-            DataType::LargeUtf8,
-            CallMode::ReturnNullOnNullInput,
+            "alloc",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
             r#"
-def to_json(object):
-    return object.__dict__
+def alloc(n):
+    return len([0] * n)
 "#,
         )
         .unwrap();
 
-    let schema = Schema::new(vec![Field::new(
-        "struct",
-        DataType::Struct(
-            vec![
-                Field::new("key", DataType::Utf8, true),
-                Field::new("value", DataType::Utf8, true),
-            ]
-            .into(),
-        ),
-        true,
-    )]);
-    let arg0 = StructArray::from(vec![
-        (
-            Arc::new(Field::new("key", DataType::Utf8, true)),
-            Arc::new(StringArray::from(vec![Some("a"), None])) as ArrayRef,
-        ),
-        (
-            Arc::new(Field::new("value", DataType::Utf8, true)),
-            Arc::new(StringArray::from(vec![Some("b"), None])),
-        ),
-    ]);
+    let schema = Schema::new(vec![Field::new("n", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![10, 1_000_000, 10]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = runtime.call("to_json", &input).unwrap();
+    runtime.call("alloc", &input).unwrap();
+    let stats = runtime.stats()["alloc"];
+    assert_eq!(stats.calls, 3);
+    assert!(stats.peak_memory >= 8_000_000);
+    assert!(!stats.disabled);
+
+    // the function is disabled after exceeding the quota
+    let quota = Quota {
+        max_calls: Some(4),
+        ..Default::default()
+    };
+    runtime.set_quota("alloc", quota).unwrap();
+    runtime.call("alloc", &input).unwrap();
+    assert!(runtime.stats()["alloc"].disabled);
+    let error = runtime.call("alloc", &input).unwrap_err();
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+------------------------------+
-| to_json                      |
-+------------------------------+
-| {"key": "a", "value": "b"}   |
-| {"key": null, "value": null} |
-+------------------------------+
-"#
-        .trim()
+        error.to_string(),
+        "function alloc is disabled for exceeding its quota"
     );
+
+    runtime.reset_stats("alloc").unwrap();
+    runtime.call("alloc", &input).unwrap();
+    assert_eq!(runtime.stats()["alloc"].calls, 3);
 }
 
 #[test]
-fn test_range() {
+fn test_cancellation() {
     let mut runtime = Runtime::new().unwrap();
-
     runtime
         .add_function(
-            "range1",
+            "square",
             DataType::Int32,
             CallMode::ReturnNullOnNullInput,
             r#"
-def range1(n: int):
+def square(x):
+    return x * x
+"#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "series",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def series(n):
     for i in range(n):
         yield i
 "#,
@@ -350,10 +368,1120 @@ def range1(n: int):
     let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let mut outputs = runtime.call_table_function("range1", &input, 2).unwrap();
+    let token = CancellationToken::new();
+    let mut context = EvalContext::new().with_cancellation_token(token.clone());
+    let output = runtime
+        .call_with_context("square", &input, &mut context)
+        .unwrap();
+    assert_eq!(output.num_rows(), 3);
 
-    assert_eq!(outputs.schema().field(0).name(), "row");
-    assert_eq!(outputs.schema().field(1).name(), "range1");
+    token.cancel();
+    let error = runtime
+        .call_with_context("square", &input, &mut context)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "function call cancelled");
+
+    let mut outputs = runtime
+        .call_table_function("series", &input, 2)
+        .unwrap()
+        .with_cancellation_token(token);
+    let error = outputs.next().unwrap().unwrap_err();
+    assert_eq!(error.to_string(), "function call cancelled");
+}
+
+#[test]
+fn test_invalid_string() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let build = |policy| {
+        let mut runtime = Runtime::builder()
+            .invalid_string_policy(policy)
+            .build()
+            .unwrap();
+        runtime
+            .add_function(
+                "lone_surrogate",
+                DataType::Utf8,
+                CallMode::ReturnNullOnNullInput,
+                r#"
+def lone_surrogate(x):
+    return x + "\udc80b"
+"#,
+            )
+            .unwrap();
+        runtime
+    };
+
+    let runtime = build(InvalidStringPolicy::Error);
+    runtime.call("lone_surrogate", &input).unwrap_err();
+
+    let runtime = build(InvalidStringPolicy::Replace);
+    let output = runtime.call("lone_surrogate", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_string::<i32>(),
+        &StringArray::from(vec![Some("a\u{FFFD}b"), None])
+    );
+
+    let runtime = build(InvalidStringPolicy::Null);
+    let output = runtime.call("lone_surrogate", &input).unwrap();
+    assert_eq!(output.column(0).null_count(), 2);
+}
+
+#[test]
+fn test_int_overflow() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int64, true)]);
+    let arg0 = Int64Array::from(vec![Some(1), Some(1 << 40), Some(-(1 << 40)), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let build = |policy| {
+        let mut runtime = Runtime::builder()
+            .int_overflow_policy(policy)
+            .build()
+            .unwrap();
+        runtime
+            .add_function(
+                "index",
+                DataType::Int32,
+                CallMode::ReturnNullOnNullInput,
+                r#"
+class Index:
+    def __init__(self, x):
+        self.x = x
+    def __index__(self):
+        return self.x
+
+def index(x):
+    return Index(x)
+"#,
+            )
+            .unwrap();
+        runtime
+    };
+
+    let runtime = build(IntOverflowPolicy::Error);
+    let err = runtime.call("index", &input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "integer 1099511627776 at row 1 is out of range of Int32"
+    );
+
+    let runtime = build(IntOverflowPolicy::Saturate);
+    let output = runtime.call("index", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(1), Some(i32::MAX), Some(i32::MIN), None])
+    );
+
+    let runtime = build(IntOverflowPolicy::Null);
+    let output = runtime.call("index", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(1), None, None, None])
+    );
+}
+
+#[test]
+fn test_numpy_scalars() {
+    // numpy can not be imported in sub-interpreters, so mock its scalar types
+    let numpy = r#"
+class bool_:
+    __module__ = "numpy"
+    def __init__(self, value):
+        self.value = value
+    def item(self):
+        return self.value
+
+class float32(bool_):
+    __module__ = "numpy"
+
+class ndarray(bool_):
+    __module__ = "numpy"
+    ndim = 0
+"#;
+    let mut runtime = Runtime::new().unwrap();
+    for (name, ty, expr) in [
+        ("np_bool", DataType::Boolean, "bool_(x % 2 == 0)"),
+        ("np_float", DataType::Float32, "float32(x / 2)"),
+        ("np_array", DataType::Int32, "ndarray(x * 2)"),
+    ] {
+        let code = format!("{numpy}\ndef {name}(x):\n    return {expr}\n");
+        runtime
+            .add_function(name, ty, CallMode::ReturnNullOnNullInput, &code)
+            .unwrap();
+    }
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(4)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("np_bool", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_boolean(),
+        &BooleanArray::from(vec![Some(false), None, Some(true)])
+    );
+    let output = runtime.call("np_float", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Float32Type>(),
+        &Float32Array::from(vec![Some(0.5), None, Some(2.0)])
+    );
+    let output = runtime.call("np_array", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(2), None, Some(8)])
+    );
+}
+
+#[test]
+fn test_context_argument() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "audit",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def audit(x: int, *, ctx) -> str:
+    return f"{ctx.metadata['query_id']}:{ctx.batch_id}:{ctx.row}:{x}"
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let mut context = EvalContext::new()
+        .with_batch_id(7)
+        .with_metadata("query_id", "q1");
+    let output = runtime
+        .call_with_context("audit", &input, &mut context)
+        .unwrap();
+    let expected = StringArray::from(vec![Some("q1:7:0:1"), None, Some("q1:7:2:3")]);
+    assert_eq!(output.column(0).as_string::<i32>(), &expected);
+}
+
+#[test]
+fn test_env() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "sign",
+            DataType::Utf8,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def sign(x: str, *, ctx) -> str:
+    if x == 'fail':
+        raise ValueError(f"bad request with key {ctx.env['API_KEY']}")
+    return f"{ctx.env['ENDPOINT']}/{x}"
+"#,
+        )
+        .unwrap();
+    let env = Env::new()
+        .with_var("ENDPOINT", "https://api.example.com")
+        .with_secret("API_KEY", "s3cr3t");
+    runtime.set_env("sign", env).unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(arg0)]).unwrap();
+    let output = runtime.call("sign", &input).unwrap();
+    let expected = StringArray::from(vec![Some("https://api.example.com/a"), None]);
+    assert_eq!(output.column(0).as_string::<i32>(), &expected);
+
+    // secrets are redacted in errors
+    let arg0 = StringArray::from(vec!["fail"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let error = runtime.call("sign", &input).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "ValueError: bad request with key *** (at row 0)"
+    );
+    let Some(UdfError::Exception { traceback, .. }) = error.downcast_ref::<UdfError>() else {
+        panic!("unexpected error: {error:?}");
+    };
+    assert!(!traceback.contains("s3cr3t"));
+
+    assert!(runtime.set_env("unknown", Env::new()).is_err());
+}
+
+#[test]
+fn test_call_with_context() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "div",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def div(a: int, b: int) -> int:
+    return a // b
+"#,
+        )
+        .unwrap();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]));
+    let batch = |x: Vec<Option<i32>>, y: Vec<Option<i32>>| {
+        let columns: Vec<ArrayRef> =
+            vec![Arc::new(Int32Array::from(x)), Arc::new(Int32Array::from(y))];
+        RecordBatch::try_new(schema.clone(), columns).unwrap()
+    };
+
+    let mut context = EvalContext::new();
+    let input = batch(vec![Some(6), None], vec![Some(3), Some(1)]);
+    let output = runtime
+        .call_with_context("div", &input, &mut context)
+        .unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(2), None])
+    );
+
+    // the context can be reused after an error
+    let input = batch(vec![Some(1), Some(2)], vec![Some(1), Some(0)]);
+    runtime
+        .call_with_context("div", &input, &mut context)
+        .unwrap_err();
+
+    let input = batch(vec![Some(9)], vec![Some(2)]);
+    let output = runtime
+        .call_with_context("div", &input, &mut context)
+        .unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(4)])
+    );
+}
+
+#[test]
+fn test_exception() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "div",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def div(a: int, b: int) -> int:
+    return a // b
+"#,
+        )
+        .unwrap();
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from(vec![Some(1), Some(2)]);
+    let arg1 = Int32Array::from(vec![Some(1), Some(0)]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let error = runtime.call("div", &input).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "ZeroDivisionError: integer division or modulo by zero (at row 1)"
+    );
+    let Some(UdfError::Exception {
+        row,
+        type_name,
+        message,
+        traceback,
+    }) = error.downcast_ref::<UdfError>()
+    else {
+        panic!("expect exception, got {error:?}");
+    };
+    assert_eq!(*row, 1);
+    assert_eq!(type_name, "ZeroDivisionError");
+    assert_eq!(message, "integer division or modulo by zero");
+    assert!(traceback.contains("in div"), "{traceback}");
+}
+
+#[test]
+fn test_fib() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "fib",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def fib(n: int) -> int:
+    if n <= 1:
+        return n
+    else:
+        return fib(n - 1) + fib(n - 2)
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![30]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("fib", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++--------+
+| fib    |
++--------+
+| 832040 |
++--------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_decimal_add() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "decimal_add",
+            decimal_field("decimal_add"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def decimal_add(a, b):
+    return a + b
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![decimal_field("a"), decimal_field("b")]);
+    let arg0 = StringArray::from(vec!["0.0001"]);
+    let arg1 = StringArray::from(vec!["0.0002"]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("decimal_add", &input).unwrap();
+    assert_eq!(output.schema().field(0), &decimal_field("decimal_add"));
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------+
+| decimal_add |
++-------------+
+| 0.0003      |
++-------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_decimal128_add() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "decimal_add",
+            DataType::Decimal128(10, 2),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def decimal_add(a, b):
+    return a + b
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Decimal128(10, 4), true),
+        Field::new("b", DataType::Decimal128(10, 4), true),
+    ]);
+    let arg0 = Decimal128Array::from(vec![12345, -1])
+        .with_precision_and_scale(10, 4)
+        .unwrap();
+    let arg1 = Decimal128Array::from(vec![10000, 0])
+        .with_precision_and_scale(10, 4)
+        .unwrap();
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("decimal_add", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------+
+| decimal_add |
++-------------+
+| 2.23        |
+| 0.00        |
++-------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_legacy_decimal() {
+    let mut runtime = Runtime::builder().legacy_decimal(true).build().unwrap();
+
+    runtime
+        .add_function(
+            "decimal_add",
+            DataType::LargeBinary,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def decimal_add(a, b):
+    return a + b
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::LargeBinary, true),
+        Field::new("b", DataType::LargeBinary, true),
+    ]);
+    let arg0 = LargeBinaryArray::from(vec![b"0.0001".as_ref()]);
+    let arg1 = LargeBinaryArray::from(vec![b"0.0002".as_ref()]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("decimal_add", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++--------------+
+| decimal_add  |
++--------------+
+| 302e30303033 |
++--------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_interval() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "double_interval",
+            DataType::Interval(IntervalUnit::MonthDayNano),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def double_interval(i):
+    return i * 2
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new(
+        "i",
+        DataType::Interval(IntervalUnit::MonthDayNano),
+        true,
+    )]);
+    let arg0 = IntervalMonthDayNanoArray::from(vec![
+        Some(IntervalMonthDayNanoType::make_value(0, 1, 1_500_000_000)),
+        None,
+    ]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("double_interval", &input).unwrap();
+    let expected = IntervalMonthDayNanoArray::from(vec![
+        Some(IntervalMonthDayNanoType::make_value(0, 2, 3_000_000_000)),
+        None,
+    ]);
+    assert_eq!(output.column(0).as_ref(), &expected as &dyn Array);
+}
+
+#[test]
+fn test_timestamp_timezone() {
+    let mut runtime = Runtime::builder()
+        .timezone("Asia/Shanghai")
+        .build()
+        .unwrap();
+    runtime
+        .add_function(
+            "hour_of",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def hour_of(t):
+    return t.hour
+"#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "to_naive",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def to_naive(t):
+    return t
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new(
+        "t",
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        true,
+    )]);
+    let arg0 = TimestampMicrosecondArray::from(vec![Some(0), None]).with_timezone("UTC");
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    // the time zone of the runtime
+    let output = runtime.call("hour_of", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(8), None])
+    );
+    let output = runtime.call("to_naive", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &TimestampMicrosecondArray::from(vec![Some(8 * 3600 * 1_000_000), None]) as &dyn Array
+    );
+
+    // the time zone of the call
+    let mut context = EvalContext::new().with_timezone("-05:00");
+    let output = runtime
+        .call_with_context("hour_of", &input, &mut context)
+        .unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(19), None])
+    );
+
+    // aware datetimes can not be converted to naive timestamps without a session time zone
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "to_naive",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def to_naive(t):
+    return t
+"#,
+        )
+        .unwrap();
+    let err = runtime.call("to_naive", &input).unwrap_err();
+    assert!(err.to_string().contains("without time zone"), "{err}");
+
+    assert!(Runtime::builder().timezone("Mars/Olympus").build().is_err());
+}
+
+#[test]
+fn test_fixed_size_binary() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "reverse",
+            DataType::FixedSizeBinary(4),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def reverse(b):
+    return b[::-1]
+"#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "truncate",
+            DataType::FixedSizeBinary(4),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def truncate(b):
+    return b[:2]
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("b", DataType::FixedSizeBinary(4), true)]);
+    let arg0 = FixedSizeBinaryArray::from(vec![Some(&[1, 2, 3, 4][..]), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("reverse", &input).unwrap();
+    let expected = FixedSizeBinaryArray::from(vec![Some(&[4, 3, 2, 1][..]), None]);
+    assert_eq!(output.column(0).as_fixed_size_binary(), &expected);
+
+    let error = runtime.call("truncate", &input).unwrap_err();
+    assert_eq!(error.to_string(), "expect 4 bytes, got 2");
+}
+
+#[test]
+fn test_union() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "describe",
+            DataType::Utf8,
+            CallMode::CalledOnNullInput,
+            r#"
+def describe(v):
+    tag, value = v
+    return f"{tag}={value!r}"
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("1"), Some("a"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    for mode in [UnionMode::Sparse, UnionMode::Dense] {
+        let fields = UnionFields::new(
+            [0, 1],
+            [
+                Field::new("int", DataType::Int32, true),
+                Field::new("str", DataType::Utf8, true),
+            ],
+        );
+        runtime
+            .add_function(
+                "parse",
+                DataType::Union(fields, mode),
+                CallMode::ReturnNullOnNullInput,
+                r#"
+def parse(s):
+    return ("int", int(s)) if s.isdigit() else ("str", s)
+"#,
+            )
+            .unwrap();
+
+        let parsed = runtime.call("parse", &input).unwrap();
+        let output = runtime.call("describe", &parsed).unwrap();
+        // null is converted to a null value of the first variant
+        let expected = StringArray::from(vec![Some("int=1"), Some("str='a'"), Some("int=None")]);
+        assert_eq!(output.column(0).as_string::<i32>(), &expected);
+    }
+}
+
+#[test]
+fn test_json_array_access() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "json_array_access",
+            json_field("json_array_access"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def json_array_access(array, i):
+    return array[i]
+            "#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        json_field("array"),
+        Field::new("i", DataType::Int32, true),
+    ]);
+    let arg0 = StringArray::from(vec![r#"[1, null, ""]"#]);
+    let arg1 = Int32Array::from(vec![0]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("json_array_access", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------------+
+| json_array_access |
++-------------------+
+| 1                 |
++-------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_legacy_json() {
+    let mut runtime = Runtime::builder().legacy_json(true).build().unwrap();
+
+    runtime
+        .add_function(
+            "json_array_access",
+            DataType::LargeUtf8,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def json_array_access(array, i):
+    return array[i]
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("array", DataType::LargeUtf8, true),
+        Field::new("i", DataType::Int32, true),
+    ]);
+    let arg0 = LargeStringArray::from(vec![r#"[1, null, ""]"#]);
+    let arg1 = Int32Array::from(vec![0]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = runtime.call("json_array_access", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------------+
+| json_array_access |
++-------------------+
+| 1                 |
++-------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_return_array() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "to_array",
+            DataType::new_list(DataType::Int32, true),
+            CallMode::CalledOnNullInput,
+            r#"
+def to_array(x):
+    if x is None:
+        return None
+    else:
+        return [x]
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("to_array", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++----------+
+| to_array |
++----------+
+| [1]      |
+|          |
+| [3]      |
++----------+
+    "#
+        .trim()
+    );
+}
+
+#[test]
+fn test_key_value() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "key_value",
+            DataType::Struct(
+                vec![
+                    Field::new("key", DataType::Utf8, true),
+                    Field::new("value", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+class KeyValue:
+    def __init__(self, key, value):
+        self.key = key
+        self.value = value
+
+def key_value(s: str):
+    key, value = s.split('=')
+    return KeyValue(key, value)
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec!["a=b"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("key_value", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++--------------------+
+| key_value          |
++--------------------+
+| {key: a, value: b} |
++--------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_struct_to_json() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "to_json",
+            json_field("to_json"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def to_json(object):
+    return object.__dict__
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new(
+        "struct",
+        DataType::Struct(
+            vec![
+                Field::new("key", DataType::Utf8, true),
+                Field::new("value", DataType::Utf8, true),
+            ]
+            .into(),
+        ),
+        true,
+    )]);
+    let arg0 = StructArray::from(vec![
+        (
+            Arc::new(Field::new("key", DataType::Utf8, true)),
+            Arc::new(StringArray::from(vec![Some("a"), None])) as ArrayRef,
+        ),
+        (
+            Arc::new(Field::new("value", DataType::Utf8, true)),
+            Arc::new(StringArray::from(vec![Some("b"), None])),
+        ),
+    ]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("to_json", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++------------------------------+
+| to_json                      |
++------------------------------+
+| {"key": "a", "value": "b"}   |
+| {"key": null, "value": null} |
++------------------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_table_function_backpressure() {
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "naturals",
+            DataType::Int64,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+import sys
+def naturals(start):
+    try:
+        while True:
+            yield start
+            start += 1
+    finally:
+        sys.naturals_closed = True
+"#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "naturals_closed",
+            DataType::Boolean,
+            CallMode::CalledOnNullInput,
+            r#"
+import sys
+def naturals_closed(x):
+    return getattr(sys, "naturals_closed", False)
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    // the generator never ends, but only the pulled chunks are evaluated
+    let mut outputs = runtime.call_table_function("naturals", &input, 3).unwrap();
+    let o1 = outputs.next().unwrap().unwrap();
+    let o2 = outputs.next().unwrap().unwrap();
+    assert_eq!(
+        o2.column(1).as_primitive::<Int64Type>(),
+        &Int64Array::from(vec![4, 5, 6])
+    );
+    assert_eq!(o1.num_rows(), 3);
+
+    let closed = runtime.call("naturals_closed", &input).unwrap();
+    assert!(!closed.column(0).as_boolean().value(0));
+    drop(outputs);
+    let closed = runtime.call("naturals_closed", &input).unwrap();
+    assert!(closed.column(0).as_boolean().value(0));
+}
+
+#[test]
+fn test_aggregate() {
+    let mut runtime = Runtime::new().unwrap();
+    let state_type = DataType::Struct(
+        vec![
+            Field::new("sum", DataType::Float64, false),
+            Field::new("count", DataType::Int32, false),
+        ]
+        .into(),
+    );
+    runtime
+        .add_aggregate(
+            "avg",
+            state_type,
+            DataType::Float64,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def create_state():
+    return {"sum": 0.0, "count": 0}
+
+def accumulate(state, value):
+    state.sum += value
+    state.count += 1
+
+def retract(state, value):
+    state.sum -= value
+    state.count -= 1
+
+def merge(state1, state2):
+    return {"sum": state1.sum + state2.sum, "count": state1.count + state2.count}
+
+def finish(state):
+    return state.sum / state.count if state.count else None
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Float64, true)]);
+    let arg0 = Float64Array::from(vec![Some(1.0), None, Some(2.0), Some(6.0)]);
+    let input = RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(arg0)]).unwrap();
+
+    let empty = runtime.create_state("avg").unwrap();
+    assert_eq!(empty.len(), 1);
+    let state = runtime.accumulate("avg", &empty, &input).unwrap();
+    let output = runtime.finish("avg", &state).unwrap();
+    assert_eq!(output.as_primitive::<Float64Type>().value(0), 3.0);
+
+    // slide the window by one row
+    let arg0 = Float64Array::from(vec![Some(1.0), Some(7.0)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let ops = BooleanArray::from(vec![true, false]);
+    let state = runtime
+        .accumulate_or_retract("avg", &state, &ops, &input)
+        .unwrap();
+    let output = runtime.finish("avg", &state).unwrap();
+    assert_eq!(output.as_primitive::<Float64Type>().value(0), 5.0);
+
+    let states =
+        arrow_select::concat::concat(&[state.as_ref(), empty.as_ref(), state.as_ref()]).unwrap();
+    let output = runtime.finish("avg", &states).unwrap();
+    assert_eq!(
+        output.as_primitive::<Float64Type>(),
+        &Float64Array::from(vec![Some(5.0), None, Some(5.0)])
+    );
+    let merged = runtime.merge("avg", &states).unwrap();
+    let output = runtime.finish("avg", &merged).unwrap();
+    assert_eq!(output.as_primitive::<Float64Type>().value(0), 5.0);
+}
+
+#[test]
+fn test_aggregate_without_retract() {
+    let mut runtime = Runtime::new().unwrap();
+    let code = r#"
+def create_state():
+    return 0
+
+def accumulate(state, value):
+    return state + value
+"#;
+    runtime
+        .add_aggregate(
+            "sum",
+            DataType::Int64,
+            DataType::Float64,
+            CallMode::ReturnNullOnNullInput,
+            code,
+        )
+        .unwrap_err();
+    runtime
+        .add_aggregate(
+            "sum",
+            DataType::Int64,
+            DataType::Int64,
+            CallMode::ReturnNullOnNullInput,
+            code,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(2)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let state = runtime.create_state("sum").unwrap();
+    let state = runtime.accumulate("sum", &state, &input).unwrap();
+    let output = runtime.finish("sum", &state).unwrap();
+    assert_eq!(output.as_primitive::<Int64Type>().value(0), 3);
+
+    let ops = BooleanArray::from(vec![true, false, false]);
+    let err = runtime
+        .accumulate_or_retract("sum", &state, &ops, &input)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "function does not support retraction");
+}
+
+#[test]
+fn test_range() {
+    let mut runtime = Runtime::new().unwrap();
+
+    runtime
+        .add_function(
+            "range1",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def range1(n: int):
+    for i in range(n):
+        yield i
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let mut outputs = runtime.call_table_function("range1", &input, 2).unwrap();
+
+    assert_eq!(outputs.schema().field(0).name(), "row");
+    assert_eq!(outputs.schema().field(1).name(), "range1");
     assert_eq!(outputs.schema().field(1).data_type(), &DataType::Int32);
 
     let o1 = outputs.next().unwrap().unwrap();
@@ -378,29 +1506,120 @@ def range1(n: int):
     );
 }
 
-/// Test there is no GIL contention across threads.
+/// Test there is no GIL contention across threads.
+#[test]
+fn test_no_gil() {
+    use std::time::Duration;
+
+    fn timeit(f: impl FnOnce()) -> Duration {
+        let start = std::time::Instant::now();
+        f();
+        start.elapsed()
+    }
+
+    let t0 = timeit(test_fib);
+    let t1 = timeit(|| {
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(test_fib);
+            }
+        })
+    });
+    assert!(
+        t1 < t0 + Duration::from_millis(10),
+        "multi-threaded execution is slower than single-threaded. there is GIL contention."
+    )
+}
+
+#[test]
+fn test_pool() {
+    let pool = Runtime::builder().build_pool(4).unwrap();
+    assert_eq!(pool.size(), 4);
+    pool.add_function(
+        "gcd",
+        DataType::Int32,
+        CallMode::ReturnNullOnNullInput,
+        r#"
+def gcd(a: int, b: int) -> int:
+    while b:
+        a, b = b, a % b
+    return a
+"#,
+    )
+    .unwrap();
+
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    for len in [0, 10, 3000] {
+        let arg0 = Int32Array::from_iter((0..len).map(|i| (i % 7 != 0).then_some(i * 6)));
+        let arg1 = Int32Array::from_iter((0..len).map(|i| Some(i * 4)));
+        let input = RecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![Arc::new(arg0), Arc::new(arg1)],
+        )
+        .unwrap();
+        let output = pool.call("gcd", &input).unwrap();
+        let expected = Int32Array::from_iter((0..len).map(|i| (i % 7 != 0).then_some(i * 2)));
+        assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
+    }
+
+    pool.del_function("gcd").unwrap();
+    let input = RecordBatch::new_empty(Arc::new(schema));
+    let error = pool.call("gcd", &input).unwrap_err();
+    assert_eq!(error.to_string(), "function not found");
+}
+
 #[test]
-fn test_no_gil() {
-    use std::time::Duration;
+fn test_pool_concurrency() {
+    let pool = Runtime::builder().build_pool(4).unwrap();
+    // a function with states can only be evaluated by one runtime
+    pool.add_function_with_concurrency(
+        "counter",
+        DataType::Int32,
+        CallMode::CalledOnNullInput,
+        r#"
+n = 0
+def counter(x: int) -> int:
+    global n
+    n += 1
+    return n
+"#,
+        1,
+    )
+    .unwrap();
 
-    fn timeit(f: impl FnOnce()) -> Duration {
-        let start = std::time::Instant::now();
-        f();
-        start.elapsed()
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(Int32Array::from_iter_values(0..3000))],
+    )
+    .unwrap();
+    for i in 0..2 {
+        let output = pool.call("counter", &input).unwrap();
+        let expected = Int32Array::from_iter_values(i * 3000 + 1..=(i + 1) * 3000);
+        assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
     }
 
-    let t0 = timeit(test_fib);
-    let t1 = timeit(|| {
-        std::thread::scope(|s| {
-            for _ in 0..4 {
-                s.spawn(test_fib);
-            }
-        })
-    });
-    assert!(
-        t1 < t0 + Duration::from_millis(10),
-        "multi-threaded execution is slower than single-threaded. there is GIL contention."
+    // re-adding a function with another concurrency replaces it
+    pool.add_function_with_concurrency(
+        "counter",
+        DataType::Int32,
+        CallMode::CalledOnNullInput,
+        r#"
+def counter(x: int) -> int:
+    return x
+"#,
+        2,
     )
+    .unwrap();
+    let output = pool.call("counter", &input).unwrap();
+    assert_eq!(output.column(0).as_ref(), input.column(0).as_ref());
+
+    pool.del_function("counter").unwrap();
+    let error = pool.call("counter", &input).unwrap_err();
+    assert_eq!(error.to_string(), "function not found");
 }
 
 #[test]
@@ -444,6 +1663,523 @@ fn test_forbid() {
     assert_err("print()", "NameError: name 'print' is not defined");
 }
 
+#[test]
+fn test_packages() {
+    let code = r#"
+def gcd(a: int, b: int) -> int:
+    while b:
+        a, b = b, a % b
+    return a
+"#;
+    let mut runtime = Runtime::new().unwrap();
+    let error = runtime
+        .add_function_with_packages(
+            "gcd",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            code,
+            &["arrow-udf-nonexistent-package>=1.0"],
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unsatisfied requirements: arrow-udf-nonexistent-package>=1.0 (not installed)"
+    );
+
+    let mut runtime = Runtime::builder().sandboxed(true).build().unwrap();
+    let error = runtime
+        .add_function_with_packages(
+            "gcd",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            code,
+            &["regex"],
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "packages are not supported in sandboxed mode"
+    );
+}
+
+#[test]
+fn test_batch_size() {
+    let mut runtime = Runtime::builder().batch_size(2).build().unwrap();
+    runtime
+        .add_function(
+            "row_of",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            r#"
+def row_of(x, *, ctx):
+    if x == 0:
+        raise ValueError("zero")
+    return ctx.row
+"#,
+        )
+        .unwrap();
+
+    let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, true)]));
+    let batch = |values: Vec<Option<i32>>| {
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))]).unwrap()
+    };
+
+    // large batches are sliced
+    let input = batch(vec![Some(1), None, Some(3), Some(4), Some(5)]);
+    let output = runtime.call("row_of", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![0, 1, 2, 3, 4])
+    );
+    let input = batch(vec![Some(1), Some(2), Some(3), Some(0)]);
+    let error = runtime.call("row_of", &input).unwrap_err();
+    let Some(UdfError::Exception { row, .. }) = error.downcast_ref::<UdfError>() else {
+        panic!("expect exception, got {error:?}");
+    };
+    assert_eq!(*row, 3);
+
+    // small batches are coalesced
+    let inputs = [
+        batch(vec![Some(1)]),
+        batch(vec![Some(2)]),
+        batch(vec![Some(3), Some(4), Some(5)]),
+    ];
+    let outputs = runtime
+        .call_batches("row_of", &inputs, &mut EvalContext::new())
+        .unwrap();
+    let outputs: Vec<_> = outputs
+        .iter()
+        .map(|o| o.column(0).as_primitive::<Int32Type>().clone())
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            Int32Array::from(vec![0]),
+            Int32Array::from(vec![1]),
+            Int32Array::from(vec![0, 1, 2]),
+        ]
+    );
+}
+
+#[test]
+fn test_preload() {
+    let runtime = Runtime::builder()
+        .preload("decimal")
+        .preload("json")
+        .build()
+        .unwrap();
+    runtime.preload(&["math", "re"]).unwrap();
+
+    let error = runtime
+        .preload(&["arrow_udf_nonexistent_module"])
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to preload module `arrow_udf_nonexistent_module`"
+    );
+    assert!(Runtime::builder()
+        .preload("arrow_udf_nonexistent_module")
+        .build()
+        .is_err());
+
+    let runtime = Runtime::builder().sandboxed(true).build().unwrap();
+    runtime.preload(&["json"]).unwrap();
+    runtime.preload(&["os"]).unwrap_err();
+}
+
+#[test]
+fn test_type_hints() {
+    let code = r#"
+from typing import Optional
+
+def weight(x: int, unit: Optional[str], tags: list[str]) -> float:
+    return x * (2.5 if unit == 'kg' else 1.0) + len(tags)
+"#;
+    let mut runtime = Runtime::new().unwrap();
+    let signature = runtime
+        .add_function_with_type_hints("weight", None, CallMode::CalledOnNullInput, code)
+        .unwrap();
+    assert_eq!(
+        signature,
+        Signature {
+            args: vec![
+                Field::new("x", DataType::Int64, true),
+                Field::new("unit", DataType::Utf8, true),
+                Field::new_list("tags", Field::new("item", DataType::Utf8, true), true),
+            ],
+            return_field: Field::new("weight", DataType::Float64, true),
+        }
+    );
+
+    let schema = Schema::new(signature.args.clone());
+    let arg0 = Int64Array::from(vec![Some(2), Some(4)]);
+    let arg1 = StringArray::from(vec![Some("kg"), None]);
+    let arg2 = {
+        let mut builder = builder::ListBuilder::new(builder::StringBuilder::new());
+        builder.append_value([Some("a")]);
+        builder.append_value([Some("b"), Some("c")]);
+        builder.finish()
+    };
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(arg0), Arc::new(arg1), Arc::new(arg2)],
+    )
+    .unwrap();
+    let output = runtime.call("weight", &input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++--------+
+| weight |
++--------+
+| 6.0    |
+| 6.0    |
++--------+
+"#
+        .trim()
+    );
+
+    // the declared signature is validated against the type hints
+    let declared = Signature {
+        args: vec![
+            Field::new("x", DataType::Int32, true),
+            Field::new("unit", DataType::LargeUtf8, true),
+            Field::new_list("tags", Field::new("item", DataType::Utf8, true), true),
+        ],
+        return_field: Field::new("weight", DataType::Float32, true),
+    };
+    runtime
+        .add_function_with_type_hints("weight", Some(&declared), CallMode::CalledOnNullInput, code)
+        .unwrap();
+    let declared = Signature {
+        args: vec![
+            Field::new("x", DataType::Utf8, true),
+            Field::new("unit", DataType::Utf8, true),
+            Field::new_list("tags", Field::new("item", DataType::Utf8, true), true),
+        ],
+        return_field: Field::new("weight", DataType::Float64, true),
+    };
+    let error = runtime
+        .add_function_with_type_hints("weight", Some(&declared), CallMode::CalledOnNullInput, code)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "argument `x` is declared as Utf8 but has type hint `int`"
+    );
+
+    // missing type hints can not be inferred
+    let error = runtime
+        .add_function_with_type_hints(
+            "f",
+            None,
+            CallMode::CalledOnNullInput,
+            "def f(x) -> int:\n    return x\n",
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "can not infer the type of argument `x` without a type hint"
+    );
+}
+
+/// `arrow.uuid` values as `uuid.UUID` objects.
+#[derive(Debug)]
+struct UuidCodec;
+
+impl ExtensionTypeCodec for UuidCodec {
+    fn name(&self) -> &str {
+        "arrow.uuid"
+    }
+
+    fn to_pyobject(
+        &self,
+        py: Python<'_>,
+        _field: &Field,
+        array: &dyn Array,
+        i: usize,
+    ) -> anyhow::Result<PyObject> {
+        let bytes = PyBytes::new(py, array.as_fixed_size_binary().value(i));
+        let uuid = py.import("uuid")?.getattr("UUID")?;
+        Ok(uuid
+            .call((), Some([("bytes", bytes)].into_py_dict(py)))?
+            .into())
+    }
+
+    fn build_array(
+        &self,
+        py: Python<'_>,
+        _field: &Field,
+        values: &[PyObject],
+    ) -> anyhow::Result<ArrayRef> {
+        let mut bytes = Vec::with_capacity(values.len());
+        for value in values {
+            match value.is_none(py) {
+                true => bytes.push(None),
+                false => bytes.push(Some(value.getattr(py, "bytes")?.extract::<Vec<u8>>(py)?)),
+            }
+        }
+        Ok(Arc::new(
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(bytes.into_iter(), 16)?,
+        ))
+    }
+}
+
+#[test]
+fn test_extension_type() {
+    let uuid_field = |name: &str| {
+        Field::new(name, DataType::FixedSizeBinary(16), true)
+            .with_metadata([("ARROW:extension:name".into(), "arrow.uuid".into())].into())
+    };
+    let mut runtime = Runtime::builder()
+        .extension_type(UuidCodec)
+        .build()
+        .unwrap();
+    runtime
+        .add_function(
+            "next_uuid",
+            uuid_field("next_uuid"),
+            CallMode::ReturnNullOnNullInput,
+            r#"
+import uuid
+
+def next_uuid(x):
+    assert isinstance(x, uuid.UUID)
+    return uuid.UUID(int=x.int + 1)
+"#,
+        )
+        .unwrap();
+
+    let schema = Schema::new(vec![uuid_field("x")]);
+    let arg0 = FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+        [Some([0u8; 16]), None].into_iter(),
+        16,
+    )
+    .unwrap();
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("next_uuid", &input).unwrap();
+    let mut expected = [0u8; 16];
+    expected[15] = 1;
+    let array = output.column(0).as_fixed_size_binary();
+    assert_eq!(array.value(0), expected);
+    assert!(array.is_null(1));
+    assert_eq!(output.schema().field(0), &uuid_field("next_uuid"));
+}
+
+#[cfg(feature = "geo")]
+#[test]
+fn test_geometry() {
+    let geometry_field = |name: &str| {
+        Field::new(name, DataType::Binary, true)
+            .with_metadata([("ARROW:extension:name".into(), "geoarrow.wkb".into())].into())
+    };
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function(
+            "st_x",
+            DataType::Float64,
+            CallMode::ReturnNullOnNullInput,
+            r#"
+def st_x(g):
+    if isinstance(g, bytes):
+        # shapely is not installed
+        import struct
+        return struct.unpack('<d', g[5:13])[0]
+    return g.x
+"#,
+        )
+        .unwrap();
+    runtime
+        .add_function(
+            "identity",
+            geometry_field("identity"),
+            CallMode::ReturnNullOnNullInput,
+            "def identity(g):\n    return g\n",
+        )
+        .unwrap();
+
+    // POINT (1 2)
+    let point: &[u8] = &[
+        0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+    ];
+    let schema = Schema::new(vec![geometry_field("g")]);
+    let arg0 = BinaryArray::from(vec![Some(point), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = runtime.call("st_x", &input).unwrap();
+    let x = output
+        .column(0)
+        .as_primitive::<arrow_array::types::Float64Type>();
+    assert_eq!(x.value(0), 1.0);
+    assert!(x.is_null(1));
+
+    let output = runtime.call("identity", &input).unwrap();
+    assert_eq!(output.column(0).as_binary::<i32>().value(0), point);
+    assert!(output.column(0).is_null(1));
+}
+
+#[test]
+fn test_archive() {
+    // a zipapp with a package of two modules and a resource
+    let dir = std::env::temp_dir().join(format!("arrow-udf-archive-{}", std::process::id()));
+    let package = dir.join("src").join("udf_text");
+    std::fs::create_dir_all(&package).unwrap();
+    std::fs::write(package.join("__init__.py"), "").unwrap();
+    std::fs::write(package.join("stopwords.txt"), "a\nthe\n").unwrap();
+    std::fs::write(
+        package.join("words.py"),
+        r#"
+from importlib.resources import files
+
+STOPWORDS = set(files(__package__).joinpath('stopwords.txt').read_text().split())
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        package.join("count.py"),
+        r#"
+from .words import STOPWORDS
+
+def word_count(s):
+    return sum(1 for w in s.split() if w not in STOPWORDS)
+"#,
+    )
+    .unwrap();
+    let archive = dir.join("text.pyz");
+    let status = std::process::Command::new("python3")
+        .args(["-m", "zipapp"])
+        .arg(dir.join("src"))
+        .arg("-o")
+        .arg(&archive)
+        .args(["-m", "udf_text.count:word_count"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .add_function_from_archive(
+            "word_count",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            &archive,
+            "udf_text.count",
+        )
+        .unwrap();
+    runtime
+        .add_function_from_archive(
+            "count",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            &archive,
+            "udf_text.count:word_count",
+        )
+        .unwrap();
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("the quick fox"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    for name in ["word_count", "count"] {
+        let output = runtime.call(name, &input).unwrap();
+        assert_eq!(
+            output
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap(),
+            &Int32Array::from(vec![Some(2), None])
+        );
+    }
+
+    let error = runtime
+        .add_function_from_archive(
+            "word_count",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            dir.join("missing.pyz"),
+            "udf_text.count",
+        )
+        .unwrap_err();
+    assert!(error.to_string().starts_with("archive not found"));
+
+    let mut runtime = Runtime::builder().sandboxed(true).build().unwrap();
+    let error = runtime
+        .add_function_from_archive(
+            "word_count",
+            DataType::Int32,
+            CallMode::ReturnNullOnNullInput,
+            &archive,
+            "udf_text.count",
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "archives are not supported in sandboxed mode"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_venv() {
+    // a fake environment with a module in its site-packages
+    let prefix = std::env::temp_dir().join(format!("arrow-udf-venv-{}", std::process::id()));
+    let site_packages = prefix.join("Lib").join("site-packages");
+    std::fs::create_dir_all(&site_packages).unwrap();
+    std::fs::write(site_packages.join("udf_venv_module.py"), "ANSWER = 42\n").unwrap();
+
+    let mut runtime = Runtime::builder().venv(&prefix).build().unwrap();
+    runtime
+        .add_function(
+            "answer",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            r#"
+from udf_venv_module import ANSWER
+
+def answer(x):
+    return ANSWER
+"#,
+        )
+        .unwrap();
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let output = runtime.call("answer", &input).unwrap();
+    assert_eq!(
+        output
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap(),
+        &Int32Array::from(vec![42, 42])
+    );
+
+    // other runtimes are not affected
+    let mut runtime = Runtime::new().unwrap();
+    let error = runtime
+        .add_function(
+            "answer",
+            DataType::Int32,
+            CallMode::CalledOnNullInput,
+            "from udf_venv_module import ANSWER",
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "ModuleNotFoundError: No module named 'udf_venv_module'"
+    );
+
+    let error = Runtime::builder()
+        .venv(prefix.join("nonexistent"))
+        .build()
+        .unwrap_err();
+    assert_eq!(error.to_string(), "failed to activate the environment");
+    std::fs::remove_dir_all(&prefix).unwrap();
+}
+
 #[track_caller]
 fn assert_err(code: &str, err: &str) {
     let mut runtime = Runtime::builder().sandboxed(true).build().unwrap();
@@ -457,3 +2193,72 @@ fn assert_err(code: &str, err: &str) {
         .unwrap_err();
     assert_eq!(error.to_string(), err);
 }
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_identity_roundtrip(seed: u64, len in 0..64usize, null_density in 0.0f32..1.0) {
+        let mut runtime = Runtime::new().unwrap();
+        let fields = [
+            DataType::Boolean,
+            DataType::Int8,
+            DataType::Int16,
+            DataType::Int32,
+            DataType::Int64,
+            DataType::UInt8,
+            DataType::UInt16,
+            DataType::UInt32,
+            DataType::UInt64,
+            DataType::Float16,
+            DataType::Float32,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::Binary,
+            DataType::LargeUtf8,
+            DataType::LargeBinary,
+            DataType::FixedSizeBinary(16),
+            DataType::Decimal128(38, 10),
+            DataType::new_list(DataType::Int32, true),
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int32, true),
+                    Field::new("b", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+        ]
+        .into_iter()
+        .map(|data_type| Field::new("x", data_type, true))
+        .chain([json_field("x"), decimal_field("x")]);
+        for field in fields {
+            runtime
+                .add_function(
+                    "identity",
+                    field.clone(),
+                    CallMode::CalledOnNullInput,
+                    r#"
+def identity(x):
+    return x
+"#,
+                )
+                .unwrap();
+            let array = random_field_array(&field, len, null_density, seed);
+            let input = RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![array]).unwrap();
+            assert_roundtrip_batch(|input: &RecordBatch| runtime.call("identity", input), &input);
+            runtime.del_function("identity").unwrap();
+        }
+    }
+}
+
+/// Returns a nullable field of JSON type.
+fn json_field(name: &str) -> Field {
+    Field::new(name, DataType::Utf8, true)
+        .with_metadata([("ARROW:extension:name".into(), "arrowudf.json".into())].into())
+}
+
+/// Returns a nullable field of decimal type.
+fn decimal_field(name: &str) -> Field {
+    Field::new(name, DataType::Utf8, true)
+        .with_metadata([("ARROW:extension:name".into(), "arrowudf.decimal".into())].into())
+}
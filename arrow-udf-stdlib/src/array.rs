@@ -0,0 +1,246 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Array functions.
+//!
+//! The kernels work on a whole [`ListArray`] at once. They compare elements in place and gather
+//! the result with a single `take` on the values, instead of collecting each row into a `Vec`.
+//! They can also be used by user functions through the `batch_fn` option:
+//!
+//! ```ignore
+//! #[function("top_tags(varchar[]) -> varchar[]", batch_fn = "top_tags")]
+//! fn top_tags(tags: &ListArray) -> ListArray {
+//!     arrow_udf_stdlib::array::distinct(tags).unwrap()
+//! }
+//! ```
+//!
+//! Floats are compared by their total order, so `NaN` equals `NaN` and is greater than any number.
+
+use std::cmp::Ordering;
+
+use arrow_array::builder::BooleanBuilder;
+use arrow_array::cast::AsArray;
+use arrow_array::types::*;
+use arrow_array::{
+    Array, ArrowNativeTypeOp, ArrowPrimitiveType, BooleanArray, ListArray, UInt32Array,
+};
+use arrow_buffer::OffsetBuffer;
+use arrow_schema::{ArrowError, DataType, TimeUnit};
+use arrow_udf::{function, Result};
+
+/// Compares the `i`-th element of the left array with the `j`-th element of the right array,
+/// ignoring nulls.
+type Comparator<'a> = Box<dyn Fn(usize, usize) -> Ordering + 'a>;
+
+/// Returns a comparator of two arrays of the same type.
+fn comparator<'a>(left: &'a dyn Array, right: &'a dyn Array) -> Result<Comparator<'a>> {
+    fn primitive<'a, T: ArrowPrimitiveType>(
+        left: &'a dyn Array,
+        right: &'a dyn Array,
+    ) -> Comparator<'a> {
+        let left = left.as_primitive::<T>().values();
+        let right = right.as_primitive::<T>().values();
+        Box::new(move |i, j| left[i].compare(right[j]))
+    }
+    fn bytes<'a, T: ByteArrayType>(left: &'a dyn Array, right: &'a dyn Array) -> Comparator<'a>
+    where
+        T::Native: Ord,
+    {
+        let left = left.as_bytes::<T>();
+        let right = right.as_bytes::<T>();
+        Box::new(move |i, j| left.value(i).cmp(right.value(j)))
+    }
+
+    if left.data_type() != right.data_type() {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "can not compare {} with {}",
+            left.data_type(),
+            right.data_type()
+        )));
+    }
+    Ok(match left.data_type() {
+        DataType::Boolean => {
+            let (left, right) = (left.as_boolean(), right.as_boolean());
+            Box::new(move |i, j| left.value(i).cmp(&right.value(j)))
+        }
+        DataType::Int8 => primitive::<Int8Type>(left, right),
+        DataType::Int16 => primitive::<Int16Type>(left, right),
+        DataType::Int32 => primitive::<Int32Type>(left, right),
+        DataType::Int64 => primitive::<Int64Type>(left, right),
+        DataType::UInt8 => primitive::<UInt8Type>(left, right),
+        DataType::UInt16 => primitive::<UInt16Type>(left, right),
+        DataType::UInt32 => primitive::<UInt32Type>(left, right),
+        DataType::UInt64 => primitive::<UInt64Type>(left, right),
+        DataType::Float32 => primitive::<Float32Type>(left, right),
+        DataType::Float64 => primitive::<Float64Type>(left, right),
+        DataType::Decimal128(_, _) => primitive::<Decimal128Type>(left, right),
+        DataType::Date32 => primitive::<Date32Type>(left, right),
+        DataType::Date64 => primitive::<Date64Type>(left, right),
+        DataType::Time32(TimeUnit::Second) => primitive::<Time32SecondType>(left, right),
+        DataType::Time32(TimeUnit::Millisecond) => primitive::<Time32MillisecondType>(left, right),
+        DataType::Time64(TimeUnit::Microsecond) => primitive::<Time64MicrosecondType>(left, right),
+        DataType::Time64(TimeUnit::Nanosecond) => primitive::<Time64NanosecondType>(left, right),
+        DataType::Timestamp(TimeUnit::Second, _) => primitive::<TimestampSecondType>(left, right),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            primitive::<TimestampMillisecondType>(left, right)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            primitive::<TimestampMicrosecondType>(left, right)
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            primitive::<TimestampNanosecondType>(left, right)
+        }
+        DataType::Utf8 => bytes::<Utf8Type>(left, right),
+        DataType::LargeUtf8 => bytes::<LargeUtf8Type>(left, right),
+        DataType::Binary => bytes::<BinaryType>(left, right),
+        DataType::LargeBinary => bytes::<LargeBinaryType>(left, right),
+        t => {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "can not compare elements of type {t}"
+            )))
+        }
+    })
+}
+
+/// Sorts the elements of each list in ascending order, with nulls last.
+pub fn sort(list: &ListArray) -> Result<ListArray> {
+    let values = list.values().as_ref();
+    let cmp = comparator(values, values)?;
+    let mut indices = Vec::with_capacity(values.len());
+    let mut offsets = Vec::with_capacity(list.len() + 1);
+    offsets.push(0);
+    for w in list.value_offsets().windows(2) {
+        let start = indices.len();
+        indices.extend(w[0] as u32..w[1] as u32);
+        indices[start..].sort_by(|&i, &j| cmp_nulls_last(values, &cmp, i as usize, j as usize));
+        offsets.push(indices.len() as i32);
+    }
+    take_list(list, offsets, indices)
+}
+
+/// Removes duplicate elements of each list, keeping the first occurrence of each element.
+///
+/// Nulls are considered equal, so at most one null is kept.
+pub fn distinct(list: &ListArray) -> Result<ListArray> {
+    let values = list.values().as_ref();
+    let cmp = comparator(values, values)?;
+    let mut indices = Vec::with_capacity(values.len());
+    let mut offsets = Vec::with_capacity(list.len() + 1);
+    offsets.push(0);
+    let mut sorted = vec![];
+    for w in list.value_offsets().windows(2) {
+        // the sort is stable, so the first occurrence comes first in each run of equal elements
+        sorted.clear();
+        sorted.extend(w[0] as u32..w[1] as u32);
+        sorted.sort_by(|&i, &j| cmp_nulls_last(values, &cmp, i as usize, j as usize));
+        let start = indices.len();
+        for (k, &i) in sorted.iter().enumerate() {
+            if k == 0 || cmp_nulls_last(values, &cmp, sorted[k - 1] as usize, i as usize).is_ne() {
+                indices.push(i);
+            }
+        }
+        // restore the original order
+        indices[start..].sort_unstable();
+        offsets.push(indices.len() as i32);
+    }
+    take_list(list, offsets, indices)
+}
+
+/// Returns whether each list contains the value in the same row.
+///
+/// Like `value = ANY(list)` in SQL, the result is null if the list or the value is null,
+/// or if the value is not found but the list contains a null.
+pub fn contains(list: &ListArray, value: &dyn Array) -> Result<BooleanArray> {
+    if list.len() != value.len() {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "expect {} values, got {}",
+            list.len(),
+            value.len()
+        )));
+    }
+    let values = list.values().as_ref();
+    let cmp = comparator(values, value)?;
+    let mut builder = BooleanBuilder::with_capacity(list.len());
+    for (row, w) in list.value_offsets().windows(2).enumerate() {
+        if list.is_null(row) || value.is_null(row) {
+            builder.append_null();
+            continue;
+        }
+        let mut has_null = false;
+        let mut found = false;
+        for i in w[0] as usize..w[1] as usize {
+            if values.is_null(i) {
+                has_null = true;
+            } else if cmp(i, row).is_eq() {
+                found = true;
+                break;
+            }
+        }
+        builder.append_option((found || !has_null).then_some(found));
+    }
+    Ok(builder.finish())
+}
+
+/// Compares two elements of the same array, with nulls last.
+fn cmp_nulls_last(values: &dyn Array, cmp: &Comparator<'_>, i: usize, j: usize) -> Ordering {
+    match (values.is_null(i), values.is_null(j)) {
+        (false, false) => cmp(i, j),
+        (false, true) => Ordering::Less,
+        (true, false) => Ordering::Greater,
+        (true, true) => Ordering::Equal,
+    }
+}
+
+/// Builds a list array whose values are taken from `list` by `indices`, split by `offsets`.
+fn take_list(list: &ListArray, offsets: Vec<i32>, indices: Vec<u32>) -> Result<ListArray> {
+    let DataType::List(field) = list.data_type() else {
+        unreachable!("list array of type {}", list.data_type());
+    };
+    let values = arrow_select::take::take(list.values(), &UInt32Array::from(indices), None)?;
+    ListArray::try_new(
+        field.clone(),
+        OffsetBuffer::new(offsets.into()),
+        values,
+        list.nulls().cloned(),
+    )
+}
+
+// The element types are limited by the signatures, so the kernels never fail below.
+
+/// Sorts the elements of an array in ascending order, with nulls last.
+#[function(
+    "array_sort({boolean, int2, int4, int8, float4, float8, date, timestamp, varchar, bytea}[]) -> {boolean, int2, int4, int8, float4, float8, date, timestamp, varchar, bytea}[]",
+    batch_fn = "array_sort"
+)]
+fn array_sort(list: &ListArray) -> ListArray {
+    sort(list).expect("failed to sort array")
+}
+
+/// Removes duplicate elements of an array, keeping the first occurrence of each element.
+#[function(
+    "array_distinct({boolean, int2, int4, int8, float4, float8, date, timestamp, varchar, bytea}[]) -> {boolean, int2, int4, int8, float4, float8, date, timestamp, varchar, bytea}[]",
+    batch_fn = "array_distinct"
+)]
+fn array_distinct(list: &ListArray) -> ListArray {
+    distinct(list).expect("failed to remove duplicates of array")
+}
+
+/// Returns whether an array contains a value.
+#[function(
+    "array_contains({boolean, int2, int4, int8, float4, float8, date, timestamp, varchar, bytea}[], {boolean, int2, int4, int8, float4, float8, date, timestamp, varchar, bytea}) -> boolean",
+    batch_fn = "array_contains"
+)]
+fn array_contains(list: &ListArray, value: &dyn Array) -> BooleanArray {
+    contains(list, value).expect("failed to search array")
+}
@@ -0,0 +1,50 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Date and time functions.
+
+use arrow_udf::function;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// Truncates the timestamp to the precision of `unit`.
+///
+/// Supported units are `microseconds`, `milliseconds`, `second`, `minute`, `hour`, `day`,
+/// `week`, `month`, `quarter` and `year`. Weeks start on Monday.
+#[function("date_trunc(varchar, timestamp) -> timestamp")]
+fn date_trunc(unit: &str, ts: NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let date = ts.date();
+    let time = ts.time();
+    let truncated = match unit.to_ascii_lowercase().as_str() {
+        "microseconds" => ts.with_nanosecond(time.nanosecond() / 1_000 * 1_000),
+        "milliseconds" => ts.with_nanosecond(time.nanosecond() / 1_000_000 * 1_000_000),
+        "second" => ts.with_nanosecond(0),
+        "minute" => date.and_hms_opt(time.hour(), time.minute(), 0),
+        "hour" => date.and_hms_opt(time.hour(), 0, 0),
+        "day" => Some(date.and_time(NaiveTime::MIN)),
+        "week" => {
+            let days = date.weekday().num_days_from_monday() as i64;
+            Some((date - Duration::days(days)).and_time(NaiveTime::MIN))
+        }
+        "month" => first_day_of_month(date.year(), date.month()),
+        "quarter" => first_day_of_month(date.year(), (date.month() - 1) / 3 * 3 + 1),
+        "year" => first_day_of_month(date.year(), 1),
+        _ => return Err(format!("unsupported unit: {unit}")),
+    };
+    truncated.ok_or_else(|| "timestamp out of range".to_string())
+}
+
+/// Returns the midnight of the first day of the month.
+fn first_day_of_month(year: i32, month: u32) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(year, month, 1).map(|date| date.and_time(NaiveTime::MIN))
+}
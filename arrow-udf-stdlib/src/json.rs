@@ -0,0 +1,37 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON functions.
+
+use arrow_udf::function;
+use serde_json::Value;
+
+/// Returns the value of `key` in a JSON object, or null if the key does not exist.
+#[function("json_get(json, varchar) -> json")]
+fn json_get(mut v: Value, key: &str) -> Option<Value> {
+    v.get_mut(key).map(Value::take)
+}
+
+/// Returns the element at `idx` of a JSON array, or null if the index is out of bounds.
+///
+/// The index starts from 0. Negative indexes count from the end of the array.
+#[function("json_get(json, int) -> json")]
+fn json_get_index(mut v: Value, idx: i32) -> Option<Value> {
+    let array = v.as_array_mut()?;
+    let idx = match idx {
+        0.. => idx as usize,
+        _ => array.len().checked_sub(idx.unsigned_abs() as usize)?,
+    };
+    array.get_mut(idx).map(Value::take)
+}
@@ -0,0 +1,65 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Math functions.
+//!
+//! Integer overflow is handled by the [`Overflow`](arrow_udf::arith::Overflow) behavior of the
+//! `EvalContext`, which is an error by default.
+
+use arrow_udf::arith::{self, Integer};
+use arrow_udf::{function, Result};
+
+/// Returns the absolute value of an integer.
+#[function("abs({int2, int4, int8}) -> {int2, int4, int8}")]
+fn abs<T: Integer>(x: T) -> Result<T> {
+    arith::abs(x)
+}
+
+/// Returns the greatest common divisor of two integers.
+#[function("gcd(int4, int4) -> int4")]
+#[function("gcd(int8, int8) -> int8")]
+fn gcd<T: Integer + Into<i128>>(a: T, b: T) -> Result<T> {
+    // `gcd(MIN, 0)` can not be represented by `T`
+    arith::narrow(gcd_i128(a.into(), b.into()))
+}
+
+/// Returns the least common multiple of two integers.
+#[function("lcm(int4, int4) -> int4")]
+#[function("lcm(int8, int8) -> int8")]
+fn lcm<T: Integer + Into<i128>>(a: T, b: T) -> Result<T> {
+    let (a, b) = (a.into(), b.into());
+    if a == 0 || b == 0 {
+        return arith::narrow(0);
+    }
+    arith::narrow(a.abs() / gcd_i128(a, b) * b.abs())
+}
+
+fn gcd_i128(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Returns the factorial of an integer.
+#[function("factorial(int8) -> int8")]
+fn factorial(n: i64) -> Result<i64> {
+    if n < 0 {
+        return Err(arrow_udf::Error::InvalidArgumentError(format!(
+            "factorial of a negative number {n}"
+        )));
+    }
+    (2..=n).try_fold(1i64, arith::mul)
+}
@@ -0,0 +1,113 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! String functions.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+
+use arrow_udf::{function, types::StructType};
+use regex::Regex;
+
+thread_local! {
+    /// The last compiled regex. Patterns are usually constant across rows.
+    static REGEX: RefCell<Option<Regex>> = const { RefCell::new(None) };
+}
+
+/// Calls `f` with the compiled `pattern`.
+fn with_regex<T>(pattern: &str, f: impl FnOnce(&Regex) -> T) -> Result<T, regex::Error> {
+    REGEX.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let regex = match cache.take() {
+            Some(regex) if regex.as_str() == pattern => regex,
+            _ => Regex::new(pattern)?,
+        };
+        Ok(f(cache.insert(regex)))
+    })
+}
+
+/// Extracts the first capture group of the first match of `pattern` in `s`.
+#[function("regexp_extract(varchar, varchar) -> varchar")]
+fn regexp_extract(s: &str, pattern: &str, writer: &mut impl Write) -> Result<Option<()>, String> {
+    regexp_extract_group(s, pattern, 1, writer)
+}
+
+/// Extracts the `idx`-th capture group of the first match of `pattern` in `s`.
+///
+/// Group 0 is the whole match. Returns null if there is no match or the group does not
+/// participate in the match.
+#[function("regexp_extract(varchar, varchar, int) -> varchar")]
+fn regexp_extract_group(
+    s: &str,
+    pattern: &str,
+    idx: i32,
+    writer: &mut impl Write,
+) -> Result<Option<()>, String> {
+    with_regex(pattern, |regex| {
+        let groups = regex.captures_len() - 1;
+        if idx < 0 || idx as usize > groups {
+            return Err(format!(
+                "regex group count is {groups}, but the specified group index is {idx}"
+            ));
+        }
+        let Some(m) = regex.captures(s).and_then(|c| c.get(idx as usize)) else {
+            return Ok(None);
+        };
+        writer.write_str(m.as_str()).unwrap();
+        Ok(Some(()))
+    })
+    .map_err(|e| e.to_string())?
+}
+
+/// Returns the Levenshtein distance between two strings, counted in characters.
+#[function("levenshtein(varchar, varchar) -> int")]
+fn levenshtein(a: &str, b: &str) -> i32 {
+    let b: Vec<char> = b.chars().collect();
+    // distances from the prefix of `a` to each prefix of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != *cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()] as i32
+}
+
+#[derive(StructType)]
+struct ParsedUrl {
+    scheme: String,
+    host: Option<String>,
+    port: Option<i32>,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+/// Parses a URL into its components.
+#[function("url_parse(varchar) -> struct ParsedUrl")]
+fn url_parse(s: &str) -> Result<ParsedUrl, url::ParseError> {
+    let url = url::Url::parse(s)?;
+    Ok(ParsedUrl {
+        scheme: url.scheme().to_string(),
+        host: url.host_str().map(|s| s.to_string()),
+        port: url.port().map(|p| p as i32),
+        path: url.path().to_string(),
+        query: url.query().map(|s| s.to_string()),
+        fragment: url.fragment().map(|s| s.to_string()),
+    })
+}
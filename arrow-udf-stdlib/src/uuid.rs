@@ -0,0 +1,25 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! UUID functions.
+
+use std::fmt::Write;
+
+use arrow_udf::function;
+
+/// Generates a random UUID (version 4).
+#[function("gen_random_uuid() -> varchar", volatile)]
+fn gen_random_uuid(writer: &mut impl Write) {
+    write!(writer, "{}", ::uuid::Uuid::new_v4()).unwrap();
+}
@@ -0,0 +1,99 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Vector similarity functions for embeddings.
+//!
+//! The kernels can also be called from user functions on `vector(N)` arguments:
+//!
+//! ```ignore
+//! #[function("similarity(vector(768), vector(768)) -> float4")]
+//! fn similarity(a: &[f32], b: &[f32]) -> f32 {
+//!     1.0 - arrow_udf_stdlib::vector::cosine_distance(a, b)
+//! }
+//! ```
+
+use arrow_udf::function;
+
+/// The number of independent accumulators, so that the loops can be vectorized.
+const LANES: usize = 16;
+
+/// Returns the sum of `f(a[i], b[i])`. The vectors must have the same length.
+#[inline(always)]
+fn sum_by(a: &[f32], b: &[f32], f: impl Fn(f32, f32) -> f32) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    let (a_chunks, b_chunks) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let tail = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| f(*x, *y))
+        .sum::<f32>();
+    let mut acc = [0.0f32; LANES];
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for ((acc, x), y) in acc.iter_mut().zip(x).zip(y) {
+            *acc += f(*x, *y);
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Returns the inner product of two vectors of the same length.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    sum_by(a, b, |x, y| x * y)
+}
+
+/// Returns the Euclidean distance between two vectors of the same length.
+pub fn l2_distance(a: &[f32], b: &[f32]) -> f32 {
+    sum_by(a, b, |x, y| (x - y) * (x - y)).sqrt()
+}
+
+/// Returns the cosine distance, i.e. 1 - cosine similarity, between two vectors of the same
+/// length. Returns NaN if either vector is zero.
+pub fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let norm = (dot(a, a) * dot(b, b)).sqrt();
+    1.0 - dot(a, b) / norm
+}
+
+/// Checks that two vectors have the same dimension.
+fn check_dimensions(a: &[f32], b: &[f32]) -> Result<(), String> {
+    if a.len() != b.len() {
+        return Err(format!(
+            "different vector dimensions {} and {}",
+            a.len(),
+            b.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the inner product of two vectors.
+#[function("inner_product(real[], real[]) -> double precision")]
+fn inner_product(a: &[f32], b: &[f32]) -> Result<f64, String> {
+    check_dimensions(a, b)?;
+    Ok(dot(a, b) as f64)
+}
+
+/// Returns the Euclidean distance between two vectors.
+#[function("l2_distance(real[], real[]) -> double precision")]
+fn l2_distance_udf(a: &[f32], b: &[f32]) -> Result<f64, String> {
+    check_dimensions(a, b)?;
+    Ok(l2_distance(a, b) as f64)
+}
+
+/// Returns the cosine distance between two vectors.
+#[function("cosine_distance(real[], real[]) -> double precision")]
+fn cosine_distance_udf(a: &[f32], b: &[f32]) -> Result<f64, String> {
+    check_dimensions(a, b)?;
+    Ok(cosine_distance(a, b) as f64)
+}
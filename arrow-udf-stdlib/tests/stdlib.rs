@@ -0,0 +1,493 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type, Int32Type, Int64Type};
+use arrow_array::{
+    Array, Int32Array, Int64Array, ListArray, RecordBatch, RecordBatchOptions, StringArray,
+    TimestampMicrosecondArray,
+};
+use arrow_cast::pretty::pretty_format_batches;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arrow_udf::arith::Overflow;
+use arrow_udf::EvalContext;
+use arrow_udf_stdlib::REGISTRY;
+
+#[test]
+fn test_regexp_extract() {
+    let input = batch(vec![
+        Arc::new(StringArray::from(vec![Some("100-200"), Some("foo"), None])),
+        Arc::new(StringArray::from(vec!["(\\d+)-(\\d+)"; 3])),
+    ]);
+    let output = call("regexp_extract", &input, DataType::Utf8);
+    check(
+        &[output],
+        r#"
++----------------+-------+
+| regexp_extract | error |
++----------------+-------+
+| 100            |       |
+|                |       |
+|                |       |
++----------------+-------+"#,
+    );
+
+    let input = batch(vec![
+        Arc::new(StringArray::from(vec!["100-200"; 4])),
+        Arc::new(StringArray::from(vec!["(\\d+)-(\\d+)"; 4])),
+        Arc::new(Int32Array::from(vec![0, 2, 3, -1])),
+    ]);
+    let output = call("regexp_extract", &input, DataType::Utf8);
+    check(
+        &[output],
+        r#"
++----------------+-------------------------------------------------------------+
+| regexp_extract | error                                                       |
++----------------+-------------------------------------------------------------+
+| 100-200        |                                                             |
+| 200            |                                                             |
+|                | regex group count is 2, but the specified group index is 3  |
+|                | regex group count is 2, but the specified group index is -1 |
++----------------+-------------------------------------------------------------+"#,
+    );
+
+    // invalid regex
+    let input = batch(vec![
+        Arc::new(StringArray::from(vec!["a"])),
+        Arc::new(StringArray::from(vec!["("])),
+    ]);
+    let output = call("regexp_extract", &input, DataType::Utf8);
+    let error = output.column_by_name("error").unwrap();
+    assert!(!error.is_null(0));
+}
+
+#[test]
+fn test_levenshtein() {
+    let input = batch(vec![
+        Arc::new(StringArray::from(vec![
+            Some("kitten"),
+            Some(""),
+            Some("flaw"),
+            Some("café"),
+            None,
+        ])),
+        Arc::new(StringArray::from(vec![
+            Some("sitting"),
+            Some("abc"),
+            Some("lawn"),
+            Some("cafe"),
+            Some("a"),
+        ])),
+    ]);
+    let output = call("levenshtein", &input, DataType::Int32);
+    let expected = Int32Array::from(vec![Some(3), Some(3), Some(2), Some(1), None]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+}
+
+#[test]
+fn test_url_parse() {
+    let input = batch(vec![Arc::new(StringArray::from(vec![
+        Some("https://example.com:8080/a/b?x=1#top"),
+        Some("mailto:user@example.com"),
+        Some("not a url"),
+        None,
+    ]))]);
+    let sig = REGISTRY.iter().find(|sig| sig.name == "url_parse").unwrap();
+    let output = sig.function.as_scalar().unwrap()(&input).unwrap();
+    check(
+        &[output],
+        r#"
++---------------------------------------------------------------------------------------+-----------------------------+
+| url_parse                                                                             | error                       |
++---------------------------------------------------------------------------------------+-----------------------------+
+| {scheme: https, host: example.com, port: 8080, path: /a/b, query: x=1, fragment: top} |                             |
+| {scheme: mailto, host: , port: , path: user@example.com, query: , fragment: }         |                             |
+|                                                                                       | relative URL without a base |
+|                                                                                       |                             |
++---------------------------------------------------------------------------------------+-----------------------------+"#,
+    );
+}
+
+#[test]
+fn test_date_trunc() {
+    // 2024-05-15 (Wednesday) 13:45:30.123456
+    let ts = 1_715_780_730_123_456;
+    let units = [
+        "milliseconds",
+        "second",
+        "minute",
+        "hour",
+        "day",
+        "week",
+        "month",
+        "quarter",
+        "year",
+        "decade",
+    ];
+    let input = batch(vec![
+        Arc::new(StringArray::from(units.to_vec())),
+        Arc::new(TimestampMicrosecondArray::from(vec![ts; units.len()])),
+    ]);
+    let output = call(
+        "date_trunc",
+        &input,
+        DataType::Timestamp(TimeUnit::Microsecond, None),
+    );
+    check(
+        &[output],
+        r#"
++-------------------------+--------------------------+
+| date_trunc              | error                    |
++-------------------------+--------------------------+
+| 2024-05-15T13:45:30.123 |                          |
+| 2024-05-15T13:45:30     |                          |
+| 2024-05-15T13:45:00     |                          |
+| 2024-05-15T13:00:00     |                          |
+| 2024-05-15T00:00:00     |                          |
+| 2024-05-13T00:00:00     |                          |
+| 2024-05-01T00:00:00     |                          |
+| 2024-04-01T00:00:00     |                          |
+| 2024-01-01T00:00:00     |                          |
+|                         | unsupported unit: decade |
++-------------------------+--------------------------+"#,
+    );
+}
+
+#[test]
+fn test_json_get() {
+    let json = |name: &str| {
+        Field::new(name, DataType::Utf8, true)
+            .with_metadata([("ARROW:extension:name".into(), "arrowudf.json".into())].into())
+    };
+    let values = StringArray::from(vec![r#"{"a": {"b": 1}}"#, r#"[1, 2, 3]"#, r#"{"c": null}"#]);
+
+    let schema = Schema::new(vec![json("x"), Field::new("key", DataType::Utf8, true)]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(values.clone()),
+            Arc::new(StringArray::from(vec!["a", "a", "c"])),
+        ],
+    )
+    .unwrap();
+    let sig = REGISTRY
+        .get_by_fields("json_get", &input.schema().fields()[..], &json("json_get"))
+        .unwrap();
+    let output = sig.function.as_scalar().unwrap()(&input).unwrap();
+    check(
+        &[output],
+        r#"
++----------+
+| json_get |
++----------+
+| {"b":1}  |
+|          |
+| null     |
++----------+"#,
+    );
+
+    let schema = Schema::new(vec![json("x"), Field::new("idx", DataType::Int32, true)]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(values), Arc::new(Int32Array::from(vec![0, -1, 0]))],
+    )
+    .unwrap();
+    let sig = REGISTRY
+        .get_by_fields("json_get", &input.schema().fields()[..], &json("json_get"))
+        .unwrap();
+    let output = sig.function.as_scalar().unwrap()(&input).unwrap();
+    check(
+        &[output],
+        r#"
++----------+
+| json_get |
++----------+
+|          |
+| 3        |
+|          |
++----------+"#,
+    );
+}
+
+#[test]
+fn test_gen_random_uuid() {
+    let input = RecordBatch::try_new_with_options(
+        Arc::new(Schema::empty()),
+        vec![],
+        &RecordBatchOptions::default().with_row_count(Some(2)),
+    )
+    .unwrap();
+    let output = call("gen_random_uuid", &input, DataType::Utf8);
+    let uuids = output
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(uuids.len(), 2);
+    assert_eq!(uuids.value(0).len(), 36);
+    assert_eq!(&uuids.value(0)[14..15], "4");
+    assert_ne!(uuids.value(0), uuids.value(1));
+
+    // the function must not be cached or evaluated once per run
+    let sig = REGISTRY.get("gen_random_uuid", &[], &DataType::Utf8).unwrap();
+    assert!(sig.volatile);
+}
+
+#[test]
+fn test_vector_distance() {
+    let vectors = |values: Vec<Option<Vec<f32>>>| -> Arc<dyn Array> {
+        Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(
+            values
+                .into_iter()
+                .map(|v| v.map(|v| v.into_iter().map(Some))),
+        ))
+    };
+    // longer than the number of lanes to cover the tail
+    let a: Vec<f32> = (0..20).map(|i| i as f32).collect();
+    let b: Vec<f32> = (0..20).map(|i| (i % 2) as f32).collect();
+    let input = batch(vec![
+        vectors(vec![
+            Some(vec![1.0, 2.0]),
+            Some(a.clone()),
+            None,
+            Some(vec![1.0]),
+        ]),
+        vectors(vec![
+            Some(vec![3.0, 4.0]),
+            Some(b.clone()),
+            Some(vec![1.0]),
+            Some(vec![1.0, 2.0]),
+        ]),
+    ]);
+    let output = call("inner_product", &input, DataType::Float64);
+    check(
+        &[output],
+        r#"
++---------------+-------------------------------------+
+| inner_product | error                               |
++---------------+-------------------------------------+
+| 11.0          |                                     |
+| 100.0         |                                     |
+|               |                                     |
+|               | different vector dimensions 1 and 2 |
++---------------+-------------------------------------+"#,
+    );
+
+    let output = call("l2_distance", &input, DataType::Float64);
+    let distances = output.column(0).as_primitive::<Float64Type>();
+    assert_eq!(distances.value(0), 8f32.sqrt() as f64);
+    let expected = a
+        .iter()
+        .zip(&b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>();
+    assert!((distances.value(1) - expected.sqrt() as f64).abs() < 1e-4);
+
+    let output = call("cosine_distance", &input, DataType::Float64);
+    let distances = output.column(0).as_primitive::<Float64Type>();
+    assert!((distances.value(0) - (1.0 - 11.0 / (5f64.sqrt() * 5.0))).abs() < 1e-6);
+    assert!(distances.is_null(2));
+}
+
+#[test]
+fn test_math() {
+    let input = batch(vec![
+        Arc::new(Int64Array::from(vec![12, -18, 0, i64::MIN])),
+        Arc::new(Int64Array::from(vec![-8, 24, 5, 0])),
+    ]);
+    let output = call("gcd", &input, DataType::Int64);
+    let values = output.column(0).as_primitive::<Int64Type>();
+    assert_eq!(&values.values()[..3], &[4, 6, 5]);
+    assert!(values.is_null(3));
+    assert_eq!(
+        output.column(1).as_string::<i32>().value(3),
+        "Compute error: Overflow happened on: 9223372036854775808 as i64"
+    );
+    let output = call("lcm", &input, DataType::Int64);
+    let values = output.column(0).as_primitive::<Int64Type>();
+    assert_eq!(&values.values()[..4], &[24, 72, 0, 0]);
+
+    let input = batch(vec![Arc::new(Int64Array::from(vec![0, 5, 20, 21]))]);
+    let output = call("factorial", &input, DataType::Int64);
+    let values = output.column(0).as_primitive::<Int64Type>();
+    assert_eq!(&values.values()[..3], &[1, 120, 2432902008176640000]);
+    assert!(values.is_null(3));
+}
+
+#[test]
+fn test_math_overflow() {
+    let input = batch(vec![Arc::new(Int32Array::from(vec![
+        Some(-3),
+        Some(i32::MIN),
+        None,
+    ]))]);
+    // overflow is an error by default
+    let output = call("abs", &input, DataType::Int32);
+    check(
+        &[output],
+        r#"
++-----+-------------------------------------------------------+
+| abs | error                                                 |
++-----+-------------------------------------------------------+
+| 3   |                                                       |
+|     | Compute error: Overflow happened on: abs(-2147483648) |
+|     |                                                       |
++-----+-------------------------------------------------------+"#,
+    );
+
+    // engines can choose to wrap or saturate
+    let ctx = EvalContext::default().with_overflow(Overflow::Saturate);
+    let output = ctx.scope(|| call("abs", &input, DataType::Int32));
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(3), Some(i32::MAX), None])
+    );
+    let ctx = EvalContext::default().with_overflow(Overflow::Wrap);
+    let output = ctx.scope(|| call("abs", &input, DataType::Int32));
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(3), Some(i32::MIN), None])
+    );
+}
+
+#[test]
+fn test_array() {
+    let list = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+        Some(vec![Some(3), None, Some(1), Some(3), None, Some(2)]),
+        Some(vec![]),
+        None,
+        Some(vec![Some(5), Some(4), Some(5)]),
+    ]);
+    let list_type = list.data_type().clone();
+    let input = batch(vec![Arc::new(list.clone())]);
+    let sorted = call("array_sort", &input, list_type.clone());
+    let distinct = call("array_distinct", &input, list_type.clone());
+    check(
+        &[sorted],
+        r#"
++------------------+
+| array_sort       |
++------------------+
+| [1, 2, 3, 3, , ] |
+| []               |
+|                  |
+| [4, 5, 5]        |
++------------------+"#,
+    );
+    check(
+        &[distinct],
+        r#"
++----------------+
+| array_distinct |
++----------------+
+| [3, , 1, 2]    |
+| []             |
+|                |
+| [5, 4]         |
++----------------+"#,
+    );
+
+    let input = batch(vec![
+        Arc::new(list.clone()),
+        Arc::new(Int32Array::from(vec![Some(2), Some(1), Some(1), None])),
+    ]);
+    let output = call("array_contains", &input, DataType::Boolean);
+    check(
+        &[output],
+        r#"
++----------------+
+| array_contains |
++----------------+
+| true           |
+| false          |
+|                |
+|                |
++----------------+"#,
+    );
+    let input = batch(vec![
+        Arc::new(list),
+        Arc::new(Int32Array::from(vec![Some(4), Some(1), Some(1), Some(4)])),
+    ]);
+    let output = call("array_contains", &input, DataType::Boolean);
+    // not found in a list with nulls
+    check(
+        &[output],
+        r#"
++----------------+
+| array_contains |
++----------------+
+|                |
+| false          |
+|                |
+| true           |
++----------------+"#,
+    );
+
+    // the kernels work on sliced lists
+    let list = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+        Some(vec![Some(1), Some(1)]),
+        Some(vec![Some(2), Some(1), Some(2)]),
+    ]);
+    let distinct = arrow_udf_stdlib::array::distinct(&list.slice(1, 1)).unwrap();
+    assert_eq!(
+        distinct,
+        ListArray::from_iter_primitive::<Int32Type, _, _>(vec![Some(vec![Some(2), Some(1)])])
+    );
+
+    let list = ListArray::from_iter_primitive::<Float64Type, _, _>(vec![Some(vec![
+        Some(f64::NAN),
+        Some(1.0),
+        Some(f64::NAN),
+    ])]);
+    let sorted = arrow_udf_stdlib::array::sort(&list).unwrap();
+    let sorted = sorted.value(0);
+    let values = sorted.as_primitive::<Float64Type>().values();
+    assert_eq!(values[0], 1.0);
+    assert!(values[1].is_nan() && values[2].is_nan());
+}
+
+/// Returns a batch of the given columns, named `c0`, `c1`, ...
+fn batch(columns: Vec<Arc<dyn Array>>) -> RecordBatch {
+    let fields: Vec<_> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| Field::new(format!("c{i}"), c.data_type().clone(), true))
+        .collect();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+}
+
+/// Looks up the function by the types of the input and calls it.
+fn call(name: &str, input: &RecordBatch, return_type: DataType) -> RecordBatch {
+    let arg_types: Vec<_> = input
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.data_type().clone())
+        .collect();
+    let sig = REGISTRY
+        .get(name, &arg_types, &return_type)
+        .unwrap_or_else(|| panic!("function not found: {name}"));
+    sig.function.as_scalar().unwrap()(input).unwrap()
+}
+
+/// Compare the actual output with the expected output.
+#[track_caller]
+fn check(actual: &[RecordBatch], expect: &str) {
+    assert_eq!(
+        expect.trim(),
+        pretty_format_batches(actual).unwrap().to_string(),
+    );
+}
@@ -15,25 +15,46 @@
 #![doc = include_str!("../README.md")]
 
 use anyhow::{anyhow, bail, ensure, Context};
-use arrow_array::RecordBatch;
-use std::collections::{HashMap, HashSet};
+use arrow_array::{Array, ArrayRef, BooleanArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use wasmtime::*;
 use wasmtime_wasi::{sync::WasiCtxBuilder, WasiCtx};
 
 #[cfg(feature = "build")]
 pub mod build;
+mod env;
+
+pub use self::env::Env;
+
+lazy_static::lazy_static! {
+    /// The global engine. Epoch interruption is enabled to cancel running calls.
+    static ref ENGINE: Engine = {
+        let mut config = wasmtime::Config::new();
+        config.epoch_interruption(true);
+        Engine::new(&config).expect("failed to create engine")
+    };
+}
 
 /// The WASM UDF runtime.
 ///
 /// This runtime contains an instance pool and can be shared by multiple threads.
+///
+/// Instances are created on demand and shared by all functions in the module. Each instance
+/// only resolves the exports of the functions that have been called on it, so modules
+/// exporting many functions stay cheap to instantiate.
 pub struct Runtime {
     module: Module,
     /// Configurations.
     config: Config,
-    /// Function names.
-    functions: HashSet<String>,
+    /// Function names -> export names.
+    functions: HashMap<String, String>,
+    /// Aggregate function names -> export names.
+    aggregates: HashMap<String, String>,
     /// User-defined types.
     types: HashMap<String, String>,
     /// Instance pool.
@@ -41,11 +62,39 @@ pub struct Runtime {
 }
 
 /// Configurations.
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub struct Config {
     /// Memory size limit in bytes.
     pub memory_size_limit: Option<usize>,
+    /// Directory to cache compiled modules.
+    ///
+    /// Compiling a module can take seconds. If set, the compiled module is saved in this
+    /// directory, keyed by the hash of the binary, and later runtimes of the same binary
+    /// load it instead of compiling again.
+    ///
+    /// Compiled modules are native code loaded without validation, so the directory must only
+    /// be writable by trusted users. The cache is not used if `trusted_keys` is set, since
+    /// compiled modules are not covered by the signature.
+    pub cache_dir: Option<PathBuf>,
+    /// Ed25519 public keys trusted to sign modules.
+    ///
+    /// If not empty, only modules signed by one of the keys can be loaded, and the signature
+    /// must be passed to [`Runtime::with_signature`].
+    pub trusted_keys: Vec<[u8; 32]>,
+    /// Environment variables and secrets of the module.
+    ///
+    /// Functions read them by `std::env::var`. Values of secrets are redacted in errors.
+    pub env: Env,
+}
+
+/// The `record_batch_iterator_next` function of a module.
+enum RecordBatchIteratorNext {
+    // extern "C" fn(iter: *mut RecordBatchIter, out: *mut CSlice) -> i32
+    Fallible(TypedFunc<(u32, u32), i32>),
+    // extern "C" fn(iter: *mut RecordBatchIter, out: *mut CSlice)
+    // before abi version 3.0, where errors end the iteration silently
+    Infallible(TypedFunc<(u32, u32), ()>),
 }
 
 struct Instance {
@@ -53,14 +102,19 @@ struct Instance {
     alloc: TypedFunc<(u32, u32), u32>,
     // extern "C" fn(ptr: *mut u8, len: usize, align: usize)
     dealloc: TypedFunc<(u32, u32, u32), ()>,
-    // extern "C" fn(iter: *mut RecordBatchIter, out: *mut CSlice)
-    record_batch_iterator_next: TypedFunc<(u32, u32), ()>,
+    record_batch_iterator_next: RecordBatchIteratorNext,
     // extern "C" fn(iter: *mut RecordBatchIter)
     record_batch_iterator_drop: TypedFunc<u32, ()>,
     // extern "C" fn(ptr: *const u8, len: usize, out: *mut CSlice) -> i32
+    // resolved on first call
     functions: HashMap<String, TypedFunc<(u32, u32, u32), i32>>,
+    // extern "C" fn(state_ptr: *const u8, state_len: usize, ptr: *const u8, len: usize, out: *mut CSlice) -> i32
+    // resolved on first call
+    aggregates: HashMap<String, TypedFunc<(u32, u32, u32, u32, u32), i32>>,
+    instance: wasmtime::Instance,
     memory: Memory,
-    store: Store<(WasiCtx, StoreLimits)>,
+    // the cancellation token of the current call
+    store: Store<(WasiCtx, StoreLimits, Option<CancellationToken>)>,
 }
 
 impl Debug for Runtime {
@@ -68,6 +122,7 @@ impl Debug for Runtime {
         f.debug_struct("Runtime")
             .field("config", &self.config)
             .field("functions", &self.functions)
+            .field("aggregates", &self.aggregates)
             .field("types", &self.types)
             .field("instances", &self.instances.lock().unwrap().len())
             .finish()
@@ -83,15 +138,32 @@ impl Runtime {
     /// Create a new UDF runtime from a WASM binary with configuration.
     pub fn with_config(binary: &[u8], config: Config) -> Result<Self> {
         // use a global engine by default
-        lazy_static::lazy_static! {
-            static ref ENGINE: Engine = Engine::default();
-        }
-        Self::with_config_engine(binary, config, &ENGINE)
+        Self::with_config_engine(binary, None, config, &ENGINE)
+    }
+
+    /// Create a new UDF runtime from a WASM binary and its ed25519 signature.
+    ///
+    /// Returns an error if the signature is not made by any of [`Config::trusted_keys`].
+    pub fn with_signature(binary: &[u8], signature: &[u8; 64], config: Config) -> Result<Self> {
+        Self::with_config_engine(binary, Some(signature), config, &ENGINE)
     }
 
     /// Create a new UDF runtime from a WASM binary with a customized engine.
-    fn with_config_engine(binary: &[u8], config: Config, engine: &Engine) -> Result<Self> {
-        let module = Module::from_binary(engine, binary).context("failed to load wasm binary")?;
+    fn with_config_engine(
+        binary: &[u8],
+        signature: Option<&[u8; 64]>,
+        config: Config,
+        engine: &Engine,
+    ) -> Result<Self> {
+        if !config.trusted_keys.is_empty() {
+            let signature = signature.context("signature required for untrusted module")?;
+            verify_signature(binary, signature, &config.trusted_keys)?;
+        }
+        // a cached module could be replaced without invalidating the signature of the binary
+        let module = match &config.cache_dir {
+            Some(dir) if config.trusted_keys.is_empty() => load_cached_module(engine, binary, dir)?,
+            _ => Module::from_binary(engine, binary).context("failed to load wasm binary")?,
+        };
 
         // check abi version
         let version = module
@@ -99,14 +171,18 @@ impl Runtime {
             .find_map(|e| e.name().strip_prefix("ARROWUDF_VERSION_"))
             .context("version not found")?;
         let (major, minor) = version.split_once('_').context("invalid version")?;
-        ensure!(major <= "2", "unsupported abi version: {major}.{minor}");
+        ensure!(major <= "3", "unsupported abi version: {major}.{minor}");
 
-        let mut functions = HashSet::new();
+        let mut functions = HashMap::new();
+        let mut aggregates = HashMap::new();
         let mut types = HashMap::new();
         for export in module.exports() {
             if let Some(encoded) = export.name().strip_prefix("arrowudf_") {
                 let name = base64_decode(encoded).context("invalid symbol")?;
-                functions.insert(name);
+                functions.insert(name, export.name().to_string());
+            } else if let Some(encoded) = export.name().strip_prefix("arrowudaf_") {
+                let name = base64_decode(encoded).context("invalid symbol")?;
+                aggregates.insert(name, export.name().to_string());
             } else if let Some(encoded) = export.name().strip_prefix("arrowudt_") {
                 let meta = base64_decode(encoded).context("invalid symbol")?;
                 let (name, fields) = meta.split_once('=').context("invalid type string")?;
@@ -118,14 +194,20 @@ impl Runtime {
             module,
             config,
             functions,
+            aggregates,
             types,
             instances: Mutex::new(vec![]),
         })
     }
 
-    /// Return available functions.
+    /// Return the signatures of available functions.
     pub fn functions(&self) -> impl Iterator<Item = &str> {
-        self.functions.iter().map(|s| s.as_str())
+        self.functions.keys().map(|s| s.as_str())
+    }
+
+    /// Return the signatures of available aggregate functions.
+    pub fn aggregates(&self) -> impl Iterator<Item = &str> {
+        self.aggregates.keys().map(|s| s.as_str())
     }
 
     /// Return available types.
@@ -133,28 +215,68 @@ impl Runtime {
         self.types.iter().map(|(k, v)| (k.as_str(), v.as_str()))
     }
 
+    /// Pre-warm the given functions.
+    ///
+    /// This makes sure there is an instance in the pool with the functions resolved,
+    /// so that the first call of them does not pay for instantiation.
+    pub fn prewarm(&self, names: &[&str]) -> Result<()> {
+        let exports = names
+            .iter()
+            .map(|name| self.export_name(name))
+            .collect::<Result<Vec<_>>>()?;
+        let mut instance = self.get_instance()?;
+        for export in exports {
+            instance.function(export)?;
+        }
+        self.instances.lock().unwrap().push(instance);
+        Ok(())
+    }
+
     /// Call a function.
     pub fn call(&self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
-        if !self.functions.contains(name) {
-            bail!("function not found: {name}");
-        }
+        self.call_inner(name, input, None)
+    }
 
-        // get an instance from the pool, or create a new one if the pool is empty
-        let mut instance = if let Some(instance) = self.instances.lock().unwrap().pop() {
-            instance
-        } else {
-            Instance::new(self)?
-        };
+    /// Call a function, which is interrupted once `token` is cancelled.
+    pub fn call_with_cancellation(
+        &self,
+        name: &str,
+        input: &RecordBatch,
+        token: &CancellationToken,
+    ) -> Result<RecordBatch> {
+        self.call_inner(name, input, Some(token))
+    }
+
+    fn call_inner(
+        &self,
+        name: &str,
+        input: &RecordBatch,
+        token: Option<&CancellationToken>,
+    ) -> Result<RecordBatch> {
+        let export = self.export_name(name)?;
+        if let Some(token) = token {
+            token.check()?;
+        }
+        let mut instance = self.get_instance()?;
 
         // call the function
-        let output = instance.call_scalar_function(name, input);
+        instance.store.data_mut().2 = token.cloned();
+        let output = instance.call_scalar_function(export, input);
+        instance.store.data_mut().2 = None;
 
         // put the instance back to the pool
         if output.is_ok() {
             self.instances.lock().unwrap().push(instance);
         }
 
-        output
+        let output = output.map_err(|e| self.config.env.redact(e))?;
+        ensure!(
+            output.num_rows() == input.num_rows(),
+            "expect {} rows in the output of {name}, got {}",
+            input.num_rows(),
+            output.num_rows()
+        );
+        Ok(output)
     }
 
     /// Call a table function.
@@ -162,22 +284,37 @@ impl Runtime {
         &'a self,
         name: &'a str,
         input: &'a RecordBatch,
+    ) -> Result<impl Iterator<Item = Result<RecordBatch>> + 'a> {
+        self.call_table_function_inner(name, input, None)
+    }
+
+    /// Call a table function, which is interrupted once `token` is cancelled.
+    pub fn call_table_function_with_cancellation<'a>(
+        &'a self,
+        name: &'a str,
+        input: &'a RecordBatch,
+        token: &CancellationToken,
+    ) -> Result<impl Iterator<Item = Result<RecordBatch>> + 'a> {
+        self.call_table_function_inner(name, input, Some(token))
+    }
+
+    fn call_table_function_inner<'a>(
+        &'a self,
+        name: &'a str,
+        input: &'a RecordBatch,
+        token: Option<&CancellationToken>,
     ) -> Result<impl Iterator<Item = Result<RecordBatch>> + 'a> {
         use genawaiter::{sync::gen, yield_};
-        if !self.functions.contains(name) {
-            bail!("function not found: {name}");
+        let export = self.export_name(name)?;
+        if let Some(token) = token {
+            token.check()?;
         }
-
-        // get an instance from the pool, or create a new one if the pool is empty
-        let mut instance = if let Some(instance) = self.instances.lock().unwrap().pop() {
-            instance
-        } else {
-            Instance::new(self)?
-        };
+        let mut instance = self.get_instance()?;
+        instance.store.data_mut().2 = token.cloned();
 
         Ok(gen!({
             // call the function
-            let iter = match instance.call_table_function(name, input) {
+            let iter = match instance.call_table_function(export, input) {
                 Ok(iter) => iter,
                 Err(e) => {
                     yield_!(Err(e));
@@ -189,10 +326,128 @@ impl Runtime {
             }
             // put the instance back to the pool
             // FIXME: if the iterator is not consumed, the instance will be dropped
+            instance.store.data_mut().2 = None;
             self.instances.lock().unwrap().push(instance);
         })
         .into_iter())
     }
+
+    /// Create a new state for an aggregate function.
+    ///
+    /// The state is an array of length 1.
+    pub fn create_state(&self, name: &str) -> Result<ArrayRef> {
+        let input = RecordBatch::new_empty(Arc::new(Schema::empty()));
+        self.call_aggregate(name, None, &input)
+    }
+
+    /// Accumulate the input rows into the state of an aggregate function.
+    pub fn accumulate(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let ops = BooleanArray::from(vec![false; input.num_rows()]);
+        self.accumulate_or_retract(name, state, &ops, input)
+    }
+
+    /// Accumulate or retract the input rows into the state of an aggregate function.
+    ///
+    /// Rows whose `ops` is true are retracted.
+    pub fn accumulate_or_retract(
+        &self,
+        name: &str,
+        state: &dyn Array,
+        ops: &BooleanArray,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        ensure!(
+            ops.len() == input.num_rows(),
+            "the length of ops does not match the input"
+        );
+        // the first column marks the rows to retract
+        let mut fields = vec![Arc::new(Field::new("retract", DataType::Boolean, true))];
+        fields.extend(input.schema().fields().iter().cloned());
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(ops.clone())];
+        columns.extend(input.columns().iter().cloned());
+        let input = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        self.call_aggregate(name, Some(state), &input)
+    }
+
+    fn call_aggregate(
+        &self,
+        name: &str,
+        state: Option<&dyn Array>,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let export = match self.aggregates.get(name) {
+            Some(export) => export,
+            None => bail!("aggregate function not found: {name}"),
+        };
+        let state = state
+            .map(|state| {
+                let state = arrow_array::make_array(state.to_data());
+                RecordBatch::try_from_iter([("state", state)])
+            })
+            .transpose()?;
+        let mut instance = self.get_instance()?;
+        let output = instance.call_aggregate(export, state.as_ref(), input);
+        // put the instance back to the pool
+        if output.is_ok() {
+            self.instances.lock().unwrap().push(instance);
+        }
+        output
+    }
+
+    /// Get the export name of a function.
+    fn export_name(&self, name: &str) -> Result<&str> {
+        match self.functions.get(name) {
+            Some(export) => Ok(export),
+            None => bail!("function not found: {name}"),
+        }
+    }
+
+    /// Get an instance from the pool, or create a new one if the pool is empty.
+    fn get_instance(&self) -> Result<Instance> {
+        if let Some(instance) = self.instances.lock().unwrap().pop() {
+            return Ok(instance);
+        }
+        Instance::new(self)
+    }
+}
+
+/// A token to cancel running function calls.
+///
+/// Clones of a token share the same state. Cancelling a token interrupts the calls with it
+/// at the next epoch check of the WebAssembly code, even if the function never returns.
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token and all its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        // make running instances check their tokens
+        ENGINE.increment_epoch();
+    }
+
+    /// Returns whether the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns an error if the token is cancelled.
+    fn check(&self) -> Result<()> {
+        ensure!(!self.is_cancelled(), "function call cancelled");
+        Ok(())
+    }
 }
 
 impl Instance {
@@ -201,12 +456,17 @@ impl Instance {
         let module = &rt.module;
         let engine = module.engine();
         let mut linker = Linker::new(engine);
-        wasmtime_wasi::add_to_linker(&mut linker, |(wasi, _)| wasi)?;
+        wasmtime_wasi::add_to_linker(&mut linker, |(wasi, _, _)| wasi)?;
 
         // Create a WASI context and put it in a Store; all instances in the store
         // share this context. `WasiCtxBuilder` provides a number of ways to
         // configure what the target program will have access to.
-        let wasi = WasiCtxBuilder::new().inherit_stdio().build();
+        let mut builder = WasiCtxBuilder::new();
+        builder.inherit_stdio();
+        for (key, value) in rt.config.env.vars() {
+            builder.env(key, value)?;
+        }
+        let wasi = builder.build();
         let limits = {
             let mut builder = StoreLimitsBuilder::new();
             if let Some(limit) = rt.config.memory_size_limit {
@@ -214,23 +474,27 @@ impl Instance {
             }
             builder.build()
         };
-        let mut store = Store::new(engine, (wasi, limits));
-        store.limiter(|(_, limiter)| limiter);
+        let mut store = Store::new(engine, (wasi, limits, None));
+        store.limiter(|(_, limiter, _)| limiter);
+        // check the cancellation token whenever the epoch is incremented
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(|store| {
+            if let Some(token) = &store.data().2 {
+                token.check()?;
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
 
         let instance = linker.instantiate(&mut store, module)?;
-        let mut functions = HashMap::new();
-        for export in module.exports() {
-            let Some(encoded) = export.name().strip_prefix("arrowudf_") else {
-                continue;
-            };
-            let name = base64_decode(encoded).context("invalid symbol")?;
-            let func = instance.get_typed_func(&mut store, export.name())?;
-            functions.insert(name, func);
-        }
         let alloc = instance.get_typed_func(&mut store, "alloc")?;
         let dealloc = instance.get_typed_func(&mut store, "dealloc")?;
         let record_batch_iterator_next =
-            instance.get_typed_func(&mut store, "record_batch_iterator_next")?;
+            match instance.get_typed_func(&mut store, "record_batch_iterator_next") {
+                Ok(func) => RecordBatchIteratorNext::Fallible(func),
+                Err(_) => RecordBatchIteratorNext::Infallible(
+                    instance.get_typed_func(&mut store, "record_batch_iterator_next")?,
+                ),
+            };
         let record_batch_iterator_drop =
             instance.get_typed_func(&mut store, "record_batch_iterator_drop")?;
         let memory = instance
@@ -244,12 +508,27 @@ impl Instance {
             record_batch_iterator_drop,
             memory,
             store,
-            functions,
+            instance,
+            functions: HashMap::new(),
+            aggregates: HashMap::new(),
         })
     }
 
+    /// Get a function by its export name, resolving it on first use.
+    fn function(&mut self, export: &str) -> Result<TypedFunc<(u32, u32, u32), i32>> {
+        if let Some(func) = self.functions.get(export) {
+            return Ok(*func);
+        }
+        let func = self
+            .instance
+            .get_typed_func(&mut self.store, export)
+            .with_context(|| format!("failed to get function: {export}"))?;
+        self.functions.insert(export.to_string(), func);
+        Ok(func)
+    }
+
     /// Call a scalar function.
-    fn call_scalar_function(&mut self, name: &str, input: &RecordBatch) -> Result<RecordBatch> {
+    fn call_scalar_function(&mut self, export: &str, input: &RecordBatch) -> Result<RecordBatch> {
         // TODO: optimize data transfer
         // currently there are 3 copies in input path:
         //      host record batch -> host encoding -> wasm memory -> wasm record batch
@@ -257,10 +536,7 @@ impl Instance {
         //      wasm record batch -> wasm memory -> host record batch
 
         // get function
-        let func = self
-            .functions
-            .get(name)
-            .with_context(|| format!("function not found: {name}"))?;
+        let func = self.function(export)?;
 
         // encode input batch
         let input = encode_record_batch(input)?;
@@ -301,10 +577,87 @@ impl Instance {
         result
     }
 
+    /// Call an aggregate function and return the new state.
+    fn call_aggregate(
+        &mut self,
+        export: &str,
+        state: Option<&RecordBatch>,
+        input: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        // get function
+        let func = match self.aggregates.get(export) {
+            Some(func) => *func,
+            None => {
+                let func = self
+                    .instance
+                    .get_typed_func(&mut self.store, export)
+                    .with_context(|| format!("failed to get function: {export}"))?;
+                self.aggregates.insert(export.to_string(), func);
+                func
+            }
+        };
+
+        // encode state and input batch
+        // an empty state means the initial state
+        let state = match state {
+            Some(state) => encode_record_batch(state)?,
+            None => vec![],
+        };
+        let input = encode_record_batch(input)?;
+
+        // allocate memory for output struct, state and input buffer
+        let alloc_len =
+            u32::try_from(4 * 2 + state.len() + input.len()).context("input too large")?;
+        let alloc_ptr = self.alloc.call(&mut self.store, (alloc_len, 4))?;
+        ensure!(alloc_ptr != 0, "failed to allocate for input");
+        let state_ptr = alloc_ptr + 4 * 2;
+        let in_ptr = state_ptr + state.len() as u32;
+
+        // write state and input to memory
+        self.memory
+            .write(&mut self.store, state_ptr as usize, &state)?;
+        self.memory
+            .write(&mut self.store, in_ptr as usize, &input)?;
+
+        // call the function
+        let errno = func.call(
+            &mut self.store,
+            (
+                state_ptr,
+                state.len() as u32,
+                in_ptr,
+                input.len() as u32,
+                alloc_ptr,
+            ),
+        )?;
+
+        // get return values
+        let out_ptr = self.read_u32(alloc_ptr)?;
+        let out_len = self.read_u32(alloc_ptr + 4)?;
+
+        // read output from memory
+        let out_bytes = self
+            .memory
+            .data(&self.store)
+            .get(out_ptr as usize..(out_ptr + out_len) as usize)
+            .context("output slice out of bounds")?;
+        let result = match errno {
+            0 => Ok(decode_record_batch(out_bytes)?.column(0).clone()),
+            _ => Err(anyhow!("{}", std::str::from_utf8(out_bytes)?)),
+        };
+
+        // deallocate memory
+        self.dealloc
+            .call(&mut self.store, (alloc_ptr, alloc_len, 4))?;
+        self.dealloc.call(&mut self.store, (out_ptr, out_len, 1))?;
+
+        result
+    }
+
     /// Call a table function.
     fn call_table_function<'a>(
         &'a mut self,
-        name: &str,
+        export: &str,
         input: &RecordBatch,
     ) -> Result<impl Iterator<Item = Result<RecordBatch>> + 'a> {
         // TODO: optimize data transfer
@@ -314,10 +667,7 @@ impl Instance {
         //      wasm record batch -> wasm memory -> host record batch
 
         // get function
-        let func = self
-            .functions
-            .get(name)
-            .with_context(|| format!("function not found: {name}"))?;
+        let func = self.function(export)?;
 
         // encode input batch
         let input = encode_record_batch(input)?;
@@ -369,13 +719,33 @@ impl Instance {
         impl RecordBatchIter<'_> {
             /// Get the next record batch.
             fn next(&mut self) -> Result<Option<RecordBatch>> {
-                self.instance
-                    .record_batch_iterator_next
-                    .call(&mut self.instance.store, (self.ptr, self.alloc_ptr))?;
+                let errno = match &self.instance.record_batch_iterator_next {
+                    RecordBatchIteratorNext::Fallible(func) => {
+                        func.call(&mut self.instance.store, (self.ptr, self.alloc_ptr))?
+                    }
+                    RecordBatchIteratorNext::Infallible(func) => {
+                        func.call(&mut self.instance.store, (self.ptr, self.alloc_ptr))?;
+                        0
+                    }
+                };
                 // get return values
                 let out_ptr = self.instance.read_u32(self.alloc_ptr)?;
                 let out_len = self.instance.read_u32(self.alloc_ptr + 4)?;
 
+                if errno != 0 {
+                    // read error message from memory
+                    let out_bytes = self
+                        .instance
+                        .memory
+                        .data(&self.instance.store)
+                        .get(out_ptr as usize..(out_ptr + out_len) as usize)
+                        .context("output slice out of bounds")?;
+                    let err = anyhow!("{}", std::str::from_utf8(out_bytes)?);
+                    self.instance
+                        .dealloc
+                        .call(&mut self.instance.store, (out_ptr, out_len, 1))?;
+                    return Err(err);
+                }
                 if out_ptr == 0 {
                     // end of iteration
                     return Ok(None);
@@ -438,6 +808,61 @@ impl Instance {
     }
 }
 
+/// Load the compiled module of the binary from the cache directory, or compile and save it.
+///
+/// The cache key includes the compatibility hash of the engine, so modules compiled by
+/// another version of wasmtime or with other settings are not reused.
+fn load_cached_module(engine: &Engine, binary: &[u8], dir: &Path) -> Result<Module> {
+    use sha2::{Digest, Sha256};
+    use std::hash::{Hash, Hasher};
+
+    let mut engine_hasher = std::collections::hash_map::DefaultHasher::new();
+    engine
+        .precompile_compatibility_hash()
+        .hash(&mut engine_hasher);
+    let mut hasher = Sha256::new();
+    hasher.update(binary);
+    hasher.update(engine_hasher.finish().to_le_bytes());
+    let key: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let path = dir.join(format!("{key}.cwasm"));
+
+    if path.exists() {
+        // SAFETY: wasmtime only checks the header of the file and runs the native code in it,
+        // so the file must be written by `Module::serialize` below. This relies on the cache
+        // directory being only writable by trusted users, as documented on `Config::cache_dir`.
+        // a file of an incompatible engine is compiled again and overwritten.
+        if let Ok(module) = unsafe { Module::deserialize_file(engine, &path) } {
+            return Ok(module);
+        }
+    }
+    let module = Module::from_binary(engine, binary).context("failed to load wasm binary")?;
+    let serialized = module.serialize()?;
+    std::fs::create_dir_all(dir).context("failed to create cache directory")?;
+    // write to a temporary file and rename it, so that other processes never see a partial file
+    let temp_path = dir.join(format!("{key}.{}.tmp", std::process::id()));
+    std::fs::write(&temp_path, serialized).context("failed to write compiled module")?;
+    std::fs::rename(&temp_path, &path).context("failed to write compiled module")?;
+    Ok(module)
+}
+
+/// Verify that the binary is signed by one of the trusted keys.
+fn verify_signature(binary: &[u8], signature: &[u8; 64], trusted_keys: &[[u8; 32]]) -> Result<()> {
+    use ed25519_dalek::{Signature, VerifyingKey};
+
+    let signature = Signature::from_bytes(signature);
+    for key in trusted_keys {
+        let key = VerifyingKey::from_bytes(key).context("invalid trusted key")?;
+        if key.verify_strict(binary, &signature).is_ok() {
+            return Ok(());
+        }
+    }
+    bail!("module is not signed by a trusted key")
+}
+
 /// Decode a string from symbol name using customized base64.
 fn base64_decode(input: &str) -> Result<String> {
     use base64::{
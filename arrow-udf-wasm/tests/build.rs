@@ -1,6 +1,13 @@
 #![cfg(feature = "build")]
 
-use arrow_udf_wasm::{build::*, Runtime};
+use std::sync::Arc;
+
+use arrow_array::{Int32Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use arrow_udf::testing::{assert_roundtrip_array, random_array};
+use arrow_udf_wasm::{build::*, CancellationToken, Config, Env, Runtime};
+use ed25519_dalek::{Signer, SigningKey};
+use proptest::test_runner::{Config as ProptestConfig, TestRunner};
 
 #[test]
 fn test_build() {
@@ -19,16 +26,151 @@ fn gcd(mut a: i32, mut b: i32) -> i32 {
     }
     a
 }
+
+#[function("getenv(varchar) -> varchar")]
+fn getenv(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
 "#;
     let binary = build(manifest, script).unwrap();
 
     let runtime = Runtime::new(&binary).unwrap();
     assert!(runtime.functions().any(|f| f == "gcd(int4,int4)->int4"));
+    runtime.prewarm(&["gcd(int4,int4)->int4"]).unwrap();
+    let err = runtime.prewarm(&["lcm(int4,int4)->int4"]).unwrap_err();
+    assert_eq!(err.to_string(), "function not found: lcm(int4,int4)->int4");
+
+    // cancelled calls fail
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::Int32, true),
+        Field::new("b", DataType::Int32, true),
+    ]);
+    let a = Int32Array::from(vec![12]);
+    let b = Int32Array::from(vec![18]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a), Arc::new(b)]).unwrap();
+    let token = CancellationToken::new();
+    let output = runtime
+        .call_with_cancellation("gcd(int4,int4)->int4", &input, &token)
+        .unwrap();
+    assert_eq!(output.num_rows(), 1);
+    token.cancel();
+    let err = runtime
+        .call_with_cancellation("gcd(int4,int4)->int4", &input, &token)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "function call cancelled");
+
+    // compile once and load from the cache
+    let cache_dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.cache_dir = Some(cache_dir.path().into());
+    for _ in 0..2 {
+        let runtime = Runtime::with_config(&binary, config.clone()).unwrap();
+        assert!(runtime.functions().any(|f| f == "gcd(int4,int4)->int4"));
+    }
+    let files = std::fs::read_dir(cache_dir.path()).unwrap().count();
+    assert_eq!(files, 1);
+
+    // only load modules signed by trusted keys
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let other_key = SigningKey::from_bytes(&[2; 32]);
+    let mut config = Config::default();
+    config.trusted_keys = vec![key.verifying_key().to_bytes()];
+    let signature = key.sign(&binary).to_bytes();
+    let runtime = Runtime::with_signature(&binary, &signature, config.clone()).unwrap();
+    assert!(runtime.functions().any(|f| f == "gcd(int4,int4)->int4"));
+    // compiled modules are not covered by the signature, so they are not cached
+    let cache_dir = tempfile::tempdir().unwrap();
+    let mut cached_config = config.clone();
+    cached_config.cache_dir = Some(cache_dir.path().into());
+    Runtime::with_signature(&binary, &signature, cached_config).unwrap();
+    let files = std::fs::read_dir(cache_dir.path()).unwrap().count();
+    assert_eq!(files, 0);
+    let err = Runtime::with_config(&binary, config.clone()).unwrap_err();
+    assert_eq!(err.to_string(), "signature required for untrusted module");
+    let signature = other_key.sign(&binary).to_bytes();
+    let err = Runtime::with_signature(&binary, &signature, config).unwrap_err();
+    assert_eq!(err.to_string(), "module is not signed by a trusted key");
+
+    // environment variables of the module
+    let mut config = Config::default();
+    config.env = Env::new()
+        .with_var("ENDPOINT", "https://api.example.com")
+        .with_secret("API_KEY", "s3cr3t");
+    assert!(!format!("{config:?}").contains("s3cr3t"));
+    let runtime = Runtime::with_config(&binary, config).unwrap();
+    let schema = Schema::new(vec![Field::new("key", DataType::Utf8, true)]);
+    let keys = StringArray::from(vec!["ENDPOINT", "API_KEY", "HOME"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(keys)]).unwrap();
+    let output = runtime.call("getenv(varchar)->varchar", &input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &StringArray::from(vec![Some("https://api.example.com"), Some("s3cr3t"), None])
+    );
 
     // build again with offline mode
     test_build_offline();
 }
 
+#[test]
+fn test_identity_roundtrip() {
+    let script = r#"
+use arrow_udf::function;
+
+#[function("identity(boolean) -> boolean")]
+fn identity_bool(x: bool) -> bool { x }
+
+#[function("identity(int2) -> int2")]
+fn identity_int2(x: i16) -> i16 { x }
+
+#[function("identity(int4) -> int4")]
+fn identity_int4(x: i32) -> i32 { x }
+
+#[function("identity(int8) -> int8")]
+fn identity_int8(x: i64) -> i64 { x }
+
+#[function("identity(float4) -> float4")]
+fn identity_float4(x: f32) -> f32 { x }
+
+#[function("identity(float8) -> float8")]
+fn identity_float8(x: f64) -> f64 { x }
+
+#[function("identity(varchar) -> varchar")]
+fn identity_varchar(x: &str) -> String { x.to_string() }
+
+#[function("identity(bytea) -> bytea")]
+fn identity_bytea(x: &[u8]) -> Vec<u8> { x.to_vec() }
+"#;
+    // building the module is slow, so it is built once and shared by all cases
+    let binary = build("", script).unwrap();
+    let runtime = Runtime::new(&binary).unwrap();
+    let types = [
+        ("boolean", DataType::Boolean),
+        ("int2", DataType::Int16),
+        ("int4", DataType::Int32),
+        ("int8", DataType::Int64),
+        ("float4", DataType::Float32),
+        ("float8", DataType::Float64),
+        ("varchar", DataType::Utf8),
+        ("bytea", DataType::Binary),
+    ];
+    let mut runner = TestRunner::new(ProptestConfig::with_cases(32));
+    runner
+        .run(
+            &(0..u64::MAX, 0..64usize, 0.0f32..1.0),
+            |(seed, len, null_density)| {
+                for (name, data_type) in &types {
+                    let function = format!("identity({name})->{name}");
+                    assert_roundtrip_array(
+                        |input: &RecordBatch| runtime.call(&function, input),
+                        random_array(data_type, len, null_density, seed),
+                    );
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+}
+
 #[test]
 fn test_build_error() {
     let err = build("??", "").unwrap_err();
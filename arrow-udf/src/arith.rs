@@ -0,0 +1,207 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Integer arithmetic with configurable overflow behavior.
+//!
+//! The helpers in this module follow the [`Overflow`] behavior of the current function, so that
+//! engines with ANSI semantics get errors instead of silently wrapped values in release builds.
+//! The behavior is set by the `overflow` option of `#[function]`, or by
+//! [`EvalContext::with_overflow`](crate::EvalContext::with_overflow) for functions without it:
+//!
+//! ```
+//! use arrow_udf::{arith, function, Result};
+//!
+//! #[function("add(int, int) -> int", overflow = "saturate")]
+//! fn add(x: i32, y: i32) -> Result<i32> {
+//!     arith::add(x, y)
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::fmt::Display;
+
+use crate::{Error, Result};
+
+/// How to handle integer overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Return an error, as required by ANSI SQL.
+    #[default]
+    Error,
+    /// Wrap around at the boundary of the type.
+    Wrap,
+    /// Saturate at the minimum or maximum value of the type.
+    Saturate,
+}
+
+thread_local! {
+    static SCOPE: Cell<Option<Overflow>> = const { Cell::new(None) };
+}
+
+impl Overflow {
+    /// Returns the behavior of the current function.
+    ///
+    /// It is the `overflow` option of the function if specified, otherwise the one of the
+    /// current [`EvalContext`](crate::EvalContext), or [`Overflow::Error`] by default.
+    pub fn current() -> Self {
+        SCOPE
+            .with(|s| s.get())
+            .or_else(crate::context::current_overflow)
+            .unwrap_or_default()
+    }
+
+    /// Runs `f` with this behavior. Used by the generated code of the `overflow` option.
+    #[doc(hidden)]
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        /// Restores the previous behavior on drop, even if `f` panics.
+        struct Restore(Option<Overflow>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                SCOPE.with(|s| s.set(self.0));
+            }
+        }
+        let _restore = Restore(SCOPE.with(|s| s.replace(Some(self))));
+        f()
+    }
+}
+
+/// Signed integer types supported by the arithmetic helpers.
+pub trait Integer: Copy + Display {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+    fn checked_neg(self) -> Option<Self>;
+    fn wrapping_neg(self) -> Self;
+    fn saturating_neg(self) -> Self;
+    fn checked_abs(self) -> Option<Self>;
+    fn wrapping_abs(self) -> Self;
+    fn saturating_abs(self) -> Self;
+    fn checked_from_i128(v: i128) -> Option<Self>;
+    fn wrapping_from_i128(v: i128) -> Self;
+    fn saturating_from_i128(v: i128) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            fn checked_add(self, rhs: Self) -> Option<Self> { self.checked_add(rhs) }
+            fn wrapping_add(self, rhs: Self) -> Self { self.wrapping_add(rhs) }
+            fn saturating_add(self, rhs: Self) -> Self { self.saturating_add(rhs) }
+            fn checked_sub(self, rhs: Self) -> Option<Self> { self.checked_sub(rhs) }
+            fn wrapping_sub(self, rhs: Self) -> Self { self.wrapping_sub(rhs) }
+            fn saturating_sub(self, rhs: Self) -> Self { self.saturating_sub(rhs) }
+            fn checked_mul(self, rhs: Self) -> Option<Self> { self.checked_mul(rhs) }
+            fn wrapping_mul(self, rhs: Self) -> Self { self.wrapping_mul(rhs) }
+            fn saturating_mul(self, rhs: Self) -> Self { self.saturating_mul(rhs) }
+            fn checked_neg(self) -> Option<Self> { self.checked_neg() }
+            fn wrapping_neg(self) -> Self { self.wrapping_neg() }
+            fn saturating_neg(self) -> Self { self.saturating_neg() }
+            fn checked_abs(self) -> Option<Self> { self.checked_abs() }
+            fn wrapping_abs(self) -> Self { self.wrapping_abs() }
+            fn saturating_abs(self) -> Self { self.saturating_abs() }
+            fn checked_from_i128(v: i128) -> Option<Self> { Self::try_from(v).ok() }
+            fn wrapping_from_i128(v: i128) -> Self { v as Self }
+            fn saturating_from_i128(v: i128) -> Self { v.clamp(Self::MIN as i128, Self::MAX as i128) as Self }
+        }
+    )*};
+}
+
+impl_integer!(i8, i16, i32, i64);
+
+/// Returns the checked result, or handles the overflow by the current behavior.
+#[inline]
+fn handle<T>(
+    checked: Option<T>,
+    wrap: impl FnOnce() -> T,
+    saturate: impl FnOnce() -> T,
+    expr: impl FnOnce() -> String,
+) -> Result<T> {
+    if let Some(v) = checked {
+        return Ok(v);
+    }
+    match Overflow::current() {
+        Overflow::Error => Err(Error::ComputeError(format!(
+            "Overflow happened on: {}",
+            expr()
+        ))),
+        Overflow::Wrap => Ok(wrap()),
+        Overflow::Saturate => Ok(saturate()),
+    }
+}
+
+/// Returns `x + y`.
+pub fn add<T: Integer>(x: T, y: T) -> Result<T> {
+    handle(
+        x.checked_add(y),
+        || x.wrapping_add(y),
+        || x.saturating_add(y),
+        || format!("{x} + {y}"),
+    )
+}
+
+/// Returns `x - y`.
+pub fn sub<T: Integer>(x: T, y: T) -> Result<T> {
+    handle(
+        x.checked_sub(y),
+        || x.wrapping_sub(y),
+        || x.saturating_sub(y),
+        || format!("{x} - {y}"),
+    )
+}
+
+/// Returns `x * y`.
+pub fn mul<T: Integer>(x: T, y: T) -> Result<T> {
+    handle(
+        x.checked_mul(y),
+        || x.wrapping_mul(y),
+        || x.saturating_mul(y),
+        || format!("{x} * {y}"),
+    )
+}
+
+/// Returns `-x`.
+pub fn neg<T: Integer>(x: T) -> Result<T> {
+    handle(
+        x.checked_neg(),
+        || x.wrapping_neg(),
+        || x.saturating_neg(),
+        || format!("-{x}"),
+    )
+}
+
+/// Returns the absolute value of `x`.
+pub fn abs<T: Integer>(x: T) -> Result<T> {
+    handle(
+        x.checked_abs(),
+        || x.wrapping_abs(),
+        || x.saturating_abs(),
+        || format!("abs({x})"),
+    )
+}
+
+/// Converts a wider integer to `T`, e.g. the result of computing in `i128`.
+pub fn narrow<T: Integer>(x: i128) -> Result<T> {
+    handle(
+        T::checked_from_i128(x),
+        || T::wrapping_from_i128(x),
+        || T::saturating_from_i128(x),
+        || format!("{x} as {}", std::any::type_name::<T>()),
+    )
+}
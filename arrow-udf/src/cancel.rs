@@ -0,0 +1,66 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cancellation of function calls.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::{Error, Result};
+
+/// A token to cancel running function calls.
+///
+/// Clones of a token share the same state. Set it on the [`EvalContext`](crate::EvalContext)
+/// of a query, and call [`CancellationToken::cancel`] when the query is cancelled. Functions
+/// defined by `#[function]` check the token every 1024 rows and return an error once it is
+/// cancelled. Table functions stop producing rows instead.
+///
+/// ```
+/// use arrow_udf::{CancellationToken, EvalContext};
+///
+/// let token = CancellationToken::new();
+/// let ctx = EvalContext::default().with_cancellation_token(token.clone());
+/// // in another thread
+/// token.cancel();
+/// assert!(ctx.is_cancelled());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token and all its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns an error if the token is cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::ComputeError("function call cancelled".into()));
+        }
+        Ok(())
+    }
+}
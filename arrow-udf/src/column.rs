@@ -0,0 +1,277 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed readers of input columns for functions implemented by hand.
+//!
+//! Functions defined by `#[function]` downcast their input columns in the generated code.
+//! When implementing a [`ScalarFunction`](crate::ScalarFunction) by hand, a [`ColumnReader`]
+//! does the same: the Rust type of its values selects the array type at compile time, and
+//! the data type of the column is checked when the reader is created.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use arrow_array::{Int32Array, RecordBatch, StringArray};
+//! # use arrow_schema::{DataType, Field, Schema};
+//! use arrow_udf::column::ColumnReader;
+//!
+//! // repeat(varchar, int) -> varchar
+//! fn repeat(input: &RecordBatch) -> arrow_udf::Result<RecordBatch> {
+//!     let s = ColumnReader::<&str>::from_batch(input, 0)?;
+//!     let n = ColumnReader::<i32>::from_batch(input, 1)?;
+//!     let array: StringArray = s
+//!         .iter()
+//!         .zip(n)
+//!         .map(|(s, n)| Some(s?.repeat(n?.max(0) as usize)))
+//!         .collect();
+//!     let schema = Schema::new(vec![Field::new("repeat", DataType::Utf8, true)]);
+//!     RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)])
+//! }
+//!
+//! let schema = Schema::new(vec![
+//!     Field::new("s", DataType::Utf8, true),
+//!     Field::new("n", DataType::Int32, true),
+//! ]);
+//! let input = RecordBatch::try_new(
+//!     Arc::new(schema),
+//!     vec![
+//!         Arc::new(StringArray::from(vec![Some("ab"), None])),
+//!         Arc::new(Int32Array::from(vec![Some(2), Some(3)])),
+//!     ],
+//! )
+//! .unwrap();
+//! let output = repeat(&input).unwrap();
+//! assert_eq!(
+//!     output.column(0).as_ref(),
+//!     &StringArray::from(vec![Some("abab"), None])
+//! );
+//!
+//! // the data type is checked against the reader
+//! assert!(ColumnReader::<i64>::from_batch(&input, 1).is_err());
+//! ```
+
+use crate::{Error, Result};
+use arrow_array::types::*;
+use arrow_array::{Array, BinaryArray, BooleanArray, PrimitiveArray, RecordBatch, StringArray};
+use arrow_schema::DataType;
+use std::ops::Range;
+
+/// A Rust type that can be read from a column.
+pub trait ColumnValue<'a>: Sized {
+    /// The array type of the column.
+    type Array: Array + 'static;
+
+    /// Returns the data type of the column.
+    fn data_type() -> DataType;
+
+    /// Returns the value at index `i` of the array.
+    ///
+    /// The value at a null slot is arbitrary. `i` must be in bounds.
+    fn value(array: &'a Self::Array, i: usize) -> Self;
+}
+
+macro_rules! impl_primitive {
+    ($($native:ty => $arrow:ty),*) => {$(
+        impl<'a> ColumnValue<'a> for $native {
+            type Array = PrimitiveArray<$arrow>;
+
+            fn data_type() -> DataType {
+                <$arrow as arrow_array::ArrowPrimitiveType>::DATA_TYPE
+            }
+
+            fn value(array: &'a Self::Array, i: usize) -> Self {
+                array.value(i)
+            }
+        }
+    )*};
+}
+
+impl_primitive! {
+    i8 => Int8Type,
+    i16 => Int16Type,
+    i32 => Int32Type,
+    i64 => Int64Type,
+    u8 => UInt8Type,
+    u16 => UInt16Type,
+    u32 => UInt32Type,
+    u64 => UInt64Type,
+    half::f16 => Float16Type,
+    f32 => Float32Type,
+    f64 => Float64Type
+}
+
+impl<'a> ColumnValue<'a> for bool {
+    type Array = BooleanArray;
+
+    fn data_type() -> DataType {
+        DataType::Boolean
+    }
+
+    fn value(array: &'a Self::Array, i: usize) -> Self {
+        array.value(i)
+    }
+}
+
+impl<'a> ColumnValue<'a> for &'a str {
+    type Array = StringArray;
+
+    fn data_type() -> DataType {
+        DataType::Utf8
+    }
+
+    fn value(array: &'a Self::Array, i: usize) -> Self {
+        array.value(i)
+    }
+}
+
+impl<'a> ColumnValue<'a> for &'a [u8] {
+    type Array = BinaryArray;
+
+    fn data_type() -> DataType {
+        DataType::Binary
+    }
+
+    fn value(array: &'a Self::Array, i: usize) -> Self {
+        array.value(i)
+    }
+}
+
+/// A typed reader of a column with values of type `T`.
+pub struct ColumnReader<'a, T: ColumnValue<'a>> {
+    array: &'a T::Array,
+}
+
+impl<'a, T: ColumnValue<'a>> Clone for ColumnReader<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: ColumnValue<'a>> Copy for ColumnReader<'a, T> {}
+
+impl<'a, T: ColumnValue<'a>> ColumnReader<'a, T> {
+    /// Creates a reader of an array.
+    ///
+    /// Returns an error if the data type of the array is not the one of `T`.
+    pub fn new(array: &'a dyn Array) -> Result<Self> {
+        let array = array
+            .as_any()
+            .downcast_ref::<T::Array>()
+            .filter(|a| a.data_type() == &T::data_type())
+            .ok_or_else(|| {
+                Error::CastError(format!(
+                    "expect {} column, got {}",
+                    T::data_type(),
+                    array.data_type()
+                ))
+            })?;
+        Ok(Self { array })
+    }
+
+    /// Creates a reader of the `i`-th column of a record batch.
+    ///
+    /// Returns an error if the column does not exist or has a different data type.
+    pub fn from_batch(input: &'a RecordBatch, i: usize) -> Result<Self> {
+        let column = input.columns().get(i).ok_or_else(|| {
+            Error::InvalidArgumentError(format!(
+                "expect at least {} columns, got {}",
+                i + 1,
+                input.num_columns()
+            ))
+        })?;
+        Self::new(column.as_ref()).map_err(|_| {
+            Error::CastError(format!(
+                "expect {} for the {i}-th argument, got {}",
+                T::data_type(),
+                column.data_type()
+            ))
+        })
+    }
+
+    /// Returns the underlying array.
+    pub fn array(&self) -> &'a T::Array {
+        self.array
+    }
+
+    /// Returns the number of rows.
+    pub fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    /// Returns true if there are no rows.
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty()
+    }
+
+    /// Returns true if the value at row `i` is null.
+    pub fn is_null(&self, i: usize) -> bool {
+        self.array.is_null(i)
+    }
+
+    /// Returns the value at row `i`, or `None` if it is null.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn get(&self, i: usize) -> Option<T> {
+        assert!(i < self.len(), "index {i} out of bounds: {}", self.len());
+        (!self.array.is_null(i)).then(|| T::value(self.array, i))
+    }
+
+    /// Returns an iterator over the values, with `None` for nulls.
+    pub fn iter(&self) -> ColumnIter<'a, T> {
+        ColumnIter {
+            reader: *self,
+            range: 0..self.len(),
+        }
+    }
+}
+
+impl<'a, T: ColumnValue<'a>> IntoIterator for ColumnReader<'a, T> {
+    type Item = Option<T>;
+    type IntoIter = ColumnIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the values of a [`ColumnReader`].
+pub struct ColumnIter<'a, T: ColumnValue<'a>> {
+    reader: ColumnReader<'a, T>,
+    range: Range<usize>,
+}
+
+impl<'a, T: ColumnValue<'a>> Iterator for ColumnIter<'a, T> {
+    type Item = Option<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.range.next()?;
+        Some(self.reader.get(i))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, T: ColumnValue<'a>> DoubleEndedIterator for ColumnIter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let i = self.range.next_back()?;
+        Some(self.reader.get(i))
+    }
+}
+
+impl<'a, T: ColumnValue<'a>> ExactSizeIterator for ColumnIter<'a, T> {}
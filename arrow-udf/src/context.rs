@@ -0,0 +1,229 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! The context of evaluating functions.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, NaiveDateTime};
+
+use crate::arith::Overflow;
+use crate::CancellationToken;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// The context of evaluating functions in a query.
+///
+/// It carries a fixed query timestamp and a seedable random number generator, so that functions
+/// relying on time or randomness are reproducible and consistent across retries. It also carries
+/// the batch id and key-value metadata provided by the engine, such as the query id or the user,
+/// and the index of the current row, which are useful for auditing and debugging.
+/// A [`CancellationToken`] can be attached to stop the evaluation when the query is cancelled.
+///
+/// Functions defined by `#[function]` can access the current context by taking `&EvalContext`
+/// as an argument after the SQL arguments:
+///
+/// ```
+/// use arrow_udf::{function, EvalContext};
+/// use rand::Rng;
+///
+/// #[function("random_int(int) -> int")]
+/// fn random_int(max: i32, ctx: &EvalContext) -> i32 {
+///     ctx.rng().gen_range(0..max)
+/// }
+/// ```
+///
+/// The context is set by [`EvalContext::scope`]. If no context is set, a new one is created
+/// from the system time and a random seed for each call.
+#[derive(Debug, Clone)]
+pub struct EvalContext {
+    now: NaiveDateTime,
+    rng: Arc<Mutex<StdRng>>,
+    batch_id: u64,
+    metadata: Arc<HashMap<String, String>>,
+    row: usize,
+    cancellation_token: Option<CancellationToken>,
+    timezone: Option<Arc<str>>,
+    overflow: Option<Overflow>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<EvalContext>> = const { RefCell::new(None) };
+}
+
+impl EvalContext {
+    /// Creates a context with the query timestamp and the seed of random number generator.
+    pub fn new(now: NaiveDateTime, seed: u64) -> Self {
+        Self {
+            now,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+            batch_id: 0,
+            metadata: Default::default(),
+            row: 0,
+            cancellation_token: None,
+            timezone: None,
+            overflow: None,
+        }
+    }
+
+    /// Sets the id of the current batch.
+    ///
+    /// ```ignore
+    /// for (id, batch) in batches.enumerate() {
+    ///     let output = ctx.clone().with_batch_id(id as u64).scope(|| eval(&batch))?;
+    /// }
+    /// ```
+    pub fn with_batch_id(mut self, batch_id: u64) -> Self {
+        self.batch_id = batch_id;
+        self
+    }
+
+    /// Adds a key-value metadata, such as the query id or the user.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.metadata).insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the token to cancel the evaluation.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Sets the session time zone, e.g. `Asia/Shanghai` or `+08:00`.
+    ///
+    /// Functions converting between `timestamp` and `timestamptz` should consult it instead of
+    /// assuming UTC, so that the results match the host engine.
+    pub fn with_timezone(mut self, timezone: impl Into<Arc<str>>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+
+    /// Returns the session time zone set by [`EvalContext::with_timezone`].
+    pub fn timezone(&self) -> Option<&str> {
+        self.timezone.as_deref()
+    }
+
+    /// Sets how integer overflow is handled by the helpers in [`arith`](crate::arith).
+    ///
+    /// Functions with the `overflow` option of `#[function]` are not affected.
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = Some(overflow);
+        self
+    }
+
+    /// Returns how integer overflow is handled, set by [`EvalContext::with_overflow`].
+    pub fn overflow(&self) -> Overflow {
+        self.overflow.unwrap_or_default()
+    }
+
+    /// Returns the cancellation token set by [`EvalContext::with_cancellation_token`].
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+
+    /// Returns whether the evaluation is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    /// Returns the id of the current batch set by [`EvalContext::with_batch_id`].
+    pub fn batch_id(&self) -> u64 {
+        self.batch_id
+    }
+
+    /// Returns the value of a metadata.
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|s| s.as_str())
+    }
+
+    /// Returns the index of the current row in the input batch.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Sets the index of the current row. Used by the generated code.
+    #[doc(hidden)]
+    pub fn set_row(&mut self, row: usize) {
+        self.row = row;
+    }
+
+    /// Returns the timestamp of the query.
+    pub fn now(&self) -> NaiveDateTime {
+        self.now
+    }
+
+    /// Returns the random number generator.
+    ///
+    /// The generator is shared by the clones of this context.
+    pub fn rng(&self) -> MutexGuard<'_, StdRng> {
+        self.rng.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `f` with this context as the current context of this thread.
+    ///
+    /// ```ignore
+    /// let ctx = EvalContext::new(now, 42);
+    /// for batch in batches {
+    ///     let output = ctx.scope(|| eval_random_int(&batch))?;
+    /// }
+    /// ```
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        /// Restores the previous context on drop, even if `f` panics.
+        struct Restore(Option<EvalContext>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|c| *c.borrow_mut() = self.0.take());
+            }
+        }
+        let _restore = Restore(CURRENT.with(|c| c.borrow_mut().replace(self.clone())));
+        f()
+    }
+
+    /// Returns the current context of this thread, or a new one if not set.
+    pub fn current() -> Self {
+        CURRENT.with(|c| c.borrow().clone()).unwrap_or_default()
+    }
+}
+
+impl Default for EvalContext {
+    /// Creates a context from the system time and a random seed.
+    fn default() -> Self {
+        let micros = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as i64);
+        let now = DateTime::from_timestamp_micros(micros)
+            .expect("invalid system time")
+            .naive_utc();
+        Self::new(now, rand::random())
+    }
+}
+
+/// Returns the cancellation token of the current context of this thread, if any.
+pub(crate) fn current_cancellation_token() -> Option<CancellationToken> {
+    CURRENT.with(|c| c.borrow().as_ref()?.cancellation_token.clone())
+}
+
+/// Returns a metadata of the current context of this thread, if any.
+pub(crate) fn current_metadata(key: &str) -> Option<String> {
+    CURRENT.with(|c| c.borrow().as_ref()?.metadata(key).map(String::from))
+}
+
+/// Returns the overflow behavior of the current context of this thread, if any.
+pub(crate) fn current_overflow() -> Option<Overflow> {
+    CURRENT.with(|c| c.borrow().as_ref()?.overflow)
+}
@@ -0,0 +1,1183 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Evaluation helpers for scalar functions.
+//!
+//! The functions in this module work with any function that maps a record batch to a record
+//! batch of the same length, including [`ScalarFunction`](crate::ScalarFunction)s and the
+//! functions of other runtimes.
+
+use crate::context::current_metadata;
+use crate::{Error, Result};
+use arrow_array::cast::AsArray;
+use arrow_array::{new_null_array, Array, ArrayRef, BooleanArray, RecordBatch, UInt32Array};
+use arrow_row::{OwnedRow, RowConverter, SortField};
+use arrow_schema::{Field, Schema, SchemaRef};
+use arrow_select::concat::concat;
+use arrow_select::filter::filter_record_batch;
+use arrow_select::interleave::interleave;
+use arrow_select::take::{take, take_record_batch};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of rows in the first chunk of [`eval_chunked`].
+const INITIAL_CHUNK_ROWS: usize = 16;
+
+/// A scalar function defined by a closure at runtime.
+pub type DynScalarFunction = Arc<dyn Fn(&RecordBatch) -> Result<RecordBatch> + Send + Sync>;
+
+/// Evaluates the function only on the rows selected by `selection`.
+///
+/// Rows that are not selected (false or null in `selection`) are not passed to the function,
+/// and all columns of their outputs are null. The output has the same number of rows as the input.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{BooleanArray, Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::eval_filtered, function};
+///
+/// #[function("neg(int) -> int", output = "eval_neg")]
+/// fn neg(x: i32) -> i32 {
+///     -x
+/// }
+///
+/// let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+/// let array = Int32Array::from(vec![1, 2, 3]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+/// let selection = BooleanArray::from(vec![true, false, true]);
+///
+/// let output = eval_filtered(&input, &selection, eval_neg).unwrap();
+/// let expected = Int32Array::from(vec![Some(-1), None, Some(-3)]);
+/// assert_eq!(output.column(0).as_ref(), &expected);
+/// ```
+pub fn eval_filtered<F>(input: &RecordBatch, selection: &BooleanArray, f: F) -> Result<RecordBatch>
+where
+    F: FnOnce(&RecordBatch) -> Result<RecordBatch>,
+{
+    if selection.len() != input.num_rows() {
+        return Err(Error::InvalidArgumentError(format!(
+            "expect selection of length {}, got {}",
+            input.num_rows(),
+            selection.len()
+        )));
+    }
+    // fast path: all rows are selected
+    if selection.null_count() == 0 && selection.true_count() == selection.len() {
+        return f(input);
+    }
+    let filtered = filter_record_batch(input, selection)?;
+    let output = f(&filtered)?;
+    if output.num_rows() != filtered.num_rows() {
+        return Err(Error::ComputeError(format!(
+            "expect {} rows from the function, got {}",
+            filtered.num_rows(),
+            output.num_rows()
+        )));
+    }
+    // map each input row to its row in the output, or null if not selected
+    let mut next = 0;
+    let indices: UInt32Array = selection
+        .iter()
+        .map(|selected| {
+            selected.unwrap_or(false).then(|| {
+                next += 1;
+                next - 1
+            })
+        })
+        .collect();
+    let columns = output
+        .columns()
+        .iter()
+        .map(|column| take(column, &indices, None))
+        .collect::<Result<Vec<_>>>()?;
+    RecordBatch::try_new(output.schema(), columns)
+}
+
+/// Evaluates the function in chunks whose outputs are limited to about `max_memory` bytes.
+///
+/// This is useful for functions whose outputs can be much bigger than the inputs.
+/// The input is split into chunks of rows, and the size of each chunk is estimated from the
+/// output size of previous chunks. The limit is soft: a chunk contains at least one row, and
+/// its output may exceed the limit if the output size varies a lot between rows.
+///
+/// The iterator stops after the first error.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::eval_chunked, function};
+///
+/// #[function("zeros(int) -> bytea", output = "eval_zeros")]
+/// fn zeros(n: i32) -> Vec<u8> {
+///     vec![0; n as usize]
+/// }
+///
+/// let schema = Schema::new(vec![Field::new("n", DataType::Int32, true)]);
+/// let array = Int32Array::from(vec![64 << 10; 1024]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+///
+/// // each chunk has about 16 rows of 64KB
+/// for output in eval_chunked(&input, 1 << 20, eval_zeros) {
+///     assert!(output.unwrap().num_rows() <= 16);
+/// }
+/// ```
+pub fn eval_chunked<'a, F>(
+    input: &'a RecordBatch,
+    max_memory: usize,
+    mut f: F,
+) -> impl Iterator<Item = Result<RecordBatch>> + 'a
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch> + 'a,
+{
+    let mut offset = 0;
+    let mut chunk_rows = INITIAL_CHUNK_ROWS;
+    std::iter::from_fn(move || {
+        if offset >= input.num_rows() {
+            return None;
+        }
+        let rows = chunk_rows.min(input.num_rows() - offset);
+        let output = match f(&input.slice(offset, rows)) {
+            Ok(output) => output,
+            Err(e) => {
+                // stop after the first error
+                offset = input.num_rows();
+                return Some(Err(e));
+            }
+        };
+        offset += rows;
+        // estimate the size of next chunk, growing by at most 4 times
+        let bytes_per_row = output.get_array_memory_size().div_ceil(rows).max(1);
+        chunk_rows = (max_memory / bytes_per_row).clamp(1, rows * 4);
+        Some(Ok(output))
+    })
+}
+
+/// Evaluates the function on columns of different lengths, broadcasting columns of length 1
+/// to all rows.
+///
+/// Engines often pass literal arguments as arrays of length 1, like the pattern of
+/// `regexp_match(col, 'pattern')`. The number of rows is the length of the other columns, which
+/// must be the same. If all columns are of length 1, the function is evaluated on one row.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{ArrayRef, Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::eval_broadcast, function};
+///
+/// #[function("add(int, int) -> int", output = "eval_add")]
+/// fn add(x: i32, y: i32) -> i32 {
+///     x + y
+/// }
+///
+/// let schema = Schema::new(vec![
+///     Field::new("x", DataType::Int32, true),
+///     Field::new("y", DataType::Int32, true),
+/// ]);
+/// let x: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+/// let y: ArrayRef = Arc::new(Int32Array::from(vec![10]));
+///
+/// let output = eval_broadcast(Arc::new(schema), &[x, y], eval_add).unwrap();
+/// let expected = Int32Array::from(vec![11, 12, 13]);
+/// assert_eq!(output.column(0).as_ref(), &expected);
+/// ```
+pub fn eval_broadcast<F>(schema: SchemaRef, columns: &[ArrayRef], f: F) -> Result<RecordBatch>
+where
+    F: FnOnce(&RecordBatch) -> Result<RecordBatch>,
+{
+    let num_rows = columns
+        .iter()
+        .map(|c| c.len())
+        .find(|&len| len != 1)
+        .unwrap_or(1);
+    if let Some((i, c)) = columns
+        .iter()
+        .enumerate()
+        .find(|(_, c)| c.len() != 1 && c.len() != num_rows)
+    {
+        return Err(Error::InvalidArgumentError(format!(
+            "expect column {i} of length 1 or {num_rows}, got {}",
+            c.len()
+        )));
+    }
+    let columns = columns
+        .iter()
+        .map(|c| match c.len() == num_rows {
+            true => Ok(c.clone()),
+            false => broadcast(c.as_ref(), num_rows),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    f(&RecordBatch::try_new(schema, columns)?)
+}
+
+/// Evaluates the function once for each distinct input row, and gathers the outputs by index.
+///
+/// This is useful for expensive functions on low-cardinality inputs, like country names.
+/// The distinct rows are only evaluated if their number is at most `max_ratio` of the
+/// number of rows. Otherwise, the function is evaluated on the whole input.
+///
+/// If the input is a single dictionary-encoded column, its keys are used instead of computing
+/// the distinct rows, and the function is evaluated on the dictionary values and a null.
+/// The function then receives a column of the value type.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{StringArray, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::eval_distinct, function};
+///
+/// #[function("upper(varchar) -> varchar", output = "eval_upper")]
+/// fn upper(s: &str) -> String {
+///     s.to_uppercase()
+/// }
+///
+/// let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+/// let array = StringArray::from(vec!["a", "b", "a", "a"]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+///
+/// // evaluates "a" and "b" only
+/// let output = eval_distinct(&input, 0.5, eval_upper).unwrap();
+/// let expected = StringArray::from(vec!["A", "B", "A", "A"]);
+/// assert_eq!(output.column(0).as_ref(), &expected);
+/// ```
+pub fn eval_distinct<F>(input: &RecordBatch, max_ratio: f64, f: F) -> Result<RecordBatch>
+where
+    F: FnOnce(&RecordBatch) -> Result<RecordBatch>,
+{
+    let max_distinct = (input.num_rows() as f64 * max_ratio) as usize;
+    if let [column] = input.columns() {
+        if let Some(dict) = column.as_any_dictionary_opt() {
+            // the values and a null row for null keys
+            let num_values = dict.values().len();
+            if num_values + 1 > max_distinct {
+                return f(input);
+            }
+            let null = new_null_array(dict.values().data_type(), 1);
+            let values = concat(&[dict.values().as_ref(), null.as_ref()])?;
+            let indices: UInt32Array = match num_values {
+                0 => vec![0; input.num_rows()].into(),
+                _ => dict
+                    .normalized_keys()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, key)| match dict.is_null(i) {
+                        true => num_values as u32,
+                        false => key as u32,
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            };
+            let schema = input.schema();
+            let field = schema.field(0);
+            let field = Field::new(field.name(), values.data_type().clone(), true)
+                .with_metadata(field.metadata().clone());
+            let distinct = RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![values])?;
+            return gather(f(&distinct)?, distinct.num_rows(), &indices);
+        }
+    }
+
+    let fields = input
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| SortField::new(f.data_type().clone()))
+        .collect::<Vec<_>>();
+    if !RowConverter::supports_fields(&fields) {
+        return f(input);
+    }
+    let rows = RowConverter::new(fields)?.convert_columns(input.columns())?;
+    let mut distinct = HashMap::new();
+    let mut distinct_indices = vec![];
+    let mut indices = Vec::with_capacity(input.num_rows());
+    for (i, row) in rows.iter().enumerate() {
+        let next = distinct.len() as u32;
+        let index = *distinct.entry(row).or_insert_with(|| {
+            distinct_indices.push(i as u32);
+            next
+        });
+        if distinct.len() > max_distinct {
+            return f(input);
+        }
+        indices.push(index);
+    }
+    let distinct_indices = UInt32Array::from(distinct_indices);
+    let columns = input
+        .columns()
+        .iter()
+        .map(|c| take(c, &distinct_indices, None))
+        .collect::<Result<Vec<_>>>()?;
+    let distinct = RecordBatch::try_new(input.schema(), columns)?;
+    gather(f(&distinct)?, distinct.num_rows(), &indices.into())
+}
+
+/// Takes the rows of the output of `num_rows` distinct rows by `indices`.
+fn gather(output: RecordBatch, num_rows: usize, indices: &UInt32Array) -> Result<RecordBatch> {
+    if output.num_rows() != num_rows {
+        return Err(Error::ComputeError(format!(
+            "expect {num_rows} rows from the function, got {}",
+            output.num_rows()
+        )));
+    }
+    let columns = output
+        .columns()
+        .iter()
+        .map(|column| take(column, indices, None))
+        .collect::<Result<Vec<_>>>()?;
+    RecordBatch::try_new(output.schema(), columns)
+}
+
+/// Repeats the only value of an array of length 1 `num_rows` times.
+pub(crate) fn broadcast(array: &dyn Array, num_rows: usize) -> Result<ArrayRef> {
+    let indices = UInt32Array::from(vec![0; num_rows]);
+    take(array, &indices, None)
+}
+
+/// Statistics of a predicate evaluated by [`eval_selectivity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Selectivity {
+    /// The number of rows passed to the function.
+    pub evaluated: usize,
+    /// The number of rows evaluated to true.
+    pub matched: usize,
+    /// The number of rows evaluated to null.
+    pub nulls: usize,
+    /// The number of rows skipped after enough matches were found.
+    pub skipped: usize,
+}
+
+impl Selectivity {
+    /// Returns the ratio of matched rows to evaluated rows, or 0 if no rows were evaluated.
+    pub fn ratio(&self) -> f64 {
+        if self.evaluated == 0 {
+            return 0.0;
+        }
+        self.matched as f64 / self.evaluated as f64
+    }
+}
+
+/// Evaluates a function returning booleans as a filter predicate, and counts the matches.
+///
+/// The first column of the output must be boolean. If `min_matches` is set, the input is
+/// evaluated in chunks of growing size, and the evaluation stops as soon as at least
+/// `min_matches` rows are true. This accelerates plans like `LIMIT n` on top of a filter,
+/// where the remaining rows would be discarded anyway. The skipped rows are false in the
+/// returned array, and counted in [`Selectivity::skipped`].
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::eval_selectivity, function};
+///
+/// #[function("is_even(int) -> boolean", output = "eval_is_even")]
+/// fn is_even(x: i32) -> bool {
+///     x % 2 == 0
+/// }
+///
+/// let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+/// let array = Int32Array::from_iter_values(0..1000);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+///
+/// let (selection, stats) = eval_selectivity(&input, None, eval_is_even).unwrap();
+/// assert_eq!(selection.true_count(), 500);
+/// assert_eq!(stats.matched, 500);
+///
+/// // stop after 10 matches
+/// let (selection, stats) = eval_selectivity(&input, Some(10), eval_is_even).unwrap();
+/// assert_eq!(selection.len(), 1000);
+/// assert!(stats.matched >= 10);
+/// assert!(stats.skipped > 0);
+/// ```
+pub fn eval_selectivity<F>(
+    input: &RecordBatch,
+    min_matches: Option<usize>,
+    mut f: F,
+) -> Result<(BooleanArray, Selectivity)>
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch>,
+{
+    let num_rows = input.num_rows();
+    let Some(min_matches) = min_matches else {
+        let output = eval_predicate(input, &mut f)?;
+        let stats = Selectivity {
+            evaluated: num_rows,
+            matched: output.true_count(),
+            nulls: output.null_count(),
+            skipped: 0,
+        };
+        return Ok((output, stats));
+    };
+    let mut stats = Selectivity::default();
+    let mut outputs = vec![];
+    let mut chunk_rows = INITIAL_CHUNK_ROWS;
+    while stats.evaluated < num_rows && stats.matched < min_matches {
+        let rows = chunk_rows.min(num_rows - stats.evaluated);
+        let output = eval_predicate(&input.slice(stats.evaluated, rows), &mut f)?;
+        stats.evaluated += rows;
+        stats.matched += output.true_count();
+        stats.nulls += output.null_count();
+        outputs.push(output);
+        // estimate the rows for the remaining matches, growing by at most 4 times
+        chunk_rows = match stats.matched {
+            0 => rows * 4,
+            matched => ((min_matches.saturating_sub(matched)) * stats.evaluated)
+                .div_ceil(matched)
+                .clamp(INITIAL_CHUNK_ROWS, (rows * 4).max(INITIAL_CHUNK_ROWS)),
+        };
+    }
+    stats.skipped = num_rows - stats.evaluated;
+    if stats.skipped > 0 {
+        outputs.push(BooleanArray::from(vec![false; stats.skipped]));
+    }
+    let output = match outputs.len() {
+        0 => BooleanArray::from(Vec::<bool>::new()),
+        1 => outputs.pop().unwrap(),
+        _ => {
+            let arrays = outputs.iter().map(|a| a as &dyn Array).collect::<Vec<_>>();
+            concat(&arrays)?.as_boolean().clone()
+        }
+    };
+    Ok((output, stats))
+}
+
+/// Evaluates the function and returns the first column of the output as booleans.
+fn eval_predicate<F>(input: &RecordBatch, f: &mut F) -> Result<BooleanArray>
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch>,
+{
+    let output = f(input)?;
+    if output.num_rows() != input.num_rows() {
+        return Err(Error::ComputeError(format!(
+            "expect {} rows from the function, got {}",
+            input.num_rows(),
+            output.num_rows()
+        )));
+    }
+    match output.columns().first() {
+        Some(column) => column.as_boolean_opt().cloned().ok_or_else(|| {
+            Error::InvalidArgumentError(format!(
+                "expect a boolean output, got {}",
+                column.data_type()
+            ))
+        }),
+        None => Err(Error::InvalidArgumentError(
+            "expect a boolean output, got no columns".into(),
+        )),
+    }
+}
+
+/// A scalar function that falls back to another function when it fails.
+///
+/// If the function returns an error, or any row of its output has an error in the `error`
+/// column, the batch is evaluated by the fallback instead, such as a local approximation of a
+/// remote function, or a function returning default values. After
+/// `max_failures` consecutive failures, the function is no longer called and all batches go to
+/// the fallback, until [`reset`](Self::reset) is called. A success resets the count.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::FallbackFunction, function};
+///
+/// #[function("div(int, int) -> int", output = "eval_div")]
+/// fn div(x: i32, y: i32) -> Result<i32, &'static str> {
+///     x.checked_div(y).ok_or("division by zero")
+/// }
+///
+/// #[function("div_or_null(int, int) -> int", output = "eval_div_or_null")]
+/// fn div_or_null(x: i32, y: i32) -> Option<i32> {
+///     x.checked_div(y)
+/// }
+///
+/// let schema = Schema::new(vec![
+///     Field::new("x", DataType::Int32, true),
+///     Field::new("y", DataType::Int32, true),
+/// ]);
+/// let x = Int32Array::from(vec![4, 4]);
+/// let y = Int32Array::from(vec![2, 0]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(x), Arc::new(y)]).unwrap();
+///
+/// let f = FallbackFunction::new(eval_div, eval_div_or_null, 3);
+/// let output = f.call(&input).unwrap();
+/// let expected = Int32Array::from(vec![Some(2), None]);
+/// assert_eq!(output.column(0).as_ref(), &expected);
+/// ```
+#[derive(Debug)]
+pub struct FallbackFunction<F, G> {
+    function: F,
+    fallback: G,
+    max_failures: u32,
+    failures: AtomicU32,
+}
+
+impl<F, G> FallbackFunction<F, G>
+where
+    F: Fn(&RecordBatch) -> Result<RecordBatch>,
+    G: Fn(&RecordBatch) -> Result<RecordBatch>,
+{
+    /// Creates a function that switches to `fallback` after `max_failures` consecutive failures.
+    pub fn new(function: F, fallback: G, max_failures: u32) -> Self {
+        Self {
+            function,
+            fallback,
+            max_failures,
+            failures: AtomicU32::new(0),
+        }
+    }
+
+    /// Evaluates the function, or the fallback if the function fails or has been switched off.
+    ///
+    /// Errors of the function are not returned, but errors of the fallback are.
+    pub fn call(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        if self.is_degraded() {
+            return (self.fallback)(input);
+        }
+        match (self.function)(input) {
+            Ok(output) if !has_errors(&output) => {
+                self.failures.store(0, Ordering::Relaxed);
+                Ok(output)
+            }
+            _ => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                (self.fallback)(input)
+            }
+        }
+    }
+
+    /// Returns whether all batches go to the fallback.
+    pub fn is_degraded(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) >= self.max_failures
+    }
+
+    /// Resets the count of consecutive failures, so that the function is called again.
+    pub fn reset(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A scalar function with multiple implementations, which routes calls to the fastest one.
+///
+/// This is useful when the same function is implemented by multiple runtimes, like a native
+/// Rust function and a Python fallback. The cost of each implementation is measured as the
+/// moving average of the time per row. Implementations that have not been measured are called
+/// first, then calls go to the cheapest one. Every `explore_interval` calls, the implementations
+/// are measured again in turn, so that the routing follows changes of their costs.
+///
+/// An implementation can be [pinned](Self::pin) to override the routing.
+/// See [`FunctionRegistry::routed_function`] to build it from the registered functions.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::RoutedFunction, function};
+///
+/// #[function("square(int) -> int", output = "eval_square")]
+/// fn square(x: i32) -> i32 {
+///     x * x
+/// }
+///
+/// let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+/// let array = Int32Array::from(vec![1, 2, 3]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+///
+/// let f = RoutedFunction::default()
+///     .with_implementation("rust", eval_square)
+///     .with_implementation("slow", |input: &RecordBatch| {
+///         std::thread::sleep(std::time::Duration::from_millis(10));
+///         eval_square(input)
+///     });
+/// // each implementation is measured once
+/// f.call(&input).unwrap();
+/// f.call(&input).unwrap();
+/// assert_eq!(f.fastest(), Some("rust"));
+///
+/// f.pin("slow").unwrap();
+/// let output = f.call(&input).unwrap();
+/// assert_eq!(output.column(0).as_ref(), &Int32Array::from(vec![1, 4, 9]));
+/// ```
+///
+/// [`FunctionRegistry::routed_function`]: crate::sig::FunctionRegistry::routed_function
+pub struct RoutedFunction {
+    implementations: Vec<Implementation>,
+    explore_interval: u64,
+    calls: AtomicU64,
+    /// The index of the pinned implementation, or `usize::MAX` if none.
+    pinned: AtomicUsize,
+}
+
+/// An implementation of a [`RoutedFunction`].
+struct Implementation {
+    name: String,
+    function: DynScalarFunction,
+    /// The moving average of nanoseconds per row as `f64` bits, or `u64::MAX` if not measured.
+    cost: AtomicU64,
+}
+
+/// The weight of the latest measurement in the moving average of costs.
+const COST_WEIGHT: f64 = 0.2;
+
+const UNPINNED: usize = usize::MAX;
+const UNMEASURED: u64 = u64::MAX;
+
+impl Default for RoutedFunction {
+    fn default() -> Self {
+        Self {
+            implementations: vec![],
+            explore_interval: 100,
+            calls: AtomicU64::new(0),
+            pinned: AtomicUsize::new(UNPINNED),
+        }
+    }
+}
+
+impl fmt::Debug for RoutedFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoutedFunction")
+            .field("costs", &self.costs())
+            .field("pinned", &self.pinned())
+            .field("explore_interval", &self.explore_interval)
+            .finish()
+    }
+}
+
+impl RoutedFunction {
+    /// Adds an implementation with the given name, like the runtime implementing it.
+    ///
+    /// An implementation with the same name is replaced.
+    pub fn with_implementation(
+        mut self,
+        name: &str,
+        function: impl Fn(&RecordBatch) -> Result<RecordBatch> + Send + Sync + 'static,
+    ) -> Self {
+        self.implementations.retain(|imp| imp.name != name);
+        self.implementations.push(Implementation {
+            name: name.into(),
+            function: Arc::new(function),
+            cost: AtomicU64::new(UNMEASURED),
+        });
+        self
+    }
+
+    /// Sets the number of calls between measuring all implementations again. The default is 100.
+    ///
+    /// Zero disables measuring again once all implementations are measured.
+    pub fn with_explore_interval(mut self, interval: u64) -> Self {
+        self.explore_interval = interval;
+        self
+    }
+
+    /// Evaluates the function by the pinned implementation, or the fastest one.
+    ///
+    /// Errors of the implementation are returned, and do not update its cost.
+    pub fn call(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        let index = self.route()?;
+        let imp = &self.implementations[index];
+        let start = Instant::now();
+        let output = (imp.function)(input)?;
+        let cost = start.elapsed().as_nanos() as f64 / input.num_rows().max(1) as f64;
+        let old = imp.cost.load(Ordering::Relaxed);
+        let new = match old {
+            UNMEASURED => cost,
+            _ => f64::from_bits(old) * (1.0 - COST_WEIGHT) + cost * COST_WEIGHT,
+        };
+        imp.cost.store(new.to_bits(), Ordering::Relaxed);
+        Ok(output)
+    }
+
+    /// Returns the index of the implementation to call.
+    fn route(&self) -> Result<usize> {
+        if self.implementations.is_empty() {
+            return Err(Error::InvalidArgumentError(
+                "routed function has no implementation".into(),
+            ));
+        }
+        let pinned = self.pinned.load(Ordering::Relaxed);
+        if pinned != UNPINNED {
+            return Ok(pinned);
+        }
+        let calls = self.calls.fetch_add(1, Ordering::Relaxed);
+        if let Some(index) = self
+            .implementations
+            .iter()
+            .position(|imp| imp.cost.load(Ordering::Relaxed) == UNMEASURED)
+        {
+            return Ok(index);
+        }
+        if calls.checked_rem(self.explore_interval) == Some(0) {
+            let round = calls / self.explore_interval;
+            return Ok((round % self.implementations.len() as u64) as usize);
+        }
+        Ok(self.fastest_index().unwrap_or(0))
+    }
+
+    /// Returns the index of the measured implementation with the lowest cost.
+    fn fastest_index(&self) -> Option<usize> {
+        self.costs()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (_, cost))| Some((i, cost?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Pins the implementation with the given name, so that all calls go to it.
+    ///
+    /// Returns an error if there is no implementation with the name.
+    pub fn pin(&self, name: &str) -> Result<()> {
+        let Some(index) = self.implementations.iter().position(|imp| imp.name == name) else {
+            return Err(Error::InvalidArgumentError(format!(
+                "no implementation named {name:?}"
+            )));
+        };
+        self.pinned.store(index, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Unpins the implementation, so that calls are routed by costs again.
+    pub fn unpin(&self) {
+        self.pinned.store(UNPINNED, Ordering::Relaxed);
+    }
+
+    /// Returns the name of the pinned implementation.
+    pub fn pinned(&self) -> Option<&str> {
+        let index = self.pinned.load(Ordering::Relaxed);
+        self.implementations.get(index).map(|imp| imp.name.as_str())
+    }
+
+    /// Returns the name of the measured implementation with the lowest cost.
+    pub fn fastest(&self) -> Option<&str> {
+        let index = self.fastest_index()?;
+        Some(&self.implementations[index].name)
+    }
+
+    /// Returns the names of the implementations in the order they were added.
+    pub fn implementations(&self) -> impl Iterator<Item = &str> {
+        self.implementations.iter().map(|imp| imp.name.as_str())
+    }
+
+    /// Returns the measured cost of each implementation in nanoseconds per row,
+    /// or `None` if it has not been called.
+    pub fn costs(&self) -> Vec<(&str, Option<f64>)> {
+        self.implementations
+            .iter()
+            .map(|imp| {
+                let cost = match imp.cost.load(Ordering::Relaxed) {
+                    UNMEASURED => None,
+                    bits => Some(f64::from_bits(bits)),
+                };
+                (imp.name.as_str(), cost)
+            })
+            .collect()
+    }
+}
+
+/// A scalar function that caches its outputs by the values of input rows.
+///
+/// This is useful for expensive functions called on few distinct values, like geocoding
+/// addresses. Rows whose arguments are in the cache are not evaluated again, and distinct
+/// arguments in a batch are evaluated only once. The least recently used entries are evicted
+/// once the cache holds `capacity` rows. Rows with an error in the `error` column are not cached.
+/// Output rows are copied into the cache, so its memory is bounded by `capacity` rows rather than
+/// by the batches they come from.
+///
+/// Only immutable functions should be cached, since the outputs of volatile functions may
+/// differ for the same arguments. See [`FunctionSignature::cached_function`].
+///
+/// Input columns of types not supported by the row format of `arrow-row` are evaluated
+/// without the cache.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use arrow_array::{Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::{eval::CachedFunction, function};
+///
+/// #[function("square(int) -> int", output = "eval_square")]
+/// fn square(x: i32) -> i32 {
+///     x * x
+/// }
+///
+/// let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+/// let array = Int32Array::from(vec![2, 3, 2, 2]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+///
+/// let f = CachedFunction::new(eval_square, 1024);
+/// let output = f.call(&input).unwrap();
+/// let expected = Int32Array::from(vec![4, 9, 4, 4]);
+/// assert_eq!(output.column(0).as_ref(), &expected);
+/// assert_eq!((f.hits(), f.misses()), (0, 4));
+///
+/// // all rows are found in the cache
+/// f.call(&input).unwrap();
+/// assert_eq!((f.hits(), f.misses()), (4, 4));
+/// ```
+///
+/// [`FunctionSignature::cached_function`]: crate::sig::FunctionSignature::cached_function
+#[derive(Debug)]
+pub struct CachedFunction<F> {
+    function: F,
+    cache: Mutex<LruCache>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<F> CachedFunction<F>
+where
+    F: Fn(&RecordBatch) -> Result<RecordBatch>,
+{
+    /// Creates a function that caches the outputs of up to `capacity` distinct rows.
+    pub fn new(function: F, capacity: usize) -> Self {
+        Self {
+            function,
+            cache: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Evaluates the function on the rows not in the cache, and caches their outputs.
+    pub fn call(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        let fields = input
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| SortField::new(f.data_type().clone()))
+            .collect::<Vec<_>>();
+        if input.num_rows() == 0 || !RowConverter::supports_fields(&fields) {
+            return (self.function)(input);
+        }
+        let rows = RowConverter::new(fields)?.convert_columns(input.columns())?;
+
+        // the source of each output row: a cached batch, or a row of the output of misses
+        enum Source {
+            Cached(RecordBatch),
+            Miss(usize),
+        }
+        let mut sources = Vec::with_capacity(input.num_rows());
+        let mut misses: HashMap<OwnedRow, usize> = HashMap::new();
+        let mut miss_indices = vec![];
+        {
+            let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            for (i, row) in rows.iter().enumerate() {
+                let row = row.owned();
+                if let Some(output) = cache.get(&row) {
+                    sources.push(Source::Cached(output));
+                    continue;
+                }
+                let next = misses.len();
+                let index = *misses.entry(row).or_insert_with(|| {
+                    miss_indices.push(i as u32);
+                    next
+                });
+                sources.push(Source::Miss(index));
+            }
+        }
+        let num_hits = sources
+            .iter()
+            .filter(|s| matches!(s, Source::Cached(_)))
+            .count();
+        self.hits.fetch_add(num_hits as u64, Ordering::Relaxed);
+        self.misses
+            .fetch_add((input.num_rows() - num_hits) as u64, Ordering::Relaxed);
+
+        // fast path: all rows are distinct misses
+        if miss_indices.len() == input.num_rows() {
+            let output = (self.function)(input)?;
+            self.insert(misses, &output)?;
+            return Ok(output);
+        }
+        let miss_output = match miss_indices.is_empty() {
+            true => None,
+            false => {
+                let indices = UInt32Array::from(miss_indices);
+                let columns = input
+                    .columns()
+                    .iter()
+                    .map(|c| take(c, &indices, None))
+                    .collect::<Result<Vec<_>>>()?;
+                let misses_input = RecordBatch::try_new(input.schema(), columns)?;
+                let output = (self.function)(&misses_input)?;
+                self.insert(misses, &output)?;
+                Some(output)
+            }
+        };
+
+        // gather the output rows from the outputs of misses and the cached rows
+        let schema = match (&miss_output, &sources[0]) {
+            (Some(output), _) => output.schema(),
+            (None, Source::Cached(output)) => output.schema(),
+            (None, Source::Miss(_)) => unreachable!("no output of misses"),
+        };
+        let mut batches = vec![];
+        batches.extend(miss_output.iter());
+        let indices = sources
+            .iter()
+            .map(|source| match source {
+                Source::Miss(row) => (0, *row),
+                Source::Cached(batch) => {
+                    batches.push(batch);
+                    (batches.len() - 1, 0)
+                }
+            })
+            .collect::<Vec<_>>();
+        let columns = (0..schema.fields().len())
+            .map(|i| {
+                let arrays = batches
+                    .iter()
+                    .map(|b| b.column(i).as_ref())
+                    .collect::<Vec<_>>();
+                interleave(&arrays, &indices)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        RecordBatch::try_new(schema, columns)
+    }
+
+    /// Caches the output rows of misses, except for those with errors.
+    fn insert(&self, misses: HashMap<OwnedRow, usize>, output: &RecordBatch) -> Result<()> {
+        if output.num_rows() != misses.len() {
+            return Err(Error::ComputeError(format!(
+                "expect {} rows from the function, got {}",
+                misses.len(),
+                output.num_rows()
+            )));
+        }
+        let errors = match has_error_column(output) {
+            true => output.columns().last(),
+            false => None,
+        };
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        for (row, i) in misses {
+            if errors.is_some_and(|errors| errors.is_valid(i)) {
+                continue;
+            }
+            // copy the row, since a slice would keep the buffers of the whole output alive
+            let indices = UInt32Array::from(vec![i as u32]);
+            cache.insert(row, take_record_batch(output, &indices)?);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of rows found in the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of rows not found in the cache.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// A least recently used cache of output rows.
+#[derive(Debug)]
+struct LruCache {
+    capacity: usize,
+    /// Input row -> (last access, output row).
+    entries: HashMap<OwnedRow, (u64, RecordBatch)>,
+    /// Last access -> input row, in the order of access.
+    order: BTreeMap<u64, OwnedRow>,
+    clock: u64,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, row: &OwnedRow) -> Option<RecordBatch> {
+        let (accessed, output) = self.entries.get_mut(row)?;
+        self.clock += 1;
+        let row = self.order.remove(accessed).unwrap();
+        self.order.insert(self.clock, row);
+        *accessed = self.clock;
+        Some(output.clone())
+    }
+
+    fn insert(&mut self, row: OwnedRow, output: RecordBatch) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.clock += 1;
+        if let Some((accessed, _)) = self.entries.insert(row.clone(), (self.clock, output)) {
+            self.order.remove(&accessed);
+        }
+        self.order.insert(self.clock, row);
+        while self.entries.len() > self.capacity {
+            let (_, row) = self.order.pop_first().unwrap();
+            self.entries.remove(&row);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+/// A record of one call to an [`AuditedFunction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord<'a> {
+    /// The name of the function.
+    pub function: &'a str,
+    /// The `tenant` metadata of the current [`EvalContext`](crate::EvalContext), if any.
+    pub tenant: Option<&'a str>,
+    /// The number of input rows.
+    pub rows: usize,
+    /// The time spent in the function.
+    pub duration: Duration,
+    /// Whether the call succeeded without errors in any row.
+    pub success: bool,
+}
+
+/// A destination of audit records, such as a log file or a table.
+///
+/// Closures taking an [`AuditRecord`] implement this trait.
+pub trait AuditSink: Send + Sync {
+    /// Records a call to a function.
+    fn record(&self, record: &AuditRecord<'_>);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord<'_>) + Send + Sync,
+{
+    fn record(&self, record: &AuditRecord<'_>) {
+        self(record)
+    }
+}
+
+/// A scalar function that reports every call to an [`AuditSink`].
+///
+/// This allows regulated environments to record who executed which function over how many
+/// rows. The tenant is read from the `tenant` metadata of the current
+/// [`EvalContext`](crate::EvalContext), set by the engine with
+/// [`with_metadata`](crate::EvalContext::with_metadata). A call fails if the function returns
+/// an error or any row has an error in the `error` column.
+///
+/// # Example
+///
+/// ```
+/// # use std::sync::{Arc, Mutex};
+/// # use arrow_array::{Int32Array, RecordBatch};
+/// # use arrow_schema::{DataType, Field, Schema};
+/// use arrow_udf::eval::{AuditRecord, AuditedFunction};
+/// use arrow_udf::{function, EvalContext};
+///
+/// #[function("neg(int) -> int", output = "eval_neg")]
+/// fn neg(x: i32) -> i32 {
+///     -x
+/// }
+///
+/// let log = Arc::new(Mutex::new(vec![]));
+/// let sink = {
+///     let log = log.clone();
+///     move |r: &AuditRecord<'_>| {
+///         let entry = format!("{} {:?} {} {}", r.function, r.tenant, r.rows, r.success);
+///         log.lock().unwrap().push(entry);
+///     }
+/// };
+/// let f = AuditedFunction::new("neg", eval_neg, sink);
+///
+/// let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+/// let array = Int32Array::from(vec![1, 2, 3]);
+/// let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+///
+/// let ctx = EvalContext::default().with_metadata("tenant", "alice");
+/// ctx.scope(|| f.call(&input)).unwrap();
+/// assert_eq!(log.lock().unwrap()[0], r#"neg Some("alice") 3 true"#);
+/// ```
+#[derive(Debug)]
+pub struct AuditedFunction<F, S> {
+    name: String,
+    function: F,
+    sink: S,
+}
+
+impl<F, S> AuditedFunction<F, S>
+where
+    F: Fn(&RecordBatch) -> Result<RecordBatch>,
+    S: AuditSink,
+{
+    /// Creates a function that reports calls of `function` named `name` to `sink`.
+    pub fn new(name: impl Into<String>, function: F, sink: S) -> Self {
+        Self {
+            name: name.into(),
+            function,
+            sink,
+        }
+    }
+
+    /// Evaluates the function and reports the call to the sink.
+    pub fn call(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        let start = Instant::now();
+        let result = (self.function)(input);
+        let duration = start.elapsed();
+        let tenant = current_metadata("tenant");
+        self.sink.record(&AuditRecord {
+            function: &self.name,
+            tenant: tenant.as_deref(),
+            rows: input.num_rows(),
+            duration,
+            success: result.as_ref().is_ok_and(|output| !has_errors(output)),
+        });
+        result
+    }
+}
+
+/// Returns whether any row of the output has an error in the `error` column.
+fn has_errors(output: &RecordBatch) -> bool {
+    has_error_column(output) && output.columns().last().unwrap().null_count() < output.num_rows()
+}
+
+/// Returns whether the last column of the output is the `error` column.
+fn has_error_column(output: &RecordBatch) -> bool {
+    let schema = output.schema();
+    match schema.fields().last() {
+        Some(field) => schema.fields().len() > 1 && field.name() == "error",
+        None => false,
+    }
+}
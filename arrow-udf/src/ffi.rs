@@ -13,8 +13,10 @@
 // limitations under the License.
 
 //! FFI interfaces.
+//!
+//! The symbols in this module are exported on WebAssembly, or with the `ffi` feature on other targets.
 
-use crate::{Error, ScalarFunction, TableFunction};
+use crate::{AggregateFunction, Error, ScalarFunction, TableFunction};
 use arrow_array::RecordBatch;
 use arrow_ipc::{reader::FileReader, writer::FileWriter};
 
@@ -26,18 +28,20 @@ use arrow_ipc::{reader::FileReader, writer::FileWriter};
 ///
 /// # Changelog
 ///
+/// - 3.0: `record_batch_iterator_next` returns an error code and the error message of the iterator.
+/// - 2.1: Add aggregate functions.
 /// - 2.0: Add user defined struct type.
 /// - 1.0: Initial version.
-#[no_mangle]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ffi"), no_mangle)]
 #[used]
-pub static ARROWUDF_VERSION_2_0: () = ();
+pub static ARROWUDF_VERSION_3_0: () = ();
 
 /// Allocate memory.
 ///
 /// # Safety
 ///
 /// See [`std::alloc::GlobalAlloc::alloc`].
-#[no_mangle]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ffi"), no_mangle)]
 pub unsafe extern "C" fn alloc(len: usize, align: usize) -> *mut u8 {
     std::alloc::alloc(std::alloc::Layout::from_size_align_unchecked(len, align))
 }
@@ -47,7 +51,7 @@ pub unsafe extern "C" fn alloc(len: usize, align: usize) -> *mut u8 {
 /// # Safety
 ///
 /// See [`std::alloc::GlobalAlloc::dealloc`].
-#[no_mangle]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ffi"), no_mangle)]
 pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize, align: usize) {
     std::alloc::dealloc(
         ptr,
@@ -124,12 +128,90 @@ fn call_scalar(function: ScalarFunction, input_bytes: &[u8]) -> Result<Box<[u8]>
     Ok(buf.into())
 }
 
+/// A wrapper for calling aggregate functions from C.
+///
+/// The state is read from the IPC buffer pointed to by `state_ptr` and `state_len`, which is a
+/// record batch with one row and one column. If `state_len` is 0, the initial state is used.
+/// The input record batch is read from the IPC buffer pointed to by `ptr` and `len`. Its first
+/// column is a boolean column marking the rows to retract.
+///
+/// The new state is written to the buffer pointed to by `out_slice` in the same format.
+/// The caller is responsible for deallocating the output buffer.
+///
+/// The return value is 0 on success, -1 on error.
+/// If failed, the error message is written to the buffer.
+///
+/// # Safety
+///
+/// `state_ptr`, `state_len`, `ptr`, `len`, `out_slice` must point to a valid buffer.
+pub unsafe fn aggregate_wrapper(
+    function: AggregateFunction,
+    state_ptr: *const u8,
+    state_len: usize,
+    ptr: *const u8,
+    len: usize,
+    out_slice: *mut CSlice,
+) -> i32 {
+    let state = match state_len {
+        0 => None,
+        _ => Some(std::slice::from_raw_parts(state_ptr, state_len)),
+    };
+    let input = std::slice::from_raw_parts(ptr, len);
+    match call_aggregate(function, state, input) {
+        Ok(data) => {
+            out_slice.write(CSlice {
+                ptr: data.as_ptr(),
+                len: data.len(),
+            });
+            std::mem::forget(data);
+            0
+        }
+        Err(err) => {
+            let msg = err.to_string().into_boxed_str();
+            out_slice.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+    }
+}
+
+fn call_aggregate(
+    function: AggregateFunction,
+    state_bytes: Option<&[u8]>,
+    input_bytes: &[u8],
+) -> Result<Box<[u8]>, Error> {
+    let state = state_bytes.map(read_record_batch).transpose()?;
+    let input = read_record_batch(input_bytes)?;
+
+    let state = function(state.as_ref().map(|s| s.column(0).as_ref()), &input)?;
+
+    let output_batch = RecordBatch::try_from_iter([("state", state)])?;
+    let mut buf = vec![];
+    let mut writer = FileWriter::try_new(&mut buf, &output_batch.schema())?;
+    writer.write(&output_batch)?;
+    writer.finish()?;
+    drop(writer);
+
+    Ok(buf.into())
+}
+
+/// Read the first record batch from an IPC buffer.
+fn read_record_batch(bytes: &[u8]) -> Result<RecordBatch, Error> {
+    let mut reader = FileReader::try_new(std::io::Cursor::new(bytes), None)?;
+    reader
+        .next()
+        .ok_or_else(|| Error::IpcError("no record batch".into()))?
+}
+
 /// An opaque type for iterating over record batches.
 pub struct RecordBatchIter {
     /// The input record batch is borrowed by `iter`. Its lifetime must be longer than `iter`.
     _input: Box<RecordBatch>,
     /// This iterator borrows `input`.
-    iter: Box<dyn Iterator<Item = RecordBatch>>,
+    iter: Box<dyn Iterator<Item = Result<RecordBatch, Error>>>,
 }
 
 /// A wrapper for calling table functions from C.
@@ -193,39 +275,63 @@ fn call_table(function: TableFunction, input_bytes: &[u8]) -> Result<Box<RecordB
 /// The output record batch is written to the buffer pointed to by `out`.
 /// The caller is responsible for deallocating the output buffer.
 ///
+/// The return value is 0 on success, -1 on error.
+/// If successful, the record batch is written to the buffer, or a null pointer at the end of the iteration.
+/// If failed, the error message is written to the buffer.
+///
 /// # Safety
 ///
 /// `iter` and `out` must be valid pointers.
-#[no_mangle]
-pub unsafe extern "C" fn record_batch_iterator_next(iter: *mut RecordBatchIter, out: *mut CSlice) {
+#[cfg_attr(any(target_arch = "wasm32", feature = "ffi"), no_mangle)]
+pub unsafe extern "C" fn record_batch_iterator_next(
+    iter: *mut RecordBatchIter,
+    out: *mut CSlice,
+) -> i32 {
     let iter = iter.as_mut().expect("null pointer");
-    if let Some(batch) = iter.iter.next() {
-        let mut buf = vec![];
-        let mut writer = FileWriter::try_new(&mut buf, &batch.schema()).unwrap();
-        writer.write(&batch).unwrap();
-        writer.finish().unwrap();
-        drop(writer);
-        let buf = buf.into_boxed_slice();
-
-        out.write(CSlice {
-            ptr: buf.as_ptr(),
-            len: buf.len(),
-        });
-        std::mem::forget(buf);
-    } else {
-        out.write(CSlice {
-            ptr: std::ptr::null(),
-            len: 0,
-        });
+    match iter.iter.next().map(|batch| write_record_batch(&batch?)) {
+        Some(Ok(data)) => {
+            out.write(CSlice {
+                ptr: data.as_ptr(),
+                len: data.len(),
+            });
+            std::mem::forget(data);
+            0
+        }
+        Some(Err(err)) => {
+            let msg = err.to_string().into_boxed_str();
+            out.write(CSlice {
+                ptr: msg.as_ptr(),
+                len: msg.len(),
+            });
+            std::mem::forget(msg);
+            -1
+        }
+        None => {
+            out.write(CSlice {
+                ptr: std::ptr::null(),
+                len: 0,
+            });
+            0
+        }
     }
 }
 
+/// Write a record batch to an IPC buffer.
+fn write_record_batch(batch: &RecordBatch) -> Result<Box<[u8]>, Error> {
+    let mut buf = vec![];
+    let mut writer = FileWriter::try_new(&mut buf, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(buf.into())
+}
+
 /// Drop the iterator.
 ///
 /// # Safety
 ///
 /// `iter` must be valid pointers.
-#[no_mangle]
+#[cfg_attr(any(target_arch = "wasm32", feature = "ffi"), no_mangle)]
 pub unsafe extern "C" fn record_batch_iterator_drop(iter: *mut RecordBatchIter) {
     drop(Box::from_raw(iter));
 }
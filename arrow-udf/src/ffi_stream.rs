@@ -0,0 +1,169 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Arrow C stream interface for calling functions.
+//!
+//! Engines written in other languages can call the functions registered in the global registry
+//! through the [Arrow C stream interface], without any Rust code on their side.
+//! Each batch of the input stream contains the arguments as columns, and each batch of the
+//! output stream contains the results.
+//!
+//! ```c
+//! struct ArrowArrayStream input = ...;
+//! struct ArrowArrayStream output;
+//! if (arrowudf_call_stream("gcd", 3, &input, &output) != 0) {
+//!     struct ArrowArray array;
+//!     output.get_next(&output, &array);
+//!     printf("%s\n", output.get_last_error(&output));
+//! }
+//! ```
+//!
+//! [Arrow C stream interface]: https://arrow.apache.org/docs/format/CStreamInterface.html
+
+use crate::sig::REGISTRY;
+use crate::{Error, Result, ScalarFunction};
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_schema::{Schema, SchemaRef};
+use std::sync::Arc;
+
+pub use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+
+/// Calls the scalar function `name` on each batch of the `input` stream.
+///
+/// The function is looked up by name and the fields of the input schema.
+/// The `input` stream is moved into the `output` stream.
+///
+/// Returns 0 on success. Otherwise, returns -1 and the `output` stream reports the error
+/// on the first call of `get_next`.
+///
+/// # Safety
+///
+/// `name` must point to `name_len` bytes. `input` must point to a valid stream, and `output`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn arrowudf_call_stream(
+    name: *const u8,
+    name_len: usize,
+    input: *mut FFI_ArrowArrayStream,
+    output: *mut FFI_ArrowArrayStream,
+) -> i32 {
+    let name = std::slice::from_raw_parts(name, name_len);
+    let input = std::ptr::replace(input, FFI_ArrowArrayStream::empty());
+    let (code, stream) = match call_stream(name, input) {
+        Ok(stream) => (0, stream),
+        Err(e) => (
+            -1,
+            FFI_ArrowArrayStream::new(Box::new(ErrorReader(Some(e)))),
+        ),
+    };
+    std::ptr::write(output, stream);
+    code
+}
+
+/// Looks up the scalar function and calls it on the input stream.
+fn call_stream(name: &[u8], input: FFI_ArrowArrayStream) -> Result<FFI_ArrowArrayStream> {
+    let name = std::str::from_utf8(name)
+        .map_err(|e| Error::InvalidArgumentError(format!("invalid function name: {e}")))?;
+    let input = ArrowArrayStreamReader::try_new(input)?;
+    let schema = input.schema();
+    let function = REGISTRY
+        .iter()
+        .filter(|sig| sig.has_name(name) && sig.matches_arg_fields(schema.fields()))
+        .filter(|sig| sig.function.as_scalar().is_some())
+        // the latest version, or the first one if there are multiple
+        .reduce(|a, b| if b.version > a.version { b } else { a })
+        .and_then(|sig| sig.function.as_scalar())
+        .ok_or_else(|| {
+            Error::InvalidArgumentError(format!("function not found: {name}({schema})"))
+        })?;
+    scalar_stream(function, Box::new(input))
+}
+
+/// Returns a stream of the outputs of a scalar function on each batch of the `input`.
+pub fn scalar_stream(
+    function: ScalarFunction,
+    input: Box<dyn RecordBatchReader + Send>,
+) -> Result<FFI_ArrowArrayStream> {
+    let reader = ScalarFunctionReader::try_new(function, input)?;
+    Ok(FFI_ArrowArrayStream::new(Box::new(reader)))
+}
+
+/// A reader that applies a scalar function on each batch of the input.
+struct ScalarFunctionReader {
+    function: ScalarFunction,
+    input: Box<dyn RecordBatchReader + Send>,
+    schema: SchemaRef,
+    /// The output of the first batch, which is evaluated in advance to get the schema.
+    first: Option<Result<RecordBatch>>,
+}
+
+impl ScalarFunctionReader {
+    fn try_new(
+        function: ScalarFunction,
+        mut input: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<Self> {
+        let (schema, first) = match input.next() {
+            Some(batch) => {
+                let output = function(&batch?)?;
+                (output.schema(), Some(Ok(output)))
+            }
+            // evaluate on an empty batch to get the schema
+            None => (
+                function(&RecordBatch::new_empty(input.schema()))?.schema(),
+                None,
+            ),
+        };
+        Ok(Self {
+            function,
+            input,
+            schema,
+            first,
+        })
+    }
+}
+
+impl Iterator for ScalarFunctionReader {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(first) = self.first.take() {
+            return Some(first);
+        }
+        let batch = self.input.next()?;
+        Some(batch.and_then(|batch| (self.function)(&batch)))
+    }
+}
+
+impl RecordBatchReader for ScalarFunctionReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// A reader that returns an error on the first call.
+struct ErrorReader(Option<Error>);
+
+impl Iterator for ErrorReader {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.take().map(Err)
+    }
+}
+
+impl RecordBatchReader for ErrorReader {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::empty())
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Geometry types.
+//!
+//! Geometries are stored as [WKB] in a `Binary` array, and the field is annotated with the
+//! GeoArrow extension name [`GEOARROW_WKB_EXTENSION_NAME`]. Functions declare them as
+//! `geometry` in the signature and get [`Geometry`] values:
+//!
+//! ```ignore
+//! #[function("st_x(geometry) -> float8")]
+//! fn st_x(g: Geometry) -> Option<f64> {
+//!     match g {
+//!         Geometry::Point(p) => Some(p.x()),
+//!         _ => None,
+//!     }
+//! }
+//! ```
+//!
+//! Only 2D geometries are supported. `Line`, `Rect` and `Triangle` are written as
+//! `LineString` and `Polygon`.
+//!
+//! [WKB]: https://libgeos.org/specifications/wkb/
+//! [`GEOARROW_WKB_EXTENSION_NAME`]: crate::types::GEOARROW_WKB_EXTENSION_NAME
+
+use crate::{Error, Result};
+use geo_types::{
+    Coord, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon, Point,
+    Polygon,
+};
+
+pub use geo_types::Geometry;
+
+const POINT: u32 = 1;
+const LINE_STRING: u32 = 2;
+const POLYGON: u32 = 3;
+const MULTI_POINT: u32 = 4;
+const MULTI_LINE_STRING: u32 = 5;
+const MULTI_POLYGON: u32 = 6;
+const GEOMETRY_COLLECTION: u32 = 7;
+
+/// Parses a geometry from WKB.
+pub fn from_wkb(wkb: &[u8]) -> Result<Geometry> {
+    let mut reader = Reader { wkb, pos: 0 };
+    let geometry = reader.geometry()?;
+    if reader.pos != wkb.len() {
+        return Err(invalid("trailing bytes"));
+    }
+    Ok(geometry)
+}
+
+/// Writes a geometry as little-endian WKB.
+pub fn to_wkb(geometry: &Geometry) -> Vec<u8> {
+    let mut wkb = Vec::with_capacity(64);
+    write_geometry(&mut wkb, geometry);
+    wkb
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::ParseError(format!("invalid WKB: {msg}"))
+}
+
+struct Reader<'a> {
+    wkb: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self
+            .wkb
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| invalid("unexpected end"))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32> {
+        let bytes = self.bytes()?;
+        Ok(match little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn f64(&mut self, little_endian: bool) -> Result<f64> {
+        let bytes = self.bytes()?;
+        Ok(match little_endian {
+            true => f64::from_le_bytes(bytes),
+            false => f64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads the byte order and type of a geometry.
+    fn header(&mut self) -> Result<(bool, u32)> {
+        let little_endian = match self.bytes::<1>()? {
+            [0] => false,
+            [1] => true,
+            _ => return Err(invalid("unknown byte order")),
+        };
+        Ok((little_endian, self.u32(little_endian)?))
+    }
+
+    /// Reads a count, and checks that there are enough bytes for `count` items of `min_size`.
+    fn count(&mut self, little_endian: bool, min_size: usize) -> Result<usize> {
+        let count = self.u32(little_endian)? as usize;
+        if count.saturating_mul(min_size) > self.wkb.len() - self.pos {
+            return Err(invalid("unexpected end"));
+        }
+        Ok(count)
+    }
+
+    fn coord(&mut self, little_endian: bool) -> Result<Coord> {
+        Ok(Coord {
+            x: self.f64(little_endian)?,
+            y: self.f64(little_endian)?,
+        })
+    }
+
+    fn line_string(&mut self, little_endian: bool) -> Result<LineString> {
+        let n = self.count(little_endian, 16)?;
+        let coords = (0..n)
+            .map(|_| self.coord(little_endian))
+            .collect::<Result<_>>()?;
+        Ok(LineString::new(coords))
+    }
+
+    fn polygon(&mut self, little_endian: bool) -> Result<Polygon> {
+        let n = self.count(little_endian, 4)?;
+        if n == 0 {
+            return Ok(Polygon::new(LineString::new(vec![]), vec![]));
+        }
+        let exterior = self.line_string(little_endian)?;
+        let interiors = (1..n)
+            .map(|_| self.line_string(little_endian))
+            .collect::<Result<_>>()?;
+        Ok(Polygon::new(exterior, interiors))
+    }
+
+    /// Reads the geometries of a multi geometry, which must all be of `expected` type.
+    fn children(&mut self, little_endian: bool, expected: Option<u32>) -> Result<Vec<Geometry>> {
+        let n = self.count(little_endian, 5)?;
+        let mut children = Vec::with_capacity(n);
+        for _ in 0..n {
+            let start = self.pos;
+            let (_, ty) = self.header()?;
+            if expected.is_some_and(|expected| expected != ty) {
+                return Err(invalid("unexpected geometry type in multi geometry"));
+            }
+            self.pos = start;
+            children.push(self.geometry()?);
+        }
+        Ok(children)
+    }
+
+    fn geometry(&mut self) -> Result<Geometry> {
+        let (le, ty) = self.header()?;
+        Ok(match ty {
+            POINT => Geometry::Point(Point(self.coord(le)?)),
+            LINE_STRING => Geometry::LineString(self.line_string(le)?),
+            POLYGON => Geometry::Polygon(self.polygon(le)?),
+            MULTI_POINT => {
+                let points = self.children(le, Some(POINT))?.into_iter();
+                Geometry::MultiPoint(MultiPoint(points.filter_map(as_point).collect()))
+            }
+            MULTI_LINE_STRING => {
+                let lines = self.children(le, Some(LINE_STRING))?.into_iter();
+                let lines = lines.filter_map(as_line_string).collect();
+                Geometry::MultiLineString(MultiLineString(lines))
+            }
+            MULTI_POLYGON => {
+                let polygons = self.children(le, Some(POLYGON))?.into_iter();
+                let polygons = polygons.filter_map(as_polygon).collect();
+                Geometry::MultiPolygon(MultiPolygon(polygons))
+            }
+            GEOMETRY_COLLECTION => {
+                Geometry::GeometryCollection(GeometryCollection(self.children(le, None)?))
+            }
+            _ => return Err(invalid(&format!("unsupported geometry type {ty}"))),
+        })
+    }
+}
+
+fn as_point(g: Geometry) -> Option<Point> {
+    match g {
+        Geometry::Point(p) => Some(p),
+        _ => None,
+    }
+}
+
+fn as_line_string(g: Geometry) -> Option<LineString> {
+    match g {
+        Geometry::LineString(l) => Some(l),
+        _ => None,
+    }
+}
+
+fn as_polygon(g: Geometry) -> Option<Polygon> {
+    match g {
+        Geometry::Polygon(p) => Some(p),
+        _ => None,
+    }
+}
+
+fn write_header(wkb: &mut Vec<u8>, ty: u32) {
+    wkb.push(1);
+    wkb.extend_from_slice(&ty.to_le_bytes());
+}
+
+fn write_count(wkb: &mut Vec<u8>, n: usize) {
+    wkb.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn write_coord(wkb: &mut Vec<u8>, coord: Coord) {
+    wkb.extend_from_slice(&coord.x.to_le_bytes());
+    wkb.extend_from_slice(&coord.y.to_le_bytes());
+}
+
+fn write_coords(wkb: &mut Vec<u8>, coords: &[Coord]) {
+    write_count(wkb, coords.len());
+    for coord in coords {
+        write_coord(wkb, *coord);
+    }
+}
+
+fn write_polygon(wkb: &mut Vec<u8>, polygon: &Polygon) {
+    write_header(wkb, POLYGON);
+    if polygon.exterior().0.is_empty() {
+        write_count(wkb, 0);
+        return;
+    }
+    write_count(wkb, 1 + polygon.interiors().len());
+    write_coords(wkb, &polygon.exterior().0);
+    for interior in polygon.interiors() {
+        write_coords(wkb, &interior.0);
+    }
+}
+
+fn write_geometry(wkb: &mut Vec<u8>, geometry: &Geometry) {
+    match geometry {
+        Geometry::Point(p) => {
+            write_header(wkb, POINT);
+            write_coord(wkb, p.0);
+        }
+        Geometry::Line(l) => {
+            write_header(wkb, LINE_STRING);
+            write_coords(wkb, &[l.start, l.end]);
+        }
+        Geometry::LineString(l) => {
+            write_header(wkb, LINE_STRING);
+            write_coords(wkb, &l.0);
+        }
+        Geometry::Polygon(p) => write_polygon(wkb, p),
+        Geometry::MultiPoint(m) => {
+            write_header(wkb, MULTI_POINT);
+            write_count(wkb, m.0.len());
+            for p in &m.0 {
+                write_header(wkb, POINT);
+                write_coord(wkb, p.0);
+            }
+        }
+        Geometry::MultiLineString(m) => {
+            write_header(wkb, MULTI_LINE_STRING);
+            write_count(wkb, m.0.len());
+            for l in &m.0 {
+                write_header(wkb, LINE_STRING);
+                write_coords(wkb, &l.0);
+            }
+        }
+        Geometry::MultiPolygon(m) => {
+            write_header(wkb, MULTI_POLYGON);
+            write_count(wkb, m.0.len());
+            for p in &m.0 {
+                write_polygon(wkb, p);
+            }
+        }
+        Geometry::GeometryCollection(c) => {
+            write_header(wkb, GEOMETRY_COLLECTION);
+            write_count(wkb, c.0.len());
+            for g in &c.0 {
+                write_geometry(wkb, g);
+            }
+        }
+        Geometry::Rect(r) => write_polygon(wkb, &r.to_polygon()),
+        Geometry::Triangle(t) => write_polygon(wkb, &t.to_polygon()),
+    }
+}
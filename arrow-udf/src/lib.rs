@@ -14,36 +14,415 @@
 
 #![doc = include_str!("../README.md")]
 
-use arrow_array::RecordBatch;
+use arrow_array::{Array, ArrayRef, RecordBatch};
 pub use arrow_schema::ArrowError as Error;
-pub use arrow_udf_macros::function;
+pub use arrow_udf_macros::{aggregate, function};
+pub use cancel::CancellationToken;
+pub use context::EvalContext;
+pub use panic::PanicError;
 
 /// A specialized `Result` type for Arrow UDF operations.
 pub type Result<T> = std::result::Result<T, Error>;
 
+pub mod arith;
+mod cancel;
+pub mod column;
+mod context;
+pub mod eval;
 pub mod ffi;
+#[cfg(feature = "ffi_stream")]
+pub mod ffi_stream;
+#[cfg(feature = "geo")]
+pub mod geo;
+mod panic;
+pub mod replay;
 #[cfg(feature = "global_registry")]
 pub mod sig;
+pub mod sql;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod types;
 
 /// A scalar function that operates on a record batch.
 pub type ScalarFunction = fn(input: &RecordBatch) -> Result<RecordBatch>;
 
+/// A scalar value, represented as an array of length 1.
+pub type ScalarValue = arrow_array::Scalar<ArrayRef>;
+
+/// A scalar function that operates on scalar values.
+///
+/// This is equivalent to calling the [`ScalarFunction`] on a batch of one row,
+/// but without building record batches. It is useful for constant folding.
+pub type ScalarValueFunction = fn(args: &[ScalarValue]) -> Result<ScalarValue>;
+
+/// A scalar function that holds a context shared by all calls.
+///
+/// The context is finished when the function is dropped.
+pub type BoxScalarFunction = Box<dyn Fn(&RecordBatch) -> Result<RecordBatch> + Send>;
+
+/// Creates a [`BoxScalarFunction`] with a new context.
+///
+/// This is generated for functions with an `init` hook. Call it once per query or stream, and
+/// drop the returned function when the query ends.
+pub type ScalarFunctionInit = fn() -> BoxScalarFunction;
+
 /// A table function that operates on a record batch and returns an iterator of record batches.
-pub type TableFunction =
-    for<'a> fn(input: &'a RecordBatch) -> Result<Box<dyn Iterator<Item = RecordBatch> + 'a>>;
+///
+/// The iterator yields an error and stops if the function fails after it started producing
+/// batches, for example when the call is cancelled.
+pub type TableFunction = for<'a> fn(
+    input: &'a RecordBatch,
+) -> Result<Box<dyn Iterator<Item = Result<RecordBatch>> + 'a>>;
+
+/// An aggregate function that accumulates rows into a state.
+///
+/// The state is an array of length 1, or `None` for the initial state. The first column of
+/// `input` is a boolean column marking the rows to retract, followed by the arguments.
+/// Returns the new state, which is also the output of the aggregate.
+pub type AggregateFunction = fn(state: Option<&dyn Array>, input: &RecordBatch) -> Result<ArrayRef>;
 
 /// Internal APIs used by macros.
 #[doc(hidden)]
 pub mod codegen {
     pub use arrow_arith;
     pub use arrow_array;
+    pub use arrow_buffer;
     pub use arrow_schema;
     pub use chrono;
     pub use genawaiter;
+    pub use half;
     pub use lazy_static;
     #[cfg(feature = "global_registry")]
     pub use linkme;
     pub use rust_decimal;
     pub use serde_json;
+
+    use arrow_array::types::{Int16Type, Int32Type, Int64Type, RunEndIndexType};
+    use arrow_array::{
+        make_array, new_null_array, Array, ArrayRef, ListArray, RecordBatch, RunArray, StructArray,
+        UInt32Array,
+    };
+    use arrow_buffer::{ArrowNativeType, NullBuffer, OffsetBuffer};
+    use arrow_data::transform::MutableArrayData;
+    use arrow_schema::{DataType, Field, Fields, Schema};
+    use std::sync::Arc;
+
+    /// The number of rows between two checks of the cancellation token.
+    const CANCEL_CHECK_ROWS: usize = 1024;
+
+    /// Returns the cancellation token of the current context, if any.
+    pub fn cancellation_token() -> Option<crate::CancellationToken> {
+        crate::context::current_cancellation_token()
+    }
+
+    /// Returns an error if the token is cancelled. Only checks every `CANCEL_CHECK_ROWS` rows.
+    // `usize::is_multiple_of` requires Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    #[inline]
+    pub fn check_cancelled(
+        token: Option<&crate::CancellationToken>,
+        row: usize,
+    ) -> crate::Result<()> {
+        match token {
+            Some(token) if row % CANCEL_CHECK_ROWS == 0 => token.check(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Converts the payload of a panic in the `row`-th row to an error.
+    pub fn panic_error(payload: Box<dyn std::any::Any + Send>, row: usize) -> crate::Error {
+        crate::Error::ExternalError(Box::new(crate::PanicError::new(payload, row)))
+    }
+
+    /// Checks that the output of the batch function `name` has `num_rows` rows.
+    ///
+    /// If `broadcast` is set, an output of a single row is repeated `num_rows` times.
+    pub fn check_output_len(
+        array: ArrayRef,
+        num_rows: usize,
+        broadcast: bool,
+        name: &str,
+    ) -> crate::Result<ArrayRef> {
+        if array.len() == num_rows {
+            return Ok(array);
+        }
+        if broadcast && array.len() == 1 {
+            return crate::eval::broadcast(array.as_ref(), num_rows);
+        }
+        Err(crate::Error::InvalidArgumentError(format!(
+            "expect {num_rows} rows in the output of {name}, got {}",
+            array.len()
+        )))
+    }
+
+    /// Downcasts the `index`-th argument to a struct array with the given fields.
+    pub fn downcast_struct<'a>(
+        array: &'a dyn Array,
+        fields: &Fields,
+        index: usize,
+    ) -> crate::Result<&'a StructArray> {
+        match array.as_any().downcast_ref::<StructArray>() {
+            Some(array)
+                if array
+                    .fields()
+                    .iter()
+                    .map(|f| f.data_type())
+                    .eq(fields.iter().map(|f| f.data_type())) =>
+            {
+                Ok(array)
+            }
+            _ => Err(crate::Error::CastError(format!(
+                "expect {} for the {index}-th argument, got {}",
+                DataType::Struct(fields.clone()),
+                array.data_type()
+            ))),
+        }
+    }
+
+    /// Combines the columns of the input into a single struct column with the given fields.
+    ///
+    /// Used by functions with `flatten_input`, which take the fields of a struct as columns.
+    pub fn unflatten_struct(input: &RecordBatch, fields: Fields) -> crate::Result<RecordBatch> {
+        if input.num_columns() != fields.len() {
+            return Err(crate::Error::InvalidArgumentError(format!(
+                "expect {} columns for the fields of the struct argument, got {}",
+                fields.len(),
+                input.num_columns()
+            )));
+        }
+        let array = StructArray::try_new(fields.clone(), input.columns().to_vec(), None)?;
+        let field = Field::new("struct", DataType::Struct(fields), true);
+        let schema = Arc::new(Schema::new(vec![field]));
+        RecordBatch::try_new(schema, vec![Arc::new(array)])
+    }
+
+    /// Returns the argument types of a function taking the fields of a struct as columns.
+    #[cfg(feature = "global_registry")]
+    pub fn flatten_struct_types(fields: &Fields) -> Vec<crate::sig::SigDataType> {
+        fields
+            .iter()
+            .map(|field| {
+                let storage = field.data_type().clone();
+                match field.metadata().get(crate::types::EXTENSION_KEY) {
+                    Some(name) => crate::sig::SigDataType::Extension {
+                        name: name.clone(),
+                        storage,
+                    },
+                    None => crate::sig::SigDataType::Exact(storage),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the first of `num_rows` non-null rows where `f` panics, or 0 if none panics.
+    ///
+    /// Used to locate the panicking row of vectorized functions. Null rows are skipped, since
+    /// vectorized kernels also call the function on the arbitrary values of null slots.
+    pub fn find_panic_row(num_rows: usize, nulls: Option<&NullBuffer>, f: impl Fn(usize)) -> usize {
+        (0..num_rows)
+            .filter(|&i| !matches!(nulls, Some(nulls) if nulls.is_null(i)))
+            .find(|&i| std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(i))).is_err())
+            .unwrap_or(0)
+    }
+
+    /// Holds the context of a function, and calls the `finish` hook on it when dropped.
+    pub struct ContextGuard<T> {
+        context: Option<T>,
+        finish: Option<fn(T)>,
+    }
+
+    impl<T> ContextGuard<T> {
+        pub fn new(context: T, finish: Option<fn(T)>) -> Self {
+            Self {
+                context: Some(context),
+                finish,
+            }
+        }
+
+        pub fn get(&self) -> &T {
+            self.context.as_ref().expect("context is finished")
+        }
+    }
+
+    impl<T> Drop for ContextGuard<T> {
+        fn drop(&mut self) {
+            if let (Some(context), Some(finish)) = (self.context.take(), self.finish) {
+                finish(context);
+            }
+        }
+    }
+
+    /// Evaluates a function on an input with run-end encoded columns.
+    ///
+    /// If all columns are run-end encoded with the same runs and `per_run` is true, the function
+    /// is evaluated once per run and the output is expanded to the rows of the input. Otherwise,
+    /// run-end encoded columns are decoded before evaluation.
+    ///
+    /// Returns `None` if no column is run-end encoded.
+    pub fn eval_run_end_encoded(
+        input: &RecordBatch,
+        per_run: bool,
+        eval: &dyn Fn(&RecordBatch) -> crate::Result<RecordBatch>,
+    ) -> crate::Result<Option<RecordBatch>> {
+        let runs = input
+            .columns()
+            .iter()
+            .map(|c| runs(c.as_ref()))
+            .collect::<Vec<_>>();
+        if runs.iter().all(|r| r.is_none()) {
+            return Ok(None);
+        }
+        let schema = input.schema();
+        let fields = schema.fields().iter().zip(&runs).map(|(field, runs)| {
+            let Some((_, values)) = runs else {
+                return field.clone();
+            };
+            let field = Field::new(field.name(), values.data_type().clone(), true)
+                .with_metadata(field.metadata().clone());
+            Arc::new(field)
+        });
+        let schema = Arc::new(Schema::new(fields.collect::<Vec<_>>()));
+
+        let ends = runs[0].as_ref().map(|(ends, _)| ends);
+        if per_run
+            && runs
+                .iter()
+                .all(|r| r.as_ref().map(|(ends, _)| ends) == ends)
+        {
+            // evaluate on the values of runs
+            let indices = expand_runs(ends.unwrap());
+            let columns = runs.into_iter().map(|r| r.unwrap().1).collect();
+            let output = eval(&RecordBatch::try_new(schema, columns)?)?;
+            let columns = output
+                .columns()
+                .iter()
+                .map(|c| arrow_select::take::take(c, &indices, None))
+                .collect::<crate::Result<Vec<_>>>()?;
+            return Ok(Some(RecordBatch::try_new(output.schema(), columns)?));
+        }
+        // decode the run-end encoded columns
+        let columns = input
+            .columns()
+            .iter()
+            .zip(runs)
+            .map(|(column, runs)| match runs {
+                Some((ends, values)) => {
+                    arrow_select::take::take(&values, &expand_runs(&ends), None)
+                }
+                None => Ok(column.clone()),
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        eval(&RecordBatch::try_new(schema, columns)?).map(Some)
+    }
+
+    /// Returns the logical end of each run and the values if the array is run-end encoded.
+    fn runs(array: &dyn Array) -> Option<(Vec<usize>, ArrayRef)> {
+        fn runs_of<R: RunEndIndexType>(array: &dyn Array) -> (Vec<usize>, ArrayRef) {
+            let array = array.as_any().downcast_ref::<RunArray<R>>().unwrap();
+            if array.is_empty() {
+                return (vec![], array.values().slice(0, 0));
+            }
+            let run_ends = array.run_ends();
+            let start = run_ends.get_start_physical_index();
+            let end = run_ends.get_end_physical_index();
+            let ends = run_ends.values()[start..=end]
+                .iter()
+                .map(|e| (e.as_usize() - run_ends.offset()).min(run_ends.len()))
+                .collect();
+            (ends, array.values().slice(start, end - start + 1))
+        }
+        let DataType::RunEndEncoded(run_ends, _) = array.data_type() else {
+            return None;
+        };
+        match run_ends.data_type() {
+            DataType::Int16 => Some(runs_of::<Int16Type>(array)),
+            DataType::Int32 => Some(runs_of::<Int32Type>(array)),
+            DataType::Int64 => Some(runs_of::<Int64Type>(array)),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the run for each row.
+    fn expand_runs(ends: &[usize]) -> UInt32Array {
+        let mut indices = Vec::with_capacity(ends.last().copied().unwrap_or(0));
+        for (i, &end) in ends.iter().enumerate() {
+            indices.resize(end, i as u32);
+        }
+        indices.into()
+    }
+
+    /// Builds an array of type `data_type` from optional arrays of length 1.
+    ///
+    /// This is used to build the output of functions returning `any`.
+    pub fn concat_scalars(
+        data_type: &DataType,
+        values: &[Option<ArrayRef>],
+    ) -> crate::Result<ArrayRef> {
+        if let Some(v) = values.iter().flatten().find(|v| v.len() != 1) {
+            return Err(crate::Error::InvalidArgumentError(format!(
+                "expect an array of length 1, got {}",
+                v.len()
+            )));
+        }
+        concat(data_type, values)
+    }
+
+    /// Builds a list array of type `data_type` from optional arrays of list items.
+    ///
+    /// This is used to build the output of functions returning `anyarray`.
+    pub fn concat_lists(
+        data_type: &DataType,
+        values: &[Option<ArrayRef>],
+    ) -> crate::Result<ArrayRef> {
+        let DataType::List(field) = data_type else {
+            return Err(crate::Error::InvalidArgumentError(format!(
+                "expect list type, got {data_type}"
+            )));
+        };
+        let lengths = values.iter().map(|v| v.as_ref().map_or(0, |v| v.len()));
+        let offsets = OffsetBuffer::from_lengths(lengths);
+        let nulls = NullBuffer::from_iter(values.iter().map(|v| v.is_some()));
+        let items: Vec<_> = values.iter().flatten().cloned().map(Some).collect();
+        let items = match items.is_empty() {
+            true => new_null_array(field.data_type(), 0),
+            false => concat(field.data_type(), &items)?,
+        };
+        Ok(Arc::new(ListArray::try_new(
+            field.clone(),
+            offsets,
+            items,
+            Some(nulls),
+        )?))
+    }
+
+    /// Concatenates the arrays of type `data_type`, and appends a null for each `None`.
+    fn concat(data_type: &DataType, values: &[Option<ArrayRef>]) -> crate::Result<ArrayRef> {
+        let datas = values
+            .iter()
+            .flatten()
+            .map(|v| v.to_data())
+            .collect::<Vec<_>>();
+        if let Some(data) = datas.iter().find(|d| d.data_type() != data_type) {
+            return Err(crate::Error::InvalidArgumentError(format!(
+                "expect {data_type}, got {}",
+                data.data_type()
+            )));
+        }
+        if datas.is_empty() {
+            return Ok(new_null_array(data_type, values.len()));
+        }
+        let len = datas.iter().map(|d| d.len()).sum::<usize>() + values.len() - datas.len();
+        let mut builder = MutableArrayData::new(datas.iter().collect(), true, len);
+        let mut index = 0;
+        for value in values {
+            match value {
+                Some(v) => {
+                    builder.extend(index, 0, v.len());
+                    index += 1;
+                }
+                None => builder.extend_nulls(1),
+            }
+        }
+        Ok(make_array(builder.freeze()))
+    }
 }
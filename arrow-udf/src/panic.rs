@@ -0,0 +1,72 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Errors of panicking functions.
+
+use std::any::Any;
+use std::fmt;
+
+/// The error of a function that panicked.
+///
+/// Functions defined by `#[function]` catch panics of each row, and return an
+/// [`Error::ExternalError`](crate::Error::ExternalError) wrapping this error instead of unwinding
+/// into the caller:
+///
+/// ```
+/// use arrow_udf::{Error, PanicError};
+///
+/// fn row_of(error: &Error) -> Option<usize> {
+///     match error {
+///         Error::ExternalError(e) => Some(e.downcast_ref::<PanicError>()?.row()),
+///         _ => None,
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicError {
+    row: usize,
+    message: String,
+}
+
+impl PanicError {
+    /// Creates an error from the payload of a panic in the `row`-th row.
+    pub fn new(payload: Box<dyn Any + Send>, row: usize) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(s) => *s,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(s) => s.to_string(),
+                Err(_) => "Box<dyn Any>".to_string(),
+            },
+        };
+        Self { row, message }
+    }
+
+    /// Returns the index of the row in the input batch.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Returns the panic message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "function panicked at row {}: {}", self.row, self.message)
+    }
+}
+
+impl std::error::Error for PanicError {}
@@ -0,0 +1,250 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Record and replay function calls for debugging.
+//!
+//! A [`Recorder`] captures the input and the output or error of every call of a function to
+//! Arrow IPC files in a directory. The calls can be replayed later by [`replay`] against a
+//! (possibly modified) implementation to find the batches whose results differ.
+//!
+//! The functions can be of any runtime, as long as they map a record batch to a record batch.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use arrow_array::{Int32Array, RecordBatch};
+//! # use arrow_schema::{DataType, Field, Schema};
+//! use arrow_udf::{function, replay::{replay, Recorder}};
+//!
+//! #[function("neg(int) -> int", output = "eval_neg")]
+//! fn neg(x: i32) -> i32 {
+//!     -x
+//! }
+//!
+//! #[function("neg_buggy(int) -> int", output = "eval_neg_buggy")]
+//! fn neg_buggy(x: i32) -> i32 {
+//!     x.min(0)
+//! }
+//!
+//! # let dir = std::env::temp_dir().join(format!("arrow-udf-replay-doc-{}", std::process::id()));
+//! let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+//! let array = Int32Array::from(vec![1, -2, 3]);
+//! let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+//!
+//! // capture calls in production
+//! let recorder = Recorder::new(&dir).unwrap();
+//! recorder.call(&input, eval_neg).unwrap();
+//!
+//! // replay them against another implementation
+//! let mismatches = replay(&dir, eval_neg_buggy).unwrap();
+//! assert_eq!(mismatches.len(), 1);
+//! assert_eq!(mismatches[0].rows, vec![0, 1, 2]);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use crate::{Error, Result};
+use arrow_array::{Array, RecordBatch};
+use arrow_ipc::{reader::FileReader, writer::FileWriter};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The suffix of files of input batches.
+const INPUT_SUFFIX: &str = ".input.arrow";
+/// The suffix of files of output batches.
+const OUTPUT_SUFFIX: &str = ".output.arrow";
+/// The suffix of files of error messages.
+const ERROR_SUFFIX: &str = ".error.txt";
+
+/// Captures the calls of a function to a directory.
+///
+/// Each call is saved as `{index}.input.arrow` and either `{index}.output.arrow` or
+/// `{index}.error.txt`. The recorder can be shared by multiple threads.
+#[derive(Debug)]
+pub struct Recorder {
+    dir: PathBuf,
+    next: AtomicU64,
+}
+
+impl Recorder {
+    /// Creates a recorder writing to `dir`, which is created if it does not exist.
+    ///
+    /// If the directory already contains calls, new calls are appended after them.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let next = match list(&dir)?.last() {
+            Some(index) => index + 1,
+            None => 0,
+        };
+        Ok(Self {
+            dir,
+            next: AtomicU64::new(next),
+        })
+    }
+
+    /// Evaluates the function on the input and records the call.
+    ///
+    /// Returns the result of the function. An error of writing the files is returned only if
+    /// the function succeeds.
+    pub fn call<F>(&self, input: &RecordBatch, f: F) -> Result<RecordBatch>
+    where
+        F: FnOnce(&RecordBatch) -> Result<RecordBatch>,
+    {
+        let output = f(input);
+        let recorded = self.record(input, &output);
+        let output = output?;
+        recorded?;
+        Ok(output)
+    }
+
+    /// Records a call with its input and result.
+    pub fn record(&self, input: &RecordBatch, output: &Result<RecordBatch>) -> Result<()> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        let path = |suffix: &str| self.dir.join(format!("{index:08}{suffix}"));
+        match output {
+            Ok(output) => write_batch(&path(OUTPUT_SUFFIX), output)?,
+            Err(e) => std::fs::write(path(ERROR_SUFFIX), e.to_string())?,
+        }
+        // the input is written last, so that a call is only listed when it is complete
+        write_batch(&path(INPUT_SUFFIX), input)
+    }
+}
+
+/// A recorded call.
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// The index of the call.
+    pub index: u64,
+    /// The input batch.
+    pub input: RecordBatch,
+    /// The output batch, or the error message.
+    pub output: std::result::Result<RecordBatch, String>,
+}
+
+/// Loads the calls recorded in `dir`, in the order they were recorded.
+pub fn load(dir: impl AsRef<Path>) -> Result<Vec<Record>> {
+    let dir = dir.as_ref();
+    let mut records = vec![];
+    for index in list(dir)? {
+        let path = |suffix: &str| dir.join(format!("{index:08}{suffix}"));
+        let input = read_batch(&path(INPUT_SUFFIX))?;
+        let output = match std::fs::read_to_string(path(ERROR_SUFFIX)) {
+            Ok(error) => Err(error),
+            Err(_) => Ok(read_batch(&path(OUTPUT_SUFFIX))?),
+        };
+        records.push(Record {
+            index,
+            input,
+            output,
+        });
+    }
+    Ok(records)
+}
+
+/// A call whose replayed result differs from the recorded one.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    /// The recorded call.
+    pub record: Record,
+    /// The result of the replay, or the error message.
+    pub actual: std::result::Result<RecordBatch, String>,
+    /// The indexes of rows whose values differ.
+    ///
+    /// It is empty if the column types or the numbers of rows differ, or one of the results is
+    /// an error.
+    pub rows: Vec<usize>,
+}
+
+/// Replays the calls recorded in `dir` and returns the calls whose results differ.
+///
+/// Errors are compared by their messages.
+pub fn replay<F>(dir: impl AsRef<Path>, f: F) -> Result<Vec<Mismatch>>
+where
+    F: Fn(&RecordBatch) -> Result<RecordBatch>,
+{
+    let mut mismatches = vec![];
+    for record in load(dir)? {
+        let actual = f(&record.input).map_err(|e| e.to_string());
+        let rows = match (&record.output, &actual) {
+            (Ok(expected), Ok(actual)) if expected == actual => continue,
+            (Err(expected), Err(actual)) if expected == actual => continue,
+            (Ok(expected), Ok(actual)) if same_shape(expected, actual) => {
+                diff_rows(expected, actual)
+            }
+            _ => vec![],
+        };
+        mismatches.push(Mismatch {
+            record,
+            actual,
+            rows,
+        });
+    }
+    Ok(mismatches)
+}
+
+/// Returns whether two batches have the same column types and number of rows.
+fn same_shape(a: &RecordBatch, b: &RecordBatch) -> bool {
+    a.num_rows() == b.num_rows()
+        && a.num_columns() == b.num_columns()
+        && (a.columns().iter())
+            .zip(b.columns())
+            .all(|(a, b)| a.data_type() == b.data_type())
+}
+
+/// Returns the indexes of rows that differ in two batches of the same shape.
+fn diff_rows(expected: &RecordBatch, actual: &RecordBatch) -> Vec<usize> {
+    (0..expected.num_rows())
+        .filter(|&i| {
+            expected
+                .columns()
+                .iter()
+                .zip(actual.columns())
+                .any(|(a, b)| &a.slice(i, 1) as &dyn Array != &b.slice(i, 1) as &dyn Array)
+        })
+        .collect()
+}
+
+/// Returns the sorted indexes of the calls in the directory.
+fn list(dir: &Path) -> Result<Vec<u64>> {
+    let mut indexes = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let Some(index) = name.to_str().and_then(|s| s.strip_suffix(INPUT_SUFFIX)) else {
+            continue;
+        };
+        if let Ok(index) = index.parse() {
+            indexes.push(index);
+        }
+    }
+    indexes.sort_unstable();
+    Ok(indexes)
+}
+
+/// Writes a batch to an Arrow IPC file.
+fn write_batch(path: &Path, batch: &RecordBatch) -> Result<()> {
+    let mut writer = FileWriter::try_new(File::create(path)?, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()
+}
+
+/// Reads a batch from an Arrow IPC file.
+fn read_batch(path: &Path) -> Result<RecordBatch> {
+    let mut reader = FileReader::try_new(File::open(path)?, None)?;
+    reader
+        .next()
+        .transpose()?
+        .ok_or_else(|| Error::IpcError(format!("no record batch in file: {}", path.display())))
+}
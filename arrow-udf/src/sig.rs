@@ -29,16 +29,50 @@
 //! // lookup the function by name and types
 //! let sig = REGISTRY.get("add", &[Int32, Int32], &Int32).unwrap();
 //! ```
+//!
+//! Functions defined in a schema, like `#[function("my_schema.add(int, int) -> int")]`,
+//! are looked up by the qualified name `my_schema.add`. Aliases defined by the `alias` option
+//! are registered as separate signatures with the same function.
+//!
+//! Functions can have multiple versions registered side by side. The latest version is returned
+//! by default, and a specific version can be pinned with [`FunctionRegistry::get_version`].
+//!
+//! A scalar function can have a fallback defined by the `fallback` option, which is used when
+//! the function fails. Use [`FunctionSignature::fallback_function`] to evaluate it with the
+//! fallback.
+//!
+//! Functions with polymorphic types like `any` match any argument types. Their actual return
+//! type can be inferred from the argument types by [`FunctionSignature::type_infer`].
+//!
+//! Functions with extension types like `json` can only be found by fields with the extension
+//! name in their metadata. Use [`FunctionRegistry::get_by_fields`] to lookup them.
 
-use super::{ScalarFunction, TableFunction};
-use arrow_schema::DataType;
+use super::{
+    Error, Result, ScalarFunction, ScalarFunctionInit, ScalarValueFunction, TableFunction,
+};
+use crate::eval::{CachedFunction, FallbackFunction, RoutedFunction};
+use crate::sql::TypeAliases;
+use crate::types::EXTENSION_KEY;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, FieldRef, Schema};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use crate::eval::DynScalarFunction;
 
 /// A function signature.
 pub struct FunctionSignature {
-    /// The name of the function.
+    /// The schema of the function, if it is defined with a qualified name like `schema.name`.
+    pub schema: Option<String>,
+
+    /// The name of the function, without the schema.
     pub name: String,
 
+    /// The version of the function. The default is 1.
+    ///
+    /// Multiple versions of the same function can be registered side by side.
+    pub version: u32,
+
     /// The argument types.
     pub arg_types: Vec<SigDataType>,
 
@@ -48,20 +82,66 @@ pub struct FunctionSignature {
     /// The return type.
     pub return_type: SigDataType,
 
+    /// Infers the actual return type from the argument types.
+    ///
+    /// This is useful for functions with polymorphic return type like `any`,
+    /// whose return type depends on the argument types.
+    pub type_infer: TypeInferFn,
+
+    /// Whether the function may return different results for the same arguments.
+    pub volatile: bool,
+
+    /// The runtime implementing the function, like `python` or `javascript`.
+    ///
+    /// It is `rust` for functions defined by `#[function]`. The same function can be registered
+    /// by multiple runtimes, and [`FunctionRegistry::routed_function`] routes calls to the fastest.
+    pub runtime: String,
+
     /// The function
     pub function: FunctionKind,
+
+    /// The function that evaluates on scalar values.
+    ///
+    /// This is only available for scalar functions that are not variadic and
+    /// don't use a customized batch function.
+    pub eval_scalar: Option<ScalarValueFunction>,
+
+    /// Creates the function with a context shared by all calls.
+    ///
+    /// This is only available for scalar functions with an `init` hook.
+    /// Calling [`FunctionKind::Scalar`] directly creates a new context for each call.
+    pub init: Option<ScalarFunctionInit>,
+
+    /// The function to use when this function fails.
+    ///
+    /// This is only available for scalar functions with the `fallback` option.
+    pub fallback: Option<Fallback>,
+}
+
+/// Infers the return type of a function from the argument types.
+pub type TypeInferFn = Box<dyn Fn(&[DataType]) -> Result<DataType> + Send + Sync>;
+
+/// The fallback of a scalar function.
+#[derive(Debug, Clone, Copy)]
+pub struct Fallback {
+    /// The fallback function with the same signature.
+    pub function: ScalarFunction,
+    /// The number of consecutive failures after which only the fallback is called.
+    pub max_failures: u32,
 }
 
 /// Function pointer.
 pub enum FunctionKind {
     Scalar(ScalarFunction),
     Table(TableFunction),
+    /// A scalar function defined by a closure, e.g. by [`FunctionRegistry::register_scalar`].
+    DynScalar(DynScalarFunction),
 }
 
 impl FunctionKind {
     /// Check if the function is a scalar function.
     pub fn is_scalar(&self) -> bool {
-        matches!(self, Self::Scalar(_))
+        matches!(self, Self::Scalar(_) | Self::DynScalar(_))
     }
 
     /// Check if the function is a table function.
@@ -84,6 +164,231 @@ impl FunctionKind {
             _ => None,
         }
     }
+
+    /// Evaluates a scalar function on the input, whether it is a function pointer or a closure.
+    ///
+    /// Returns an error for table functions.
+    pub fn eval(&self, input: &RecordBatch) -> Result<RecordBatch> {
+        match self {
+            Self::Scalar(f) => f(input),
+            Self::DynScalar(f) => f(input),
+            Self::Table(_) => Err(Error::InvalidArgumentError(
+                "can not evaluate a table function as a scalar function".into(),
+            )),
+        }
+    }
+}
+
+/// A builder of [`FunctionSignature`] for functions defined at runtime.
+///
+/// # Example
+///
+/// ```
+/// # use arrow_array::RecordBatch;
+/// use arrow_schema::DataType;
+/// use arrow_udf::sig::FunctionSignature;
+///
+/// fn identity(input: &RecordBatch) -> arrow_udf::Result<RecordBatch> {
+///     Ok(input.clone())
+/// }
+///
+/// let sig = FunctionSignature::builder()
+///     .name("identity")
+///     .arg(DataType::Int32)
+///     .returns(DataType::Int32)
+///     .scalar(identity)
+///     .build()
+///     .unwrap();
+/// assert_eq!((sig.type_infer)(&[DataType::Int32]).unwrap(), DataType::Int32);
+/// ```
+#[derive(Default)]
+pub struct FunctionSignatureBuilder {
+    schema: Option<String>,
+    name: Option<String>,
+    version: Option<u32>,
+    arg_types: Vec<SigDataType>,
+    variadic: bool,
+    return_type: Option<SigDataType>,
+    type_infer: Option<TypeInferFn>,
+    volatile: bool,
+    runtime: Option<String>,
+    function: Option<FunctionKind>,
+    eval_scalar: Option<ScalarValueFunction>,
+    init: Option<ScalarFunctionInit>,
+    fallback: Option<Fallback>,
+}
+
+impl FunctionSignatureBuilder {
+    /// Sets the name of the function. A qualified name like `schema.name` also sets the schema.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        match name.split_once('.') {
+            Some((schema, name)) => {
+                self.schema = Some(schema.into());
+                self.name = Some(name.into());
+            }
+            None => self.name = Some(name),
+        }
+        self
+    }
+
+    /// Sets the version of the function. The default is 1.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Appends an argument type.
+    pub fn arg(mut self, ty: impl Into<SigDataType>) -> Self {
+        self.arg_types.push(ty.into());
+        self
+    }
+
+    /// Appends argument types.
+    pub fn args(mut self, types: impl IntoIterator<Item = impl Into<SigDataType>>) -> Self {
+        self.arg_types.extend(types.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether the function accepts any number of arguments after the declared ones.
+    pub fn variadic(mut self, variadic: bool) -> Self {
+        self.variadic = variadic;
+        self
+    }
+
+    /// Sets the return type.
+    pub fn returns(mut self, ty: impl Into<SigDataType>) -> Self {
+        self.return_type = Some(ty.into());
+        self
+    }
+
+    /// Sets the function to infer the return type from the argument types.
+    ///
+    /// It is required for polymorphic return types. For exact return types, the default
+    /// returns the return type.
+    pub fn type_infer(
+        mut self,
+        f: impl Fn(&[DataType]) -> Result<DataType> + Send + Sync + 'static,
+    ) -> Self {
+        self.type_infer = Some(Box::new(f));
+        self
+    }
+
+    /// Sets whether the function may return different results for the same arguments.
+    pub fn volatile(mut self, volatile: bool) -> Self {
+        self.volatile = volatile;
+        self
+    }
+
+    /// Sets the runtime implementing the function. The default is `rust`.
+    pub fn runtime(mut self, runtime: impl Into<String>) -> Self {
+        self.runtime = Some(runtime.into());
+        self
+    }
+
+    /// Sets a scalar function.
+    pub fn scalar(mut self, function: ScalarFunction) -> Self {
+        self.function = Some(FunctionKind::Scalar(function));
+        self
+    }
+
+    /// Sets a scalar function defined by a closure.
+    pub fn dyn_scalar(
+        mut self,
+        function: impl Fn(&RecordBatch) -> Result<RecordBatch> + Send + Sync + 'static,
+    ) -> Self {
+        self.function = Some(FunctionKind::DynScalar(Arc::new(function)));
+        self
+    }
+
+    /// Sets a table function.
+    pub fn table(mut self, function: TableFunction) -> Self {
+        self.function = Some(FunctionKind::Table(function));
+        self
+    }
+
+    /// Sets the function that evaluates on scalar values.
+    pub fn eval_scalar(mut self, function: ScalarValueFunction) -> Self {
+        self.eval_scalar = Some(function);
+        self
+    }
+
+    /// Sets the function to create the function with a shared context.
+    pub fn init(mut self, init: ScalarFunctionInit) -> Self {
+        self.init = Some(init);
+        self
+    }
+
+    /// Sets the function to use after `max_failures` consecutive failures.
+    pub fn fallback(mut self, function: ScalarFunction, max_failures: u32) -> Self {
+        self.fallback = Some(Fallback {
+            function,
+            max_failures,
+        });
+        self
+    }
+
+    /// Builds the signature.
+    ///
+    /// Returns an error if the name, return type or function is missing, or if the options
+    /// are inconsistent.
+    pub fn build(self) -> Result<FunctionSignature> {
+        let invalid = |msg: String| Err(Error::InvalidArgumentError(msg));
+        let Some(name) = self.name else {
+            return invalid("function name is required".into());
+        };
+        let is_identifier = |s: &str| !s.is_empty() && !s.contains('.');
+        let valid_schema = match &self.schema {
+            Some(schema) => is_identifier(schema),
+            None => true,
+        };
+        if !is_identifier(&name) || !valid_schema {
+            return invalid(format!("invalid function name: {name:?}"));
+        }
+        let Some(return_type) = self.return_type else {
+            return invalid(format!("return type of function {name} is required"));
+        };
+        let Some(function) = self.function else {
+            return invalid(format!("function {name} has no implementation"));
+        };
+        if self.version == Some(0) {
+            return invalid(format!("version of function {name} must be positive"));
+        }
+        if !function.is_scalar()
+            && (self.eval_scalar.is_some() || self.init.is_some() || self.fallback.is_some())
+        {
+            return invalid(format!(
+                "eval_scalar, init and fallback are only supported for scalar function {name}"
+            ));
+        }
+        let type_infer = match (self.type_infer, &return_type) {
+            (Some(f), _) => f,
+            (None, SigDataType::Exact(ty) | SigDataType::Extension { storage: ty, .. }) => {
+                let ty = ty.clone();
+                Box::new(move |_: &[DataType]| Ok(ty.clone()))
+            }
+            (None, _) => {
+                return invalid(format!(
+                    "type inference is required for polymorphic return type of function {name}"
+                ))
+            }
+        };
+        Ok(FunctionSignature {
+            schema: self.schema,
+            name,
+            version: self.version.unwrap_or(1),
+            arg_types: self.arg_types,
+            variadic: self.variadic,
+            return_type,
+            type_infer,
+            volatile: self.volatile,
+            runtime: self.runtime.unwrap_or_else(|| "rust".into()),
+            function,
+            eval_scalar: self.eval_scalar,
+            init: self.init,
+            fallback: self.fallback,
+        })
+    }
 }
 
 /// An extended data type that can be used to declare a function's argument or result type.
@@ -91,11 +396,63 @@ impl FunctionKind {
 pub enum SigDataType {
     /// Exact data type
     Exact(DataType),
+    /// Extension type with the given name and storage type
+    Extension { name: String, storage: DataType },
     /// Accepts any data type
     Any,
+    /// Accepts any list type
+    AnyList,
 }
 
 impl FunctionSignature {
+    /// Returns a builder to define a function at runtime.
+    pub fn builder() -> FunctionSignatureBuilder {
+        FunctionSignatureBuilder::default()
+    }
+
+    /// Returns the name qualified by the schema, like `schema.name`.
+    pub fn qualified_name(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!("{schema}.{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Check if the function has the name, which is qualified like `schema.name` if the function
+    /// is in a schema.
+    pub fn has_name(&self, name: &str) -> bool {
+        match name.split_once('.') {
+            Some((schema, name)) => self.schema.as_deref() == Some(schema) && self.name == name,
+            None => self.schema.is_none() && self.name == name,
+        }
+    }
+
+    /// Returns the scalar function wrapped with its fallback.
+    ///
+    /// Returns `None` if the function is not a scalar function or has no fallback.
+    /// The returned function keeps the count of failures, so it should be reused across batches.
+    pub fn fallback_function(&self) -> Option<FallbackFunction<ScalarFunction, ScalarFunction>> {
+        let function = self.function.as_scalar()?;
+        let fallback = self.fallback?;
+        Some(FallbackFunction::new(
+            function,
+            fallback.function,
+            fallback.max_failures,
+        ))
+    }
+
+    /// Returns the scalar function wrapped with a cache of up to `capacity` rows.
+    ///
+    /// Returns `None` if the function is not a scalar function or is volatile.
+    /// The returned function owns the cache, so it should be reused across batches.
+    pub fn cached_function(&self, capacity: usize) -> Option<CachedFunction<ScalarFunction>> {
+        if self.volatile {
+            return None;
+        }
+        let function = self.function.as_scalar()?;
+        Some(CachedFunction::new(function, capacity))
+    }
+
     /// Check if the function signature matches the given argument types and return type.
     fn matches(&self, arg_types: &[DataType], return_type: &DataType) -> bool {
         if !self.return_type.matches(return_type) {
@@ -115,6 +472,28 @@ impl FunctionSignature {
             arg_types.len() == self.arg_types.len()
         }
     }
+
+    /// Check if the function signature matches the given argument fields and return field.
+    fn matches_fields(&self, arg_fields: &[FieldRef], return_field: &Field) -> bool {
+        self.return_type.matches_field(return_field) && self.matches_arg_fields(arg_fields)
+    }
+
+    /// Check if the function signature matches the given argument fields.
+    pub(crate) fn matches_arg_fields(&self, arg_fields: &[FieldRef]) -> bool {
+        if arg_fields.len() < self.arg_types.len() {
+            return false;
+        }
+        for (target, field) in self.arg_types.iter().zip(arg_fields) {
+            if !target.matches_field(field) {
+                return false;
+            }
+        }
+        if self.variadic {
+            true
+        } else {
+            arg_fields.len() == self.arg_types.len()
+        }
+    }
 }
 
 impl SigDataType {
@@ -122,7 +501,22 @@ impl SigDataType {
     fn matches(&self, data_type: &DataType) -> bool {
         match self {
             Self::Exact(ty) => ty == data_type,
+            Self::Extension { .. } => false,
             Self::Any => true,
+            Self::AnyList => matches!(data_type, DataType::List(_)),
+        }
+    }
+
+    /// Check if the field matches the signature data type.
+    fn matches_field(&self, field: &Field) -> bool {
+        let extension = field.metadata().get(EXTENSION_KEY);
+        match self {
+            Self::Exact(ty) => extension.is_none() && ty == field.data_type(),
+            Self::Extension { name, storage } => {
+                extension == Some(name) && storage == field.data_type()
+            }
+            Self::Any => true,
+            Self::AnyList => matches!(field.data_type(), DataType::List(_)),
         }
     }
 }
@@ -144,7 +538,7 @@ lazy_static::lazy_static! {
         let mut signatures = HashMap::<String, Vec<FunctionSignature>>::new();
         for sig in SIGNATURES {
             let sig = sig();
-            signatures.entry(sig.name.clone()).or_default().push(sig);
+            signatures.entry(sig.qualified_name()).or_default().push(sig);
         }
         FunctionRegistry { signatures }
     };
@@ -153,11 +547,96 @@ lazy_static::lazy_static! {
 /// Function registry.
 #[derive(Default)]
 pub struct FunctionRegistry {
+    /// Signatures indexed by the qualified name.
     signatures: HashMap<String, Vec<FunctionSignature>>,
 }
 
 impl FunctionRegistry {
+    /// Registers a function signature.
+    ///
+    /// Signatures of the same name are kept side by side, like multiple versions of a function.
+    pub fn register(&mut self, sig: FunctionSignature) {
+        self.signatures
+            .entry(sig.qualified_name())
+            .or_default()
+            .push(sig);
+    }
+
+    /// Registers a closure as a scalar function.
+    ///
+    /// The closure returns the output column, which is named after the function. This is useful
+    /// to wrap existing compute kernels without defining functions by `#[function]`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use arrow_array::{Int32Array, RecordBatch};
+    /// # use arrow_schema::{DataType, Field, Schema};
+    /// use arrow_udf::sig::FunctionRegistry;
+    ///
+    /// let mut registry = FunctionRegistry::default();
+    /// registry
+    ///     .register_scalar("add", &[DataType::Int32, DataType::Int32], DataType::Int32, |input| {
+    ///         Ok(arrow_arith::numeric::add(input.column(0), input.column(1))?)
+    ///     })
+    ///     .unwrap();
+    ///
+    /// # let schema = Schema::new(vec![
+    /// #     Field::new("a", DataType::Int32, true),
+    /// #     Field::new("b", DataType::Int32, true),
+    /// # ]);
+    /// # let input = RecordBatch::try_new(
+    /// #     Arc::new(schema),
+    /// #     vec![Arc::new(Int32Array::from(vec![1, 2])), Arc::new(Int32Array::from(vec![3, 4]))],
+    /// # ).unwrap();
+    /// let sig = registry.get("add", &[DataType::Int32, DataType::Int32], &DataType::Int32).unwrap();
+    /// let output = sig.function.eval(&input).unwrap();
+    /// assert_eq!(output.column(0).as_ref(), &Int32Array::from(vec![4, 6]));
+    /// ```
+    pub fn register_scalar(
+        &mut self,
+        name: &str,
+        arg_types: &[DataType],
+        return_type: DataType,
+        function: impl Fn(&RecordBatch) -> Result<ArrayRef> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let column_name = match name.split_once('.') {
+            Some((_, name)) => name,
+            None => name,
+        };
+        let field = Field::new(column_name, return_type.clone(), true);
+        let schema = Arc::new(Schema::new(vec![field]));
+        let sig = FunctionSignature::builder()
+            .name(name)
+            .args(arg_types.iter().cloned())
+            .returns(return_type)
+            .dyn_scalar(move |input| {
+                let array = function(input)?;
+                if array.len() != input.num_rows() {
+                    return Err(Error::InvalidArgumentError(format!(
+                        "expect {} rows in the output, got {}",
+                        input.num_rows(),
+                        array.len()
+                    )));
+                }
+                RecordBatch::try_new(schema.clone(), vec![array])
+            })
+            .build()?;
+        self.register(sig);
+        Ok(())
+    }
+
     /// Get the function signature by name and types.
+    ///
+    /// If there are multiple versions of the function, the latest one is returned.
+    /// Use [`get_version`](Self::get_version) to get a specific version.
+    ///
+    /// Functions in a schema are found by the qualified name like `schema.name`.
+    /// An unqualified name only finds functions without a schema.
+    ///
+    /// Functions with extension types in their signature will never be matched.
+    /// Use [`get_by_fields`](Self::get_by_fields) for them.
     pub fn get(
         &self,
         name: &str,
@@ -165,7 +644,152 @@ impl FunctionRegistry {
         return_type: &DataType,
     ) -> Option<&FunctionSignature> {
         let sigs = self.signatures.get(name)?;
-        sigs.iter().find(|sig| sig.matches(arg_types, return_type))
+        latest(
+            sigs.iter()
+                .filter(|sig| sig.matches(arg_types, return_type)),
+        )
+    }
+
+    /// Returns a function that routes calls to the fastest runtime implementing the function.
+    ///
+    /// All scalar functions of the latest version matching the name and types are candidates,
+    /// one per [`runtime`](FunctionSignature::runtime). Returns `None` if there is no candidate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use arrow_schema::DataType;
+    /// use arrow_udf::sig::{FunctionRegistry, FunctionSignature};
+    ///
+    /// let mut registry = FunctionRegistry::default();
+    /// for runtime in ["rust", "python"] {
+    ///     let sig = FunctionSignature::builder()
+    ///         .name("identity")
+    ///         .arg(DataType::Int32)
+    ///         .returns(DataType::Int32)
+    ///         .runtime(runtime)
+    ///         .dyn_scalar(|input| Ok(input.clone()))
+    ///         .build()
+    ///         .unwrap();
+    ///     registry.register(sig);
+    /// }
+    /// let f = registry
+    ///     .routed_function("identity", &[DataType::Int32], &DataType::Int32)
+    ///     .unwrap();
+    /// assert_eq!(f.implementations().collect::<Vec<_>>(), ["rust", "python"]);
+    /// // calls always go to the pinned runtime
+    /// f.pin("python").unwrap();
+    /// ```
+    pub fn routed_function(
+        &self,
+        name: &str,
+        arg_types: &[DataType],
+        return_type: &DataType,
+    ) -> Option<RoutedFunction> {
+        let sigs = self.signatures.get(name)?;
+        let matches = || {
+            sigs.iter()
+                .filter(|sig| sig.function.is_scalar() && sig.matches(arg_types, return_type))
+        };
+        let version = latest(matches())?.version;
+        let mut routed = RoutedFunction::default();
+        for sig in matches().filter(|sig| sig.version == version) {
+            if routed.implementations().any(|name| name == sig.runtime) {
+                continue;
+            }
+            routed = match &sig.function {
+                FunctionKind::Scalar(f) => routed.with_implementation(&sig.runtime, *f),
+                FunctionKind::DynScalar(f) => {
+                    let f = f.clone();
+                    routed.with_implementation(&sig.runtime, move |input| f(input))
+                }
+                FunctionKind::Table(_) => unreachable!(),
+            };
+        }
+        Some(routed)
+    }
+
+    /// Get the function signature of a specific version by name and types.
+    pub fn get_version(
+        &self,
+        name: &str,
+        version: u32,
+        arg_types: &[DataType],
+        return_type: &DataType,
+    ) -> Option<&FunctionSignature> {
+        let sigs = self.signatures.get(name)?;
+        sigs.iter()
+            .find(|sig| sig.version == version && sig.matches(arg_types, return_type))
+    }
+
+    /// Get the function signature by name and fields.
+    ///
+    /// If there are multiple versions of the function, the latest one is returned.
+    ///
+    /// Extension types are identified by the extension name in the field metadata.
+    pub fn get_by_fields(
+        &self,
+        name: &str,
+        arg_fields: &[FieldRef],
+        return_field: &Field,
+    ) -> Option<&FunctionSignature> {
+        let sigs = self.signatures.get(name)?;
+        latest(
+            sigs.iter()
+                .filter(|sig| sig.matches_fields(arg_fields, return_field)),
+        )
+    }
+
+    /// Get the function signature of a specific version by name and fields.
+    pub fn get_by_fields_version(
+        &self,
+        name: &str,
+        version: u32,
+        arg_fields: &[FieldRef],
+        return_field: &Field,
+    ) -> Option<&FunctionSignature> {
+        let sigs = self.signatures.get(name)?;
+        sigs.iter()
+            .find(|sig| sig.version == version && sig.matches_fields(arg_fields, return_field))
+    }
+
+    /// Get the function signature by name and type names like `int` or `varchar[]`.
+    ///
+    /// If there are multiple versions of the function, the latest one is returned.
+    ///
+    /// Type names are resolved by the alias table, so engines can look up functions with the
+    /// type names of their own dialect. Returns an error if a type name is unknown.
+    ///
+    /// ```
+    /// use arrow_schema::DataType;
+    /// use arrow_udf::sig::FunctionRegistry;
+    /// use arrow_udf::sql::TypeAliases;
+    ///
+    /// let mut registry = FunctionRegistry::default();
+    /// registry
+    ///     .register_scalar("sqrt", &[DataType::Float64], DataType::Float64, |input| {
+    ///         Ok(input.column(0).clone())
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let mut aliases = TypeAliases::new();
+    /// aliases.insert("double", "float8").unwrap();
+    /// let sig = registry.get_by_type_names("sqrt", &["double"], "double", &aliases);
+    /// assert!(sig.unwrap().is_some());
+    /// ```
+    pub fn get_by_type_names(
+        &self,
+        name: &str,
+        arg_types: &[&str],
+        return_type: &str,
+        aliases: &TypeAliases,
+    ) -> Result<Option<&FunctionSignature>> {
+        let arg_fields = arg_types
+            .iter()
+            .map(|ty| Ok(Arc::new(aliases.field("", ty)?)))
+            .collect::<Result<Vec<FieldRef>>>()?;
+        let return_field = aliases.field("", return_type)?;
+        Ok(self.get_by_fields(name, &arg_fields, &return_field))
     }
 
     /// Iterate over all function signatures.
@@ -173,3 +797,11 @@ impl FunctionRegistry {
         self.signatures.values().flatten()
     }
 }
+
+/// Returns the signature with the latest version, or the first one if there are multiple.
+fn latest<'a>(
+    sigs: impl DoubleEndedIterator<Item = &'a FunctionSignature>,
+) -> Option<&'a FunctionSignature> {
+    // `max_by_key` returns the last maximum element
+    sigs.rev().max_by_key(|sig| sig.version)
+}
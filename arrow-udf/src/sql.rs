@@ -0,0 +1,726 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Parsing `CREATE FUNCTION` statements and SQL type names.
+//!
+//! Engines embedding the runtimes can parse the statement with [`parse_create_function`],
+//! and pass the function to the runtime of its language:
+//!
+//! ```
+//! use arrow_udf::sql::parse_create_function;
+//!
+//! let function = parse_create_function(
+//!     "CREATE FUNCTION gcd(a int, b int) RETURNS int LANGUAGE javascript AS $$
+//!         export function gcd(a, b) {
+//!             while (b) {
+//!                 [a, b] = [b, a % b];
+//!             }
+//!             return a;
+//!         }
+//!     $$",
+//! )
+//! .unwrap();
+//! assert_eq!(function.name, "gcd");
+//! assert_eq!(function.language, "javascript");
+//! // runtime.add_function(&function.name, function.return_type, mode, &function.body)
+//! ```
+//!
+//! Type names of other dialects can be mapped to the builtin ones with [`TypeAliases`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field, IntervalUnit, TimeUnit};
+
+use crate::types::{
+    DECIMAL_EXTENSION_NAME, EXTENSION_KEY, GEOARROW_WKB_EXTENSION_NAME, JSON_EXTENSION_NAME,
+};
+use crate::{Error, Result};
+
+/// A parsed `CREATE FUNCTION` statement.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct CreateFunction {
+    /// Whether `OR REPLACE` is specified.
+    pub or_replace: bool,
+    /// The function name, which may be qualified by a schema like `schema.name`.
+    pub name: String,
+    /// The arguments. Unnamed arguments are named by their positions like `$1`.
+    pub args: Vec<Field>,
+    /// The return type named after the function without the schema.
+    /// For `RETURNS TABLE (..)`, it is a struct of the columns.
+    pub return_type: Field,
+    /// Whether it is a table function returning `SETOF` or `TABLE`.
+    pub is_table_function: bool,
+    /// The language in lowercase.
+    pub language: String,
+    /// The function body.
+    pub body: String,
+    /// Whether the function returns null on null input, as specified by `STRICT` or
+    /// `RETURNS NULL ON NULL INPUT`.
+    pub return_null_on_null_input: bool,
+    /// Whether the function is volatile. Functions are volatile unless `IMMUTABLE` or `STABLE`
+    /// is specified.
+    pub volatile: bool,
+}
+
+/// Parse a `CREATE FUNCTION` statement in the PostgreSQL dialect.
+///
+/// ```sql
+/// CREATE [ OR REPLACE ] FUNCTION name ( [ [ argname ] argtype [, ...] ] )
+///     { RETURNS rettype | RETURNS SETOF rettype | RETURNS TABLE ( colname coltype [, ...] ) }
+///     { LANGUAGE lang_name
+///       | IMMUTABLE | STABLE | VOLATILE
+///       | CALLED ON NULL INPUT | RETURNS NULL ON NULL INPUT | STRICT
+///       | AS 'definition' | AS $$definition$$
+///     } ...
+/// ```
+pub fn parse_create_function(sql: &str) -> Result<CreateFunction> {
+    TypeAliases::new().parse_create_function(sql)
+}
+
+/// A table of type aliases, mapping the type names of an engine to the builtin type names.
+///
+/// Aliases are resolved before the builtin aliases like `integer` and `double precision`,
+/// so they can also override them.
+///
+/// ```
+/// use arrow_schema::DataType;
+/// use arrow_udf::sql::TypeAliases;
+///
+/// let mut aliases = TypeAliases::new();
+/// aliases.insert("double", "float8").unwrap();
+/// aliases.insert("string", "large_varchar").unwrap();
+///
+/// assert_eq!(aliases.data_type("DOUBLE").unwrap(), DataType::Float64);
+/// assert_eq!(aliases.data_type("string[]").unwrap(), DataType::new_list(DataType::LargeUtf8, true));
+/// assert_eq!(aliases.resolve("string"), "large_varchar");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeAliases {
+    /// Normalized aliases to builtin type names.
+    aliases: HashMap<String, String>,
+}
+
+impl TypeAliases {
+    /// Creates an empty table with only the builtin aliases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an alias of a type.
+    ///
+    /// Returns an error if the type is unknown. The type can not be another alias in the table.
+    pub fn insert(&mut self, alias: &str, ty: &str) -> Result<()> {
+        let ty = normalize_type(ty, None);
+        data_type_of(&ty)?;
+        self.aliases.insert(normalize_words(alias), ty);
+        Ok(())
+    }
+
+    /// Removes an alias. Returns the type it was mapped to.
+    pub fn remove(&mut self, alias: &str) -> Option<String> {
+        self.aliases.remove(&normalize_words(alias))
+    }
+
+    /// Iterates over the aliases and their types.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Normalizes a type name to the name used in function signatures, resolving the aliases.
+    ///
+    /// The name is not checked. Unknown types are returned in lowercase.
+    pub fn resolve(&self, ty: &str) -> String {
+        normalize_type(ty, Some(self))
+    }
+
+    /// Returns a nullable [`Field`] from name and type name. See [`field`].
+    pub fn field(&self, name: &str, ty: &str) -> Result<Field> {
+        field_of(name, &self.resolve(ty))
+    }
+
+    /// Returns the [`DataType`] of a type name. See [`data_type`].
+    pub fn data_type(&self, ty: &str) -> Result<DataType> {
+        data_type_of(&self.resolve(ty))
+    }
+
+    /// Parses a `CREATE FUNCTION` statement with the aliases in argument and return types.
+    /// See [`parse_create_function`].
+    pub fn parse_create_function(&self, sql: &str) -> Result<CreateFunction> {
+        let mut parser = Parser {
+            tokens: tokenize(sql)?,
+            pos: 0,
+        };
+        parser.expect_keyword("create")?;
+        let or_replace = match parser.consume_keyword("or") {
+            true => {
+                parser.expect_keyword("replace")?;
+                true
+            }
+            false => false,
+        };
+        parser.expect_keyword("function")?;
+        let name = parser.parse_name()?;
+
+        // arguments
+        let args = parser
+            .parse_list()?
+            .into_iter()
+            .enumerate()
+            .map(|(i, tokens)| parse_arg(&tokens, i, self))
+            .collect::<Result<Vec<_>>>()?;
+
+        // return type named after the function
+        parser.expect_keyword("returns")?;
+        let column_name = name.rsplit('.').next().unwrap();
+        let (return_type, is_table_function) = if parser.consume_keyword("table") {
+            let columns = parser
+                .parse_list()?
+                .into_iter()
+                .map(|tokens| match tokens.split_first() {
+                    Some((Token::Word(name) | Token::Quoted(name), ty)) if !ty.is_empty() => {
+                        self.field(name, &type_name(ty))
+                    }
+                    _ => Err(parse_error("expect column name and type in RETURNS TABLE")),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let data_type = DataType::Struct(columns.into());
+            (Field::new(column_name, data_type, true), true)
+        } else {
+            let is_table_function = parser.consume_keyword("setof");
+            let tokens = parser.take_type();
+            if tokens.is_empty() {
+                return Err(parser.unexpected("return type"));
+            }
+            (
+                self.field(column_name, &type_name(&tokens))?,
+                is_table_function,
+            )
+        };
+
+        // options in any order
+        let mut language = None;
+        let mut body = None;
+        let mut return_null_on_null_input = false;
+        let mut volatile = true;
+        while let Some(Token::Word(word)) = parser.peek() {
+            match word.to_lowercase().as_str() {
+                "language" => {
+                    parser.pos += 1;
+                    match parser.next() {
+                        Some(Token::Word(s) | Token::Quoted(s) | Token::String(s)) => {
+                            language = Some(s.to_lowercase())
+                        }
+                        _ => return Err(parser.unexpected_previous("language name")),
+                    }
+                }
+                "as" => {
+                    parser.pos += 1;
+                    match parser.next() {
+                        Some(Token::String(s)) => body = Some(s),
+                        _ => return Err(parser.unexpected_previous("function body")),
+                    }
+                    if parser.peek() == Some(&Token::Symbol(',')) {
+                        return Err(parse_error("object file and link symbol are not supported"));
+                    }
+                }
+                "strict" => {
+                    parser.pos += 1;
+                    return_null_on_null_input = true;
+                }
+                "returns" => {
+                    parser.pos += 1;
+                    parser.expect_keywords(&["null", "on", "null", "input"])?;
+                    return_null_on_null_input = true;
+                }
+                "called" => {
+                    parser.pos += 1;
+                    parser.expect_keywords(&["on", "null", "input"])?;
+                    return_null_on_null_input = false;
+                }
+                "immutable" | "stable" => {
+                    parser.pos += 1;
+                    volatile = false;
+                }
+                "volatile" => {
+                    parser.pos += 1;
+                    volatile = true;
+                }
+                _ => break,
+            }
+        }
+        if parser.peek() == Some(&Token::Symbol(';')) {
+            parser.pos += 1;
+        }
+        if parser.peek().is_some() {
+            return Err(parser.unexpected("end of statement"));
+        }
+
+        Ok(CreateFunction {
+            or_replace,
+            name,
+            args,
+            return_type,
+            is_table_function,
+            language: language.ok_or_else(|| parse_error("LANGUAGE is required"))?,
+            body: body.ok_or_else(|| parse_error("function body is required"))?,
+            return_null_on_null_input,
+            volatile,
+        })
+    }
+}
+
+/// Returns a nullable [`Field`] from name and SQL type name.
+///
+/// For extension types like `json` and `decimal`, the extension name is set in the field
+/// metadata.
+///
+/// ```
+/// use arrow_schema::DataType;
+/// use arrow_udf::sql::field;
+///
+/// let f = field("x", "character varying(255)").unwrap();
+/// assert_eq!(f.data_type(), &DataType::Utf8);
+/// ```
+pub fn field(name: &str, ty: &str) -> Result<Field> {
+    field_of(name, &normalize_type(ty, None))
+}
+
+/// Returns the [`DataType`] of a SQL type name.
+///
+/// Extension types are represented by their storage types. Use [`field`] to keep the
+/// extension name.
+pub fn data_type(ty: &str) -> Result<DataType> {
+    data_type_of(&normalize_type(ty, None))
+}
+
+/// Returns a nullable `Field` of a normalized type name.
+fn field_of(name: &str, ty: &str) -> Result<Field> {
+    let data_type = data_type_of(ty)?;
+    let extension = match ty {
+        "json" => Some(JSON_EXTENSION_NAME),
+        "decimal" => Some(DECIMAL_EXTENSION_NAME),
+        "geometry" => Some(GEOARROW_WKB_EXTENSION_NAME),
+        _ => None,
+    };
+    let field = Field::new(name, data_type, true);
+    Ok(match extension {
+        Some(extension) => field.with_metadata([(EXTENSION_KEY.into(), extension.into())].into()),
+        None => field,
+    })
+}
+
+/// Returns the `DataType` of a normalized type name.
+fn data_type_of(ty: &str) -> Result<DataType> {
+    if let Some(item) = ty.strip_suffix("[]") {
+        return Ok(DataType::List(Arc::new(field_of("item", item)?)));
+    }
+    if let Some(n) = ty.strip_prefix("bytea(").and_then(|s| s.strip_suffix(')')) {
+        let n = n
+            .parse()
+            .map_err(|_| parse_error(format!("invalid type: {ty}")))?;
+        return Ok(DataType::FixedSizeBinary(n));
+    }
+    if let Some(n) = ty.strip_prefix("vector(").and_then(|s| s.strip_suffix(')')) {
+        let n = n
+            .parse()
+            .map_err(|_| parse_error(format!("invalid type: {ty}")))?;
+        let item = Field::new("item", DataType::Float32, true);
+        return Ok(DataType::FixedSizeList(Arc::new(item), n));
+    }
+    Ok(match ty {
+        "void" => DataType::Null,
+        "boolean" => DataType::Boolean,
+        "int2" => DataType::Int16,
+        "int4" => DataType::Int32,
+        "int8" => DataType::Int64,
+        "float2" => DataType::Float16,
+        "float4" => DataType::Float32,
+        "float8" => DataType::Float64,
+        "decimal" | "json" | "varchar" => DataType::Utf8,
+        "date" => DataType::Date32,
+        "time" => DataType::Time64(TimeUnit::Microsecond),
+        "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, None),
+        "interval" => DataType::Interval(IntervalUnit::MonthDayNano),
+        "bytea" | "geometry" => DataType::Binary,
+        "large_varchar" => DataType::LargeUtf8,
+        "large_bytea" => DataType::LargeBinary,
+        _ => return Err(parse_error(format!("unknown type: {ty}"))),
+    })
+}
+
+/// Collapse whitespace and lowercase the words of a type name.
+fn normalize_words(ty: &str) -> String {
+    let ty = ty.split_whitespace().collect::<Vec<_>>().join(" ");
+    ty.to_lowercase()
+        .replace(" (", "(")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace(" ,", ",")
+        .replace(", ", ",")
+        .replace(" [", "[")
+        .replace("[ ", "[")
+}
+
+/// Normalize a type name to the name used in function signatures.
+///
+/// Keywords are lowercased and aliases are resolved, e.g. `INTEGER` => `int4`,
+/// `Double  Precision` => `float8`, `varchar(10)[]` => `varchar[]`.
+fn normalize_type(ty: &str, aliases: Option<&TypeAliases>) -> String {
+    let ty = normalize_words(ty);
+    if let Some(t) = ty.strip_suffix("[]") {
+        return format!("{}[]", normalize_type(t, aliases));
+    }
+    // the length of strings and the precision of decimals are ignored
+    let base = match ty.split_once('(') {
+        Some((base, _)) if base != "bytea" && base != "vector" => base,
+        _ => &ty,
+    };
+    if let Some(aliases) = aliases {
+        // aliases of the whole name like `varchar(max)` take precedence
+        if let Some(t) = aliases
+            .aliases
+            .get(&ty)
+            .or_else(|| aliases.aliases.get(base))
+        {
+            return t.clone();
+        }
+    }
+    match base {
+        "bool" => "boolean",
+        "smallint" => "int2",
+        "int" | "integer" => "int4",
+        "bigint" => "int8",
+        "half" => "float2",
+        "real" => "float4",
+        "double precision" => "float8",
+        "numeric" => "decimal",
+        "character varying" | "text" | "string" => "varchar",
+        "large_string" => "large_varchar",
+        "large_binary" => "large_bytea",
+        "jsonb" => "json",
+        "time without time zone" => "time",
+        "timestamp without time zone" => "timestamp",
+        _ => base,
+    }
+    .to_string()
+}
+
+/// Parse an argument of `CREATE FUNCTION`: `[ IN ] [ argname ] argtype`.
+fn parse_arg(tokens: &[Token], index: usize, aliases: &TypeAliases) -> Result<Field> {
+    let mut tokens = tokens;
+    if let [Token::Word(mode), rest @ ..] = tokens {
+        match mode.to_lowercase().as_str() {
+            "in" if !rest.is_empty() => tokens = rest,
+            "out" | "inout" | "variadic" => {
+                return Err(parse_error(format!(
+                    "argument mode {mode} is not supported"
+                )))
+            }
+            _ => {}
+        }
+    }
+    if tokens.iter().any(|t| {
+        matches!(t, Token::Word(w) if w.eq_ignore_ascii_case("default")) || *t == Token::Symbol('=')
+    }) {
+        return Err(parse_error("default values of arguments are not supported"));
+    }
+    // the type can have multiple words like `double precision`, so try it as a type first
+    let unnamed = format!("${}", index + 1);
+    if let Ok(field) = aliases.field(&unnamed, &type_name(tokens)) {
+        return Ok(field);
+    }
+    match tokens.split_first() {
+        Some((Token::Word(name) | Token::Quoted(name), ty)) if !ty.is_empty() => {
+            aliases.field(name, &type_name(ty))
+        }
+        _ => Err(parse_error(format!(
+            "invalid argument: {}",
+            type_name(tokens)
+        ))),
+    }
+}
+
+/// Join the tokens of a type into a type name.
+fn type_name(tokens: &[Token]) -> String {
+    let mut s = String::new();
+    for token in tokens {
+        match token {
+            Token::Word(w) | Token::Quoted(w) | Token::Number(w) => {
+                if s.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    s.push(' ');
+                }
+                s.push_str(w);
+            }
+            Token::String(w) => s.push_str(w),
+            Token::Symbol(c) => s.push(*c),
+        }
+    }
+    s
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A keyword or unquoted identifier.
+    Word(String),
+    /// A quoted identifier.
+    Quoted(String),
+    /// A string in single quotes or dollar quotes.
+    String(String),
+    Number(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(s) | Token::Number(s) => write!(f, "{s}"),
+            Token::Quoted(s) => write!(f, "\"{s}\""),
+            Token::String(s) => write!(f, "'{s}'"),
+            Token::Symbol(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+/// Split the statement into tokens with their byte offsets. Comments are skipped.
+fn tokenize(sql: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    let mut chars = sql.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let rest = &sql[start..];
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let (token, len) = if rest.starts_with("--") {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            skip(&mut chars, start + len);
+            continue;
+        } else if rest.starts_with("/*") {
+            let len = rest
+                .find("*/")
+                .ok_or_else(|| parse_error(format!("unterminated comment at offset {start}")))?;
+            skip(&mut chars, start + len + 2);
+            continue;
+        } else if c == '\'' {
+            // quotes are escaped by doubling them
+            let mut s = String::new();
+            let mut i = 1;
+            loop {
+                let end = rest[i..]
+                    .find('\'')
+                    .ok_or_else(|| parse_error(format!("unterminated string at offset {start}")))?;
+                s.push_str(&rest[i..i + end]);
+                i += end + 1;
+                if rest[i..].starts_with('\'') {
+                    s.push('\'');
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            (Token::String(s), i)
+        } else if c == '"' {
+            let end = rest[1..]
+                .find('"')
+                .ok_or_else(|| parse_error(format!("unterminated identifier at offset {start}")))?;
+            (Token::Quoted(rest[1..1 + end].to_string()), end + 2)
+        } else if c == '$' {
+            // dollar-quoted string: $tag$ .. $tag$
+            let tag_len = rest[1..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map(|n| n + 1)
+                .filter(|n| rest[*n..].starts_with('$'))
+                .ok_or_else(|| parse_error(format!("invalid dollar quote at offset {start}")))?;
+            let tag = &rest[..tag_len + 1];
+            let end = rest[tag.len()..].find(tag).ok_or_else(|| {
+                parse_error(format!(
+                    "unterminated dollar-quoted string at offset {start}"
+                ))
+            })?;
+            let body = &rest[tag.len()..tag.len() + end];
+            (Token::String(body.to_string()), end + 2 * tag.len())
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (Token::Word(rest[..len].to_string()), len)
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            (Token::Number(rest[..len].to_string()), len)
+        } else if "(),[].;=".contains(c) {
+            (Token::Symbol(c), 1)
+        } else {
+            return Err(parse_error(format!(
+                "unexpected character {c:?} at offset {start}"
+            )));
+        };
+        tokens.push((token, start));
+        skip(&mut chars, start + len);
+    }
+    Ok(tokens)
+}
+
+/// Advance the iterator to the byte offset.
+fn skip(chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>, offset: usize) {
+    while chars.next_if(|(i, _)| *i < offset).is_some() {}
+}
+
+/// Keywords that end the return type.
+const OPTION_KEYWORDS: &[&str] = &[
+    "language",
+    "as",
+    "strict",
+    "returns",
+    "called",
+    "immutable",
+    "stable",
+    "volatile",
+];
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    /// Consume the next token if it is the given keyword.
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.consume_keyword(keyword) {
+            true => Ok(()),
+            false => Err(self.unexpected(&keyword.to_uppercase())),
+        }
+    }
+
+    fn expect_keywords(&mut self, keywords: &[&str]) -> Result<()> {
+        keywords.iter().try_for_each(|k| self.expect_keyword(k))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<()> {
+        match self.peek() == Some(&Token::Symbol(symbol)) {
+            true => {
+                self.pos += 1;
+                Ok(())
+            }
+            false => Err(self.unexpected(&symbol.to_string())),
+        }
+    }
+
+    /// Parse a name which may be qualified by a schema.
+    fn parse_name(&mut self) -> Result<String> {
+        let mut name = String::new();
+        loop {
+            match self.next() {
+                Some(Token::Word(s)) => name.push_str(&s.to_lowercase()),
+                Some(Token::Quoted(s)) => name.push_str(&s),
+                _ => return Err(self.unexpected_previous("function name")),
+            }
+            if self.peek() != Some(&Token::Symbol('.')) {
+                return Ok(name);
+            }
+            self.pos += 1;
+            name.push('.');
+        }
+    }
+
+    /// Parse a parenthesized list separated by commas. Returns the tokens of each item.
+    fn parse_list(&mut self) -> Result<Vec<Vec<Token>>> {
+        self.expect_symbol('(')?;
+        let mut items = vec![];
+        let mut item = vec![];
+        let mut depth = 0;
+        loop {
+            let token = self.next().ok_or_else(|| self.unexpected_previous(")"))?;
+            match token {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') if depth == 0 => break,
+                Token::Symbol(')') => depth -= 1,
+                Token::Symbol(',') if depth == 0 => {
+                    items.push(std::mem::take(&mut item));
+                    continue;
+                }
+                _ => {}
+            }
+            item.push(token);
+        }
+        if !item.is_empty() || !items.is_empty() {
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    /// Take the tokens of a type until an option keyword or the end of statement.
+    fn take_type(&mut self) -> Vec<Token> {
+        let mut tokens = vec![];
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Word(w)
+                    if depth == 0 && OPTION_KEYWORDS.contains(&w.to_lowercase().as_str()) =>
+                {
+                    break
+                }
+                Token::Symbol(';') if depth == 0 => break,
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token.clone());
+            self.pos += 1;
+        }
+        tokens
+    }
+
+    /// Returns an error for the next token.
+    fn unexpected(&self, expected: &str) -> Error {
+        match self.tokens.get(self.pos) {
+            Some((token, offset)) => parse_error(format!(
+                "expected {expected}, found {token} at offset {offset}"
+            )),
+            None => parse_error(format!("expected {expected}, found end of statement")),
+        }
+    }
+
+    /// Returns an error for the token just consumed.
+    fn unexpected_previous(&mut self, expected: &str) -> Error {
+        self.pos -= 1;
+        self.unexpected(expected)
+    }
+}
+
+fn parse_error(msg: impl Into<String>) -> Error {
+    Error::ParseError(msg.into())
+}
@@ -0,0 +1,329 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utilities for testing UDF runtimes.
+//!
+//! This module is only available with the `testing` feature enabled.
+//!
+//! The main entry point is [`assert_roundtrip`], which checks that a runtime passes
+//! random values of a type through an identity function unchanged:
+//!
+//! ```ignore
+//! use arrow_udf::testing::assert_roundtrip;
+//!
+//! runtime.add_function("identity", DataType::Int32, "def identity(x): return x")?;
+//! assert_roundtrip(|input| runtime.call("identity", input), &DataType::Int32);
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use arrow_array::types::IntervalMonthDayNanoType;
+use arrow_array::*;
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_data::decimal::MAX_DECIMAL_FOR_EACH_PRECISION;
+use arrow_schema::{DataType, Field, IntervalUnit, Schema, TimeUnit};
+use half::f16;
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::types::{DECIMAL_EXTENSION_NAME, EXTENSION_KEY, JSON_EXTENSION_NAME};
+
+/// Generates a random array of the given type.
+///
+/// Each value is null with probability `null_density`.
+/// The same `seed` always generates the same array.
+///
+/// Values are generated in the canonical form of the type, so that they survive a round trip
+/// through any runtime:
+///
+/// - JSON values are numbers, booleans or ASCII strings.
+/// - Decimal strings (`arrowudf.decimal`) have no trailing zeros.
+///
+/// # Panics
+///
+/// Panics if the type is not supported.
+pub fn random_array(data_type: &DataType, len: usize, null_density: f32, seed: u64) -> ArrayRef {
+    let field = Field::new("x", data_type.clone(), true);
+    random_field_array(&field, len, null_density, seed)
+}
+
+/// Generates a random array for the given field.
+///
+/// Unlike [`random_array`], this function respects extension types in the field metadata.
+pub fn random_field_array(field: &Field, len: usize, null_density: f32, seed: u64) -> ArrayRef {
+    let mut rng = StdRng::seed_from_u64(seed);
+    gen_array(&mut rng, field, len, null_density)
+}
+
+/// Asserts that `runtime` returns its input unchanged for random arrays of the given type.
+///
+/// `runtime` is called with a batch containing a single column `x`, and should evaluate an
+/// identity function that returns `data_type`.
+pub fn assert_roundtrip<F, E>(runtime: F, data_type: &DataType)
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch, E>,
+    E: Debug,
+{
+    assert_field_roundtrip(runtime, &Field::new("x", data_type.clone(), true));
+}
+
+/// Asserts that `runtime` returns its input unchanged for random arrays of the given field.
+///
+/// This is the same as [`assert_roundtrip`], but the input column is described by `field`,
+/// so that extension types can be tested.
+pub fn assert_field_roundtrip<F, E>(mut runtime: F, field: &Field)
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch, E>,
+    E: Debug,
+{
+    for (seed, (len, null_density)) in [(0, 0.0), (1, 0.0), (1, 1.0), (16, 0.5), (100, 0.1)]
+        .into_iter()
+        .enumerate()
+    {
+        let array = random_field_array(field, len, null_density, seed as u64);
+        let schema = Schema::new(vec![field.clone()]);
+        let input = RecordBatch::try_new(Arc::new(schema), vec![array]).unwrap();
+        assert_roundtrip_batch(&mut runtime, &input);
+    }
+}
+
+/// Asserts that `runtime` returns the given array unchanged.
+///
+/// See [`assert_roundtrip`] for the requirements of `runtime`.
+pub fn assert_roundtrip_array<F, E>(runtime: F, array: ArrayRef)
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch, E>,
+    E: Debug,
+{
+    let schema = Schema::new(vec![Field::new("x", array.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![array]).unwrap();
+    assert_roundtrip_batch(runtime, &input);
+}
+
+/// Asserts that `runtime` returns the single column of `input` unchanged.
+///
+/// Both the values and the extension type of the column are checked.
+pub fn assert_roundtrip_batch<F, E>(mut runtime: F, input: &RecordBatch)
+where
+    F: FnMut(&RecordBatch) -> Result<RecordBatch, E>,
+    E: Debug,
+{
+    let field = input.schema().field(0).clone();
+    let output = match runtime(input) {
+        Ok(output) => output,
+        Err(e) => panic!("failed to call function on {}: {e:?}", field.data_type()),
+    };
+    assert_eq!(output.num_columns(), 1, "expect exactly one output column");
+    assert_eq!(
+        output.schema().field(0).metadata().get(EXTENSION_KEY),
+        field.metadata().get(EXTENSION_KEY),
+        "round trip of {} changed the extension type",
+        field.data_type()
+    );
+    assert_eq!(
+        output.column(0).to_data(),
+        input.column(0).to_data(),
+        "round trip of {} changed the value",
+        field.data_type()
+    );
+}
+
+fn gen_array(rng: &mut StdRng, field: &Field, len: usize, null_density: f32) -> ArrayRef {
+    let data_type = field.data_type();
+    macro_rules! random {
+        ($array_type:ty, $value:expr) => {{
+            let array: $array_type = (0..len)
+                .map(|_| match rng.gen::<f32>() < null_density {
+                    true => None,
+                    false => Some($value),
+                })
+                .collect();
+            Arc::new(array)
+        }};
+    }
+    match extension(field) {
+        Some(JSON_EXTENSION_NAME) => {
+            return match data_type {
+                DataType::Utf8 => random!(StringArray, gen_json(rng)),
+                _ => random!(LargeStringArray, gen_json(rng)),
+            };
+        }
+        Some(DECIMAL_EXTENSION_NAME) => {
+            return match data_type {
+                DataType::Utf8 => random!(StringArray, gen_decimal(rng)),
+                _ => random!(LargeStringArray, gen_decimal(rng)),
+            };
+        }
+        _ => {}
+    }
+    match data_type {
+        DataType::Boolean => random!(BooleanArray, rng.gen::<bool>()),
+        DataType::Int8 => random!(Int8Array, rng.gen::<i8>()),
+        DataType::Int16 => random!(Int16Array, rng.gen::<i16>()),
+        DataType::Int32 => random!(Int32Array, rng.gen::<i32>()),
+        DataType::Int64 => random!(Int64Array, rng.gen::<i64>()),
+        DataType::UInt8 => random!(UInt8Array, rng.gen::<u8>()),
+        DataType::UInt16 => random!(UInt16Array, rng.gen::<u16>()),
+        DataType::UInt32 => random!(UInt32Array, rng.gen::<u32>()),
+        DataType::UInt64 => random!(UInt64Array, rng.gen::<u64>()),
+        DataType::Float16 => random!(Float16Array, f16::from_f32(rng.gen_range(-1e4f32..1e4))),
+        DataType::Float32 => random!(Float32Array, rng.gen_range(-1e6f32..1e6)),
+        DataType::Float64 => random!(Float64Array, rng.gen_range(-1e12f64..1e12)),
+        // 0001-01-01 ~ 9999-12-31
+        DataType::Date32 => random!(Date32Array, rng.gen_range(-719_162i32..2_932_896)),
+        DataType::Time64(TimeUnit::Microsecond) => {
+            random!(Time64MicrosecondArray, rng.gen_range(0i64..86_400_000_000))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, tz) => {
+            // 0001-01-01 ~ 9999-12-31
+            let range = -62_135_596_800_000_000i64..253_402_300_800_000_000;
+            let array: TimestampMicrosecondArray = (0..len)
+                .map(|_| match rng.gen::<f32>() < null_density {
+                    true => None,
+                    false => Some(rng.gen_range(range.clone())),
+                })
+                .collect();
+            Arc::new(array.with_timezone_opt(tz.clone()))
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => random!(
+            IntervalMonthDayNanoArray,
+            IntervalMonthDayNanoType::make_value(rng.gen(), rng.gen(), rng.gen())
+        ),
+        DataType::Utf8 => random!(StringArray, gen_string(rng)),
+        DataType::Binary => random!(BinaryArray, {
+            let len = rng.gen_range(0..16);
+            (0..len).map(|_| rng.gen::<u8>()).collect::<Vec<u8>>()
+        }),
+        DataType::LargeUtf8 => random!(LargeStringArray, gen_string(rng)),
+        DataType::LargeBinary => random!(LargeBinaryArray, {
+            let len = rng.gen_range(0..16);
+            (0..len).map(|_| rng.gen::<u8>()).collect::<Vec<u8>>()
+        }),
+        DataType::FixedSizeBinary(size) => {
+            let values = (0..len).map(|_| match rng.gen::<f32>() < null_density {
+                true => None,
+                false => Some((0..*size).map(|_| rng.gen::<u8>()).collect::<Vec<u8>>()),
+            });
+            let array =
+                FixedSizeBinaryArray::try_from_sparse_iter_with_size(values, *size).unwrap();
+            Arc::new(array)
+        }
+        DataType::Decimal128(precision, scale) => {
+            let max = MAX_DECIMAL_FOR_EACH_PRECISION[*precision as usize - 1];
+            let array: Decimal128Array = (0..len)
+                .map(|_| match rng.gen::<f32>() < null_density {
+                    true => None,
+                    false => Some(rng.gen_range(-max..=max)),
+                })
+                .collect();
+            Arc::new(array.with_precision_and_scale(*precision, *scale).unwrap())
+        }
+        DataType::List(field) => {
+            let mut offsets = Vec::with_capacity(len + 1);
+            let mut valid = Vec::with_capacity(len);
+            offsets.push(0);
+            for _ in 0..len {
+                let is_valid = rng.gen::<f32>() >= null_density;
+                let list_len = if is_valid { rng.gen_range(0..4) } else { 0 };
+                offsets.push(offsets.last().unwrap() + list_len);
+                valid.push(is_valid);
+            }
+            let values = gen_array(
+                rng,
+                field,
+                *offsets.last().unwrap() as usize,
+                child_null_density(field, null_density),
+            );
+            Arc::new(ListArray::new(
+                field.clone(),
+                OffsetBuffer::new(offsets.into()),
+                values,
+                Some(NullBuffer::from(valid)),
+            ))
+        }
+        DataType::Struct(fields) => {
+            let valid = (0..len)
+                .map(|_| rng.gen::<f32>() >= null_density)
+                .collect::<Vec<_>>();
+            let arrays = fields
+                .iter()
+                .map(|field| {
+                    let null_density = child_null_density(field, null_density);
+                    gen_array(rng, field, len, null_density)
+                })
+                .collect();
+            Arc::new(StructArray::new(
+                fields.clone(),
+                arrays,
+                Some(NullBuffer::from(valid)),
+            ))
+        }
+        _ => panic!("unsupported data type: {data_type}"),
+    }
+}
+
+/// Returns the extension name of the field.
+fn extension(field: &Field) -> Option<&str> {
+    field.metadata().get(EXTENSION_KEY).map(|s| s.as_str())
+}
+
+/// Returns the null density for a child field.
+fn child_null_density(field: &Field, null_density: f32) -> f32 {
+    if field.is_nullable() {
+        null_density
+    } else {
+        0.0
+    }
+}
+
+/// Generates a random string of any unicode characters.
+fn gen_string(rng: &mut StdRng) -> String {
+    let len = rng.gen_range(0..16);
+    (0..len).map(|_| rng.gen::<char>()).collect()
+}
+
+/// Generates a random JSON value in its compact form.
+fn gen_json(rng: &mut StdRng) -> String {
+    match rng.gen_range(0..3) {
+        0 => rng.gen::<i32>().to_string(),
+        1 => rng.gen::<bool>().to_string(),
+        _ => {
+            let len = rng.gen_range(0..16);
+            let s: String = (0..len).map(|_| rng.sample(Alphanumeric) as char).collect();
+            format!("\"{s}\"")
+        }
+    }
+}
+
+/// Generates a random decimal string without trailing zeros, such as `-123.45`.
+fn gen_decimal(rng: &mut StdRng) -> String {
+    let int = rng.gen_range(0..1_000_000u32);
+    let frac = match rng.gen_range(0..4) {
+        0 => String::new(),
+        scale => {
+            let digits: String = (0..scale - 1)
+                .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+                .collect();
+            format!(".{digits}{}", rng.gen_range(1..10))
+        }
+    };
+    let sign = if rng.gen::<bool>() && (int != 0 || !frac.is_empty()) {
+        "-"
+    } else {
+        ""
+    };
+    format!("{sign}{int}{frac}")
+}
@@ -13,14 +13,44 @@
 // limitations under the License.
 
 use arrow_array::builder::StructBuilder;
+use arrow_array::ArrayRef;
 use arrow_schema::Fields;
 pub use arrow_udf_macros::StructType;
+pub use half::f16;
+
+/// The metadata key for the extension name of a field.
+pub const EXTENSION_KEY: &str = "ARROW:extension:name";
+
+/// The extension name of JSON type.
+///
+/// JSON values are stored as strings in a `Utf8` array, and the field is annotated with
+/// this name under the [`EXTENSION_KEY`] metadata.
+pub const JSON_EXTENSION_NAME: &str = "arrowudf.json";
+
+/// The extension name of decimal type.
+///
+/// Decimal values are stored as strings in a `Utf8` array, such as `-123.45`, and the field
+/// is annotated with this name under the [`EXTENSION_KEY`] metadata.
+pub const DECIMAL_EXTENSION_NAME: &str = "arrowudf.decimal";
+
+/// The extension name of geometry type.
+///
+/// Geometries are stored as WKB in a `Binary` array, and the field is annotated with
+/// this GeoArrow extension name under the [`EXTENSION_KEY`] metadata.
+pub const GEOARROW_WKB_EXTENSION_NAME: &str = "geoarrow.wkb";
 
 /// Interval type.
+///
+/// This is the Rust type of `interval` in function signatures, stored in an
+/// `IntervalMonthDayNanoArray`. The three components are independent, because the number of
+/// days in a month and the length of a day vary.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Interval {
+    /// The number of months.
     pub months: i32,
+    /// The number of days.
     pub days: i32,
+    /// The number of nanoseconds.
     pub nanos: i64,
 }
 
@@ -35,3 +65,15 @@ pub trait StructType {
     /// Appends a null value to the builder.
     fn append_null(builder: &mut StructBuilder);
 }
+
+/// A trait for user-defined struct types that can be read from arrays.
+///
+/// It is derived along with [`StructType`] if no field is a list, a struct or a fixed-size type,
+/// so that the struct can be an argument of `#[function]`.
+pub trait StructValue<'a>: StructType + Sized {
+    /// Reads the struct at the `row`-th row from the columns of its fields.
+    ///
+    /// Returns `None` if a field that is not `Option<T>` is null.
+    /// The columns must have the types of [`StructType::fields`].
+    fn from_columns(columns: &'a [ArrayRef], row: usize) -> Option<Self>;
+}
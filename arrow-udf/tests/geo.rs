@@ -0,0 +1,124 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "geo")]
+
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, BinaryArray, Float64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use arrow_udf::function;
+use arrow_udf::geo::{from_wkb, to_wkb, Geometry};
+use arrow_udf::types::{EXTENSION_KEY, GEOARROW_WKB_EXTENSION_NAME};
+use geo_types::{Coord, GeometryCollection, LineString, MultiPoint, Point, Polygon};
+
+#[function("st_point(float8, float8) -> geometry")]
+fn st_point(x: f64, y: f64) -> Point {
+    Point::new(x, y)
+}
+
+#[function("st_x(geometry) -> float8")]
+fn st_x(g: Geometry) -> Option<f64> {
+    match g {
+        Geometry::Point(p) => Some(p.x()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_geometry_function() {
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Float64, true),
+        Field::new("y", DataType::Float64, true),
+    ]);
+    let x = Float64Array::from(vec![Some(1.0), None]);
+    let y = Float64Array::from(vec![Some(2.0), Some(3.0)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(x), Arc::new(y)]).unwrap();
+
+    let output = st_point_float8_float8_geometry_eval(&input).unwrap();
+    let field = output.schema().field(0).clone();
+    assert_eq!(field.data_type(), &DataType::Binary);
+    assert_eq!(
+        field.metadata().get(EXTENSION_KEY).map(|s| s.as_str()),
+        Some(GEOARROW_WKB_EXTENSION_NAME)
+    );
+    let points = output.column(0).as_binary::<i32>();
+    assert_eq!(
+        points.value(0),
+        [
+            0x01, 0x01, 0x00, 0x00, 0x00, // little endian, point
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, // 1.0
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, // 2.0
+        ]
+    );
+    assert!(points.is_null(1));
+
+    let input = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![field])),
+        vec![output.column(0).clone()],
+    )
+    .unwrap();
+    let output = st_x_geometry_float8_eval(&input).unwrap();
+    let x = output
+        .column(0)
+        .as_primitive::<arrow_array::types::Float64Type>();
+    assert_eq!(x.value(0), 1.0);
+    assert!(x.is_null(1));
+}
+
+#[test]
+fn test_wkb_roundtrip() {
+    let ring = |coords: &[(f64, f64)]| {
+        LineString::new(coords.iter().map(|&(x, y)| Coord { x, y }).collect())
+    };
+    let polygon = Polygon::new(
+        ring(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 0.0)]),
+        vec![ring(&[(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 1.0)])],
+    );
+    let geometries = [
+        Geometry::Point(Point::new(1.0, 2.0)),
+        Geometry::LineString(ring(&[(0.0, 0.0), (1.0, 1.0)])),
+        Geometry::Polygon(polygon.clone()),
+        Geometry::MultiPoint(MultiPoint(vec![Point::new(1.0, 2.0), Point::new(3.0, 4.0)])),
+        Geometry::GeometryCollection(GeometryCollection(vec![
+            Geometry::Point(Point::new(1.0, 2.0)),
+            Geometry::Polygon(polygon),
+        ])),
+    ];
+    for geometry in geometries {
+        assert_eq!(from_wkb(&to_wkb(&geometry)).unwrap(), geometry);
+    }
+
+    // big endian
+    let point = [
+        0x00, 0x00, 0x00, 0x00, 0x01, // big endian, point
+        0x3f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 1.0
+        0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 2.0
+    ];
+    assert_eq!(
+        from_wkb(&point).unwrap(),
+        Geometry::Point(Point::new(1.0, 2.0))
+    );
+
+    let invalid = BinaryArray::from(vec![&[0x01, 0x01, 0x00][..], &[0x01, 0x09, 0, 0, 0]]);
+    assert_eq!(
+        from_wkb(invalid.value(0)).unwrap_err().to_string(),
+        "Parser error: invalid WKB: unexpected end"
+    );
+    assert_eq!(
+        from_wkb(invalid.value(1)).unwrap_err().to_string(),
+        "Parser error: invalid WKB: unsupported geometry type 9"
+    );
+}
@@ -0,0 +1,231 @@
+// Copyright 2024 RisingWave Labs
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field, Fields, TimeUnit};
+use arrow_udf::sql::{data_type, field, parse_create_function, TypeAliases};
+use arrow_udf::types::{DECIMAL_EXTENSION_NAME, EXTENSION_KEY};
+
+#[test]
+fn test_create_function() {
+    let f = parse_create_function(
+        r#"
+        -- greatest common divisor
+        CREATE OR REPLACE FUNCTION Gcd(a INT, "B" integer) RETURNS bigint
+        LANGUAGE Python IMMUTABLE STRICT AS $$
+def gcd(a, b):
+    while b:
+        a, b = b, a % b
+    return a
+$$;
+        "#,
+    )
+    .unwrap();
+    assert!(f.or_replace);
+    assert_eq!(f.name, "gcd");
+    assert_eq!(
+        f.args,
+        [
+            Field::new("a", DataType::Int32, true),
+            Field::new("B", DataType::Int32, true),
+        ]
+    );
+    assert_eq!(f.return_type, Field::new("gcd", DataType::Int64, true));
+    assert!(!f.is_table_function);
+    assert_eq!(f.language, "python");
+    assert!(f.body.starts_with("\ndef gcd(a, b):\n"));
+    assert!(f.return_null_on_null_input);
+    assert!(!f.volatile);
+}
+
+#[test]
+fn test_create_function_options() {
+    // unnamed arguments, multi-word types and quoted body
+    let f = parse_create_function(
+        "create function my_schema.f(double precision, varchar(10)[], x timestamp without time zone)
+         returns numeric(10, 2) as 'export function f() { return ''1''; }'
+         called on null input language javascript",
+    )
+    .unwrap();
+    assert!(!f.or_replace);
+    assert_eq!(f.name, "my_schema.f");
+    assert_eq!(
+        f.args,
+        [
+            Field::new("$1", DataType::Float64, true),
+            Field::new(
+                "$2",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true
+            ),
+            Field::new("x", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        ]
+    );
+    assert_eq!(f.return_type.name(), "f");
+    assert_eq!(
+        f.return_type.metadata().get(EXTENSION_KEY).unwrap(),
+        DECIMAL_EXTENSION_NAME
+    );
+    assert_eq!(f.body, "export function f() { return '1'; }");
+    assert_eq!(f.language, "javascript");
+    assert!(!f.return_null_on_null_input);
+    assert!(f.volatile);
+
+    // table functions
+    let f = parse_create_function(
+        "CREATE FUNCTION series(int) RETURNS SETOF int LANGUAGE javascript AS $body$ $$ $body$",
+    )
+    .unwrap();
+    assert!(f.is_table_function);
+    assert_eq!(f.return_type, Field::new("series", DataType::Int32, true));
+    assert_eq!(f.body, " $$ ");
+
+    let f = parse_create_function(
+        "CREATE FUNCTION kv(varchar) RETURNS TABLE (key varchar, value int) LANGUAGE python AS ''",
+    )
+    .unwrap();
+    assert!(f.is_table_function);
+    let fields = Fields::from(vec![
+        Field::new("key", DataType::Utf8, true),
+        Field::new("value", DataType::Int32, true),
+    ]);
+    assert_eq!(
+        f.return_type,
+        Field::new("kv", DataType::Struct(fields), true)
+    );
+}
+
+#[test]
+fn test_create_function_errors() {
+    let cases = [
+        (
+            "CREATE TABLE t (a int)",
+            "expected FUNCTION, found TABLE at offset 7",
+        ),
+        (
+            "CREATE FUNCTION f(a foo) RETURNS int LANGUAGE python AS ''",
+            "Parser error: unknown type: foo",
+        ),
+        (
+            "CREATE FUNCTION f(int) RETURNS int AS ''",
+            "Parser error: LANGUAGE is required",
+        ),
+        (
+            "CREATE FUNCTION f(int) RETURNS int LANGUAGE python",
+            "Parser error: function body is required",
+        ),
+        (
+            "CREATE FUNCTION f(int RETURNS int",
+            "Parser error: expected ), found end of statement",
+        ),
+        (
+            "CREATE FUNCTION f(out a int) RETURNS int LANGUAGE python AS ''",
+            "Parser error: argument mode out is not supported",
+        ),
+        (
+            "CREATE FUNCTION f(a int = 1) RETURNS int LANGUAGE python AS ''",
+            "Parser error: default values of arguments are not supported",
+        ),
+        (
+            "CREATE FUNCTION f() RETURNS int LANGUAGE python AS $$ 1",
+            "Parser error: unterminated dollar-quoted string at offset 51",
+        ),
+        (
+            "CREATE FUNCTION f() RETURNS int LANGUAGE python AS '' foo",
+            "Parser error: expected end of statement, found foo at offset 54",
+        ),
+    ];
+    for (sql, msg) in cases {
+        let err = parse_create_function(sql).unwrap_err();
+        assert!(err.to_string().ends_with(msg), "{sql}: {err}");
+    }
+}
+
+#[test]
+fn test_type_names() {
+    let cases = [
+        ("BOOL", DataType::Boolean),
+        ("smallint", DataType::Int16),
+        ("Double  Precision", DataType::Float64),
+        ("character varying ( 255 )", DataType::Utf8),
+        ("text", DataType::Utf8),
+        ("large_string", DataType::LargeUtf8),
+        ("bytea(16)", DataType::FixedSizeBinary(16)),
+        (
+            "vector(3)",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 3),
+        ),
+        (
+            "int[]",
+            DataType::List(Arc::new(Field::new("item", DataType::Int32, true))),
+        ),
+    ];
+    for (name, expected) in cases {
+        assert_eq!(data_type(name).unwrap(), expected, "{name}");
+    }
+    let json = field("j", "jsonb").unwrap();
+    assert_eq!(json.data_type(), &DataType::Utf8);
+    assert_eq!(json.metadata().get(EXTENSION_KEY).unwrap(), "arrowudf.json");
+    assert_eq!(
+        data_type("unknown").unwrap_err().to_string(),
+        "Parser error: unknown type: unknown"
+    );
+}
+
+#[test]
+fn test_type_aliases() {
+    let mut aliases = TypeAliases::new();
+    aliases.insert("Double", "double precision").unwrap();
+    aliases.insert("string", "varchar").unwrap();
+    aliases.insert("text", "large_varchar").unwrap();
+    aliases.insert("variant", "json").unwrap();
+    assert!(aliases.insert("long", "unknown").is_err());
+
+    assert_eq!(aliases.resolve("DOUBLE"), "float8");
+    assert_eq!(aliases.resolve("string[][]"), "varchar[][]");
+    // builtin aliases can be overridden
+    assert_eq!(aliases.resolve("text"), "large_varchar");
+    assert_eq!(aliases.resolve("integer"), "int4");
+    assert_eq!(aliases.data_type("text").unwrap(), DataType::LargeUtf8);
+    let variant = aliases.field("v", "variant").unwrap();
+    assert_eq!(
+        variant.metadata().get(EXTENSION_KEY).unwrap(),
+        "arrowudf.json"
+    );
+
+    let function = aliases
+        .parse_create_function(
+            "CREATE FUNCTION f(s string, double) RETURNS text LANGUAGE python AS 'def f(s, d): ...'",
+        )
+        .unwrap();
+    assert_eq!(
+        function.args,
+        vec![
+            Field::new("s", DataType::Utf8, true),
+            Field::new("$2", DataType::Float64, true),
+        ]
+    );
+    assert_eq!(
+        function.return_type,
+        Field::new("f", DataType::LargeUtf8, true)
+    );
+    // without aliases
+    assert!(
+        parse_create_function("CREATE FUNCTION f(double) RETURNS text LANGUAGE python AS ''")
+            .is_err()
+    );
+
+    assert_eq!(aliases.remove("text").as_deref(), Some("large_varchar"));
+    assert_eq!(aliases.resolve("text"), "varchar");
+}
@@ -13,20 +13,31 @@
 // limitations under the License.
 
 use std::iter::Sum;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
 use std::{ops::Neg, sync::Arc};
 
+use arrow_array::builder::{LargeStringBuilder, ListBuilder};
 use arrow_array::cast::AsArray;
 use arrow_array::temporal_conversions::time_to_time64us;
-use arrow_array::types::{Date32Type, Int32Type};
+use arrow_array::types::{Date32Type, Float32Type, Int32Type, IntervalMonthDayNanoType};
 use arrow_array::{
-    BinaryArray, Date32Array, Int32Array, LargeBinaryArray, LargeStringArray, ListArray,
-    RecordBatch, StringArray, Time64MicrosecondArray,
+    Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, FixedSizeBinaryArray,
+    FixedSizeListArray, Float32Array, Float64Array, Int32Array, Int64Array,
+    IntervalMonthDayNanoArray, LargeBinaryArray, LargeStringArray, ListArray, RecordBatch,
+    RunArray, StringArray, StructArray, Time64MicrosecondArray, TimestampMicrosecondArray,
 };
+use arrow_buffer::NullBuffer;
 use arrow_cast::pretty::pretty_format_batches;
-use arrow_schema::{DataType, Field, Schema, TimeUnit};
-use arrow_udf::function;
-use arrow_udf::types::{Interval, StructType};
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use arrow_schema::{DataType, Field, IntervalUnit, Schema, TimeUnit};
+use arrow_udf::arith::Overflow;
+use arrow_udf::column::ColumnReader;
+use arrow_udf::testing::{assert_roundtrip_batch, random_field_array};
+use arrow_udf::types::{
+    Interval, StructType, DECIMAL_EXTENSION_NAME, EXTENSION_KEY, JSON_EXTENSION_NAME,
+};
+use arrow_udf::{aggregate, function, CancellationToken, EvalContext, PanicError, ScalarFunction};
+use chrono::{Duration, Months, NaiveDate, NaiveDateTime, NaiveTime};
+use proptest::prelude::*;
 use rust_decimal::Decimal;
 
 // test no return value
@@ -40,11 +51,7 @@ fn zero() -> i32 {
 }
 
 // test simd with 1 arguments
-#[function("neg(int2) -> int2")]
-#[function("neg(int4) -> int4")]
-#[function("neg(int8) -> int8")]
-#[function("neg(float4) -> float4")]
-#[function("neg(float8) -> float8")]
+#[function("neg(*numeric) -> *numeric")]
 #[function("neg(decimal) -> decimal")]
 fn neg<T: Neg<Output = T>>(x: T) -> T {
     x.neg()
@@ -63,17 +70,17 @@ fn gcd(mut a: i32, mut b: i32) -> i32 {
 #[function("identity(int2) -> int2")]
 #[function("identity(int4) -> int4")]
 #[function("identity(int8) -> int8")]
+#[function("identity(float2) -> float2")]
 #[function("identity(float4) -> float4")]
 #[function("identity(float8) -> float8")]
 #[function("identity(decimal) -> decimal")]
-#[function("identity(date) -> date")]
-#[function("identity(time) -> time")]
-#[function("identity(timestamp) -> timestamp")]
+#[function("identity({date, time, timestamp, interval}) -> {date, time, timestamp, interval}")]
 // #[function("identity(timestamptz) -> timestamptz")]
-#[function("identity(interval) -> interval")]
 #[function("identity(json) -> json")]
 #[function("identity(varchar) -> varchar")]
 #[function("identity(bytea) -> bytea")]
+#[function("identity(large_varchar) -> large_varchar")]
+#[function("identity(large_bytea) -> large_bytea")]
 fn identity<T>(x: T) -> T {
     x
 }
@@ -107,8 +114,22 @@ fn datetime(date: NaiveDate, time: NaiveTime) -> NaiveDateTime {
     NaiveDateTime::new(date, time)
 }
 
+#[function("add_interval(timestamp, interval) -> timestamp")]
+fn add_interval(ts: NaiveDateTime, interval: Interval) -> Option<NaiveDateTime> {
+    let months = Months::new(interval.months.unsigned_abs());
+    let ts = if interval.months >= 0 {
+        ts.checked_add_months(months)?
+    } else {
+        ts.checked_sub_months(months)?
+    };
+    ts.checked_add_signed(Duration::days(interval.days as i64))?
+        .checked_add_signed(Duration::nanoseconds(interval.nanos))
+}
+
 #[function("length(varchar) -> int")]
 #[function("length(bytea) -> int")]
+#[function("length(large_varchar) -> int")]
+#[function("length(large_bytea) -> int")]
 fn length(s: impl AsRef<[u8]>) -> i32 {
     s.as_ref().len() as i32
 }
@@ -141,6 +162,14 @@ fn to_string3(x: i32, output: &mut impl std::fmt::Write) {
     write!(output, "{}", x).unwrap();
 }
 
+#[function("repeat(varchar, int) -> varchar", output_size_hint = "input * 2")]
+#[function("repeat(large_varchar, int) -> large_varchar")]
+fn repeat(s: &str, n: i32, output: &mut impl std::fmt::Write) {
+    for _ in 0..n {
+        output.write_str(s).unwrap();
+    }
+}
+
 #[function("to_string4(int) -> varchar")]
 fn to_string4(x: i32, output: &mut impl std::fmt::Write) -> Option<()> {
     let x = usize::try_from(x).ok()?;
@@ -148,6 +177,35 @@ fn to_string4(x: i32, output: &mut impl std::fmt::Write) -> Option<()> {
     Some(())
 }
 
+static DOUBLE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[function("double_counted(int) -> int")]
+fn double_counted(x: i32) -> i32 {
+    DOUBLE_CALLS.fetch_add(1, Ordering::Relaxed);
+    x * 2
+}
+
+#[function("xor_digest(bytea(4), bytea(4)) -> bytea(4)")]
+fn xor_digest(a: &[u8], b: &[u8]) -> [u8; 4] {
+    std::array::from_fn(|i| a[i] ^ b[i])
+}
+
+#[function("normalize(vector(3)) -> vector(3)")]
+fn normalize(v: &[f32]) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    v.iter().map(|x| x / norm).collect()
+}
+
+#[function("repeat_vector(float4, int) -> vector(3)")]
+fn repeat_vector(x: f32, n: i32) -> Vec<f32> {
+    vec![x; n as usize]
+}
+
+#[function("repeat_vectors(int) -> setof vector(2)")]
+fn repeat_vectors(n: i32) -> impl Iterator<Item = Vec<f32>> {
+    (0..2).map(move |_| vec![1.0; n as usize])
+}
+
 #[function("bytes1(int) -> bytea")]
 fn bytes1(x: i32) -> Vec<u8> {
     vec![0; x as usize]
@@ -171,11 +229,7 @@ fn bytes3(x: i32) -> [u8; 10] {
 //     }
 // }
 
-#[function("array_sum(int2[]) -> int2")]
-#[function("array_sum(int4[]) -> int4")]
-#[function("array_sum(int8[]) -> int8")]
-#[function("array_sum(float4[]) -> float4")]
-#[function("array_sum(float8[]) -> float8")]
+#[function("array_sum(*numeric[]) -> *numeric")]
 fn array_sum<T: Sum + Copy>(s: &[T]) -> T {
     s.iter().cloned().sum()
 }
@@ -185,6 +239,16 @@ fn split(s: &str) -> impl Iterator<Item = &str> {
     s.split(',')
 }
 
+#[function("array_first(anyarray) -> any")]
+fn array_first(array: ArrayRef) -> Option<ArrayRef> {
+    (!array.is_empty()).then(|| array.slice(0, 1))
+}
+
+#[function("array_of(anyelement) -> anyarray")]
+fn array_of(x: ArrayRef) -> ArrayRef {
+    x
+}
+
 #[function("int2_array(int2[]) -> int2[]")]
 fn int2_array(_: &[i16]) -> impl Iterator<Item = i16> {
     [].into_iter()
@@ -220,6 +284,15 @@ fn string_array1(_: &StringArray) -> impl Iterator<Item = String> {
     [].into_iter()
 }
 
+#[function("large_string_array(large_varchar[]) -> large_varchar[]")]
+fn large_string_array(s: &LargeStringArray) -> impl Iterator<Item = String> {
+    s.iter()
+        .flatten()
+        .map(str::to_uppercase)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
 #[function("byte_array(bytea[]) -> bytea[]")]
 fn byte_array<'b>(_: &BinaryArray) -> impl Iterator<Item = &'b [u8]> {
     [].into_iter()
@@ -242,6 +315,90 @@ fn key_value(kv: &str) -> Option<KeyValue<'_>> {
     Some(KeyValue { key, value })
 }
 
+#[derive(StructType)]
+struct Point {
+    x: f64,
+    y: Option<f64>,
+}
+
+#[function("dist(struct Point) -> float8")]
+fn dist(p: Point) -> f64 {
+    p.x.hypot(p.y.unwrap_or(0.0))
+}
+
+#[function("dist_flat(struct Point) -> float8", flatten_input)]
+fn dist_flat(p: Point) -> f64 {
+    dist(p)
+}
+
+#[function("key_of(struct KeyValue) -> varchar", flatten_input)]
+fn key_of(kv: KeyValue<'_>) -> &str {
+    kv.key
+}
+
+#[function("split_host_port(varchar) -> (varchar, int)")]
+fn split_host_port(addr: &str) -> Option<(&str, Option<i32>)> {
+    match addr.rsplit_once(':') {
+        Some((host, port)) => Some((host, Some(port.parse().ok()?))),
+        None if addr.is_empty() => None,
+        None => Some((addr, None)),
+    }
+}
+
+struct Counter(AtomicI32);
+
+static COUNTER_FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+fn counter_init() -> Counter {
+    Counter(AtomicI32::new(0))
+}
+
+fn counter_finish(_: Counter) {
+    COUNTER_FINISHED.fetch_add(1, Ordering::SeqCst);
+}
+
+#[function(
+    "row_number(int) -> int",
+    init = "counter_init",
+    finish = "counter_finish"
+)]
+fn row_number(counter: &Counter, _: i32) -> i32 {
+    counter.0.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+#[function("random_int(int) -> int")]
+fn random_int(max: i32, ctx: &EvalContext) -> i32 {
+    use rand::Rng;
+    ctx.rng().gen_range(0..max)
+}
+
+#[function("query_time() -> timestamp")]
+fn query_time(ctx: &EvalContext) -> NaiveDateTime {
+    ctx.now()
+}
+
+#[function("audit(int) -> varchar")]
+fn audit(x: i32, ctx: &EvalContext) -> String {
+    let query_id = ctx.metadata("query_id").unwrap_or_default();
+    format!("{query_id}:{}:{}:{x}", ctx.batch_id(), ctx.row())
+}
+
+#[function("checked_inc(int) -> int")]
+fn checked_inc(x: i32) -> i32 {
+    x.checked_add(1).expect("overflow")
+}
+
+#[function("non_empty(varchar) -> varchar")]
+fn non_empty(s: &str) -> &str {
+    assert!(!s.is_empty(), "empty string");
+    s
+}
+
+#[function("wrapping_add(int, int) -> int", overflow = "wrap")]
+fn wrapping_add(x: i32, y: i32) -> arrow_udf::Result<i32> {
+    arrow_udf::arith::add(x, y)
+}
+
 #[derive(StructType)]
 struct StructOfAll {
     // FIXME: panic on 'StructBuilder and field_builders are of unequal lengths.'
@@ -312,6 +469,39 @@ fn json_array_elements(
     }
 }
 
+#[allow(dead_code)]
+#[function("max_value(int) -> int", batch_fn = "max_value_batch")]
+#[function(
+    "max_value_broadcast(int) -> int",
+    batch_fn = "max_value_batch",
+    broadcast
+)]
+fn max_value(x: i32) -> i32 {
+    x
+}
+
+fn max_value_batch(x: &Int32Array) -> Int32Array {
+    Int32Array::from(vec![arrow_arith::aggregate::max(x)])
+}
+
+#[function("word_count(varchar) -> table(word varchar, count int)")]
+fn word_count(s: &str) -> impl Iterator<Item = (&str, i32)> {
+    let mut counts = std::collections::BTreeMap::new();
+    for word in s.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts.into_iter()
+}
+
+#[function("parse_kv(varchar) ->> table(key varchar, value int)")]
+fn parse_kv(s: &str) -> Result<impl Iterator<Item = (&str, Option<i32>)>, String> {
+    let pairs = s
+        .split(',')
+        .map(|kv| kv.split_once('=').ok_or(format!("invalid pair: {kv}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(pairs.into_iter().map(|(k, v)| (k, v.parse().ok())))
+}
+
 #[function("many_args(int, int, int, int, int, int, int, int, int, int, int, int, int, int, int, int) -> int")]
 #[allow(clippy::too_many_arguments)]
 fn many_args(
@@ -389,219 +579,1723 @@ fn test_div() {
 }
 
 #[test]
-fn test_key_value() {
-    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
-    let arg0 = StringArray::from(vec!["a=b", "??"]);
+fn test_split_host_port() {
+    let schema = Schema::new(vec![Field::new("addr", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![
+        Some("localhost:8080"),
+        Some("example.com"),
+        Some("host:port"),
+        Some(""),
+        None,
+    ]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = key_value_varchar_struct_KeyValue_eval(&input).unwrap();
+    let output = split_host_port_varchar_varchar_int4_eval(&input).unwrap();
     assert_eq!(
         pretty_format_batches(std::slice::from_ref(&output))
             .unwrap()
             .to_string(),
         r#"
-+--------------------+
-| key_value          |
-+--------------------+
-| {key: a, value: b} |
-|                    |
-+--------------------+
++-------------------+-------------------+
+| split_host_port_0 | split_host_port_1 |
++-------------------+-------------------+
+| localhost         | 8080              |
+| example.com       |                   |
+|                   |                   |
+|                   |                   |
+|                   |                   |
++-------------------+-------------------+
 "#
         .trim()
     );
+    assert_eq!(output.column(0).null_count(), 3);
 }
 
 #[test]
-fn test_struct_of_all() {
-    let schema = Schema::new(vec![Field::new("int32", DataType::Int32, true)]);
-    let arg0 = Int32Array::from(vec![1]);
+fn test_init_finish() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = struct_of_all_struct_StructOfAll_eval(&input).unwrap();
+    // the context is shared by all calls
+    let row_number = row_number_int4_int4_eval_init();
+    let output = row_number(&input).unwrap();
+    let expected = Int32Array::from(vec![Some(1), None, Some(2)]);
+    assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
+    let output = row_number(&input).unwrap();
+    let expected = Int32Array::from(vec![Some(3), None, Some(4)]);
+    assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
+    assert_eq!(COUNTER_FINISHED.load(Ordering::SeqCst), 0);
+    drop(row_number);
+    assert_eq!(COUNTER_FINISHED.load(Ordering::SeqCst), 1);
+
+    // a new context is created for each call of the eval function
+    let output = row_number_int4_int4_eval(&input).unwrap();
+    let expected = Int32Array::from(vec![Some(1), None, Some(2)]);
+    assert_eq!(output.column(0).as_primitive::<Int32Type>(), &expected);
+    assert_eq!(COUNTER_FINISHED.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_eval_context() {
+    let schema = Schema::new(vec![Field::new("max", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1000); 10]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let now = NaiveDate::from_ymd_opt(2024, 1, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+
+    // the same seed generates the same values
+    let output1 = EvalContext::new(now, 42).scope(|| random_int_int4_int4_eval(&input).unwrap());
+    let output2 = EvalContext::new(now, 42).scope(|| random_int_int4_int4_eval(&input).unwrap());
+    assert_eq!(output1, output2);
+
+    // the random number generator continues across calls in the same context
+    let ctx = EvalContext::new(now, 42);
+    let output3 = ctx.scope(|| random_int_int4_int4_eval(&input).unwrap());
+    let output4 = ctx.scope(|| random_int_int4_int4_eval(&input).unwrap());
+    assert_eq!(output1, output3);
+    assert_ne!(output3, output4);
+
+    let input = RecordBatch::try_new_with_options(
+        Arc::new(Schema::empty()),
+        vec![],
+        &arrow_array::RecordBatchOptions::default().with_row_count(Some(2)),
+    )
+    .unwrap();
+    let output = ctx.scope(|| query_time_timestamp_eval(&input).unwrap());
+    let expected = TimestampMicrosecondArray::from(vec![now.timestamp_micros(); 2]);
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+
-| struct_of_all                                                                                                                                                                                                                                                  |
-+----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+
-| {b: , c: 1, d: 2, e: 3, f: 4.0, g: 5.0, h: 302e303036, i: 2022-04-08, j: 12:34:56.789012, k: 2022-04-08T12:34:56.789012, l: 0 years 7 mons 8 days 0 hours 0 mins 0.000000009 secs, m: {"key":"value"}, n: string, o: 0a0b0c, p: [a, b], q: {key: a, value: b}} |
-+----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+
-"#
-        .trim()
+        output
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap(),
+        &expected
     );
 }
 
 #[test]
-fn test_split() {
-    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
-    let arg0 = StringArray::from(vec!["a,b"]);
+fn test_eval_context_metadata() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
-    let output = split_varchar_varchararray_eval(&input).unwrap();
+    let ctx = EvalContext::default().with_metadata("query_id", "q1");
+    let output = ctx
+        .with_batch_id(7)
+        .scope(|| audit_int4_varchar_eval(&input).unwrap());
+    let expected = StringArray::from(vec![Some("q1:7:0:1"), None, Some("q1:7:2:3")]);
+    assert_eq!(output.column(0).as_string::<i32>(), &expected);
+}
+
+#[test]
+fn test_cancellation() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let token = CancellationToken::new();
+    let ctx = EvalContext::default().with_cancellation_token(token.clone());
+    let output = ctx.scope(|| to_string1_int4_varchar_eval(&input).unwrap());
+    assert_eq!(output.num_rows(), 3);
+
+    token.cancel();
+    assert!(ctx.is_cancelled());
+    let err = ctx
+        .scope(|| to_string1_int4_varchar_eval(&input))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Compute error: function call cancelled");
+    // table functions stop producing rows with an error
+    let mut iter = ctx.scope(|| range_int4_int4_eval(&input).unwrap());
+    let err = iter.next().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "Compute error: function call cancelled");
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_panic() {
+    let panic_of = |err: arrow_udf::Error| match err {
+        arrow_udf::Error::ExternalError(e) => e.downcast::<PanicError>().map(|e| *e).unwrap(),
+        e => panic!("expect panic error, got {e}"),
+    };
+
+    // vectorized function
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1, i32::MAX, 3]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let err = panic_of(checked_inc_int4_int4_eval(&input).unwrap_err());
+    assert_eq!(err.row(), 1);
+    assert_eq!(err.message(), "overflow");
+
+    // function called for each row
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a"), None, Some("")]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let err = panic_of(non_empty_varchar_varchar_eval(&input).unwrap_err());
+    assert_eq!(err.row(), 2);
+    assert_eq!(err.to_string(), "function panicked at row 2: empty string");
+
+    // null slots are skipped when looking for the panicking row
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::new(
+        vec![1, i32::MAX, i32::MAX].into(),
+        Some(NullBuffer::from(vec![true, false, true])),
+    );
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let err = panic_of(checked_inc_int4_int4_eval(&input).unwrap_err());
+    assert_eq!(err.row(), 2);
+
+    // table functions yield the panic and stop
+    let schema = Schema::new(vec![Field::new("n", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1, -1]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let mut iter = countdown_int4_int4_eval(&input).unwrap();
+    let err = panic_of(iter.next().unwrap().unwrap_err());
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+--------+
-| split  |
-+--------+
-| [a, b] |
-+--------+
-"#
-        .trim()
+        err.to_string(),
+        "function panicked at row 0: attempt to divide by zero"
     );
+    assert!(iter.next().is_none());
+
+    let arg0 = Int32Array::from(vec![-1]);
+    let input = RecordBatch::try_new(input.schema(), vec![Arc::new(arg0)]).unwrap();
+    let mut iter = countdown_int4_int4_eval(&input).unwrap();
+    let err = panic_of(iter.next().unwrap().unwrap_err());
+    assert_eq!(
+        err.to_string(),
+        "function panicked at row 0: negative count"
+    );
+    assert!(iter.next().is_none());
+}
+
+#[function("countdown(int) -> setof int")]
+fn countdown(n: i32) -> impl Iterator<Item = i32> {
+    assert!(n >= 0, "negative count");
+    (0..=n).rev().map(|i| 10 / i)
 }
 
 #[test]
-fn test_option_add() {
+fn test_overflow() {
     let schema = Schema::new(vec![
         Field::new("x", DataType::Int32, true),
         Field::new("y", DataType::Int32, true),
     ]);
-    let arg0 = Int32Array::from(vec![Some(1), Some(1), None, None]);
-    let arg1 = Int32Array::from(vec![Some(1), None, Some(1), None]);
+    let arg0 = Int32Array::from(vec![1, i32::MAX]);
+    let arg1 = Int32Array::from(vec![2, 1]);
     let input =
         RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
 
-    let output = option_add_int4_int4_int4_eval(&input).unwrap();
+    // the option of the function overrides the context
+    let ctx = EvalContext::default().with_overflow(Overflow::Error);
+    let output = ctx.scope(|| wrapping_add_int4_int4_int4_eval(&input).unwrap());
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+------------+
-| option_add |
-+------------+
-| 2          |
-| 1          |
-|            |
-|            |
-+------------+
-"#
-        .trim()
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![3, i32::MIN])
+    );
+    assert_eq!(output.column(1).null_count(), 2);
+
+    // helpers called outside of functions follow the context
+    assert!(arrow_udf::arith::add(i32::MAX, 1).is_err());
+    let ctx = EvalContext::default().with_overflow(Overflow::Saturate);
+    assert_eq!(
+        ctx.scope(|| arrow_udf::arith::add(i32::MAX, 1)).unwrap(),
+        i32::MAX
     );
 }
 
 #[test]
-fn test_array_sum() {
-    let schema = Schema::new(vec![Field::new(
-        "x",
-        DataType::new_list(DataType::Int32, true),
-        true,
-    )]);
-    let arg0 = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
-        Some(vec![Some(0), Some(1), Some(2)]),
-        None,
-        Some(vec![Some(3), None, Some(5)]),
-        Some(vec![Some(6), Some(7)]),
+fn test_fixed_size_binary() {
+    let schema = Schema::new(vec![
+        Field::new("a", DataType::FixedSizeBinary(4), true),
+        Field::new("b", DataType::FixedSizeBinary(4), true),
     ]);
-    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let arg0 = FixedSizeBinaryArray::from(vec![Some(&[1, 2, 3, 4][..]), None, Some(&[0; 4])]);
+    let arg1 = FixedSizeBinaryArray::from(vec![
+        Some(&[1, 1, 1, 1][..]),
+        Some(&[0; 4]),
+        Some(&[255; 4]),
+    ]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
 
-    let output = array_sum_int4array_int4_eval(&input).unwrap();
+    let output = xor_digest_bytea_4_bytea_4_bytea_4_eval(&input).unwrap();
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+-----------+
-| array_sum |
-+-----------+
-| 3         |
-|           |
-| 8         |
-| 13        |
-+-----------+
-"#
-        .trim()
+        output.schema().field(0).data_type(),
+        &DataType::FixedSizeBinary(4)
     );
+    let expected = FixedSizeBinaryArray::from(vec![Some(&[0, 3, 2, 5][..]), None, Some(&[255; 4])]);
+    assert_eq!(output.column(0).as_fixed_size_binary(), &expected);
 }
 
 #[test]
-fn test_temporal() {
+fn test_vector_dimension_mismatch() {
     let schema = Schema::new(vec![
-        Field::new("date", DataType::Date32, true),
-        Field::new("time", DataType::Time64(TimeUnit::Microsecond), true),
+        Field::new("x", DataType::Float32, true),
+        Field::new("n", DataType::Int32, true),
     ]);
-    let arg0 = Date32Array::from(vec![Date32Type::from_naive_date(
-        NaiveDate::from_ymd_opt(2022, 4, 8).unwrap(),
-    )]);
-    let arg1 = Time64MicrosecondArray::from(vec![time_to_time64us(
-        NaiveTime::from_hms_micro_opt(12, 34, 56, 789_012).unwrap(),
-    )]);
+    let arg0 = Float32Array::from(vec![1.0, 2.0]);
+    let arg1 = Int32Array::from(vec![3, 2]);
     let input =
         RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
 
-    let output = datetime_date_time_timestamp_eval(&input).unwrap();
+    let err = repeat_vector_float4_int4_vector_3_eval(&input).unwrap_err();
     assert_eq!(
-        pretty_format_batches(std::slice::from_ref(&output))
-            .unwrap()
-            .to_string(),
-        r#"
-+----------------------------+
-| datetime                   |
-+----------------------------+
-| 2022-04-08T12:34:56.789012 |
-+----------------------------+
-"#
-        .trim()
+        err.to_string(),
+        "Invalid argument error: expect a vector of dimension 3 in row 1, got 2"
     );
 }
 
 #[test]
-fn test_decimal() {
-    let schema = Schema::new(vec![Field::new("d", DataType::LargeBinary, true)]);
-    let arg0 = LargeBinaryArray::from(vec![&b"0.001"[..]]);
-    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
-
-    let output = identity_decimal_decimal_eval(&input).unwrap();
+fn test_vector_table_function() {
+    let schema = Schema::new(vec![Field::new("n", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![2]);
+    let input = RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(arg0)]).unwrap();
+    let output = repeat_vectors_int4_vector_2_eval(&input)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
     assert_eq!(
         pretty_format_batches(std::slice::from_ref(&output))
             .unwrap()
             .to_string(),
         r#"
-+------------+
-| identity   |
-+------------+
-| 302e303031 |
-+------------+
++-----+----------------+
+| row | repeat_vectors |
++-----+----------------+
+| 0   | [1.0, 1.0]     |
+| 0   | [1.0, 1.0]     |
++-----+----------------+
 "#
         .trim()
     );
+
+    // a vector of a wrong dimension aborts the call
+    let arg0 = Int32Array::from(vec![2, 3]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let mut iter = repeat_vectors_int4_vector_2_eval(&input).unwrap();
+    let err = iter.next().unwrap().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: expect a vector of dimension 2 in row 1, got 3"
+    );
+    assert!(iter.next().is_none());
 }
 
 #[test]
-fn test_json() {
-    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
-    let arg0 = Int32Array::from(vec![Some(1), None]);
+fn test_ffi_table_function_error() {
+    use arrow_udf::ffi::{
+        dealloc, record_batch_iterator_drop, record_batch_iterator_next, table_wrapper, CSlice,
+        RecordBatchIter,
+    };
+
+    let schema = Schema::new(vec![Field::new("n", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![2, 3]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let mut buf = vec![];
+    let mut writer = arrow_ipc::writer::FileWriter::try_new(&mut buf, &input.schema()).unwrap();
+    writer.write(&input).unwrap();
+    writer.finish().unwrap();
+    drop(writer);
+
+    // the error of the iterator is returned instead of ending the iteration
+    let mut out = CSlice {
+        ptr: std::ptr::null(),
+        len: 0,
+    };
+    unsafe {
+        let errno = table_wrapper(
+            repeat_vectors_int4_vector_2_eval,
+            buf.as_ptr(),
+            buf.len(),
+            &mut out,
+        );
+        assert_eq!(errno, 0);
+        let iter = out.ptr as *mut RecordBatchIter;
+        let errno = record_batch_iterator_next(iter, &mut out);
+        assert_eq!(errno, -1);
+        let msg = std::str::from_utf8(std::slice::from_raw_parts(out.ptr, out.len)).unwrap();
+        assert_eq!(
+            msg,
+            "Invalid argument error: expect a vector of dimension 2 in row 1, got 3"
+        );
+        dealloc(out.ptr as *mut u8, out.len, 1);
+        record_batch_iterator_drop(iter);
+    }
+}
 
-    let output = to_json_int4_json_eval(&input).unwrap();
+#[test]
+fn test_vector() {
+    let item = Arc::new(Field::new("item", DataType::Float32, true));
+    let schema = Schema::new(vec![Field::new(
+        "v",
+        DataType::FixedSizeList(item.clone(), 3),
+        true,
+    )]);
+    let arg0 = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+        vec![
+            Some(vec![Some(3.0), Some(4.0), Some(0.0)]),
+            None,
+            Some(vec![Some(0.0), Some(0.0), Some(2.0)]),
+        ],
+        3,
+    );
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = normalize_vector_3_vector_3_eval(&input).unwrap();
+    assert_eq!(
+        output.schema().field(0).data_type(),
+        &DataType::FixedSizeList(item, 3)
+    );
     assert_eq!(
         pretty_format_batches(std::slice::from_ref(&output))
             .unwrap()
             .to_string(),
         r#"
-+---------+
-| to_json |
-+---------+
-| 1       |
-| null    |
-+---------+
++-----------------+
+| normalize       |
++-----------------+
+| [0.6, 0.8, 0.0] |
+|                 |
+| [0.0, 0.0, 1.0] |
++-----------------+
 "#
         .trim()
     );
 }
 
 #[test]
-fn test_range() {
-    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
-    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
-    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+fn test_run_end_encoded() {
+    let ree = |values: Vec<i32>, run_ends: Vec<i32>| -> ArrayRef {
+        let run_ends = Int32Array::from(run_ends);
+        Arc::new(RunArray::try_new(&run_ends, &Int32Array::from(values)).unwrap())
+    };
+    let ree_field = |name| {
+        Field::new(
+            name,
+            DataType::RunEndEncoded(
+                Arc::new(Field::new("run_ends", DataType::Int32, false)),
+                Arc::new(Field::new("values", DataType::Int32, true)),
+            ),
+            true,
+        )
+    };
+
+    // evaluated once per run
+    let schema = Schema::new(vec![ree_field("x")]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![ree(vec![1, 2], vec![3, 5])]).unwrap();
+    let output = double_counted_int4_int4_eval(&input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![2, 2, 2, 4, 4])
+    );
+    assert_eq!(DOUBLE_CALLS.load(Ordering::Relaxed), 2);
 
-    let output = range_int4_int4_eval(&input).unwrap().next().unwrap();
+    let output = double_counted_int4_int4_eval(&input.slice(1, 3)).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![2, 2, 4])
+    );
+
+    // decoded if mixed with other columns
+    let schema = Schema::new(vec![ree_field("x"), Field::new("y", DataType::Int32, true)]);
+    let arg1 = Int32Array::from(vec![Some(4), Some(3), None, Some(10), Some(5)]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![ree(vec![2, 5], vec![3, 5]), Arc::new(arg1)],
+    )
+    .unwrap();
+    let output = gcd_int4_int4_int4_eval(&input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(2), Some(1), None, Some(5), Some(5)])
+    );
+}
+
+#[test]
+fn test_repeat() {
+    let schema = Schema::new(vec![
+        Field::new("s", DataType::Utf8, true),
+        Field::new("n", DataType::Int32, true),
+    ]);
+    let arg0 = StringArray::from(vec![Some("ab"), Some("c"), None, Some("d")]);
+    let arg1 = Int32Array::from(vec![Some(2), Some(3), Some(1), None]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = repeat_varchar_int4_varchar_eval(&input).unwrap();
+    let expected = StringArray::from(vec![Some("abab"), Some("ccc"), None, None]);
+    assert_eq!(output.column(0).as_string::<i32>(), &expected);
+}
+
+#[test]
+fn test_large_string() {
+    let schema = Schema::new(vec![
+        Field::new("s", DataType::LargeUtf8, true),
+        Field::new("n", DataType::Int32, true),
+    ]);
+    let arg0 = LargeStringArray::from(vec![Some("ab"), Some("c"), None, Some("d")]);
+    let arg1 = Int32Array::from(vec![Some(2), Some(3), Some(1), None]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = repeat_large_varchar_int4_large_varchar_eval(&input).unwrap();
+    assert_eq!(output.schema().field(0).data_type(), &DataType::LargeUtf8);
+    let expected = LargeStringArray::from(vec![Some("abab"), Some("ccc"), None, None]);
+    assert_eq!(output.column(0).as_string::<i64>(), &expected);
+
+    let schema = Schema::new(vec![Field::new("b", DataType::LargeBinary, true)]);
+    let arg0 = LargeBinaryArray::from(vec![Some(b"abc".as_ref()), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let output = length_large_bytea_int4_eval(&input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(3), None])
+    );
+
+    let mut builder = ListBuilder::new(LargeStringBuilder::new());
+    builder.append_value([Some("x"), None]);
+    let list: ArrayRef = Arc::new(builder.finish());
+    let schema = Schema::new(vec![Field::new("l", list.data_type().clone(), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![list]).unwrap();
+    let output = large_string_array_large_varchararray_large_varchararray_eval(&input).unwrap();
+    let output = output.column(0).as_list::<i32>().value(0);
+    assert_eq!(
+        output.as_string::<i64>(),
+        &LargeStringArray::from(vec!["X"])
+    );
+}
+
+#[test]
+fn test_key_value() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec!["a=b", "??"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = key_value_varchar_struct_KeyValue_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++--------------------+
+| key_value          |
++--------------------+
+| {key: a, value: b} |
+|                    |
++--------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_struct_argument() {
+    let fields = Point::fields();
+    let x = Float64Array::from(vec![Some(3.0), None, Some(3.0), Some(1.0)]);
+    let y = Float64Array::from(vec![Some(4.0), Some(4.0), None, Some(1.0)]);
+    let columns: Vec<ArrayRef> = vec![Arc::new(x), Arc::new(y)];
+    let expected = Float64Array::from(vec![Some(5.0), None, Some(3.0), None]);
+
+    // a struct column, whose last row is null
+    let nulls = NullBuffer::from(vec![true, true, true, false]);
+    let array = StructArray::new(fields.clone(), columns.clone(), Some(nulls));
+    let schema = Schema::new(vec![Field::new("p", DataType::Struct(fields), true)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(array)]).unwrap();
+    let output = dist_struct_Point_float8_eval(&input).unwrap();
+    assert_eq!(output.column(0).as_ref(), &expected);
+
+    // the fields as separate columns
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Float64, true),
+        Field::new("y", DataType::Float64, true),
+    ]);
+    let input = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+    let output = dist_flat_struct_Point_float8_eval(&input).unwrap();
+    let expected = Float64Array::from(vec![Some(5.0), None, Some(3.0), Some(2f64.sqrt())]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+    // the number of columns must match the fields
+    let input = input.project(&[0]).unwrap();
+    assert!(dist_flat_struct_Point_float8_eval(&input).is_err());
+
+    // run-end encoded fields are evaluated once per run
+    let ree = |values: Vec<f64>| -> ArrayRef {
+        let run_ends = Int32Array::from(vec![2, 3]);
+        Arc::new(RunArray::try_new(&run_ends, &Float64Array::from(values)).unwrap())
+    };
+    let ree_field = |name| {
+        let run_ends = Arc::new(Field::new("run_ends", DataType::Int32, false));
+        let values = Arc::new(Field::new("values", DataType::Float64, true));
+        Field::new(name, DataType::RunEndEncoded(run_ends, values), true)
+    };
+    let schema = Schema::new(vec![ree_field("x"), ree_field("y")]);
+    let columns = vec![ree(vec![3.0, 6.0]), ree(vec![4.0, 8.0])];
+    let input = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+    let output = dist_flat_struct_Point_float8_eval(&input).unwrap();
+    let expected = Float64Array::from(vec![5.0, 5.0, 10.0]);
+    assert_eq!(output.column(0).as_ref(), &expected);
+
+    // flattened functions are found by the types of the fields
+    let sig = arrow_udf::sig::REGISTRY
+        .get("key_of", &[DataType::Utf8, DataType::Utf8], &DataType::Utf8)
+        .unwrap();
+    let schema = Schema::new(vec![
+        Field::new("key", DataType::Utf8, true),
+        Field::new("value", DataType::Utf8, true),
+    ]);
+    let key = StringArray::from(vec![Some("a"), Some("b")]);
+    let value = StringArray::from(vec![Some("1"), None]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(key), Arc::new(value)]).unwrap();
+    let output = sig.function.eval(&input).unwrap();
+    assert_eq!(
+        output.column(0).as_ref(),
+        &StringArray::from(vec![Some("a"), None])
+    );
+}
+
+#[test]
+fn test_struct_of_all() {
+    let schema = Schema::new(vec![Field::new("int32", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = struct_of_all_struct_StructOfAll_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+
+| struct_of_all                                                                                                                                                                                                                                             |
++-----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+
+| {b: , c: 1, d: 2, e: 3, f: 4.0, g: 5.0, h: 0.006, i: 2022-04-08, j: 12:34:56.789012, k: 2022-04-08T12:34:56.789012, l: 0 years 7 mons 8 days 0 hours 0 mins 0.000000009 secs, m: {"key":"value"}, n: string, o: 0a0b0c, p: [a, b], q: {key: a, value: b}} |
++-----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_split() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec!["a,b"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = split_varchar_varchararray_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++--------+
+| split  |
++--------+
+| [a, b] |
++--------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_eval_filtered() {
+    use arrow_array::BooleanArray;
+    use arrow_udf::eval::eval_filtered;
+
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from(vec![Some(1), Some(-1), Some(4), None]);
+    let arg1 = Int32Array::from(vec![Some(0), Some(-1), Some(2), None]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+    // skip the row that divides by zero
+    let selection = BooleanArray::from(vec![Some(false), Some(true), None, Some(true)]);
+
+    let output = eval_filtered(&input, &selection, div_int4_int4_int4_eval).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-----+-------+
+| div | error |
++-----+-------+
+|     |       |
+| 1   |       |
+|     |       |
+|     |       |
++-----+-------+
+"#
+        .trim()
+    );
+
+    let selection = BooleanArray::from(vec![true]);
+    assert!(eval_filtered(&input, &selection, div_int4_int4_int4_eval).is_err());
+}
+
+#[test]
+fn test_eval_chunked() {
+    use arrow_udf::eval::eval_chunked;
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1000; 2000]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let max_memory = 1 << 20;
+    let outputs = eval_chunked(&input, max_memory, bytes1_int4_bytea_eval)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(outputs.len() > 1);
+    assert_eq!(outputs.iter().map(|b| b.num_rows()).sum::<usize>(), 2000);
+    for output in &outputs {
+        // the limit is soft
+        assert!(output.get_array_memory_size() < max_memory * 2);
+    }
+    let output = arrow_select::concat::concat_batches(&outputs[0].schema(), &outputs).unwrap();
+    assert_eq!(output, bytes1_int4_bytea_eval(&input).unwrap());
+}
+
+#[test]
+fn test_eval_broadcast() {
+    use arrow_udf::eval::eval_broadcast;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("s", DataType::Utf8, true),
+        Field::new("start", DataType::Int32, true),
+    ]));
+    let s: ArrayRef = Arc::new(StringArray::from(vec![Some("hello"), None, Some("arrow")]));
+    let start: ArrayRef = Arc::new(Int32Array::from(vec![2]));
+    let output = eval_broadcast(
+        schema.clone(),
+        &[s.clone(), start],
+        substring_varchar_int4_varchar_eval,
+    )
+    .unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-----------+
+| substring |
++-----------+
+| llo       |
+|           |
+| row       |
++-----------+
+"#
+        .trim()
+    );
+
+    // all columns of length 1
+    let s1: ArrayRef = Arc::new(StringArray::from(vec!["hello"]));
+    let start: ArrayRef = Arc::new(Int32Array::from(vec![1]));
+    let output = eval_broadcast(
+        schema.clone(),
+        &[s1, start],
+        substring_varchar_int4_varchar_eval,
+    )
+    .unwrap();
+    assert_eq!(output.num_rows(), 1);
+
+    // mismatched lengths
+    let start: ArrayRef = Arc::new(Int32Array::from(vec![1, 2]));
+    let err = eval_broadcast(schema, &[s, start], substring_varchar_int4_varchar_eval).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: expect column 1 of length 1 or 3, got 2"
+    );
+}
+
+#[test]
+fn test_eval_distinct() {
+    use arrow_array::DictionaryArray;
+    use arrow_udf::eval::eval_distinct;
+
+    // count the rows evaluated by the function
+    let evaluated = AtomicUsize::new(0);
+    let eval = |input: &RecordBatch| {
+        evaluated.fetch_add(input.num_rows(), Ordering::Relaxed);
+        length_varchar_int4_eval(input)
+    };
+
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![
+        Some("a"),
+        Some("bb"),
+        Some("a"),
+        None,
+        Some("bb"),
+        Some("a"),
+    ]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let expected = length_varchar_int4_eval(&input).unwrap();
+    let output = eval_distinct(&input, 0.5, eval).unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(evaluated.swap(0, Ordering::Relaxed), 3);
+
+    // too many distinct rows
+    let output = eval_distinct(&input, 0.3, eval).unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(evaluated.swap(0, Ordering::Relaxed), 6);
+
+    // dictionary values are evaluated with a null
+    let schema = Schema::new(vec![Field::new(
+        "s",
+        DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+        true,
+    )]);
+    let arg0: DictionaryArray<Int32Type> = vec![Some("a"), None, Some("bb"), Some("a")]
+        .into_iter()
+        .collect();
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let output = eval_distinct(&input, 1.0, eval).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![Some(1), None, Some(2), Some(1)])
+    );
+    assert_eq!(evaluated.swap(0, Ordering::Relaxed), 3);
+}
+
+#[test]
+fn test_eval_selectivity() {
+    use arrow_udf::eval::eval_selectivity;
+
+    let schema = Schema::new(vec![Field::new("x", DataType::Boolean, true)]);
+    let values = (0..2000).map(|i| match i {
+        0..=99 => Some(false),
+        100..=109 => None,
+        _ => Some(i % 2 == 0),
+    });
+    let arg0 = BooleanArray::from_iter(values);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let (selection, stats) = eval_selectivity(&input, None, identity_boolean_boolean_eval).unwrap();
+    assert_eq!(stats.evaluated, 2000);
+    assert_eq!(stats.matched, 945);
+    assert_eq!(stats.nulls, 10);
+    assert_eq!(stats.skipped, 0);
+    assert_eq!(selection.true_count(), 945);
+
+    // stop early after enough matches
+    let (partial, stats) =
+        eval_selectivity(&input, Some(20), identity_boolean_boolean_eval).unwrap();
+    assert!(stats.matched >= 20);
+    assert!(stats.skipped > 0);
+    assert_eq!(stats.evaluated + stats.skipped, 2000);
+    assert_eq!(partial.len(), 2000);
+    assert_eq!(partial.true_count(), stats.matched);
+    assert_eq!(
+        partial.slice(0, stats.evaluated),
+        selection.slice(0, stats.evaluated)
+    );
+
+    // a batch smaller than the first chunk
+    let schema = Schema::new(vec![Field::new("x", DataType::Boolean, true)]);
+    let arg0 = BooleanArray::from(vec![false, true, false]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let (selection, stats) =
+        eval_selectivity(&input, Some(10), identity_boolean_boolean_eval).unwrap();
+    assert_eq!((stats.evaluated, stats.matched, stats.skipped), (3, 1, 0));
+    assert_eq!(selection, BooleanArray::from(vec![false, true, false]));
+
+    // the output must be boolean
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![1, 2]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let err = eval_selectivity(&input, None, neg_int4_int4_eval).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: expect a boolean output, got Int32"
+    );
+}
+
+#[test]
+#[cfg(feature = "ffi_stream")]
+fn test_ffi_stream() {
+    use arrow_array::{RecordBatchIterator, RecordBatchReader};
+    use arrow_udf::ffi_stream::{
+        arrowudf_call_stream, ArrowArrayStreamReader, FFI_ArrowArrayStream,
+    };
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]));
+    let batch = |x: Vec<i32>, y: Vec<i32>| {
+        let columns: Vec<ArrayRef> =
+            vec![Arc::new(Int32Array::from(x)), Arc::new(Int32Array::from(y))];
+        RecordBatch::try_new(schema.clone(), columns)
+    };
+    let batches = vec![batch(vec![15, 6], vec![25, 4]), batch(vec![7], vec![3])];
+    let reader = RecordBatchIterator::new(batches, schema.clone());
+
+    let mut input = FFI_ArrowArrayStream::new(Box::new(reader));
+    let mut output = FFI_ArrowArrayStream::empty();
+    let code = unsafe { arrowudf_call_stream("gcd".as_ptr(), 3, &mut input, &mut output) };
+    assert_eq!(code, 0);
+    let output = ArrowArrayStreamReader::try_new(output).unwrap();
+    assert_eq!(output.schema().field(0).name(), "gcd");
+    let values = output
+        .map(|batch| batch.unwrap().column(0).as_primitive::<Int32Type>().clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        [Int32Array::from(vec![5, 2]), Int32Array::from(vec![1])]
+    );
+
+    // function not found
+    let reader = RecordBatchIterator::new(vec![], schema.clone());
+    let mut input = FFI_ArrowArrayStream::new(Box::new(reader));
+    let mut output = FFI_ArrowArrayStream::empty();
+    let code = unsafe { arrowudf_call_stream("gcd2".as_ptr(), 4, &mut input, &mut output) };
+    assert_eq!(code, -1);
+    let mut output = ArrowArrayStreamReader::try_new(output).unwrap();
+    let err = output.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("function not found"), "{err}");
+}
+
+#[test]
+fn test_eval_scalar() {
+    use arrow_array::{Array, Datum, Scalar};
+
+    let scalar = |v: Option<i32>| Scalar::new(Arc::new(Int32Array::from(vec![v])) as ArrayRef);
+    let value = |v: arrow_udf::ScalarValue| v.get().0.as_primitive::<Int32Type>().clone();
+
+    let output = gcd_int4_int4_int4_eval_scalar(&[scalar(Some(15)), scalar(Some(25))]).unwrap();
+    assert_eq!(value(output).value(0), 5);
+
+    let output = gcd_int4_int4_int4_eval_scalar(&[scalar(Some(15)), scalar(None)]).unwrap();
+    assert!(value(output).is_null(0));
+
+    let err = div_int4_int4_int4_eval_scalar(&[scalar(Some(1)), scalar(Some(0))]).unwrap_err();
+    assert_eq!(err.to_string(), "Compute error: division by zero");
+
+    let err = gcd_int4_int4_int4_eval_scalar(&[scalar(Some(1))]).unwrap_err();
+    assert!(err.to_string().contains("expect 2 arguments"));
+
+    // polymorphic
+    let input = Scalar::new(Arc::new(StringArray::from(vec!["a"])) as ArrayRef);
+    let output = array_of_any_anyarray_eval_scalar(&[input]).unwrap();
+    assert_eq!(
+        output.get().0.data_type(),
+        &DataType::new_list(DataType::Utf8, true)
+    );
+}
+
+static NEXT_ID: AtomicI32 = AtomicI32::new(0);
+
+#[function("next_id() -> int", volatile)]
+fn next_id() -> i32 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[test]
+#[cfg(feature = "global_registry")]
+fn test_eval_scalar_volatile() {
+    use arrow_udf::sig::REGISTRY;
+
+    // volatile functions can not be folded into constants
+    let sig = REGISTRY.get("next_id", &[], &DataType::Int32).unwrap();
+    assert!(sig.volatile);
+    assert!(sig.eval_scalar.is_none());
+}
+
+#[test]
+fn test_polymorphic() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a,b"), Some(""), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = array_of_any_anyarray_eval(&input).unwrap();
+    assert_eq!(
+        output.schema().field(0).data_type(),
+        &DataType::new_list(DataType::Utf8, true)
+    );
+    let output = array_first_anyarray_any_eval(&output).unwrap();
+    assert_eq!(output.schema().field(0).data_type(), &DataType::Utf8);
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-------------+
+| array_first |
++-------------+
+| a,b         |
+|             |
+|             |
++-------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+#[cfg(feature = "global_registry")]
+fn test_type_infer() {
+    use arrow_udf::sig::REGISTRY;
+
+    let args = [DataType::new_list(DataType::Int32, true)];
+    let sig = REGISTRY
+        .get("array_first", &args, &DataType::Int32)
+        .unwrap();
+    assert_eq!((sig.type_infer)(&args).unwrap(), DataType::Int32);
+
+    let sig = REGISTRY
+        .get("neg", &[DataType::Int32], &DataType::Int32)
+        .unwrap();
+    assert_eq!(
+        (sig.type_infer)(&[DataType::Int32]).unwrap(),
+        DataType::Int32
+    );
+}
+
+#[test]
+#[cfg(feature = "global_registry")]
+fn test_signature_builder() {
+    use arrow_udf::sig::{FunctionSignature, SigDataType};
+
+    fn identity(input: &RecordBatch) -> arrow_udf::Result<RecordBatch> {
+        Ok(input.clone())
+    }
+
+    let sig = FunctionSignature::builder()
+        .name("remote.identity")
+        .version(2)
+        .arg(DataType::Int32)
+        .returns(DataType::Int32)
+        .volatile(true)
+        .scalar(identity)
+        .build()
+        .unwrap();
+    assert_eq!(sig.qualified_name(), "remote.identity");
+    assert_eq!(sig.version, 2);
+    assert_eq!(sig.arg_types, [SigDataType::Exact(DataType::Int32)]);
+    assert!(sig.volatile);
+    assert_eq!(
+        (sig.type_infer)(&[DataType::Int32]).unwrap(),
+        DataType::Int32
+    );
+
+    // polymorphic return types require type inference
+    let err = FunctionSignature::builder()
+        .name("identity")
+        .arg(SigDataType::Any)
+        .returns(SigDataType::Any)
+        .scalar(identity)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: type inference is required for polymorphic return type of function identity"
+    );
+    let sig = FunctionSignature::builder()
+        .name("identity")
+        .arg(SigDataType::Any)
+        .returns(SigDataType::Any)
+        .type_infer(|args| Ok(args[0].clone()))
+        .scalar(identity)
+        .build()
+        .unwrap();
+    assert_eq!((sig.type_infer)(&[DataType::Utf8]).unwrap(), DataType::Utf8);
+
+    let err = FunctionSignature::builder()
+        .name("identity")
+        .returns(DataType::Int32)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: function identity has no implementation"
+    );
+    assert!(FunctionSignature::builder()
+        .name("")
+        .returns(DataType::Int32)
+        .scalar(identity)
+        .build()
+        .is_err());
+
+    // signatures of `#[function]` are marked volatile by the option
+    let sig = arrow_udf::sig::REGISTRY
+        .get("neg", &[DataType::Int32], &DataType::Int32)
+        .unwrap();
+    assert!(!sig.volatile);
+}
+
+#[test]
+#[cfg(feature = "global_registry")]
+fn test_register_scalar() {
+    use arrow_udf::sig::FunctionRegistry;
+
+    let mut registry = FunctionRegistry::default();
+    let offset = 10;
+    registry
+        .register_scalar(
+            "math.add_offset",
+            &[DataType::Int32],
+            DataType::Int32,
+            move |input| {
+                let array: Int32Array = input
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .unary(|x| x + offset);
+                Ok(Arc::new(array))
+            },
+        )
+        .unwrap();
+    registry
+        .register_scalar("truncate", &[DataType::Int32], DataType::Int32, |input| {
+            Ok(input.column(0).slice(0, 1))
+        })
+        .unwrap();
+
+    let schema = Schema::new(vec![field(DataType::Int32)]);
+    let arg = Int32Array::from(vec![Some(1), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg)]).unwrap();
+
+    assert!(registry
+        .get("add_offset", &[DataType::Int32], &DataType::Int32)
+        .is_none());
+    let sig = registry
+        .get("math.add_offset", &[DataType::Int32], &DataType::Int32)
+        .unwrap();
+    assert!(sig.function.is_scalar());
+    assert!(sig.function.as_scalar().is_none());
+    let output = sig.function.eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++------------+
+| add_offset |
++------------+
+| 11         |
+|            |
++------------+
+"#
+        .trim()
+    );
+
+    let sig = registry
+        .get("truncate", &[DataType::Int32], &DataType::Int32)
+        .unwrap();
+    let err = sig.function.eval(&input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: expect 2 rows in the output, got 1"
+    );
+}
+
+#[function("text.reverse(varchar) -> varchar", alias = "rev, text.rev")]
+fn reverse(s: &str) -> String {
+    s.chars().rev().collect()
+}
+
+#[test]
+fn test_schema_and_alias() {
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("abc"), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = text_reverse_varchar_varchar_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++---------+
+| reverse |
++---------+
+| cba     |
+|         |
++---------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+#[cfg(feature = "global_registry")]
+fn test_registry_schema() {
+    use arrow_udf::sig::REGISTRY;
+
+    let args = [DataType::Utf8];
+    for name in ["text.reverse", "rev", "text.rev"] {
+        let sig = REGISTRY.get(name, &args, &DataType::Utf8).unwrap();
+        assert!(sig.has_name(name));
+    }
+    let sig = REGISTRY.get("text.rev", &args, &DataType::Utf8).unwrap();
+    assert_eq!(sig.schema.as_deref(), Some("text"));
+    assert_eq!(sig.name, "rev");
+    assert_eq!(sig.qualified_name(), "text.rev");
+
+    // unqualified names only find functions without a schema
+    assert!(REGISTRY.get("reverse", &args, &DataType::Utf8).is_none());
+    assert!(REGISTRY
+        .get("other.reverse", &args, &DataType::Utf8)
+        .is_none());
+}
+
+#[test]
+fn test_replay() {
+    use arrow_udf::replay::{load, replay, Recorder};
+
+    let dir = std::env::temp_dir().join(format!("arrow-udf-test-replay-{}", std::process::id()));
+    let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, true)]));
+    let batch = |x: Vec<i32>| {
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(x))]).unwrap()
+    };
+    let f = |input: &RecordBatch| {
+        if input.num_rows() == 0 {
+            return Err(arrow_udf::Error::ComputeError("empty input".into()));
+        }
+        neg_int4_int4_eval(input)
+    };
+
+    let recorder = Recorder::new(&dir).unwrap();
+    recorder.call(&batch(vec![1, 2]), f).unwrap();
+    recorder.call(&batch(vec![]), f).unwrap_err();
+    // appends to existing calls
+    let recorder = Recorder::new(&dir).unwrap();
+    recorder.call(&batch(vec![3, -4]), f).unwrap();
+
+    let records = load(&dir).unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].input, batch(vec![1, 2]));
+    assert_eq!(
+        records[1].output.as_ref().unwrap_err(),
+        "Compute error: empty input"
+    );
+    assert_eq!(records[2].index, 2);
+
+    // the same implementation has no mismatch
+    assert!(replay(&dir, f).unwrap().is_empty());
+    // a modified implementation
+    let mismatches = replay(&dir, |input| {
+        let output = neg_int4_int4_eval(input)?;
+        let values = output.column(0).as_primitive::<Int32Type>();
+        let values: Int32Array = values.unary(|x| x.min(0));
+        Ok(RecordBatch::try_new(output.schema(), vec![Arc::new(values)]).unwrap())
+    })
+    .unwrap();
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].record.index, 1);
+    assert!(mismatches[0].actual.is_ok());
+    assert_eq!(mismatches[1].record.index, 2);
+    assert_eq!(mismatches[1].rows, vec![1]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[function(
+    "checked_div(int, int) -> int",
+    fallback = "eval_div_or_zero",
+    max_failures = "2"
+)]
+fn checked_div(x: i32, y: i32) -> Result<i32, &'static str> {
+    x.checked_div(y).ok_or("division by zero")
+}
+
+#[function("div_or_zero(int, int) -> int", output = "eval_div_or_zero")]
+fn div_or_zero(x: i32, y: i32) -> i32 {
+    x.checked_div(y).unwrap_or(0)
+}
+
+#[test]
+fn test_fallback() {
+    use arrow_udf::sig::REGISTRY;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]));
+    let batch = |y: Vec<i32>| {
+        let x = Int32Array::from(vec![6; y.len()]);
+        let y = Int32Array::from(y);
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(x), Arc::new(y)]).unwrap()
+    };
+    let good = batch(vec![2, 3]);
+    let bad = batch(vec![2, 0]);
+
+    let sig = REGISTRY
+        .get(
+            "checked_div",
+            &[DataType::Int32, DataType::Int32],
+            &DataType::Int32,
+        )
+        .unwrap();
+    assert_eq!(sig.fallback.unwrap().max_failures, 2);
+    let f = sig.fallback_function().unwrap();
+
+    // batches with errors are evaluated by the fallback
+    let output = f.call(&bad).unwrap();
+    assert_eq!(output.num_columns(), 1);
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>().values(),
+        &[3, 0]
+    );
+    assert!(!f.is_degraded());
+    // a success resets the count
+    f.call(&good).unwrap();
+    f.call(&bad).unwrap();
+    assert!(!f.is_degraded());
+    // switch to the fallback after 2 consecutive failures
+    f.call(&bad).unwrap();
+    assert!(f.is_degraded());
+    let output = f.call(&good).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>().values(),
+        &[3, 2]
+    );
+    f.reset();
+    assert!(!f.is_degraded());
+}
+
+#[test]
+fn test_cached_function() {
+    use arrow_udf::eval::CachedFunction;
+    use arrow_udf::sig::{FunctionSignature, REGISTRY};
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]));
+    let batch = |y: Vec<Option<i32>>| {
+        let x = Int32Array::from(vec![6; y.len()]);
+        let y = Int32Array::from(y);
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(x), Arc::new(y)]).unwrap()
+    };
+    // count the rows evaluated by the function
+    let evaluated = AtomicUsize::new(0);
+    let eval = |input: &RecordBatch| {
+        evaluated.fetch_add(input.num_rows(), Ordering::Relaxed);
+        checked_div_int4_int4_int4_eval(input)
+    };
+    let f = CachedFunction::new(eval, 16);
+
+    let input = batch(vec![
+        Some(2),
+        Some(3),
+        Some(2),
+        Some(0),
+        None,
+        Some(0),
+        None,
+    ]);
+    let expected = checked_div_int4_int4_int4_eval(&input).unwrap();
+    assert_eq!(f.call(&input).unwrap(), expected);
+    // distinct rows are evaluated once
+    assert_eq!(evaluated.load(Ordering::Relaxed), 4);
+    assert_eq!((f.hits(), f.misses()), (0, 7));
+
+    // rows with errors are not cached
+    assert_eq!(f.call(&input).unwrap(), expected);
+    assert_eq!(evaluated.load(Ordering::Relaxed), 5);
+    assert_eq!((f.hits(), f.misses()), (5, 9));
+
+    // all rows are cached
+    let input = batch(vec![Some(3), None, Some(2)]);
+    let output = f.call(&input).unwrap();
+    assert_eq!(output, checked_div_int4_int4_int4_eval(&input).unwrap());
+    assert_eq!(evaluated.load(Ordering::Relaxed), 5);
+
+    // the least recently used rows are evicted
+    f.clear();
+    let f = CachedFunction::new(checked_div_int4_int4_int4_eval, 2);
+    f.call(&batch(vec![Some(1), Some(2), Some(3)])).unwrap();
+    f.call(&batch(vec![Some(3), Some(2), Some(1)])).unwrap();
+    assert_eq!((f.hits(), f.misses()), (2, 4));
+
+    // volatile functions can not be cached
+    let sig = REGISTRY
+        .get(
+            "checked_div",
+            &[DataType::Int32, DataType::Int32],
+            &DataType::Int32,
+        )
+        .unwrap();
+    assert!(sig.cached_function(16).is_some());
+    let sig = FunctionSignature::builder()
+        .name("identity")
+        .arg(DataType::Int32)
+        .returns(DataType::Int32)
+        .volatile(true)
+        .scalar(|input| Ok(input.clone()))
+        .build()
+        .unwrap();
+    assert!(sig.cached_function(16).is_none());
+}
+
+#[test]
+fn test_routed_function() {
+    use arrow_udf::sig::{FunctionRegistry, FunctionSignature};
+    use std::time::Duration;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]));
+    let x = Int32Array::from(vec![6, 6]);
+    let y = Int32Array::from(vec![2, 3]);
+    let input = RecordBatch::try_new(schema, vec![Arc::new(x), Arc::new(y)]).unwrap();
+
+    // a native function and a slower python fallback of the same signature
+    let slow_calls = Arc::new(AtomicUsize::new(0));
+    let mut registry = FunctionRegistry::default();
+    let sig = FunctionSignature::builder()
+        .name("checked_div")
+        .args([DataType::Int32, DataType::Int32])
+        .returns(DataType::Int32)
+        .scalar(checked_div_int4_int4_int4_eval)
+        .build()
+        .unwrap();
+    assert_eq!(sig.runtime, "rust");
+    registry.register(sig);
+    let sig = FunctionSignature::builder()
+        .name("checked_div")
+        .args([DataType::Int32, DataType::Int32])
+        .returns(DataType::Int32)
+        .runtime("python")
+        .dyn_scalar({
+            let slow_calls = slow_calls.clone();
+            move |input| {
+                slow_calls.fetch_add(1, Ordering::Relaxed);
+                std::thread::sleep(Duration::from_millis(10));
+                checked_div_int4_int4_int4_eval(input)
+            }
+        })
+        .build()
+        .unwrap();
+    registry.register(sig);
+
+    let f = registry
+        .routed_function(
+            "checked_div",
+            &[DataType::Int32, DataType::Int32],
+            &DataType::Int32,
+        )
+        .unwrap()
+        .with_explore_interval(4);
+    assert_eq!(f.implementations().collect::<Vec<_>>(), ["rust", "python"]);
+    assert_eq!(f.fastest(), None);
+
+    // each implementation is measured once, then calls go to the fastest
+    let expected = checked_div_int4_int4_int4_eval(&input).unwrap();
+    for _ in 0..3 {
+        assert_eq!(f.call(&input).unwrap(), expected);
+    }
+    assert_eq!(slow_calls.load(Ordering::Relaxed), 1);
+    assert_eq!(f.fastest(), Some("rust"));
+    assert!(f.costs().iter().all(|(_, cost)| cost.is_some()));
+
+    // implementations are measured again periodically
+    for _ in 0..8 {
+        f.call(&input).unwrap();
+    }
+    assert_eq!(slow_calls.load(Ordering::Relaxed), 2);
+
+    // pinning overrides the routing
+    assert!(f.pin("javascript").is_err());
+    f.pin("python").unwrap();
+    assert_eq!(f.pinned(), Some("python"));
+    f.call(&input).unwrap();
+    assert_eq!(slow_calls.load(Ordering::Relaxed), 3);
+    f.unpin();
+    assert_eq!(f.pinned(), None);
+    f.call(&input).unwrap();
+    assert_eq!(slow_calls.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn test_audit() {
+    use arrow_udf::eval::{AuditRecord, AuditedFunction};
+    use std::sync::Mutex;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]));
+    let batch = |y: Vec<i32>| {
+        let x = Int32Array::from(vec![6; y.len()]);
+        let y = Int32Array::from(y);
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(x), Arc::new(y)]).unwrap()
+    };
+
+    let records = Arc::new(Mutex::new(vec![]));
+    let sink = {
+        let records = records.clone();
+        move |r: &AuditRecord<'_>| {
+            let tenant = r.tenant.map(String::from);
+            records
+                .lock()
+                .unwrap()
+                .push((r.function.to_string(), tenant, r.rows, r.success));
+        }
+    };
+    let f = AuditedFunction::new("checked_div", checked_div_int4_int4_int4_eval, sink);
+
+    let ctx = EvalContext::default().with_metadata("tenant", "t1");
+    ctx.scope(|| f.call(&batch(vec![1, 2, 3]))).unwrap();
+    // errors in rows are failures
+    ctx.scope(|| f.call(&batch(vec![0]))).unwrap();
+    // no tenant without a context
+    f.call(&batch(vec![2, 3])).unwrap();
+
+    let records = records.lock().unwrap();
+    let name = "checked_div".to_string();
+    assert_eq!(
+        *records,
+        vec![
+            (name.clone(), Some("t1".to_string()), 3, true),
+            (name.clone(), Some("t1".to_string()), 1, false),
+            (name, None, 2, true),
+        ]
+    );
+}
+
+#[function("greet(varchar) -> varchar")]
+fn greet(name: &str) -> String {
+    format!("hello {name}")
+}
+
+#[function("greet(varchar) -> varchar", version = "2")]
+fn greet_v2(name: &str) -> String {
+    format!("hi {name}")
+}
+
+#[test]
+fn test_versions() {
+    let schema = Schema::new(vec![Field::new("name", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec!["arrow"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = greet_varchar_varchar_eval(&input).unwrap();
+    assert_eq!(output.column(0).as_string::<i32>().value(0), "hello arrow");
+    let output = greet_v2_varchar_varchar_eval(&input).unwrap();
+    assert_eq!(output.column(0).as_string::<i32>().value(0), "hi arrow");
+}
+
+#[test]
+#[cfg(feature = "global_registry")]
+fn test_registry_versions() {
+    use arrow_udf::sig::REGISTRY;
+
+    let args = [DataType::Utf8];
+    let latest = REGISTRY.get("greet", &args, &DataType::Utf8).unwrap();
+    assert_eq!(latest.version, 2);
+    let v1 = REGISTRY
+        .get_version("greet", 1, &args, &DataType::Utf8)
+        .unwrap();
+    assert_eq!(v1.version, 1);
+    assert!(REGISTRY
+        .get_version("greet", 3, &args, &DataType::Utf8)
+        .is_none());
+
+    let schema = Schema::new(vec![Field::new("name", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec!["arrow"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+    let output = v1.function.as_scalar().unwrap()(&input).unwrap();
+    assert_eq!(output.column(0).as_string::<i32>().value(0), "hello arrow");
+}
+
+#[test]
+fn test_option_add() {
+    let schema = Schema::new(vec![
+        Field::new("x", DataType::Int32, true),
+        Field::new("y", DataType::Int32, true),
+    ]);
+    let arg0 = Int32Array::from(vec![Some(1), Some(1), None, None]);
+    let arg1 = Int32Array::from(vec![Some(1), None, Some(1), None]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = option_add_int4_int4_int4_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++------------+
+| option_add |
++------------+
+| 2          |
+| 1          |
+|            |
+|            |
++------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_array_sum() {
+    let schema = Schema::new(vec![Field::new(
+        "x",
+        DataType::new_list(DataType::Int32, true),
+        true,
+    )]);
+    let arg0 = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+        Some(vec![Some(0), Some(1), Some(2)]),
+        None,
+        Some(vec![Some(3), None, Some(5)]),
+        Some(vec![Some(6), Some(7)]),
+    ]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = array_sum_int4array_int4_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-----------+
+| array_sum |
++-----------+
+| 3         |
+|           |
+| 8         |
+| 13        |
++-----------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_temporal() {
+    let schema = Schema::new(vec![
+        Field::new("date", DataType::Date32, true),
+        Field::new("time", DataType::Time64(TimeUnit::Microsecond), true),
+    ]);
+    let arg0 = Date32Array::from(vec![Date32Type::from_naive_date(
+        NaiveDate::from_ymd_opt(2022, 4, 8).unwrap(),
+    )]);
+    let arg1 = Time64MicrosecondArray::from(vec![time_to_time64us(
+        NaiveTime::from_hms_micro_opt(12, 34, 56, 789_012).unwrap(),
+    )]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = datetime_date_time_timestamp_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++----------------------------+
+| datetime                   |
++----------------------------+
+| 2022-04-08T12:34:56.789012 |
++----------------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_interval() {
+    let schema = Schema::new(vec![
+        Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        Field::new("i", DataType::Interval(IntervalUnit::MonthDayNano), true),
+    ]);
+    let ts = NaiveDate::from_ymd_opt(2022, 1, 31)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .timestamp_micros();
+    let arg0 = TimestampMicrosecondArray::from(vec![ts, ts]);
+    let arg1 = IntervalMonthDayNanoArray::from(vec![
+        IntervalMonthDayNanoType::make_value(1, 1, 3_600_000_000_000),
+        IntervalMonthDayNanoType::make_value(-2, 0, -1_000),
+    ]);
+    let input =
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0), Arc::new(arg1)]).unwrap();
+
+    let output = add_interval_timestamp_interval_timestamp_eval(&input).unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++----------------------------+
+| add_interval               |
++----------------------------+
+| 2022-03-01T13:00:00        |
+| 2021-11-30T11:59:59.999999 |
++----------------------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_decimal() {
+    let schema = Schema::new(vec![decimal_field("d")]);
+    let arg0 = StringArray::from(vec!["0.001"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = identity_decimal_decimal_eval(&input).unwrap();
+    assert_eq!(output.schema().field(0), &decimal_field("identity"));
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++----------+
+| identity |
++----------+
+| 0.001    |
++----------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_json() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = to_json_int4_json_eval(&input).unwrap();
+    assert_eq!(output.schema().field(0), &json_field("to_json"));
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++---------+
+| to_json |
++---------+
+| 1       |
+| null    |
++---------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_range() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = range_int4_int4_eval(&input)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
     assert_eq!(
         pretty_format_batches(std::slice::from_ref(&output))
             .unwrap()
@@ -626,6 +2320,7 @@ fn test_range() {
     // for large set, the output is split into multiple batches
     let mut i = 0;
     for output in range_int4_int4_eval(&input).unwrap() {
+        let output = output.unwrap();
         let array = output
             .column(1)
             .as_any()
@@ -640,13 +2335,14 @@ fn test_range() {
 
 #[test]
 fn test_json_array_elements() {
-    let schema = Schema::new(vec![Field::new("d", DataType::LargeUtf8, true)]);
-    let arg0 = LargeStringArray::from(vec![r#"[null,1,""]"#, "1"]);
+    let schema = Schema::new(vec![json_field("d")]);
+    let arg0 = StringArray::from(vec![r#"[null,1,""]"#, "1"]);
     let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
 
     let output = json_array_elements_json_json_eval(&input)
         .unwrap()
         .next()
+        .unwrap()
         .unwrap();
     assert_eq!(
         pretty_format_batches(std::slice::from_ref(&output))
@@ -665,3 +2361,285 @@ fn test_json_array_elements() {
         .trim()
     );
 }
+
+#[test]
+fn test_batch_fn_output_len() {
+    let schema = Schema::new(vec![Field::new("x", DataType::Int32, true)]);
+    let arg0 = Int32Array::from(vec![Some(1), None, Some(3)]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let err = max_value_int4_int4_eval(&input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: expect 3 rows in the output of max_value_batch, got 1"
+    );
+
+    let output = max_value_broadcast_int4_int4_eval(&input).unwrap();
+    assert_eq!(
+        output.column(0).as_primitive::<Int32Type>(),
+        &Int32Array::from(vec![3, 3, 3])
+    );
+}
+
+#[test]
+fn test_table_function_columns() {
+    use arrow_schema::Fields;
+    use arrow_udf::sig::REGISTRY;
+
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec![Some("a b a"), None, Some("c")]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = word_count_varchar_varchar_int4_eval(&input)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-----+------+-------+
+| row | word | count |
++-----+------+-------+
+| 0   | a    | 2     |
+| 0   | b    | 1     |
+| 2   | c    | 1     |
++-----+------+-------+
+"#
+        .trim()
+    );
+
+    let sig = REGISTRY
+        .get(
+            "word_count",
+            &[DataType::Utf8],
+            &DataType::Struct(Fields::from(vec![
+                Field::new("word", DataType::Utf8, true),
+                Field::new("count", DataType::Int32, true),
+            ])),
+        )
+        .unwrap();
+    assert!(sig.function.is_table());
+}
+
+#[test]
+fn test_table_function_columns_error() {
+    let schema = Schema::new(vec![Field::new("s", DataType::Utf8, true)]);
+    let arg0 = StringArray::from(vec!["a=1,b=x", "c"]);
+    let input = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(arg0)]).unwrap();
+
+    let output = parse_kv_varchar_varchar_int4_eval(&input)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        pretty_format_batches(std::slice::from_ref(&output))
+            .unwrap()
+            .to_string(),
+        r#"
++-----+-----+-------+-----------------+
+| row | key | value | error           |
++-----+-----+-------+-----------------+
+| 0   | a   | 1     |                 |
+| 0   | b   |       |                 |
+| 1   |     |       | invalid pair: c |
++-----+-----+-------+-----------------+
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn test_column_reader() {
+    let schema = Schema::new(vec![field(DataType::Int32), field(DataType::Utf8)]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from(vec![Some(1), None, Some(3), Some(4)])),
+            Arc::new(StringArray::from(vec![
+                Some("a"),
+                Some("b"),
+                None,
+                Some("d"),
+            ])),
+        ],
+    )
+    .unwrap()
+    .slice(1, 3);
+
+    let ints = ColumnReader::<i32>::from_batch(&input, 0).unwrap();
+    let strs = ColumnReader::<&str>::from_batch(&input, 1).unwrap();
+    assert_eq!(ints.len(), 3);
+    assert_eq!(ints.iter().collect::<Vec<_>>(), [None, Some(3), Some(4)]);
+    assert_eq!(
+        strs.iter().rev().collect::<Vec<_>>(),
+        [Some("d"), None, Some("b")]
+    );
+    assert_eq!(strs.get(0), Some("b"));
+    assert!(strs.is_null(1));
+
+    let err = ColumnReader::<&str>::from_batch(&input, 0).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Cast error: expect Utf8 for the 0-th argument, got Int32"
+    );
+    let err = ColumnReader::<i32>::from_batch(&input, 2).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: expect at least 3 columns, got 2"
+    );
+}
+
+#[aggregate("sum(int4) -> int8", init_state = "0")]
+fn sum(state: i64, value: i32, retract: bool) -> i64 {
+    if retract {
+        state - value as i64
+    } else {
+        state + value as i64
+    }
+}
+
+#[aggregate("string_agg(varchar) -> varchar")]
+fn string_agg(state: Option<String>, value: &str) -> String {
+    match state {
+        Some(s) => format!("{s},{value}"),
+        None => value.to_string(),
+    }
+}
+
+#[test]
+fn test_aggregate() {
+    let schema = Schema::new(vec![
+        Field::new("retract", DataType::Boolean, true),
+        field(DataType::Int32),
+    ]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(BooleanArray::from(vec![false, false, true, false])),
+            Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(1), None])),
+        ],
+    )
+    .unwrap();
+
+    // start from the initial state
+    let state = sum_int4_int8_eval(None, &input).unwrap();
+    assert_eq!(state.as_ref(), &Int64Array::from(vec![2]) as &dyn Array);
+    // continue from the previous state
+    let state = sum_int4_int8_eval(Some(&state), &input).unwrap();
+    assert_eq!(state.as_ref(), &Int64Array::from(vec![4]) as &dyn Array);
+    // no rows
+    let state = sum_int4_int8_eval(Some(&state), &input.slice(0, 0)).unwrap();
+    assert_eq!(state.as_ref(), &Int64Array::from(vec![4]) as &dyn Array);
+}
+
+#[test]
+fn test_aggregate_without_retract() {
+    let schema = Schema::new(vec![
+        Field::new("retract", DataType::Boolean, true),
+        field(DataType::Utf8),
+    ]);
+    let input = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(BooleanArray::from(vec![false, false, false])),
+            Arc::new(StringArray::from(vec![Some("a"), None, Some("b")])),
+        ],
+    )
+    .unwrap();
+    let state = string_agg_varchar_varchar_eval(None, &input.slice(0, 0)).unwrap();
+    assert!(state.is_null(0));
+    let state = string_agg_varchar_varchar_eval(Some(&state), &input).unwrap();
+    assert_eq!(
+        state.as_ref(),
+        &StringArray::from(vec!["a,b"]) as &dyn Array
+    );
+
+    let input = RecordBatch::try_new(
+        input.schema(),
+        vec![
+            Arc::new(BooleanArray::from(vec![true])),
+            Arc::new(StringArray::from(vec!["a"])),
+        ],
+    )
+    .unwrap();
+    let err = string_agg_varchar_varchar_eval(Some(&state), &input).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid argument error: function does not support retraction"
+    );
+}
+
+proptest! {
+    #[test]
+    fn test_identity_roundtrip(seed: u64, len in 0..64usize, null_density in 0.0f32..1.0) {
+        let cases: [(Field, ScalarFunction); 15] = [
+            (field(DataType::Boolean), identity_boolean_boolean_eval),
+            (field(DataType::Int16), identity_int2_int2_eval),
+            (field(DataType::Int32), identity_int4_int4_eval),
+            (field(DataType::Int64), identity_int8_int8_eval),
+            (field(DataType::Float16), identity_float2_float2_eval),
+            (field(DataType::Float32), identity_float4_float4_eval),
+            (field(DataType::Float64), identity_float8_float8_eval),
+            (decimal_field("x"), identity_decimal_decimal_eval),
+            (field(DataType::Date32), identity_date_date_eval),
+            (field(DataType::Time64(TimeUnit::Microsecond)), identity_time_time_eval),
+            (
+                field(DataType::Timestamp(TimeUnit::Microsecond, None)),
+                identity_timestamp_timestamp_eval,
+            ),
+            (
+                field(DataType::Interval(IntervalUnit::MonthDayNano)),
+                identity_interval_interval_eval,
+            ),
+            (json_field("x"), identity_json_json_eval),
+            (field(DataType::Utf8), identity_varchar_varchar_eval),
+            (field(DataType::Binary), identity_bytea_bytea_eval),
+        ];
+        for (field, function) in cases {
+            let array = random_field_array(&field, len, null_density, seed);
+            let input = RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![array]).unwrap();
+            assert_roundtrip_batch(function, &input);
+        }
+    }
+
+    #[test]
+    fn test_null_union(seed: u64, len in 0..64usize, null_density in 0.0f32..1.0) {
+        // the output is null iff any input is null
+        let fields = vec![
+            Field::new("s", DataType::Utf8, true),
+            Field::new("start", DataType::Int32, true),
+        ];
+        let arrays = fields
+            .iter()
+            .enumerate()
+            .map(|(i, f)| random_field_array(f, len, null_density, seed + i as u64))
+            .collect::<Vec<_>>();
+        let input = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).unwrap();
+        let output = substring_varchar_int4_varchar_eval(&input).unwrap();
+        for i in 0..len {
+            let any_null = input.columns().iter().any(|c| c.is_null(i));
+            prop_assert_eq!(output.column(0).is_null(i), any_null);
+        }
+    }
+}
+
+/// Returns a nullable field of the given type named `x`.
+fn field(data_type: DataType) -> Field {
+    Field::new("x", data_type, true)
+}
+
+/// Returns a nullable field of JSON type.
+fn json_field(name: &str) -> Field {
+    Field::new(name, DataType::Utf8, true)
+        .with_metadata([(EXTENSION_KEY.into(), JSON_EXTENSION_NAME.into())].into())
+}
+
+/// Returns a nullable field of decimal type.
+fn decimal_field(name: &str) -> Field {
+    Field::new(name, DataType::Utf8, true)
+        .with_metadata([(EXTENSION_KEY.into(), DECIMAL_EXTENSION_NAME.into())].into())
+}